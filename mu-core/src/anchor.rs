@@ -0,0 +1,215 @@
+//! Passes over the links in HTML before it's rendered: numbered footnotes, warnings after
+//! deceptive links, and `mailto:` links shown as their addresses (listed after the text)
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use crate::render::style;
+use crate::{cleanup, color, spoof, theme, tracker};
+
+/// A link: its target and the HTML inside it
+static ANCHOR: LazyLock<Regex> =
+    LazyLock::new(|| cleanup::re(r#"(?is)<a\s[^>]*?href\s*=\s*["']([^"']+)["'][^>]*>(.*?)</a>"#));
+
+/// Tags inside link text
+static TAG: LazyLock<Regex> = LazyLock::new(|| cleanup::re(r"<[^>]*>"));
+
+/// `html` with `[n]` in place of each web link, and the links' targets (a target linked
+/// twice keeps its number)
+pub(crate) fn footnotes(html: &str) -> (Cow<'_, str>, Vec<String>) {
+    let mut targets: Vec<String> = Vec::new();
+    let html = ANCHOR.replace_all(html, |caps: &regex::Captures| {
+        let target = caps[1].trim().replace("&amp;", "&");
+        if !target.starts_with("http") {
+            return caps[0].to_string();
+        }
+        let n = match targets.iter().position(|t| *t == target) {
+            Some(i) => i + 1,
+            None => {
+                targets.push(target);
+                targets.len()
+            }
+        };
+        format!("{}[{}]", &caps[2], n)
+    });
+    (html, targets)
+}
+
+/// `html` with `⚠ [why]` after links that aren't what they seem: text showing one site while
+/// going to another, punycode or lookalike domains (see [`spoof`])
+pub(crate) fn flag_links(html: &str) -> Cow<'_, str> {
+    ANCHOR.replace_all(html, |caps: &regex::Captures| {
+        let target = caps[1].trim().replace("&amp;", "&");
+        let text = TAG.replace_all(&caps[2], "").replace("&amp;", "&");
+        match spoof::link_warning(&text, &target) {
+            Some(why) => format!(
+                "{} <strong>⚠&nbsp;[{}]</strong>",
+                &caps[0],
+                color::escape_html(&why).replace(' ', "&nbsp;")
+            ),
+            None => caps[0].to_string(),
+        }
+    })
+}
+
+/// `html` with each `mailto:` link as its address after a `✉` (behind the link's text, when
+/// that's something else), for every renderer to show the same way; the addresses and names
+/// not seen before go to `mentioned`
+pub(crate) fn mailto_links<'a>(
+    html: &'a str,
+    mentioned: &mut Vec<(String, Option<String>)>,
+) -> Cow<'a, str> {
+    ANCHOR.replace_all(html, |caps: &regex::Captures| {
+        let target = caps[1].trim().replace("&amp;", "&");
+        let Some(to) = target
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+            .map(|_| &target[7..])
+        else {
+            return caps[0].to_string();
+        };
+        let to = tracker::percent_decode(to.split('?').next().unwrap_or_default());
+        // `Name <address>` is the address
+        let addresses: Vec<&str> = to
+            .split(',')
+            .map(|address| {
+                let address = address.trim();
+                address
+                    .split_once('<')
+                    .and_then(|(_, rest)| rest.split_once('>'))
+                    .map_or(address, |(inside, _)| inside.trim())
+            })
+            .filter(|address| address.contains('@'))
+            .collect();
+        if addresses.is_empty() {
+            return caps[2].to_string();
+        }
+        let text = TAG.replace_all(&caps[2], "").replace("&amp;", "&");
+        let text = text.trim().trim_start_matches("mailto:");
+        // Link text that is an address says nothing more
+        let name = (!text.is_empty() && !text.contains('@')).then(|| text.to_string());
+        for address in &addresses {
+            if !mentioned
+                .iter()
+                .any(|(seen, _)| seen.eq_ignore_ascii_case(address))
+            {
+                mentioned.push((address.to_string(), name.clone()));
+            }
+        }
+        let marked = addresses
+            .iter()
+            .map(|address| format!("✉&nbsp;{}", color::escape_html(address)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match name {
+            Some(_) => format!("{} ({})", &caps[2], marked),
+            None => marked,
+        }
+    })
+}
+
+/// The addresses `mailto:` links pointed to, after a blank line
+pub(crate) fn contact_list(mentioned: &[(String, Option<String>)]) -> String {
+    if mentioned.is_empty() {
+        return String::new();
+    }
+    let theme = theme::get();
+    let mut list = format!("\n\n{}", style(&theme.muted, "Contacts mentioned:"));
+    for (address, name) in mentioned {
+        list.push_str(&format!("\n  {}", style(&theme.address, address)));
+        if let Some(name) = name {
+            list.push_str(&format!(" {}", style(&theme.muted, format!("({})", name))));
+        }
+    }
+    list
+}
+
+/// The numbered list of footnoted link targets, after a blank line
+pub(crate) fn link_list(targets: &[String]) -> String {
+    let mut list = String::new();
+    for (i, target) in targets.iter().enumerate() {
+        let n = style(&theme::get().muted, format!("[{}]", i + 1));
+        list.push_str(&format!("\n{} {}", n, target));
+    }
+    if !list.is_empty() {
+        list.insert(0, '\n');
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colorize::clean_text;
+
+    #[test]
+    fn test_footnotes() {
+        let html = concat!(
+            r#"<p><a href="https://a.example/x?a=1&amp;b=2">Docs</a>, <a href='mailto:j@x'>mail</a>, "#,
+            r#"<a class="btn" href="https://b.example/">B</a> and <A HREF="https://a.example/x?a=1&amp;b=2">again</A></p>"#
+        );
+        let (html, targets) = footnotes(html);
+        assert_eq!(
+            html,
+            "<p>Docs[1], <a href='mailto:j@x'>mail</a>, B[2] and again[1]</p>"
+        );
+        assert_eq!(
+            targets,
+            ["https://a.example/x?a=1&b=2", "https://b.example/"]
+        );
+        assert_eq!(
+            crate::color::strip(&link_list(&targets)),
+            "\n\n[1] https://a.example/x?a=1&b=2\n[2] https://b.example/"
+        );
+        assert_eq!(link_list(&[]), "");
+    }
+
+    #[test]
+    fn test_mailto_links() {
+        let html = concat!(
+            r#"<p>Write to <a href="mailto:Jane%20Doe%20<jane@x.example>?subject=Hi">Jane</a>, "#,
+            r#"<a href='MAILTO:bob@x.example'>bob@x.example</a> or "#,
+            r#"<a href="mailto:jane@x.example">her</a>. <a href="https://x.example">Site</a></p>"#
+        );
+        let mut mentioned = Vec::new();
+        let html = mailto_links(html, &mut mentioned);
+        assert_eq!(
+            html,
+            concat!(
+                "<p>Write to Jane (✉&nbsp;jane@x.example), ✉&nbsp;bob@x.example or ",
+                "her (✉&nbsp;jane@x.example). <a href=\"https://x.example\">Site</a></p>"
+            )
+        );
+        assert_eq!(
+            mentioned,
+            [
+                ("jane@x.example".to_string(), Some("Jane".to_string())),
+                ("bob@x.example".to_string(), None),
+            ]
+        );
+        let marker = "✉\u{a0}bob@x.example";
+        assert_eq!(
+            clean_text(&format!("or {}.", marker), true),
+            format!("or {}.", style(&theme::get().address, marker))
+        );
+        assert_eq!(
+            crate::color::strip(&contact_list(&mentioned[1..2])),
+            "\n\nContacts mentioned:\n  bob@x.example"
+        );
+    }
+
+    #[test]
+    fn test_deceptive_links() {
+        let html = "<p><a href=\"https://evil.example/x\"><b>www.paypal.com</b></a> \
+<a href=\"https://www.paypal.com/\">www.paypal.com</a></p>";
+        let flagged = flag_links(html);
+        assert_eq!(flagged.matches("⚠").count(), 1);
+        assert!(flagged.contains("</a> <strong>⚠&nbsp;[link&nbsp;goes&nbsp;to&nbsp;evil.example]"));
+        let marker = "⚠\u{a0}[link\u{a0}goes\u{a0}to\u{a0}evil.example]";
+        assert_eq!(
+            clean_text(&format!("x {} y", marker), true),
+            format!("x {} y", style(&theme::get().alert, marker))
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Right-to-left text (Arabic, Hebrew) next to the borders, padding and colors mu adds
+//!
+//! Terminals that lay out bidi text themselves (VTE, Konsole, mlterm) only need each cell
+//! isolated (U+2068 … U+2069) so borders and columns stay where they are; the others show
+//! characters in the order they come, so `reorder` puts RTL runs in display order first.
+
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::sync::{LazyLock, OnceLock};
+use unicode_bidi::{BidiClass, BidiInfo, bidi_class};
+
+use crate::cleanup;
+use crate::config::Config;
+
+/// What happens to cells with right-to-left text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Wrapped in isolate marks, for terminals that do bidi
+    #[default]
+    Isolate,
+    /// Reordered for display, for terminals that don't
+    Reorder,
+    /// Left as they are
+    Off,
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Gaps and borders between table columns
+static GAP: LazyLock<regex::Regex> = LazyLock::new(|| cleanup::re(r"\s{2,}|\s*[│┃|]\s*"));
+
+/// Use `mode` for this process (before anything is rendered; later calls are ignored)
+pub fn set(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+/// The mode set, else `[render] bidi` from the config
+fn mode() -> Mode {
+    *MODE.get_or_init(|| Config::load().map(|c| c.render.bidi).unwrap_or_default())
+}
+
+fn is_rtl(c: char) -> bool {
+    matches!(bidi_class(c), BidiClass::R | BidiClass::AL)
+}
+
+/// A table row, header or card line with each column holding RTL text isolated or reordered
+pub(crate) fn line(text: &str) -> Cow<'_, str> {
+    with_mode(text, mode())
+}
+
+fn with_mode(text: &str, mode: Mode) -> Cow<'_, str> {
+    if mode == Mode::Off || !text.chars().any(is_rtl) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut last = 0;
+    for gap in GAP.find_iter(text) {
+        out.push_str(&cell(&text[last..gap.start()], mode));
+        out.push_str(gap.as_str());
+        last = gap.end();
+    }
+    out.push_str(&cell(&text[last..], mode));
+    Cow::Owned(out)
+}
+
+fn cell(text: &str, mode: Mode) -> Cow<'_, str> {
+    if !text.chars().any(is_rtl) {
+        return Cow::Borrowed(text);
+    }
+    match mode {
+        Mode::Isolate => Cow::Owned(format!("\u{2068}{}\u{2069}", text)),
+        Mode::Reorder => {
+            let info = BidiInfo::new(text, None);
+            match info.paragraphs.first() {
+                Some(paragraph) => Cow::Owned(
+                    info.reorder_line(paragraph, paragraph.range.clone())
+                        .into_owned(),
+                ),
+                None => Cow::Borrowed(text),
+            }
+        }
+        Mode::Off => Cow::Borrowed(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::width;
+
+    #[test]
+    fn test_isolate() {
+        assert_eq!(with_mode("Total:  $12", Mode::Isolate), "Total:  $12");
+        let row = with_mode("שלום:  עולם  12", Mode::Isolate);
+        assert_eq!(row, "\u{2068}שלום:\u{2069}  \u{2068}עולם\u{2069}  12");
+        assert_eq!(width::width(&row), width::width("שלום:  עולם  12"));
+    }
+
+    #[test]
+    fn test_reorder() {
+        assert_eq!(with_mode("שלום  x", Mode::Reorder), "םולש  x");
+        // Numbers after RTL text go with it
+        assert_eq!(with_mode("From: אבג 12", Mode::Reorder), "From: 12 גבא");
+        assert_eq!(with_mode("שלום עולם│12", Mode::Reorder), "םלוע םולש│12");
+        assert_eq!(with_mode("שלום", Mode::Off), "שלום");
+    }
+}
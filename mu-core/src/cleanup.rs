@@ -0,0 +1,171 @@
+//! Text cleanup for rendered mail, one pass over the lines
+//!
+//! Every line goes through all the steps once; the only state carried between lines is
+//! the blank-line run. The built-in converter's markdown gets more in `markdown`.
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use crate::lines::LineFilter;
+use crate::{render, theme};
+
+pub(crate) fn re(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("valid cleanup regex")
+}
+
+static LONG_URL: LazyLock<Regex> = LazyLock::new(|| re(r"https?://[^\s]{40,}"));
+
+/// A long URL as a markdown link (text and URL in groups 1 and 2), in angle brackets
+/// (group 3) or bare (group 4)
+static LONG_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    re(
+        r#"\[([^\]]+)\]\((https?://[^\s)]{40,})\)|<(https?://[^\s>]{40,})>|(https?://[^\s<>"]{40,})"#,
+    )
+});
+
+/// A URL's host, without `www.`
+static HOST: LazyLock<Regex> =
+    LazyLock::new(|| re(r"^https?://(?:[^/@\s]*@)?(?:www\.)?([^/:?#\s]+)"));
+
+/// Zero-width and other invisible characters newsletters pad text with
+const INVISIBLE: [char; 5] = ['\u{034F}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{FEFF}'];
+
+/// Drop long URLs
+pub(crate) fn strip_long_urls(text: &str) -> String {
+    LONG_URL.replace_all(text, "").into_owned()
+}
+
+/// Long URLs shortened to `[host/…]` in lines pushed one at a time (in the theme's `muted`
+/// color if `colors`), when `on`
+#[derive(Debug)]
+pub(crate) struct ShortUrls {
+    pub(crate) on: bool,
+    pub(crate) colors: bool,
+}
+
+impl ShortUrls {
+    fn short(&self, url: &str) -> String {
+        let host = HOST
+            .captures(url)
+            .map_or("", |caps| caps.get(1).map_or("", |m| m.as_str()));
+        let short = format!("[{}/…]", host);
+        if self.colors {
+            render::style(&theme::get().muted, short)
+        } else {
+            short
+        }
+    }
+}
+
+impl LineFilter for ShortUrls {
+    fn push(&mut self, line: String) -> String {
+        if !self.on {
+            return line;
+        }
+        match LONG_LINK.replace_all(&line, |caps: &Captures| match caps.get(2) {
+            Some(url) => format!("{} {}", &caps[1], self.short(url.as_str())),
+            None => self.short((3..=4).find_map(|i| caps.get(i)).map_or("", |m| m.as_str())),
+        }) {
+            Cow::Borrowed(_) => line,
+            Cow::Owned(short) => short,
+        }
+    }
+
+    fn finish(self) -> String {
+        String::new()
+    }
+}
+
+/// Lines without long URLs (if asked) or invisible characters, runs of empty lines
+/// squeezed to one, and the text trimmed at both ends
+pub(crate) fn tidy_lines(text: &str, strip_urls: bool) -> Vec<String> {
+    let mut lines = Lines::default();
+    for line in text.lines() {
+        let mut line = Cow::Borrowed(line);
+        if strip_urls {
+            line = replace_all(line, &LONG_URL, "");
+        }
+        lines.push(strip_invisible(line));
+    }
+    lines.finish()
+}
+
+/// [`tidy_lines`], joined
+pub(crate) fn tidy(text: &str, strip_urls: bool) -> String {
+    tidy_lines(text, strip_urls).join("\n")
+}
+
+/// Output lines: runs of empty lines kept as one, whitespace trimmed off both ends
+#[derive(Default)]
+pub(crate) struct Lines {
+    lines: Vec<String>,
+}
+
+impl Lines {
+    pub(crate) fn push(&mut self, line: Cow<str>) {
+        let empty_run = line.is_empty() && self.lines.last().is_some_and(String::is_empty);
+        // Leading whitespace-only lines go with the trim
+        if !empty_run && (!self.lines.is_empty() || !line.trim().is_empty()) {
+            self.lines.push(line.into_owned());
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<String> {
+        while self.lines.last().is_some_and(|l| l.trim().is_empty()) {
+            self.lines.pop();
+        }
+        if let Some(last) = self.lines.last_mut() {
+            last.truncate(last.trim_end().len());
+        }
+        if let Some(first) = self.lines.first_mut() {
+            *first = first.trim_start().to_string();
+        }
+        self.lines
+    }
+}
+
+pub(crate) fn replace_all<'a>(text: Cow<'a, str>, re: &Regex, with: &str) -> Cow<'a, str> {
+    match re.replace_all(&text, with) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(changed) => Cow::Owned(changed),
+    }
+}
+
+pub(crate) fn strip_invisible(text: Cow<'_, str>) -> Cow<'_, str> {
+    if text.contains(INVISIBLE) {
+        Cow::Owned(text.chars().filter(|c| !INVISIBLE.contains(c)).collect())
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tidy() {
+        let text = "\n  \n   Title\u{200B}\n\n\n\nsee https://very-long-url.example.com/path/to/something/else\n \n\n";
+        assert_eq!(tidy(text, true), "Title\n\nsee");
+        assert_eq!(tidy(text, false).lines().count(), 3);
+    }
+
+    #[test]
+    fn test_short_urls() {
+        let mut urls = ShortUrls {
+            on: true,
+            colors: false,
+        };
+        let line = "Track it: https://www.ups.example/track?num=1Z999AA10123456784&loc=en_US\n";
+        assert_eq!(urls.push(line.to_string()), "Track it: [ups.example/…]\n");
+        let line = "[Your order](https://shop.example/orders/12345678901234567890?utm=mail) or \
+<https://help.example/articles/returns-and-refunds-policy>";
+        assert_eq!(
+            urls.push(line.to_string()),
+            "Your order [shop.example/…] or [help.example/…]"
+        );
+        let line = "See https://x.example/short";
+        assert_eq!(urls.push(line.to_string()), line);
+    }
+}
@@ -0,0 +1,319 @@
+//! ANSI colors on stdout and stderr, decided once per process (`--color`, `NO_COLOR`)
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::{LazyLock, OnceLock};
+
+/// When to color output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Mode {
+    /// Color a stream only if it's a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Which outputs get colors
+#[derive(Debug)]
+struct Enabled {
+    stdout: bool,
+    stderr: bool,
+    /// Files written with `--output` (only with `Always`)
+    files: bool,
+}
+
+static ENABLED: OnceLock<Enabled> = OnceLock::new();
+
+/// SGR sequences (`ESC [ … m`); cursor controls like `ESC [ K` are left alone
+static SGR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("\x1b\\[[0-9;]*m").expect("valid SGR regex"));
+
+/// Use `mode` for this process (before anything is printed; later calls are ignored)
+pub fn set(mode: Mode) {
+    let _ = ENABLED.set(decide(mode));
+}
+
+fn decide(mode: Mode) -> Enabled {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let terminals = (
+        std::io::stdout().is_terminal(),
+        std::io::stderr().is_terminal(),
+    );
+    policy(mode, no_color, terminals)
+}
+
+/// What `mode` enables given a non-empty `NO_COLOR` and whether stdout and stderr are terminals
+fn policy(mode: Mode, no_color: bool, (stdout, stderr): (bool, bool)) -> Enabled {
+    let all = |on| Enabled {
+        stdout: on,
+        stderr: on,
+        files: on,
+    };
+    match mode {
+        Mode::Always => all(true),
+        Mode::Never => all(false),
+        Mode::Auto => Enabled {
+            stdout: !no_color && stdout,
+            stderr: !no_color && stderr,
+            files: false,
+        },
+    }
+}
+
+fn enabled() -> &'static Enabled {
+    ENABLED.get_or_init(|| decide(Mode::Auto))
+}
+
+/// Whether stdout gets colors
+pub fn stdout() -> bool {
+    enabled().stdout
+}
+
+/// Whether stderr gets colors
+pub fn stderr() -> bool {
+    enabled().stderr
+}
+
+/// Whether files written by mu get colors
+pub fn files() -> bool {
+    enabled().files
+}
+
+/// `text` in SGR style `sgr` (e.g. `"1;36"`) for stdout
+pub fn paint(sgr: &str, text: impl std::fmt::Display) -> String {
+    styled(stdout(), sgr, text)
+}
+
+/// `text` in SGR style `sgr` for stderr
+pub fn epaint(sgr: &str, text: impl std::fmt::Display) -> String {
+    styled(stderr(), sgr, text)
+}
+
+fn styled(enabled: bool, sgr: &str, text: impl std::fmt::Display) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", sgr, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A `=== title ===` section header for stdout
+pub fn header(title: impl std::fmt::Display) -> String {
+    paint(&crate::theme::get().section, format!("=== {} ===", title))
+}
+
+/// Green ✓ for stderr status lines
+pub fn ok() -> &'static str {
+    if stderr() {
+        "\x1b[32m✓\x1b[0m"
+    } else {
+        "✓"
+    }
+}
+
+/// Yellow ! for stderr warnings
+pub fn warn() -> &'static str {
+    if stderr() { "\x1b[33m!\x1b[0m" } else { "!" }
+}
+
+/// Red ✗ for stderr failures
+pub fn fail() -> &'static str {
+    if stderr() {
+        "\x1b[31m✗\x1b[0m"
+    } else {
+        "✗"
+    }
+}
+
+/// Text with its color codes removed
+pub fn strip(text: &str) -> Cow<'_, str> {
+    SGR.replace_all(text, "")
+}
+
+/// Colored text as-is for a colored stdout, stripped otherwise
+pub fn for_stdout(text: String) -> String {
+    keep_if(stdout(), text)
+}
+
+/// Colored text as-is if files get colors, stripped otherwise
+pub fn for_file(text: String) -> String {
+    keep_if(files(), text)
+}
+
+fn keep_if(enabled: bool, text: String) -> String {
+    if enabled {
+        text
+    } else {
+        strip(&text).into_owned()
+    }
+}
+
+/// Colored text as HTML: escaped, each SGR style a `<span>` with inline CSS
+pub fn to_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 4);
+    let mut open = false;
+    let mut last = 0;
+    for sgr in SGR.find_iter(text) {
+        out.push_str(&escape_html(&text[last..sgr.start()]));
+        last = sgr.end();
+        if open {
+            out.push_str("</span>");
+            open = false;
+        }
+        let css = css(&text[sgr.start() + 2..sgr.end() - 1]);
+        if !css.is_empty() {
+            out.push_str(&format!("<span style=\"{}\">", css));
+            open = true;
+        }
+    }
+    out.push_str(&escape_html(&text[last..]));
+    if open {
+        out.push_str("</span>");
+    }
+    out
+}
+
+/// `text` with `&`, `<`, `>` and `"` escaped for HTML
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// CSS for an SGR sequence's parameters (`1;38;5;244`); empty for a reset
+fn css(params: &str) -> String {
+    let codes: Vec<u8> = params.split(';').filter_map(|p| p.parse().ok()).collect();
+    let mut rules = Vec::new();
+    let mut i = 0;
+    while i < codes.len() {
+        let (property, color) = match codes[i] {
+            1 => (Some("font-weight:bold"), None),
+            2 => (Some("opacity:0.6"), None),
+            3 => (Some("font-style:italic"), None),
+            4 => (Some("text-decoration:underline"), None),
+            9 => (Some("text-decoration:line-through"), None),
+            n @ (30..=37 | 90..=97) => (None, Some(("color", xterm(n % 10 + 8 * (n / 90))))),
+            n @ (40..=47 | 100..=107) => {
+                (None, Some(("background", xterm(n % 10 + 8 * (n / 100)))))
+            }
+            n @ (38 | 48) if codes.get(i + 1) == Some(&5) && i + 2 < codes.len() => {
+                i += 2;
+                (None, Some((role(n), xterm(codes[i]))))
+            }
+            n @ (38 | 48) if codes.get(i + 1) == Some(&2) && i + 4 < codes.len() => {
+                let rgb = (codes[i + 2], codes[i + 3], codes[i + 4]);
+                i += 4;
+                (None, Some((role(n), rgb)))
+            }
+            _ => (None, None),
+        };
+        rules.extend(property.map(str::to_string));
+        if let Some((property, (r, g, b))) = color {
+            rules.push(format!("{}:#{:02x}{:02x}{:02x}", property, r, g, b));
+        }
+        i += 1;
+    }
+    rules.join(";")
+}
+
+fn role(code: u8) -> &'static str {
+    if code == 38 { "color" } else { "background" }
+}
+
+/// An xterm 256-color palette entry
+fn xterm(n: u8) -> (u8, u8, u8) {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let level = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
+    match n {
+        0..=15 => BASIC[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (n - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        let text = "\x1b[1;36m=== Headers ===\x1b[0m\n\r\x1b[K\x1b[2m░\x1b[0m";
+        assert_eq!(strip(text), "=== Headers ===\n\r\x1b[K░");
+        assert!(matches!(strip("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn test_styled() {
+        assert_eq!(styled(true, "33", 5), "\x1b[33m5\x1b[0m");
+        assert_eq!(styled(false, "33", 5), "5");
+        assert!(!decide(Mode::Never).stdout);
+        assert!(!decide(Mode::Auto).files);
+        assert_eq!(keep_if(false, "\x1b[2mx\x1b[0m".to_string()), "x");
+    }
+
+    #[test]
+    fn test_policy() {
+        let on = |e: Enabled| (e.stdout, e.stderr, e.files);
+        // Auto: terminals only, never files
+        assert_eq!(
+            on(policy(Mode::Auto, false, (true, true))),
+            (true, true, false)
+        );
+        assert_eq!(
+            on(policy(Mode::Auto, false, (false, true))),
+            (false, true, false)
+        );
+        // NO_COLOR turns auto off, but not an explicit --color=always
+        assert_eq!(
+            on(policy(Mode::Auto, true, (true, true))),
+            (false, false, false)
+        );
+        assert_eq!(
+            on(policy(Mode::Always, true, (false, false))),
+            (true, true, true)
+        );
+        assert_eq!(
+            on(policy(Mode::Never, false, (true, true))),
+            (false, false, false)
+        );
+    }
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            to_html("a<b \x1b[1;31mred\x1b[0m \x1b[3;38;5;244mdim\x1b[0m"),
+            "a&lt;b <span style=\"font-weight:bold;color:#cd0000\">red</span> \
+<span style=\"font-style:italic;color:#808080\">dim</span>"
+        );
+        assert_eq!(
+            to_html("\x1b[2mopen"),
+            "<span style=\"opacity:0.6\">open</span>"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Converter output tidied and colored: headings, section titles and quotes, label/value
+//! lines boxed as tables, the marks left by the link passes; and the cards events and
+//! contacts are drawn in
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use crate::render::style;
+use crate::{bidi, cleanup, renderer, theme, width};
+
+/// The marker after a deceptive link (its spaces are non-breaking, for renderers to keep it on
+/// one line)
+static WARNING: LazyLock<Regex> = LazyLock::new(|| cleanup::re(r"⚠\s\[[^\]\n]*\]"));
+
+/// An address from a `mailto:` link, marked (with a non-breaking space) for highlighting
+static ADDRESS: LazyLock<Regex> =
+    LazyLock::new(|| cleanup::re(r"✉\s[^\s()<>,\[\]]+@[^\s()<>,\[\]]*[^\s()<>,.\[\]]"));
+
+/// Tidy and colorize converter output
+pub(crate) fn clean_text(text: &str, strip_urls: bool) -> String {
+    let theme = theme::get();
+    let colored = add_colors(&cleanup::tidy_lines(text, strip_urls));
+    let colored = match WARNING.replace_all(&colored, |caps: &regex::Captures| {
+        style(&theme.alert, &caps[0])
+    }) {
+        Cow::Borrowed(_) => colored,
+        Cow::Owned(warned) => warned,
+    };
+    match ADDRESS.replace_all(&colored, |caps: &regex::Captures| {
+        style(&theme.address, &caps[0])
+    }) {
+        Cow::Borrowed(_) => colored,
+        Cow::Owned(marked) => marked,
+    }
+}
+
+fn add_colors(lines: &[String]) -> String {
+    let theme = theme::get();
+    let mut result = String::with_capacity(lines.iter().map(|l| l.len() + 1).sum());
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].as_str();
+        if i > 0 {
+            result.push('\n');
+        }
+
+        // Detect table-like structures (lines with multiple columns separated by spaces)
+        if is_table_row(line) {
+            // Collect consecutive table rows
+            let mut table_lines = vec![line];
+            let mut j = i + 1;
+            while j < lines.len() && (is_table_row(&lines[j]) || lines[j].trim().is_empty()) {
+                if !lines[j].trim().is_empty() {
+                    table_lines.push(&lines[j]);
+                }
+                j += 1;
+            }
+
+            // Boxed only if the borders fit the render width
+            let widest = table_lines.iter().map(|l| width::width(l)).max();
+            if table_lines.len() >= 2 && widest.is_some_and(|w| w + 4 <= renderer::cols()) {
+                // Format as a table with borders
+                result.push_str(&format_table(&table_lines));
+                i = j;
+                continue;
+            }
+        }
+
+        // Color headers (centered text, ALL CAPS, or short bold-looking lines)
+        if is_header(line) {
+            result.push_str(&style(&theme.heading, bidi::line(line)));
+        }
+        // Color section titles (lines ending with :)
+        else if line.trim().ends_with(':') && line.trim().len() < 50 && !line.contains("  ") {
+            result.push_str(&style(&theme.subheading, bidi::line(line)));
+        }
+        // Quoted text
+        else if line.trim_start().starts_with('>') {
+            result.push_str(&style(&theme.quote, line));
+        } else {
+            result.push_str(line);
+        }
+
+        i += 1;
+    }
+
+    result
+}
+
+fn is_table_row(line: &str) -> bool {
+    // A table row has key:value pairs with whitespace alignment
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.len() < 15 {
+        return false;
+    }
+
+    // Must have a label (word followed by :) and a value after whitespace
+    // Pattern: "Label:    Value" with significant gap
+    let colon_pos = trimmed.find(':');
+    if let Some(pos) = colon_pos {
+        // Check there's content after the colon with whitespace gap
+        let after_colon = &trimmed[pos + 1..];
+        let has_gap = after_colon.starts_with("  ") || after_colon.starts_with(" \t");
+        let has_value = !after_colon.trim().is_empty();
+
+        // Label shouldn't be too short or contain URL-like content
+        let label = &trimmed[..pos];
+        let valid_label =
+            label.len() >= 3 && label.len() <= 30 && !label.contains("//") && !label.contains("@");
+
+        return has_gap && has_value && valid_label;
+    }
+
+    false
+}
+
+fn is_header(line: &str) -> bool {
+    let trimmed = line.trim();
+
+    // Empty or too long = not a header
+    if trimmed.is_empty() || trimmed.len() > 60 {
+        return false;
+    }
+
+    // Centered text (significant leading whitespace)
+    let leading_spaces = line.len() - line.trim_start().len();
+    let is_centered = leading_spaces > 10 && trimmed.len() < 50;
+
+    // ALL CAPS (at least 3 words)
+    let words: Vec<&str> = trimmed.split_whitespace().collect();
+    let is_all_caps = words.len() >= 2
+        && words.iter().all(|w| {
+            let letters: String = w.chars().filter(|c| c.is_alphabetic()).collect();
+            letters.len() >= 2 && letters == letters.to_uppercase()
+        });
+
+    is_centered || is_all_caps
+}
+
+fn format_table(lines: &[&str]) -> String {
+    // Find the max display width
+    let max_len = lines.iter().map(|l| width::width(l)).max().unwrap_or(0);
+    let box_width = max_len + 2; // Add padding
+
+    let border = &theme::get().border;
+    let mut result = Vec::new();
+
+    // Add top border
+    result.push(style(border, format!("┌{}┐", "─".repeat(box_width))));
+
+    for line in lines {
+        // Format the row content with colors, its RTL cells kept apart from the borders
+        let formatted = format_table_row(&bidi::line(line));
+        // Pad to align right border (use visual width for proper alignment)
+        let vis_len = width::width(line);
+        let padding = box_width.saturating_sub(vis_len + 1);
+        result.push(format!(
+            "{} {}{}{}",
+            style(border, "│"),
+            formatted,
+            " ".repeat(padding),
+            style(border, "│")
+        ));
+    }
+
+    // Add bottom border
+    result.push(style(border, format!("└{}┘", "─".repeat(box_width))));
+
+    result.join("\n")
+}
+
+/// A box with `title` on its top border and `label: value` rows (event and contact cards)
+pub(crate) fn card(title: &str, rows: &[(&str, String)]) -> String {
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| width::width(label) + 1)
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<String> = rows
+        .iter()
+        .flat_map(|(label, value)| {
+            value.lines().enumerate().map(move |(i, line)| {
+                let label = if i == 0 {
+                    format!("{}:", label)
+                } else {
+                    String::new()
+                };
+                format!("{} {}", width::pad(&label, label_width), bidi::line(line))
+            })
+        })
+        .collect();
+    let inner = lines
+        .iter()
+        .map(|line| width::width(line) + 2)
+        .chain([width::width(title) + 4])
+        .max()
+        .unwrap_or(0);
+
+    let theme = theme::get();
+    let top = "─".repeat(inner - width::width(title) - 3);
+    let mut result = vec![format!(
+        "{} {} {}",
+        style(&theme.border, "╭─"),
+        style(&theme.title, bidi::line(title)),
+        style(&theme.border, format!("{}╮", top))
+    )];
+    for line in &lines {
+        let padding = " ".repeat(inner - width::width(line) - 2);
+        let side = style(&theme.border, "│");
+        result.push(format!("{side} {line}{padding} {side}"));
+    }
+    result.push(style(&theme.border, format!("╰{}╯", "─".repeat(inner))));
+    result.join("\n")
+}
+
+fn format_table_row(line: &str) -> String {
+    // Color labels (words ending with :) in yellow
+    let mut result = String::new();
+    let mut current_word = String::new();
+
+    for c in line.chars() {
+        current_word.push(c);
+
+        // Check if this ends a label (word followed by :)
+        if c == ':' && !current_word.trim().is_empty() {
+            let word = current_word.trim();
+            if word.len() > 1
+                && word
+                    .chars()
+                    .next()
+                    .map(|ch| ch.is_alphabetic())
+                    .unwrap_or(false)
+            {
+                // It's a label - color it
+                result.push_str(&style(&theme::get().label, word));
+                current_word.clear();
+                continue;
+            }
+        }
+
+        // Flush on whitespace
+        if c == ' ' || c == '\t' {
+            result.push_str(&current_word);
+            current_word.clear();
+        }
+    }
+
+    result.push_str(&current_word);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_fit_the_width() {
+        let lines = ["Order number:   12345678", "Delivery date:  Friday"].map(String::from);
+        renderer::set_cols(Some(80));
+        assert!(add_colors(&lines).contains('┌'));
+        renderer::set_cols(Some(20));
+        assert!(!add_colors(&lines).contains('┌'));
+    }
+
+    #[test]
+    fn test_wide_characters_keep_borders_straight() {
+        let table = format_table(&["Name:   東京 🍣", "Total:  ¥1200"]);
+        let widths: Vec<usize> = table.lines().map(width::width).collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "{}", table);
+        let card = card("会議", &[("When", "月曜日".to_string())]);
+        let widths: Vec<usize> = card.lines().map(width::width).collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "{}", card);
+    }
+}
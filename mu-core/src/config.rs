@@ -0,0 +1,928 @@
+//! User configuration (~/.config/mu/config.toml)
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::bidi;
+use crate::error::Error;
+use crate::process;
+use crate::render::Signatures;
+
+/// mu configuration, loaded from `~/.config/mu/config.toml`
+///
+/// Every section is optional; a missing file yields the defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Mail accounts (folders under the notmuch root)
+    pub accounts: Vec<Account>,
+
+    /// Tag rules applied by `mu tagrules` (and the notmuch post-new hook)
+    pub tag_rules: Vec<TagRule>,
+
+    /// Settings for `mu muttrc generate`
+    pub muttrc: Muttrc,
+
+    /// Settings for `mu mailcap generate`
+    pub mailcap: Mailcap,
+
+    /// Contact sync sources for `mu contacts sync`
+    pub contacts: Contacts,
+
+    /// Calendar extraction settings for `mu cal`
+    pub calendar: Calendar,
+
+    /// Delivery rules for `mu filter`
+    pub filter: Filter,
+
+    /// Built-in full-text index settings for `mu index`
+    pub index: IndexConfig,
+
+    /// Bulk mail cleanup rules for `mu sweep`
+    pub sweep: Sweep,
+
+    /// External summarizer for `mu summarize`
+    pub summarize: Summarize,
+
+    /// Folder ↔ tag mappings for `mu label-sync`
+    pub label_sync: Vec<LabelMap>,
+
+    /// Per-folder retention rules for `mu expire`
+    pub expire: Expire,
+
+    /// Sender avatar sources for notifications and previews
+    pub avatars: Avatars,
+
+    /// Action-item export for `mu todo`
+    pub todo: Todo,
+
+    /// Translation backend for `mu translate`
+    pub translate: Translate,
+
+    /// Shipment tracking for `mu track`
+    pub track: Track,
+
+    /// Invoice PDF collection for `mu receipts`
+    pub receipts: Receipts,
+
+    /// JMAP server for `mu sync --backend jmap` and `mu fzf --backend jmap`
+    pub jmap: Jmap,
+
+    /// Phone push notifications from `mu sync`
+    pub push: Push,
+
+    /// Language and date formats for notifications, previews and listings
+    pub locale: Locale,
+
+    /// Which tools turn HTML mail into text, in order of preference
+    pub render: Render,
+
+    /// Commands that can change or veto what mu renders, picks and notifies about
+    pub hooks: Hooks,
+
+    /// Rhai script for tagging, notification filtering and listing/preview formatting
+    pub script: Script,
+}
+
+/// A mail account (`[[accounts]]`)
+#[derive(Debug, Deserialize)]
+pub struct Account {
+    pub name: String,
+    /// Account directory relative to the notmuch database root (defaults to `name`)
+    #[serde(default)]
+    pub maildir: Option<String>,
+    /// Folders shown in listings like `mu sidebar`
+    #[serde(default)]
+    pub folders: Vec<String>,
+    /// From addresses that belong to this account (`mu send` picks the Sent folder by these)
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Sent folder inside the account directory (defaults to `Sent`)
+    #[serde(default)]
+    pub sent: Option<String>,
+    /// Server storage quota (`15GB`, `500MB`) for `mu quota` projections
+    #[serde(default)]
+    pub quota: Option<String>,
+    /// IMAP server for `mu fzf --server-search` (`imaps://imap.example.com`)
+    #[serde(default)]
+    pub imap_url: Option<String>,
+    #[serde(default)]
+    pub imap_user: Option<String>,
+    /// Shell command printing the IMAP password (e.g. `pass show mail/work`)
+    #[serde(default)]
+    pub imap_password_command: Option<String>,
+    /// Server folders searched by `--server-search` (defaults to `INBOX`)
+    #[serde(default)]
+    pub server_folders: Vec<String>,
+}
+
+impl Account {
+    /// Account directory relative to the notmuch root
+    pub fn dir(&self) -> &str {
+        self.maildir.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Sent folder inside the account directory
+    pub fn sent_folder(&self) -> &str {
+        self.sent.as_deref().unwrap_or("Sent")
+    }
+
+    /// notmuch query matching one of the account's folders
+    pub fn folder_query(&self, folder: &str) -> String {
+        format!("folder:\"{}/{}\"", self.dir(), folder)
+    }
+}
+
+/// Apply `tags` (e.g. `+work`, `-inbox`) to messages matching `query`
+#[derive(Debug, Deserialize)]
+pub struct TagRule {
+    pub query: String,
+    pub tags: Vec<String>,
+}
+
+/// Gmail label folder mirrored as a notmuch tag (`[[label_sync]]`)
+#[derive(Debug, Deserialize)]
+pub struct LabelMap {
+    /// Folder relative to the notmuch root (`gmail/[Gmail]/Starred`)
+    pub folder: String,
+    pub tag: String,
+}
+
+/// neomutt glue parameters (`[muttrc]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Muttrc {
+    /// How neomutt should invoke mu
+    pub bin: String,
+    pub search_key: String,
+    pub sync_key: String,
+    pub quick_sync_key: String,
+    /// How the search macro gets mu's command: `file`, `stdout` or `fifo:PATH` (`mu fzf --emit`)
+    pub search_emit: String,
+    pub unsubscribe_key: String,
+    /// Add the sender to the alias file (`mu alias add`)
+    pub alias_key: String,
+    /// mutt alias file written by `mu alias add`
+    pub alias_file: String,
+    /// Use `mu render` as display_filter
+    pub display_filter: bool,
+}
+
+impl Default for Muttrc {
+    fn default() -> Self {
+        Self {
+            bin: "mu".to_string(),
+            search_key: "<C-f>".to_string(),
+            sync_key: "S".to_string(),
+            quick_sync_key: "s".to_string(),
+            search_emit: "file".to_string(),
+            unsubscribe_key: "U".to_string(),
+            alias_key: "a".to_string(),
+            alias_file: "~/.config/neomutt/aliases".to_string(),
+            display_filter: true,
+        }
+    }
+}
+
+/// Mailcap generation settings (`[mailcap]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Mailcap {
+    /// Show images in the terminal (`terminal`) or an external viewer (`external`)
+    pub images: String,
+    /// Terminal image viewer used when `images = "terminal"`
+    pub image_command: String,
+}
+
+impl Default for Mailcap {
+    fn default() -> Self {
+        Self {
+            images: "external".to_string(),
+            image_command: "chafa".to_string(),
+        }
+    }
+}
+
+/// Contact sync sources (`[contacts]`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Contacts {
+    /// khard/vdirsyncer address book directory (one .vcf per contact)
+    pub khard_dir: Option<String>,
+    /// CardDAV address book collection URL
+    pub carddav_url: Option<String>,
+    pub carddav_user: Option<String>,
+    /// Shell command printing the CardDAV password (e.g. `pass show dav`)
+    pub carddav_password_command: Option<String>,
+}
+
+/// Calendar extraction settings (`[calendar]`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Calendar {
+    /// Directory receiving extracted .ics files
+    pub dir: Option<String>,
+}
+
+/// Local delivery settings (`[filter]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    /// Maildir root that rule folders are relative to
+    pub maildir: String,
+    /// Folder for messages no rule matched
+    pub default_folder: String,
+    /// Rules, first match wins
+    pub rules: Vec<FilterRule>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            maildir: "~/Mail".to_string(),
+            default_folder: "INBOX".to_string(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// One delivery rule (`[[filter.rules]]`); all given conditions must match
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FilterRule {
+    /// Header to test with `matches` (e.g. `List-Id`)
+    pub header: Option<String>,
+    /// Case-insensitive regex for `header`
+    pub matches: Option<Pattern>,
+    /// notmuch-style terms (`from:github.com subject:build not to:me`)
+    pub query: Option<String>,
+    /// Minimum message size in bytes
+    pub larger_than: Option<usize>,
+    /// Match messages flagged by SpamAssassin/rspamd headers
+    pub spam: Option<bool>,
+    /// Deliver into this folder
+    pub folder: Option<String>,
+    /// Drop the message
+    pub discard: bool,
+    /// Forward to this address with sendmail (combined with `folder` to keep a copy)
+    pub forward: Option<String>,
+}
+
+/// A case-insensitive regex, compiled (and so checked) when the config is read
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Deref for Pattern {
+    type Target = Regex;
+
+    fn deref(&self) -> &Regex {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Pattern)
+            .map_err(|e| serde::de::Error::custom(regex_error(&e)))
+    }
+}
+
+/// The gist of a regex error (the last of its lines, under a picture of the pattern)
+fn regex_error(e: &regex::Error) -> String {
+    let text = e.to_string();
+    let gist = text.lines().last().unwrap_or_default().trim();
+    format!("bad regex: {}", gist.trim_start_matches("error: "))
+}
+
+/// Built-in index settings (`[index]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Maildir root to index (all folders below it)
+    pub maildir: String,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            maildir: "~/Mail".to_string(),
+        }
+    }
+}
+
+/// Bulk mail cleanup (`[sweep]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Sweep {
+    /// Minimum age of swept mail (`30d`, `2w`, `6m`, `1y`)
+    pub age: String,
+    /// What happens to bulk mail no sender rule matches
+    pub action: SweepAction,
+    /// Per-sender overrides, first match wins
+    pub senders: Vec<SweepRule>,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            age: "30d".to_string(),
+            action: SweepAction::Archive,
+            senders: Vec::new(),
+        }
+    }
+}
+
+/// Per-sender sweep rule (`[[sweep.senders]]`)
+#[derive(Debug, Deserialize)]
+pub struct SweepRule {
+    /// Substring of the From address (`news@shop.example`, `@github.com`)
+    pub from: String,
+    pub action: SweepAction,
+    /// Overrides `[sweep] age`
+    pub age: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SweepAction {
+    /// Remove from the inbox
+    Archive,
+    /// Tag deleted (and remove from the inbox)
+    Delete,
+    /// Leave alone
+    Keep,
+}
+
+/// Push notification services (`[push]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Push {
+    /// Services every pushed message goes to
+    pub targets: Vec<PushTarget>,
+    /// Which new mail gets pushed, first match wins (none: all new mail)
+    pub rules: Vec<PushRule>,
+    /// Also show desktop notifications (turn off on headless boxes)
+    pub desktop: bool,
+    /// Notifications allowed per `limit_minutes` (0: no limit); mail past it is held back
+    /// and counted in the next one
+    pub limit: usize,
+    pub limit_minutes: u64,
+}
+impl Default for Push {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            rules: Vec::new(),
+            desktop: true,
+            limit: 5,
+            limit_minutes: 10,
+        }
+    }
+}
+
+/// One push service (`[[push.targets]]`)
+#[derive(Debug, Deserialize)]
+pub struct PushTarget {
+    pub service: PushService,
+    /// Server URL (ntfy default `https://ntfy.sh`; required for Gotify)
+    pub url: Option<String>,
+    /// ntfy topic
+    pub topic: Option<String>,
+    /// Pushover user key
+    pub user: Option<String>,
+    /// Shell command printing the access token (ntfy), app token (Pushover, Gotify)
+    pub token_command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushService {
+    Ntfy,
+    Pushover,
+    Gotify,
+}
+
+/// Mail worth pushing (`[[push.rules]]`)
+#[derive(Debug, Deserialize)]
+pub struct PushRule {
+    /// notmuch query (`from:boss@example.com`, `tag:important`)
+    pub query: String,
+    #[serde(default)]
+    pub priority: PushPriority,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushPriority {
+    Low,
+    #[default]
+    Default,
+    High,
+}
+
+/// JMAP account (`[jmap]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Jmap {
+    /// Session resource URL
+    pub session_url: String,
+    /// Shell command printing the API token (e.g. `pass show fastmail/jmap`)
+    pub token_command: Option<String>,
+    /// Maildir root the mailboxes are downloaded into
+    pub maildir: String,
+}
+
+impl Default for Jmap {
+    fn default() -> Self {
+        Self {
+            session_url: "https://api.fastmail.com/jmap/session".to_string(),
+            token_command: None,
+            maildir: "~/Mail/jmap".to_string(),
+        }
+    }
+}
+
+/// Receipt collection (`[receipts]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Receipts {
+    /// Where collected PDFs go
+    pub dest: String,
+    /// Messages considered (the heuristics then pick receipts among them)
+    pub query: String,
+    /// Per-sender overrides, first match wins
+    pub rules: Vec<ReceiptRule>,
+}
+
+impl Default for Receipts {
+    fn default() -> Self {
+        Self {
+            dest: "~/Documents/receipts".to_string(),
+            query: "tag:attachment".to_string(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Per-sender receipt rule (`[[receipts.rules]]`)
+#[derive(Debug, Deserialize)]
+pub struct ReceiptRule {
+    /// Substring of the From address (`@hetzner.com`)
+    pub from: String,
+    /// Vendor name used in file names (default: from the sender's domain)
+    pub vendor: Option<String>,
+    /// Never collect from this sender
+    #[serde(default)]
+    pub skip: bool,
+}
+
+/// Shipment tracking (`[track]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Track {
+    /// Messages scanned for tracking numbers
+    pub query: String,
+    /// Shell command printing a status line; gets `MU_TRACK_CARRIER`/`MU_TRACK_NUMBER`
+    pub status_command: Option<String>,
+    /// `mu sync` notifies when new tracking numbers arrive
+    pub notify_on_sync: bool,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            query: "tag:inbox".to_string(),
+            status_command: None,
+            notify_on_sync: false,
+        }
+    }
+}
+
+/// Body translation (`[translate]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Translate {
+    /// Target language (ISO 639-1)
+    pub to: String,
+    /// Shell command reading text on stdin; gets `MU_TRANSLATE_FROM`/`MU_TRANSLATE_TO`
+    pub command: Option<String>,
+    /// LibreTranslate-compatible endpoint (`https://host/translate`), used without a command
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+}
+
+impl Default for Translate {
+    fn default() -> Self {
+        Self {
+            to: "en".to_string(),
+            command: None,
+            url: None,
+            api_key: None,
+        }
+    }
+}
+
+/// Mail as tasks (`[todo]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Todo {
+    /// Messages exported as action items
+    pub query: String,
+    /// Tags applied when an item is marked done
+    pub done_tags: Vec<String>,
+}
+
+impl Default for Todo {
+    fn default() -> Self {
+        Self {
+            query: "tag:flagged".to_string(),
+            done_tags: vec!["-flagged".to_string(), "+done".to_string()],
+        }
+    }
+}
+
+/// Sender avatars (`[avatars]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Avatars {
+    /// Lookup order: `local`, `gravatar`, `libravatar`, `bimi`
+    pub sources: Vec<String>,
+    /// Local overrides: `<address>.png` or `<domain>.png` (also .jpg, .svg)
+    pub dir: String,
+    /// Attach the sender's avatar to new-mail notifications
+    pub notifications: bool,
+    /// Draw the avatar in `mu preview` (needs chafa)
+    pub preview: bool,
+}
+
+impl Default for Avatars {
+    fn default() -> Self {
+        Self {
+            sources: ["local", "gravatar", "libravatar", "bimi"]
+                .map(String::from)
+                .to_vec(),
+            dir: "~/.config/mu/avatars".to_string(),
+            notifications: true,
+            preview: false,
+        }
+    }
+}
+
+/// Retention policy (`[expire]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Expire {
+    /// Run the rules from `mu sync` before indexing
+    pub on_sync: bool,
+    /// Where `archive-compress` writes `<folder>.mbox.gz`
+    pub archive_dir: String,
+    /// First rule whose folder glob matches wins
+    pub rules: Vec<ExpireRule>,
+}
+
+impl Default for Expire {
+    fn default() -> Self {
+        Self {
+            on_sync: false,
+            archive_dir: "~/.local/share/mu/expired".to_string(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Retention rule (`[[expire.rules]]`)
+#[derive(Debug, Deserialize)]
+pub struct ExpireRule {
+    /// Folder glob relative to the Maildir root (`*/Trash`, `Lists/**`)
+    pub folder: String,
+    /// Minimum age (`30d`, `1y`); required for `delete` and `archive-compress`, ignored for
+    /// `keep`
+    #[serde(default)]
+    pub age: Option<String>,
+    pub action: ExpireAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExpireAction {
+    /// Remove the message files
+    Delete,
+    /// Append to a gzipped mbox in `archive_dir`, then remove
+    ArchiveCompress,
+    /// Never expire (shields a folder from later, broader rules)
+    Keep,
+}
+
+/// Language and date formats (`[locale]`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Locale {
+    /// `pl`, `de_DE.UTF-8`…; defaults to `$LC_ALL`, `$LC_MESSAGES` or `$LANG`
+    pub language: Option<String>,
+    /// Date and time in previews (`%a, %-d %b %Y %H:%M`); defaults to the language's
+    pub date_format: Option<String>,
+    /// Day in listings (`%d.%m.%Y`); defaults to the language's
+    pub day_format: Option<String>,
+    /// Replacement strings by key (`new_messages = "{n} mail|{n} mails"`), plural forms split by `|`
+    pub messages: BTreeMap<String, String>,
+}
+
+/// HTML renderers (`[render]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Render {
+    /// Tried in order, skipping ones that aren't installed: `w3m`, `lynx`, `elinks`,
+    /// `native`, `builtin` or any program given `args`
+    pub chain: Vec<String>,
+    /// Arguments by renderer, replacing its defaults; HTML arrives on stdin and `{cols}`
+    /// stands for the render width
+    pub args: BTreeMap<String, Vec<String>>,
+    /// Signatures in `mu render` and previews: `dim` (default), `strip` or `keep`
+    pub signatures: Signatures,
+    /// Right-to-left text in tables and cards: `isolate` (default), `reorder` or `off`
+    pub bidi: bidi::Mode,
+    /// Regexes for company boilerplate dropped like legal disclaimers (a paragraph matching
+    /// one goes)
+    pub footers: Vec<String>,
+}
+
+impl Default for Render {
+    fn default() -> Self {
+        Self {
+            chain: ["w3m", "lynx", "elinks", "native", "builtin"]
+                .map(String::from)
+                .to_vec(),
+            args: BTreeMap::new(),
+            signatures: Signatures::default(),
+            bidi: bidi::Mode::default(),
+            footers: Vec::new(),
+        }
+    }
+}
+
+/// Extension points (`[hooks]`): shell commands given JSON on stdin, in order
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Before rendering: `{content_type, text}` of the input
+    pub pre_render: Vec<String>,
+    /// After rendering: `{content_type, text}` of the rendered text
+    pub post_render: Vec<String>,
+    /// After a pick in `mu fzf`: `{query, line, command}`, `command` going to neomutt
+    pub post_search_selection: Vec<String>,
+    /// Before each notification: `{channel, title, body, messages, held}`
+    pub pre_notify: Vec<String>,
+}
+
+/// Rhai script (`[script]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Script {
+    /// Script defining any of `tag`, `notify`, `listing` and `preview`
+    pub path: Option<String>,
+    /// Threads `mu tagrules` hands to the script's `tag`
+    pub query: String,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self {
+            path: None,
+            query: "tag:inbox".to_string(),
+        }
+    }
+}
+
+/// Thread summarizer (`[summarize]`)
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Summarize {
+    /// Shell command reading thread text on stdin and printing a summary
+    pub command: Option<String>,
+    /// `--preview` only summarizes threads with at least this many messages
+    pub preview_min_messages: usize,
+}
+
+impl Default for Summarize {
+    fn default() -> Self {
+        Self {
+            command: None,
+            preview_min_messages: 8,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file, falling back to defaults if it doesn't exist
+    ///
+    /// `MU_*` environment overrides are applied on top (see [`Config::apply_env`]).
+    pub fn load() -> Result<Self> {
+        let mut cfg = Self::load_file(&config_path())?;
+        cfg.apply_env(|name| std::env::var(name).ok());
+        Ok(cfg)
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content).map_err(|e| {
+            Error::ConfigInvalid {
+                path: path.display().to_string(),
+                message: format!("{:#}", e),
+            }
+            .into()
+        })
+    }
+
+    /// Override settings from the environment (`env` looks a variable up)
+    ///
+    /// `MU_NO_NOTIFY` turns off desktop and push notifications.
+    pub fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) {
+        if env_flag(env("MU_NO_NOTIFY")) {
+            self.push.desktop = false;
+            self.push.targets.clear();
+        }
+    }
+
+    /// Parse config from TOML text
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+/// The config file: `$MU_CONFIG`, else `config.toml` in [`config_dir`]
+pub fn config_path() -> PathBuf {
+    env_path("MU_CONFIG").unwrap_or_else(|| config_dir().join("config.toml"))
+}
+
+/// The mbsync config: `$MU_MBSYNCRC`, else `~/.mbsyncrc`
+pub fn mbsyncrc_path() -> PathBuf {
+    env_path("MU_MBSYNCRC").unwrap_or_else(|| home_dir().join(".mbsyncrc"))
+}
+
+/// A path from a non-empty environment variable, `~/` expanded
+pub fn env_path(name: &str) -> Option<PathBuf> {
+    non_empty_var(name).map(|value| expand_tilde(&value))
+}
+
+/// Whether a flag variable is set: anything but unset, empty, `0`, `false` or `no`
+fn env_flag(value: Option<String>) -> bool {
+    value.is_some_and(|v| !matches!(v.to_lowercase().as_str(), "" | "0" | "false" | "no"))
+}
+
+/// mu config directory (`$XDG_CONFIG_HOME/mu`, else `%APPDATA%\mu` on Windows and
+/// `~/.config/mu` elsewhere)
+pub fn config_dir() -> PathBuf {
+    let base = non_empty_var("XDG_CONFIG_HOME")
+        .or_else(|| cfg!(windows).then(|| non_empty_var("APPDATA")).flatten())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(".config"));
+    base.join("mu")
+}
+
+/// mu data directory (`$XDG_DATA_HOME/mu`, else `%LOCALAPPDATA%\mu` on Windows and
+/// `~/.local/share/mu` elsewhere)
+pub fn data_dir() -> PathBuf {
+    let base = non_empty_var("XDG_DATA_HOME")
+        .or_else(|| {
+            cfg!(windows)
+                .then(|| non_empty_var("LOCALAPPDATA"))
+                .flatten()
+        })
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(".local").join("share"));
+    base.join("mu")
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Expand a leading `~/` (or `~\` on Windows) to the home directory
+pub fn expand_tilde(path: &str) -> PathBuf {
+    let rest = path
+        .strip_prefix("~/")
+        .or_else(|| path.strip_prefix("~\\").filter(|_| cfg!(windows)));
+    match rest {
+        Some(rest) => home_dir().join(rest),
+        None if path == "~" => home_dir(),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Run a shell command and return its trimmed stdout (password commands etc.)
+pub fn command_output(command: &str) -> Result<String> {
+    let output = process::run(&mut process::shell(command))
+        .with_context(|| format!("Failed to run {}", command))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed", command);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// User home directory (`$HOME`, or `%USERPROFILE%` where that's unset, as on Windows)
+pub fn home_dir() -> PathBuf {
+    PathBuf::from(
+        non_empty_var("HOME")
+            .or_else(|| non_empty_var("USERPROFILE"))
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty() {
+        let cfg = Config::parse("").unwrap();
+        assert!(cfg.tag_rules.is_empty());
+    }
+
+    #[test]
+    fn test_parse_tag_rules() {
+        let cfg = Config::parse(
+            r#"
+[[tag_rules]]
+query = "from:github.com"
+tags = ["+github", "-inbox"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.tag_rules.len(), 1);
+        assert_eq!(cfg.tag_rules[0].tags, vec!["+github", "-inbox"]);
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let text = "[push]\n[[push.targets]]\nservice = \"ntfy\"\ntopic = \"mail\"\n";
+        let mut cfg = Config::parse(text).unwrap();
+        cfg.apply_env(|name| (name == "MU_NO_NOTIFY").then(|| "0".to_string()));
+        assert!(cfg.push.desktop);
+        assert_eq!(cfg.push.targets.len(), 1);
+        cfg.apply_env(|name| (name == "MU_NO_NOTIFY").then(|| "1".to_string()));
+        assert!(!cfg.push.desktop);
+        assert!(cfg.push.targets.is_empty());
+    }
+
+    #[test]
+    fn test_parse_accounts() {
+        let cfg = Config::parse(
+            r#"
+[[accounts]]
+name = "work"
+maildir = "Work"
+folders = ["INBOX", "Sent Items"]
+"#,
+        )
+        .unwrap();
+        let acct = &cfg.accounts[0];
+        assert_eq!(acct.dir(), "Work");
+        assert_eq!(
+            acct.folder_query("Sent Items"),
+            "folder:\"Work/Sent Items\""
+        );
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let cfg = Config::parse(
+            r#"
+[filter]
+maildir = "/mail"
+
+[[filter.rules]]
+header = "List-Id"
+matches = "rust"
+folder = "Lists/rust"
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.filter.default_folder, "INBOX");
+        assert_eq!(cfg.filter.rules[0].folder.as_deref(), Some("Lists/rust"));
+        assert!(!cfg.filter.rules[0].discard);
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        assert_eq!(expand_tilde("/abs/path"), PathBuf::from("/abs/path"));
+        assert!(expand_tilde("~/Mail").ends_with("Mail"));
+        assert!(!expand_tilde("~/Mail").starts_with("~"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Config::parse("tag_rules = 3").is_err());
+    }
+}
@@ -0,0 +1,579 @@
+//! Encrypted and signed mail (PGP/MIME through gpg, S/MIME through gpgsm): decrypted with
+//! the user's keys and signatures checked, summed up in a banner above the body
+
+use anyhow::{Context, Result};
+use mail_parser::{Message, MimeHeaders, PartType};
+use std::process::Command;
+
+use crate::{color, mime, process};
+
+const ARMOR_BEGIN: &str = "-----BEGIN PGP MESSAGE-----";
+const ARMOR_END: &str = "-----END PGP MESSAGE-----";
+
+/// What gpg writes with `--output`, and a detached signature it reads, in its scratch directory
+const OUTPUT: &str = "output";
+const SIGNATURE: &str = "signature";
+
+/// The program for a kind of mail: gpg for PGP/MIME, gpgsm for S/MIME
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Gpg,
+    Gpgsm,
+}
+
+impl Tool {
+    fn program(self) -> &'static str {
+        match self {
+            Tool::Gpg => "gpg",
+            Tool::Gpgsm => "gpgsm",
+        }
+    }
+}
+
+/// What gpg (or gpgsm) made of a message
+#[derive(Debug, PartialEq)]
+pub struct Verdict {
+    pub tool: Tool,
+    /// Whether it was encrypted, and if so whether it could be decrypted
+    pub encrypted: Option<bool>,
+    pub signature: Option<Signature>,
+}
+
+/// A signature and whose it is
+#[derive(Debug, PartialEq)]
+pub struct Signature {
+    /// The signer's user id (`Name <address>` from a certificate), or the key id when the
+    /// key isn't known
+    pub signer: String,
+    pub state: State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Good,
+    /// Intact, but the S/MIME certificate doesn't chain to a trusted root
+    Untrusted,
+    Bad,
+    ExpiredKey,
+    RevokedKey,
+    /// It couldn't be checked (usually: no public key)
+    Unchecked,
+}
+
+impl Verdict {
+    /// One line, green when all is well, yellow for a signature that can't be vouched for,
+    /// red for a bad one or mail that couldn't be decrypted
+    pub fn banner(&self) -> String {
+        let signed = self.signature.as_ref().map(|signature| {
+            let (state, sgr) = match signature.state {
+                State::Good => ("valid", "1;32"),
+                State::Untrusted => ("valid, certificate not trusted", "1;33"),
+                State::Bad => ("INVALID", "1;31"),
+                State::ExpiredKey => ("expired key", "1;33"),
+                State::RevokedKey => ("revoked key", "1;31"),
+                State::Unchecked => ("unverified: no public key", "1;33"),
+            };
+            (format!("by {} ({})", signature.signer, state), sgr)
+        });
+        let (text, sgr) = match (self.encrypted, signed) {
+            (Some(false), _) => (
+                format!(
+                    "🔒 Encrypted ({} could not decrypt it)",
+                    self.tool.program()
+                ),
+                "1;31",
+            ),
+            (Some(true), None) => ("🔒 Encrypted".to_string(), "1;32"),
+            (Some(true), Some((by, sgr))) => (format!("🔒 Encrypted, signed {}", by), sgr),
+            (None, Some((by, sgr))) => (format!("✎ Signed {}", by), sgr),
+            (None, None) => return String::new(),
+        };
+        color::paint(sgr, text)
+    }
+}
+
+/// How a message's top part is wrapped
+#[derive(Debug, PartialEq)]
+enum Layer {
+    Encrypted(Tool),
+    /// S/MIME signed-data: the content travels inside the signature
+    Opaque,
+    Signed(Tool),
+}
+
+/// The message to show and the verdict on it, for encrypted or signed mail (None for
+/// anything else)
+///
+/// Encrypted mail comes back as the decrypted entity under the outer headers (as it was
+/// when it can't be decrypted), S/MIME signed-data as its content, and multipart/signed as
+/// its signed part alone, so nothing unsigned is shown under the signature's banner.
+pub fn open(raw: &[u8]) -> Option<(Vec<u8>, Verdict)> {
+    let mut shown = raw.to_vec();
+    let mut verdict: Option<Verdict> = None;
+    // S/MIME signs, then encrypts the signed entity: two layers at most
+    for _ in 0..2 {
+        let message = mime::parse(&shown).ok()?;
+        let Some(layer) = layer(&message) else {
+            break;
+        };
+        let (tool, unwrapped) = match layer {
+            Layer::Encrypted(tool) => (tool, decrypt_entity(tool, &message)),
+            Layer::Opaque => (Tool::Gpgsm, signed_content(&message)),
+            Layer::Signed(tool) => (tool, Ok(verify(tool, &message))),
+        };
+        let verdict = verdict.get_or_insert(Verdict {
+            tool,
+            encrypted: None,
+            signature: None,
+        });
+        match unwrapped {
+            Ok((entity, signature)) => {
+                verdict.signature = signature.or(verdict.signature.take());
+                if let Layer::Encrypted(_) = layer {
+                    verdict.encrypted = Some(true);
+                }
+                if entity.is_empty() {
+                    break;
+                }
+                let mut inner = outer_headers(&shown);
+                inner.extend_from_slice(&entity);
+                shown = inner;
+            }
+            Err(e) => {
+                tracing::debug!(error = %e, "not decrypted");
+                verdict.encrypted = Some(false);
+                break;
+            }
+        }
+    }
+    verdict.map(|verdict| (shown, verdict))
+}
+
+/// The wrapping of a message's top part, if it's encrypted or signed
+fn layer(message: &Message) -> Option<Layer> {
+    let content_type = message.parts.first()?.content_type()?;
+    let essence = format!(
+        "{}/{}",
+        content_type.ctype(),
+        content_type.subtype().unwrap_or_default()
+    )
+    .to_lowercase();
+    let protocol = content_type
+        .attribute("protocol")
+        .unwrap_or_default()
+        .to_lowercase();
+    let smime = protocol.ends_with("pkcs7-signature");
+    match essence.as_str() {
+        "multipart/encrypted" if protocol.starts_with("application/pgp-") => {
+            Some(Layer::Encrypted(Tool::Gpg))
+        }
+        "multipart/signed" if protocol.starts_with("application/pgp-") => {
+            Some(Layer::Signed(Tool::Gpg))
+        }
+        "multipart/signed" if smime => Some(Layer::Signed(Tool::Gpgsm)),
+        "application/pkcs7-mime" | "application/x-pkcs7-mime" => {
+            match content_type.attribute("smime-type") {
+                Some(kind) if kind.eq_ignore_ascii_case("signed-data") => Some(Layer::Opaque),
+                _ => Some(Layer::Encrypted(Tool::Gpgsm)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The decrypted entity, and the signature it carried (PGP signs inside the encryption)
+fn decrypt_entity(tool: Tool, message: &Message) -> Result<(Vec<u8>, Option<Signature>)> {
+    let part = match tool {
+        Tool::Gpg => mime::find_part(message, "application/octet-stream"),
+        Tool::Gpgsm => message.parts.first(),
+    }
+    .context("No encrypted part")?;
+    let (plain, status) = run(
+        tool,
+        &["--output", OUTPUT, "--decrypt"],
+        &[],
+        part.contents(),
+    )?;
+    // A bad signature fails the run too, but the plaintext is there
+    if !reports(&status, "DECRYPTION_OKAY") {
+        anyhow::bail!("{} could not decrypt the message", tool.program());
+    }
+    Ok((plain, signature(&status, tool)))
+}
+
+/// S/MIME signed-data: the content gpgsm extracts, and its signature
+fn signed_content(message: &Message) -> Result<(Vec<u8>, Option<Signature>)> {
+    let part = message.parts.first().context("No signed part")?;
+    let (content, status) = run(
+        Tool::Gpgsm,
+        &["--output", OUTPUT, "--verify"],
+        &[],
+        part.contents(),
+    )?;
+    Ok((content, signature(&status, Tool::Gpgsm)))
+}
+
+/// Check a multipart/signed body: its first part, as sent, against the second
+///
+/// The signed part comes back as the entity to show (empty when the body isn't two parts),
+/// with the signature (None when it couldn't be checked at all).
+fn verify(tool: Tool, message: &Message) -> (Vec<u8>, Option<Signature>) {
+    let signed = || {
+        let PartType::Multipart(children) = &message.parts.first()?.body else {
+            return None;
+        };
+        let signed = message.parts.get(*children.first()? as usize)?;
+        let sig = message.parts.get(*children.get(1)? as usize)?;
+        let bytes = message
+            .raw_message
+            .get(signed.offset_header as usize..signed.offset_end as usize)?;
+        Some((bytes, sig.contents()))
+    };
+    let Some((bytes, sig)) = signed() else {
+        return (Vec::new(), None);
+    };
+    // The signed text goes in with CRLF line ends, as it was signed
+    let checked = run(
+        tool,
+        &["--verify", SIGNATURE, "-"],
+        &[(SIGNATURE, sig)],
+        &crlf(bytes),
+    );
+    let signature = checked
+        .ok()
+        .and_then(|(_, status)| signature(&status, tool));
+    (bytes.to_vec(), signature)
+}
+
+/// The `[GNUPG:] ` status lines: keyword and arguments
+///
+/// Only whole lines count, and only from the status stream (never gpg's messages, which can
+/// quote text from the mail).
+fn statuses(status: &str) -> Vec<(&str, &str)> {
+    status
+        .lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix("[GNUPG:] ")?;
+            Some(line.split_once(' ').unwrap_or((line, "")))
+        })
+        .collect()
+}
+
+/// Whether a status line with `keyword` was reported
+fn reports(status: &str, keyword: &str) -> bool {
+    statuses(status).iter().any(|(k, _)| *k == keyword)
+}
+
+/// The first signature reported in the status lines
+fn signature(status: &str, tool: Tool) -> Option<Signature> {
+    let statuses = statuses(status);
+    let (state, rest) = statuses.iter().find_map(|(keyword, rest)| {
+        let state = match *keyword {
+            "GOODSIG" => State::Good,
+            "BADSIG" => State::Bad,
+            "EXPKEYSIG" => State::ExpiredKey,
+            "REVKEYSIG" => State::RevokedKey,
+            "ERRSIG" => State::Unchecked,
+            _ => return None,
+        };
+        Some((state, *rest))
+    })?;
+    // Web-of-trust levels are gpg's business; for S/MIME the chain is the point
+    let untrusted = statuses
+        .iter()
+        .any(|(keyword, _)| matches!(*keyword, "TRUST_UNDEFINED" | "TRUST_NEVER"));
+    let state = match state {
+        State::Good if tool == Tool::Gpgsm && untrusted => State::Untrusted,
+        state => state,
+    };
+    let (key, user) = rest.split_once(' ').unwrap_or((rest, ""));
+    let signer = match state {
+        State::Unchecked => format!("key {}", key),
+        _ if user.starts_with('/') => from_dn(user),
+        _ => user.to_string(),
+    };
+    Some(Signature { signer, state })
+}
+
+/// `Name <address>` from a certificate subject (`/CN=Jane Doe/O=Example/EMail=jane@x`)
+fn from_dn(dn: &str) -> String {
+    let field = |name: &str| {
+        dn.split('/')
+            .find_map(|f| {
+                f.split_once('=')
+                    .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            })
+            .map(|(_, v)| v)
+    };
+    match (field("CN"), field("EMail")) {
+        (Some(name), Some(address)) => format!("{} <{}>", name, address),
+        (Some(name), None) => name.to_string(),
+        (None, Some(address)) => address.to_string(),
+        (None, None) => dn.to_string(),
+    }
+}
+
+/// `\n` line ends made `\r\n`, as signatures over MIME are computed
+fn crlf(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + bytes.len() / 40);
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\n' && (i == 0 || bytes[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// The message with its encrypted body replaced by the plaintext
+///
+/// PGP/MIME yields the decrypted MIME entity under the outer headers;
+/// inline PGP becomes a text/plain body with the armored block decrypted.
+pub fn decrypted(raw: &[u8]) -> Result<Vec<u8>> {
+    let message = mime::parse(raw)?;
+    let (entity, inline) = match mime::find_part(&message, "application/octet-stream")
+        .filter(|_| mime::content_type(&message.parts[0]) == "multipart/encrypted")
+    {
+        Some(part) => (part.contents().to_vec(), false),
+        None => {
+            let text = mime::find_part(&message, "text/plain")
+                .map(mime::part_text)
+                .filter(|t| t.contains(ARMOR_BEGIN))
+                .context("No PGP message found")?;
+            (text.into_bytes(), true)
+        }
+    };
+
+    let text = String::from_utf8_lossy(&entity);
+    let start = text.find(ARMOR_BEGIN).context("No PGP message found")?;
+    let end = text[start..]
+        .find(ARMOR_END)
+        .map_or(text.len(), |i| start + i + ARMOR_END.len());
+    let (plain, status) = run(
+        Tool::Gpg,
+        &["--output", OUTPUT, "--decrypt"],
+        &[],
+        text[start..end].as_bytes(),
+    )?;
+    if !reports(&status, "DECRYPTION_OKAY") {
+        anyhow::bail!("gpg could not decrypt the message");
+    }
+
+    let mut out = outer_headers(raw);
+    if inline {
+        out.extend_from_slice(b"Content-Type: text/plain; charset=utf-8\r\n\r\n");
+        out.extend_from_slice(text[..start].as_bytes());
+        out.extend_from_slice(&plain);
+        out.extend_from_slice(text[end..].as_bytes());
+    } else {
+        out.extend_from_slice(&plain);
+    }
+    Ok(out)
+}
+
+/// Header block without MIME structure headers (the decrypted entity brings its own)
+fn outer_headers(raw: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(raw);
+    let head = text
+        .split_once("\r\n\r\n")
+        .or_else(|| text.split_once("\n\n"))
+        .map_or(text.as_ref(), |(head, _)| head);
+    let mut out = String::new();
+    let mut skip = false;
+    for line in head.lines() {
+        if !line.starts_with([' ', '\t']) {
+            let name = line.split(':').next().unwrap_or("").to_lowercase();
+            skip = name.starts_with("content-") || name == "mime-version";
+        }
+        if !skip {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+    }
+    out.into_bytes()
+}
+
+/// Run `tool` on `input` in a scratch directory holding `files`: what it wrote to
+/// [`OUTPUT`] and its status lines
+///
+/// Status goes to stdout, kept apart from the messages on stderr. The directory is created
+/// fresh (owner-only) and removed afterwards; paths in it are relative, so the command line
+/// stays the same from run to run.
+fn run(
+    tool: Tool,
+    args: &[&str],
+    files: &[(&str, &[u8])],
+    input: &[u8],
+) -> Result<(Vec<u8>, String)> {
+    let dir = tempfile::Builder::new()
+        .prefix("mu-gpg-")
+        .tempdir()
+        .context("Failed to create a scratch directory")?;
+    for (name, contents) in files {
+        std::fs::write(dir.path().join(name), contents)
+            .with_context(|| format!("Failed to write {}", name))?;
+    }
+    // It may stop reading early; what it made of that is in the status
+    let output = process::run_with_input(
+        Command::new(tool.program())
+            .current_dir(dir.path())
+            .args(["--batch", "--quiet", "--status-fd", "1"])
+            .args(args),
+        input,
+    )
+    .with_context(|| format!("Failed to run {}", tool.program()))?;
+    let written = std::fs::read(dir.path().join(OUTPUT)).unwrap_or_default();
+    Ok((
+        written,
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    const ENCRYPTED: &[u8] = b"From: a@x\r\n\
+Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=b\r\n\r\n\
+--b\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n\
+--b\r\nContent-Type: application/octet-stream\r\n\r\n\
+-----BEGIN PGP MESSAGE-----\r\nhQEM\r\n-----END PGP MESSAGE-----\r\n--b--\r\n";
+
+    /// gpg answering `--decrypt` with `stdout` for status and `stderr` for messages
+    fn decrypting(stdout: &str, stderr: &str) -> Replay {
+        let recording = serde_json::json!({
+            "program": "gpg",
+            "args": ["--batch", "--quiet", "--status-fd", "1", "--output", "output", "--decrypt"],
+            "status": 2,
+            "stdout": stdout,
+            "stderr": stderr,
+        });
+        Replay::parse(&recording.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_status_only_from_status_lines() {
+        // The marker in gpg's messages (which can quote the mail) is not a status
+        let spoofed = decrypting(
+            "[GNUPG:] BEGIN_DECRYPTION\n[GNUPG:] DECRYPTION_FAILED\n",
+            "[GNUPG:] DECRYPTION_OKAY\ngpg: decryption failed: No secret key\n",
+        );
+        runner::scoped(spoofed, || {
+            assert!(decrypted(ENCRYPTED).is_err());
+            let (_, verdict) = open(ENCRYPTED).unwrap();
+            assert_eq!(verdict.encrypted, Some(false));
+        });
+        // Nor is it mid-line on the status stream
+        let quoted = decrypting("[GNUPG:] PLAINTEXT 62 0 [GNUPG:] DECRYPTION_OKAY\n", "");
+        runner::scoped(quoted, || assert!(decrypted(ENCRYPTED).is_err()));
+        let okay = decrypting("[GNUPG:] DECRYPTION_OKAY\n[GNUPG:] END_DECRYPTION\n", "");
+        runner::scoped(okay, || {
+            let (_, verdict) = open(ENCRYPTED).unwrap();
+            assert_eq!(verdict.encrypted, Some(true));
+        });
+        assert!(!reports("gpg: [GNUPG:] DECRYPTION_OKAY", "DECRYPTION_OKAY"));
+        assert!(reports("[GNUPG:] DECRYPTION_OKAY", "DECRYPTION_OKAY"));
+    }
+
+    #[test]
+    fn test_signed_part_alone_is_shown() {
+        let raw = b"From: a@x\r\nSubject: Hi\r\n\
+Content-Type: multipart/signed; protocol=\"application/pgp-signature\"; boundary=b\r\n\r\n\
+--b\r\nContent-Type: text/plain\r\n\r\nSigned text\r\n\
+--b\r\nContent-Type: application/pgp-signature\r\n\r\nSIG\r\n\
+--b\r\nContent-Type: text/plain\r\n\r\nAdded later\r\n--b--\r\n";
+        let recording = serde_json::json!({
+            "program": "gpg",
+            "args": ["--batch", "--quiet", "--status-fd", "1", "--verify", "signature", "-"],
+            "stdout": "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@x>\n",
+        });
+        let replay = Replay::parse(&recording.to_string()).unwrap();
+        let (shown, verdict) = runner::scoped(replay, || open(raw)).unwrap();
+        let shown = String::from_utf8(shown).unwrap();
+        assert!(shown.starts_with("From: a@x\r\nSubject: Hi\r\nContent-Type: text/plain"));
+        assert!(shown.contains("Signed text"));
+        assert!(!shown.contains("Added later"));
+        assert_eq!(
+            color::strip(&verdict.banner()),
+            "✎ Signed by Jane Doe <jane@x> (valid)"
+        );
+    }
+
+    #[test]
+    fn test_outer_headers() {
+        let raw = b"From: a@x\r\nContent-Type: multipart/encrypted;\r\n protocol=\"application/pgp-encrypted\"\r\n\
+Subject: Hi\r\nMIME-Version: 1.0\r\n\r\nbody\r\n";
+        assert_eq!(outer_headers(raw), b"From: a@x\r\nSubject: Hi\r\n");
+    }
+
+    #[test]
+    fn test_decrypted_without_pgp() {
+        assert!(decrypted(b"Subject: x\r\n\r\nplain\r\n").is_err());
+        assert!(open(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b--\r\n").is_none());
+    }
+
+    #[test]
+    fn test_signature_and_banner() {
+        let status = "gpg: Signature made today\n[GNUPG:] NEWSIG\n\
+[GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@x>\n[GNUPG:] TRUST_UNDEFINED 0 pgp";
+        let verdict = Verdict {
+            tool: Tool::Gpg,
+            encrypted: Some(true),
+            signature: signature(status, Tool::Gpg),
+        };
+        assert_eq!(
+            color::strip(&verdict.banner()),
+            "🔒 Encrypted, signed by Jane Doe <jane@x> (valid)"
+        );
+        let unknown = Verdict {
+            tool: Tool::Gpg,
+            encrypted: None,
+            signature: signature(
+                "[GNUPG:] ERRSIG 0123456789ABCDEF 1 10 00 1700000000 9 -",
+                Tool::Gpg,
+            ),
+        };
+        assert_eq!(
+            color::strip(&unknown.banner()),
+            "✎ Signed by key 0123456789ABCDEF (unverified: no public key)"
+        );
+        assert_eq!(crlf(b"a\nb\r\nc"), b"a\r\nb\r\nc");
+    }
+
+    #[test]
+    fn test_smime() {
+        let status = "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG A045 /CN=Jane Doe/O=Example/EMail=jane@x\n\
+[GNUPG:] TRUST_UNDEFINED 98";
+        let signature = signature(status, Tool::Gpgsm).unwrap();
+        assert_eq!(signature.signer, "Jane Doe <jane@x>");
+        assert_eq!(signature.state, State::Untrusted);
+        let failed = Verdict {
+            tool: Tool::Gpgsm,
+            encrypted: Some(false),
+            signature: None,
+        };
+        assert_eq!(
+            color::strip(&failed.banner()),
+            "🔒 Encrypted (gpgsm could not decrypt it)"
+        );
+
+        let layer_of = |content_type: &str| {
+            let raw = format!("Content-Type: {}\r\n\r\nMIIB\r\n", content_type);
+            layer(&mime::parse(raw.as_bytes()).unwrap())
+        };
+        assert_eq!(
+            layer_of("application/pkcs7-mime; smime-type=enveloped-data; name=smime.p7m"),
+            Some(Layer::Encrypted(Tool::Gpgsm))
+        );
+        assert_eq!(
+            layer_of("application/x-pkcs7-mime; smime-type=signed-data"),
+            Some(Layer::Opaque)
+        );
+        assert_eq!(
+            layer_of("multipart/signed; protocol=\"application/x-pkcs7-signature\"; boundary=b"),
+            Some(Layer::Signed(Tool::Gpgsm))
+        );
+        assert_eq!(layer_of("multipart/mixed; boundary=b"), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Failure kinds that wrapping scripts can branch on, each with a stable exit code
+//!
+//! Functions keep returning `anyhow::Result`; these errors travel inside it and are
+//! found again with [`find`].
+
+use serde::Serialize;
+
+/// A classified failure
+#[derive(Debug, thiserror::Error, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Error {
+    /// An external program isn't installed or not on PATH
+    #[error("{tool} is not installed (or not on PATH)")]
+    ToolMissing { tool: String },
+
+    /// `config.toml` couldn't be parsed
+    #[error("Invalid config {path}: {message}")]
+    ConfigInvalid { path: String, message: String },
+
+    /// An external program exited unsuccessfully
+    #[error("{tool} failed{}", details(.stderr))]
+    ExternalCommandFailed { tool: String, stderr: String },
+
+    /// An external program was killed after running too long
+    #[error("{tool} timed out after {seconds}s")]
+    Timeout { tool: String, seconds: u64 },
+
+    /// Input (a message, a server response) couldn't be parsed
+    #[error("Failed to parse {what}: {message}")]
+    ParseError { what: String, message: String },
+
+    /// A query or reference matched no message
+    #[error("No message matches {query}")]
+    NoMatch { query: String },
+
+    /// A message couldn't be delivered now; the MTA should try again later
+    #[error("Delivery deferred")]
+    Deferred,
+}
+
+/// Exit code for failures without a kind
+pub const EXIT_OTHER: u8 = 1;
+
+impl Error {
+    /// Process exit code (2 is left to argument errors)
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::ConfigInvalid { .. } => 3,
+            Error::ToolMissing { .. } => 4,
+            Error::ExternalCommandFailed { .. } => 5,
+            Error::Timeout { .. } => 6,
+            Error::ParseError { .. } => 7,
+            Error::NoMatch { .. } => 8,
+            // sysexits' EX_TEMPFAIL, which MTAs retry
+            Error::Deferred => 75,
+        }
+    }
+
+    /// A failed run of `tool`, with its (trimmed) stderr
+    pub fn command_failed(tool: &str, stderr: &[u8]) -> Self {
+        Error::ExternalCommandFailed {
+            tool: tool.to_string(),
+            stderr: String::from_utf8_lossy(stderr).trim().to_string(),
+        }
+    }
+}
+
+/// A spawn failure: `ToolMissing` when the program doesn't exist, else the I/O error
+pub fn spawn_failed(tool: &str, err: std::io::Error) -> anyhow::Error {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        Error::ToolMissing {
+            tool: tool.to_string(),
+        }
+        .into()
+    } else {
+        anyhow::Error::new(err).context(format!("Failed to run {}", tool))
+    }
+}
+
+/// The classified error somewhere in an error's chain
+pub fn find(err: &anyhow::Error) -> Option<&Error> {
+    err.chain().find_map(|cause| cause.downcast_ref::<Error>())
+}
+
+/// Whether a write went to a closed pipe (e.g. the pager was quit early)
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+fn details(stderr: &str) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", stderr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_find_through_context() {
+        let err = Err::<(), _>(Error::command_failed("notmuch", b"  no database\n"))
+            .context("Failed to list threads")
+            .unwrap_err();
+        let found = find(&err).unwrap();
+        assert_eq!(found.exit_code(), 5);
+        assert_eq!(found.to_string(), "notmuch failed: no database");
+        assert_eq!(
+            serde_json::to_value(found).unwrap(),
+            serde_json::json!({"kind": "external_command_failed", "tool": "notmuch", "stderr": "no database"})
+        );
+    }
+
+    #[test]
+    fn test_spawn_failed() {
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let err = spawn_failed("fzf", missing);
+        assert_eq!(find(&err).map(Error::exit_code), Some(4));
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(find(&spawn_failed("fzf", denied)).is_none());
+        assert_eq!(
+            Error::command_failed("notmuch tag", b"").to_string(),
+            "notmuch tag failed"
+        );
+    }
+}
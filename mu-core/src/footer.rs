@@ -0,0 +1,201 @@
+//! Legal footers in rendered mail: the "this email is confidential…" paragraphs under a
+//! message, and company boilerplate matching `[render] footers`, dropped
+//!
+//! Lines are held a paragraph at a time. A paragraph goes if it opens with a notice heading,
+//! shows two signs of a disclaimer, or matches a configured pattern; once one goes, paragraphs
+//! after it need only one sign (disclaimers run to several paragraphs).
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::config::Config;
+use crate::lines::LineFilter;
+use crate::{cleanup, color};
+
+/// A paragraph longer than this is text, not a footer
+const MAX_LINES: usize = 30;
+
+/// A heading announcing a notice
+static HEADING: LazyLock<Regex> = LazyLock::new(|| {
+    cleanup::re(concat!(
+        r"(?i)^\W*(?:disclaimer|confidentiality (?:notice|note|statement)|legal notice",
+        r"|important notice|privileged and confidential|haftungsausschluss)\b",
+    ))
+});
+
+/// A footer on its own line, needing nothing else
+static LONE: LazyLock<Regex> = LazyLock::new(|| {
+    cleanup::re(r"(?i)consider the environment before printing|think before you print")
+});
+
+/// Signs of a disclaimer, each counted once
+static SIGNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"(?i)\bconfidential",
+        r"(?i)\bprivileged\b",
+        concat!(
+            r"(?i)\bintended (?:solely |only )?for the (?:(?:sole )?use of the )?",
+            r"(?:addressee|(?:named |intended )?recipient|individual|person|entity)",
+        ),
+        concat!(
+            r"(?i)\b(?:received|receive|got) this (?:e-?mail|message|communication|transmission)",
+            r" (?:in error|by mistake)",
+        ),
+        concat!(
+            r"(?i)\b(?:notify|inform) (?:the sender|us)",
+            r"|\b(?:delete|destroy) (?:it|this (?:e-?mail|message)|all copies)\b",
+        ),
+        r"(?i)\bstrictly prohibited\b|\bunauthori[sz]ed (?:use|review|disclosure|copying|distribution)",
+        r"(?i)\bvirus(?:es)?\b",
+        r"(?i)\b(?:accept|assume)s? no (?:responsibility|liability)|\bliability (?:for|arising)",
+        r"(?i)\bregistered (?:office|in england|number)|\bcompany (?:no|number|registration)\b",
+        r"(?i)\bvertraulich|irrtümlich erhalten|\bconfidentiel",
+    ]
+    .into_iter()
+    .map(cleanup::re)
+    .collect()
+});
+
+/// `[render] footers` from the config; patterns that don't compile are skipped with a warning
+static PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    let patterns = Config::load()
+        .map(|cfg| cfg.render.footers)
+        .unwrap_or_default();
+    patterns
+        .iter()
+        .filter_map(|pattern| {
+            Regex::new(pattern)
+                .inspect_err(|e| tracing::warn!(%pattern, error = %e, "footer pattern ignored"))
+                .ok()
+        })
+        .collect()
+});
+
+/// Drops footer paragraphs from lines pushed one at a time
+#[derive(Debug)]
+pub(crate) struct Footers<'a> {
+    on: bool,
+    patterns: &'a [Regex],
+    paragraph: Vec<String>,
+    /// The last paragraph was a footer
+    footer: bool,
+    /// Blank lines after a dropped paragraph go with it
+    swallow: bool,
+}
+
+impl Footers<'static> {
+    /// A filter with the configured patterns (passing everything through unless `on`)
+    pub(crate) fn new(on: bool) -> Self {
+        let patterns: &'static [Regex] = if on { &PATTERNS } else { &[] };
+        Footers::with(on, patterns)
+    }
+}
+
+impl<'a> Footers<'a> {
+    fn with(on: bool, patterns: &'a [Regex]) -> Self {
+        Self {
+            on,
+            patterns,
+            paragraph: Vec::new(),
+            footer: false,
+            swallow: false,
+        }
+    }
+
+    /// The held paragraph, unless it's a footer
+    fn settle(&mut self) -> String {
+        if self.paragraph.is_empty() {
+            return String::new();
+        }
+        let lines = std::mem::take(&mut self.paragraph);
+        let text: Vec<String> = lines
+            .iter()
+            .map(|line| color::strip(line).trim().to_string())
+            .collect();
+        let text = text.join(" ");
+        let signs = SIGNS.iter().filter(|sign| sign.is_match(&text)).count();
+        self.footer = HEADING.is_match(&text)
+            || LONE.is_match(&text)
+            || self.patterns.iter().any(|pattern| pattern.is_match(&text))
+            || signs >= 2
+            || (self.footer && signs >= 1);
+        self.swallow = self.footer;
+        if self.footer {
+            String::new()
+        } else {
+            lines.concat()
+        }
+    }
+}
+
+impl LineFilter for Footers<'_> {
+    fn push(&mut self, line: String) -> String {
+        if !self.on {
+            return line;
+        }
+        if color::strip(&line).trim().is_empty() {
+            let mut out = self.settle();
+            if !self.swallow {
+                out.push_str(&line);
+            }
+            return out;
+        }
+        self.swallow = false;
+        self.paragraph.push(line);
+        if self.paragraph.len() > MAX_LINES {
+            self.footer = false;
+            return std::mem::take(&mut self.paragraph).concat();
+        }
+        String::new()
+    }
+
+    fn finish(mut self) -> String {
+        self.settle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines;
+
+    const DISCLAIMER: &str = "This email and any attachments are confidential and intended\n\
+solely for the use of the addressee.\n\
+\n\
+If you have received this email in error, please notify the sender and delete it.\n\
+Any unauthorized use is strictly prohibited.\n";
+
+    fn strip(text: &str) -> String {
+        lines::apply(Footers::with(true, &[]), text)
+    }
+
+    #[test]
+    fn test_disclaimer() {
+        let mail = format!(
+            "Hi,\n\nThe report is attached.\n\n-- \nJane\n\n{}",
+            DISCLAIMER
+        );
+        assert_eq!(
+            strip(&mail),
+            "Hi,\n\nThe report is attached.\n\n-- \nJane\n\n"
+        );
+        let notice = "See you at 5.\n\nCONFIDENTIALITY NOTICE: This message may contain…\n";
+        assert_eq!(strip(notice), "See you at 5.\n\n");
+        let lone = "Thanks!\n\nPlease consider the environment before printing this email.\n";
+        assert_eq!(strip(lone), "Thanks!\n\n");
+        // Mentioning confidentiality once is just text
+        let text = "Keep this confidential until Friday.\n\nThanks\n";
+        assert_eq!(strip(text), text);
+        assert_eq!(lines::apply(Footers::new(false), DISCLAIMER), DISCLAIMER);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let patterns = [Regex::new(r"^Acme Corp\. is a member of").unwrap()];
+        let mail = "Done.\n\nAcme Corp. is a member of the Acme Group,\nLondon.\n\nBye\n";
+        assert_eq!(
+            lines::apply(Footers::with(true, &patterns), mail),
+            "Done.\n\nBye\n"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! A raw message's header block above its rendered body: every header decoded and wrapped to
+//! the render width, the ones people read colored and the Received chain folded to one line
+
+use mail_parser::{HeaderName, Message};
+
+use crate::render::style;
+use crate::{mime, renderer, theme, width};
+
+/// Headers shown first and colored, in this order
+const KEY: [&str; 7] = ["From", "To", "Cc", "Bcc", "Reply-To", "Subject", "Date"];
+
+/// Continuation lines of a wrapped value start this far in
+const INDENT: usize = 4;
+
+/// The header block of `message`
+pub(crate) fn block(message: &Message) -> String {
+    let theme = theme::get();
+    let cols = renderer::cols();
+    let mut lines = Vec::new();
+    for name in KEY {
+        let Some(value) = message.header_raw(name) else {
+            continue;
+        };
+        let value = wrapped(name, &mime::decode_words(value), cols);
+        let value = match name {
+            "Subject" => style(&theme.subject, value),
+            _ => value,
+        };
+        lines.push(format!(
+            "{} {}",
+            style(&theme.label, format!("{}:", name)),
+            value
+        ));
+    }
+    let mut folded = false;
+    for (name, value) in message.headers_raw() {
+        if KEY.iter().any(|key| key.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        if name.eq_ignore_ascii_case("Received") {
+            if !folded {
+                lines.push(style(&theme.muted, received(message)));
+                folded = true;
+            }
+            continue;
+        }
+        let line = format!(
+            "{}: {}",
+            name,
+            wrapped(name, &mime::decode_words(value), cols)
+        );
+        lines.push(style(&theme.muted, line));
+    }
+    lines.join("\n")
+}
+
+/// `value` wrapped to `cols` after `name: `
+fn wrapped(name: &str, value: &str, cols: usize) -> String {
+    let first = cols.saturating_sub(width::width(name) + 2);
+    let indent = " ".repeat(INDENT);
+    width::wrap(value, first, cols.saturating_sub(INDENT)).join(&format!("\n{}", indent))
+}
+
+/// `▸ Received: 3 hops, laptop → mx.example.net → mail.local`, oldest first
+fn received(message: &Message) -> String {
+    let hops: Vec<_> = message
+        .headers()
+        .iter()
+        .filter(|header| header.name == HeaderName::Received)
+        .filter_map(|header| header.value.as_received())
+        .collect();
+    let mut hosts: Vec<String> = Vec::new();
+    for hop in hops.iter().rev() {
+        for host in [hop.from(), hop.by()].into_iter().flatten() {
+            let host = host.to_string();
+            if hosts.last() != Some(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    let n = message
+        .headers_raw()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("Received"))
+        .count();
+    let hops = if n == 1 { "hop" } else { "hops" };
+    if hosts.is_empty() {
+        format!("▸ Received: {} {}", n, hops)
+    } else {
+        format!("▸ Received: {} {}, {}", n, hops, hosts.join(" → "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    #[test]
+    fn test_block() {
+        let raw = b"Received: from mx.example.net by mail.local with ESMTPS; Mon, 2 Feb 2026 10:00:30 +0000\r\n\
+Received: from laptop by mx.example.net with ESMTPSA; Mon, 2 Feb 2026 10:00:05 +0000\r\n\
+Subject: =?UTF-8?B?VXJnZW50?=\r\n\
+From: Bank <support@bank.example>\r\n\
+X-Mailer: Mailer\r\n\
+Date: Mon, 2 Feb 2026 10:00:00 +0000\r\n\r\nbody";
+        let message = mime::parse(raw).unwrap();
+        assert_eq!(
+            color::strip(&block(&message)),
+            "From: Bank <support@bank.example>\nSubject: Urgent\n\
+Date: Mon, 2 Feb 2026 10:00:00 +0000\n\
+▸ Received: 2 hops, laptop → mx.example.net → mail.local\nX-Mailer: Mailer"
+        );
+        assert_eq!(
+            wrapped("To", "a@x.example, b@x.example", 20),
+            "a@x.example,\n    b@x.example"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Extension points: configured commands that see mu's data as JSON and may change or veto it
+//!
+//! Each command under `[hooks]` gets the data as a JSON object on stdin (and the point's
+//! name in `$MU_HOOK`). It prints nothing to leave the data alone, the whole object changed
+//! to transform it, or `{"veto": true}` to stop what mu was about to do. Commands at one
+//! point run in order, each seeing what the one before it left.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::{self, Config};
+use crate::error::Error;
+use crate::process;
+use crate::render::ContentType;
+
+/// A hook that hangs is killed and counts as failed
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where hooks run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Point {
+    /// Before rendering, with [`Content`] of the input
+    PreRender,
+    /// After rendering, with [`Content`] of the output
+    PostRender,
+    /// After a pick in `mu fzf`, before its neomutt command goes out
+    PostSearchSelection,
+    /// Before each desktop or push notification
+    PreNotify,
+}
+
+impl Point {
+    /// `$MU_HOOK`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Point::PreRender => "pre-render",
+            Point::PostRender => "post-render",
+            Point::PostSearchSelection => "post-search-selection",
+            Point::PreNotify => "pre-notify",
+        }
+    }
+
+    fn commands(self, hooks: &config::Hooks) -> &[String] {
+        match self {
+            Point::PreRender => &hooks.pre_render,
+            Point::PostRender => &hooks.post_render,
+            Point::PostSearchSelection => &hooks.post_search_selection,
+            Point::PreNotify => &hooks.pre_notify,
+        }
+    }
+}
+
+/// What render hooks see: `{"content_type": "html", "text": "…"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Content {
+    pub content_type: ContentType,
+    pub text: String,
+}
+
+static HOOKS: OnceLock<config::Hooks> = OnceLock::new();
+
+/// Use `hooks` for this process (before any hook point is reached; later calls are ignored)
+pub fn set(hooks: config::Hooks) {
+    let _ = HOOKS.set(hooks);
+}
+
+fn configured() -> &'static config::Hooks {
+    HOOKS.get_or_init(|| Config::load().map(|c| c.hooks).unwrap_or_default())
+}
+
+/// Whether any command runs at `point`
+pub fn enabled(point: Point) -> bool {
+    !point.commands(configured()).is_empty()
+}
+
+/// `data` after the hooks at `point`, or None if one vetoed
+pub fn run<T: Serialize + DeserializeOwned>(point: Point, data: T) -> Result<Option<T>> {
+    let mut data = data;
+    for command in point.commands(configured()) {
+        let _span = tracing::debug_span!("hook", point = point.name(), command).entered();
+        match apply(point, command, &data)
+            .with_context(|| format!("{} hook `{}`", point.name(), command))?
+        {
+            Outcome::Keep => {}
+            Outcome::Replace(changed) => data = changed,
+            Outcome::Veto => {
+                tracing::debug!("vetoed");
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(data))
+}
+
+enum Outcome<T> {
+    Keep,
+    Replace(T),
+    Veto,
+}
+
+fn apply<T: Serialize + DeserializeOwned>(
+    point: Point,
+    command: &str,
+    data: &T,
+) -> Result<Outcome<T>> {
+    let input = serde_json::to_vec(data)?;
+    let mut cmd = process::shell(command);
+    cmd.env("MU_HOOK", point.name());
+    let output = process::block_on(process::output(cmd, Some(&input), TIMEOUT))??;
+    if !output.status.success() {
+        return Err(Error::command_failed(command, &output.stderr).into());
+    }
+    let stdout = output.stdout.trim_ascii();
+    if stdout.is_empty() {
+        return Ok(Outcome::Keep);
+    }
+    let value: serde_json::Value = serde_json::from_slice(stdout).context("Output isn't JSON")?;
+    if value.get("veto").and_then(|v| v.as_bool()) == Some(true) {
+        return Ok(Outcome::Veto);
+    }
+    let changed = serde_json::from_value(value).context("Output doesn't match the input")?;
+    Ok(Outcome::Replace(changed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str) -> Content {
+        Content {
+            content_type: ContentType::Html,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_keeps_replaces_and_vetoes() {
+        let data = content("<p>Hi</p>");
+        let keep = apply(Point::PreRender, "cat > /dev/null", &data).unwrap();
+        assert!(matches!(keep, Outcome::Keep));
+
+        let sed = r#"sed 's/"text":"[^"]*"/"text":"'"$MU_HOOK"'"/'"#;
+        let Outcome::Replace(changed) = apply(Point::PostRender, sed, &data).unwrap() else {
+            panic!("not replaced");
+        };
+        assert_eq!(changed, content("post-render"));
+
+        let veto = apply(Point::PreRender, r#"echo '{"veto": true}'"#, &data).unwrap();
+        assert!(matches!(veto, Outcome::Veto));
+    }
+
+    #[test]
+    fn test_apply_rejects_bad_output() {
+        let data = content("x");
+        let err = apply(Point::PreRender, "echo nope", &data).err().unwrap();
+        assert_eq!(err.to_string(), "Output isn't JSON");
+        let err = apply(Point::PreRender, "echo '{}'", &data).err().unwrap();
+        assert_eq!(err.to_string(), "Output doesn't match the input");
+        let err = apply(Point::PreRender, "exit 3", &data).err().unwrap();
+        assert_eq!(err.to_string(), "exit 3 failed");
+    }
+}
@@ -0,0 +1,153 @@
+//! HTML mail to text: trackers removed, links marked, the article picked out in reader mode,
+//! then laid out by the renderer chain (or converted to markdown) and tidied
+
+use anyhow::Result;
+use std::borrow::Cow;
+use std::time::Instant;
+
+use crate::anchor::{contact_list, flag_links, footnotes, link_list, mailto_links};
+use crate::colorize::clean_text;
+use crate::render::{Links, Options, declutter, style, tidiers};
+use crate::{color, lines, reader, renderer, theme, tracker};
+
+/// HTML as markdown: the built-in converter's, else the renderer chain's text
+pub(crate) fn markdown(html: &str, options: &Options) -> Result<String> {
+    let (html, removed) = tracker::clean(html);
+    let mut mentioned = Vec::new();
+    let html = mailto_links(&html, &mut mentioned);
+    let html = flag_links(&html);
+    let html = article(&html, options);
+    let mut text = match fallback(&html, options.links.strip_urls()) {
+        Ok(md) => lines::apply(tidiers(options, false), &md),
+        Err(_) => render(&html, options)?,
+    };
+    if options.contacts {
+        text.push_str(&contact_list(&mentioned));
+    }
+    if options.tracker_report {
+        text.push_str(&tracker_report(removed));
+    }
+    Ok(color::strip(&text).into_owned())
+}
+
+/// Only the article of `html` in reader mode
+pub(crate) fn article<'a>(html: &'a str, options: &Options) -> Cow<'a, str> {
+    if options.reader {
+        Cow::Owned(reader::extract(html))
+    } else {
+        Cow::Borrowed(html)
+    }
+}
+
+pub(crate) fn render(html: &str, options: &Options) -> Result<String> {
+    let (html, removed) = tracker::clean(html);
+    let mut mentioned = Vec::new();
+    let html = mailto_links(&html, &mut mentioned);
+    let html = flag_links(&html);
+    let html = article(&html, options);
+    let (html, targets) = match options.links {
+        Links::Footnotes => footnotes(&html),
+        _ => (Cow::Borrowed(html.as_ref()), Vec::new()),
+    };
+    // The first of the configured dump tools (w3m by default) that works
+    let strip_urls = options.links.strip_urls();
+    let text = renderer::convert(renderer::chain(), &html, strip_urls)?;
+
+    // Clean up the dump
+    let started = Instant::now();
+    let mut cleaned = declutter(&clean_text(&text, strip_urls), options);
+    tracing::trace!(bytes = text.len(), elapsed = ?started.elapsed(), "cleaned");
+    cleaned.push_str(&link_list(&targets));
+    if options.contacts {
+        cleaned.push_str(&contact_list(&mentioned));
+    }
+    if options.tracker_report {
+        cleaned.push_str(&tracker_report(removed));
+    }
+    Ok(cleaned)
+}
+
+/// The dim line after rendered HTML saying how many trackers went
+pub(crate) fn tracker_report(removed: usize) -> String {
+    format!(
+        "\n\n{}",
+        style(&theme::get().muted, tracker::report(removed))
+    )
+}
+
+/// HTML laid out as text, [`renderer::cols`] wide (the `native` renderer); links become
+/// numbered footnotes unless URLs are stripped
+#[cfg(feature = "native")]
+pub(crate) fn native(html: &str, strip_urls: bool) -> Result<String> {
+    let started = Instant::now();
+    let text = html2text::config::plain_no_decorate()
+        .link_footnotes(!strip_urls)
+        .allow_width_overflow()
+        .string_from_read(html.as_bytes(), renderer::cols())?;
+    tracing::trace!(bytes = html.len(), elapsed = ?started.elapsed(), "laid out");
+    Ok(text)
+}
+
+/// Without the native layout, HTML needs another renderer
+#[cfg(not(feature = "native"))]
+pub(crate) fn native(_: &str, _: bool) -> Result<String> {
+    anyhow::bail!("No native HTML layout (mu-core was built without the `native` feature)")
+}
+
+/// HTML to markdown with the built-in converter (the `builtin` renderer)
+#[cfg(feature = "html")]
+pub(crate) fn fallback(html: &str, strip_urls: bool) -> Result<String> {
+    use html_to_markdown_rs::{ConversionOptions, convert};
+
+    let started = Instant::now();
+    let options = ConversionOptions {
+        wrap: true,
+        wrap_width: renderer::cols(),
+        ..ConversionOptions::default()
+    };
+    let md = convert(html, Some(options))?;
+    tracing::trace!(bytes = html.len(), elapsed = ?started.elapsed(), "converted");
+    Ok(crate::markdown::clean(&md, strip_urls))
+}
+
+/// Without the built-in converter, HTML needs an external renderer
+#[cfg(not(feature = "html"))]
+pub(crate) fn fallback(_: &str, _: bool) -> Result<String> {
+    anyhow::bail!("No built-in HTML converter (mu-core was built without the `html` feature)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{ContentType, render_as};
+
+    #[test]
+    fn test_render_html_cleans_w3m_output() {
+        crate::renderer::set_cols(Some(120));
+        let replay = crate::runner::Replay::parse(
+            r#"{"program":"w3m","args":["-dump","-T","text/html","-cols","120"],"stdout":"\n\nNEWSLETTER\n\n\n\nsee https://example.com/a/very/long/tracking/link/that/goes/on\n"}"#,
+        )
+        .unwrap();
+        let text = crate::runner::scoped(replay, || {
+            render_as("<p>x</p>", ContentType::Html, &Options::default())
+        });
+        assert_eq!(crate::color::strip(&text.unwrap()), "NEWSLETTER\n\nsee");
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_native_layout() {
+        crate::renderer::set_cols(Some(30));
+        let html = concat!(
+            r#"<div><div><p>Deep <a href="https://a.example/">link</a></p></div></div>"#,
+            "<table><tr><td>Tea</td><td>$8</td></tr><tr><td>Mug</td><td>$12</td></tr></table>",
+            "<ul><li>One<ul><li>Two</li></ul></li></ul>",
+        );
+        let table = "───┬───\nTea│$8 \n───┼───\nMug│$12\n───┴───\n";
+        assert_eq!(
+            native(html, false).unwrap(),
+            format!("Deep [link][1]\n\n{table}* One\n  * Two\n\n[1]: https://a.example/\n")
+        );
+        assert!(!native(html, true).unwrap().contains("https://"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Minimal HTTP client on top of curl
+
+use anyhow::{Context, Result};
+use std::process::Command;
+
+use crate::error::{self, Error};
+use crate::process;
+
+/// An HTTP request, sent via `curl -K -`
+///
+/// Everything (including credentials) goes through curl's config on stdin,
+/// so secrets never show up in the process list.
+pub struct Request {
+    method: String,
+    url: String,
+    headers: Vec<String>,
+    user: Option<String>,
+    body: Option<String>,
+}
+
+/// Status code and raw body of a response
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+impl Request {
+    pub fn new(method: &str, url: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: Vec::new(),
+            user: None,
+            body: None,
+        }
+    }
+
+    pub fn header(mut self, header: &str) -> Self {
+        self.headers.push(header.to_string());
+        self
+    }
+
+    pub fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.user = Some(format!("{}:{}", user, password));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    /// curl config file contents for this request
+    fn config(&self) -> String {
+        let mut lines = vec![
+            format!("url = {}", quote(&self.url)),
+            format!("request = {}", quote(&self.method)),
+        ];
+        lines.extend(
+            self.headers
+                .iter()
+                .map(|h| format!("header = {}", quote(h))),
+        );
+        if let Some(user) = &self.user {
+            lines.push(format!("user = {}", quote(user)));
+        }
+        if let Some(body) = &self.body {
+            lines.push(format!("data-binary = {}", quote(body)));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Send the request and return the response (non-2xx is not an error)
+    pub fn send(&self) -> Result<Response> {
+        let mut cmd = Command::new("curl");
+        cmd.args([
+            "-sS",
+            "-L",
+            "--max-time",
+            "30",
+            "-K",
+            "-",
+            "-w",
+            "\n%{http_code}",
+        ]);
+        // The URL goes to curl on stdin, so it's not among the command's arguments
+        tracing::debug!(method = %self.method, url = %self.url, "request");
+        let output = process::run_with_input(&mut cmd, self.config().as_bytes())
+            .map_err(|e| error::spawn_failed("curl", e))?;
+        if !output.status.success() {
+            return Err(
+                Error::command_failed(&format!("curl {}", self.url), &output.stderr).into(),
+            );
+        }
+        split_status(output.stdout)
+    }
+}
+
+/// Quote a value for curl's config syntax
+pub fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Split `body\nSTATUS` as produced by `-w "\n%{http_code}"`
+fn split_status(mut out: Vec<u8>) -> Result<Response> {
+    let pos = out
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .context("Malformed curl output")?;
+    let status = String::from_utf8_lossy(&out[pos + 1..])
+        .trim()
+        .parse()
+        .context("Malformed HTTP status")?;
+    out.truncate(pos);
+    Ok(Response { status, body: out })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_quotes_everything() {
+        let req = Request::new("REPORT", "https://dav.example/ab/")
+            .header("Depth: 1")
+            .basic_auth("me", "p\"w")
+            .body("<a>\n</a>");
+        let cfg = req.config();
+        assert!(cfg.contains("request = \"REPORT\""));
+        assert!(cfg.contains("user = \"me:p\\\"w\""));
+        assert!(cfg.contains("data-binary = \"<a>\\n</a>\""));
+    }
+
+    #[test]
+    fn test_split_status() {
+        let resp = split_status(b"hello\nworld\n404".to_vec()).unwrap();
+        assert_eq!(resp.status, 404);
+        assert_eq!(resp.text(), "hello\nworld");
+        assert!(!resp.is_success());
+        assert!(split_status(b"nope".to_vec()).is_err());
+    }
+}
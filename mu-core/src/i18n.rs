@@ -0,0 +1,373 @@
+//! Translated user-facing strings and locale date formats (`[locale]`)
+//!
+//! English, German, French, Spanish and Polish are built in; any string can be replaced
+//! from the config, and the language comes from the config or the usual `LANG` variables.
+
+use mail_parser::DateTime;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+use crate::config::{self, Config};
+
+/// A translated string and its arguments
+#[derive(Debug, Clone, Copy)]
+pub enum Msg<'a> {
+    /// `{n}` new messages (sync total, notification title)
+    NewMessages(usize),
+    /// New mail from `{sender}` (notification title)
+    NewMailFrom(&'a str),
+    /// `{n}` new, per channel in the sync summary
+    Fetched(usize),
+    /// `{n}` deleted, per channel
+    Deleted(usize),
+    /// `{n}` flag changes, per channel
+    FlagChanges(usize),
+    /// …and `{n}` more messages (notification body, past the listed ones)
+    AndMore(usize),
+    /// A folded quote of `{n}` lines in rendered mail
+    Quoted(usize),
+}
+
+/// `[locale.messages]` keys, in [`Msg`] order
+pub const KEYS: [&str; 7] = [
+    "new_messages",
+    "new_mail_from",
+    "fetched",
+    "deleted",
+    "flag_changes",
+    "and_more",
+    "quoted",
+];
+
+impl Msg<'_> {
+    /// Position in [`KEYS`] and the catalogs
+    fn slot(&self) -> usize {
+        match self {
+            Msg::NewMessages(_) => 0,
+            Msg::NewMailFrom(_) => 1,
+            Msg::Fetched(_) => 2,
+            Msg::Deleted(_) => 3,
+            Msg::FlagChanges(_) => 4,
+            Msg::AndMore(_) => 5,
+            Msg::Quoted(_) => 6,
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Msg::NewMessages(n)
+            | Msg::Fetched(n)
+            | Msg::Deleted(n)
+            | Msg::FlagChanges(n)
+            | Msg::AndMore(n)
+            | Msg::Quoted(n) => *n,
+            Msg::NewMailFrom(_) => 1,
+        }
+    }
+}
+
+/// One language's strings, names and formats; plural forms are split by `|`
+struct Catalog {
+    code: &'static str,
+    /// Plural form index for a count
+    plural: fn(usize) -> usize,
+    months: [&'static str; 12],
+    /// Sunday first
+    weekdays: [&'static str; 7],
+    date: &'static str,
+    day: &'static str,
+    /// Same order as [`KEYS`]
+    messages: [&'static str; 7],
+}
+
+fn one_other(n: usize) -> usize {
+    usize::from(n != 1)
+}
+
+const EN: Catalog = Catalog {
+    code: "en",
+    plural: one_other,
+    months: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    weekdays: ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"],
+    date: "%a, %-d %b %Y %H:%M",
+    day: "%Y-%m-%d",
+    messages: [
+        "{n} new message|{n} new messages",
+        "New mail from {sender}",
+        "{n} new",
+        "{n} del",
+        "{n} flags",
+        "…and {n} more message|…and {n} more messages",
+        "quoted message ({n} line)|quoted message ({n} lines)",
+    ],
+};
+
+const DE: Catalog = Catalog {
+    code: "de",
+    plural: one_other,
+    months: [
+        "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+    ],
+    weekdays: ["So", "Mo", "Di", "Mi", "Do", "Fr", "Sa"],
+    date: "%a, %-d. %b %Y, %H:%M",
+    day: "%d.%m.%Y",
+    messages: [
+        "{n} neue Nachricht|{n} neue Nachrichten",
+        "Neue Mail von {sender}",
+        "{n} neu",
+        "{n} gelöscht",
+        "{n} Markierungen",
+        "…und {n} weitere Nachricht|…und {n} weitere Nachrichten",
+        "zitierte Nachricht ({n} Zeile)|zitierte Nachricht ({n} Zeilen)",
+    ],
+};
+
+const FR: Catalog = Catalog {
+    code: "fr",
+    // 0 and 1 are singular in French
+    plural: |n| usize::from(n > 1),
+    months: [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+        "déc.",
+    ],
+    weekdays: ["dim.", "lun.", "mar.", "mer.", "jeu.", "ven.", "sam."],
+    date: "%a %-d %b %Y, %H:%M",
+    day: "%d/%m/%Y",
+    messages: [
+        "{n} nouveau message|{n} nouveaux messages",
+        "Nouveau message de {sender}",
+        "{n} nouveau|{n} nouveaux",
+        "{n} supprimé|{n} supprimés",
+        "{n} drapeau|{n} drapeaux",
+        "…et {n} autre message|…et {n} autres messages",
+        "message cité ({n} ligne)|message cité ({n} lignes)",
+    ],
+};
+
+const ES: Catalog = Catalog {
+    code: "es",
+    plural: one_other,
+    months: [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ],
+    weekdays: ["dom", "lun", "mar", "mié", "jue", "vie", "sáb"],
+    date: "%a, %-d %b %Y, %H:%M",
+    day: "%d/%m/%Y",
+    messages: [
+        "{n} mensaje nuevo|{n} mensajes nuevos",
+        "Correo nuevo de {sender}",
+        "{n} nuevo|{n} nuevos",
+        "{n} borrado|{n} borrados",
+        "{n} marca|{n} marcas",
+        "…y {n} mensaje más|…y {n} mensajes más",
+        "mensaje citado ({n} línea)|mensaje citado ({n} líneas)",
+    ],
+};
+
+const PL: Catalog = Catalog {
+    code: "pl",
+    // 1 / 2-4, 22-24… / 0, 5-21, 25…
+    plural: |n| match (n % 10, n % 100) {
+        _ if n == 1 => 0,
+        (2..=4, tens) if !(12..=14).contains(&tens) => 1,
+        _ => 2,
+    },
+    months: [
+        "sty", "lut", "mar", "kwi", "maj", "cze", "lip", "sie", "wrz", "paź", "lis", "gru",
+    ],
+    weekdays: ["niedz.", "pon.", "wt.", "śr.", "czw.", "pt.", "sob."],
+    date: "%a, %-d %b %Y, %H:%M",
+    day: "%d.%m.%Y",
+    messages: [
+        "{n} nowa wiadomość|{n} nowe wiadomości|{n} nowych wiadomości",
+        "Nowa poczta od {sender}",
+        "{n} nowa|{n} nowe|{n} nowych",
+        "{n} usunięta|{n} usunięte|{n} usuniętych",
+        "{n} zmiana flag|{n} zmiany flag|{n} zmian flag",
+        "…i jeszcze {n} wiadomość|…i jeszcze {n} wiadomości|…i jeszcze {n} wiadomości",
+        "cytowana wiadomość ({n} wiersz)|cytowana wiadomość ({n} wiersze)|cytowana wiadomość ({n} wierszy)",
+    ],
+};
+
+const CATALOGS: [&Catalog; 5] = [&EN, &DE, &FR, &ES, &PL];
+
+/// Strings and formats for one user
+pub struct Locale {
+    catalog: &'static Catalog,
+    date_format: String,
+    day_format: String,
+    overrides: BTreeMap<String, String>,
+}
+
+static CURRENT: OnceLock<Locale> = OnceLock::new();
+
+/// The locale from the config (loaded on first use) and environment
+pub fn current() -> &'static Locale {
+    CURRENT.get_or_init(|| {
+        let cfg = Config::load().map(|c| c.locale).unwrap_or_default();
+        Locale::new(&cfg, |name| std::env::var(name).ok())
+    })
+}
+
+/// [`Locale::text`] in the current locale
+pub fn tr(msg: Msg) -> String {
+    current().text(msg)
+}
+
+impl Locale {
+    /// The `[locale]` settings, with `env` supplying `LC_ALL`/`LC_MESSAGES`/`LANG`
+    pub fn new(cfg: &config::Locale, env: impl Fn(&str) -> Option<String>) -> Self {
+        let language = cfg.language.clone().or_else(|| {
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .into_iter()
+                .filter_map(env)
+                .find(|v| !v.is_empty())
+        });
+        let catalog = language.as_deref().map_or(&EN, catalog);
+        Self {
+            catalog,
+            date_format: cfg.date_format.as_deref().unwrap_or(catalog.date).into(),
+            day_format: cfg.day_format.as_deref().unwrap_or(catalog.day).into(),
+            overrides: cfg.messages.clone(),
+        }
+    }
+
+    /// `msg` translated, with its count or sender filled in
+    pub fn text(&self, msg: Msg) -> String {
+        let slot = msg.slot();
+        let forms = self
+            .overrides
+            .get(KEYS[slot])
+            .map_or(self.catalog.messages[slot], String::as_str);
+        let forms: Vec<&str> = forms.split('|').collect();
+        let form = (self.catalog.plural)(msg.count()).min(forms.len() - 1);
+        let text = forms[form].replace("{n}", &msg.count().to_string());
+        match msg {
+            Msg::NewMailFrom(sender) => text.replace("{sender}", sender),
+            _ => text,
+        }
+    }
+
+    /// Date and time for previews, in the date's own time zone
+    pub fn date(&self, date: &DateTime) -> String {
+        self.format(&self.date_format, date)
+    }
+
+    /// Day for listings
+    pub fn day(&self, date: &DateTime) -> String {
+        self.format(&self.day_format, date)
+    }
+
+    /// strftime-style `pattern`: `%Y %m %d %H %M %S` (`%-d` etc. unpadded), `%a %b` names,
+    /// `%z` offset, `%%`; anything else is kept as written
+    fn format(&self, pattern: &str, date: &DateTime) -> String {
+        let mut out = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let mut spec = chars.next();
+            let padded = spec != Some('-');
+            if !padded {
+                spec = chars.next();
+            }
+            let number = |n: u16, width: usize| match padded {
+                true => format!("{:0width$}", n),
+                false => n.to_string(),
+            };
+            match spec {
+                Some('Y') => out.push_str(&date.year.to_string()),
+                Some('m') => out.push_str(&number(date.month.into(), 2)),
+                Some('d') => out.push_str(&number(date.day.into(), 2)),
+                Some('H') => out.push_str(&number(date.hour.into(), 2)),
+                Some('M') => out.push_str(&number(date.minute.into(), 2)),
+                Some('S') => out.push_str(&number(date.second.into(), 2)),
+                Some('a') => {
+                    out.push_str(self.catalog.weekdays[usize::from(date.day_of_week() % 7)])
+                }
+                Some('b') => out.push_str(self.month(date)),
+                Some('z') => {
+                    let sign = if date.tz_before_gmt { '-' } else { '+' };
+                    let _ = write!(out, "{}{:02}{:02}", sign, date.tz_hour, date.tz_minute);
+                }
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    if !padded {
+                        out.push('-');
+                    }
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+
+    fn month(&self, date: &DateTime) -> &'static str {
+        let index = usize::from(date.month.clamp(1, 12)) - 1;
+        self.catalog.months[index]
+    }
+}
+
+/// The catalog for a language like `pl`, `pl_PL.UTF-8` or `de-AT` (English if unknown)
+fn catalog(language: &str) -> &'static Catalog {
+    let code = language
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    CATALOGS.into_iter().find(|c| c.code == code).unwrap_or(&EN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(toml: &str) -> Locale {
+        let cfg = Config::parse(toml).unwrap();
+        Locale::new(&cfg.locale, |name| {
+            (name == "LANG").then(|| "pl_PL.UTF-8".to_string())
+        })
+    }
+
+    #[test]
+    fn test_plurals() {
+        let pl = locale("");
+        assert_eq!(pl.text(Msg::NewMessages(1)), "1 nowa wiadomość");
+        assert_eq!(pl.text(Msg::NewMessages(23)), "23 nowe wiadomości");
+        assert_eq!(pl.text(Msg::NewMessages(12)), "12 nowych wiadomości");
+        assert_eq!(pl.text(Msg::NewMailFrom("Ala")), "Nowa poczta od Ala");
+        assert_eq!(pl.text(Msg::Quoted(42)), "cytowana wiadomość (42 wiersze)");
+        let en = locale("[locale]\nlanguage = \"en_US\"");
+        assert_eq!(en.text(Msg::NewMessages(1)), "1 new message");
+        assert_eq!(en.text(Msg::Deleted(3)), "3 del");
+        let fr = locale("[locale]\nlanguage = \"fr\"");
+        assert_eq!(fr.text(Msg::Fetched(0)), "0 nouveau");
+    }
+
+    #[test]
+    fn test_overrides() {
+        let custom = locale(
+            "[locale]\nlanguage = \"xx\"\n[locale.messages]\nnew_messages = \"{n} letter|{n} letters\"",
+        );
+        assert_eq!(custom.text(Msg::NewMessages(2)), "2 letters");
+        assert_eq!(custom.text(Msg::FlagChanges(2)), "2 flags");
+    }
+
+    #[test]
+    fn test_dates() {
+        let date = DateTime::parse_rfc822("Mon, 2 Feb 2026 09:05:00 +0100").unwrap();
+        let pl = locale("");
+        assert_eq!(pl.date(&date), "pon., 2 lut 2026, 09:05");
+        assert_eq!(pl.day(&date), "02.02.2026");
+        let en = locale("[locale]\nlanguage = \"C\"\ndate_format = \"%A %-m/%d %z 100%%\"");
+        assert_eq!(en.date(&date), "%A 2/02 +0100 100%");
+        assert_eq!(en.day(&date), "2026-02-02");
+    }
+}
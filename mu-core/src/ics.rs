@@ -0,0 +1,288 @@
+//! iCalendar (text/calendar) parsing, and invites rendered as event cards
+
+use jiff::civil::DateTime;
+use jiff::tz::TimeZone;
+
+use crate::colorize;
+
+/// A parsed iCalendar property (`NAME;PARAM=x:value`)
+#[derive(Debug, Clone)]
+pub struct Property {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl Property {
+    /// Look up a parameter value (case-insensitive)
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Check whether text looks like an iCalendar object
+pub fn is_calendar(text: &str) -> bool {
+    text.trim_start().starts_with("BEGIN:VCALENDAR")
+}
+
+/// Unfold continuation lines (RFC 5545 §3.1) and parse properties
+pub fn parse(text: &str) -> Vec<Property> {
+    let mut unfolded: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            unfolded.last_mut(),
+        ) {
+            (Some(cont), Some(last)) => last.push_str(cont),
+            _ => unfolded.push(line.to_string()),
+        }
+    }
+    unfolded.iter().filter_map(|l| parse_line(l)).collect()
+}
+
+fn parse_line(line: &str) -> Option<Property> {
+    let (head, value) = line.split_once(':')?;
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: unescape(value),
+    })
+}
+
+/// Undo iCalendar text escaping (`\n`, `\,`, `\;`)
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", "\n")
+        .replace("\\N", "\n")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// Properties of the first VEVENT
+pub fn first_event(props: &[Property]) -> Vec<Property> {
+    props
+        .iter()
+        .skip_while(|p| !(p.name == "BEGIN" && p.value == "VEVENT"))
+        .skip(1)
+        .take_while(|p| !(p.name == "END" && p.value == "VEVENT"))
+        .cloned()
+        .collect()
+}
+
+/// Format an iCalendar date/date-time (`20260215T100000Z`) for display
+pub fn format_datetime(value: &str) -> String {
+    let v = value.trim();
+    if !v.is_ascii() {
+        return v.to_string();
+    }
+    let date = |s: &str| format!("{}-{}-{}", &s[0..4], &s[4..6], &s[6..8]);
+    match v.len() {
+        8 if v.chars().all(|c| c.is_ascii_digit()) => date(v),
+        15 | 16 if v.as_bytes()[8] == b'T' => {
+            let tz = if v.ends_with('Z') { " UTC" } else { "" };
+            format!("{} {}:{}{}", date(v), &v[9..11], &v[11..13], tz)
+        }
+        _ => v.to_string(),
+    }
+}
+
+/// Render the first event as a card (summary, time, place, organizer, replies so far), its
+/// description below
+pub fn render(text: &str) -> String {
+    render_in(text, &TimeZone::system())
+}
+
+fn render_in(text: &str, zone: &TimeZone) -> String {
+    let props = parse(text);
+    let event = first_event(&props);
+    let get = |name: &str| event.iter().find(|p| p.name == name);
+    let method = props.iter().find(|p| p.name == "METHOD");
+
+    let mut rows = Vec::new();
+    if let Some(p) = get("DTSTART") {
+        rows.push(("When", when(p, get("DTEND"), zone)));
+    }
+    if let Some(p) = get("LOCATION") {
+        rows.push(("Where", p.value.clone()));
+    }
+    if let Some(p) = get("ORGANIZER") {
+        rows.push(("Organizer", person(p)));
+    }
+    if let Some(replies) = rsvp(&event, method.is_some_and(|m| m.value == "REPLY")) {
+        rows.push(("RSVP", replies));
+    }
+    if get("STATUS").is_some_and(|p| p.value == "CANCELLED")
+        || method.is_some_and(|m| m.value == "CANCEL")
+    {
+        rows.push(("Status", "Cancelled".to_string()));
+    }
+    let title = get("SUMMARY").map_or("Event", |p| p.value.as_str());
+    let mut card = colorize::card(title, &rows);
+    if let Some(p) = get("DESCRIPTION").filter(|p| !p.value.trim().is_empty()) {
+        card.push_str("\n\n");
+        card.push_str(p.value.trim());
+    }
+    card
+}
+
+/// Start – end in `zone` (the local one), or as written when the time is floating or its
+/// `TZID` unknown
+fn when(start: &Property, end: Option<&Property>, zone: &TimeZone) -> String {
+    let show = |p: &Property| {
+        let at = moment(p, zone);
+        at.as_ref().map_or_else(
+            || format_datetime(&p.value),
+            |at| at.strftime("%Y-%m-%d %H:%M").to_string(),
+        )
+    };
+    let from = show(start);
+    let mut text = from.clone();
+    if let Some(end) = end {
+        let to = show(end);
+        // The same day: only the end time
+        let to = match (from.split_once(' '), to.split_once(' ')) {
+            (Some((day, _)), Some((end_day, time))) if day == end_day => time.to_string(),
+            _ => to,
+        };
+        text.push_str(&format!(" – {}", to));
+    }
+    let label = match moment(start, zone) {
+        Some(at) => Some(at.strftime("%Z").to_string()),
+        None => start.param("TZID").map(String::from),
+    };
+    if let Some(label) = label {
+        text.push_str(&format!(" ({})", label));
+    }
+    text
+}
+
+/// A UTC (`…Z`) or `TZID` date-time, in `zone`
+fn moment(p: &Property, zone: &TimeZone) -> Option<jiff::Zoned> {
+    let value = p.value.trim();
+    let (local, utc) = match value.strip_suffix('Z') {
+        Some(local) => (local, true),
+        None => (value, false),
+    };
+    let at: DateTime = DateTime::strptime("%Y%m%dT%H%M%S", local).ok()?;
+    let from = match p.param("TZID") {
+        _ if utc => TimeZone::UTC,
+        Some(tzid) => TimeZone::get(tzid).ok()?,
+        None => return None,
+    };
+    Some(at.to_zoned(from).ok()?.with_time_zone(zone.clone()))
+}
+
+/// `Name <address>` of an ORGANIZER or ATTENDEE
+fn person(p: &Property) -> String {
+    let address = p
+        .value
+        .trim_start_matches("mailto:")
+        .trim_start_matches("MAILTO:");
+    match p.param("CN") {
+        Some(name) => format!("{} <{}>", name, address),
+        None => address.to_string(),
+    }
+}
+
+/// The attendees' answers (`2 accepted, 1 awaiting`); in a REPLY, who answered what
+fn rsvp(event: &[Property], reply: bool) -> Option<String> {
+    let attendees: Vec<&Property> = event.iter().filter(|p| p.name == "ATTENDEE").collect();
+    if reply {
+        let attendee = attendees.first()?;
+        return Some(format!("{} {}", person(attendee), answer(attendee)));
+    }
+    let counts: Vec<String> = ["accepted", "tentative", "declined", "delegated", "awaiting"]
+        .into_iter()
+        .map(|state| {
+            (
+                state,
+                attendees.iter().filter(|a| answer(a) == state).count(),
+            )
+        })
+        .filter(|(_, n)| *n > 0)
+        .map(|(state, n)| format!("{} {}", n, state))
+        .collect();
+    (!counts.is_empty()).then(|| counts.join(", "))
+}
+
+fn answer(attendee: &Property) -> &'static str {
+    match attendee.param("PARTSTAT").map(str::to_uppercase).as_deref() {
+        Some("ACCEPTED") => "accepted",
+        Some("TENTATIVE") => "tentative",
+        Some("DECLINED") => "declined",
+        Some("DELEGATED") => "delegated",
+        _ => "awaiting",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nSUMMARY:Quarterly\r\n  review\r\n\
+DTSTART:20260215T100000Z\r\nDTEND:20260215T110000Z\r\nLOCATION:Room 4\\, 2nd floor\r\n\
+ORGANIZER;CN=\"Jane Doe\":mailto:jane@example.com\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parse_unfolds_and_escapes() {
+        let event = first_event(&parse(ICS));
+        assert_eq!(event[0].value, "Quarterly review");
+        let loc = event.iter().find(|p| p.name == "LOCATION").unwrap();
+        assert_eq!(loc.value, "Room 4, 2nd floor");
+        let org = event.iter().find(|p| p.name == "ORGANIZER").unwrap();
+        assert_eq!(org.param("cn"), Some("Jane Doe"));
+    }
+
+    fn card(ics: &str, zone: &str) -> String {
+        let zone = TimeZone::get(zone).unwrap();
+        crate::color::strip(&render_in(ics, &zone)).into_owned()
+    }
+
+    #[test]
+    fn test_render() {
+        assert!(is_calendar(ICS));
+        let out = card(ICS, "UTC");
+        assert!(out.starts_with("╭─ Quarterly review ─"));
+        assert!(out.contains("│ When:      2026-02-15 10:00 – 11:00 (UTC) "));
+        assert!(out.contains("│ Organizer: Jane Doe <jane@example.com> "));
+        assert!(!out.contains("RSVP"));
+    }
+
+    #[test]
+    fn test_render_in_local_zone_with_replies() {
+        let ics = ICS.replace(
+            "END:VEVENT",
+            "ATTENDEE;PARTSTAT=ACCEPTED:mailto:a@x\r\nATTENDEE;RSVP=TRUE:mailto:b@x\r\n\
+ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:c@x\r\nEND:VEVENT",
+        );
+        // Winter time in Warsaw, whatever the date today
+        let out = card(&ics, "Europe/Warsaw");
+        assert!(out.contains("When:      2026-02-15 11:00 – 12:00 (CET)"));
+        assert!(out.contains("RSVP:      1 accepted, 2 awaiting"));
+        let reply = ics.replace("METHOD:REQUEST", "METHOD:REPLY");
+        assert!(card(&reply, "UTC").contains("RSVP:      a@x accepted"));
+        let zoned = ics
+            .replace("DTSTART:", "DTSTART;TZID=America/New_York:")
+            .replace("DTEND:", "DTEND;TZID=America/New_York:");
+        let out = card(&zoned.replace("00Z", "00"), "Europe/Warsaw");
+        assert!(out.contains("When:      2026-02-15 16:00 – 17:00 (CET)"));
+    }
+
+    #[test]
+    fn test_format_datetime() {
+        assert_eq!(format_datetime("20260215"), "2026-02-15");
+        assert_eq!(format_datetime("20260215T093000"), "2026-02-15 09:30");
+        assert_eq!(format_datetime("garbage"), "garbage");
+    }
+}
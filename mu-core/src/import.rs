@@ -0,0 +1,132 @@
+//! mbox import into Maildir and picking up new files with notmuch
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{self, Config};
+use crate::error::{self, Error};
+use crate::{maildir, mbox, notmuch, process};
+
+/// notmuch's database root, falling back to the configured Maildir
+pub fn maildir_root(cfg: &Config) -> PathBuf {
+    match notmuch::config_get("database.path") {
+        Some(path) => PathBuf::from(path),
+        None => config::expand_tilde(&cfg.index.maildir),
+    }
+}
+
+/// Deliver every message of an mbox into `dest`, keeping read/replied/flagged state
+pub fn import_mbox(file: &Path, dest: &Path) -> Result<usize> {
+    let data = std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let messages = mbox::split(&data);
+    for raw in &messages {
+        maildir::deliver(dest, raw, Some(&mbox::flags(raw)))?;
+    }
+    Ok(messages.len())
+}
+
+/// Pick up the new files with `notmuch new`
+pub fn index() -> Result<()> {
+    let status = process::status(Command::new("notmuch").args(["new", "--quiet"]))
+        .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !status.success() {
+        return Err(Error::command_failed("notmuch new", b"").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_mbox() {
+        let dir = std::env::temp_dir().join(format!("mu-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("list.mbox");
+        std::fs::write(
+            &file,
+            "From a@x Mon Feb  2 10:00:00 2026\nSubject: one\nStatus: RO\n\nhi\n\n\
+From b@x Mon Feb  2 11:00:00 2026\nSubject: two\n\nyo\n",
+        )
+        .unwrap();
+
+        let dest = dir.join("Archive/2019");
+        assert_eq!(import_mbox(&file, &dest).unwrap(), 2);
+        let mut names: Vec<String> = std::fs::read_dir(dest.join("cur"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort_by_key(|n| n.ends_with(":2,"));
+        assert!(names[0].ends_with(":2,S") && names[1].ends_with(":2,"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The messages delivered to `dest`, by flags then content
+    fn delivered(dest: &Path) -> Vec<(String, String)> {
+        let mut found: Vec<(String, String)> = std::fs::read_dir(dest.join("cur"))
+            .unwrap()
+            .map(|e| {
+                let path = e.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                let flags = name.rsplit_once(":2,").unwrap().1.to_string();
+                (flags, std::fs::read_to_string(&path).unwrap())
+            })
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_import_unescapes_from_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.mbox");
+        // A body line starting `From ` is quoted in the mbox; without a year it doesn't split
+        std::fs::write(
+            &file,
+            "From a@x Mon Feb  2 10:00:00 2026\nSubject: one\n\n>From the start\n\
+>>From quoted\nFrom here on\n\n\
+From b@x Mon Feb  2 11:00:00 2026\nSubject: two\n\nyo\n",
+        )
+        .unwrap();
+        assert_eq!(import_mbox(&file, dir.path()).unwrap(), 2);
+        let messages = delivered(dir.path());
+        assert_eq!(
+            messages[0].1,
+            "Subject: one\n\nFrom the start\n>From quoted\nFrom here on\n"
+        );
+        assert_eq!(messages[1].1, "Subject: two\n\nyo\n");
+    }
+
+    #[test]
+    fn test_import_keeps_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("flags.mbox");
+        std::fs::write(
+            &file,
+            "From a@x Mon Feb  2 10:00:00 2026\nSubject: read\nStatus: RO\n\nhi\n\n\
+From b@x Mon Feb  2 11:00:00 2026\nSubject: answered\nStatus: RO\nX-Status: AF\n\nyo\n\n\
+From c@x Mon Feb  2 12:00:00 2026\nSubject: deleted\nStatus: O\nX-Status: D\n\nbye\n",
+        )
+        .unwrap();
+        assert_eq!(import_mbox(&file, dir.path()).unwrap(), 3);
+        let flags: Vec<String> = delivered(dir.path()).into_iter().map(|m| m.0).collect();
+        assert_eq!(flags, ["FRS", "S", "T"]);
+    }
+
+    #[test]
+    fn test_index_runs_notmuch_new() {
+        use crate::runner::{self, Replay};
+
+        let replay = |status| {
+            let recording = format!(
+                r#"{{"program":"notmuch","args":["new","--quiet"],"status":{}}}"#,
+                status
+            );
+            Replay::parse(&recording).unwrap()
+        };
+        assert!(runner::scoped(replay(0), index).is_ok());
+        assert!(runner::scoped(replay(1), index).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Built-in full-text index (tantivy) for setups without notmuch
+
+use anyhow::{Context, Result};
+use mail_parser::Message;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{AllQuery, QueryParser};
+use tantivy::schema::{Field, INDEXED, STORED, STRING, Schema, TEXT, Value};
+use tantivy::{DateTime, Index, IndexWriter, Order, TantivyDocument, Term, doc};
+
+use crate::config;
+use crate::{maildir, mime};
+
+/// A search result
+struct Hit {
+    id: String,
+    date: i64,
+    from: String,
+    subject: String,
+}
+
+/// Index fields
+struct Fields {
+    path: Field,
+    id: Field,
+    from: Field,
+    to: Field,
+    subject: Field,
+    body: Field,
+    date: Field,
+}
+
+/// Default index location (`~/.local/share/mu/index`)
+pub fn index_dir() -> PathBuf {
+    config::data_dir().join("index")
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        path: builder.add_text_field("path", STRING | STORED),
+        id: builder.add_text_field("id", STRING | STORED),
+        from: builder.add_text_field("from", TEXT | STORED),
+        to: builder.add_text_field("to", TEXT),
+        subject: builder.add_text_field("subject", TEXT | STORED),
+        body: builder.add_text_field("body", TEXT),
+        date: builder.add_i64_field("date", INDEXED | STORED | tantivy::schema::FAST),
+    };
+    (builder.build(), fields)
+}
+
+fn open(dir: &Path, rebuild: bool) -> Result<(Index, Fields)> {
+    let (schema, fields) = schema();
+    if rebuild && dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("Failed to clear {}", dir.display()))?;
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let index = Index::open_or_create(tantivy::directory::MmapDirectory::open(dir)?, schema)?;
+    Ok((index, fields))
+}
+
+/// Sync the index with the Maildir; returns (added, removed)
+pub fn update(dir: &Path, root: &Path, rebuild: bool) -> Result<(usize, usize)> {
+    let on_disk: HashSet<String> = maildir::files(root)?
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    sync(dir, &on_disk, rebuild, |path| std::fs::read(path).ok())
+}
+
+/// Index the files not yet in the index and drop those gone from `on_disk`
+///
+/// `load` returns the message bytes to index for a path, or None to skip it.
+pub fn sync(
+    dir: &Path,
+    on_disk: &HashSet<String>,
+    rebuild: bool,
+    mut load: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<(usize, usize)> {
+    let (index, f) = open(dir, rebuild)?;
+    let indexed = indexed_paths(&index, &f)?;
+
+    let mut writer: IndexWriter = index.writer(50_000_000)?;
+    let mut removed = 0;
+    for path in indexed.difference(on_disk) {
+        writer.delete_term(Term::from_field_text(f.path, path));
+        removed += 1;
+    }
+
+    let mut added = 0;
+    for path in on_disk.difference(&indexed) {
+        let Some(raw) = load(path) else {
+            continue;
+        };
+        let Ok(message) = mime::parse(&raw) else {
+            continue;
+        };
+        writer.add_document(document(&f, path, &message))?;
+        added += 1;
+    }
+    writer.commit()?;
+    Ok((added, removed))
+}
+
+/// Index document for a message file
+fn document(f: &Fields, path: &str, message: &Message) -> TantivyDocument {
+    let addresses = |a: Option<&mail_parser::Address>| {
+        a.map(|a| {
+            a.iter()
+                .map(|x| format!("{} {}", x.name().unwrap_or(""), x.address().unwrap_or("")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+    };
+    doc!(
+        f.path => path,
+        f.id => message.message_id().unwrap_or(path),
+        f.from => addresses(message.from()),
+        f.to => addresses(message.to()),
+        f.subject => message.subject().unwrap_or(""),
+        f.body => message.body_text(0).unwrap_or_default().to_string(),
+        f.date => message.date().map(|d| d.to_timestamp()).unwrap_or(0),
+    )
+}
+
+/// Paths currently in the index
+fn indexed_paths(index: &Index, f: &Fields) -> Result<HashSet<String>> {
+    let searcher = index.reader()?.searcher();
+    let mut paths = HashSet::new();
+    for addr in searcher.search(&AllQuery, &DocSetCollector)? {
+        let doc: TantivyDocument = searcher.doc(addr)?;
+        if let Some(p) = doc.get_first(f.path).and_then(|v| v.as_str()) {
+            paths.insert(p.to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// fzf listing lines (`id:<message-id>  date  from; subject`), newest first
+pub fn listing(dir: &Path, query: &str) -> Result<Vec<String>> {
+    Ok(search(dir, query, 10_000)?
+        .into_iter()
+        .map(|h| {
+            format!(
+                "id:{}  {}  {}; {}",
+                h.id,
+                format_date(h.date),
+                h.from.trim(),
+                h.subject
+            )
+        })
+        .collect())
+}
+
+/// Search the index (`from:x subject:y words`), newest first
+fn search(dir: &Path, query: &str, limit: usize) -> Result<Vec<Hit>> {
+    let (index, f) = open(dir, false)?;
+    let searcher = index.reader()?.searcher();
+    let parser = QueryParser::for_index(&index, vec![f.from, f.subject, f.body]);
+    let query = parser.parse_query(query).context("Invalid search query")?;
+    let top = TopDocs::with_limit(limit).order_by_fast_field::<i64>("date", Order::Desc);
+
+    let mut hits = Vec::new();
+    for (_, addr) in searcher.search(&query, &top)? {
+        let doc: TantivyDocument = searcher.doc(addr)?;
+        let text = |field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        hits.push(Hit {
+            id: text(f.id),
+            date: doc.get_first(f.date).and_then(|v| v.as_i64()).unwrap_or(0),
+            from: text(f.from),
+            subject: text(f.subject),
+        });
+    }
+    Ok(hits)
+}
+
+/// File path of an indexed message by Message-ID (with or without `id:`)
+pub fn path_for(dir: &Path, id: &str) -> Result<String> {
+    let id = id.strip_prefix("id:").unwrap_or(id);
+    let (index, f) = open(dir, false)?;
+    let searcher = index.reader()?.searcher();
+    let query = tantivy::query::TermQuery::new(
+        Term::from_field_text(f.id, id),
+        tantivy::schema::IndexRecordOption::Basic,
+    );
+    let top = searcher.search(&query, &TopDocs::with_limit(1))?;
+    let Some((_, addr)) = top.first() else {
+        anyhow::bail!("{} is not in the index (run `mu index update`)", id);
+    };
+    let doc: TantivyDocument = searcher.doc(*addr)?;
+    Ok(doc
+        .get_first(f.path)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string())
+}
+
+/// `YYYY-MM-DD` for listings
+fn format_date(ts: i64) -> String {
+    DateTime::from_timestamp_secs(ts)
+        .into_utc()
+        .date()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, id: &str, subject: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let raw = format!(
+            "Message-ID: <{}>\r\nFrom: Alice <a@x.org>\r\nSubject: {}\r\n\
+Date: Mon, 2 Feb 2026 10:00:00 +0000\r\n\r\nquarterly numbers attached\r\n",
+            id, subject
+        );
+        std::fs::write(path, raw).unwrap();
+    }
+
+    #[test]
+    fn test_update_and_search() {
+        let base = std::env::temp_dir().join(format!("mu-index-{}", std::process::id()));
+        let (root, dir) = (base.join("Mail"), base.join("index"));
+        write(&root.join("INBOX/cur/1:2,S"), "one@x", "Report");
+        write(&root.join("Archive/.Old/new/2"), "two@x", "Lunch");
+
+        assert_eq!(update(&dir, &root, false).unwrap(), (2, 0));
+        assert_eq!(update(&dir, &root, false).unwrap(), (0, 0));
+
+        std::fs::remove_file(root.join("INBOX/cur/1:2,S")).unwrap();
+        assert_eq!(update(&dir, &root, false).unwrap(), (0, 1));
+
+        let (index, f) = open(&dir, false).unwrap();
+        let parser = QueryParser::for_index(&index, vec![f.subject, f.body]);
+        let searcher = index.reader().unwrap().searcher();
+        let hits = searcher
+            .search(
+                &parser.parse_query("lunch").unwrap(),
+                &TopDocs::with_limit(5),
+            )
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(1_770_026_400), "2026-02-02");
+    }
+}
@@ -0,0 +1,507 @@
+//! JMAP (RFC 8620/8621) client: Maildir download for `mu sync`, live search for `mu fzf`
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Jmap};
+use crate::{http, maildir, notmuch};
+
+const USING: [&str; 2] = ["urn:ietf:params:jmap:core", "urn:ietf:params:jmap:mail"];
+
+/// Properties fetched for each downloaded email
+const PROPERTIES: [&str; 5] = ["id", "blobId", "mailboxIds", "keywords", "messageId"];
+
+/// Keywords that map to Maildir flags (notmuch turns those into tags itself)
+const FLAG_KEYWORDS: [(&str, char); 4] = [
+    ("$draft", 'D'),
+    ("$flagged", 'F'),
+    ("$answered", 'R'),
+    ("$seen", 'S'),
+];
+
+/// An authenticated session
+struct Client {
+    api_url: String,
+    download_url: String,
+    account: String,
+    token: String,
+}
+
+/// Local copy of one email
+#[derive(Default, Serialize, Deserialize)]
+struct Local {
+    path: PathBuf,
+    tags: Vec<String>,
+}
+
+/// Sync state (`~/.local/share/mu/jmap.json`)
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    /// Email state the local copy reflects
+    email_state: Option<String>,
+    emails: BTreeMap<String, Local>,
+    /// Tag batch lines waiting for the messages to be indexed
+    pending_tags: Vec<String>,
+}
+
+/// What one sync did
+#[derive(Default)]
+pub struct Fetched {
+    pub new: usize,
+    pub deleted: usize,
+    pub updated: usize,
+}
+
+impl Client {
+    fn connect(cfg: &Jmap) -> Result<Self> {
+        let command = cfg
+            .token_command
+            .as_deref()
+            .context("No JMAP token (set [jmap] token_command in config.toml)")?;
+        let token = config::command_output(command)?;
+        let response = http::Request::new("GET", &cfg.session_url)
+            .header(&format!("Authorization: Bearer {}", token))
+            .send()?;
+        if !response.is_success() {
+            anyhow::bail!("JMAP session failed ({})", response.status);
+        }
+        let session: Value = serde_json::from_slice(&response.body)?;
+        let text = |v: &Value| v.as_str().map(String::from);
+        Ok(Self {
+            api_url: text(&session["apiUrl"]).context("Session has no apiUrl")?,
+            download_url: text(&session["downloadUrl"]).context("Session has no downloadUrl")?,
+            account: text(&session["primaryAccounts"][USING[1]])
+                .context("No mail account in the session")?,
+            token,
+        })
+    }
+
+    /// Run method calls; returns each response's arguments in order
+    fn call(&self, calls: Vec<(&str, Value)>) -> Result<Vec<Value>> {
+        let method_calls: Vec<Value> = calls
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, mut args))| {
+                args["accountId"] = json!(self.account);
+                json!([name, args, format!("c{}", i)])
+            })
+            .collect();
+        let request = json!({ "using": USING, "methodCalls": method_calls });
+        let response = http::Request::new("POST", &self.api_url)
+            .header(&format!("Authorization: Bearer {}", self.token))
+            .header("Content-Type: application/json")
+            .body(&request.to_string())
+            .send()?;
+        if !response.is_success() {
+            anyhow::bail!(
+                "JMAP request failed ({}): {}",
+                response.status,
+                response.text()
+            );
+        }
+        let reply: Value = serde_json::from_slice(&response.body)?;
+        let mut results = Vec::new();
+        for r in reply["methodResponses"].as_array().into_iter().flatten() {
+            if r[0] == "error" {
+                anyhow::bail!("JMAP error: {}", r[1]["type"].as_str().unwrap_or("unknown"));
+            }
+            results.push(r[1].clone());
+        }
+        Ok(results)
+    }
+
+    /// Raw RFC 5322 bytes of a blob
+    fn download(&self, blob: &str) -> Result<Vec<u8>> {
+        let url = self
+            .download_url
+            .replace("{accountId}", &self.account)
+            .replace("{blobId}", blob)
+            .replace("{name}", "message.eml")
+            .replace("{type}", "message%2Frfc822");
+        let response = http::Request::new("GET", &url)
+            .header(&format!("Authorization: Bearer {}", self.token))
+            .send()?;
+        if !response.is_success() {
+            anyhow::bail!("Download of {} failed ({})", blob, response.status);
+        }
+        Ok(response.body)
+    }
+
+    fn get_emails(&self, ids: &[String], properties: &[&str]) -> Result<Vec<Value>> {
+        let mut emails = Vec::new();
+        for chunk in ids.chunks(100) {
+            let reply = self.call(vec![(
+                "Email/get",
+                json!({ "ids": chunk, "properties": properties }),
+            )])?;
+            emails.extend(reply[0]["list"].as_array().cloned().unwrap_or_default());
+        }
+        Ok(emails)
+    }
+}
+
+/// Download new mail, apply changes and deletions (tags wait for [`apply_tags`])
+pub fn fetch(cfg: &Jmap) -> Result<Fetched> {
+    let client = Client::connect(cfg)?;
+    let root = config::expand_tilde(&cfg.maildir);
+    let mut state = load()?;
+    let folders = folder_paths(
+        client.call(vec![("Mailbox/get", json!({ "ids": null }))])?[0]["list"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+    );
+
+    let (created, updated, destroyed, new_state) = changes(&client, state.email_state.as_deref())?;
+    let mut fetched = Fetched::default();
+    for id in destroyed {
+        if let Some(local) = state.emails.remove(&id) {
+            let _ = std::fs::remove_file(&local.path);
+            fetched.deleted += 1;
+        }
+    }
+    for email in client.get_emails(&created, &PROPERTIES)? {
+        let (folder, flags, tags) = placement(&email, &folders);
+        let raw = client.download(email["blobId"].as_str().unwrap_or(""))?;
+        let path = maildir::deliver(&root.join(&folder), &raw, Some(&flags))?;
+        state.pending_tags.extend(tag_line(&email, &[], &tags));
+        state.emails.insert(id_of(&email), Local { path, tags });
+        fetched.new += 1;
+    }
+    for email in client.get_emails(&updated, &PROPERTIES)? {
+        let Some(local) = state.emails.get_mut(&id_of(&email)) else {
+            continue;
+        };
+        let (folder, flags, tags) = placement(&email, &folders);
+        local.path = relocate(&local.path, &root.join(&folder), &flags)?;
+        state
+            .pending_tags
+            .extend(tag_line(&email, &local.tags, &tags));
+        local.tags = tags;
+        fetched.updated += 1;
+    }
+
+    state.email_state = Some(new_state);
+    save(&state)?;
+    Ok(fetched)
+}
+
+/// Tag the downloaded messages after `notmuch new` has indexed them
+pub fn apply_tags() -> Result<()> {
+    let mut state = load()?;
+    if state.pending_tags.is_empty() {
+        return Ok(());
+    }
+    notmuch::tag_batch(&state.pending_tags)?;
+    state.pending_tags.clear();
+    save(&state)
+}
+
+type Changes = (Vec<String>, Vec<String>, Vec<String>, String);
+
+/// (created, updated, destroyed, new state) since the last sync; everything on the first
+fn changes(client: &Client, since: Option<&str>) -> Result<Changes> {
+    let ids = |v: &Value| -> Vec<String> {
+        v.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.as_str().map(String::from))
+            .collect()
+    };
+    let Some(since) = since else {
+        let state = client.call(vec![("Email/get", json!({ "ids": [] }))])?[0]["state"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let mut all = Vec::new();
+        loop {
+            let reply = client.call(vec![(
+                "Email/query",
+                json!({ "position": all.len(), "limit": 1000 }),
+            )])?;
+            let page = ids(&reply[0]["ids"]);
+            if page.is_empty() {
+                break;
+            }
+            all.extend(page);
+        }
+        return Ok((all, Vec::new(), Vec::new(), state));
+    };
+
+    let (mut created, mut updated, mut destroyed) = (Vec::new(), Vec::new(), Vec::new());
+    let mut state = since.to_string();
+    loop {
+        let reply = client.call(vec![(
+            "Email/changes",
+            json!({ "sinceState": state, "maxChanges": 500 }),
+        )])?;
+        created.extend(ids(&reply[0]["created"]));
+        updated.extend(ids(&reply[0]["updated"]));
+        destroyed.extend(ids(&reply[0]["destroyed"]));
+        state = reply[0]["newState"].as_str().unwrap_or(&state).to_string();
+        if reply[0]["hasMoreChanges"] != true {
+            return Ok((created, updated, destroyed, state));
+        }
+    }
+}
+
+/// Folder path per mailbox id: parents joined with `/`, the inbox role as `INBOX`
+fn folder_paths(mailboxes: &[Value]) -> HashMap<String, String> {
+    let by_id: HashMap<&str, &Value> = mailboxes
+        .iter()
+        .filter_map(|m| Some((m["id"].as_str()?, m)))
+        .collect();
+    by_id
+        .iter()
+        .map(|(id, m)| (id.to_string(), folder_path(m, &by_id)))
+        .collect()
+}
+
+fn folder_path<'a>(mut mailbox: &'a Value, by_id: &HashMap<&str, &'a Value>) -> String {
+    if mailbox["role"] == "inbox" {
+        return "INBOX".to_string();
+    }
+    let name = |m: &Value| m["name"].as_str().unwrap_or("").replace('/', "-");
+    let mut names = vec![name(mailbox)];
+    // Bounded, in case a broken server reports a parent cycle
+    while let Some(parent) = mailbox["parentId"].as_str().and_then(|p| by_id.get(p))
+        && names.len() < 16
+    {
+        names.push(name(parent));
+        mailbox = parent;
+    }
+    names.reverse();
+    names.join("/")
+}
+
+/// Folder (the inbox if it's in it, else the first by name), Maildir flags and tags
+fn placement(email: &Value, folders: &HashMap<String, String>) -> (String, String, Vec<String>) {
+    let mut paths: Vec<&str> = email["mailboxIds"]
+        .as_object()
+        .into_iter()
+        .flat_map(|m| m.keys())
+        .filter_map(|id| folders.get(id).map(String::as_str))
+        .collect();
+    paths.sort_by_key(|p| (*p != "INBOX", *p));
+    let folder = paths.first().copied().unwrap_or("INBOX").to_string();
+
+    let keywords: Vec<&str> = email["keywords"]
+        .as_object()
+        .into_iter()
+        .flat_map(|k| k.keys().map(String::as_str))
+        .collect();
+    let flags: String = FLAG_KEYWORDS
+        .iter()
+        .filter(|(k, _)| keywords.contains(k))
+        .map(|(_, f)| *f)
+        .collect();
+    let mut tags: Vec<String> = paths
+        .iter()
+        .skip(1)
+        .map(|p| p.to_lowercase())
+        .chain(
+            keywords
+                .iter()
+                .filter(|k| !k.starts_with('$'))
+                .map(|k| k.to_lowercase()),
+        )
+        .collect();
+    tags.sort();
+    (folder, flags, tags)
+}
+
+fn id_of(email: &Value) -> String {
+    email["id"].as_str().unwrap_or("").to_string()
+}
+
+/// `notmuch tag --batch` line moving from the old to the new tag set
+fn tag_line(email: &Value, old: &[String], new: &[String]) -> Option<String> {
+    let message_id = email["messageId"][0].as_str()?;
+    let changes: Vec<String> = old
+        .iter()
+        .filter(|t| !new.contains(t))
+        .map(|t| format!("-{}", encode_tag(t)))
+        .chain(
+            new.iter()
+                .filter(|t| !old.contains(t))
+                .map(|t| format!("+{}", encode_tag(t))),
+        )
+        .collect();
+    (!changes.is_empty()).then(|| {
+        format!(
+            "{} -- id:\"{}\"",
+            changes.join(" "),
+            message_id.replace('"', "\"\"")
+        )
+    })
+}
+
+/// Batch-format tag encoding (spaces and specials as %XX)
+fn encode_tag(tag: &str) -> String {
+    tag.bytes()
+        .map(|b| match b {
+            b' ' | b'%' | b'"' | b'(' | b')' => format!("%{:02x}", b),
+            _ => (b as char).to_string(),
+        })
+        .collect()
+}
+
+/// Move a message file into `folder`'s cur/ with the given flags
+fn relocate(path: &Path, folder: &Path, flags: &str) -> Result<PathBuf> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let base = name.split(":2,").next().unwrap_or(name);
+    let dest = folder.join("cur").join(format!("{}:2,{}", base, flags));
+    if dest != path {
+        std::fs::create_dir_all(folder.join("cur"))?;
+        std::fs::rename(path, &dest)
+            .with_context(|| format!("Failed to move {}", path.display()))?;
+    }
+    Ok(dest)
+}
+
+/// fzf listing lines (`jmap:<id>  date  from; subject`), straight from the server
+pub fn listing(cfg: &Jmap, query: &str) -> Result<Vec<String>> {
+    let client = Client::connect(cfg)?;
+    let filter = if query == "*" {
+        json!(null)
+    } else {
+        json!({ "text": query })
+    };
+    let reply = client.call(vec![
+        (
+            "Email/query",
+            json!({ "filter": filter, "sort": [{ "property": "receivedAt", "isAscending": false }], "limit": 500 }),
+        ),
+        (
+            "Email/get",
+            json!({
+                "#ids": { "resultOf": "c0", "name": "Email/query", "path": "/ids" },
+                "properties": ["id", "from", "subject", "receivedAt"],
+            }),
+        ),
+    ])?;
+    Ok(reply
+        .get(1)
+        .and_then(|r| r["list"].as_array())
+        .into_iter()
+        .flatten()
+        .map(listing_line)
+        .collect())
+}
+
+fn listing_line(email: &Value) -> String {
+    let from = &email["from"][0];
+    format!(
+        "jmap:{}  {}  {}; {}",
+        email["id"].as_str().unwrap_or(""),
+        email["receivedAt"]
+            .as_str()
+            .unwrap_or("")
+            .get(..10)
+            .unwrap_or(""),
+        from["name"]
+            .as_str()
+            .or(from["email"].as_str())
+            .unwrap_or(""),
+        email["subject"].as_str().unwrap_or("")
+    )
+}
+
+/// Raw message and Message-ID of a `jmap:<id>` listing entry
+pub fn message(cfg: &Jmap, id: &str) -> Result<(Vec<u8>, String)> {
+    let client = Client::connect(cfg)?;
+    let id = id.strip_prefix("jmap:").unwrap_or(id).to_string();
+    let email = client
+        .get_emails(std::slice::from_ref(&id), &["blobId", "messageId"])?
+        .pop()
+        .with_context(|| format!("No email {} on the server", id))?;
+    let raw = client.download(email["blobId"].as_str().unwrap_or(""))?;
+    Ok((
+        raw,
+        email["messageId"][0].as_str().unwrap_or("").to_string(),
+    ))
+}
+
+fn state_path() -> PathBuf {
+    config::data_dir().join("jmap.json")
+}
+
+fn load() -> Result<State> {
+    match std::fs::read_to_string(state_path()) {
+        Ok(text) => serde_json::from_str(&text).context("Invalid JMAP state"),
+        Err(_) => Ok(State::default()),
+    }
+}
+
+fn save(state: &State) -> Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(state)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailboxes() -> HashMap<String, String> {
+        folder_paths(&[
+            json!({ "id": "m1", "name": "Inbox", "role": "inbox" }),
+            json!({ "id": "m2", "name": "Work" }),
+            json!({ "id": "m3", "name": "Clients", "parentId": "m2" }),
+        ])
+    }
+
+    #[test]
+    fn test_folder_paths() {
+        let folders = mailboxes();
+        assert_eq!(folders["m1"], "INBOX");
+        assert_eq!(folders["m3"], "Work/Clients");
+    }
+
+    #[test]
+    fn test_placement_and_tags() {
+        let email = json!({
+            "id": "e1",
+            "messageId": ["a@x"],
+            "mailboxIds": { "m3": true, "m1": true },
+            "keywords": { "$seen": true, "$flagged": true, "Invoices": true },
+        });
+        let (folder, flags, tags) = placement(&email, &mailboxes());
+        assert_eq!((folder.as_str(), flags.as_str()), ("INBOX", "FS"));
+        assert_eq!(tags, ["invoices", "work/clients"]);
+        assert_eq!(
+            tag_line(&email, &["old label".into(), "invoices".into()], &tags).unwrap(),
+            "-old%20label +work/clients -- id:\"a@x\""
+        );
+        assert_eq!(tag_line(&email, &tags, &tags), None);
+    }
+
+    #[test]
+    fn test_relocate() {
+        let dir = std::env::temp_dir().join(format!("mu-jmap-{}", std::process::id()));
+        let path = maildir::deliver(&dir.join("INBOX"), b"Subject: x\r\n\r\n", Some("S")).unwrap();
+        let moved = relocate(&path, &dir.join("Archive"), "FS").unwrap();
+        assert!(
+            moved.starts_with(dir.join("Archive/cur"))
+                && moved.to_string_lossy().ends_with(":2,FS")
+        );
+        assert!(!path.exists() && moved.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_listing_line() {
+        let email = json!({
+            "id": "M1", "subject": "Hi", "receivedAt": "2026-02-03T10:00:00Z",
+            "from": [{ "name": null, "email": "a@x" }],
+        });
+        assert_eq!(listing_line(&email), "jmap:M1  2026-02-03  a@x; Hi");
+    }
+}
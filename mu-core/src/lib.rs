@@ -0,0 +1,78 @@
+//! Building blocks behind the `mu` mail tool, usable from other Rust programs
+//!
+//! - [`render`] turns HTML mail into terminal text with w3m (or the built-in converter,
+//!   feature `html`), or into markdown, plain text or sanitized HTML ([`page`]: a standalone
+//!   page for the browser); [`ics`] calendar invites,
+//!   [`patch`] diffs; [`stream`] does the same from a reader to a
+//!   writer in bounded memory
+//! - [`mime`] parses and inspects messages, [`mbox`] and [`maildir`] read and write mail stores
+//! - [`notmuch`] runs searches and tagging (locally or over SSH via [`remote`]);
+//!   `index` is a tantivy full-text index for setups without notmuch (feature `index`)
+//! - `jmap` downloads a JMAP account into Maildir (feature `jmap`), [`import`] brings mbox
+//!   files in
+//! - [`config`] reads `~/.config/mu/config.toml` ([`schema`] checks it with precise locations),
+//!   [`http`] is the curl-based client the rest uses
+//! - [`error`] classifies failures (missing tools, bad config, failed commands) with exit codes
+//! - [`process`] runs subprocesses concurrently, with timeouts (tokio); [`runner`] replays
+//!   recorded command outputs instead (`--simulate`) or scripts them in tests
+//! - [`color`] decides whether stdout and stderr get ANSI colors, [`theme`] which ones;
+//!   [`sanitize`] takes scripts and other active content out of HTML for browsers, [`spoof`]
+//!   spots deceptive links and lookalike domains
+//! - [`width`] measures and cuts text by terminal columns (wide characters, emoji)
+//! - [`i18n`] translates notification and summary strings and formats dates per locale
+//!
+//! ```
+//! let text = mu_core::render::plain_text("<p>Hello <b>world</b></p>").unwrap();
+//! assert!(text.contains("Hello") && text.contains("world"));
+//!
+//! let message = mu_core::mime::parse(b"Subject: =?utf-8?q?Caf=C3=A9?=\r\n\r\nhi\r\n").unwrap();
+//! assert_eq!(message.subject(), Some("Café"));
+//! ```
+
+mod anchor;
+pub mod bidi;
+mod cleanup;
+pub mod color;
+mod colorize;
+pub mod config;
+pub mod decrypt;
+pub mod error;
+mod footer;
+mod header;
+pub mod hook;
+mod html;
+pub mod http;
+pub mod i18n;
+pub mod ics;
+pub mod import;
+#[cfg(feature = "index")]
+pub mod index;
+#[cfg(feature = "jmap")]
+pub mod jmap;
+mod lines;
+pub mod maildir;
+#[cfg(feature = "html")]
+mod markdown;
+pub mod mbox;
+mod message;
+pub mod mime;
+pub mod notmuch;
+pub mod page;
+pub mod patch;
+pub mod process;
+mod quote;
+mod reader;
+pub mod remote;
+pub mod render;
+pub mod renderer;
+pub mod runner;
+pub mod sanitize;
+pub mod schema;
+mod signature;
+mod sniff;
+pub mod spoof;
+pub mod stream;
+pub mod theme;
+pub mod tracker;
+pub mod vcard;
+pub mod width;
@@ -0,0 +1,174 @@
+//! Filters over rendered text a line at a time, holding back what they can't settle yet, so
+//! whole documents and streamed output get the same result
+
+use std::io::{self, Write};
+
+/// Takes lines (with their line endings) and hands back the ones settled
+pub(crate) trait LineFilter {
+    /// Take `line`; the text now settled
+    fn push(&mut self, line: String) -> String;
+
+    /// The text held back, at the end of the input
+    fn finish(self) -> String;
+}
+
+/// `text` through `filter`, line by line
+pub(crate) fn feed(filter: &mut impl LineFilter, text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| filter.push(line.to_string()))
+        .collect()
+}
+
+/// A whole text through `filter`
+pub(crate) fn apply(mut filter: impl LineFilter, text: &str) -> String {
+    let mut out = feed(&mut filter, text);
+    out.push_str(&filter.finish());
+    out
+}
+
+/// The first filter's output through the second
+impl<A: LineFilter, B: LineFilter> LineFilter for (A, B) {
+    fn push(&mut self, line: String) -> String {
+        let settled = self.0.push(line);
+        feed(&mut self.1, &settled)
+    }
+
+    fn finish(self) -> String {
+        let (first, mut second) = self;
+        let mut out = feed(&mut second, &first.finish());
+        out.push_str(&second.finish());
+        out
+    }
+}
+
+/// A writer passing whole lines through a [`LineFilter`]; [`Filtered::finish`] writes the end
+pub(crate) struct Filtered<W: Write, F: LineFilter> {
+    inner: W,
+    filter: F,
+    partial: Vec<u8>,
+}
+
+impl<W: Write, F: LineFilter> Filtered<W, F> {
+    pub(crate) fn new(inner: W, filter: F) -> Self {
+        Self {
+            inner,
+            filter,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Write what's held back
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let mut out = String::new();
+        if !self.partial.is_empty() {
+            let last = String::from_utf8_lossy(&self.partial).into_owned();
+            out.push_str(&self.filter.push(last));
+        }
+        out.push_str(&self.filter.finish());
+        self.inner.write_all(out.as_bytes())?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write, F: LineFilter> Write for Filtered<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        if let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') {
+            let lines: Vec<u8> = self.partial.drain(..=end).collect();
+            let out = feed(&mut self.filter, &String::from_utf8_lossy(&lines));
+            self.inner.write_all(out.as_bytes())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Joins each paragraph onto one line, holding it until the blank line that ends it
+    #[derive(Default)]
+    struct Paragraphs(Vec<String>);
+
+    impl LineFilter for Paragraphs {
+        fn push(&mut self, line: String) -> String {
+            if !line.trim().is_empty() {
+                self.0.push(line.trim().to_string());
+                return String::new();
+            }
+            let paragraph = std::mem::take(&mut self.0);
+            match paragraph.is_empty() {
+                true => line,
+                false => format!("{}\n{}", paragraph.join(" "), line),
+            }
+        }
+
+        fn finish(self) -> String {
+            match self.0.is_empty() {
+                true => String::new(),
+                false => format!("{}\n", self.0.join(" ")),
+            }
+        }
+    }
+
+    /// Upper-cases every line as it comes
+    struct Upper;
+
+    impl LineFilter for Upper {
+        fn push(&mut self, line: String) -> String {
+            line.to_uppercase()
+        }
+
+        fn finish(self) -> String {
+            String::new()
+        }
+    }
+
+    const TEXT: &str = "one\ntwo\n\nthree\nfour";
+
+    #[test]
+    fn test_apply_settles_what_was_held() {
+        let mut filter = Paragraphs::default();
+        assert_eq!(feed(&mut filter, "one\ntwo\n"), "");
+        assert_eq!(feed(&mut filter, "\n"), "one two\n\n");
+        assert_eq!(
+            apply(Paragraphs::default(), TEXT),
+            "one two\n\nthree four\n"
+        );
+        assert_eq!(
+            apply((Paragraphs::default(), Upper), TEXT),
+            "ONE TWO\n\nTHREE FOUR\n"
+        );
+    }
+
+    #[test]
+    fn test_filtered_matches_apply_however_it_is_written() {
+        let whole = apply((Paragraphs::default(), Upper), TEXT);
+        for size in 1..=TEXT.len() {
+            let mut writer = Filtered::new(Vec::new(), (Paragraphs::default(), Upper));
+            for chunk in TEXT.as_bytes().chunks(size) {
+                writer.write_all(chunk).unwrap();
+            }
+            let written = writer.finish().unwrap();
+            assert_eq!(
+                String::from_utf8(written).unwrap(),
+                whole,
+                "chunks of {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_filtered_writes_settled_lines_at_once() {
+        let mut writer = Filtered::new(Vec::new(), Upper);
+        writer.write_all(b"a\nb").unwrap();
+        assert_eq!(writer.inner, b"A\n");
+        writer.write_all(b"c\n").unwrap();
+        assert_eq!(writer.inner, b"A\nBC\n");
+    }
+}
@@ -0,0 +1,154 @@
+//! Maildir delivery and flags
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Deliveries made by this process (keeps names unique within a microsecond)
+static DELIVERIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Write to tmp/, then rename into new/ (or into cur/ with `:2,<flags>` when flags are given)
+pub fn deliver(folder: &Path, raw: &[u8], flags: Option<&str>) -> Result<PathBuf> {
+    for sub in ["tmp", "new", "cur"] {
+        std::fs::create_dir_all(folder.join(sub))
+            .with_context(|| format!("Failed to create {}", folder.join(sub).display()))?;
+    }
+
+    let name = unique_name();
+    let tmp = folder.join("tmp").join(&name);
+    let mut file = std::fs::File::create(&tmp)
+        .with_context(|| format!("Failed to create {}", tmp.display()))?;
+    file.write_all(raw)?;
+    file.sync_all()?;
+
+    let dest = match flags {
+        Some(flags) => folder
+            .join("cur")
+            .join(format!("{}:2,{}", name, normalize_flags(flags))),
+        None => folder.join("new").join(&name),
+    };
+    std::fs::rename(&tmp, &dest)
+        .with_context(|| format!("Failed to deliver to {}", dest.display()))?;
+    Ok(dest)
+}
+
+/// Message files in every `cur/` and `new/` below the root
+pub fn files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let entries =
+        std::fs::read_dir(root).with_context(|| format!("Failed to read {}", root.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        match entry.file_name().to_str() {
+            Some("cur" | "new") => {
+                let messages = std::fs::read_dir(&path)?.flatten().map(|e| e.path());
+                found.extend(messages.filter(|p| p.is_file()));
+            }
+            Some("tmp") => {}
+            _ => found.extend(files(&path)?),
+        }
+    }
+    Ok(found)
+}
+
+/// Maildir flags from a file name (`...:2,FS` → `FS`)
+pub fn flags(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(|n| n.rsplit_once(":2,"))
+        .map_or("", |(_, flags)| flags)
+}
+
+/// Rename a message file to carry exactly `flags`, moving it from new/ to cur/ (where
+/// flagged files live); the new path
+pub fn set_flags(path: &Path, flags: &str) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .with_context(|| format!("Not a maildir file: {}", path.display()))?;
+    let base = name.rsplit_once(":2,").map_or(name, |(base, _)| base);
+    let folder = path
+        .parent()
+        .and_then(Path::parent)
+        .with_context(|| format!("Not in a maildir folder: {}", path.display()))?;
+    let dest = folder
+        .join("cur")
+        .join(format!("{}:2,{}", base, normalize_flags(flags)));
+    if dest != path {
+        std::fs::rename(path, &dest)
+            .with_context(|| format!("Failed to rename {}", path.display()))?;
+    }
+    Ok(dest)
+}
+
+/// Maildir flags must be unique and in ASCII order (`FRS`, not `SRF`); lowercase letters are
+/// keywords (Dovecot's, say) and are kept after the standard ones
+fn normalize_flags(flags: &str) -> String {
+    let mut chars: Vec<char> = flags.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    chars.sort_unstable();
+    chars.dedup();
+    chars.into_iter().collect()
+}
+
+/// `<secs>.M<micros>P<pid>Q<n>.<host>` as recommended for Maildir
+fn unique_name() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let host = std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|h| h.trim().replace(['/', ':'], "_"))
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "localhost".to_string());
+    format!(
+        "{}.M{}P{}Q{}.{}",
+        now.as_secs(),
+        now.subsec_micros(),
+        std::process::id(),
+        DELIVERIES.fetch_add(1, Ordering::Relaxed),
+        host
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deliver() {
+        let dir = std::env::temp_dir().join(format!("mu-maildir-{}", std::process::id()));
+        let new = deliver(&dir.join("INBOX"), b"Subject: a\r\n\r\n", None).unwrap();
+        assert!(new.starts_with(dir.join("INBOX").join("new")));
+        assert_eq!(std::fs::read(&new).unwrap(), b"Subject: a\r\n\r\n");
+
+        let cur = deliver(&dir.join("INBOX"), b"Subject: b\r\n\r\n", Some("SFS")).unwrap();
+        assert!(cur.starts_with(dir.join("INBOX").join("cur")));
+        assert!(cur.to_string_lossy().ends_with(":2,FS"));
+        assert_ne!(new.file_name(), cur.file_name());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(flags(Path::new("/m/cur/1.x,U=5:2,FS")), "FS");
+        assert_eq!(flags(Path::new("/m/new/1.x")), "");
+
+        let dir = std::env::temp_dir().join(format!("mu-flags-{}", std::process::id()));
+        let new = deliver(&dir, b"Subject: a\r\n\r\n", None).unwrap();
+        let seen = set_flags(&new, "S").unwrap();
+        assert_eq!(seen.parent(), Some(dir.join("cur").as_path()));
+        assert!(!new.exists());
+        let both = set_flags(&seen, "SF").unwrap();
+        assert_eq!(flags(&both), "FS");
+        assert_eq!(set_flags(&both, "FS").unwrap(), both);
+        let keywords = set_flags(&both, "bSaF,b").unwrap();
+        assert_eq!(flags(&keywords), "FSab");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
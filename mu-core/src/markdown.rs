@@ -0,0 +1,145 @@
+//! Cleanup for the built-in HTML converter's markdown (feature `html`)
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use crate::cleanup::{Lines, re, replace_all, strip_invisible};
+
+static LONG_BARE_URL: LazyLock<Regex> = LazyLock::new(|| re(r"https?://[^\s\)\]]{40,}"));
+static ANGLE_URL: LazyLock<Regex> = LazyLock::new(|| re(r"<https?://[^>]+>"));
+static LINK: LazyLock<Regex> = LazyLock::new(|| re(r"\[([^\]]+)\]\(https?://[^)]+\)"));
+static MAILTO_LINK: LazyLock<Regex> = LazyLock::new(|| re(r"\[([^\]]+)\]\(mailto:[^)]+\)"));
+static LIST_SINGLE_CELL: LazyLock<Regex> = LazyLock::new(|| re(r"^(-\s*)\|\s*([^|]+?)\s*\|$"));
+static SINGLE_CELL: LazyLock<Regex> = LazyLock::new(|| re(r"^\|\s*([^|]+?)\s*\|$"));
+static EMPTY_CELLS: LazyLock<Regex> = LazyLock::new(|| re(r"\|[ \t]*\|"));
+static EMPTY_TABLE_LINE: LazyLock<Regex> = LazyLock::new(|| re(r"^-?\s*\|\s*\|?\s*$"));
+static TABLE_SEPARATOR: LazyLock<Regex> = LazyLock::new(|| re(r"^\|\s*[-:]+\s*\|"));
+
+/// Clean html-to-markdown output: frontmatter and `---` rules, links (if asked),
+/// single-cell and empty tables, repeated table separators
+pub(crate) fn clean(md: &str, strip_urls: bool) -> String {
+    let mut lines = Lines::default();
+    let mut tables = Separators::default();
+    let frontmatter = frontmatter(md);
+    for (i, line) in md.lines().enumerate() {
+        let line = match frontmatter {
+            Some((start, end)) if (start..end).contains(&i) => continue,
+            // The closing rule is `---` at the start of a line; anything after it stays
+            Some((_, end)) if i == end => match &line[3..] {
+                "" => continue,
+                rest => rest,
+            },
+            _ => line,
+        };
+        let Some(line) = markdown_line(line, strip_urls) else {
+            continue;
+        };
+        if tables.keep(&line) {
+            lines.push(line);
+        }
+    }
+    lines.finish().join("\n")
+}
+
+/// One markdown line through every rewrite; None drops it
+fn markdown_line(line: &str, strip_urls: bool) -> Option<Cow<'_, str>> {
+    if line == "---" {
+        return None;
+    }
+    let mut line = Cow::Borrowed(line);
+    if strip_urls {
+        // [text](url) → text, <url> and long bare URLs removed, [text](mailto:…) → text
+        line = replace_all(line, &LINK, "$1");
+        line = replace_all(line, &ANGLE_URL, "");
+        line = replace_all(line, &LONG_BARE_URL, "");
+        line = replace_all(line, &MAILTO_LINK, "$1");
+    }
+    // "- | text |" → "- **text**", "| text |" → "**text**" (empty for separator rows)
+    if let Some(caps) = LIST_SINGLE_CELL.captures(&line) {
+        line = Cow::Owned(bold_cell(&caps[1], &caps[2]));
+    } else if let Some(caps) = SINGLE_CELL.captures(&line) {
+        line = Cow::Owned(bold_cell("", &caps[1]));
+    }
+    let line = replace_all(line, &EMPTY_CELLS, "|");
+    if EMPTY_TABLE_LINE.is_match(&line) {
+        return None;
+    }
+    Some(strip_invisible(line))
+}
+
+fn bold_cell(prefix: &str, text: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() || text.chars().all(|c| c == '-' || c == ' ') {
+        String::new()
+    } else {
+        format!("{}**{}**", prefix, text)
+    }
+}
+
+/// Line numbers of the first YAML frontmatter block: a `---` line, at least one line,
+/// then a line starting with `---`
+fn frontmatter(md: &str) -> Option<(usize, usize)> {
+    let mut open = None;
+    for (i, line) in md.lines().enumerate() {
+        match open {
+            Some(start) if i >= start + 2 && line.starts_with("---") => return Some((start, i)),
+            None if line == "---" => open = Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Drops a table's separator rows after its first
+#[derive(Default)]
+struct Separators {
+    in_table: bool,
+    had_separator: bool,
+}
+
+impl Separators {
+    fn keep(&mut self, line: &str) -> bool {
+        if !(line.starts_with('|') && line.ends_with('|')) {
+            *self = Self::default();
+            return true;
+        }
+        let separator =
+            TABLE_SEPARATOR.is_match(line) && line.chars().filter(|c| *c == '-').count() > 2;
+        let repeated = separator && self.in_table && self.had_separator;
+        self.had_separator |= separator;
+        self.in_table = true;
+        !repeated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean() {
+        let md = "---\ntitle: x\n---\n\n[Read more](https://example.com/x) or [mail](mailto:a@b.c)\n\n- | Item |\n| Note |\n| --- |\n| a | | b |\n| |\n\n---\n\n\nend <https://example.com/>";
+        assert_eq!(
+            clean(md, true),
+            "Read more or mail\n\n- **Item**\n**Note**\n\n| a | b |\n\nend"
+        );
+    }
+
+    #[test]
+    fn test_markdown_tables_keep_rows() {
+        let md = "| A | B |\n| --- | --- |\n| 1 | 2 |\n| --- | --- |\n| 3 | 4 |";
+        assert_eq!(
+            clean(md, true),
+            "| A | B |\n| --- | --- |\n| 1 | 2 |\n| 3 | 4 |"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_frontmatter() {
+        assert_eq!(clean("---\nno closing rule", false), "no closing rule");
+        assert_eq!(clean("text\n---\nmore", false), "text\nmore");
+        assert_eq!(clean("---\n---\nkey: v\n---x\nbody", false), "x\nbody");
+        assert_eq!(clean("---\n---\n---\nbody", false), "body");
+    }
+}
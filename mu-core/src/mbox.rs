@@ -0,0 +1,163 @@
+//! mbox splitting (mboxo/mboxrd) and Status-header flags
+
+use mail_parser::{DateTime, MessageParser};
+
+/// Split an mbox into raw messages: From_ separators removed, `>From ` unescaped
+pub fn split(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut prev_blank = true;
+
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        if prev_blank && is_separator(line) {
+            if let Some(msg) = current.take() {
+                messages.push(trim_separator_blank(msg));
+            }
+            current = Some(Vec::new());
+            prev_blank = false;
+            continue;
+        }
+        prev_blank = line.iter().all(|b| b.is_ascii_whitespace());
+        if let Some(msg) = current.as_mut() {
+            msg.extend_from_slice(unescape(line));
+        }
+    }
+    if let Some(msg) = current {
+        messages.push(trim_separator_blank(msg));
+    }
+    messages.retain(|m| !m.is_empty());
+    messages
+}
+
+/// One mboxrd entry: From_ line (dated from the Date header), `From ` lines quoted, blank line after
+pub fn entry(raw: &[u8]) -> Vec<u8> {
+    let date = MessageParser::default()
+        .parse_headers(raw)
+        .and_then(|m| m.date().map(|d| d.to_timestamp()))
+        .unwrap_or(0);
+    let mut out = format!("From MAILER-DAEMON {}\n", asctime(date)).into_bytes();
+    for line in raw.split_inclusive(|&b| b == b'\n') {
+        let quotes = line.iter().take_while(|&&b| b == b'>').count();
+        if line[quotes..].starts_with(b"From ") {
+            out.push(b'>');
+        }
+        out.extend_from_slice(line);
+    }
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    out.push(b'\n');
+    out
+}
+
+/// `Thu Jan  1 00:00:00 1970`
+fn asctime(ts: i64) -> String {
+    const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let d = DateTime::from_timestamp(ts);
+    format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {}",
+        DAYS[d.day_of_week() as usize % 7],
+        MONTHS[(d.month as usize + 11) % 12],
+        d.day,
+        d.hour,
+        d.minute,
+        d.second,
+        d.year
+    )
+}
+
+/// `From sender date` — requires a trailing year so body text starting with "From " doesn't split
+fn is_separator(line: &[u8]) -> bool {
+    let Some(rest) = line.strip_prefix(b"From ") else {
+        return false;
+    };
+    let text = String::from_utf8_lossy(rest);
+    text.split_whitespace()
+        .any(|w| w.len() == 4 && w.starts_with(['1', '2']) && w.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// mboxrd quoting: `>From ` → `From `, `>>From ` → `>From `
+fn unescape(line: &[u8]) -> &[u8] {
+    let quotes = line.iter().take_while(|&&b| b == b'>').count();
+    if quotes > 0 && line[quotes..].starts_with(b"From ") {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+/// Drop the blank line that precedes the next From_ separator
+fn trim_separator_blank(mut msg: Vec<u8>) -> Vec<u8> {
+    if msg.ends_with(b"\r\n\r\n") {
+        msg.truncate(msg.len() - 2);
+    } else if msg.ends_with(b"\n\n") {
+        msg.truncate(msg.len() - 1);
+    }
+    msg
+}
+
+/// Maildir flags from mutt/Thunderbird `Status:` and `X-Status:` headers
+pub fn flags(raw: &[u8]) -> String {
+    let Some(message) = MessageParser::default().parse_headers(raw) else {
+        return String::new();
+    };
+    let status = message.header_raw("Status").unwrap_or("");
+    let x_status = message.header_raw("X-Status").unwrap_or("");
+
+    let mut flags = String::new();
+    if status.contains('R') {
+        flags.push('S');
+    }
+    for (mbox, maildir) in [('A', 'R'), ('F', 'F'), ('D', 'T'), ('T', 'D')] {
+        if x_status.contains(mbox) {
+            flags.push(maildir);
+        }
+    }
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MBOX: &[u8] = b"From alice@example.com Mon Feb  2 10:00:00 2026\n\
+Subject: one\nStatus: RO\nX-Status: AF\n\nFrom now on, hi\n>From the start\n\n\
+From bob@example.com Tue Feb  3 11:00:00 2026\nSubject: two\n\nbye\n";
+
+    #[test]
+    fn test_split() {
+        let msgs = split(MBOX);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(
+            msgs[0],
+            b"Subject: one\nStatus: RO\nX-Status: AF\n\nFrom now on, hi\nFrom the start\n".to_vec()
+        );
+        assert_eq!(msgs[1], b"Subject: two\n\nbye\n".to_vec());
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let raw = b"Date: Tue, 2 Jan 2024 03:04:05 +0000\nSubject: x\n\nFrom here\n>From there\n";
+        let entry = entry(raw);
+        assert!(entry.starts_with(b"From MAILER-DAEMON Tue Jan  2 03:04:05 2024\n"));
+        assert_eq!(split(&entry), vec![raw.to_vec()]);
+    }
+
+    #[test]
+    fn test_flags() {
+        let msgs = split(MBOX);
+        assert_eq!(flags(&msgs[0]), "SRF");
+        assert_eq!(flags(&msgs[1]), "");
+    }
+
+    #[test]
+    fn test_is_separator() {
+        assert!(is_separator(
+            b"From MAILER-DAEMON Fri Jul  8 12:08:34 2011\n"
+        ));
+        assert!(!is_separator(b"From the desk of the CEO\n"));
+    }
+}
@@ -0,0 +1,110 @@
+//! A raw message rendered: its best part, after its header block and the verdict on its
+//! encryption or signature and its invites, before its contact cards
+
+use anyhow::Result;
+
+use crate::render::{ContentType, Format, Options, convert, no_text, render_content};
+use crate::{decrypt, header, ics, mime, vcard};
+
+/// A raw message's best part, after its headers if asked, the verdict on its encryption or
+/// signature and its invites, before its contacts
+pub(crate) fn render(raw: &[u8], options: &Options) -> Result<String> {
+    let format = options.format;
+    let headers = if options.headers {
+        let block = header::block(&mime::parse(raw)?);
+        Some(convert(block, ContentType::Plain, format))
+    } else {
+        None
+    };
+    let (raw, verdict) = match decrypt::open(raw) {
+        Some((shown, verdict)) => (
+            shown,
+            Some(convert(verdict.banner(), ContentType::Plain, format)),
+        ),
+        None => (raw.to_vec(), None),
+    };
+    let message = mime::parse(&raw)?;
+    let Some((part, content_type)) = mime::best_part(&message) else {
+        // Mail that couldn't be decrypted has nothing more to show
+        return verdict.ok_or_else(no_text);
+    };
+    let mut text = mime::part_text(part);
+    if format == Format::Html && content_type == ContentType::Html {
+        text = mime::inline_images(&text, &message);
+    }
+    let body = render_content(&text, content_type.or_patch(&text), options)?;
+    let mut blocks: Vec<String> = headers.into_iter().chain(verdict).collect();
+    blocks.extend(
+        invites(&message, content_type)
+            .into_iter()
+            .map(|card| convert(card, ContentType::Calendar, format)),
+    );
+    blocks.push(body);
+    blocks.extend(
+        contacts(&message, content_type)
+            .into_iter()
+            .map(|card| convert(card, ContentType::Contact, format)),
+    );
+    Ok(blocks.join("\n\n"))
+}
+
+/// Event cards for a message's calendar invites (with an event), unless its best part is
+/// the invite
+pub(crate) fn invites(message: &mail_parser::Message, best: ContentType) -> Vec<String> {
+    if best == ContentType::Calendar {
+        return Vec::new();
+    }
+    mime::calendar_parts(message)
+        .iter()
+        .filter(|text| !ics::first_event(&ics::parse(text)).is_empty())
+        .map(|text| ics::render(text))
+        .collect()
+}
+
+/// Contact cards for a message's vCards, unless its best part is one
+pub(crate) fn contacts(message: &mail_parser::Message, best: ContentType) -> Vec<String> {
+    if best == ContentType::Contact {
+        return Vec::new();
+    }
+    mime::contact_parts(message)
+        .iter()
+        .map(|text| vcard::render(text))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::render_as;
+
+    #[test]
+    fn test_render_message_picks_a_part() {
+        let raw = "MIME-Version: 1.0\r\nContent-Type: multipart/alternative; boundary=b\r\n\r\n\
+--b\r\nContent-Type: text/calendar\r\n\r\nBEGIN:VCALENDAR\r\n\
+--b\r\nContent-Type: text/plain; charset=iso-8859-1\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\r\nCaf=E9 at noon\r\n--b--\r\n";
+        let text = render_as(raw, ContentType::Message, &Options::default()).unwrap();
+        assert_eq!(text.trim(), "Caf\u{e9} at noon");
+        let invite = raw.replace(
+            "VCALENDAR\r\n",
+            "VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Lunch\r\nEND:VEVENT\r\n",
+        );
+        let text = render_as(&invite, ContentType::Message, &Options::default()).unwrap();
+        let text = crate::color::strip(&text);
+        assert!(text.starts_with("╭─ Lunch ─╮\n╰"));
+        assert!(text.ends_with("\n\nCaf\u{e9} at noon"));
+        let contact = raw.replace(
+            "--b--",
+            "--b\r\nContent-Type: text/x-vcard; name=jane.vcf\r\n\r\n\
+BEGIN:VCARD\r\nFN:Jane\r\nEND:VCARD\r\n--b--",
+        );
+        let text = render_as(&contact, ContentType::Message, &Options::default()).unwrap();
+        assert!(crate::color::strip(&text).ends_with("noon\n\n╭─ Jane ─╮\n╰────────╯"));
+        let err = render_as(
+            "MIME-Version: 1.0\r\nContent-Type: image/png\r\n\r\nPNG",
+            ContentType::Message,
+            &Options::default(),
+        );
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! MIME message parsing (mail-parser)
+
+use anyhow::Result;
+use base64::Engine;
+use mail_parser::{Message, MessageParser, MessagePart, MimeHeaders};
+use regex::Regex;
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::sync::LazyLock;
+
+use crate::cleanup::re;
+use crate::error::Error;
+use crate::render::{self, ContentType};
+use crate::{ics, notmuch, tracker, vcard};
+
+/// A `cid:` URL in HTML, the Content-ID in group 1
+static CID: LazyLock<Regex> = LazyLock::new(|| re(r#"(?i)\bcid:([^"'\s>)]+)"#));
+
+/// Load a raw message by reference (message-id or notmuch query), or from stdin
+pub fn load(reference: Option<&str>) -> Result<Vec<u8>> {
+    match reference {
+        Some(r) => notmuch::raw(&notmuch::query_for(r)),
+        None => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Parse a raw RFC822 message
+pub fn parse(raw: &[u8]) -> Result<Message<'_>> {
+    MessageParser::default().parse(raw).ok_or_else(|| {
+        Error::ParseError {
+            what: "message".to_string(),
+            message: "not an RFC 5322 message".to_string(),
+        }
+        .into()
+    })
+}
+
+/// Lowercase `type/subtype` of a part (`text/plain` if unspecified)
+pub fn content_type(part: &MessagePart) -> String {
+    match part.content_type() {
+        Some(ct) => {
+            format!("{}/{}", ct.ctype(), ct.subtype().unwrap_or("octet-stream")).to_lowercase()
+        }
+        None => "text/plain".to_string(),
+    }
+}
+
+/// Decoded text of a part (binary parts are decoded lossily as UTF-8)
+pub fn part_text(part: &MessagePart) -> String {
+    match part.text_contents() {
+        Some(text) => text.to_string(),
+        None => String::from_utf8_lossy(part.contents()).to_string(),
+    }
+}
+
+/// First part with the given content type
+pub fn find_part<'a>(message: &'a Message, content_type: &str) -> Option<&'a MessagePart<'a>> {
+    let wanted = content_type.to_lowercase();
+    message
+        .parts
+        .iter()
+        .find(|p| self::content_type(p) == wanted)
+}
+
+/// Whether a part is a calendar invite: text/calendar, application/ics or an `.ics` file
+pub fn is_calendar(part: &MessagePart) -> bool {
+    matches!(
+        content_type(part).as_str(),
+        "text/calendar" | "application/ics"
+    ) || part
+        .attachment_name()
+        .is_some_and(|name| name.to_lowercase().ends_with(".ics"))
+}
+
+/// Text of every calendar part
+pub fn calendar_parts(message: &Message) -> Vec<String> {
+    message
+        .parts
+        .iter()
+        .filter(|part| is_calendar(part))
+        .map(part_text)
+        .filter(|text| ics::is_calendar(text))
+        .collect()
+}
+
+/// Whether a part is a vCard: text/vcard (or the older names) or a `.vcf` file
+pub fn is_contact(part: &MessagePart) -> bool {
+    matches!(
+        content_type(part).as_str(),
+        "text/vcard" | "text/x-vcard" | "text/directory"
+    ) || part
+        .attachment_name()
+        .is_some_and(|name| name.to_lowercase().ends_with(".vcf"))
+}
+
+/// Text of every vCard part
+pub fn contact_parts(message: &Message) -> Vec<String> {
+    message
+        .parts
+        .iter()
+        .filter(|part| is_contact(part))
+        .map(part_text)
+        .filter(|text| vcard::is_vcard(text))
+        .collect()
+}
+
+/// The part to render for a reader, and as what: HTML, else plain text, else a calendar or
+/// a vCard
+pub fn best_part<'a>(message: &'a Message<'a>) -> Option<(&'a MessagePart<'a>, ContentType)> {
+    [
+        ("text/html", ContentType::Html),
+        ("text/plain", ContentType::Plain),
+        ("text/calendar", ContentType::Calendar),
+        ("text/vcard", ContentType::Contact),
+        ("text/x-vcard", ContentType::Contact),
+    ]
+    .into_iter()
+    .find_map(|(mime, content_type)| Some((find_part(message, mime)?, content_type)))
+}
+
+/// `html` with its `cid:` references to parts of `message` (inline images) replaced by
+/// `data:` URLs holding them; references to no part stay
+pub fn inline_images(html: &str, message: &Message) -> String {
+    CID.replace_all(html, |caps: &regex::Captures| {
+        let id = tracker::percent_decode(&caps[1]);
+        let part = message.parts.iter().find(|part| {
+            part.content_id()
+                .is_some_and(|cid| cid.trim_matches(['<', '>']) == id)
+        });
+        match part {
+            Some(part) => format!(
+                "data:{};base64,{}",
+                content_type(part),
+                base64::engine::general_purpose::STANDARD.encode(part.contents())
+            ),
+            None => caps[0].to_string(),
+        }
+    })
+    .into_owned()
+}
+
+/// From/Date/Subject and the body as plain text (HTML-only mail rendered)
+pub fn as_text(message: &Message) -> Result<String> {
+    let mut text: String = ["From", "Date", "Subject"]
+        .into_iter()
+        .filter_map(|h| {
+            message
+                .header_raw(h)
+                .map(|v| format!("{}: {}\n", h, decode_words(v)))
+        })
+        .collect();
+    text.push('\n');
+    text.push_str(&body_text(message)?);
+    Ok(text)
+}
+
+/// The body as plain text: the text part, else the HTML part rendered (empty if neither)
+pub fn body_text(message: &Message) -> Result<String> {
+    match (
+        find_part(message, "text/plain"),
+        find_part(message, "text/html"),
+    ) {
+        (Some(part), _) => Ok(part_text(part)),
+        (None, Some(part)) => render::plain_text(&part_text(part)),
+        (None, None) => Ok(String::new()),
+    }
+}
+
+/// Unfold a raw header value (RFC 5322 folding) into a single line
+pub fn unfold(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Decode RFC 2047 encoded-words (`=?UTF-8?B?...?=`) in a header value
+pub fn decode_words(value: &str) -> String {
+    let value = unfold(value);
+    if !value.contains("=?") {
+        return value;
+    }
+    let raw = format!("Subject: {}\r\n\r\n", value);
+    MessageParser::default()
+        .parse_headers(raw.as_bytes())
+        .and_then(|m| m.subject().map(String::from))
+        .unwrap_or(value)
+}
+
+/// A run of RFC 2047 encoded-words (whitespace between them belongs to the encoding)
+static ENCODED_WORDS: LazyLock<Regex> = LazyLock::new(|| {
+    let word = r"=\?[^?\s]+\?[BbQq]\?[^?\s]*\?=";
+    Regex::new(&format!(r"{word}(?:\s+{word})*")).expect("valid encoded-word regex")
+});
+
+/// Decode the encoded-words in a line of text (a listing line, not a single header value),
+/// leaving everything else, spacing included, as it is
+pub fn decode_line(line: &str) -> Cow<'_, str> {
+    if !line.contains("=?") {
+        return Cow::Borrowed(line);
+    }
+    ENCODED_WORDS.replace_all(line, |caps: &regex::Captures| decode_words(&caps[0]))
+}
+
+/// Domain part of an email address (lowercased)
+pub fn domain_of(address: &str) -> Option<String> {
+    let addr = address.trim().trim_start_matches('<').trim_end_matches('>');
+    addr.rsplit_once('@')
+        .map(|(_, domain)| domain.to_lowercase())
+        .filter(|d| !d.is_empty())
+}
+
+/// Human-readable size (`512 B`, `2 KB`, `1.5 MB`)
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1048576 => format!("{:.0} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_words() {
+        assert_eq!(decode_words("=?UTF-8?B?SGVsbG8gV29ybGQ=?="), "Hello World");
+        assert_eq!(decode_words("=?ISO-8859-1?Q?caf=E9?="), "café");
+        assert_eq!(decode_words("plain\r\n  folded"), "plain folded");
+    }
+
+    #[test]
+    fn test_decode_line() {
+        let line = "thread:01  today [1/1] =?UTF-8?Q?J=C3=B6rg?=; =?UTF-8?B?SGVsbG8=?= =?UTF-8?B?IFdvcmxk?= (inbox)";
+        assert_eq!(
+            decode_line(line),
+            "thread:01  today [1/1] J\u{f6}rg; Hello World (inbox)"
+        );
+        assert!(matches!(decode_line("a  b"), Cow::Borrowed("a  b")));
+    }
+
+    #[test]
+    fn test_content_type_and_part_text() {
+        let raw = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nhello\r\n--b\r\nContent-Type: application/ics\r\n\
+Content-Transfer-Encoding: base64\r\n\r\nQkVHSU46VkNBTEVOREFS\r\n--b--\r\n";
+        let msg = parse(raw).unwrap();
+        let types: Vec<String> = msg.parts.iter().map(content_type).collect();
+        assert_eq!(
+            types,
+            vec!["multipart/mixed", "text/plain", "application/ics"]
+        );
+        assert_eq!(part_text(&msg.parts[2]), "BEGIN:VCALENDAR");
+    }
+
+    #[test]
+    fn test_inline_images() {
+        let raw = b"Content-Type: multipart/related; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/html\r\n\r\n<img src=\"cid:logo@x\"><img src=cid:gone>\r\n--b\r\n\
+Content-Type: image/png\r\nContent-ID: <logo@x>\r\nContent-Transfer-Encoding: base64\r\n\r\n\
+iVBO\r\n--b--\r\n";
+        let msg = parse(raw).unwrap();
+        let html = part_text(&msg.parts[1]);
+        assert_eq!(
+            inline_images(&html, &msg),
+            "<img src=\"data:image/png;base64,iVBO\"><img src=cid:gone>"
+        );
+    }
+
+    #[test]
+    fn test_as_text() {
+        let raw = b"From: Bob <b@x>\r\nSubject: =?utf-8?q?Pl=C3=A4n?=\r\n\
+Content-Type: text/plain; charset=iso-8859-1\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n\
+Ship it Fr=E9day.\r\n";
+        assert_eq!(
+            as_text(&parse(raw).unwrap()).unwrap(),
+            "From: Bob <b@x>\nSubject: Pl\u{e4}n\n\nShip it Fr\u{e9}day.\r\n"
+        );
+    }
+
+    #[test]
+    fn test_domain_of() {
+        assert_eq!(
+            domain_of("<Bounce@Mail.Example.COM>"),
+            Some("mail.example.com".into())
+        );
+        assert_eq!(domain_of("nobody"), None);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2 KB");
+        assert_eq!(format_size(1572864), "1.5 MB");
+    }
+}
@@ -0,0 +1,223 @@
+//! notmuch query helpers
+
+use anyhow::Result;
+
+use crate::error::{self, Error};
+use crate::{process, remote};
+
+/// Turn a message reference into a notmuch query
+///
+/// Accepts full queries (`id:...`, `thread:...`) as well as bare
+/// message-ids, with or without angle brackets.
+pub fn query_for(reference: &str) -> String {
+    let trimmed = reference.trim();
+    if trimmed.starts_with("id:") || trimmed.starts_with("thread:") || trimmed.contains(' ') {
+        return trimmed.to_string();
+    }
+    let id = trimmed.trim_start_matches('<').trim_end_matches('>');
+    format!("id:{}", id)
+}
+
+/// Value of a notmuch config key (`notmuch config get`), if set
+pub fn config_get(key: &str) -> Option<String> {
+    let output = process::run(&mut remote::command("notmuch", &["config", "get", key])).ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+/// All notmuch config as `key=value` lines (`notmuch config list`)
+pub fn config_list() -> Result<String> {
+    let output = process::run(&mut remote::command("notmuch", &["config", "list"]))
+        .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !output.status.success() {
+        return Err(Error::command_failed("notmuch config", &output.stderr).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Get the file path of the first message matching a query
+///
+/// The query goes after `--` in these helpers, so one starting with `-` (a reference taken
+/// as a query, see [`query_for`]) is never read as an option.
+pub fn first_file(query: &str) -> Result<String> {
+    let output = process::run(&mut remote::command(
+        "notmuch",
+        &["search", "--output=files", "--limit=1", "--", query],
+    ))
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+
+    if !output.status.success() {
+        return Err(Error::command_failed("notmuch search", &output.stderr).into());
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    match text.lines().next() {
+        Some(path) if !path.trim().is_empty() => Ok(path.trim().to_string()),
+        _ => Err(Error::NoMatch {
+            query: query.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Lines of `notmuch search --output=<output>` (`messages` gives `id:...`, `files` paths)
+pub fn search(output: &str, query: &str) -> Result<Vec<String>> {
+    let result = process::run(&mut remote::command(
+        "notmuch",
+        &["search", &format!("--output={}", output), "--", query],
+    ))
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+
+    if !result.status.success() {
+        return Err(Error::command_failed("notmuch search", &result.stderr).into());
+    }
+    Ok(String::from_utf8_lossy(&result.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// notmuch's JSON summary of each matching thread (`thread`, `authors`, `subject`, `tags`…),
+/// newest first
+pub fn summaries(query: &str) -> Result<Vec<serde_json::Value>> {
+    let result = process::run(&mut remote::command(
+        "notmuch",
+        &["search", "--format=json", "--output=summary", "--", query],
+    ))
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !result.status.success() {
+        return Err(Error::command_failed("notmuch search", &result.stderr).into());
+    }
+    serde_json::from_slice(&result.stdout).map_err(|e| {
+        Error::ParseError {
+            what: "notmuch search output".to_string(),
+            message: e.to_string(),
+        }
+        .into()
+    })
+}
+
+/// notmuch's JSON tree of the threads matching a query (`notmuch show`), with message
+/// bodies only if `bodies`
+pub fn threads(query: &str, bodies: bool) -> Result<serde_json::Value> {
+    let result = process::run(&mut remote::command(
+        "notmuch",
+        &[
+            "show",
+            "--format=json",
+            "--entire-thread=true",
+            &format!("--body={}", bodies),
+            "--",
+            query,
+        ],
+    ))
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !result.status.success() {
+        return Err(Error::command_failed("notmuch show", &result.stderr).into());
+    }
+    serde_json::from_slice(&result.stdout).map_err(|e| {
+        Error::ParseError {
+            what: "notmuch show output".to_string(),
+            message: e.to_string(),
+        }
+        .into()
+    })
+}
+
+/// Quote a `id:...` search result so it is safe inside a larger query
+pub fn quote_id(id: &str) -> String {
+    let id = id.strip_prefix("id:").unwrap_or(id);
+    format!("id:\"{}\"", id.replace('"', "\"\""))
+}
+
+/// One file per message of a thread or query, oldest first
+pub fn thread_files(query: &str) -> Result<Vec<String>> {
+    let result = process::run(&mut remote::command(
+        "notmuch",
+        &[
+            "search",
+            "--output=files",
+            "--duplicate=1",
+            "--sort=oldest-first",
+            "--",
+            query,
+        ],
+    ))
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !result.status.success() {
+        return Err(Error::command_failed("notmuch search", &result.stderr).into());
+    }
+    Ok(String::from_utf8_lossy(&result.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Read the raw bytes of the first message matching a query
+pub fn raw(query: &str) -> Result<Vec<u8>> {
+    remote::read(&first_file(query)?)
+}
+
+/// Apply `notmuch tag --batch` lines (`+a -b -- query`)
+pub fn tag_batch(lines: &[String]) -> Result<()> {
+    let input: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    let output = process::run_with_input(
+        &mut remote::command("notmuch", &["tag", "--batch"]),
+        input.as_bytes(),
+    )
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !output.status.success() {
+        return Err(Error::command_failed("notmuch tag --batch", &output.stderr).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_and_failure() {
+        let replay = crate::runner::Replay::parse(concat!(
+            r#"{"program":"notmuch","args":["search","--output=messages","--","tag:inbox"],"stdout":"id:a@x\n\nid:b@x\n"}"#,
+            "\n",
+            r#"{"program":"notmuch","args":["search","--output=files","--limit=1","--","id:c"],"status":1,"stderr":"no database"}"#,
+            "\n",
+            r#"{"program":"notmuch","args":["tag","--batch"],"status":1,"stderr":"bad tag"}"#,
+            "\n",
+            r#"{"program":"notmuch","args":["search","--output=files","--limit=1","--","--config=/x y"],"stdout":"/m/1\n"}"#,
+        ))
+        .unwrap();
+        crate::runner::scoped(replay, || {
+            assert_eq!(
+                search("messages", "tag:inbox").unwrap(),
+                ["id:a@x", "id:b@x"]
+            );
+            let err = first_file("id:c").unwrap_err();
+            assert!(format!("{:#}", err).contains("no database"));
+            let err = tag_batch(&["+a -- id:a@x".to_string()]).unwrap_err();
+            assert!(format!("{:#}", err).contains("bad tag"));
+            // A reference like this (it has a space) stays a query, not an option
+            let query = query_for("--config=/x y");
+            assert_eq!(first_file(&query).unwrap(), "/m/1");
+        });
+    }
+
+    #[test]
+    fn test_query_for() {
+        assert_eq!(query_for("<abc@example.com>"), "id:abc@example.com");
+        assert_eq!(query_for("abc@example.com"), "id:abc@example.com");
+        assert_eq!(
+            query_for("thread:0000000000000001"),
+            "thread:0000000000000001"
+        );
+        assert_eq!(query_for("from:a and tag:inbox"), "from:a and tag:inbox");
+    }
+
+    #[test]
+    fn test_quote_id() {
+        assert_eq!(quote_id("id:a\"b@x"), "id:\"a\"\"b@x\"");
+    }
+}
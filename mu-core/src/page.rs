@@ -0,0 +1,76 @@
+//! Rendered mail as a standalone HTML page for the browser: headers, the sanitized body with
+//! its inline images embedded, and a policy that keeps the page from loading anything else
+
+use anyhow::Result;
+
+use crate::color::escape_html;
+use crate::mime;
+use crate::render::{self, ContentType, Format, Options};
+
+/// Only what's in the page: inline styles and embedded images
+const CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; img-src data:";
+
+const STYLE: &str = "body{font:15px sans-serif;max-width:60em;margin:1em auto}\
+table.h{margin-bottom:1em}table.h td{padding:0 .5em}pre{white-space:pre-wrap}";
+
+/// Headers shown above a message
+const HEADERS: [&str; 5] = ["From", "To", "Cc", "Date", "Subject"];
+
+/// `text` of `content_type` as a page (a raw message gets its headers and inline images)
+pub fn page(text: &str, content_type: ContentType, options: &Options) -> Result<String> {
+    let options = Options {
+        format: Format::Html,
+        // The page has its own header table
+        headers: false,
+        ..options.clone()
+    };
+    let body = render::render_as(text, content_type, &options)?;
+    let mut title = String::from("mu");
+    let mut headers = String::new();
+    if content_type == ContentType::Message {
+        let message = mime::parse(text.as_bytes())?;
+        if let Some(subject) = message.subject() {
+            title = subject.to_string();
+        }
+        for name in HEADERS {
+            if let Some(value) = message.header_raw(name) {
+                headers.push_str(&format!(
+                    "<tr><td><b>{}</b></td><td>{}</td></tr>",
+                    name,
+                    escape_html(&mime::decode_words(value))
+                ));
+            }
+        }
+    }
+    if !headers.is_empty() {
+        headers = format!("<table class=h>{}</table><hr>", headers);
+    }
+    Ok(format!(
+        "<!doctype html><html><head><meta charset=utf-8>\
+<meta http-equiv=Content-Security-Policy content=\"{}\"><title>{}</title><style>{}</style>\
+</head><body>{}{}</body></html>\n",
+        CSP,
+        escape_html(&title),
+        STYLE,
+        headers,
+        body
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page() {
+        let raw = "From: Jane <jane@x.example>\r\nSubject: =?utf-8?q?News_&_more?=\r\n\
+Content-Type: multipart/related; boundary=b\r\n\r\n--b\r\nContent-Type: text/html\r\n\r\n\
+<p onclick=go()>Hi</p><img src=\"cid:logo\">\r\n--b\r\nContent-Type: image/gif\r\n\
+Content-ID: <logo>\r\nContent-Transfer-Encoding: base64\r\n\r\nR0lG\r\n--b--\r\n";
+        let page = page(raw, ContentType::Message, &Options::default()).unwrap();
+        assert!(page.contains("<title>News &amp; more</title>"));
+        assert!(page.contains("<td>Jane &lt;jane@x.example&gt;</td>"));
+        assert!(page.contains("<p>Hi</p><img src=\"data:image/gif;base64,R0lG\">"));
+        assert!(page.contains(CSP));
+    }
+}
@@ -0,0 +1,154 @@
+//! Patches and unified diffs in mail (`git format-patch`, `diff -u` output), rendered with
+//! colored file headers, hunks, additions and deletions
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::{cleanup, render, theme};
+
+/// A hunk header, with the old and new line counts (1 when left out)
+static HUNK: LazyLock<Regex> =
+    LazyLock::new(|| cleanup::re(r"^@@ -\d+(?:,(\d+))? \+\d+(?:,(\d+))? @@"));
+
+/// A diffstat line (` src/main.rs | 12 +++++-----`)
+static STAT: LazyLock<Regex> = LazyLock::new(|| cleanup::re(r"^ \S.* \| +\d+ [+-]+$"));
+
+/// Lines that start a file's diff or describe it (git's extended headers)
+const FILE_HEADERS: [&str; 13] = [
+    "diff ",
+    "index ",
+    "--- ",
+    "+++ ",
+    "new file mode ",
+    "deleted file mode ",
+    "old mode ",
+    "new mode ",
+    "similarity index ",
+    "rename from ",
+    "rename to ",
+    "copy from ",
+    "copy to ",
+];
+
+/// Whether `text` carries a diff: a `diff --git` line, or `---`/`+++` lines and a hunk
+pub fn is_patch(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+    lines.iter().any(|line| line.starts_with("diff --git "))
+        || lines
+            .windows(3)
+            .any(|w| w[0].starts_with("--- ") && w[1].starts_with("+++ ") && HUNK.is_match(w[2]))
+}
+
+/// Colors a patch line by line, following hunk lengths so a `-- ` signature after the last
+/// hunk isn't taken for a deletion
+#[derive(Debug, Default)]
+pub struct Highlighter {
+    /// Old and new lines left in the current hunk
+    left: (usize, usize),
+}
+
+impl Highlighter {
+    /// `line` colored for where it falls in the patch, its line ending kept
+    pub fn line(&mut self, line: &str) -> String {
+        let body = line.trim_end_matches(['\r', '\n']);
+        format!("{}{}", self.style(body), &line[body.len()..])
+    }
+
+    fn style(&mut self, line: &str) -> String {
+        let theme = theme::get();
+        let (old, new) = &mut self.left;
+        if *old > 0 || *new > 0 {
+            return match line.as_bytes().first() {
+                Some(b'-') => {
+                    *old = old.saturating_sub(1);
+                    render::style(&theme.removed, line)
+                }
+                Some(b'+') => {
+                    *new = new.saturating_sub(1);
+                    render::style(&theme.added, line)
+                }
+                Some(b'\\') => render::style(&theme.muted, line),
+                _ => {
+                    *old = old.saturating_sub(1);
+                    *new = new.saturating_sub(1);
+                    line.to_string()
+                }
+            };
+        }
+        if let Some(caps) = HUNK.captures(line) {
+            let count = |i| caps.get(i).map_or(Some(1), |m| m.as_str().parse().ok());
+            self.left = (count(1).unwrap_or(0), count(2).unwrap_or(0));
+            return render::style(&theme.hunk, line);
+        }
+        if FILE_HEADERS.iter().any(|header| line.starts_with(header)) {
+            return render::style(&theme.diff_header, line);
+        }
+        if STAT.is_match(line) {
+            let bar = line.trim_end_matches(['+', '-']);
+            let plus = line[bar.len()..].trim_end_matches('-');
+            let minus = &line[bar.len() + plus.len()..];
+            return format!(
+                "{}{}{}",
+                bar,
+                render::style(&theme.added, plus),
+                render::style(&theme.removed, minus)
+            );
+        }
+        line.to_string()
+    }
+}
+
+/// A whole patch, colored
+pub fn render(text: &str) -> String {
+    let mut highlighter = Highlighter::default();
+    text.split_inclusive('\n')
+        .map(|line| highlighter.line(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    const PATCH: &str = "Subject: [PATCH] Fix the count\n\nOff by one.\n---\n \
+src/count.rs | 3 ++-\n 1 file changed, 2 insertions(+), 1 deletion(-)\n\n\
+diff --git a/src/count.rs b/src/count.rs\nindex 1a2b..3c4d 100644\n\
+--- a/src/count.rs\n+++ b/src/count.rs\n@@ -1,2 +1,3 @@\n fn count() {\n\
+-    n\n+    n + 1\n+    // fixed\n-- \n2.45.0\n";
+
+    #[test]
+    fn test_is_patch() {
+        assert!(is_patch(PATCH));
+        assert!(is_patch("--- a.txt\n+++ b.txt\n@@ -1 +1 @@\n-a\n+b\n"));
+        assert!(!is_patch("Hi,\n---\nsent from my phone\n"));
+        assert!(!is_patch("--- a.txt\n+++ b.txt\nno hunk\n"));
+    }
+
+    #[test]
+    fn test_render() {
+        let theme = theme::get();
+        let out = render(PATCH);
+        assert_eq!(color::strip(&out), PATCH);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "Subject: [PATCH] Fix the count");
+        assert_eq!(
+            lines[4],
+            format!(
+                " src/count.rs | 3 {}{}",
+                render::style(&theme.added, "++"),
+                render::style(&theme.removed, "-")
+            )
+        );
+        assert_eq!(
+            lines[8],
+            render::style(&theme.diff_header, "index 1a2b..3c4d 100644")
+        );
+        assert_eq!(lines[11], render::style(&theme.hunk, "@@ -1,2 +1,3 @@"));
+        assert_eq!(lines[12], " fn count() {");
+        assert_eq!(lines[13], render::style(&theme.removed, "-    n"));
+        assert_eq!(lines[15], render::style(&theme.added, "+    // fixed"));
+        // The signature after the hunk
+        assert_eq!(lines[16], "-- ");
+    }
+}
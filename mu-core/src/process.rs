@@ -0,0 +1,281 @@
+//! Subprocesses on a tokio runtime, with timeouts and kill-on-cancel
+//!
+//! Every command run through here is traced: a `command` span with the program and its
+//! arguments, and a debug event with the exit status and how long it took. The active
+//! [`runner`] may answer instead (`--simulate`, tests) or record the outcome (`--record`).
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Output, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::Instrument;
+
+use crate::error::{self, Error};
+use crate::runner;
+
+/// Drive a future to completion on a fresh current-thread runtime
+///
+/// For synchronous callers; must not be called from inside a runtime.
+pub fn block_on<F: Future>(future: F) -> Result<F::Output> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the async runtime")?;
+    Ok(runtime.block_on(future))
+}
+
+/// Run a command to completion with `input` on stdin, capturing stdout and stderr
+///
+/// The child is killed when the timeout expires or the future is dropped
+/// (e.g. a sibling task failed and the batch was cancelled).
+pub async fn output(
+    cmd: impl Into<Command>,
+    input: Option<&[u8]>,
+    timeout: Duration,
+) -> Result<Output> {
+    let mut cmd: Command = cmd.into();
+    let span = span(cmd.as_std());
+    let program = cmd.as_std().get_program().to_string_lossy().to_string();
+    if let Some(output) = span.in_scope(|| runner::intercept(cmd.as_std())) {
+        return output.map_err(|e| error::spawn_failed(&program, e));
+    }
+    cmd.kill_on_drop(true)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn().map_err(|e| error::spawn_failed(&program, e))?;
+
+    // Feed stdin while collecting output, so a chatty child can't deadlock on a full pipe
+    let stdin = child.stdin.take();
+    let write = async move {
+        if let (Some(data), Some(mut stdin)) = (input, stdin) {
+            stdin.write_all(data).await?;
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    let started = Instant::now();
+    let run = async { tokio::join!(write, child.wait_with_output()) };
+    match tokio::time::timeout(timeout, run)
+        .instrument(span.clone())
+        .await
+    {
+        Ok((written, output)) => {
+            let output = output.with_context(|| format!("Failed to run {}", program))?;
+            span.in_scope(|| exited(started, output.status));
+            runner::record(cmd.as_std(), &output);
+            // A child that exits without reading all its input is not an error in itself
+            if let Err(e) = written
+                && e.kind() != std::io::ErrorKind::BrokenPipe
+            {
+                return Err(e).with_context(|| format!("Failed to write to {}", program));
+            }
+            Ok(output)
+        }
+        Err(_) => {
+            span.in_scope(|| tracing::debug!(elapsed = ?started.elapsed(), "timed out"));
+            Err(Error::Timeout {
+                tool: program,
+                seconds: timeout.as_secs(),
+            }
+            .into())
+        }
+    }
+}
+
+/// [`std::process::Command::output`], traced
+pub fn run(cmd: &mut std::process::Command) -> io::Result<Output> {
+    let _span = span(cmd).entered();
+    if let Some(output) = runner::intercept(cmd) {
+        return output;
+    }
+    let started = Instant::now();
+    let output = cmd.output();
+    match &output {
+        Ok(output) => {
+            exited(started, output.status);
+            runner::record(cmd, output);
+        }
+        Err(e) => tracing::debug!(error = %e, "failed to start"),
+    }
+    output
+}
+
+/// [`run`] with `input` on stdin
+///
+/// stdin is fed from another thread while the output is collected, so a chatty child can't
+/// deadlock on a full pipe; a child that exits without reading all its input is fine.
+pub fn run_with_input(cmd: &mut std::process::Command, input: &[u8]) -> io::Result<Output> {
+    let _span = span(cmd).entered();
+    if let Some(output) = runner::intercept(cmd) {
+        return output;
+    }
+    let started = Instant::now();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .inspect_err(|e| tracing::debug!(error = %e, "failed to start"))?;
+    let mut stdin = child.stdin.take();
+    let output = std::thread::scope(|scope| {
+        let feeder = scope.spawn(move || match stdin.as_mut() {
+            Some(stdin) => io::Write::write_all(stdin, input),
+            None => Ok(()),
+        });
+        let output = child.wait_with_output();
+        match feeder.join() {
+            Ok(Err(e)) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+            _ => output,
+        }
+    })?;
+    exited(started, output.status);
+    runner::record(cmd, &output);
+    Ok(output)
+}
+
+/// [`std::process::Command::status`], traced
+pub fn status(cmd: &mut std::process::Command) -> io::Result<ExitStatus> {
+    let _span = span(cmd).entered();
+    if let Some(output) = runner::intercept(cmd) {
+        return output.map(|o| o.status);
+    }
+    let started = Instant::now();
+    let status = cmd.status();
+    match &status {
+        Ok(status) => {
+            exited(started, *status);
+            // Output went to the terminal; only the status is kept
+            let output = Output {
+                status: *status,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            };
+            runner::record(cmd, &output);
+        }
+        Err(e) => tracing::debug!(error = %e, "failed to start"),
+    }
+    status
+}
+
+/// `command` for the system shell: `sh -c` (`cmd /C` on Windows)
+pub fn shell(command: &str) -> std::process::Command {
+    shell_with_args(command, &[] as &[&str])
+}
+
+/// A configured command (`sendmail -t`, `gpg --batch`) with `args` passed on unquoted
+///
+/// `sh -c 'exec <command> "$@"'` on unix; on Windows `cmd /C` gets the command verbatim and
+/// the arguments quoted as usual.
+pub fn shell_with_args<S: AsRef<std::ffi::OsStr>>(
+    command: &str,
+    args: &[S],
+) -> std::process::Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.arg("/C").raw_arg(command).args(args);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = std::process::Command::new("sh");
+        if args.is_empty() {
+            cmd.args(["-c", command]);
+        } else {
+            cmd.args(["-c", &format!("exec {} \"$@\"", command), "sh"])
+                .args(args);
+        }
+        cmd
+    }
+}
+
+/// Find an executable on PATH (trying the `PATHEXT` extensions on Windows)
+pub fn which(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    let names = executable_names(name);
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+fn executable_names(name: &str) -> Vec<String> {
+    let mut names = vec![name.to_string()];
+    if cfg!(windows) {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.CMD;.BAT".into());
+        names.extend(
+            extensions
+                .split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| format!("{}{}", name, ext.to_lowercase())),
+        );
+    }
+    names
+}
+
+/// A `command` span for `cmd`, for commands spawned and waited on by hand
+pub fn span(cmd: &std::process::Command) -> tracing::Span {
+    let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+    tracing::debug_span!(
+        "command",
+        program = %cmd.get_program().to_string_lossy(),
+        args = %args.join(" ")
+    )
+}
+
+/// Log how a command ended, `started` being when it was spawned
+pub fn exited(started: Instant, status: ExitStatus) {
+    tracing::debug!(%status, elapsed = ?started.elapsed(), "exited");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_with_input() {
+        let output =
+            run_with_input(std::process::Command::new("tr").args(["a-z", "A-Z"]), b"hi").unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"HI");
+    }
+
+    #[test]
+    fn test_output_with_input() {
+        let mut tr = std::process::Command::new("tr");
+        tr.args(["a-z", "A-Z"]);
+        let output = block_on(output(tr, Some(b"hello"), Duration::from_secs(5)))
+            .unwrap()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"HELLO");
+    }
+
+    #[test]
+    fn test_shell_with_args_keeps_words() {
+        let output = run(&mut shell_with_args("printf '%s|'", &["a b", "$HOME"])).unwrap();
+        assert_eq!(output.stdout, b"a b|$HOME|");
+        let output = run(&mut shell("echo one; echo two")).unwrap();
+        assert_eq!(output.stdout, b"one\ntwo\n");
+    }
+
+    #[test]
+    fn test_output_timeout_kills() {
+        let mut sleep = std::process::Command::new("sleep");
+        sleep.arg("5");
+        let started = std::time::Instant::now();
+        let err = block_on(output(sleep, None, Duration::from_millis(100)))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "sleep timed out after 0s");
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
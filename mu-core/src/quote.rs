@@ -0,0 +1,249 @@
+//! Quoted replies folded to one line: an attribution (`On … wrote:`) and the quoted block
+//! under it, or an Outlook-style `-----Original Message-----` header and everything after
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::i18n::{self, Msg};
+use crate::lines::LineFilter;
+use crate::{cleanup, color, render, theme};
+
+/// Quoted lines a block needs to be folded (shorter ones are likely inline replies)
+const FOLD_MIN: usize = 4;
+
+/// The end of an attribution line
+static ATTRIBUTION: LazyLock<Regex> = LazyLock::new(|| {
+    cleanup::re(concat!(
+        r"(?i)\b(?:wrote|writes|schrieb|a écrit|escribió|ha scritto|schreef|escreveu",
+        r"|napisał\(a\)|napisał|napisała)\s*:$",
+    ))
+});
+
+/// The start of an attribution that may wrap before its `wrote:`
+static OPENING: LazyLock<Regex> =
+    LazyLock::new(|| cleanup::re(r"(?i)^(?:on|am|le|el|il|op|em|w dniu|dnia)\s"));
+
+/// Outlook's separator above the original message
+static ORIGINAL: LazyLock<Regex> = LazyLock::new(|| {
+    cleanup::re(concat!(
+        r"(?i)^-{2,}\s*(?:original message|ursprüngliche nachricht|message d'origine",
+        r"|mensaje original|oryginalna wiadomość)\s*-{2,}$",
+    ))
+});
+
+/// Whether `line` may open a quoted reply (an attribution or Outlook's separator)
+pub(crate) fn is_opening(line: &str) -> bool {
+    let text = color::strip(line);
+    let text = text.trim();
+    ORIGINAL.is_match(text) || ATTRIBUTION.is_match(text) || OPENING.is_match(text)
+}
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Text,
+    /// A line that may start an attribution or an Outlook header
+    Held(String),
+    /// An attribution, and any blank lines after it
+    Attributed(Vec<String>),
+    /// Inside the quoted block
+    Quote(Quote),
+    /// The original message to the end
+    Rest(usize),
+}
+
+/// A quoted block being read
+#[derive(Debug)]
+struct Quote {
+    /// Its lines, until it's long enough to fold
+    held: Vec<String>,
+    /// How many of them are quoted
+    quoted: usize,
+    /// How many lines a marker would stand for
+    lines: usize,
+    /// Blank lines not yet known to be inside it
+    blanks: String,
+}
+
+/// Folds quoted replies in lines pushed one at a time
+#[derive(Debug)]
+pub(crate) struct Folder {
+    fold: bool,
+    colors: bool,
+    state: State,
+}
+
+impl Folder {
+    /// A folder (passing everything through unless `fold`) whose marker is in color only if
+    /// `colors`
+    pub(crate) fn new(fold: bool, colors: bool) -> Self {
+        Self {
+            fold,
+            colors,
+            state: State::Text,
+        }
+    }
+
+    /// A line outside any quote
+    fn text(&mut self, line: String) -> String {
+        let text = color::strip(&line);
+        let text = text.trim();
+        if ORIGINAL.is_match(text) {
+            self.state = State::Rest(1);
+        } else if ATTRIBUTION.is_match(text) {
+            self.state = State::Attributed(vec![line]);
+        } else if OPENING.is_match(text) || text.starts_with("From: ") {
+            self.state = State::Held(line);
+        } else {
+            return line;
+        }
+        String::new()
+    }
+
+    /// The line after one that may start an attribution or an Outlook header
+    fn held(&mut self, held: String, line: String) -> String {
+        let text = color::strip(&line);
+        let text = text.trim();
+        if ATTRIBUTION.is_match(text) {
+            self.state = State::Attributed(vec![held, line]);
+        } else if color::strip(&held).starts_with("From: ") && text.starts_with("Sent: ") {
+            self.state = State::Rest(2);
+        } else {
+            return held + &self.text(line);
+        }
+        String::new()
+    }
+
+    /// A line after an attribution: the quote starts, a blank is held, or it wasn't one
+    fn attributed(&mut self, mut lines: Vec<String>, line: String) -> String {
+        let text = color::strip(&line);
+        let text = text.trim();
+        if text.starts_with('>') {
+            lines.push(line);
+            self.state = State::Quote(Quote {
+                lines: lines.len(),
+                held: lines,
+                quoted: 1,
+                blanks: String::new(),
+            });
+        } else if text.is_empty() && lines.len() < 3 {
+            lines.push(line);
+            self.state = State::Attributed(lines);
+        } else {
+            return lines.concat() + &self.text(line);
+        }
+        String::new()
+    }
+
+    /// A line inside a quoted block; the first unquoted line ends it, folded if it's long
+    /// enough
+    fn quote(&mut self, mut quote: Quote, line: String) -> String {
+        let text = color::strip(&line);
+        let text = text.trim();
+        if text.starts_with('>') {
+            quote.lines += blanks_in(&quote.blanks) + 1;
+            let blanks = std::mem::take(&mut quote.blanks);
+            if quote.quoted < FOLD_MIN {
+                quote.held.push(blanks);
+                quote.held.push(line);
+            } else {
+                quote.held.clear();
+            }
+            quote.quoted += 1;
+        } else if text.is_empty() {
+            quote.blanks.push_str(&line);
+        } else {
+            let folded = match quote.quoted < FOLD_MIN {
+                true => quote.held.concat(),
+                false => self.marker(quote.lines),
+            };
+            return folded + &quote.blanks + &self.text(line);
+        }
+        self.state = State::Quote(quote);
+        String::new()
+    }
+
+    /// `▸ quoted message (N lines)`
+    fn marker(&self, lines: usize) -> String {
+        let marker = format!("▸ {}", i18n::tr(Msg::Quoted(lines)));
+        if self.colors {
+            format!("{}\n", render::style(&theme::get().muted, marker))
+        } else {
+            format!("{}\n", marker)
+        }
+    }
+}
+
+impl LineFilter for Folder {
+    fn push(&mut self, line: String) -> String {
+        if !self.fold {
+            return line;
+        }
+        match std::mem::take(&mut self.state) {
+            State::Text => self.text(line),
+            State::Held(held) => self.held(held, line),
+            State::Attributed(lines) => self.attributed(lines, line),
+            State::Quote(quote) => self.quote(quote, line),
+            State::Rest(lines) => {
+                self.state = State::Rest(lines + 1);
+                String::new()
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        match std::mem::take(&mut self.state) {
+            State::Text => String::new(),
+            State::Held(line) => line,
+            State::Attributed(lines) => lines.concat(),
+            State::Quote(quote) => match quote.quoted < FOLD_MIN {
+                true => quote.held.concat() + &quote.blanks,
+                false => self.marker(quote.lines),
+            },
+            State::Rest(lines) => self.marker(lines),
+        }
+    }
+}
+
+/// Lines in a run of blank lines
+fn blanks_in(blanks: &str) -> usize {
+    blanks.matches('\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines;
+
+    fn fold(text: &str) -> String {
+        lines::apply(Folder::new(true, false), text)
+    }
+
+    fn marker(lines: usize) -> String {
+        format!("▸ {}\n", i18n::tr(Msg::Quoted(lines)))
+    }
+
+    #[test]
+    fn test_attributed_quote() {
+        let reply = "Sounds good.\n\nOn Mon, 2 Feb 2026, Jane <jane@x.example>\nwrote:\n\
+> Lunch on Friday?\n>\n> We could try\n\n> the new place.\nJ\n";
+        assert_eq!(fold(reply), format!("Sounds good.\n\n{}J\n", marker(7)));
+        // An inline reply keeps its short quotes
+        let inline = "On Mon, Jane wrote:\n> Friday?\n\nYes.\n";
+        assert_eq!(fold(inline), inline);
+        // Mentioning someone's words isn't a quote
+        let text = "As Jane wrote:\nbring snacks\n";
+        assert_eq!(fold(text), text);
+    }
+
+    #[test]
+    fn test_outlook_original() {
+        let reply =
+            "Approved.\n\n-----Original Message-----\nFrom: Bob\nSent: Monday\n\nPlease approve.\n";
+        assert_eq!(fold(reply), format!("Approved.\n\n{}", marker(5)));
+        let reply = "Approved.\nFrom: Bob <b@x.example>\nSent: Monday\nTo: Ann\n";
+        assert_eq!(fold(reply), format!("Approved.\n{}", marker(3)));
+        let text = "From: the team\nThanks all\n";
+        assert_eq!(fold(text), text);
+    }
+}
@@ -0,0 +1,307 @@
+//! Reader mode: the main text of a newsletter without its layout tables, buttons and footers,
+//! found the way Readability does it
+//!
+//! Paragraphs score their parent and grandparent by length and commas; the best-scoring
+//! block, less its share of link text, is the article. Its headline comes along if it sits
+//! outside it.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::cleanup::re;
+
+/// Comments, scripts, styles and the head, which hold no text
+static HIDDEN: LazyLock<Regex> = LazyLock::new(|| {
+    re(r"(?is)<!--.*?-->|<script\b.*?</script\s*>|<style\b.*?</style\s*>|<head\b.*?</head\s*>")
+});
+
+/// A tag (its name in group 2, a `/` in group 1 if it closes) or a run of text
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| re(r"<(/?)([a-zA-Z][\w:-]*)([^>]*)>|[^<]+|<"));
+
+static TITLE: LazyLock<Regex> = LazyLock::new(|| re(r"(?is)<title\b[^>]*>(.*?)</title\s*>"));
+
+/// Class or id words that mark content, and ones that mark everything around it
+static POSITIVE: LazyLock<Regex> =
+    LazyLock::new(|| re(r"(?i)article|body|content|entry|main|post|story|text|column"));
+static NEGATIVE: LazyLock<Regex> = LazyLock::new(|| {
+    re(concat!(
+        r"(?i)footer|header|nav|menu|social|share|sidebar|unsubscribe|button|btn|promo",
+        r"|banner|sponsor|preheader|preference|legal|copyright",
+    ))
+});
+
+static CLASS: LazyLock<Regex> =
+    LazyLock::new(|| re(r#"(?i)\b(?:class|id)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#));
+
+/// Elements without a closing tag
+const VOID: [&str; 12] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "wbr",
+];
+
+/// Text shorter than this isn't a paragraph
+const MIN_PARAGRAPH: usize = 25;
+
+/// Less text than this isn't worth extracting; the document stays whole
+const MIN_ARTICLE: usize = 250;
+
+#[derive(Debug)]
+struct Node {
+    tag: String,
+    parent: Option<usize>,
+    /// Where the element starts and ends in the document
+    start: usize,
+    end: usize,
+    /// Class and id weight
+    weight: f64,
+    /// Text directly inside, and inside altogether
+    own: usize,
+    text: usize,
+    /// Text inside links
+    links: usize,
+    commas: usize,
+}
+
+/// The elements of `html`, parents before their children
+fn parse(html: &str) -> Vec<Node> {
+    let mut tree = Tree::default();
+    for token in TOKEN.captures_iter(html) {
+        let Some(whole) = token.get(0) else {
+            continue;
+        };
+        match token.get(2) {
+            None => tree.text(whole.as_str().trim()),
+            Some(name) if &token[1] == "/" => {
+                tree.close(&name.as_str().to_ascii_lowercase(), whole.end())
+            }
+            Some(name) => tree.open(name.as_str().to_ascii_lowercase(), &token[3], whole),
+        }
+    }
+    tree.finish(html.len())
+}
+
+/// The elements read so far, and which are open
+#[derive(Default)]
+struct Tree {
+    nodes: Vec<Node>,
+    open: Vec<usize>,
+    /// How many open elements are links
+    in_link: usize,
+}
+
+impl Tree {
+    /// Text, counted for the innermost element
+    fn text(&mut self, text: &str) {
+        if let Some(&i) = self.open.last()
+            && !text.is_empty()
+        {
+            let length = text.chars().count();
+            self.nodes[i].own += length;
+            self.nodes[i].commas += text.matches(',').count();
+            if self.in_link > 0 {
+                self.nodes[i].links += length;
+            }
+        }
+    }
+
+    /// A start tag; void and self-closing elements are closed at once
+    fn open(&mut self, tag: String, attributes: &str, whole: regex::Match) {
+        self.nodes.push(Node {
+            weight: weight(attributes),
+            parent: self.open.last().copied(),
+            start: whole.start(),
+            end: whole.end(),
+            tag,
+            own: 0,
+            text: 0,
+            links: 0,
+            commas: 0,
+        });
+        let i = self.nodes.len() - 1;
+        if !VOID.contains(&self.nodes[i].tag.as_str()) && !attributes.ends_with('/') {
+            self.open.push(i);
+            if self.nodes[i].tag == "a" {
+                self.in_link += 1;
+            }
+        }
+    }
+
+    /// An end tag: closes up to the matching element, if it's open
+    fn close(&mut self, tag: &str, end: usize) {
+        if let Some(depth) = self.open.iter().rposition(|&i| self.nodes[i].tag == tag) {
+            for i in self.open.drain(depth..) {
+                self.nodes[i].end = end;
+                if self.nodes[i].tag == "a" {
+                    self.in_link -= 1;
+                }
+            }
+        }
+    }
+
+    /// The elements, those still open ending at `end`, with each one's totals added to its
+    /// parent's (children come after their parents)
+    fn finish(mut self, end: usize) -> Vec<Node> {
+        let mut nodes = self.nodes;
+        for i in self.open.drain(..) {
+            nodes[i].end = end;
+        }
+        for node in nodes.iter_mut() {
+            node.text = node.own;
+        }
+        for i in (0..nodes.len()).rev() {
+            if let Some(parent) = nodes[i].parent {
+                let (text, links, commas) = (nodes[i].text, nodes[i].links, nodes[i].commas);
+                nodes[parent].text += text;
+                nodes[parent].links += links;
+                nodes[parent].commas += commas;
+            }
+        }
+        nodes
+    }
+}
+
+/// ±25 for class or id names that mark content or what's around it
+fn weight(attributes: &str) -> f64 {
+    CLASS.captures_iter(attributes).fold(0.0, |weight, caps| {
+        let value = (1..=3).find_map(|i| caps.get(i)).map_or("", |m| m.as_str());
+        let mut weight = weight;
+        if NEGATIVE.is_match(value) {
+            weight -= 25.0;
+        }
+        if POSITIVE.is_match(value) {
+            weight += 25.0;
+        }
+        weight
+    })
+}
+
+/// A candidate's starting score, by what kind of element it is
+fn base_score(node: &Node) -> f64 {
+    node.weight
+        + match node.tag.as_str() {
+            "div" | "article" | "main" | "section" => 5.0,
+            "td" | "pre" | "blockquote" => 3.0,
+            "form" | "ol" | "ul" | "dl" | "li" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        }
+}
+
+/// Whether `node` holds a paragraph's worth of text
+fn is_paragraph(node: &Node) -> bool {
+    let text = match node.tag.as_str() {
+        "p" | "pre" | "blockquote" => node.text,
+        // Layout cells and blocks hold text directly more often than in `<p>`s
+        "td" | "div" | "section" | "span" | "font" => node.own,
+        _ => return false,
+    };
+    text >= MIN_PARAGRAPH
+}
+
+fn link_density(node: &Node) -> f64 {
+    match node.text {
+        0 => 0.0,
+        text => node.links as f64 / text as f64,
+    }
+}
+
+/// The main content of `html` (with its headline), or `html` itself when nothing stands out
+pub fn extract(html: &str) -> String {
+    let title = TITLE.captures(html).map(|caps| caps[1].trim().to_string());
+    let text = HIDDEN.replace_all(html, "");
+    let nodes = parse(&text);
+    let mut scores: Vec<Option<f64>> = vec![None; nodes.len()];
+    for node in nodes.iter().filter(|node| is_paragraph(node)) {
+        let score = 1.0 + node.commas as f64 + (node.text as f64 / 100.0).min(3.0);
+        let parent = node.parent;
+        let grandparent = parent.and_then(|i| nodes[i].parent);
+        for (ancestor, share) in [(parent, 1.0), (grandparent, 0.5)] {
+            if let Some(i) = ancestor {
+                let current = scores[i].unwrap_or_else(|| base_score(&nodes[i]));
+                scores[i] = Some(current + score * share);
+            }
+        }
+    }
+    let ranked = scores
+        .iter()
+        .enumerate()
+        .filter_map(|(i, score)| Some((i, score.as_ref()? * (1.0 - link_density(&nodes[i])))));
+    let Some((best, top)) = ranked.max_by(|a, b| a.1.total_cmp(&b.1)) else {
+        return html.to_string();
+    };
+    let article = &nodes[best];
+    if article.text - article.links < MIN_ARTICLE {
+        return html.to_string();
+    }
+
+    // Siblings scoring close to it are part of it (sections of one newsletter)
+    let threshold = (top * 0.2).max(10.0);
+    let mut body = String::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let sibling = i == best
+            || node.parent.is_some() && node.parent == article.parent && {
+                let score = scores[i].map_or(0.0, |s| s * (1.0 - link_density(node)));
+                score >= threshold
+            };
+        if sibling {
+            body.push_str(&text[node.start..node.end]);
+        }
+    }
+    let headline = headline(&text, &nodes, article)
+        .or_else(|| title.map(|title| format!("<h1>{}</h1>", title)))
+        .unwrap_or_default();
+    format!("{}{}", headline, body)
+}
+
+/// The first `<h1>` (else `<h2>`) outside the article; empty if the article has its own, and
+/// None if there's neither
+fn headline(html: &str, nodes: &[Node], article: &Node) -> Option<String> {
+    let outside = |node: &&Node| node.start < article.start || node.start >= article.end;
+    for tag in ["h1", "h2"] {
+        if let Some(heading) = nodes
+            .iter()
+            .filter(outside)
+            .find(|node| node.tag == tag && node.text > 0)
+        {
+            return Some(html[heading.start..heading.end].to_string());
+        }
+        if nodes.iter().any(|node| node.tag == tag && !outside(&node)) {
+            return Some(String::new());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORY: &str = "Rust 2026 is out, and it brings faster builds, smaller binaries and a \
+new borrow checker, which the team has worked on for years.";
+
+    fn newsletter() -> String {
+        format!(
+            "<html><head><title>Weekly</title><style>p{{}}</style></head><body>\
+<table><tr><td class=preheader><a href=/a>View in browser</a> | <a href=/b>Shop now</a></td></tr>\
+<tr><td><h1>This week in Rust</h1></td></tr>\
+<tr><td class=content><p>{0}</p><p>{0}</p><p>{0}</p></td></tr>\
+<tr><td class=footer><p>You're receiving this, because you signed up. \
+<a href=/u>Unsubscribe</a>, <a href=/p>preferences</a>, or <a href=/s>share</a>.</p></td></tr>\
+</table></body></html>",
+            STORY
+        )
+    }
+
+    #[test]
+    fn test_extract() {
+        let article = extract(&newsletter());
+        assert!(article.starts_with("<h1>This week in Rust</h1><td class=content><p>"));
+        assert_eq!(article.matches(STORY).count(), 3);
+        assert!(!article.contains("Unsubscribe") && !article.contains("Shop now"));
+    }
+
+    #[test]
+    fn test_short_mail_stays_whole() {
+        let mail = "<p>Lunch at noon?</p><p><a href=/x>Calendar</a></p>";
+        assert_eq!(extract(mail), mail);
+    }
+}
@@ -0,0 +1,145 @@
+//! Run notmuch/mbsync on another machine over SSH (`--remote HOST`), or in WSL (`wsl`)
+
+use anyhow::{Context, Result};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::process;
+
+/// Environment variable carrying the host to child processes (fzf previews)
+pub const ENV: &str = "MU_REMOTE";
+
+static HOST: OnceLock<Option<String>> = OnceLock::new();
+
+/// Use `host` for this process (None falls back to `$MU_REMOTE`)
+pub fn set(host: Option<String>) {
+    let host = host.or_else(|| std::env::var(ENV).ok().filter(|h| !h.is_empty()));
+    let _ = HOST.set(host);
+}
+
+/// The remote host, if one is configured
+pub fn host() -> Option<&'static str> {
+    HOST.get_or_init(|| std::env::var(ENV).ok().filter(|h| !h.is_empty()))
+        .as_deref()
+}
+
+/// `program args...`, locally or through a multiplexed SSH connection
+pub fn command(program: &str, args: &[&str]) -> Command {
+    match host() {
+        Some(host) => on(host, program, args),
+        None => {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        }
+    }
+}
+
+/// `program args...` on `host`: `wsl` or `wsl:DISTRO` is WSL on this (Windows) machine
+fn on(host: &str, program: &str, args: &[&str]) -> Command {
+    let distro = host.strip_prefix("wsl:");
+    if host == "wsl" || distro.is_some() {
+        let mut cmd = Command::new("wsl");
+        if let Some(distro) = distro {
+            cmd.args(["--distribution", distro]);
+        }
+        // From the Linux home, as over SSH (relative paths like `.mbsyncrc`)
+        cmd.args(["--cd", "~", "--exec", program]).args(args);
+        return cmd;
+    }
+    let mut cmd = Command::new("ssh");
+    cmd.args(ssh_args(host)).arg(remote_line(program, args));
+    cmd
+}
+
+/// Options sharing one master connection across the many short calls of a preview session
+///
+/// Windows' OpenSSH can't multiplex, so there each call gets its own connection.
+fn ssh_args(host: &str) -> Vec<String> {
+    let shared: &[&str] = if cfg!(windows) {
+        &[]
+    } else {
+        &[
+            "-o",
+            "ControlMaster=auto",
+            "-o",
+            "ControlPath=~/.ssh/mu-%C",
+            "-o",
+            "ControlPersist=10m",
+        ]
+    };
+    shared
+        .iter()
+        .chain(&["-o", "BatchMode=yes", host, "--"])
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Command line for the remote shell, every word single-quoted
+fn remote_line(program: &str, args: &[&str]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Contents of a file (relative paths are under the remote home directory)
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    if host().is_none() {
+        return std::fs::read(path).with_context(|| format!("Failed to read {}", path));
+    }
+    let output = process::run(&mut command("cat", &["--", path])).context("Failed to run ssh")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to read {} on {}: {}",
+            path,
+            host().unwrap_or(""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_line() {
+        assert_eq!(
+            remote_line("notmuch", &["search", "from:o'brien and tag:inbox"]),
+            r"'notmuch' 'search' 'from:o'\''brien and tag:inbox'"
+        );
+        assert_eq!(ssh_args("home")[8..], ["home", "--"]);
+    }
+
+    #[test]
+    fn test_wsl_runs_without_a_shell() {
+        let cmd = on(
+            "wsl:Debian",
+            "notmuch",
+            &["search", "tag:inbox and from:o'brien"],
+        );
+        assert_eq!(cmd.get_program(), "wsl");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "--distribution",
+                "Debian",
+                "--cd",
+                "~",
+                "--exec",
+                "notmuch",
+                "search",
+                "tag:inbox and from:o'brien"
+            ]
+        );
+        assert_eq!(on("home", "notmuch", &[]).get_program(), "ssh");
+    }
+}
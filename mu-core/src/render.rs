@@ -0,0 +1,358 @@
+//! HTML to terminal rendering
+//!
+//! What to render and how ([`Options`], [`ContentType`]) and the dispatch on content type;
+//! the passes live in their own modules: see `html` and `message` for the conversions,
+//! `anchor` and `colorize` for link handling and coloring, `footer`, `signature` and `quote`
+//! for the tidying filters.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::hook::{self, Content, Point};
+use crate::{
+    anchor, cleanup, color, footer, html, ics, lines, message, patch, quote, renderer, sanitize,
+    signature, sniff, tracker, vcard,
+};
+
+/// How to treat input
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    Html,
+    Plain,
+    Calendar,
+    /// vCards, rendered as contact cards
+    Contact,
+    /// A whole RFC 822 message, rendered as its best part (see [`crate::mime::best_part`])
+    Message,
+    /// A patch or unified diff, colored instead of cleaned up
+    Patch,
+}
+
+impl ContentType {
+    /// From a MIME type like `text/html; charset=utf-8`; anything unknown is plain text
+    pub fn from_mime(mime: &str) -> Self {
+        let essence = mime
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        match essence.as_str() {
+            "text/html" | "application/xhtml+xml" => ContentType::Html,
+            "text/calendar" | "application/ics" => ContentType::Calendar,
+            "text/vcard" | "text/x-vcard" | "text/directory" => ContentType::Contact,
+            "message/rfc822" => ContentType::Message,
+            "text/x-diff" | "text/x-patch" => ContentType::Patch,
+            _ => ContentType::Plain,
+        }
+    }
+
+    /// Guess from how the text starts: a calendar, a vCard, a raw message, an HTML tag (a whole
+    /// document or a fragment), else a patch if it has a diff, or else plain text — HTML quoted
+    /// further down stays text
+    pub fn sniff(text: &str) -> Self {
+        let start = text.trim_start_matches(|c: char| c.is_whitespace() || c == '\u{FEFF}');
+        if ics::is_calendar(start) {
+            ContentType::Calendar
+        } else if vcard::is_vcard(start) {
+            ContentType::Contact
+        } else if sniff::is_raw_message(start) {
+            ContentType::Message
+        } else if sniff::starts_with_html(start) {
+            ContentType::Html
+        } else {
+            ContentType::Plain.or_patch(start)
+        }
+    }
+
+    /// A plain text part that carries a diff as [`ContentType::Patch`]
+    pub(crate) fn or_patch(self, text: &str) -> Self {
+        if self == ContentType::Plain && patch::is_patch(text) {
+            ContentType::Patch
+        } else {
+            self
+        }
+    }
+}
+
+/// What happens to URLs in rendered text
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Links {
+    /// Link targets and long bare URLs are dropped
+    #[default]
+    Strip,
+    /// URLs stay where they are
+    Keep,
+    /// HTML links become `text[1]`, their targets listed at the end; other URLs stay
+    Footnotes,
+    /// Long URLs become `[host/…]`
+    Shorten,
+}
+
+impl Links {
+    pub(crate) fn strip_urls(self) -> bool {
+        self == Links::Strip
+    }
+}
+
+/// What happens to signatures (see the `signature` module)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Signatures {
+    Keep,
+    /// Shown in the theme's `signature` color
+    #[default]
+    Dim,
+    Strip,
+}
+
+/// What rendering produces
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Format {
+    /// Terminal text in the theme's colors
+    #[default]
+    Ansi,
+    /// Markdown (HTML through the built-in converter), for glow and other viewers
+    Markdown,
+    /// Text without escape codes
+    Plain,
+    /// Sanitized HTML; other content in `<pre>` blocks, its colors kept
+    Html,
+}
+
+impl Format {
+    /// Whether the output is built from the whole document rather than streamed
+    pub(crate) fn is_whole(self) -> bool {
+        matches!(self, Format::Markdown | Format::Html)
+    }
+}
+
+/// How to render
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// What to produce
+    pub format: Format,
+    /// What happens to URLs
+    pub links: Links,
+    /// What happens to signatures
+    pub signatures: Signatures,
+    /// Leave quoted replies as they are instead of folding them to one line
+    pub show_quotes: bool,
+    /// End rendered HTML with a line counting the trackers removed
+    pub tracker_report: bool,
+    /// Keep only the main text of HTML and its headline (see the `reader` module)
+    pub reader: bool,
+    /// Leave legal disclaimers and configured boilerplate in (see the `footer` module)
+    pub keep_footers: bool,
+    /// End rendered HTML with the addresses its `mailto:` links point to
+    pub contacts: bool,
+    /// Start a raw message with its whole header block (see the `header` module)
+    pub headers: bool,
+}
+
+/// The filters tidying rendered text: its long URLs (when shortened), its legal footers, its
+/// signature, then its quoted replies
+pub(crate) fn tidiers(
+    options: &Options,
+    colors: bool,
+) -> (
+    cleanup::ShortUrls,
+    (footer::Footers<'static>, (signature::Filter, quote::Folder)),
+) {
+    let urls = cleanup::ShortUrls {
+        on: options.links == Links::Shorten,
+        colors,
+    };
+    (
+        urls,
+        (
+            footer::Footers::new(!options.keep_footers),
+            (
+                signature::Filter::new(options.signatures, colors),
+                quote::Folder::new(!options.show_quotes, colors),
+            ),
+        ),
+    )
+}
+
+/// `text` with its footers, signature and quoted replies treated as `options` say
+pub fn declutter(text: &str, options: &Options) -> String {
+    lines::apply(tidiers(options, true), text)
+}
+
+/// Render mail content to clean terminal text (for piping to glow/bat), guessing its type
+pub fn render(text: &str, options: &Options) -> Result<String> {
+    render_as(text, ContentType::sniff(text), options)
+}
+
+/// Render content of a known type, through the `pre-render` and `post-render` hooks
+pub fn render_as(text: &str, content_type: ContentType, options: &Options) -> Result<String> {
+    let _span = tracing::debug_span!("render", ?content_type, bytes = text.len()).entered();
+    if !hook::enabled(Point::PreRender) && !hook::enabled(Point::PostRender) {
+        return render_content(text, content_type, options);
+    }
+    let input = Content {
+        content_type,
+        text: text.to_string(),
+    };
+    // A veto renders nothing
+    let Some(input) = hook::run(Point::PreRender, input)? else {
+        return Ok(String::new());
+    };
+    let output = Content {
+        text: render_content(&input.text, input.content_type, options)?,
+        ..input
+    };
+    Ok(hook::run(Point::PostRender, output)?.map_or_else(String::new, |c| c.text))
+}
+
+pub(crate) fn render_content(
+    text: &str,
+    content_type: ContentType,
+    options: &Options,
+) -> Result<String> {
+    let rendered = match content_type {
+        // Calendar invites (mailcap text/calendar entry)
+        ContentType::Calendar => ics::render(text),
+        ContentType::Contact => vcard::render(text),
+        ContentType::Html => match options.format {
+            Format::Markdown => return html::markdown(text, options),
+            // Browsers open `mailto:` links themselves
+            Format::Html => {
+                let (html, _) = tracker::clean(text);
+                let html = anchor::flag_links(&html);
+                return Ok(sanitize::html(&html::article(&html, options)));
+            }
+            Format::Ansi | Format::Plain => html::render(text, options)?,
+        },
+        ContentType::Plain => declutter(&render_plain(text, options.links.strip_urls()), options),
+        // Its parts are converted on their own
+        ContentType::Message => return message::render(text.as_bytes(), options),
+        ContentType::Patch => patch::render(text),
+    };
+    Ok(convert(rendered, content_type, options.format))
+}
+
+/// Rendered text of `content_type` in `format`
+pub(crate) fn convert(text: String, content_type: ContentType, format: Format) -> String {
+    match format {
+        Format::Ansi => text,
+        Format::Plain => color::strip(&text).into_owned(),
+        Format::Markdown => match content_type {
+            ContentType::Patch => format!("```diff\n{}\n```", color::strip(text.trim_end())),
+            // Cards keep their borders
+            ContentType::Calendar | ContentType::Contact => {
+                format!("```\n{}\n```", color::strip(&text))
+            }
+            _ => color::strip(&text).into_owned(),
+        },
+        Format::Html => format!("<pre>{}</pre>", color::to_html(text.trim_end())),
+    }
+}
+
+/// The error for a message with nothing to render
+pub(crate) fn no_text() -> anyhow::Error {
+    anyhow::anyhow!("The message has no text/html, text/plain, text/calendar or text/vcard part")
+}
+
+/// Render HTML to uncolored plain text (for quoting and forwarding)
+pub fn plain_text(html: &str) -> Result<String> {
+    let _span = tracing::debug_span!("plain_text", bytes = html.len()).entered();
+    let text = renderer::convert(renderer::chain(), html, true)?;
+    Ok(cleanup::tidy(&text, true))
+}
+
+/// `text` in SGR style `sgr` (a [`theme`] role); stripped later for outputs without colors
+pub(crate) fn style(sgr: &str, text: impl std::fmt::Display) -> String {
+    format!("\x1b[{}m{}\x1b[0m", sgr, text)
+}
+
+fn render_plain(text: &str, strip_urls: bool) -> String {
+    if strip_urls {
+        cleanup::strip_long_urls(text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_plain() {
+        let input = "Hello world";
+        let output = render(input, &Options::default()).unwrap();
+        assert_eq!(output.trim(), "Hello world");
+    }
+
+    #[test]
+    fn test_from_mime() {
+        assert_eq!(
+            ContentType::from_mime("Text/HTML; charset=utf-8"),
+            ContentType::Html
+        );
+        assert_eq!(
+            ContentType::from_mime("text/calendar"),
+            ContentType::Calendar
+        );
+        assert_eq!(ContentType::from_mime("text/x-vcard"), ContentType::Contact);
+        assert_eq!(
+            ContentType::from_mime("message/rfc822"),
+            ContentType::Message
+        );
+        assert_eq!(ContentType::from_mime("text/x-diff"), ContentType::Patch);
+        assert_eq!(ContentType::from_mime("text/markdown"), ContentType::Plain);
+    }
+
+    #[test]
+    fn test_render_html() {
+        let input = "<html><body><p>Hello</p></body></html>";
+        let output = render(input, &Options::default()).unwrap();
+        assert!(output.contains("Hello"));
+    }
+
+    #[test]
+    fn test_formats() {
+        let options = |format| Options {
+            format,
+            ..Options::default()
+        };
+        let html = "<h1>Hi</h1><p onclick=\"go()\">Some <b>bold</b></p><script>go()</script>";
+        let plain = render_as(html, ContentType::Html, &options(Format::Plain)).unwrap();
+        assert!(plain.contains("bold") && !plain.contains('\x1b'));
+        assert_eq!(
+            render_as(html, ContentType::Html, &options(Format::Html)).unwrap(),
+            "<h1>Hi</h1><p>Some <b>bold</b></p>"
+        );
+        #[cfg(feature = "html")]
+        assert!(
+            render_as(html, ContentType::Html, &options(Format::Markdown))
+                .unwrap()
+                .contains("Some **bold**")
+        );
+        let diff = "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a<\n+b\n";
+        assert_eq!(
+            render_as(diff, ContentType::Patch, &options(Format::Markdown)).unwrap(),
+            format!("```diff\n{}```", diff)
+        );
+        let page = render_as(diff, ContentType::Patch, &options(Format::Html)).unwrap();
+        assert!(page.starts_with("<pre><span style=\"font-weight:bold\">--- a/x</span>"));
+        assert!(page.contains("-a&lt;"));
+    }
+
+    #[test]
+    fn test_plain_text_has_no_colors() {
+        let output = plain_text("<html><body><h1>Title</h1><p>Hello</p></body></html>").unwrap();
+        assert!(output.contains("Hello"));
+        assert!(!output.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_strip_urls() {
+        let input = "Check https://very-long-url.example.com/path/to/something here";
+        let output = cleanup::strip_long_urls(input);
+        assert!(!output.contains("https://"));
+    }
+}
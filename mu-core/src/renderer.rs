@@ -0,0 +1,254 @@
+//! The HTML renderer chain: dump tools tried in order, then the built-in converter
+//!
+//! An external renderer reads HTML on stdin and prints text. One that isn't installed,
+//! fails or hangs hands over to the next in `[render] chain`, so the same config works on
+//! machines with different tools.
+
+use anyhow::Result;
+use std::cell::Cell;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::{self, Config};
+use crate::error::Error;
+use crate::process;
+
+/// A wedged renderer hands over to the next instead of hanging the preview
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Renderers that work without configuring `args`
+pub const KNOWN: [&str; 5] = ["w3m", "lynx", "elinks", "native", "builtin"];
+
+/// Width when neither `--cols` nor the terminal says otherwise
+pub const DEFAULT_COLS: usize = 120;
+
+/// Stands for the render width in renderer arguments (defaults and `[render.args]`)
+const COLS: &str = "{cols}";
+
+/// Default arguments: a dump of HTML on stdin, `{cols}` wide, without link lists
+fn default_args(name: &str) -> Option<&'static [&'static str]> {
+    Some(match name {
+        "w3m" => &["-dump", "-T", "text/html", "-cols", COLS],
+        "lynx" => &[
+            "-dump",
+            "-stdin",
+            "-force_html",
+            "-nolist",
+            "-width={cols}",
+            "-display_charset=utf-8",
+        ],
+        "elinks" => &[
+            "-dump",
+            "-force-html",
+            "-no-references",
+            "-no-numbering",
+            "-dump-width",
+            COLS,
+            "-dump-charset",
+            "utf-8",
+        ],
+        _ => return None,
+    })
+}
+
+thread_local! {
+    /// `--cols`, or the width a preview worker was asked for, on this thread
+    static WIDTH: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Render `cols` wide on this thread (None: detect)
+pub fn set_cols(cols: Option<usize>) {
+    WIDTH.set(cols);
+}
+
+/// The render width: `--cols`, else the fzf preview pane, `$COLUMNS` or the terminal
+pub fn cols() -> usize {
+    WIDTH.get().or_else(detect_cols).unwrap_or(DEFAULT_COLS)
+}
+
+/// The width of the fzf preview pane, `$COLUMNS` or the terminal on stdout
+pub fn detect_cols() -> Option<usize> {
+    ["FZF_PREVIEW_COLUMNS", "COLUMNS"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok()?.trim().parse().ok())
+        .filter(|&cols| cols > 0)
+        .or_else(|| {
+            let (width, _) = terminal_size::terminal_size()?;
+            Some(usize::from(width.0))
+        })
+}
+
+/// One step of the chain
+#[derive(Debug, Clone, PartialEq)]
+pub enum Renderer {
+    /// A program reading HTML on stdin and printing text
+    External { program: String, args: Vec<String> },
+    /// The native HTML-to-text layout (the `native` feature)
+    Native,
+    /// The built-in HTML-to-markdown converter (the `html` feature)
+    Builtin,
+}
+
+impl Renderer {
+    /// The renderer called `name`, with `args` replacing its defaults; None for a program
+    /// mu doesn't know given no `args`
+    pub fn new(name: &str, args: Option<&[String]>) -> Option<Self> {
+        match name {
+            "native" => return Some(Renderer::Native),
+            "builtin" => return Some(Renderer::Builtin),
+            _ => {}
+        }
+        let args = match args {
+            Some(args) => args.to_vec(),
+            None => default_args(name)?.iter().map(|a| a.to_string()).collect(),
+        };
+        Some(Renderer::External {
+            program: name.to_string(),
+            args,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Renderer::External { program, .. } => program,
+            Renderer::Native => "native",
+            Renderer::Builtin => "builtin",
+        }
+    }
+
+    /// The command to run, None for the native layout and the built-in converter
+    pub fn command(&self) -> Option<Command> {
+        let Renderer::External { program, args } = self else {
+            return None;
+        };
+        let cols = cols().to_string();
+        let mut cmd = Command::new(program);
+        cmd.args(args.iter().map(|arg| arg.replace(COLS, &cols)));
+        Some(cmd)
+    }
+
+    /// Whether it can run here: the program is on PATH, or the layout or converter was
+    /// compiled in
+    pub fn installed(&self) -> bool {
+        match self {
+            Renderer::External { program, .. } => process::which(program).is_some(),
+            Renderer::Native => cfg!(feature = "native"),
+            Renderer::Builtin => cfg!(feature = "html"),
+        }
+    }
+
+    /// `html` as text, before cleanup
+    pub fn convert(&self, html: &str, strip_urls: bool) -> Result<String> {
+        let Some(cmd) = self.command() else {
+            return match self {
+                Renderer::Native => crate::html::native(html, strip_urls),
+                _ => crate::html::fallback(html, strip_urls),
+            };
+        };
+        let output = process::block_on(process::output(cmd, Some(html.as_bytes()), TIMEOUT))??;
+        if !output.status.success() {
+            return Err(Error::command_failed(self.name(), &output.stderr).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+static CHAIN: OnceLock<Vec<Renderer>> = OnceLock::new();
+
+/// Use `chain` for this process (before anything is rendered; later calls are ignored)
+pub fn set(chain: Vec<Renderer>) {
+    let _ = CHAIN.set(chain);
+}
+
+/// The chain from the config (loaded on first use)
+pub fn chain() -> &'static [Renderer] {
+    CHAIN.get_or_init(|| from_config(&Config::load().map(|c| c.render).unwrap_or_default()))
+}
+
+/// The `[render]` chain; programs mu doesn't know and that have no `args` are left out
+pub fn from_config(cfg: &config::Render) -> Vec<Renderer> {
+    cfg.chain
+        .iter()
+        .filter_map(|name| {
+            let renderer = Renderer::new(name, cfg.args.get(name).map(Vec::as_slice));
+            if renderer.is_none() {
+                tracing::warn!(name = %name, "renderer has no `[render.args]`, skipped");
+            }
+            renderer
+        })
+        .collect()
+}
+
+/// `html` as text from the first renderer in `chain` that works
+pub fn convert(chain: &[Renderer], html: &str, strip_urls: bool) -> Result<String> {
+    let mut last = None;
+    for renderer in chain {
+        match renderer.convert(html, strip_urls) {
+            Ok(text) => return Ok(text),
+            Err(e) => {
+                tracing::debug!(renderer = renderer.name(), error = %e, "trying the next renderer");
+                last = Some(e);
+            }
+        }
+    }
+    Err(exhausted(chain, last))
+}
+
+/// The error once every renderer in `chain` failed, `last` being the last failure
+pub fn exhausted(chain: &[Renderer], last: Option<anyhow::Error>) -> anyhow::Error {
+    let names: Vec<&str> = chain.iter().map(Renderer::name).collect();
+    match last {
+        Some(e) => e.context(format!(
+            "No HTML renderer worked (tried {})",
+            names.join(", ")
+        )),
+        None => anyhow::anyhow!("No HTML renderer configured (`[render] chain` is empty)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{Replay, scoped};
+
+    #[test]
+    fn test_from_config() {
+        let cfg: config::Render = toml::from_str(
+            "chain = [\"lynx\", \"links\", \"pandoc\", \"builtin\"]\n[args]\npandoc = [\"-t\", \"plain\"]\n",
+        )
+        .unwrap();
+        let chain = from_config(&cfg);
+        let names: Vec<&str> = chain.iter().map(Renderer::name).collect();
+        assert_eq!(names, ["lynx", "pandoc", "builtin"]);
+        let args: Vec<_> = chain[1]
+            .command()
+            .unwrap()
+            .get_args()
+            .map(|a| a.to_owned())
+            .collect();
+        assert_eq!(args, ["-t", "plain"]);
+        assert!(chain[2].command().is_none());
+    }
+
+    #[test]
+    fn test_convert_falls_through() {
+        let chain = from_config(&config::Render::default());
+        set_cols(Some(72));
+        // w3m fails, lynx has no recording (as if missing), elinks works
+        let replay = Replay::parse(concat!(
+            r#"{"program":"w3m","args":["-dump","-T","text/html","-cols","72"],"status":1,"stderr":"bad"}"#,
+            "\n",
+            r#"{"program":"elinks","args":["-dump","-force-html","-no-references","-no-numbering","-dump-width","72","-dump-charset","utf-8"],"stdout":"Hello\n"}"#,
+        ))
+        .unwrap();
+        let text = scoped(replay, || convert(&chain, "<p>Hello</p>", true)).unwrap();
+        assert_eq!(text, "Hello\n");
+
+        let err = scoped(Replay::new(Vec::new()), || {
+            convert(&chain[..2], "<p>x</p>", true)
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "No HTML renderer worked (tried w3m, lynx)");
+    }
+}
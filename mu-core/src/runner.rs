@@ -0,0 +1,291 @@
+//! Who answers for external commands: the system, recorded outputs (`--simulate`) or a test
+//!
+//! [`process`](crate::process) asks the active [`Runner`] before starting anything, so
+//! notmuch, mbsync, w3m and fzf can be replayed from a file of recordings (made with
+//! `--record`) without a mail setup, and tests can script their outputs.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Output};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Stands in for running commands
+pub trait Runner: Send + Sync {
+    /// The outcome of `cmd`, or None to run it for real
+    fn run(&self, cmd: &Command) -> Option<io::Result<Output>>;
+
+    /// A command that was run for real finished with `output`
+    fn ran(&self, _cmd: &Command, _output: &Output) {}
+
+    /// Commands are answered without running, so nothing else should change either
+    fn simulates(&self) -> bool {
+        false
+    }
+}
+
+/// One command's recorded outcome (a line of a `--simulate`/`--record` file)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Exit code
+    #[serde(default)]
+    pub status: i32,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+impl Recording {
+    /// `cmd` with the output it gave
+    pub fn new(cmd: &Command, output: &Output) -> Self {
+        let (program, args) = words(cmd);
+        Self {
+            program,
+            args,
+            status: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+
+    fn output(&self) -> Output {
+        Output {
+            status: exit_status(self.status),
+            stdout: self.stdout.clone().into_bytes(),
+            stderr: self.stderr.clone().into_bytes(),
+        }
+    }
+}
+
+/// Answers every command from recordings, in order; unrecorded commands fail
+///
+/// A command recorded more than once gets its recordings one after the other, then the last
+/// again.
+pub struct Replay {
+    recordings: Vec<Recording>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl Replay {
+    pub fn new(recordings: Vec<Recording>) -> Self {
+        let used = Mutex::new(vec![false; recordings.len()]);
+        Self { recordings, used }
+    }
+
+    /// Recordings from a JSON-lines file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Bad recordings in {}", path.display()))
+    }
+
+    /// Recordings from JSON lines, one [`Recording`] each
+    pub fn parse(content: &str) -> Result<Self> {
+        let recordings = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| serde_json::from_str(line).with_context(|| format!("line {}", i + 1)))
+            .collect::<Result<_>>()?;
+        Ok(Self::new(recordings))
+    }
+}
+
+impl Runner for Replay {
+    fn run(&self, cmd: &Command) -> Option<io::Result<Output>> {
+        let (program, args) = words(cmd);
+        let matching: Vec<usize> = (0..self.recordings.len())
+            .filter(|&i| self.recordings[i].program == program && self.recordings[i].args == args)
+            .collect();
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(&i) = matching.iter().find(|&&i| !used[i]).or(matching.last()) else {
+            let line = std::iter::once(program).chain(args).collect::<Vec<_>>();
+            return Some(Err(io::Error::other(format!(
+                "no recorded output for `{}`",
+                line.join(" ")
+            ))));
+        };
+        used[i] = true;
+        Some(Ok(self.recordings[i].output()))
+    }
+
+    fn simulates(&self) -> bool {
+        true
+    }
+}
+
+/// Runs commands for real, appending each outcome to a JSON-lines file for [`Replay`]
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Runner for Recorder {
+    fn run(&self, _cmd: &Command) -> Option<io::Result<Output>> {
+        None
+    }
+
+    fn ran(&self, cmd: &Command, output: &Output) {
+        let Ok(line) = serde_json::to_string(&Recording::new(cmd, output)) else {
+            return;
+        };
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            tracing::warn!(error = %e, "failed to record a command");
+        }
+    }
+}
+
+static GLOBAL: OnceLock<Box<dyn Runner>> = OnceLock::new();
+
+thread_local! {
+    static SCOPED: RefCell<Option<Arc<dyn Runner>>> = const { RefCell::new(None) };
+}
+
+/// Use `runner` for this process (before any command runs; later calls are ignored)
+pub fn set(runner: Box<dyn Runner>) {
+    let _ = GLOBAL.set(runner);
+}
+
+/// Run `f` with `runner` answering the commands started on this thread (for tests)
+pub fn scoped<T>(runner: impl Runner + 'static, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED.with(|s| s.borrow_mut().replace(Arc::new(runner)));
+    // Put the previous runner back even if `f` panics
+    struct Restore(Option<Arc<dyn Runner>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            SCOPED.with(|s| *s.borrow_mut() = previous);
+        }
+    }
+    let _restore = Restore(previous);
+    f()
+}
+
+fn with_active<T>(f: impl FnOnce(&dyn Runner) -> Option<T>) -> Option<T> {
+    if let Some(runner) = SCOPED.with(|s| s.borrow().clone()) {
+        return f(runner.as_ref());
+    }
+    GLOBAL.get().and_then(|runner| f(runner.as_ref()))
+}
+
+/// The active runner's outcome for `cmd`, if it doesn't want it run
+pub fn intercept(cmd: &Command) -> Option<io::Result<Output>> {
+    let output = with_active(|runner| runner.run(cmd))?;
+    tracing::debug!(ok = output.is_ok(), "simulated");
+    Some(output)
+}
+
+/// Whether this is a simulated run (`--simulate`, a test's replay): steps that change files
+/// or state outside commands are skipped
+pub fn simulating() -> bool {
+    with_active(|runner| runner.simulates().then_some(())).is_some()
+}
+
+/// Tell the active runner `cmd` ran for real
+pub fn record(cmd: &Command, output: &Output) {
+    with_active(|runner| {
+        runner.ran(cmd, output);
+        None::<()>
+    });
+}
+
+fn words(cmd: &Command) -> (String, Vec<String>) {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let args = cmd
+        .get_args()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+    (program, args)
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(args: &[&str], stdout: &str) -> Recording {
+        Recording {
+            program: "notmuch".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            status: 0,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_replay_in_order_then_last() {
+        let replay = Replay::new(vec![
+            recording(&["count"], "1"),
+            recording(&["new"], ""),
+            recording(&["count"], "2"),
+        ]);
+        let mut count = Command::new("notmuch");
+        count.arg("count");
+        let stdout = |cmd: &Command| replay.run(cmd).unwrap().unwrap().stdout;
+        assert_eq!(stdout(&count), b"1");
+        assert_eq!(stdout(&count), b"2");
+        assert_eq!(stdout(&count), b"2");
+        let err = replay.run(&Command::new("mbsync")).unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "no recorded output for `mbsync`");
+    }
+
+    #[test]
+    fn test_recording_round_trip() {
+        let line = r#"{"program":"mbsync","args":["-V","work"],"status":1,"stderr":"auth"}"#;
+        let recording: Recording = serde_json::from_str(line).unwrap();
+        let output = recording.output();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(output.stderr, b"auth");
+        let mut cmd = Command::new("mbsync");
+        cmd.args(["-V", "work"]);
+        let again = Recording::new(&cmd, &output);
+        assert_eq!(again.args, ["-V", "work"]);
+        assert_eq!(again.status, 1);
+    }
+
+    #[test]
+    fn test_scoped_is_per_thread() {
+        let replay = Replay::new(vec![recording(&[], "scripted")]);
+        assert!(!simulating());
+        scoped(replay, || {
+            assert!(simulating());
+            assert!(intercept(&Command::new("notmuch")).is_some());
+            std::thread::spawn(|| assert!(intercept(&Command::new("notmuch")).is_none()))
+                .join()
+                .unwrap();
+        });
+        assert!(intercept(&Command::new("notmuch")).is_none());
+    }
+}
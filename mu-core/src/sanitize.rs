@@ -0,0 +1,108 @@
+//! HTML mail made safe to open in a browser: no scripts, frames, plugins, forms or event
+//! handlers, and no `javascript:` links
+//!
+//! Trackers are [`tracker`](crate::tracker)'s job; this only takes out what runs or submits.
+
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+use crate::cleanup::re;
+
+/// Elements dropped with everything inside them
+static ACTIVE: LazyLock<Regex> = LazyLock::new(|| {
+    re(concat!(
+        r"(?is)<script\b.*?</script\s*>|<iframe\b.*?</iframe\s*>|<object\b.*?</object\s*>",
+        r"|<applet\b.*?</applet\s*>|<frameset\b.*?</frameset\s*>",
+    ))
+});
+
+/// Tags dropped on their own (form fields keep the text around them)
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    re(concat!(
+        r"(?i)</?(?:script|iframe|object|applet|embed|frame|frameset|base|link|meta|form",
+        r"|input|button|textarea|select|option)\b[^>]*>",
+    ))
+});
+
+/// An opening tag, its attributes in group 1
+static OPENING: LazyLock<Regex> = LazyLock::new(|| re(r"(?s)<[a-zA-Z][\w:-]*(\s[^>]*)>"));
+
+/// An attribute and its value, quoted or not
+static ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| re(r#"(?i)\s([\w:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#));
+
+/// Sanitized `html`
+pub fn html(html: &str) -> String {
+    let html = ACTIVE.replace_all(html, "");
+    let html = TAG.replace_all(&html, "");
+    OPENING
+        .replace_all(&html, |caps: &Captures| {
+            let attributes = &caps[1];
+            let kept = ATTRIBUTE.replace_all(attributes, |attr: &Captures| {
+                let value = (2..=4).find_map(|i| attr.get(i)).map_or("", |m| m.as_str());
+                if is_safe(&attr[1], value) {
+                    attr[0].to_string()
+                } else {
+                    String::new()
+                }
+            });
+            caps[0].replacen(attributes, &kept, 1)
+        })
+        .into_owned()
+}
+
+/// Whether attribute `name` may keep `value`
+fn is_safe(name: &str, value: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    if name.starts_with("on") {
+        return false;
+    }
+    // Browsers ignore whitespace and control characters inside a scheme
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let value = value.replace("&#58;", ":").replace("&colon;", ":");
+    match name.as_str() {
+        "href" | "src" | "action" | "formaction" | "xlink:href" | "background" | "srcset" => {
+            !["javascript:", "vbscript:", "data:text/html"]
+                .iter()
+                .any(|scheme| value.starts_with(scheme))
+        }
+        "style" => !value.contains("expression(") && !value.contains("javascript:"),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_content() {
+        let mail = "<p onclick=\"steal()\" class=x>Hi<script>alert(1)</script></p>\
+<iframe src=\"https://x.example\">frame</iframe><form action=/x>Name <input name=n></form>";
+        assert_eq!(html(mail), "<p class=x>Hi</p>Name ");
+        assert_eq!(
+            html("<embed src=x.swf><a href=\"javascript:x()\">x</a>"),
+            "<a>x</a>"
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            html("<a href=\" java\tscript:go()\">x</a> <a href='https://x.example'>y</a>"),
+            "<a>x</a> <a href='https://x.example'>y</a>"
+        );
+        assert_eq!(
+            html("<img src=\"data:image/png;base64,AA\" alt=a>"),
+            "<img src=\"data:image/png;base64,AA\" alt=a>"
+        );
+        assert_eq!(
+            html("<div style=\"width: expression(go())\">z</div>"),
+            "<div>z</div>"
+        );
+    }
+}
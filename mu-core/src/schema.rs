@@ -0,0 +1,496 @@
+//! The shape of a config type, read off its serde impls, and TOML checked against it
+//!
+//! [`Shape::of`] drives a type's `Deserialize` with a probing deserializer, so the known
+//! keys, value types and enum choices are exactly what the structs accept; [`check`] then
+//! reports every unknown key, wrong type and bad choice with its place in the file.
+
+use serde::de::value::{Error as ProbeError, StrDeserializer};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use std::ops::Range;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+/// What a TOML value may be
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// A struct: its keys and their shapes
+    Table(Vec<(&'static str, Shape)>),
+    /// Any keys, every value the same shape
+    Map(Box<Shape>),
+    List(Box<Shape>),
+    /// One of these strings
+    Choice(&'static [&'static str]),
+    /// `string`, `integer`, `float` or `boolean`
+    Scalar(&'static str),
+    Any,
+}
+
+impl Shape {
+    /// The shape `T` deserializes from (`Any` where it can't be told)
+    pub fn of<T: DeserializeOwned>() -> Self {
+        let mut shape = Shape::Any;
+        if let Err(e) = T::deserialize(Probe(&mut shape)) {
+            tracing::debug!(error = %e, "schema probe failed");
+            return Shape::Any;
+        }
+        shape
+    }
+
+    /// The shape at a dotted path (`muttrc.bin`; list items are taken as a whole)
+    pub fn at(&self, path: &str) -> Option<&Shape> {
+        path.split('.').try_fold(self, |shape, key| match shape {
+            Shape::Table(fields) => fields.iter().find(|(k, _)| *k == key).map(|(_, s)| s),
+            Shape::Map(inner) => Some(inner.as_ref()),
+            Shape::List(inner) => inner.at(key),
+            _ => None,
+        })
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Shape::Table(_) | Shape::Map(_) => "a table".to_string(),
+            Shape::List(_) => "an array".to_string(),
+            Shape::Choice(_) => "a string".to_string(),
+            Shape::Scalar(kind @ "integer") => format!("an {}", kind),
+            Shape::Scalar(kind) => format!("a {}", kind),
+            Shape::Any => "anything".to_string(),
+        }
+    }
+}
+
+/// Something wrong in a config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Byte range in the file
+    pub span: Range<usize>,
+    pub message: String,
+    /// What was probably meant
+    pub suggestion: Option<String>,
+}
+
+impl Problem {
+    pub fn new(span: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    /// Line and column (1-based, in characters) where it starts in `text`
+    pub fn position(&self, text: &str) -> (usize, usize) {
+        let start = text.floor_char_boundary(self.span.start.min(text.len()));
+        let before = &text[..start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    fn from_toml(e: &toml::de::Error) -> Self {
+        Self::new(e.span().unwrap_or(0..0), e.message().trim())
+    }
+}
+
+/// Every problem in `text` as config for `T`, in file order
+///
+/// Syntax errors stop the check; otherwise unknown keys, wrong types and bad choices are
+/// all reported, and, when there are none, what `T` itself rejects (missing keys…).
+pub fn check<T: DeserializeOwned>(text: &str) -> Vec<Problem> {
+    let table = match DeTable::parse(text) {
+        Ok(table) => table,
+        Err(e) => return vec![Problem::from_toml(&e)],
+    };
+    let mut problems = Vec::new();
+    table_problems(table.get_ref(), &Shape::of::<T>(), "", &mut problems);
+    if problems.is_empty()
+        && let Err(e) = toml::from_str::<T>(text)
+    {
+        problems.push(Problem::from_toml(&e));
+    }
+    problems.sort_by_key(|p| p.span.start);
+    problems
+}
+
+/// Where the value at a dotted path is (`filter.rules.2.matches`)
+pub fn span_of(text: &str, path: &str) -> Option<Range<usize>> {
+    let table = DeTable::parse(text).ok()?;
+    let mut keys = path.split('.');
+    let first = keys.next()?;
+    let mut value = table
+        .get_ref()
+        .iter()
+        .find(|(k, _)| *k.get_ref() == first)?
+        .1;
+    for key in keys {
+        value = match value.get_ref() {
+            DeValue::Table(t) => t.iter().find(|(k, _)| *k.get_ref() == key)?.1,
+            DeValue::Array(items) => items.get(key.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value.span())
+}
+
+/// The `candidates` entry closest to `word`, if it's close enough to be a typo
+pub fn closest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|c| (strsim::levenshtein(word, c), *c))
+        .filter(|(distance, c)| *distance <= (c.len() / 3).max(1) || c.contains(word))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+fn table_problems(table: &DeTable, shape: &Shape, path: &str, problems: &mut Vec<Problem>) {
+    for (key, value) in table.iter() {
+        let name = if path.is_empty() {
+            key.get_ref().to_string()
+        } else {
+            format!("{}.{}", path, key.get_ref())
+        };
+        match shape {
+            Shape::Table(fields) => match fields.iter().find(|(k, _)| *k == key.get_ref()) {
+                Some((_, field)) => value_problems(value, field, &name, problems),
+                None => {
+                    let known: Vec<&str> = fields.iter().map(|(k, _)| *k).collect();
+                    let section = if path.is_empty() {
+                        "at the top level".to_string()
+                    } else {
+                        format!("in [{}]", path)
+                    };
+                    problems.push(Problem {
+                        suggestion: closest(key.get_ref(), &known).map(String::from),
+                        ..Problem::new(key.span(), format!("unknown key `{}` {}", key, section))
+                    });
+                }
+            },
+            Shape::Map(inner) => value_problems(value, inner, &name, problems),
+            _ => {}
+        }
+    }
+}
+
+fn value_problems(
+    value: &Spanned<DeValue>,
+    shape: &Shape,
+    path: &str,
+    problems: &mut Vec<Problem>,
+) {
+    match (shape, value.get_ref()) {
+        (Shape::Any, _) => {}
+        (Shape::Table(_) | Shape::Map(_), DeValue::Table(table)) => {
+            table_problems(table, shape, path, problems)
+        }
+        (Shape::List(inner), DeValue::Array(items)) => {
+            for item in items.iter() {
+                value_problems(item, inner, path, problems);
+            }
+        }
+        (Shape::Choice(choices), DeValue::String(s)) => {
+            if !choices.contains(&s.as_ref()) {
+                problems.push(Problem {
+                    suggestion: closest(s, choices).map(String::from),
+                    ..Problem::new(
+                        value.span(),
+                        format!("`{}` should be one of: {}", path, choices.join(", ")),
+                    )
+                });
+            }
+        }
+        (Shape::Scalar("float"), DeValue::Integer(_)) => {}
+        (Shape::Scalar(kind), found) if found.type_str() == *kind => {}
+        (_, found) => problems.push(Problem::new(
+            value.span(),
+            format!(
+                "`{}` should be {}, not {}",
+                path,
+                shape.describe(),
+                found.type_str()
+            ),
+        )),
+    }
+}
+
+/// A deserializer that hands every visitor placeholder values, noting what was asked for
+struct Probe<'a>(&'a mut Shape);
+
+macro_rules! scalar {
+    ($($method:ident => $kind:literal, $visit:ident($value:expr);)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+            *self.0 = Shape::Scalar($kind);
+            visitor.$visit($value)
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for Probe<'_> {
+    type Error = ProbeError;
+
+    scalar! {
+        deserialize_bool => "boolean", visit_bool(false);
+        deserialize_i8 => "integer", visit_i64(0);
+        deserialize_i16 => "integer", visit_i64(0);
+        deserialize_i32 => "integer", visit_i64(0);
+        deserialize_i64 => "integer", visit_i64(0);
+        deserialize_u8 => "integer", visit_u64(0);
+        deserialize_u16 => "integer", visit_u64(0);
+        deserialize_u32 => "integer", visit_u64(0);
+        deserialize_u64 => "integer", visit_u64(0);
+        deserialize_f32 => "float", visit_f64(0.0);
+        deserialize_f64 => "float", visit_f64(0.0);
+        deserialize_char => "string", visit_char(' ');
+        deserialize_str => "string", visit_str("");
+        deserialize_string => "string", visit_str("");
+        deserialize_bytes => "string", visit_bytes(&[]);
+        deserialize_byte_buf => "string", visit_bytes(&[]);
+        deserialize_identifier => "string", visit_str("");
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        *self.0 = Shape::Any;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        let mut item = Shape::Any;
+        let value = visitor.visit_seq(One {
+            shape: &mut item,
+            done: false,
+        })?;
+        *self.0 = Shape::List(Box::new(item));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        let mut value_shape = Shape::Any;
+        let value = visitor.visit_map(OneEntry {
+            shape: &mut value_shape,
+            done: false,
+        })?;
+        *self.0 = Shape::Map(Box::new(value_shape));
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        let mut shapes: Vec<_> = fields.iter().map(|f| (*f, Shape::Any)).collect();
+        let value = visitor.visit_map(Fields {
+            shapes: &mut shapes,
+            next: 0,
+        })?;
+        *self.0 = Shape::Table(shapes);
+        Ok(value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        *self.0 = Shape::Choice(variants);
+        let first: StrDeserializer<ProbeError> = variants
+            .first()
+            .copied()
+            .unwrap_or_default()
+            .into_deserializer();
+        visitor.visit_enum(first)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_unit()
+    }
+}
+
+/// A sequence of one probed item
+struct One<'a> {
+    shape: &'a mut Shape,
+    done: bool,
+}
+
+impl<'de> SeqAccess<'de> for One<'_> {
+    type Error = ProbeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ProbeError> {
+        if std::mem::replace(&mut self.done, true) {
+            return Ok(None);
+        }
+        seed.deserialize(Probe(self.shape)).map(Some)
+    }
+}
+
+/// A map of one entry with a probed value
+struct OneEntry<'a> {
+    shape: &'a mut Shape,
+    done: bool,
+}
+
+impl<'de> MapAccess<'de> for OneEntry<'_> {
+    type Error = ProbeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ProbeError> {
+        if std::mem::replace(&mut self.done, true) {
+            return Ok(None);
+        }
+        seed.deserialize(Probe(&mut Shape::Any)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ProbeError> {
+        seed.deserialize(Probe(self.shape))
+    }
+}
+
+/// A struct's fields, each with a probed value
+struct Fields<'a> {
+    shapes: &'a mut [(&'static str, Shape)],
+    next: usize,
+}
+
+impl<'de> MapAccess<'de> for Fields<'_> {
+    type Error = ProbeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ProbeError> {
+        match self.shapes.get(self.next) {
+            Some((name, _)) => seed.deserialize(name.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ProbeError> {
+        let next = self.next;
+        self.next += 1;
+        match self.shapes.get_mut(next) {
+            Some((_, shape)) => seed.deserialize(Probe(shape)),
+            None => Err(de::Error::custom("value without a key")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_shape_of_config() {
+        let shape = Shape::of::<Config>();
+        assert_eq!(shape.at("muttrc.bin"), Some(&Shape::Scalar("string")));
+        assert_eq!(shape.at("push.desktop"), Some(&Shape::Scalar("boolean")));
+        assert_eq!(
+            shape.at("sweep.action"),
+            Some(&Shape::Choice(&["archive", "delete", "keep"]))
+        );
+        assert_eq!(
+            shape.at("accounts.imap_url"),
+            Some(&Shape::Scalar("string"))
+        );
+        assert_eq!(
+            shape.at("locale.messages"),
+            Some(&Shape::Map(Box::new(Shape::Scalar("string"))))
+        );
+    }
+
+    #[test]
+    fn test_check_reports_everything_with_places() {
+        let text = "[muttrc]\nserch_key = \"x\"\ndisplay_filter = \"yes\"\n\n[[accounts]]\nname = \"work\"\nimap_urll = \"imaps://x\"\n\n[sweep]\naction = \"archve\"\n";
+        let problems = check::<Config>(text);
+        let found: Vec<_> = problems
+            .iter()
+            .map(|p| (p.position(text), p.suggestion.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ((2, 1), Some("search_key")),
+                ((3, 18), None),
+                ((7, 1), Some("imap_url")),
+                ((10, 10), Some("archive")),
+            ]
+        );
+        assert_eq!(
+            problems[1].message,
+            "`muttrc.display_filter` should be a boolean, not string"
+        );
+    }
+
+    #[test]
+    fn test_check_falls_back_to_serde() {
+        let problems = check::<Config>("[[accounts]]\nmaildir = \"x\"\n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("missing field `name`"));
+        let problems = check::<Config>("[muttrc\n");
+        assert_eq!(problems[0].position("[muttrc\n"), (1, 8));
+    }
+
+    #[test]
+    fn test_span_of() {
+        let text = "[[filter.rules]]\nfolder = \"a\"\n\n[[filter.rules]]\nmatches = \"(\"\n";
+        let span = span_of(text, "filter.rules.1.matches").unwrap();
+        assert_eq!(&text[span], "\"(\"");
+    }
+}
@@ -0,0 +1,246 @@
+//! Signatures in rendered mail: what follows a `-- ` line, or a closing block of contact
+//! details (phone numbers, addresses, logos), dimmed or dropped
+//!
+//! Lines pass through a window of [`WINDOW`] lines, so a signature is found without holding
+//! the whole message: a delimiter leaving the window has seen all its signature can span.
+
+use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+
+use crate::lines::LineFilter;
+use crate::render::{self, Signatures};
+use crate::{cleanup, color, quote, theme};
+
+/// Lines a signature may have after its delimiter
+const MAX_LINES: usize = 20;
+
+/// Lines held back
+const WINDOW: usize = MAX_LINES + 1;
+
+/// Lines longer than this are prose, not contact details
+const PROSE_CHARS: usize = 72;
+
+/// Phone numbers, email and web addresses, logos, street addresses and social profiles
+static CONTACT: LazyLock<Regex> = LazyLock::new(|| {
+    cleanup::re(concat!(
+        r"(?i)\+?\(?\d[\d\s().-]{7,}\d",
+        r"|[\w.+-]+@[\w-]+\.\w",
+        r"|\b(?:https?://|www\.)\S",
+        r"|\[(?:logo|image|cid)\b",
+        r"|\b\d+\s+\w+(?:\s\w+)?\s+(?:street|st|avenue|ave|road|rd|blvd|lane|drive|suite)\b",
+        r"|\b(?:linkedin|twitter|facebook|instagram)\b",
+    ))
+});
+
+/// Finds the signature in lines pushed one at a time, handing back lines once settled
+#[derive(Debug)]
+pub(crate) struct Filter {
+    mode: Signatures,
+    colors: bool,
+    pending: VecDeque<String>,
+    /// Signature lines still to come
+    left: usize,
+    found: bool,
+}
+
+impl Filter {
+    /// A filter for `mode`, dimming in color only if `colors`
+    pub(crate) fn new(mode: Signatures, colors: bool) -> Self {
+        Self {
+            mode,
+            colors,
+            pending: VecDeque::new(),
+            left: 0,
+            found: false,
+        }
+    }
+
+    fn settle(&mut self, line: String) -> String {
+        if self.left == 0 {
+            return line;
+        }
+        self.left -= 1;
+        self.sign(&line)
+    }
+
+    /// A signature line: dropped, or without its colors and dimmed
+    fn sign(&self, line: &str) -> String {
+        if self.mode == Signatures::Strip {
+            return String::new();
+        }
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let plain = color::strip(body);
+        if self.colors && !plain.is_empty() {
+            format!(
+                "{}{}",
+                render::style(&theme::get().signature, plain),
+                ending
+            )
+        } else {
+            format!("{}{}", plain, ending)
+        }
+    }
+}
+
+impl LineFilter for Filter {
+    fn push(&mut self, line: String) -> String {
+        if self.mode == Signatures::Keep {
+            return line;
+        }
+        self.pending.push_back(line);
+        let mut out = String::new();
+        while self.pending.len() > WINDOW {
+            let Some(line) = self.pending.pop_front() else {
+                break;
+            };
+            if !self.found
+                && is_delimiter(&line)
+                && let Some(n) = delimited(&self.pending)
+            {
+                self.left = n + 1;
+                self.found = true;
+            }
+            out.push_str(&self.settle(line));
+        }
+        out
+    }
+
+    fn finish(mut self) -> String {
+        let mut rest: Vec<String> = self.pending.drain(..).collect();
+        let settled = self.left.min(rest.len());
+        let mut out: String = rest
+            .drain(..settled)
+            .map(|line| self.settle(line))
+            .collect();
+        let signature = if self.found {
+            None
+        } else {
+            (0..rest.len())
+                .filter(|&i| is_delimiter(&rest[i]))
+                .find_map(|i| delimited(&rest[i + 1..]).map(|n| i..i + 1 + n))
+                .or_else(|| trailing(&rest).map(|start| start..rest.len()))
+        };
+        for (i, line) in rest.into_iter().enumerate() {
+            if signature.as_ref().is_some_and(|range| range.contains(&i)) {
+                out.push_str(&self.sign(&line));
+            } else {
+                out.push_str(&line);
+            }
+        }
+        out
+    }
+}
+
+/// `-- ` (or `--`, once trailing spaces are trimmed) on a line of its own
+fn is_delimiter(line: &str) -> bool {
+    matches!(
+        color::strip(line).trim_end_matches(['\r', '\n']),
+        "-- " | "--"
+    )
+}
+
+fn is_quoted(line: &str) -> bool {
+    color::strip(line).trim_start().starts_with('>')
+}
+
+/// How many of the lines `after` a delimiter are its signature: up to a quoted reply or the
+/// end, if that's at most [`MAX_LINES`]
+fn delimited<'a>(after: impl IntoIterator<Item = &'a String>) -> Option<usize> {
+    let mut n = 0;
+    for line in after {
+        if is_quoted(line) || quote::is_opening(line) {
+            break;
+        }
+        n += 1;
+        if n > MAX_LINES {
+            return None;
+        }
+    }
+    Some(n)
+}
+
+/// Where a closing block of contact details starts: at least three of them, making up more
+/// than half its lines
+fn trailing(lines: &[String]) -> Option<usize> {
+    let (mut contacts, mut total, mut start) = (0, 0, None);
+    for (i, line) in lines.iter().enumerate().rev() {
+        let text = color::strip(line);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if text.starts_with('>') || text.chars().count() > PROSE_CHARS {
+            break;
+        }
+        total += 1;
+        if CONTACT.is_match(text) {
+            contacts += 1;
+        }
+        if contacts >= 3 && contacts * 2 > total {
+            start = Some(i);
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lines::{self, Filtered};
+    use std::io::Write;
+
+    fn apply(text: &str, mode: Signatures) -> String {
+        lines::apply(Filter::new(mode, true), text)
+    }
+
+    fn strip(text: &str) -> String {
+        apply(text, Signatures::Strip)
+    }
+
+    #[test]
+    fn test_delimiter() {
+        let mail = "Hi,\n\nSee you.\n-- \nJane\nAcme\n";
+        assert_eq!(strip(mail), "Hi,\n\nSee you.\n");
+        let dimmed = apply(mail, Signatures::Dim);
+        assert_eq!(color::strip(&dimmed), mail);
+        assert!(dimmed.contains(&render::style(&theme::get().signature, "Jane")));
+        assert_eq!(apply(mail, Signatures::Keep), mail);
+        // Top-posted, above the quoted mail
+        assert_eq!(strip("Yes.\n--\nJane\n\n> Lunch?\n"), "Yes.\n> Lunch?\n");
+        assert_eq!(
+            strip("Yes.\n-- \nJane\nOn Mon, Ann wrote:\n> Lunch?\n"),
+            "Yes.\nOn Mon, Ann wrote:\n> Lunch?\n"
+        );
+        // Too long for a signature
+        let long = format!("Intro\n-- \n{}", "line\n".repeat(MAX_LINES + 1));
+        assert_eq!(strip(&long), long);
+    }
+
+    #[test]
+    fn test_contact_block() {
+        let mail = "Thanks,\n\nJane Doe\nHead of Sales\nTel: +1 (555) 123-4567\n\
+jane@acme.example | www.acme.example\n[image: Acme logo]\n";
+        assert_eq!(strip(mail), "Thanks,\n\n");
+        // Contact details in the message itself
+        let mail = "Call me on +1 555 123 4567 when you land, or mail me.\nBye\n";
+        assert_eq!(strip(mail), mail);
+    }
+
+    #[test]
+    fn test_streaming_matches_apply() {
+        let mail = format!(
+            "{}-- \nJane\n{}",
+            "Body line\n".repeat(40),
+            "\n".repeat(MAX_LINES - 2)
+        );
+        let mut signed = Filtered::new(Vec::new(), Filter::new(Signatures::Strip, true));
+        for piece in mail.as_bytes().chunks(7) {
+            signed.write_all(piece).unwrap();
+        }
+        let out = String::from_utf8(signed.finish().unwrap()).unwrap();
+        assert_eq!(out, strip(&mail));
+        assert_eq!(out, "Body line\n".repeat(40));
+    }
+}
@@ -0,0 +1,129 @@
+//! Telling what mail content is from how it starts: an HTML tag, or a raw message's headers
+//! (see `ContentType::sniff` in the `render` module)
+
+/// Tags an HTML document or fragment can open with (the WHATWG sniffing patterns,
+/// plus ones mail clients commonly start a body with)
+const HTML_STARTS: [&str; 28] = [
+    "!doctype html",
+    "html",
+    "head",
+    "script",
+    "iframe",
+    "h1",
+    "div",
+    "font",
+    "table",
+    "a",
+    "style",
+    "title",
+    "b",
+    "body",
+    "br",
+    "p",
+    "?xml",
+    "meta",
+    "span",
+    "center",
+    "img",
+    "h2",
+    "h3",
+    "ul",
+    "ol",
+    "blockquote",
+    "pre",
+    "o:p",
+];
+
+/// A header block with a MIME or transport header, ended by a blank line (the headers mutt
+/// formats for a `display_filter` have neither, and stay plain text)
+pub(crate) fn is_raw_message(text: &str) -> bool {
+    let Some((headers, _)) = text
+        .split_once("\n\n")
+        .or_else(|| text.split_once("\r\n\r\n"))
+    else {
+        return false;
+    };
+    let mut raw = false;
+    for (i, line) in headers.lines().enumerate() {
+        if i == 0 && line.starts_with("From ") {
+            continue;
+        }
+        if line.starts_with([' ', '\t']) && i > 0 {
+            continue;
+        }
+        let Some((name, _)) = line.split_once(':') else {
+            return false;
+        };
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic()) {
+            return false;
+        }
+        raw |= RAW_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h));
+    }
+    raw
+}
+
+/// Headers only an unformatted message carries
+const RAW_HEADERS: [&str; 6] = [
+    "MIME-Version",
+    "Content-Type",
+    "Content-Transfer-Encoding",
+    "Received",
+    "Return-Path",
+    "Delivered-To",
+];
+
+pub(crate) fn starts_with_html(text: &str) -> bool {
+    let Some(rest) = text.strip_prefix('<') else {
+        return false;
+    };
+    if rest.starts_with("!--") {
+        return true;
+    }
+    HTML_STARTS.iter().any(|tag| {
+        let (Some(name), Some(after)) = (rest.get(..tag.len()), rest.get(tag.len()..)) else {
+            return false;
+        };
+        name.eq_ignore_ascii_case(tag)
+            && after
+                .chars()
+                .next()
+                .is_some_and(|c| c == '>' || c == '/' || c.is_whitespace())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::ContentType;
+
+    #[test]
+    fn test_sniff() {
+        let sniff = ContentType::sniff;
+        assert_eq!(sniff("<!DOCTYPE html>\n<html>"), ContentType::Html);
+        assert_eq!(sniff("\u{FEFF}  <p>Hello</p>"), ContentType::Html);
+        assert_eq!(sniff("<div\nclass=x>"), ContentType::Html);
+        assert_eq!(sniff("<!-- generated --><table>"), ContentType::Html);
+        assert_eq!(sniff("BEGIN:VCALENDAR\r\n"), ContentType::Calendar);
+        // Quoted or mentioned HTML in a plain message
+        assert_eq!(sniff("Hi,\n> <html><body>broken"), ContentType::Plain);
+        assert_eq!(sniff("<bob@example.com> wrote:"), ContentType::Plain);
+        assert_eq!(sniff("<pre-release> notes"), ContentType::Plain);
+        assert_eq!(sniff("<é"), ContentType::Plain);
+        assert_eq!(sniff("begin:vcard\r\nFN:Jane"), ContentType::Contact);
+        // Raw messages, but not the headers mutt formats for a display_filter
+        let raw = "Return-Path: <a@x>\nReceived: from x\n  by y\nSubject: Hi\n\nBody";
+        assert_eq!(sniff(raw), ContentType::Message);
+        assert_eq!(
+            sniff("From a@x Mon Jan  1 00:00:00 2024\r\nMIME-Version: 1.0\r\n\r\n"),
+            ContentType::Message
+        );
+        assert_eq!(sniff("Date: today\nFrom: a@x\n\nBody"), ContentType::Plain);
+        assert_eq!(
+            sniff("Date: today\n\nFix\n---\ndiff --git a/x b/x\n"),
+            ContentType::Patch
+        );
+        assert_eq!(
+            sniff("Note: MIME-Version matters\n\nBody"),
+            ContentType::Plain
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Deceptive links and domains: link text naming one site while the link goes to another,
+//! punycode and non-Latin lookalike hosts, and misspellings of well-known brands
+
+/// Frequently impersonated brands (second-level domain labels)
+pub const BRANDS: &[&str] = &[
+    "paypal",
+    "apple",
+    "icloud",
+    "microsoft",
+    "office365",
+    "outlook",
+    "google",
+    "amazon",
+    "netflix",
+    "facebook",
+    "instagram",
+    "linkedin",
+    "dropbox",
+    "docusign",
+    "chase",
+    "wellsfargo",
+    "bankofamerica",
+    "dhl",
+    "fedex",
+    "usps",
+    "coinbase",
+    "binance",
+];
+
+/// What's wrong with a link whose text is `text` and target `url`, in a few words
+pub fn link_warning(text: &str, url: &str) -> Option<String> {
+    let href = host(url)?;
+    if let Some(shown) = shown_host(text)
+        && base_domain(&shown) != base_domain(&href)
+    {
+        return Some(format!("link goes to {}", href));
+    }
+    host_warning(&href)
+}
+
+/// What's wrong with `host` itself: punycode, letters outside ASCII, or a brand's lookalike
+pub fn host_warning(host: &str) -> Option<String> {
+    if is_punycode(host) {
+        Some(format!("punycode domain {}", host))
+    } else if !host.is_ascii() {
+        Some(format!("lookalike letters in {}", host))
+    } else {
+        imitated(host).map(|brand| format!("{} imitates {}", base_domain(host), brand))
+    }
+}
+
+/// Whether a label of `host` is punycode (an internationalized name, written in ASCII)
+pub fn is_punycode(host: &str) -> bool {
+    host.split('.').any(|label| label.starts_with("xn--"))
+}
+
+/// The brand `host`'s domain is a lookalike of (`paypa1.com`, `rnicrosoft.com`)
+pub fn imitated(host: &str) -> Option<&'static str> {
+    let base = base_domain(host);
+    let label = base.split('.').next().unwrap_or(&base);
+    BRANDS
+        .iter()
+        .find(|brand| label != **brand && lookalike(label, brand))
+        .copied()
+}
+
+/// Same after undoing common character swaps, or one edit away
+fn lookalike(label: &str, brand: &str) -> bool {
+    let skeleton = label
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .replace('0', "o")
+        .replace('1', "l")
+        .replace('3', "e");
+    skeleton == brand || (brand.len() >= 5 && edit_distance(label, brand) == 1)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// Lowercase host of a URL (userinfo and port removed)
+pub fn host(url: &str) -> Option<String> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// Host named by anchor text that looks like a URL or domain (`www.bank.com`, `https://bank.com/login`)
+pub fn shown_host(text: &str) -> Option<String> {
+    let text = text.trim().trim_end_matches('/');
+    if text.contains(' ') || !text.contains('.') {
+        return None;
+    }
+    let candidate = if text.contains("://") {
+        host(text)?
+    } else {
+        text.split('/').next()?.to_lowercase()
+    };
+    let tld = candidate.rsplit('.').next()?;
+    (tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic())).then_some(candidate)
+}
+
+/// Registrable domain, roughly: last two labels (three under `co.uk`-style suffixes)
+pub fn base_domain(host: &str) -> String {
+    let labels: Vec<&str> = host.trim_end_matches('.').split('.').collect();
+    let n = labels.len();
+    let second_level = ["co", "com", "org", "net", "ac", "gov", "edu"];
+    let keep = if n >= 3 && labels[n - 1].len() == 2 && second_level.contains(&labels[n - 2]) {
+        3
+    } else {
+        2
+    };
+    labels[n.saturating_sub(keep)..].join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_warning() {
+        assert_eq!(
+            link_warning(
+                "https://www.paypal.com",
+                "https://secure-login.example.net/x"
+            )
+            .as_deref(),
+            Some("link goes to secure-login.example.net")
+        );
+        assert_eq!(
+            link_warning("here", "https://paypa1.com/").as_deref(),
+            Some("paypa1.com imitates paypal")
+        );
+        assert_eq!(
+            link_warning("Sign in", "https://xn--pypal-4ve.com/").as_deref(),
+            Some("punycode domain xn--pypal-4ve.com")
+        );
+        assert_eq!(
+            link_warning("Sign in", "https://pаypal.com/").as_deref(),
+            Some("lookalike letters in pаypal.com")
+        );
+        assert_eq!(
+            link_warning("example.co.uk", "https://www.example.co.uk/a"),
+            None
+        );
+        assert_eq!(link_warning("Docs", "mailto:a@x.example"), None);
+    }
+
+    #[test]
+    fn test_hosts() {
+        assert_eq!(
+            host("https://user@evil.com:8080/x").as_deref(),
+            Some("evil.com")
+        );
+        assert_eq!(shown_host("Click here"), None);
+        assert_eq!(
+            shown_host("WWW.Bank.com/login").as_deref(),
+            Some("www.bank.com")
+        );
+        assert_eq!(base_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(imitated("login.rnicrosoft.com"), Some("microsoft"));
+        assert_eq!(edit_distance("amazom", "amazon"), 1);
+    }
+}
@@ -0,0 +1,624 @@
+//! `mu render` from a reader to a writer, through bounded buffers
+//!
+//! Only a prefix of the input is held to decide how to render it; HTML streams through the
+//! configured renderer and plain text line by line, and cleaned text is written out a chunk
+//! at a time.
+
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::error::{self, Error};
+use crate::hook::{self, Point};
+use crate::lines::Filtered;
+use crate::render::{self, ContentType, Format, Links, Options};
+use crate::{
+    anchor, cleanup, color, colorize, decrypt, header, ics, mime, patch, process, renderer, runner,
+    tracker, vcard,
+};
+
+/// How much of the input is inspected to tell HTML, calendars, vCards and raw messages from
+/// plain text
+const SNIFF_BYTES: usize = 64 * 1024;
+
+/// Cleaned text is written out once this much has gathered (at the next blank line)
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// A chunk without blank lines is cut here regardless
+const MAX_CHUNK_BYTES: usize = 4 * CHUNK_BYTES;
+
+/// Render `input` like [`render::render_as`], writing to `output` as it goes
+///
+/// Without a `content_type` it's sniffed from the start of the input; `colors: false`
+/// strips the ANSI codes the renderer adds. Markdown and HTML take the whole input.
+pub fn render(
+    input: impl Read + Send,
+    mut output: impl Write,
+    content_type: Option<ContentType>,
+    options: &Options,
+    colors: bool,
+) -> Result<()> {
+    let mut input = BufReader::with_capacity(CHUNK_BYTES, input);
+    let mut prefix = Vec::with_capacity(SNIFF_BYTES);
+    (&mut input)
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut prefix)
+        .context("Failed to read input")?;
+    let sniffed = content_type.is_none();
+    let content_type =
+        content_type.unwrap_or_else(|| ContentType::sniff(&String::from_utf8_lossy(&prefix)));
+    let _span = tracing::debug_span!("render", ?content_type, sniffed).entered();
+    let source = io::Cursor::new(prefix).chain(input);
+    let colors = colors && options.format == Format::Ansi;
+
+    // Render hooks see whole documents (of a raw message, the part rendered)
+    let hooked = hook::enabled(Point::PreRender) || hook::enabled(Point::PostRender);
+    if options.format.is_whole() || hooked && content_type != ContentType::Message {
+        let text = render::render_as(&read_lossy(source)?, content_type, options)?;
+        write_text(&mut output, &text, colors)?;
+        return output.flush().context("Failed to write output");
+    }
+    match content_type {
+        ContentType::Calendar => {
+            // Invites are small; the parser wants the whole thing
+            let text = read_lossy(source)?;
+            write_text(&mut output, &ics::render(&text), colors)?;
+        }
+        ContentType::Contact => {
+            let text = read_lossy(source)?;
+            write_text(&mut output, &vcard::render(&text), colors)?;
+        }
+        ContentType::Html => html(source, &mut output, options, colors)?,
+        ContentType::Plain => {
+            let mut tidied = Filtered::new(&mut output, render::tidiers(options, colors));
+            plain(source, &mut tidied, options.links.strip_urls())?;
+            tidied.finish().context("Failed to write output")?;
+        }
+        ContentType::Message => return message(source, &mut output, options, colors),
+        ContentType::Patch => patch(source, &mut output, colors)?,
+    }
+    output.flush().context("Failed to write output")
+}
+
+/// A raw message: its best part, rendered as that part's type, after its headers if asked, the
+/// verdict on its encryption or signature and cards for its invites, before those for its
+/// contacts
+fn message(
+    source: impl Read,
+    output: &mut dyn Write,
+    options: &Options,
+    colors: bool,
+) -> Result<()> {
+    let mut raw = Vec::new();
+    BufReader::new(source)
+        .read_to_end(&mut raw)
+        .context("Failed to read input")?;
+    if options.headers {
+        let block = header::block(&mime::parse(&raw)?);
+        write_text(output, &format!("{}\n\n", block), colors)?;
+    }
+    let (raw, verdict) = match decrypt::open(&raw) {
+        Some((shown, verdict)) => (shown, Some(verdict)),
+        None => (raw, None),
+    };
+    if let Some(verdict) = &verdict {
+        write_text(output, &format!("{}\n\n", verdict.banner()), colors)?;
+    }
+    let message = mime::parse(&raw)?;
+    let Some((part, content_type)) = mime::best_part(&message) else {
+        // Mail that couldn't be decrypted has nothing more to show
+        return match verdict {
+            Some(_) => output.flush().context("Failed to write output"),
+            None => Err(render::no_text()),
+        };
+    };
+    for card in crate::message::invites(&message, content_type) {
+        write_text(output, &format!("{}\n\n", card), colors)?;
+    }
+    let text = mime::part_text(part);
+    render(
+        text.as_bytes(),
+        &mut *output,
+        Some(content_type.or_patch(&text)),
+        options,
+        colors,
+    )?;
+    for card in crate::message::contacts(&message, content_type) {
+        write_text(output, &format!("\n\n{}", card), colors)?;
+    }
+    output.flush().context("Failed to write output")
+}
+
+/// HTML without its trackers (its signature and quotes treated as asked), then footnoted links,
+/// the addresses mentioned and the tracker count if asked (numbering the links and finding the
+/// article take the whole document)
+fn html(
+    source: impl Read + Send,
+    output: &mut impl Write,
+    options: &Options,
+    colors: bool,
+) -> Result<()> {
+    let removed = AtomicUsize::new(0);
+    let mentioned = Mutex::new(Vec::new());
+    let untracked = Untracked::new(source, &removed, &mentioned);
+    if options.links == Links::Footnotes || options.reader {
+        let text = read_lossy(untracked)?;
+        let text = crate::html::article(&text, options);
+        let (html, targets) = match options.links {
+            Links::Footnotes => anchor::footnotes(&text),
+            _ => (Cow::Borrowed(text.as_ref()), Vec::new()),
+        };
+        let mut tidied = Filtered::new(&mut *output, render::tidiers(options, colors));
+        convert(
+            io::Cursor::new(html.into_owned()),
+            &mut tidied,
+            options.links.strip_urls(),
+            colors,
+        )?;
+        tidied.finish().context("Failed to write output")?;
+        write_text(output, &anchor::link_list(&targets), colors)?;
+    } else {
+        let mut tidied = Filtered::new(&mut *output, render::tidiers(options, colors));
+        convert(untracked, &mut tidied, options.links.strip_urls(), colors)?;
+        tidied.finish().context("Failed to write output")?;
+    }
+    if options.contacts {
+        let mentioned = mentioned.into_inner().unwrap_or_else(|e| e.into_inner());
+        write_text(output, &anchor::contact_list(&mentioned), colors)?;
+    }
+    if options.tracker_report {
+        let report = crate::html::tracker_report(removed.into_inner());
+        write_text(output, &report, colors)?;
+    }
+    Ok(())
+}
+
+/// HTML read with its trackers removed and `mailto:` links marked, a piece ending at a `>` at
+/// a time
+struct Untracked<'a, R> {
+    source: BufReader<R>,
+    cleaned: io::Cursor<Vec<u8>>,
+    removed: &'a AtomicUsize,
+    mentioned: &'a Mutex<Vec<(String, Option<String>)>>,
+}
+
+impl<'a, R: Read> Untracked<'a, R> {
+    fn new(
+        source: R,
+        removed: &'a AtomicUsize,
+        mentioned: &'a Mutex<Vec<(String, Option<String>)>>,
+    ) -> Self {
+        Self {
+            source: BufReader::with_capacity(CHUNK_BYTES, source),
+            cleaned: io::Cursor::default(),
+            removed,
+            mentioned,
+        }
+    }
+}
+
+impl<R: Read> Read for Untracked<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.cleaned.read(buf)?;
+        if n > 0 || buf.is_empty() {
+            return Ok(n);
+        }
+        let mut piece = Vec::new();
+        // A link's text stays with its target, to be checked against it
+        while (piece.len() < CHUNK_BYTES || piece.len() < MAX_CHUNK_BYTES && in_link(&piece))
+            && self.source.read_until(b'>', &mut piece)? > 0
+        {}
+        if piece.is_empty() {
+            return Ok(0);
+        }
+        let piece = String::from_utf8_lossy(&piece);
+        let (html, removed) = tracker::clean(&piece);
+        self.removed.fetch_add(removed, Ordering::Relaxed);
+        let mut mentioned = self.mentioned.lock().unwrap_or_else(|e| e.into_inner());
+        let html = anchor::mailto_links(&html, &mut mentioned);
+        let html = anchor::flag_links(&html).into_owned();
+        self.cleaned = io::Cursor::new(html.into_bytes());
+        self.cleaned.read(buf)
+    }
+}
+
+/// Whether `html` ends inside a link
+fn in_link(html: &[u8]) -> bool {
+    let html = String::from_utf8_lossy(html).to_ascii_lowercase();
+    html.rfind("<a ")
+        .is_some_and(|open| html.rfind("</a").is_none_or(|close| close < open))
+}
+
+/// HTML through the first renderer in the chain that starts (the native layout and the
+/// built-in converter take the whole document)
+fn convert(
+    source: impl Read + Send,
+    output: &mut impl Write,
+    strip_urls: bool,
+    colors: bool,
+) -> Result<()> {
+    let clean = |chunk: &str| colorize::clean_text(chunk, strip_urls);
+    let chain = renderer::chain();
+    let mut last = None;
+    for renderer in chain {
+        let Some(mut cmd) = renderer.command() else {
+            let text = renderer.convert(&read_lossy(source)?, strip_urls)?;
+            return clean_chunks(io::Cursor::new(text), output, clean, colors);
+        };
+        let name = renderer.name();
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        let _span = process::span(&cmd).entered();
+        let spawned = match runner::intercept(&cmd) {
+            Some(Ok(recorded)) => {
+                return clean_chunks(io::Cursor::new(recorded.stdout), output, clean, colors);
+            }
+            // Without a recording it's as good as missing
+            Some(Err(e)) => Err(io::Error::new(io::ErrorKind::NotFound, e)),
+            None => cmd.spawn(),
+        };
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                tracing::debug!(error = %e, "trying the next renderer");
+                last = Some(error::spawn_failed(name, e));
+                continue;
+            }
+            Err(e) => return Err(error::spawn_failed(name, e)),
+        };
+        let started = Instant::now();
+        through(&mut child, source, |text| {
+            clean_chunks(text, output, clean, colors)
+        })?;
+        let status = child
+            .wait()
+            .with_context(|| format!("Failed to run {}", name))?;
+        process::exited(started, status);
+        if !status.success() {
+            return Err(Error::command_failed(name, b"").into());
+        }
+        return Ok(());
+    }
+    Err(renderer::exhausted(chain, last))
+}
+
+/// Feed `source` to `child` from another thread while `read` takes its output
+fn through(
+    child: &mut Child,
+    mut source: impl Read + Send,
+    read: impl FnOnce(BufReader<ChildStdout>) -> Result<()>,
+) -> Result<()> {
+    let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        anyhow::bail!("Child process started without pipes");
+    };
+    std::thread::scope(|scope| {
+        let feeder = scope.spawn(move || io::copy(&mut source, &mut stdin));
+        let written = read(BufReader::new(stdout));
+        if written.is_err() {
+            // The reader went away: stop the child so the feeder stops too
+            let _ = child.kill();
+        }
+        let fed = feeder
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("feeder panicked")));
+        written?;
+        match fed {
+            // w3m may quit without reading everything it was sent
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                Err(anyhow::Error::new(e).context("Failed to read input"))
+            }
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Plain text line by line, with long URLs dropped
+fn plain(mut source: impl BufRead, output: &mut impl Write, strip_urls: bool) -> Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if source
+            .read_until(b'\n', &mut line)
+            .context("Failed to read input")?
+            == 0
+        {
+            return Ok(());
+        }
+        if strip_urls {
+            let text = cleanup::strip_long_urls(&String::from_utf8_lossy(&line));
+            output.write_all(text.as_bytes())
+        } else {
+            output.write_all(&line)
+        }
+        .context("Failed to write output")?;
+    }
+}
+
+/// A patch line by line, colored
+fn patch(mut source: impl BufRead, output: &mut impl Write, colors: bool) -> Result<()> {
+    let mut highlighter = patch::Highlighter::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if source
+            .read_until(b'\n', &mut line)
+            .context("Failed to read input")?
+            == 0
+        {
+            return Ok(());
+        }
+        let styled = highlighter.line(&String::from_utf8_lossy(&line));
+        write_text(output, &styled, colors)?;
+    }
+}
+
+/// Clean `reader`'s text a chunk at a time, chunks ending at blank lines
+fn clean_chunks(
+    mut reader: impl BufRead,
+    output: &mut impl Write,
+    clean: impl Fn(&str) -> String,
+    colors: bool,
+) -> Result<()> {
+    let mut chunk = String::new();
+    let mut line = Vec::new();
+    let mut first = true;
+    loop {
+        line.clear();
+        let done = reader
+            .read_until(b'\n', &mut line)
+            .context("Failed to read input")?
+            == 0;
+        let text = String::from_utf8_lossy(&line);
+        chunk.push_str(&text);
+        let at_break = text.trim().is_empty() && chunk.len() >= CHUNK_BYTES;
+        if done || at_break || chunk.len() >= MAX_CHUNK_BYTES {
+            let started = Instant::now();
+            let cleaned = clean(&chunk);
+            tracing::trace!(bytes = chunk.len(), elapsed = ?started.elapsed(), "cleaned chunk");
+            chunk.clear();
+            if !cleaned.is_empty() {
+                if !first {
+                    output
+                        .write_all(b"\n\n")
+                        .context("Failed to write output")?;
+                }
+                write_text(output, &cleaned, colors)?;
+                first = false;
+            }
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+fn write_text(output: &mut (impl Write + ?Sized), text: &str, colors: bool) -> Result<()> {
+    let text = if colors {
+        text.into()
+    } else {
+        color::strip(text)
+    };
+    output
+        .write_all(text.as_bytes())
+        .context("Failed to write output")
+}
+
+fn read_lossy(mut source: impl Read) -> Result<String> {
+    let mut bytes = Vec::new();
+    source
+        .read_to_end(&mut bytes)
+        .context("Failed to read input")?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n;
+
+    fn rendered(input: &str, colors: bool) -> String {
+        let mut out = Vec::new();
+        render(
+            input.as_bytes(),
+            &mut out,
+            None,
+            &Options::default(),
+            colors,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_plain_passes_through() {
+        let input =
+            "Hi,\nsee https://very-long-url.example.com/path/to/something/else here\n\nBye\n";
+        assert_eq!(rendered(input, true), "Hi,\nsee  here\n\nBye\n");
+    }
+
+    #[test]
+    fn test_html_matches_render() {
+        let input = "<html><body><h1>NEWS LETTER</h1><p>Hello <b>world</b></p></body></html>";
+        assert_eq!(
+            rendered(input, true),
+            render::render(input, &Options::default()).unwrap()
+        );
+        assert!(!rendered(input, false).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_reader_matches_render() {
+        let story = "<p>A long story, with commas, about things that happened this week.</p>";
+        let input = format!(
+            "<table><tr><td class=nav><a href=/s>Shop</a></td></tr>\
+<tr><td class=content><h1>Week</h1>{}</td></tr></table>",
+            story.repeat(5)
+        );
+        let options = Options {
+            reader: true,
+            ..Options::default()
+        };
+        let mut out = Vec::new();
+        render(input.as_bytes(), &mut out, None, &options, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, render::render(&input, &options).unwrap());
+        assert!(out.contains("Week") && !out.contains("Shop"));
+    }
+
+    #[test]
+    fn test_contacts_match_render() {
+        let input = "<p>Mail <a href=\"mailto:ann@x.example\">Ann</a> today.</p>";
+        let options = Options {
+            contacts: true,
+            ..Options::default()
+        };
+        let mut out = Vec::new();
+        render(input.as_bytes(), &mut out, None, &options, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, render::render(input, &options).unwrap());
+        assert!(color::strip(&out).ends_with("Contacts mentioned:\n  ann@x.example (Ann)"));
+    }
+
+    #[test]
+    fn test_headers_match_render() {
+        let input = "Received: from a by b; Mon, 2 Feb 2026 10:00:05 +0000\r\n\
+From: Ann <ann@x.example>\r\nSubject: Hi\r\n\r\nHello\r\n";
+        let options = Options {
+            headers: true,
+            ..Options::default()
+        };
+        let mut out = Vec::new();
+        render(input.as_bytes(), &mut out, None, &options, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, render::render(input, &options).unwrap());
+        assert!(color::strip(&out).starts_with("From: Ann <ann@x.example>\nSubject: Hi\n"));
+    }
+
+    #[test]
+    fn test_tidied_like_render() {
+        let input = format!(
+            "Fine by me.\n-- \nAnn\n\nOn Mon, Bob wrote:\n{}",
+            "> Lunch?\n".repeat(30)
+        );
+        assert_eq!(
+            rendered(&input, true),
+            render::render(&input, &Options::default()).unwrap()
+        );
+        assert_eq!(
+            rendered(&input, false),
+            format!(
+                "Fine by me.\n-- \nAnn\n\n▸ {}\n",
+                i18n::tr(i18n::Msg::Quoted(31))
+            )
+        );
+    }
+
+    #[test]
+    fn test_patch_matches_render() {
+        let input = "Fix\n---\n--- a/x\r\n+++ b/x\r\n@@ -1 +1 @@\r\n-a\r\n+b\r\n-- \r\n";
+        assert_eq!(
+            rendered(input, true),
+            render::render(input, &Options::default()).unwrap()
+        );
+        assert!(rendered(input, true).contains("\x1b"));
+        assert_eq!(rendered(input, false), input);
+    }
+
+    #[test]
+    fn test_whole_formats_match_render() {
+        let options = Options {
+            format: Format::Html,
+            ..Options::default()
+        };
+        let input = "Hi <there>\n";
+        let mut out = Vec::new();
+        render(input.as_bytes(), &mut out, None, &options, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, render::render(input, &options).unwrap());
+        assert_eq!(out, "<pre>Hi &lt;there&gt;</pre>");
+    }
+
+    #[test]
+    fn test_content_type_overrides_sniffing() {
+        let mut out = Vec::new();
+        let fragment = "Hello <b>world</b>";
+        render(
+            fragment.as_bytes(),
+            &mut out,
+            Some(ContentType::Html),
+            &Options::default(),
+            false,
+        )
+        .unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("<b>"));
+    }
+
+    #[test]
+    fn test_in_link() {
+        assert!(in_link(b"<p><A HREF=x>text"));
+        assert!(!in_link(b"<p><a href=x>text</a>"));
+        assert!(!in_link(b"<p>text"));
+    }
+
+    #[test]
+    fn test_untracked() {
+        let html = concat!(
+            r#"<p>Hi<img src="https://x.example/o.gif" width=1 height=1></p>"#,
+            r#"<a href="https://l.facebook.com/l.php?u=https%3A%2F%2Fa.example%2F">A</a>"#,
+            r#" <a href="mailto:a@x.example">Ann</a>"#,
+        );
+        let removed = AtomicUsize::new(0);
+        let mentioned = Mutex::new(Vec::new());
+        let text = read_lossy(Untracked::new(html.as_bytes(), &removed, &mentioned)).unwrap();
+        assert_eq!(
+            text,
+            r#"<p>Hi</p><a href="https://a.example/">A</a> Ann (✉&nbsp;a@x.example)"#
+        );
+        assert_eq!(removed.into_inner(), 2);
+        assert_eq!(mentioned.into_inner().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_clean_chunks_splits_at_blank_lines() {
+        let para = format!("{}\n\n\n", "word ".repeat(CHUNK_BYTES / 5));
+        let input = format!("{}{}last\n", para, para);
+        let mut out = Vec::new();
+        let calls = std::cell::Cell::new(0);
+        let clean = |chunk: &str| {
+            calls.set(calls.get() + 1);
+            chunk.trim().to_string()
+        };
+        clean_chunks(input.as_bytes(), &mut out, clean, true).unwrap();
+        assert_eq!(calls.get(), 3);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with("word\n\nlast"));
+        assert_eq!(out.matches("\n\n").count(), 2);
+    }
+
+    #[test]
+    fn test_closed_output_is_an_error() {
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let err = render(
+            "text\n".as_bytes(),
+            Closed,
+            None,
+            &Options::default(),
+            false,
+        )
+        .unwrap_err();
+        assert!(error::is_broken_pipe(&err));
+    }
+}
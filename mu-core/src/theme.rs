@@ -0,0 +1,235 @@
+//! Output colors from `~/.config/mu/theme.toml` (or `$MU_THEME`), to match the terminal's
+//! scheme
+//!
+//! Each role takes attributes and colors by name (`"bold cyan"`, `"dim"`, `"italic
+//! bright-blue"`, `"white on red"`), a 256-color number (`"208"`), `"#rrggbb"`, or raw SGR
+//! codes (`"1;36"`). Roles left out keep their defaults.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config;
+
+/// Colors by role, as SGR codes
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// `=== Headers ===` section headers
+    pub section: Style,
+    /// Headlines in rendered mail (centered or all-caps lines)
+    pub heading: Style,
+    /// Lines ending in `:` in rendered mail
+    pub subheading: Style,
+    /// `Label:` in rendered tables
+    pub label: Style,
+    /// Table and card borders
+    pub border: Style,
+    /// Event and contact card titles
+    pub title: Style,
+    /// Quoted (`> `) lines in rendered mail
+    pub quote: Style,
+    /// File headers (`diff --git`, `--- a/…`) in patches
+    pub diff_header: Style,
+    /// `@@ … @@` hunk headers in patches
+    pub hunk: Style,
+    /// Added lines in patches
+    pub added: Style,
+    /// Removed lines in patches
+    pub removed: Style,
+    /// Signatures in rendered mail, when dimmed
+    pub signature: Style,
+    /// The subject in previews
+    pub subject: Style,
+    /// Warning banners (phishing) and failed SPF/DKIM/DMARC checks in previews, deceptive
+    /// links in rendered mail
+    pub alert: Style,
+    /// Addresses from `mailto:` links in rendered mail
+    pub address: Style,
+    /// Passing SPF/DKIM/DMARC checks in previews
+    pub pass: Style,
+    /// Notes and secondary text
+    pub muted: Style,
+    /// The done part of the sync progress bar
+    pub progress: Style,
+    /// The rest of the progress bar
+    pub progress_empty: Style,
+    /// Channel names in sync output
+    pub channel: Style,
+    /// The new mail marker in sync output
+    pub new_mail: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let style = |sgr: &str| Style(sgr.to_string());
+        Self {
+            section: style("1;36"),
+            heading: style("1;36"),
+            subheading: style("1;33"),
+            label: style("33"),
+            border: style("2"),
+            title: style("1"),
+            quote: style("2"),
+            diff_header: style("1"),
+            hunk: style("36"),
+            added: style("32"),
+            removed: style("31"),
+            signature: style("2"),
+            subject: style("1;33"),
+            alert: style("1;31"),
+            address: style("4;34"),
+            pass: style("32"),
+            muted: style("2"),
+            progress: style("36"),
+            progress_empty: style("2"),
+            channel: style("33"),
+            new_mail: style("32"),
+        }
+    }
+}
+
+/// An SGR style (e.g. `1;36`), parsed from a color spec
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Style(String);
+
+impl TryFrom<String> for Style {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        sgr(&spec).map(Style)
+    }
+}
+
+impl Deref for Style {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Color names in SGR order
+const COLORS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Attribute names and their SGR codes
+const ATTRIBUTES: [(&str, &str); 6] = [
+    ("bold", "1"),
+    ("dim", "2"),
+    ("italic", "3"),
+    ("underline", "4"),
+    ("reverse", "7"),
+    ("plain", "0"),
+];
+
+/// SGR codes for a color spec
+fn sgr(spec: &str) -> Result<String, String> {
+    if spec.contains(';') && spec.split(';').all(|code| code.parse::<u8>().is_ok()) {
+        return Ok(spec.to_string());
+    }
+    let mut codes = Vec::new();
+    let mut background = false;
+    for word in spec.split_whitespace() {
+        if word == "on" {
+            background = true;
+            continue;
+        }
+        let code = ATTRIBUTES
+            .iter()
+            .find(|(name, _)| *name == word)
+            .map(|(_, code)| code.to_string())
+            .or_else(|| color(word, background))
+            .ok_or_else(|| format!("unknown color or attribute `{}` in \"{}\"", word, spec))?;
+        codes.push(code);
+        background = false;
+    }
+    if codes.is_empty() {
+        codes.push("0".to_string());
+    }
+    Ok(codes.join(";"))
+}
+
+/// The SGR code of a color name, 256-color number or `#rrggbb`
+fn color(word: &str, background: bool) -> Option<String> {
+    let (base, extended) = if background { (40, 48) } else { (30, 38) };
+    let (bright, name) = match word.strip_prefix("bright-") {
+        Some(name) => (60, name),
+        None => (0, word),
+    };
+    if let Some(i) = COLORS.iter().position(|c| *c == name) {
+        return Some((base + bright + i).to_string());
+    }
+    if let Ok(n) = word.parse::<u8>() {
+        return Some(format!("{};5;{}", extended, n));
+    }
+    let hex = word.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(format!(
+        "{};2;{};{};{}",
+        extended,
+        channel(0)?,
+        channel(2)?,
+        channel(4)?
+    ))
+}
+
+/// The theme file: `$MU_THEME`, else `theme.toml` in [`config::config_dir`]
+pub fn path() -> PathBuf {
+    config::env_path("MU_THEME").unwrap_or_else(|| config::config_dir().join("theme.toml"))
+}
+
+/// The theme in `path`, defaults if there is no such file
+pub fn load(path: &Path) -> Result<Theme> {
+    if !path.exists() {
+        return Ok(Theme::default());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("Invalid theme {}", path.display()))
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The theme for this process (loaded on first use; a broken file leaves the defaults)
+pub fn get() -> &'static Theme {
+    THEME.get_or_init(|| {
+        load(&path()).unwrap_or_else(|e| {
+            tracing::warn!(error = %format!("{:#}", e), "theme ignored");
+            Theme::default()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sgr() {
+        assert_eq!(sgr("bold cyan").unwrap(), "1;36");
+        assert_eq!(sgr("1;36").unwrap(), "1;36");
+        assert_eq!(sgr("italic bright-blue").unwrap(), "3;94");
+        assert_eq!(sgr("white on red").unwrap(), "37;41");
+        assert_eq!(sgr("208").unwrap(), "38;5;208");
+        assert_eq!(sgr("bold #E5c07b").unwrap(), "1;38;2;229;192;123");
+        assert_eq!(sgr("").unwrap(), "0");
+        assert!(sgr("bold teal").is_err());
+        assert!(sgr("#e5c0").is_err());
+    }
+
+    #[test]
+    fn test_theme_file() {
+        let theme: Theme =
+            toml::from_str("heading = \"bold magenta\"\nborder = \"240\"\n").unwrap();
+        assert_eq!(&*theme.heading, "1;35");
+        assert_eq!(&*theme.border, "38;5;240");
+        assert_eq!(theme.label, Theme::default().label);
+        assert!(toml::from_str::<Theme>("heading = \"glowing\"\n").is_err());
+        assert!(toml::from_str::<Theme>("headline = \"red\"\n").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Trackers in HTML mail: open-tracking pixels and click-tracking redirects
+//!
+//! Pixels are 1×1 or hidden images, or any image from a known tracking host; redirects
+//! that carry their destination in a query parameter are unwrapped to it.
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use crate::cleanup::re;
+
+/// Query parameters that commonly carry the real destination
+const TARGET_PARAMS: &[&str] = &[
+    "url",
+    "u",
+    "q",
+    "target",
+    "redirect",
+    "redirect_url",
+    "dest",
+    "link",
+    "r",
+];
+
+/// Hosts of email service providers' open and click tracking
+const TRACKER_HOSTS: &[&str] = &[
+    "sendgrid.net",
+    "list-manage.com",
+    "mandrillapp.com",
+    "mailgun.org",
+    "mailchimp.com",
+    "hubspotlinks.com",
+    "hubspotemail.net",
+    "exct.net",
+    "sparkpostmail.com",
+    "mjt.lu",
+    "rs6.net",
+    "mailtrack.io",
+    "mixmax.com",
+    "pixel.watch",
+];
+
+static IMG: LazyLock<Regex> = LazyLock::new(|| re(r"(?is)<img\b[^>]*>"));
+static HREF: LazyLock<Regex> =
+    LazyLock::new(|| re(r#"(?is)(<a\s[^>]*?href\s*=\s*)(["'])([^"']*)["']"#));
+static SRC: LazyLock<Regex> =
+    LazyLock::new(|| re(r#"(?i)\bsrc\s*=\s*["']?https?://([^/"'\s>:]+)"#));
+static TINY: LazyLock<Regex> = LazyLock::new(|| {
+    re(
+        r#"(?i)\b(width|height)\s*=\s*["']?[01](px)?["'\s/>]|(width|height)\s*:\s*[01]px|display\s*:\s*none"#,
+    )
+});
+
+/// Unwrap tracking redirects that carry the destination in a query parameter
+pub fn unwrap(url: &str) -> String {
+    let mut current = url.to_string();
+    // Redirectors are sometimes nested (safelinks → google → target)
+    for _ in 0..5 {
+        let Some((_, query)) = current.split_once('?') else {
+            break;
+        };
+        let next = query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let decoded = percent_decode(value);
+            (TARGET_PARAMS.contains(&key.to_lowercase().as_str()) && decoded.starts_with("http"))
+                .then_some(decoded)
+        });
+        match next {
+            Some(n) => current = n,
+            None => break,
+        }
+    }
+    current
+}
+
+/// Decode `%XX` escapes (and `+` as space)
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(hi), Some(lo)) => {
+                    out.push(hi * 16 + lo);
+                    i += 3;
+                    continue;
+                }
+                _ => out.push(b'%'),
+            },
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Whether an `<img>` tag is a tracking pixel: 1×1 (either side), hidden, or from a
+/// tracking host
+pub fn is_pixel(tag: &str) -> bool {
+    TINY.is_match(tag)
+        || SRC
+            .captures(tag)
+            .is_some_and(|caps| is_tracker_host(&caps[1].to_ascii_lowercase()))
+}
+
+fn is_tracker_host(host: &str) -> bool {
+    TRACKER_HOSTS
+        .iter()
+        .any(|known| host == *known || host.ends_with(&format!(".{}", known)))
+}
+
+/// `html` without tracking pixels and with redirect links unwrapped, and how many trackers
+/// went
+///
+/// Each change stays inside one tag, so HTML cut after a `>` can be cleaned piece by piece.
+pub fn clean(html: &str) -> (Cow<'_, str>, usize) {
+    let mut removed = 0;
+    let html = IMG.replace_all(html, |caps: &Captures| {
+        if is_pixel(&caps[0]) {
+            removed += 1;
+            String::new()
+        } else {
+            caps[0].to_string()
+        }
+    });
+    let unwrapped = HREF.replace_all(&html, |caps: &Captures| {
+        let href = caps[3].replace("&amp;", "&");
+        let target = unwrap(&href);
+        if target == href {
+            return caps[0].to_string();
+        }
+        removed += 1;
+        let quote = &caps[2];
+        format!(
+            "{}{}{}{}",
+            &caps[1],
+            quote,
+            target.replace('&', "&amp;"),
+            quote
+        )
+    });
+    let html = match unwrapped {
+        Cow::Borrowed(_) => html,
+        Cow::Owned(html) => Cow::Owned(html),
+    };
+    (html, removed)
+}
+
+/// The summary line for `removed` trackers
+pub fn report(removed: usize) -> String {
+    match removed {
+        0 => "no trackers found".to_string(),
+        1 => "1 tracker removed".to_string(),
+        n => format!("{} trackers removed", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwrap_redirects() {
+        let google = "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1&sa=D";
+        assert_eq!(unwrap(google), "https://example.com/a?b=1");
+        let nested = format!(
+            "https://eur01.safelinks.protection.outlook.com/?url={}&data=x",
+            google
+                .replace('%', "%25")
+                .replace('&', "%26")
+                .replace('?', "%3F")
+                .replace('=', "%3D")
+        );
+        assert_eq!(unwrap(&nested), "https://example.com/a?b=1");
+        assert_eq!(
+            unwrap("https://example.com/?page=2"),
+            "https://example.com/?page=2"
+        );
+    }
+
+    #[test]
+    fn test_clean() {
+        let html = concat!(
+            r#"<p><img src="https://x.example/logo.png" alt="Logo">"#,
+            r#"<img src="https://x.example/o.gif" width="1" height="1" alt="">"#,
+            r#"<img style="display:none" src="/t.gif">"#,
+            r#"<IMG SRC="https://u1.ct.sendgrid.net/wf/open?upn=abc"></p>"#,
+            r#"<a class="b" href="https://www.google.com/url?q=https%3A%2F%2Fa.example%2F%3Fx%3D1%26y%3D2&amp;sa=D">A</a>"#,
+            r#"<a href='https://a.example/plain'>B</a>"#,
+        );
+        let (html, removed) = clean(html);
+        assert_eq!(
+            html,
+            concat!(
+                r#"<p><img src="https://x.example/logo.png" alt="Logo"></p>"#,
+                r#"<a class="b" href="https://a.example/?x=1&amp;y=2">A</a>"#,
+                r#"<a href='https://a.example/plain'>B</a>"#,
+            )
+        );
+        assert_eq!(removed, 4);
+        assert!(matches!(clean("<p>none</p>"), (Cow::Borrowed(_), 0)));
+        assert_eq!(report(3), "3 trackers removed");
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b+c%2"), "a b c%2");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
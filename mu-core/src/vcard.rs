@@ -0,0 +1,207 @@
+//! vCard (text/vcard) parsing and serialization, and contacts rendered as cards
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::{colorize, ics};
+
+/// A contact parsed from a vCard
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contact {
+    pub uid: String,
+    pub name: String,
+    pub emails: Vec<String>,
+    pub phones: Vec<String>,
+    pub org: Option<String>,
+}
+
+impl Contact {
+    /// Key used to dedupe contacts (first email, else name)
+    pub fn key(&self) -> String {
+        self.emails
+            .first()
+            .unwrap_or(&self.name)
+            .trim()
+            .to_lowercase()
+    }
+
+    /// Merge another record for the same person into this one
+    pub fn merge(&mut self, other: &Contact) {
+        if self.name.is_empty() {
+            self.name = other.name.clone();
+        }
+        if self.org.is_none() {
+            self.org = other.org.clone();
+        }
+        for e in &other.emails {
+            if !self.emails.iter().any(|x| x.eq_ignore_ascii_case(e)) {
+                self.emails.push(e.clone());
+            }
+        }
+        for p in &other.phones {
+            if !self.phones.contains(p) {
+                self.phones.push(p.clone());
+            }
+        }
+    }
+}
+
+/// Check whether text looks like a vCard
+pub fn is_vcard(text: &str) -> bool {
+    text.trim_start()
+        .get(..11)
+        .is_some_and(|start| start.eq_ignore_ascii_case("BEGIN:VCARD"))
+}
+
+/// Every vCard in `text` as a card: the name on the border, then emails, phones and
+/// organization
+pub fn render(text: &str) -> String {
+    let cards: Vec<String> = parse_all(text).iter().map(card).collect();
+    cards.join("\n\n")
+}
+
+fn card(contact: &Contact) -> String {
+    let mut rows = Vec::new();
+    if !contact.emails.is_empty() {
+        rows.push(("Email", contact.emails.join("\n")));
+    }
+    if !contact.phones.is_empty() {
+        rows.push(("Phone", contact.phones.join("\n")));
+    }
+    if let Some(org) = &contact.org {
+        rows.push(("Org", org.clone()));
+    }
+    let title = match contact.emails.first() {
+        _ if !contact.name.is_empty() => &contact.name,
+        Some(email) => email,
+        None => "Contact",
+    };
+    colorize::card(title, &rows)
+}
+
+/// Parse all vCards in a text blob
+pub fn parse_all(text: &str) -> Vec<Contact> {
+    let mut contacts = Vec::new();
+    let mut current: Option<Contact> = None;
+
+    for prop in ics::parse(text) {
+        match (prop.name.as_str(), current.as_mut()) {
+            ("BEGIN", _) if prop.value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(Contact::default())
+            }
+            ("END", Some(_)) if prop.value.eq_ignore_ascii_case("VCARD") => {
+                if let Some(mut c) = current.take() {
+                    if c.uid.is_empty() {
+                        c.uid = generate_uid(&c.key());
+                    }
+                    contacts.push(c);
+                }
+            }
+            ("FN", Some(c)) => c.name = prop.value,
+            ("N", Some(c)) if c.name.is_empty() => c.name = name_from_n(&prop.value),
+            ("EMAIL", Some(c)) => c.emails.push(prop.value),
+            ("TEL", Some(c)) => c.phones.push(prop.value),
+            ("ORG", Some(c)) => {
+                c.org = Some(
+                    prop.value
+                        .replace(';', ", ")
+                        .trim_end_matches(", ")
+                        .to_string(),
+                )
+            }
+            ("UID", Some(c)) => c.uid = prop.value,
+            _ => {}
+        }
+    }
+    contacts
+}
+
+/// `Family;Given;...` → `Given Family`
+fn name_from_n(value: &str) -> String {
+    let mut parts = value.split(';');
+    let family = parts.next().unwrap_or("");
+    let given = parts.next().unwrap_or("");
+    format!("{} {}", given, family).trim().to_string()
+}
+
+/// Stable UID derived from the contact key
+pub fn generate_uid(key: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!("mu-{:016x}", hasher.finish())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+/// Serialize a contact as a vCard 3.0
+pub fn to_vcard(c: &Contact) -> String {
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:3.0".to_string(),
+        format!("UID:{}", c.uid),
+        format!("FN:{}", escape(&c.name)),
+    ];
+    lines.extend(c.emails.iter().map(|e| format!("EMAIL:{}", e)));
+    lines.extend(c.phones.iter().map(|p| format!("TEL:{}", p)));
+    if let Some(org) = &c.org {
+        lines.push(format!("ORG:{}", escape(org)));
+    }
+    lines.push("END:VCARD".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VCF: &str = "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;Jane;;;\r\nEMAIL;TYPE=work:jane@example.com\r\n\
+TEL;TYPE=cell:+1 555 0100\r\nORG:Example Inc.;R&D\r\nEND:VCARD\r\nBEGIN:VCARD\r\nFN:Bob\r\nUID:b1\r\nEND:VCARD\r\n";
+
+    #[test]
+    fn test_parse_all() {
+        let contacts = parse_all(VCF);
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(contacts[0].name, "Jane Doe");
+        assert_eq!(contacts[0].emails, vec!["jane@example.com"]);
+        assert_eq!(contacts[0].org.as_deref(), Some("Example Inc., R&D"));
+        assert!(contacts[0].uid.starts_with("mu-"));
+        assert_eq!(contacts[1].uid, "b1");
+    }
+
+    #[test]
+    fn test_render() {
+        assert!(is_vcard(VCF));
+        let cards = crate::color::strip(&render(VCF)).into_owned();
+        assert_eq!(
+            cards,
+            concat!(
+                "╭─ Jane Doe ───────────────╮\n",
+                "│ Email: jane@example.com  │\n",
+                "│ Phone: +1 555 0100       │\n",
+                "│ Org:   Example Inc., R&D │\n",
+                "╰──────────────────────────╯\n\n",
+                "╭─ Bob ─╮\n╰───────╯"
+            )
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_and_merge() {
+        let mut c = parse_all(VCF).remove(0);
+        let again = parse_all(&to_vcard(&c)).remove(0);
+        assert_eq!(again, c);
+
+        let other = Contact {
+            emails: vec!["JANE@example.com".into(), "jd@home.org".into()],
+            ..Default::default()
+        };
+        c.merge(&other);
+        assert_eq!(c.emails, vec!["jane@example.com", "jd@home.org"]);
+    }
+}
@@ -0,0 +1,109 @@
+//! Terminal display width: wide (CJK) and zero-width characters and emoji counted as the
+//! terminal draws them, text cut between grapheme clusters and ANSI colors taking no room
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::color;
+
+/// Columns `text` takes on a terminal
+pub fn width(text: &str) -> usize {
+    color::strip(text).width()
+}
+
+/// The longest start of `text` that fits in `max` columns (whole grapheme clusters only)
+pub fn take(text: &str, max: usize) -> &str {
+    let mut used = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        used += grapheme.width();
+        if used > max {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// `text` cut to `max` columns, ending in `…` if anything was cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    format!("{}…", take(text, max.saturating_sub(1)))
+}
+
+/// `text` padded with spaces to `columns`
+pub fn pad(text: &str, columns: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(columns.saturating_sub(width(text)))
+    )
+}
+
+/// `text` in lines of at most `first` columns, then `rest`, broken between words (and inside
+/// words too long for a line)
+pub fn wrap(text: &str, first: usize, rest: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut max = first.max(1);
+    for word in text.split_whitespace() {
+        let mut word = word;
+        while !word.is_empty() {
+            let gap = usize::from(!line.is_empty());
+            if width(&line) + gap + width(word) <= max {
+                if gap == 1 {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                break;
+            }
+            if line.is_empty() {
+                // Too long for any line: as much as fits here, the rest on the next
+                let head = match take(word, max) {
+                    "" => word.graphemes(true).next().unwrap_or(word),
+                    head => head,
+                };
+                line.push_str(head);
+                word = &word[head.len()..];
+            }
+            lines.push(std::mem::take(&mut line));
+            max = rest.max(1);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("hello"), 5);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("👍🏽 ok"), 5);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("\x1b[1mbold\x1b[0m"), 4);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello world", 8), "hello w…");
+        assert_eq!(truncate("日本語のメール", 6), "日本…");
+        // A family emoji is one cluster, kept whole or left out
+        assert_eq!(truncate("hi 👨‍👩‍👧 there", 5), "hi …");
+        assert_eq!(take("cafe\u{301}s", 4), "cafe\u{301}");
+        assert_eq!(pad("日本", 6), "日本  ");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("one two three four", 8, 10), ["one two", "three four"]);
+        assert_eq!(wrap("abcdefghij xy", 4, 6), ["abcd", "efghij", "xy"]);
+        assert_eq!(wrap("日本語", 4, 4), ["日本", "語"]);
+        assert_eq!(wrap("", 4, 4), [""]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Address completion from notmuch and the contact store (neomutt query_command)
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::process::Command;
+
+use crate::{contacts, process};
+
+/// Arguments for `mu address`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Name or address fragment to complete
+    pattern: String,
+}
+
+/// Print matching addresses in mutt query_command format
+pub fn run(args: Args) -> Result<()> {
+    let query = format!("from:\"{0}\" or to:\"{0}\"", args.pattern.replace('"', ""));
+    let output = process::run(Command::new("notmuch").args([
+        "address",
+        "--format=json",
+        "--output=sender",
+        "--output=recipients",
+        "--deduplicate=address",
+        &query,
+    ]))
+    .context("Failed to run notmuch address")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "notmuch address failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout).context("Invalid notmuch JSON")?;
+    let mut matches = stored_matches(&args.pattern)?;
+    for (addr, name) in filter_matches(&json, &args.pattern) {
+        if !matches.iter().any(|(a, _)| a.eq_ignore_ascii_case(&addr)) {
+            matches.push((addr, name));
+        }
+    }
+
+    // mutt expects a status line first, then "address<TAB>name<TAB>other"
+    println!("Searching notmuch... {} matches", matches.len());
+    for (addr, name) in matches {
+        println!("{}\t{}\t", addr, name);
+    }
+    Ok(())
+}
+
+/// Matches from the contact store (people who may never have emailed)
+fn stored_matches(pattern: &str) -> Result<Vec<(String, String)>> {
+    let needle = pattern.to_lowercase();
+    let store = contacts::load(&contacts::store_path())?;
+    Ok(store
+        .iter()
+        .filter(|c| {
+            c.name.to_lowercase().contains(&needle)
+                || c.emails.iter().any(|e| e.to_lowercase().contains(&needle))
+        })
+        .flat_map(|c| c.emails.iter().map(|e| (e.clone(), c.name.clone())))
+        .collect())
+}
+
+/// Keep entries whose name or address contains the pattern (case-insensitive)
+fn filter_matches(json: &Value, pattern: &str) -> Vec<(String, String)> {
+    let needle = pattern.to_lowercase();
+    json.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let addr = entry["address"].as_str()?.to_string();
+            let name = entry["name"].as_str().unwrap_or("").to_string();
+            let hit =
+                addr.to_lowercase().contains(&needle) || name.to_lowercase().contains(&needle);
+            hit.then_some((addr, name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_matches() {
+        let json = serde_json::json!([
+            {"name": "Alice Smith", "address": "alice@example.com"},
+            {"name": "", "address": "bob@example.com"},
+            {"name": "Carol", "address": "c@other.org"}
+        ]);
+        let m = filter_matches(&json, "SMITH");
+        assert_eq!(m, vec![("alice@example.com".into(), "Alice Smith".into())]);
+        assert_eq!(filter_matches(&json, "example").len(), 2);
+    }
+}
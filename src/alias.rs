@@ -0,0 +1,211 @@
+//! mutt alias file management
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::color;
+use crate::config::{self, Config};
+use crate::mime;
+
+/// Arguments for `mu alias`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+
+    /// Alias file (default: `[muttrc] alias_file`)
+    #[arg(short, long, global = true)]
+    file: Option<String>,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Add the sender of the message on stdin
+    Add {
+        /// Nickname to use instead of a generated one
+        #[arg(long)]
+        nick: Option<String>,
+    },
+    /// Find aliases by nickname, name or address
+    Grep { pattern: String },
+}
+
+/// One `alias nick Name <address>` line
+#[derive(Debug, PartialEq)]
+struct Entry {
+    nick: String,
+    name: String,
+    address: String,
+}
+
+/// Manage the alias file
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let path = config::expand_tilde(args.file.as_deref().unwrap_or(&cfg.muttrc.alias_file));
+    let content = std::fs::read_to_string(&path).unwrap_or_default();
+    let entries = parse(&content);
+
+    match args.action {
+        Action::Add { nick } => add(&path, content, &entries, nick)?,
+        Action::Grep { pattern } => {
+            let pattern = pattern.to_lowercase();
+            for e in entries.iter().filter(|e| {
+                [&e.nick, &e.name, &e.address]
+                    .iter()
+                    .any(|f| f.to_lowercase().contains(&pattern))
+            }) {
+                println!("{}\t{} <{}>", e.nick, e.name, e.address);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Append the sender of the message on stdin, unless the address is already aliased
+fn add(path: &Path, content: String, entries: &[Entry], nick: Option<String>) -> Result<()> {
+    let raw = mime::load(None)?;
+    let message = mime::parse(&raw)?;
+    let sender = message
+        .from()
+        .and_then(|a| a.first())
+        .context("Message has no From address")?;
+    let address = sender
+        .address()
+        .context("Message has no From address")?
+        .to_string();
+    let name = sender.name().unwrap_or("").to_string();
+
+    if let Some(existing) = entries
+        .iter()
+        .find(|e| e.address.eq_ignore_ascii_case(&address))
+    {
+        eprintln!("{} is already aliased as {}", address, existing.nick);
+        return Ok(());
+    }
+    let nick = nick.unwrap_or_else(|| nickname(&name, &address, entries));
+    let line = format_entry(&nick, &name, &address);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut updated = content;
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(&line);
+    updated.push('\n');
+    std::fs::write(path, updated).with_context(|| format!("Failed to write {}", path.display()))?;
+    eprintln!("{} {}", color::ok(), line);
+    Ok(())
+}
+
+/// Parse `alias` lines (other lines are ignored)
+fn parse(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix("alias ")?;
+            let (nick, target) = rest.trim().split_once(char::is_whitespace)?;
+            let target = target.trim();
+            let (name, address) = match target.rsplit_once('<') {
+                Some((name, addr)) => (
+                    name.trim().trim_matches('"').replace("\\\"", "\""),
+                    addr.trim_end_matches('>'),
+                ),
+                None => (String::new(), target),
+            };
+            Some(Entry {
+                nick: nick.to_string(),
+                name,
+                address: address.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `first-last` from the display name (or the address local part), unique in the file
+fn nickname(name: &str, address: &str, taken: &[Entry]) -> String {
+    let clean = |s: &str| -> String {
+        s.to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+            .collect()
+    };
+    // "Doe, John" → John Doe
+    let name = match name.split_once(',') {
+        Some((last, first)) => format!("{} {}", first, last),
+        None => name.to_string(),
+    };
+    let words: Vec<String> = name
+        .split_whitespace()
+        .map(clean)
+        .filter(|w| !w.is_empty())
+        .collect();
+    let base = match words.as_slice() {
+        [] => clean(address.split('@').next().unwrap_or(address)),
+        [only] => only.clone(),
+        [first, .., last] => format!("{}-{}", first, last),
+    };
+
+    let mut nick = base.clone();
+    let mut n = 2;
+    while taken.iter().any(|e| e.nick == nick) {
+        nick = format!("{}{}", base, n);
+        n += 1;
+    }
+    nick
+}
+
+/// `alias nick "Name, With Specials" <address>`
+fn format_entry(nick: &str, name: &str, address: &str) -> String {
+    if name.is_empty() {
+        return format!("alias {} <{}>", nick, address);
+    }
+    let needs_quotes = name.chars().any(|c| ",;:\"()<>@.[]\\".contains(c));
+    let name = if needs_quotes {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_string()
+    };
+    format!("alias {} {} <{}>", nick, name, address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let entries = parse(
+            "# comment\nalias jd John Doe <jd@x.org>\nalias bob bob@x.org\nalias q \"Doe, J.\" <q@x>\n",
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, "John Doe");
+        assert_eq!(entries[1].address, "bob@x.org");
+        assert_eq!(entries[2].name, "Doe, J.");
+    }
+
+    #[test]
+    fn test_nickname() {
+        let taken = parse("alias john-doe x <a@b>\n");
+        assert_eq!(nickname("John Q. Doe", "jd@x.org", &taken), "john-doe2");
+        assert_eq!(nickname("Doe, Jane", "jd@x.org", &[]), "jane-doe");
+        assert_eq!(nickname("", "Bob.Smith@x.org", &[]), "bob.smith");
+    }
+
+    #[test]
+    fn test_format_entry() {
+        assert_eq!(
+            format_entry("jd", "John Doe", "jd@x"),
+            "alias jd John Doe <jd@x>"
+        );
+        assert_eq!(
+            format_entry("jd", "Doe, John", "jd@x"),
+            "alias jd \"Doe, John\" <jd@x>"
+        );
+        assert_eq!(format_entry("b", "", "b@x"), "alias b <b@x>");
+    }
+}
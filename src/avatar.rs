@@ -0,0 +1,302 @@
+//! Sender avatars: local overrides, Gravatar, Libravatar and BIMI, cached on disk
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{self, Avatars, Config};
+use crate::{mime, process};
+
+/// What `[avatars] sources` may list
+pub const SOURCES: [&str; 4] = ["local", "gravatar", "libravatar", "bimi"];
+
+/// Image extensions tried for local overrides
+const EXTENSIONS: &[&str] = &["png", "jpg", "svg"];
+
+/// Arguments for `mu avatar`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Sender address
+    address: String,
+
+    /// Draw the image in the terminal (chafa) instead of printing its path
+    #[arg(long)]
+    show: bool,
+
+    /// Ignore cached results
+    #[arg(long)]
+    refresh: bool,
+}
+
+/// Print (or draw) the avatar for an address
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?;
+    if args.refresh {
+        online::clear_cache(&args.address);
+    }
+    let path = lookup(&cfg.avatars, &args.address)
+        .with_context(|| format!("No avatar for {}", args.address))?;
+    if args.show {
+        show(&path, &mut std::io::stdout().lock())
+    } else {
+        println!("{}", path.display());
+        Ok(())
+    }
+}
+
+/// Avatar image for an address, trying the configured sources in order
+pub fn lookup(cfg: &Avatars, address: &str) -> Option<PathBuf> {
+    let address = address
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase();
+    let domain = mime::domain_of(&address)?;
+    for source in &cfg.sources {
+        let found = match source.as_str() {
+            "local" => local(&config::expand_tilde(&cfg.dir), &address, &domain),
+            source => online::lookup(source, &address, &domain),
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// Draw an image for the terminal to `out` (chafa handles kitty/sixel/symbols)
+pub fn show(path: &Path, out: &mut impl Write) -> Result<()> {
+    if process::which("chafa").is_none() {
+        anyhow::bail!("chafa is not installed");
+    }
+    let output = process::run(
+        Command::new("chafa")
+            .args(["--size=16x8", "--animate=off"])
+            .arg(path),
+    )
+    .context("Failed to run chafa")?;
+    Ok(out.write_all(&output.stdout)?)
+}
+
+/// `<dir>/<address>.png`, then `<dir>/<domain>.png`
+fn local(dir: &Path, address: &str, domain: &str) -> Option<PathBuf> {
+    [address, domain]
+        .iter()
+        .flat_map(|name| {
+            EXTENSIONS
+                .iter()
+                .map(move |ext| dir.join(format!("{}.{}", name, ext)))
+        })
+        .find(|p| p.is_file())
+}
+
+/// Gravatar, Libravatar and BIMI, cached on disk (feature `avatars`)
+#[cfg(feature = "avatars")]
+mod online {
+    use anyhow::{Context, Result};
+    use sha2::{Digest, Sha256};
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::{Duration, SystemTime};
+
+    use super::EXTENSIONS;
+    use crate::process;
+    use crate::{config, http, mime};
+
+    /// How long a failed lookup is remembered before trying the network again
+    const MISS_TTL: Duration = Duration::from_secs(7 * 86_400);
+
+    /// The avatar from a network `source` (None for unknown sources)
+    pub fn lookup(source: &str, address: &str, domain: &str) -> Option<PathBuf> {
+        match source {
+            "gravatar" => cached(address, "gravatar", || {
+                fetch(&format!(
+                    "https://gravatar.com/avatar/{}?d=404&s=128",
+                    hash(address)
+                ))
+            }),
+            "libravatar" => cached(address, "libravatar", || {
+                fetch(&format!(
+                    "https://seccdn.libravatar.org/avatar/{}?d=404&s=128",
+                    hash(address)
+                ))
+            }),
+            "bimi" => cached(domain, "bimi", || match bimi_url(domain)? {
+                Some(url) => fetch(&url),
+                None => Ok(None),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Lowercase hex SHA-256 of the address (accepted by Gravatar and Libravatar)
+    fn hash(address: &str) -> String {
+        Sha256::digest(address.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Image bytes and extension from a URL (None on 404 or non-image, Err when unreachable)
+    fn fetch(url: &str) -> Result<Option<(Vec<u8>, &'static str)>> {
+        let response = http::Request::new("GET", url).send()?;
+        if !response.is_success() {
+            return Ok(None);
+        }
+        let ext = match response.body.get(..4) {
+            Some([0x89, b'P', b'N', b'G']) => "png",
+            Some([0xff, 0xd8, _, _]) => "jpg",
+            _ if String::from_utf8_lossy(&response.body).contains("<svg") => "svg",
+            _ => return Ok(None),
+        };
+        Ok(Some((response.body, ext)))
+    }
+
+    /// Logo URL from the domain's `default._bimi` TXT record
+    fn bimi_url(domain: &str) -> Result<Option<String>> {
+        let output = process::run(Command::new("dig").args([
+            "+short",
+            "TXT",
+            &format!("default._bimi.{}", domain),
+        ]))
+        .context("Failed to run dig")?;
+        if !output.status.success() {
+            anyhow::bail!("dig failed");
+        }
+        Ok(parse_bimi(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// `"v=BIMI1; l=https://.../logo.svg; a=..."` → the `l=` URL
+    fn parse_bimi(txt: &str) -> Option<String> {
+        let record: String = txt.split('"').filter(|s| !s.trim().is_empty()).collect();
+        if !record.trim_start().starts_with("v=BIMI1") {
+            return None;
+        }
+        record
+            .split(';')
+            .find_map(|kv| kv.trim().strip_prefix("l="))
+            .map(|url| url.trim().to_string())
+            .filter(|url| url.starts_with("https://"))
+    }
+
+    /// Cache directory entry for a key and source; a `.miss` file remembers definite misses
+    ///
+    /// Network errors aren't remembered, so an offline run doesn't hide avatars for a week.
+    fn cached(
+        key: &str,
+        source: &str,
+        fetch: impl FnOnce() -> Result<Option<(Vec<u8>, &'static str)>>,
+    ) -> Option<PathBuf> {
+        let dir = cache_dir();
+        let stem = format!("{}-{}", source, hash(key));
+        if let Some(hit) = EXTENSIONS
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+            .find(|p| p.is_file())
+        {
+            return Some(hit);
+        }
+        let miss = dir.join(format!("{}.miss", stem));
+        let fresh_miss = std::fs::metadata(&miss)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| SystemTime::now().duration_since(t).unwrap_or_default() < MISS_TTL);
+        if fresh_miss {
+            return None;
+        }
+
+        std::fs::create_dir_all(&dir).ok()?;
+        match fetch().ok()? {
+            Some((bytes, ext)) => {
+                let path = dir.join(format!("{}.{}", stem, ext));
+                std::fs::write(&path, bytes).ok()?;
+                let _ = std::fs::remove_file(&miss);
+                Some(path)
+            }
+            None => {
+                let _ = std::fs::write(&miss, "");
+                None
+            }
+        }
+    }
+
+    /// Forget cached avatars and misses for an address and its domain
+    pub fn clear_cache(address: &str) {
+        let address = address.trim().to_lowercase();
+        let keys = [Some(address.clone()), mime::domain_of(&address)];
+        let stems: Vec<String> = keys.iter().flatten().map(|k| hash(k)).collect();
+        if let Ok(entries) = std::fs::read_dir(cache_dir()) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if stems.iter().any(|s| name.contains(s.as_str())) {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+
+    fn cache_dir() -> PathBuf {
+        config::data_dir().join("avatars")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_hash() {
+            assert_eq!(
+                hash("test@example.com"),
+                "973dfe463ec85785f5f95af5ba3906eedb2d931c24e69824a89ea65dba4e813b"
+            );
+        }
+
+        #[test]
+        fn test_parse_bimi() {
+            let txt =
+                "\"v=BIMI1; l=https://example.com/logo.svg; a=https://example.com/vmc.pem\"\n";
+            assert_eq!(
+                parse_bimi(txt).as_deref(),
+                Some("https://example.com/logo.svg")
+            );
+            assert_eq!(parse_bimi("\"v=spf1 -all\""), None);
+            assert_eq!(parse_bimi("\"v=BIMI1; l=http://x/logo.svg\""), None);
+        }
+    }
+}
+
+/// Without the `avatars` feature only local overrides are looked up
+#[cfg(not(feature = "avatars"))]
+mod online {
+    use std::path::PathBuf;
+
+    pub fn lookup(source: &str, _: &str, _: &str) -> Option<PathBuf> {
+        tracing::debug!(source, "mu was built without the `avatars` feature");
+        None
+    }
+
+    pub fn clear_cache(_: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_override() {
+        let dir = std::env::temp_dir().join(format!("mu-avatars-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("example.com.svg"), "<svg/>").unwrap();
+        assert_eq!(
+            local(&dir, "bob@example.com", "example.com"),
+            Some(dir.join("example.com.svg"))
+        );
+        std::fs::write(dir.join("bob@example.com.png"), "png").unwrap();
+        assert_eq!(
+            local(&dir, "bob@example.com", "example.com"),
+            Some(dir.join("bob@example.com.png"))
+        );
+        assert_eq!(local(&dir, "x@other.org", "other.org"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
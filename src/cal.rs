@@ -0,0 +1,196 @@
+//! Calendar extraction: invitations in mail → .ics files / khal
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{self, Config};
+use crate::{color, ics, mime, process};
+
+/// Arguments for `mu cal`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Extract calendar parts from matching mail into .ics files
+    Extract {
+        /// notmuch query selecting candidate messages
+        #[arg(short, long, default_value = "tag:inbox date:1m..")]
+        query: String,
+
+        /// Target directory (default: `[calendar] dir`, or ~/.local/share/mu/calendar)
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+
+        /// Also import new/updated events into this khal calendar
+        #[arg(long)]
+        khal: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    New,
+    Updated,
+    Unchanged,
+    /// Existing file has a higher SEQUENCE (older invite arrived later)
+    Stale,
+}
+
+/// Extract calendar invitations
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let Action::Extract { query, dir, khal } = args.action;
+    let dir = dir
+        .or_else(|| cfg.calendar.dir.as_deref().map(config::expand_tilde))
+        .unwrap_or_else(|| config::data_dir().join("calendar"));
+    std::fs::create_dir_all(&dir)?;
+
+    let query = format!(
+        "({}) and (mimetype:text/calendar or mimetype:application/ics)",
+        query
+    );
+    let (mut new, mut updated) = (0, 0);
+    for file in notmuch_files(&query)? {
+        let raw = std::fs::read(&file)?;
+        let Ok(message) = mime::parse(&raw) else {
+            continue;
+        };
+        for ics_text in mime::calendar_parts(&message) {
+            let Some(uid) = event_uid(&ics_text) else {
+                continue;
+            };
+            let path = dir.join(format!("{}.ics", sanitize(&uid)));
+            let outcome = store(&path, &ics_text)?;
+            match outcome {
+                Outcome::New => new += 1,
+                Outcome::Updated => updated += 1,
+                _ => continue,
+            }
+            if let Some(calendar) = &khal {
+                khal_import(calendar, &path)?;
+            }
+        }
+    }
+
+    eprintln!(
+        "{} {} new, {} updated events in {}",
+        color::ok(),
+        new,
+        updated,
+        dir.display()
+    );
+    Ok(())
+}
+
+fn notmuch_files(query: &str) -> Result<Vec<String>> {
+    let output = process::run(Command::new("notmuch").args(["search", "--output=files", query]))
+        .context("Failed to run notmuch search")?;
+    if !output.status.success() {
+        anyhow::bail!("notmuch search failed");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+fn event_uid(text: &str) -> Option<String> {
+    ics::first_event(&ics::parse(text))
+        .into_iter()
+        .find(|p| p.name == "UID")
+        .map(|p| p.value)
+}
+
+fn sequence(text: &str) -> i64 {
+    ics::first_event(&ics::parse(text))
+        .iter()
+        .find(|p| p.name == "SEQUENCE")
+        .and_then(|p| p.value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Write the event unless an equal or newer revision is already stored
+fn store(path: &Path, text: &str) -> Result<Outcome> {
+    let outcome = match std::fs::read_to_string(path) {
+        Err(_) => Outcome::New,
+        Ok(existing) if existing == text => return Ok(Outcome::Unchanged),
+        Ok(existing) if sequence(&existing) > sequence(text) => return Ok(Outcome::Stale),
+        Ok(_) => Outcome::Updated,
+    };
+    std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(outcome)
+}
+
+fn khal_import(calendar: &str, path: &Path) -> Result<()> {
+    let status = process::status(
+        Command::new("khal")
+            .args(["import", "--batch", "-a", calendar])
+            .arg(path),
+    )
+    .context("Failed to run khal import")?;
+    if !status.success() {
+        anyhow::bail!("khal import {} failed", path.display());
+    }
+    Ok(())
+}
+
+/// Make a UID safe to use as a file name
+fn sanitize(uid: &str) -> String {
+    uid.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "@._-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite(seq: u32) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:abc/123@example.com\r\nSEQUENCE:{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            seq
+        )
+    }
+
+    #[test]
+    fn test_uid_and_sanitize() {
+        let uid = event_uid(&invite(0)).unwrap();
+        assert_eq!(sanitize(&uid), "abc_123@example.com");
+    }
+
+    #[test]
+    fn test_store_sequence_handling() {
+        let path = std::env::temp_dir().join(format!("mu-cal-{}.ics", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(store(&path, &invite(1)).unwrap(), Outcome::New);
+        assert_eq!(store(&path, &invite(1)).unwrap(), Outcome::Unchanged);
+        assert_eq!(store(&path, &invite(0)).unwrap(), Outcome::Stale);
+        assert_eq!(store(&path, &invite(2)).unwrap(), Outcome::Updated);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_calendar_parts() {
+        let raw = format!(
+            "Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\nContent-Type: text/plain\r\n\r\nhi\r\n\
+--b\r\nContent-Type: text/calendar; method=REQUEST\r\n\r\n{}--b--\r\n",
+            invite(0)
+        );
+        let msg = mime::parse(raw.as_bytes()).unwrap();
+        assert_eq!(mime::calendar_parts(&msg).len(), 1);
+    }
+}
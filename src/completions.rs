@@ -0,0 +1,121 @@
+//! Shell completion scripts, with values (accounts, channels, saved searches) read at completion time
+
+use anyhow::Result;
+use clap_complete::CompletionCandidate;
+use clap_complete::env::{Bash, EnvCompleter, Fish, Zsh};
+use std::io;
+
+use crate::config::Config;
+use crate::{notmuch, sync};
+
+/// Environment variable the completion scripts call `mu` back with
+pub const ENV: &str = "COMPLETE";
+
+/// Arguments for `mu completions`
+#[derive(clap::Args)]
+pub struct Args {
+    shell: Shell,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Print the registration script for a shell
+pub fn run(args: Args) -> Result<()> {
+    let completer: &dyn EnvCompleter = match args.shell {
+        Shell::Bash => &Bash,
+        Shell::Zsh => &Zsh,
+        Shell::Fish => &Fish,
+    };
+    completer.write_registration(ENV, "mu", "mu", "mu", &mut io::stdout())?;
+    Ok(())
+}
+
+/// `[[accounts]]` names from the config
+pub fn accounts() -> Vec<CompletionCandidate> {
+    Config::load()
+        .map(|cfg| {
+            cfg.accounts
+                .into_iter()
+                .map(|a| CompletionCandidate::new(a.name))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Channels defined in ~/.mbsyncrc
+pub fn channels() -> Vec<CompletionCandidate> {
+    sync::read_mbsyncrc()
+        .map(|content| {
+            sync::channel_names(&content)
+                .into_iter()
+                .map(CompletionCandidate::new)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// notmuch saved searches (`query.NAME` config keys) as `query:NAME`
+pub fn searches() -> Vec<CompletionCandidate> {
+    notmuch::config_list()
+        .map(|list| {
+            saved_searches(&list)
+                .into_iter()
+                .map(|(name, query)| {
+                    CompletionCandidate::new(format!("query:{}", name)).help(Some(query.into()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `(name, query)` of each `query.NAME=QUERY` line of `notmuch config list`
+fn saved_searches(list: &str) -> Vec<(String, String)> {
+    list.lines()
+        .filter_map(|line| line.strip_prefix("query.")?.split_once('='))
+        .map(|(name, query)| (name.to_string(), query.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_saved_searches() {
+        let list = "database.path=/m\nquery.inbox=tag:inbox and not tag:lists\nquery.bugs=from:bugzilla\nuser.name=A";
+        assert_eq!(
+            saved_searches(list),
+            vec![
+                (
+                    "inbox".to_string(),
+                    "tag:inbox and not tag:lists".to_string()
+                ),
+                ("bugs".to_string(), "from:bugzilla".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_searches_from_notmuch() {
+        let list = serde_json::json!({
+            "program": "notmuch",
+            "args": ["config", "list"],
+            "stdout": "database.path=/m\nquery.inbox=tag:inbox and not tag:lists\n",
+        });
+        let found = runner::scoped(Replay::parse(&list.to_string()).unwrap(), searches);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_value(), "query:inbox");
+        assert_eq!(
+            found[0].get_help().map(ToString::to_string).as_deref(),
+            Some("tag:inbox and not tag:lists")
+        );
+        // Completing never fails: without notmuch there's nothing to offer
+        assert!(runner::scoped(Replay::new(Vec::new()), searches).is_empty());
+    }
+}
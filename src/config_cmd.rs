@@ -0,0 +1,383 @@
+//! `mu config`: check the config file, or write a starter one
+
+use anyhow::{Context, Result};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config, PushService};
+use crate::error::Error;
+use crate::schema::{self, Problem};
+use crate::{avatar, color, expire, fzf, i18n, renderer, script, sweep, sync};
+
+/// Arguments for `mu config`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+
+    /// Config file (default: the one mu reads)
+    #[arg(long, global = true)]
+    file: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Report unknown keys, wrong types and bad values, with where they are
+    Check,
+    /// Write a starter config, asking about accounts and notifications
+    Init {
+        /// Replace an existing file
+        #[arg(long)]
+        force: bool,
+
+        /// Don't ask; take every suggested answer
+        #[arg(long)]
+        defaults: bool,
+    },
+}
+
+/// Check or scaffold the config
+pub fn run(args: Args) -> Result<()> {
+    let path = args.file.unwrap_or_else(config::config_path);
+    match args.action {
+        Action::Check => check(&path),
+        Action::Init { force, defaults } => init(&path, force, defaults),
+    }
+}
+
+fn check(path: &Path) -> Result<()> {
+    if !path.exists() {
+        eprintln!(
+            "{} no config at {} (mu uses the defaults)",
+            color::ok(),
+            path.display()
+        );
+        return Ok(());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let problems = problems(&text);
+    if problems.is_empty() {
+        eprintln!("{} {} is valid", color::ok(), path.display());
+        return Ok(());
+    }
+    for problem in &problems {
+        let (line, column) = problem.position(&text);
+        let hint = match &problem.suggestion {
+            Some(s) => format!(" (did you mean `{}`?)", s),
+            None => String::new(),
+        };
+        println!(
+            "{}:{}:{}: {}{}",
+            path.display(),
+            line,
+            column,
+            problem.message,
+            hint
+        );
+    }
+    Err(Error::ConfigInvalid {
+        path: path.display().to_string(),
+        message: format!("{} problem(s)", problems.len()),
+    }
+    .into())
+}
+
+/// Schema problems, then (when the file parses) values mu would reject at run time
+pub fn problems(text: &str) -> Vec<Problem> {
+    let mut problems = schema::check::<Config>(text);
+    if problems.is_empty()
+        && let Ok(cfg) = Config::parse(text)
+    {
+        problems = values(&cfg)
+            .into_iter()
+            .map(|(path, message, suggestion)| Problem {
+                suggestion,
+                ..Problem::new(schema::span_of(text, &path).unwrap_or(0..0), message)
+            })
+            .collect();
+    }
+    problems
+}
+
+type Finding = (String, String, Option<String>);
+
+/// Values that are well-typed but unusable: (dotted path, message, suggestion)
+fn values(cfg: &Config) -> Vec<Finding> {
+    let mut found = Vec::new();
+    let mut add = |path: String, message: String, suggestion: Option<&str>| {
+        found.push((path, message, suggestion.map(String::from)))
+    };
+    for (path, age) in ages(cfg) {
+        if let Err(e) = sweep::parse_age(age) {
+            add(path, e.to_string(), None);
+        }
+    }
+    for i in expire::ageless(&cfg.expire) {
+        let message = "a rule that deletes or archives needs an `age`".to_string();
+        add(format!("expire.rules.{}", i), message, None);
+    }
+    for (i, source) in cfg.avatars.sources.iter().enumerate() {
+        if !avatar::SOURCES.contains(&source.as_str()) {
+            let message = format!("unknown avatar source `{}`", source);
+            add(
+                format!("avatars.sources.{}", i),
+                message,
+                schema::closest(source, &avatar::SOURCES),
+            );
+        }
+    }
+    if !["terminal", "external"].contains(&cfg.mailcap.images.as_str()) {
+        let message = "`mailcap.images` should be `terminal` or `external`".to_string();
+        add("mailcap.images".to_string(), message, None);
+    }
+    if let Err(e) = cfg.muttrc.search_emit.parse::<fzf::Emit>() {
+        add("muttrc.search_emit".to_string(), e.to_string(), None);
+    }
+    for (i, target) in cfg.push.targets.iter().enumerate() {
+        if let Some(missing) = push_missing(target) {
+            let message = format!("{:?} push target needs `{}`", target.service, missing);
+            add(format!("push.targets.{}", i), message, None);
+        }
+    }
+    found.extend(renderers_and_script(cfg));
+    found.extend(names_and_messages(cfg));
+    found
+}
+
+/// Renderers without arguments to run them with, and a script that doesn't compile
+fn renderers_and_script(cfg: &Config) -> Vec<Finding> {
+    let mut found = Vec::new();
+    for (i, name) in cfg.render.chain.iter().enumerate() {
+        if !renderer::KNOWN.contains(&name.as_str()) && !cfg.render.args.contains_key(name) {
+            found.push((
+                format!("render.chain.{}", i),
+                format!("renderer `{}` needs `[render.args]`", name),
+                schema::closest(name, &renderer::KNOWN).map(String::from),
+            ));
+        }
+    }
+    if let Err(e) = script::load(&cfg.script) {
+        found.push(("script.path".to_string(), format!("{:#}", e), None));
+    }
+    found
+}
+
+/// Every age setting, by path
+fn ages(cfg: &Config) -> Vec<(String, &str)> {
+    let mut ages = vec![("sweep.age".to_string(), cfg.sweep.age.as_str())];
+    for (i, rule) in cfg.sweep.senders.iter().enumerate() {
+        if let Some(age) = &rule.age {
+            ages.push((format!("sweep.senders.{}.age", i), age));
+        }
+    }
+    for (i, rule) in cfg.expire.rules.iter().enumerate() {
+        if let Some(age) = &rule.age {
+            ages.push((format!("expire.rules.{}.age", i), age));
+        }
+    }
+    ages
+}
+
+/// Duplicate account names and unknown `[locale.messages]` keys
+fn names_and_messages(cfg: &Config) -> Vec<Finding> {
+    let mut found = Vec::new();
+    for (i, account) in cfg.accounts.iter().enumerate() {
+        if cfg.accounts[..i].iter().any(|a| a.name == account.name) {
+            let message = format!("account `{}` is defined twice", account.name);
+            found.push((format!("accounts.{}.name", i), message, None));
+        }
+    }
+    for key in cfg.locale.messages.keys() {
+        if !i18n::KEYS.contains(&key.as_str()) {
+            found.push((
+                format!("locale.messages.{}", key),
+                format!("unknown message `{}`", key),
+                schema::closest(key, &i18n::KEYS).map(String::from),
+            ));
+        }
+    }
+    found
+}
+
+/// The key a push service can't do without, if it's missing
+fn push_missing(target: &config::PushTarget) -> Option<&'static str> {
+    match target.service {
+        PushService::Ntfy if target.topic.is_none() => Some("topic"),
+        PushService::Pushover if target.user.is_none() => Some("user"),
+        PushService::Gotify if target.url.is_none() => Some("url"),
+        PushService::Pushover | PushService::Gotify if target.token_command.is_none() => {
+            Some("token_command")
+        }
+        _ => None,
+    }
+}
+
+fn init(path: &Path, force: bool, defaults: bool) -> Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!("{} exists (--force replaces it)", path.display());
+    }
+    let channels = sync::read_mbsyncrc()
+        .map(|rc| sync::channel_names(&rc))
+        .unwrap_or_default();
+    let mut prompt = Prompt {
+        input: io::stdin().lock(),
+        output: io::stderr(),
+        defaults,
+    };
+    let text = scaffold(&channels, &mut prompt)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    eprintln!(
+        "{} wrote {} (`mu config check` after editing it)",
+        color::ok(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Questions on `output`, answers from `input`; an empty answer (or end of input) takes
+/// the suggestion
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+    defaults: bool,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    fn ask(&mut self, question: &str, suggestion: &str) -> Result<String> {
+        if self.defaults {
+            return Ok(suggestion.to_string());
+        }
+        match suggestion {
+            "" => write!(self.output, "{}: ", question)?,
+            _ => write!(self.output, "{} [{}]: ", question, suggestion)?,
+        }
+        self.output.flush()?;
+        let mut line = String::new();
+        self.input.read_line(&mut line)?;
+        Ok(match line.trim() {
+            "" => suggestion.to_string(),
+            answer => answer.to_string(),
+        })
+    }
+
+    fn yes(&mut self, question: &str, suggestion: bool) -> Result<bool> {
+        let answer = self.ask(question, if suggestion { "Y/n" } else { "y/N" })?;
+        Ok(match answer.to_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => suggestion,
+        })
+    }
+}
+
+/// A starter config from the answers; accounts are suggested from the mbsync `channels`
+fn scaffold(channels: &[String], prompt: &mut Prompt<impl BufRead, impl Write>) -> Result<String> {
+    let suggested = match channels {
+        [] => "personal".to_string(),
+        _ => channels.join(", "),
+    };
+    let names = prompt.ask("Accounts (comma-separated)", &suggested)?;
+    let mut text = String::from("# mu configuration; `mu config check` validates it\n");
+    for name in list(&names) {
+        let maildir = prompt.ask(&format!("{}: folder under the notmuch root", name), &name)?;
+        let addresses = prompt.ask(&format!("{}: your addresses (comma-separated)", name), "")?;
+        text.push_str(&format!("\n[[accounts]]\nname = {}\n", quote(&name)));
+        if maildir != name {
+            text.push_str(&format!("maildir = {}\n", quote(&maildir)));
+        }
+        let addresses: Vec<String> = list(&addresses).iter().map(|a| quote(a)).collect();
+        if !addresses.is_empty() {
+            text.push_str(&format!("addresses = [{}]\n", addresses.join(", ")));
+        }
+    }
+
+    let desktop = prompt.yes("Desktop notifications for new mail?", true)?;
+    let avatars = desktop && prompt.yes("Sender avatars in notifications?", true)?;
+    let topic = prompt.ask("ntfy topic for phone notifications (empty: none)", "")?;
+    text.push_str(&format!("\n[push]\ndesktop = {}\n", desktop));
+    if !topic.is_empty() {
+        text.push_str(&format!(
+            "\n[[push.targets]]\nservice = \"ntfy\"\ntopic = {}\n",
+            quote(&topic)
+        ));
+    }
+    text.push_str(&format!("\n[avatars]\nnotifications = {}\n", avatars));
+
+    let language = prompt.ask("Language: en, de, fr, es, pl (empty: from $LANG)", "")?;
+    if !language.is_empty() {
+        text.push_str(&format!("\n[locale]\nlanguage = {}\n", quote(&language)));
+    }
+    Ok(text)
+}
+
+fn list(answer: &str) -> Vec<String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn quote(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_are_located() {
+        let text = "[[filter.rules]]\nheader = \"x\"\nfolder = \"x\"\n\n[avatars]\nsources = [\"local\", \"gravtar\"]\n\n[[push.targets]]\nservice = \"gotify\"\nurl = \"https://push.example\"\n\n[render]\nchain = [\"elink\", \"builtin\"]\n";
+        let found: Vec<_> = problems(text)
+            .iter()
+            .map(|p| (p.position(text).0, p.suggestion.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (6, Some("gravatar".to_string())),
+                (8, None),
+                (13, Some("elinks".to_string()))
+            ]
+        );
+        let text = "[[expire.rules]]\nfolder = \"*/Trash\"\naction = \"delete\"\n";
+        let found = problems(text);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].message.contains("needs an `age`"));
+        // Filter regexes are compiled with the config, so a bad one stops it loading
+        let text = "[[filter.rules]]\nmatches = \"(unclosed\"\nfolder = \"x\"\n";
+        let found = problems(text);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].position(text).0, 2);
+        assert!(
+            found[0].message.contains("bad regex"),
+            "{}",
+            found[0].message
+        );
+    }
+
+    #[test]
+    fn test_scaffold_passes_check() {
+        let answers = "\nwork\nme@work.example, me@alt.example\nn\nmail-abc\nPL\n";
+        let mut prompt = Prompt {
+            input: answers.as_bytes(),
+            output: Vec::new(),
+            defaults: false,
+        };
+        let text = scaffold(&["gmail".to_string()], &mut prompt).unwrap();
+        assert!(text.contains("name = \"gmail\"\nmaildir = \"work\"\n"));
+        assert!(text.contains("addresses = [\"me@work.example\", \"me@alt.example\"]"));
+        assert!(text.contains("desktop = false"));
+        assert!(text.contains("topic = \"mail-abc\""));
+        assert_eq!(problems(&text), []);
+        let questions = String::from_utf8(prompt.output).unwrap();
+        assert!(questions.starts_with("Accounts (comma-separated) [gmail]: "));
+    }
+}
@@ -0,0 +1,296 @@
+//! Contacts: vCard import/export, khard and CardDAV sync
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::color;
+use crate::config::{self, Config};
+use crate::http::Request;
+use crate::vcard::{self, Contact};
+
+/// Arguments for `mu contacts`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Import contacts from vCard files
+    Import {
+        /// .vcf files to import
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Export all contacts as vCard
+    Export {
+        /// Output file (writes stdout if not provided)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Sync with the khard directory and/or CardDAV server from the config
+    Sync,
+    /// List stored contacts
+    List,
+}
+
+/// Manage the contact store
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let path = store_path();
+    let mut store = load(&path)?;
+
+    match args.action {
+        Action::Import { files } => {
+            let before = store.len();
+            for file in files {
+                let text = std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                merge_into(&mut store, vcard::parse_all(&text));
+            }
+            save(&path, &store)?;
+            eprintln!(
+                "{} {} contacts ({} new)",
+                color::ok(),
+                store.len(),
+                store.len() - before
+            );
+        }
+        Action::Export { output } => {
+            let text: String = store.iter().map(vcard::to_vcard).collect();
+            match output {
+                Some(p) => std::fs::write(p, text)?,
+                None => print!("{}", text),
+            }
+        }
+        Action::Sync => {
+            sync(&mut store, cfg)?;
+            save(&path, &store)?;
+        }
+        Action::List => {
+            for c in &store {
+                println!("{}\t{}", c.emails.join(", "), c.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Location of the local contact store
+pub fn store_path() -> PathBuf {
+    config::data_dir().join("contacts.vcf")
+}
+
+/// Load stored contacts (empty if the store doesn't exist yet)
+pub fn load(path: &Path) -> Result<Vec<Contact>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(vcard::parse_all(&text))
+}
+
+fn save(path: &Path, store: &[Contact]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text: String = store.iter().map(vcard::to_vcard).collect();
+    std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Merge contacts, deduping by key; returns how many were new
+fn merge_into(store: &mut Vec<Contact>, incoming: Vec<Contact>) -> usize {
+    let mut added = 0;
+    for contact in incoming {
+        match store.iter_mut().find(|c| c.key() == contact.key()) {
+            Some(existing) => existing.merge(&contact),
+            None => {
+                store.push(contact);
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+fn sync(store: &mut Vec<Contact>, cfg: &Config) -> Result<()> {
+    let c = &cfg.contacts;
+    if c.khard_dir.is_none() && c.carddav_url.is_none() {
+        anyhow::bail!("Configure [contacts] khard_dir or carddav_url first");
+    }
+
+    if let Some(dir) = &c.khard_dir {
+        let dir = config::expand_tilde(dir);
+        let added = merge_into(store, read_vdir(&dir)?);
+        let pushed = write_vdir(&dir, store)?;
+        eprintln!("{} khard: {} pulled, {} pushed", color::ok(), added, pushed);
+    }
+
+    if let Some(url) = &c.carddav_url {
+        let password = match &c.carddav_password_command {
+            Some(cmd) => Some(config::command_output(cmd)?),
+            None => None,
+        };
+        let auth = c.carddav_user.as_deref().zip(password.as_deref());
+        let remote = carddav_fetch(url, auth)?;
+        let remote_keys: Vec<String> = remote.iter().map(Contact::key).collect();
+        let added = merge_into(store, remote);
+        let mut pushed = 0;
+        for contact in store.iter().filter(|c| !remote_keys.contains(&c.key())) {
+            carddav_put(url, auth, contact)?;
+            pushed += 1;
+        }
+        eprintln!(
+            "{} CardDAV: {} pulled, {} pushed",
+            color::ok(),
+            added,
+            pushed
+        );
+    }
+    Ok(())
+}
+
+/// Read every .vcf in a vdir (khard/vdirsyncer layout)
+fn read_vdir(dir: &Path) -> Result<Vec<Contact>> {
+    let mut contacts = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "vcf") {
+            contacts.extend(vcard::parse_all(&std::fs::read_to_string(&path)?));
+        }
+    }
+    Ok(contacts)
+}
+
+/// Write contacts missing from the vdir as `<uid>.vcf`; returns how many were written
+fn write_vdir(dir: &Path, store: &[Contact]) -> Result<usize> {
+    let existing: Vec<String> = read_vdir(dir)?.iter().map(Contact::key).collect();
+    let mut written = 0;
+    for contact in store.iter().filter(|c| !existing.contains(&c.key())) {
+        std::fs::write(
+            dir.join(format!("{}.vcf", contact.uid)),
+            vcard::to_vcard(contact),
+        )?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+const ADDRESSBOOK_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<C:addressbook-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+  <D:prop><D:getetag/><C:address-data/></D:prop>
+</C:addressbook-query>"#;
+
+fn carddav_fetch(url: &str, auth: Option<(&str, &str)>) -> Result<Vec<Contact>> {
+    let mut req = Request::new("REPORT", url)
+        .header("Depth: 1")
+        .header("Content-Type: application/xml; charset=utf-8")
+        .body(ADDRESSBOOK_QUERY);
+    if let Some((user, pass)) = auth {
+        req = req.basic_auth(user, pass);
+    }
+    let resp = req.send()?;
+    if !resp.is_success() {
+        anyhow::bail!("CardDAV REPORT failed with HTTP {}", resp.status);
+    }
+    Ok(vcard::parse_all(&address_data(&resp.text())?))
+}
+
+/// Pull the vCard payloads out of a multistatus response
+fn address_data(xml: &str) -> Result<String> {
+    let re =
+        Regex::new(r"(?s)<[A-Za-z0-9]*:?address-data[^>]*>(.*?)</[A-Za-z0-9]*:?address-data>")?;
+    Ok(re
+        .captures_iter(xml)
+        .filter_map(|c| c.get(1))
+        .map(|m| unescape_xml(m.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&amp;", "&")
+}
+
+fn carddav_put(url: &str, auth: Option<(&str, &str)>, contact: &Contact) -> Result<()> {
+    let target = format!("{}/{}.vcf", url.trim_end_matches('/'), contact.uid);
+    let mut req = Request::new("PUT", &target)
+        .header("Content-Type: text/vcard; charset=utf-8")
+        .header("If-None-Match: *")
+        .body(&vcard::to_vcard(contact));
+    if let Some((user, pass)) = auth {
+        req = req.basic_auth(user, pass);
+    }
+    let resp = req.send()?;
+    if !resp.is_success() {
+        anyhow::bail!("CardDAV PUT {} failed with HTTP {}", target, resp.status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str, email: &str) -> Contact {
+        Contact {
+            uid: vcard::generate_uid(email),
+            name: name.into(),
+            emails: vec![email.into()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_into_dedupes() {
+        let mut store = vec![contact("Jane", "jane@example.com")];
+        let added = merge_into(
+            &mut store,
+            vec![
+                contact("", "JANE@example.com"),
+                contact("Bob", "bob@example.com"),
+            ],
+        );
+        assert_eq!(added, 1);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store[0].name, "Jane");
+    }
+
+    #[test]
+    fn test_vdir_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("mu-vdir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = vec![contact("Jane", "jane@example.com")];
+        assert_eq!(write_vdir(&dir, &store).unwrap(), 1);
+        assert_eq!(write_vdir(&dir, &store).unwrap(), 0);
+        assert_eq!(read_vdir(&dir).unwrap()[0].name, "Jane");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_address_data() {
+        let xml = "<d:multistatus><d:response><card:address-data>BEGIN:VCARD\nFN:A &amp; B\nEND:VCARD\n\
+</card:address-data></d:response></d:multistatus>";
+        let contacts = vcard::parse_all(&address_data(xml).unwrap());
+        assert_eq!(contacts[0].name, "A & B");
+    }
+
+    #[test]
+    fn test_sync_requires_source() {
+        let mut store = Vec::new();
+        assert!(sync(&mut store, &Config::default()).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! Opt-in full-text index of decrypted PGP mail, kept apart from the main index
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::{color, config, decrypt, index, notmuch};
+
+/// Arguments for `mu crypt`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Decrypt encrypted mail through the gpg agent and index the plaintext
+    Index {
+        /// Messages to index
+        #[arg(short, long, default_value = "tag:encrypted")]
+        query: String,
+
+        /// Rebuild from scratch
+        #[arg(long)]
+        rebuild: bool,
+    },
+    /// Delete the decrypted index
+    Clear,
+}
+
+/// Build, update or delete the decrypted index
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &index_dir())
+}
+
+/// [`run`] on the index in `dir`
+fn run_with(args: Args, dir: &Path) -> Result<()> {
+    match args.action {
+        Action::Index { query, rebuild } => {
+            let (added, removed, failed) = update(dir, &query, rebuild)?;
+            if failed > 0 {
+                eprintln!(
+                    "{} {} message(s) could not be decrypted",
+                    color::warn(),
+                    failed
+                );
+            }
+            eprintln!(
+                "{} Indexed {} decrypted, removed {} deleted",
+                color::ok(),
+                added,
+                removed
+            );
+        }
+        Action::Clear => {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)
+                    .with_context(|| format!("Failed to remove {}", dir.display()))?;
+            }
+            eprintln!("{} Removed {}", color::ok(), dir.display());
+        }
+    }
+    Ok(())
+}
+
+/// `~/.local/share/mu/crypt-index`, readable by the owner only
+pub fn index_dir() -> PathBuf {
+    config::data_dir().join("crypt-index")
+}
+
+/// Sync the index in `dir` with the matching files; returns (added, removed, failed)
+fn update(dir: &Path, query: &str, rebuild: bool) -> Result<(usize, usize, usize)> {
+    if rebuild && dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("Failed to clear {}", dir.display()))?;
+    }
+    // Restrict before anything decrypted is written
+    std::fs::create_dir_all(dir)?;
+    protect(dir)?;
+    let files: HashSet<String> = notmuch::search("files", query)?.into_iter().collect();
+    let mut failed = 0;
+    let (added, removed) = index::sync(dir, &files, false, |path| {
+        let plain = std::fs::read(path)
+            .ok()
+            .and_then(|raw| decrypt::decrypted(&raw).ok());
+        if plain.is_none() {
+            failed += 1;
+        }
+        plain
+    })?;
+    Ok((added, removed, failed))
+}
+
+#[cfg(unix)]
+fn protect(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("Failed to restrict {}", dir.display()))
+}
+
+#[cfg(not(unix))]
+fn protect(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    const ENCRYPTED: &str = "From: a@x\r\nSubject: Secret plans\r\nMessage-ID: <s@x>\r\n\
+Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=b\r\n\r\n\
+--b\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n\
+--b\r\nContent-Type: application/octet-stream\r\n\r\n\
+-----BEGIN PGP MESSAGE-----\r\nhQEM\r\n-----END PGP MESSAGE-----\r\n--b--\r\n";
+
+    /// `notmuch` listing `files`, and gpg answering every `--decrypt` with `status`
+    fn replay(files: &[&Path], status: &str) -> Replay {
+        let files: String = files.iter().map(|f| format!("{}\n", f.display())).collect();
+        let search = serde_json::json!({
+            "program": "notmuch",
+            "args": ["search", "--output=files", "--", "tag:encrypted"],
+            "stdout": files,
+        });
+        let gpg = serde_json::json!({
+            "program": "gpg",
+            "args": ["--batch", "--quiet", "--status-fd", "1", "--output", "output", "--decrypt"],
+            "stdout": status,
+        });
+        Replay::parse(&format!("{}\n{}", search, gpg)).unwrap()
+    }
+
+    fn indexing(query: &str, rebuild: bool) -> Args {
+        Args {
+            action: Action::Index {
+                query: query.to_string(),
+                rebuild,
+            },
+        }
+    }
+
+    #[test]
+    fn test_update_decrypts_through_gpg() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("crypt-index");
+        let secret = tmp.path().join("secret");
+        let plain = tmp.path().join("plain");
+        std::fs::write(&secret, ENCRYPTED).unwrap();
+        std::fs::write(&plain, "Subject: Not encrypted\r\n\r\nHello\r\n").unwrap();
+
+        let okay = replay(&[&secret, &plain], "[GNUPG:] DECRYPTION_OKAY\n");
+        let counts = runner::scoped(okay, || update(&dir, "tag:encrypted", false)).unwrap();
+        // The plain message has nothing to decrypt, so it's counted as failed
+        assert_eq!(counts, (1, 0, 1));
+        let found = index::listing(&dir, "secret").unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("id:s@x "));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        // Gone from the query: dropped from the index
+        let gone = replay(&[], "");
+        let counts = runner::scoped(gone, || update(&dir, "tag:encrypted", false)).unwrap();
+        assert_eq!(counts, (0, 1, 0));
+        assert!(index::listing(&dir, "secret").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update_skips_what_gpg_cannot_decrypt() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("crypt-index");
+        let secret = tmp.path().join("secret");
+        std::fs::write(&secret, ENCRYPTED).unwrap();
+        let failing = replay(&[&secret], "[GNUPG:] DECRYPTION_FAILED\n");
+        let counts = runner::scoped(failing, || update(&dir, "tag:encrypted", false)).unwrap();
+        assert_eq!(counts, (0, 0, 1));
+        assert!(index::listing(&dir, "secret").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_and_clear() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("crypt-index");
+        let secret = tmp.path().join("secret");
+        std::fs::write(&secret, ENCRYPTED).unwrap();
+        let okay = || replay(&[&secret], "[GNUPG:] DECRYPTION_OKAY\n");
+        runner::scoped(okay(), || run_with(indexing("tag:encrypted", false), &dir)).unwrap();
+        // Already indexed: an update adds nothing, a rebuild indexes it again
+        let counts = runner::scoped(okay(), || update(&dir, "tag:encrypted", false)).unwrap();
+        assert_eq!(counts, (0, 0, 0));
+        let counts = runner::scoped(okay(), || update(&dir, "tag:encrypted", true)).unwrap();
+        assert_eq!(counts, (1, 0, 0));
+
+        let clear = Args {
+            action: Action::Clear,
+        };
+        run_with(clear, &dir).unwrap();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,197 @@
+//! Outgoing encryption guard: don't answer an encrypted thread in plaintext
+
+use anyhow::{Context, Result};
+use mail_parser::{Address, Message};
+use regex::Regex;
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+
+use crate::error::Error;
+use crate::{color, mime, notmuch, process};
+
+/// Arguments for `mu cryptcheck`
+#[derive(clap::Args)]
+pub struct Args {
+    /// After the check passes, pipe the message into this sendmail command
+    #[arg(long, value_name = "CMD")]
+    exec: Option<String>,
+
+    /// Arguments passed through to the sendmail command (recipients)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    sendmail_args: Vec<String>,
+}
+
+/// Check a draft (or outgoing message) on stdin
+pub fn run(args: Args) -> Result<()> {
+    let mut raw = Vec::new();
+    io::stdin().read_to_end(&mut raw)?;
+    let message = mime::parse(&raw)?;
+
+    let ids = referenced_ids(&message)?;
+    let thread_encrypted = ids.iter().any(|id| {
+        notmuch::raw(&notmuch::query_for(id))
+            .ok()
+            .and_then(|r| mime::parse(&r).ok().map(|m| is_encrypted(&m)))
+            .unwrap_or(false)
+    });
+
+    if thread_encrypted {
+        let missing: Vec<String> = recipients(&message, &args.sendmail_args)
+            .into_iter()
+            .filter(|addr| !has_public_key(addr))
+            .collect();
+        let problems = problems(is_encrypted(&message), &missing);
+        if !problems.is_empty() {
+            for p in &problems {
+                eprintln!("{} {}", color::fail(), p);
+            }
+            anyhow::bail!("refusing to send: the conversation you're replying to was encrypted");
+        }
+    }
+
+    match args.exec {
+        Some(cmd) => pipe_to(&cmd, &args.sendmail_args, &raw),
+        None => Ok(()),
+    }
+}
+
+fn problems(draft_encrypted: bool, missing_keys: &[String]) -> Vec<String> {
+    let mut problems = Vec::new();
+    if !draft_encrypted {
+        problems.push("this reply is not encrypted".to_string());
+    }
+    for addr in missing_keys {
+        problems.push(format!("no public key for {}", addr));
+    }
+    problems
+}
+
+/// PGP/MIME, S/MIME, inline PGP, or a draft marked for encryption by neomutt
+fn is_encrypted(message: &Message) -> bool {
+    let marked = ["X-Mutt-PGP", "X-Mutt-SMIME"]
+        .iter()
+        .any(|h| message.header_raw(*h).is_some_and(|v| v.contains('E')));
+    marked
+        || message.parts.iter().any(|part| {
+            let ct = mime::content_type(part);
+            ct == "multipart/encrypted"
+                || ct.ends_with("pkcs7-mime")
+                || (ct == "text/plain"
+                    && mime::part_text(part).contains("-----BEGIN PGP MESSAGE-----"))
+        })
+}
+
+/// Message-ids from In-Reply-To and References
+fn referenced_ids(message: &Message) -> Result<Vec<String>> {
+    let id = Regex::new(r"<([^<>\s]+)>")?;
+    let mut ids: Vec<String> = Vec::new();
+    for header in ["In-Reply-To", "References"] {
+        let value = message.header_raw(header).unwrap_or("");
+        for cap in id.captures_iter(value) {
+            if !ids.contains(&cap[1].to_string()) {
+                ids.push(cap[1].to_string());
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// To/Cc/Bcc addresses plus recipients given on the sendmail command line
+fn recipients(message: &Message, sendmail_args: &[String]) -> Vec<String> {
+    let mut addrs: Vec<String> = [message.to(), message.cc(), message.bcc()]
+        .into_iter()
+        .flatten()
+        .flat_map(Address::iter)
+        .filter_map(|a| a.address().map(str::to_lowercase))
+        .collect();
+    addrs.extend(
+        sendmail_args
+            .iter()
+            .filter(|a| a.contains('@') && !a.starts_with('-'))
+            .map(|a| a.to_lowercase()),
+    );
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+fn has_public_key(address: &str) -> bool {
+    process::status(
+        Command::new("gpg")
+            .args([
+                "--batch",
+                "--list-keys",
+                "--with-colons",
+                &format!("<{}>", address),
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )
+    .is_ok_and(|s| s.success())
+}
+
+/// Hand the message on to the real sendmail
+fn pipe_to(cmd: &str, args: &[String], raw: &[u8]) -> Result<()> {
+    let output = process::run_with_input(&mut process::shell_with_args(cmd, args), raw)
+        .with_context(|| format!("Failed to run {}", cmd))?;
+    if !output.status.success() {
+        return Err(Error::command_failed(cmd, &output.stderr).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: Args,
+    }
+
+    const REPLY: &[u8] = b"To: Alice <Alice@example.com>\r\nCc: bob@example.com\r\n\
+In-Reply-To: <m2@x>\r\nReferences: <m1@x> <m2@x>\r\nSubject: Re: secret\r\n\r\nok\r\n";
+
+    #[test]
+    fn test_referenced_ids_and_recipients() {
+        let msg = mime::parse(REPLY).unwrap();
+        assert_eq!(referenced_ids(&msg).unwrap(), vec!["m2@x", "m1@x"]);
+        let extra = vec![
+            "-oi".to_string(),
+            "--".to_string(),
+            "carol@example.com".to_string(),
+        ];
+        assert_eq!(
+            recipients(&msg, &extra),
+            vec!["alice@example.com", "bob@example.com", "carol@example.com"]
+        );
+    }
+
+    #[test]
+    fn test_is_encrypted() {
+        assert!(!is_encrypted(&mime::parse(REPLY).unwrap()));
+        let pgp = b"Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=b\r\n\r\n\
+--b\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n--b--\r\n";
+        assert!(is_encrypted(&mime::parse(pgp).unwrap()));
+        let inline = b"Subject: x\r\n\r\n-----BEGIN PGP MESSAGE-----\r\nabc\r\n";
+        assert!(is_encrypted(&mime::parse(inline).unwrap()));
+        assert!(is_encrypted(
+            &mime::parse(b"X-Mutt-PGP: ES\r\n\r\nhi").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_problems() {
+        assert!(problems(true, &[]).is_empty());
+        assert_eq!(problems(false, &["a@x".to_string()]).len(), 2);
+    }
+
+    #[test]
+    fn test_sendmail_passthrough_args() {
+        let cli = Cli::try_parse_from(["mu", "--exec", "msmtp", "-oi", "--", "a@x"]).unwrap();
+        assert_eq!(cli.args.exec.as_deref(), Some("msmtp"));
+        assert_eq!(cli.args.sendmail_args, vec!["-oi", "--", "a@x"]);
+    }
+}
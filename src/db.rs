@@ -0,0 +1,272 @@
+//! notmuch database maintenance: compaction, tag backups and consistency checks
+
+use anyhow::{Context, Result};
+use mail_parser::DateTime;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, Config};
+use crate::{color, import, maildir, mime, notmuch, process, runner, sync};
+
+/// Xapian tables rewritten by a compaction (glass backend)
+const TABLES: usize = 6;
+
+/// Arguments for `mu db`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Compact the Xapian database, showing progress per table
+    Compact {
+        /// Keep the uncompacted database in this directory
+        #[arg(long)]
+        backup: Option<String>,
+    },
+    /// Back up all tags to a gzipped dump
+    Dump {
+        /// Output file (default: ~/.local/share/mu/dumps/tags-DATE.gz)
+        file: Option<String>,
+    },
+    /// Restore tags from a dump
+    Restore {
+        file: String,
+
+        /// Add the dumped tags instead of replacing each message's tags
+        #[arg(long)]
+        accumulate: bool,
+    },
+    /// Compare database entries with the files on disk
+    Verify {
+        /// Rescan every directory (`notmuch new --full-scan`) and check again
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
+/// Run a maintenance action
+pub fn run(args: Args) -> Result<()> {
+    match args.action {
+        Action::Compact { backup } => compact(backup.as_deref()),
+        Action::Dump { file } => {
+            let path = match file {
+                Some(file) => config::expand_tilde(&file),
+                None => default_dump(),
+            };
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let output = format!("--output={}", path.display());
+            notmuch_status(&["dump", "--gzip", &output])?;
+            eprintln!("{} Tags saved to {}", color::ok(), path.display());
+            Ok(())
+        }
+        Action::Restore { file, accumulate } => {
+            let input = format!("--input={}", config::expand_tilde(&file).display());
+            let mut args = vec!["restore", input.as_str()];
+            if accumulate {
+                args.push("--accumulate");
+            }
+            notmuch_status(&args)?;
+            eprintln!("{} Tags restored from {}", color::ok(), file);
+            Ok(())
+        }
+        Action::Verify { repair } => verify(repair),
+    }
+}
+
+/// `~/.local/share/mu/dumps/tags-YYYY-MM-DD.gz`
+fn default_dump() -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let date = DateTime::from_timestamp(now).to_rfc3339();
+    config::data_dir()
+        .join("dumps")
+        .join(format!("tags-{}.gz", &date[..10]))
+}
+
+fn notmuch_status(args: &[&str]) -> Result<()> {
+    let status = process::status(Command::new("notmuch").args(args))
+        .with_context(|| format!("Failed to run notmuch {}", args[0]))?;
+    if !status.success() {
+        anyhow::bail!("notmuch {} failed", args[0]);
+    }
+    Ok(())
+}
+
+/// `notmuch compact`, turning its per-table report into a progress bar
+fn compact(backup: Option<&str>) -> Result<()> {
+    let xapian = xapian_dir();
+    let before = xapian.as_deref().map(dir_size);
+
+    let mut cmd = Command::new("notmuch");
+    cmd.arg("compact");
+    if let Some(dir) = backup {
+        cmd.arg(format!("--backup={}", config::expand_tilde(dir).display()));
+    }
+    // Read as it runs for the progress bar, so not through `process::run`
+    let _span = process::span(&cmd).entered();
+    if let Some(output) = runner::intercept(&cmd) {
+        let output = output.context("Failed to run notmuch compact")?;
+        if !output.status.success() {
+            anyhow::bail!("notmuch compact failed");
+        }
+        return Ok(());
+    }
+    let started = Instant::now();
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run notmuch compact")?;
+    let mut done = 0;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = line?;
+            if let Some(table) = table_of(&line) {
+                done += 1;
+                sync::print_progress(done.min(TABLES), TABLES, table);
+            }
+        }
+    }
+    sync::clear_progress();
+    let status = child.wait()?;
+    process::exited(started, status);
+    if !status.success() {
+        anyhow::bail!("notmuch compact failed");
+    }
+
+    match (before, xapian.as_deref().map(dir_size)) {
+        (Some(before), Some(after)) => eprintln!(
+            "{} Compacted {} → {}",
+            color::ok(),
+            mime::format_size(before),
+            mime::format_size(after)
+        ),
+        _ => eprintln!("{} Compacted", color::ok()),
+    }
+    Ok(())
+}
+
+/// Table name from a compactor report line (`    postlist: Reduced by 48% …`)
+fn table_of(line: &str) -> Option<&str> {
+    let (name, _) = line.trim().split_once(':')?;
+    (!name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase())).then_some(name)
+}
+
+/// The Xapian directory, in the mail root (`.notmuch/xapian`) or the XDG data dir
+fn xapian_dir() -> Option<PathBuf> {
+    let root = notmuch::config_get("database.path").map(PathBuf::from);
+    let profile = std::env::var("NOTMUCH_PROFILE").unwrap_or_else(|_| "default".to_string());
+    let xdg = std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| config::home_dir().join(".local/share"))
+        .join("notmuch")
+        .join(profile)
+        .join("xapian");
+    root.map(|r| r.join(".notmuch/xapian"))
+        .into_iter()
+        .chain([xdg])
+        .find(|p| p.is_dir())
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Report files the database lists but are gone, and mail it doesn't know
+fn verify(repair: bool) -> Result<()> {
+    let (missing, unindexed) = check()?;
+    report(&missing, &unindexed);
+    if !repair || (missing.is_empty() && unindexed.is_empty()) {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{}",
+        color::epaint("1;36", format!("=== {} ===", "Repairing"))
+    );
+    notmuch_status(&["new", "--full-scan"])?;
+    let (missing, unindexed) = check()?;
+    report(&missing, &unindexed);
+    Ok(())
+}
+
+/// `(in the database but not on disk, on disk but not in the database)`
+fn check() -> Result<(Vec<String>, Vec<String>)> {
+    let root = import::maildir_root(&Config::load()?);
+    let indexed: HashSet<String> = notmuch::search("files", "*")?.into_iter().collect();
+    let on_disk: HashSet<String> = maildir::files(&root)?
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    Ok(compare(&indexed, &on_disk))
+}
+
+fn compare(indexed: &HashSet<String>, on_disk: &HashSet<String>) -> (Vec<String>, Vec<String>) {
+    let mut missing: Vec<String> = indexed.difference(on_disk).cloned().collect();
+    let mut unindexed: Vec<String> = on_disk.difference(indexed).cloned().collect();
+    missing.sort();
+    unindexed.sort();
+    (missing, unindexed)
+}
+
+fn report(missing: &[String], unindexed: &[String]) {
+    if missing.is_empty() && unindexed.is_empty() {
+        eprintln!("{} Database matches the files on disk", color::ok());
+        return;
+    }
+    for (title, files) in [
+        ("Missing from disk", missing),
+        ("Not in the database", unindexed),
+    ] {
+        if files.is_empty() {
+            continue;
+        }
+        println!("{}", color::header(format!("{} ({})", title, files.len())));
+        for f in files.iter().take(20) {
+            println!("  {}", f);
+        }
+        if files.len() > 20 {
+            println!("  … and {} more", files.len() - 20);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_of() {
+        assert_eq!(
+            table_of("    postlist: Reduced by 48% 8192K (17024K -> 8832K)"),
+            Some("postlist")
+        );
+        assert_eq!(table_of("termlist: doesn't exist"), Some("termlist"));
+        assert_eq!(table_of("Compacting database..."), None);
+        assert_eq!(table_of("Note: Compacting into /x"), None);
+    }
+
+    #[test]
+    fn test_compare() {
+        let set = |f: &[&str]| f.iter().map(|s| s.to_string()).collect::<HashSet<_>>();
+        let (missing, unindexed) = compare(&set(&["/m/a", "/m/b"]), &set(&["/m/b", "/m/c"]));
+        assert_eq!(missing, vec!["/m/a"]);
+        assert_eq!(unindexed, vec!["/m/c"]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Compare two messages (revised contracts, edited newsletters, suspected spoofs): decoded
+//! headers and body text, as a unified or side-by-side diff
+
+use anyhow::Result;
+use difflib::sequencematcher::{Opcode, SequenceMatcher};
+
+use crate::{color, mime, notmuch, width};
+
+/// Headers compared, when either message has them
+const HEADERS: [&str; 8] = [
+    "From",
+    "Reply-To",
+    "Return-Path",
+    "To",
+    "Cc",
+    "Date",
+    "Subject",
+    "Message-ID",
+];
+
+/// Arguments for `mu diff`
+#[derive(clap::Args)]
+pub struct Args {
+    /// First message (message-id or notmuch query)
+    first: String,
+
+    /// Second message
+    second: String,
+
+    /// Two columns instead of a unified diff
+    #[arg(short = 'y', long)]
+    side_by_side: bool,
+
+    /// Unchanged lines shown around each change
+    #[arg(short = 'U', long, value_name = "LINES", default_value_t = 3)]
+    context: usize,
+
+    /// Width of the side-by-side view
+    #[arg(short = 'W', long, default_value_t = 160)]
+    width: usize,
+}
+
+/// Print the differences between two messages
+pub fn run(args: Args) -> Result<()> {
+    let first = as_text(&args.first)?;
+    let second = as_text(&args.second)?;
+    let a: Vec<&str> = first.lines().collect();
+    let b: Vec<&str> = second.lines().collect();
+    let groups = SequenceMatcher::new(&a, &b).get_grouped_opcodes(args.context);
+    if groups.is_empty() {
+        eprintln!("{} Headers and bodies are identical", color::ok());
+        return Ok(());
+    }
+    if args.side_by_side {
+        print!("{}", side_by_side(&a, &b, &groups, args.width));
+    } else {
+        print!("{}", unified(&a, &b, &groups, (&args.first, &args.second)));
+    }
+    Ok(())
+}
+
+/// Decoded headers, a blank line, then the body as plain text
+fn as_text(reference: &str) -> Result<String> {
+    let raw = notmuch::raw(&notmuch::query_for(reference))?;
+    let message = mime::parse(&raw)?;
+    let mut text: String = HEADERS
+        .into_iter()
+        .filter_map(|name| {
+            let value = message.header_raw(name)?;
+            Some(format!("{}: {}\n", name, mime::decode_words(value)))
+        })
+        .collect();
+    text.push('\n');
+    text.push_str(&mime::body_text(&message)?);
+    Ok(text)
+}
+
+/// `diff -u` style: `-` lines from the first message, `+` from the second
+fn unified(a: &[&str], b: &[&str], groups: &[Vec<Opcode>], names: (&str, &str)) -> String {
+    let mut out = format!(
+        "{}\n{}\n",
+        color::paint("1", format!("--- {}", names.0)),
+        color::paint("1", format!("+++ {}", names.1))
+    );
+    for group in groups {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let hunk = format!(
+            "@@ -{},{} +{},{} @@",
+            first.first_start + 1,
+            last.first_end - first.first_start,
+            first.second_start + 1,
+            last.second_end - first.second_start
+        );
+        out.push_str(&format!("{}\n", color::paint("36", hunk)));
+        for op in group {
+            if op.tag == "equal" {
+                for line in &a[op.first_start..op.first_end] {
+                    out.push_str(&format!(" {}\n", line));
+                }
+                continue;
+            }
+            for line in &a[op.first_start..op.first_end] {
+                out.push_str(&format!("{}\n", color::paint("31", format!("-{}", line))));
+            }
+            for line in &b[op.second_start..op.second_end] {
+                out.push_str(&format!("{}\n", color::paint("32", format!("+{}", line))));
+            }
+        }
+    }
+    out
+}
+
+/// `diff -y` style: the first message on the left, `<` `>` `|` marking changed lines
+fn side_by_side(a: &[&str], b: &[&str], groups: &[Vec<Opcode>], width: usize) -> String {
+    let column = width.saturating_sub(3) / 2;
+    let mut rows = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        if i > 0 {
+            rows.push(color::paint("2", "···"));
+        }
+        for op in group {
+            let left = &a[op.first_start..op.first_end];
+            let right = &b[op.second_start..op.second_end];
+            for row in 0..left.len().max(right.len()) {
+                let (l, r) = (left.get(row), right.get(row));
+                let marker = match (op.tag.as_str(), l, r) {
+                    ("equal", ..) => ' ',
+                    (_, Some(_), Some(_)) => '|',
+                    (_, Some(_), None) => '<',
+                    _ => '>',
+                };
+                let l = cell(l.copied().unwrap_or_default(), column);
+                let r = cell(r.copied().unwrap_or_default(), column);
+                rows.push(match marker {
+                    ' ' => format!("{} {} {}", l, marker, r).trim_end().to_string(),
+                    _ => format!(
+                        "{} {} {}",
+                        color::paint("31", l),
+                        marker,
+                        color::paint("32", r.trim_end())
+                    ),
+                });
+            }
+        }
+    }
+    rows.iter().map(|row| format!("{}\n", row)).collect()
+}
+
+/// `text` cut or padded to `columns`
+fn cell(text: &str, columns: usize) -> String {
+    width::pad(width::take(text, columns), columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(a: &[&str], b: &[&str]) -> Vec<Vec<Opcode>> {
+        SequenceMatcher::new(a, b).get_grouped_opcodes(1)
+    }
+
+    #[test]
+    fn test_unified_and_side_by_side() {
+        let a = [
+            "From: a@x",
+            "Subject: Offer",
+            "",
+            "Price: 100",
+            "Terms: 30 days",
+            "Thanks",
+        ];
+        let b = [
+            "From: a@x",
+            "Subject: Offer",
+            "",
+            "Price: 120",
+            "Terms: 30 days",
+            "Thanks",
+            "PS",
+        ];
+        let groups = groups(&a, &b);
+        assert_eq!(
+            unified(&a, &b, &groups, ("one", "two")),
+            concat!(
+                "--- one\n+++ two\n@@ -3,4 +3,5 @@\n \n-Price: 100\n+Price: 120\n",
+                " Terms: 30 days\n Thanks\n+PS\n"
+            )
+        );
+        assert_eq!(
+            side_by_side(&a, &b, &groups, 23),
+            concat!(
+                "\n",
+                "Price: 100 | Price: 120\n",
+                "Terms: 30    Terms: 30\n",
+                "Thanks       Thanks\n",
+                "           > PS\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_identical() {
+        assert!(groups(&["a", "b"], &["a", "b"]).is_empty());
+    }
+}
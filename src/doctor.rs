@@ -0,0 +1,331 @@
+//! Environment diagnostics: tools, configs, notmuch database, neomutt glue
+
+use anyhow::Result;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::{self, Config};
+use crate::renderer::{self, Renderer};
+use crate::{color, config_cmd, hooks, mailcap, notmuch, process, theme};
+
+/// Arguments for `mu doctor`
+#[derive(clap::Args)]
+pub struct Args {}
+
+#[derive(Debug, PartialEq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One diagnostic result, with a suggested fix when it isn't OK
+struct Check {
+    level: Level,
+    label: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(label: impl Into<String>) -> Self {
+        Self {
+            level: Level::Ok,
+            label: label.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(label: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            level: Level::Warn,
+            label: label.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(label: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            level: Level::Fail,
+            label: label.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// External tools: (name, required, what it's for)
+const TOOLS: &[(&str, bool, &str)] = &[
+    ("notmuch", true, "search, tagging, fzf listings"),
+    ("mbsync", true, "mu sync"),
+    ("fzf", false, "mu fzf"),
+    ("gpg", false, "encrypted/signed mail"),
+    ("gpgsm", false, "S/MIME mail"),
+    ("curl", false, "CardDAV, mu links --resolve"),
+];
+
+/// Run all checks and print fixes for anything that's off
+pub fn run(_args: Args) -> Result<()> {
+    let sections = [
+        ("Tools", tool_checks()),
+        ("Configuration", config_checks()),
+        ("notmuch database", notmuch_checks()),
+        ("neomutt glue", glue_checks()),
+    ];
+
+    let mut failures = 0;
+    for (title, checks) in &sections {
+        println!("{}", color::header(title));
+        for check in checks {
+            let mark = match check.level {
+                Level::Ok => color::paint("32", "✓"),
+                Level::Warn => color::paint("33", "!"),
+                Level::Fail => color::paint("31", "✗"),
+            };
+            println!("{} {}", mark, check.label);
+            if let Some(fix) = &check.fix {
+                println!("  {}", color::paint("2", format!("→ {}", fix)));
+            }
+            failures += usize::from(check.level == Level::Fail);
+        }
+        println!();
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{} problem(s) found", failures);
+    }
+    Ok(())
+}
+
+fn tool_checks() -> Vec<Check> {
+    let notifier = if cfg!(target_os = "macos") {
+        "terminal-notifier"
+    } else {
+        "notify-send"
+    };
+    let mut checks: Vec<Check> = TOOLS
+        .iter()
+        .map(|(name, required, purpose)| tool(name, *required, purpose))
+        .collect();
+    checks.push(tool(notifier, false, "new mail notifications"));
+    checks.extend(renderer_checks());
+    checks
+}
+
+/// Each HTML renderer in the chain; only having none that can run is a failure
+fn renderer_checks() -> Vec<Check> {
+    let chain = renderer::chain();
+    let mut checks: Vec<Check> = chain
+        .iter()
+        .map(|r| match r {
+            Renderer::Native if r.installed() => Check::ok("native HTML layout"),
+            Renderer::Native => Check::warn(
+                "native HTML layout not compiled in",
+                "build with the `native` feature",
+            ),
+            Renderer::Builtin if r.installed() => Check::ok("built-in HTML converter"),
+            Renderer::Builtin => Check::warn(
+                "built-in HTML converter not compiled in",
+                "build with the `html` feature",
+            ),
+            Renderer::External { program, .. } => tool(program, false, "HTML rendering"),
+        })
+        .collect();
+    if !chain.iter().any(Renderer::installed) {
+        checks.push(Check::fail(
+            "no renderer in `[render] chain` can run — HTML mail won't display",
+            install_hint("w3m"),
+        ));
+    }
+    checks
+}
+
+fn tool(name: &str, required: bool, purpose: &str) -> Check {
+    match process::which(name) {
+        Some(path) => Check::ok(format!("{} ({})", name, path.display())),
+        None if required => Check::fail(
+            format!("{} not found — needed for {}", name, purpose),
+            install_hint(name),
+        ),
+        None => Check::warn(
+            format!("{} not found — optional, for {}", name, purpose),
+            install_hint(name),
+        ),
+    }
+}
+
+fn install_hint(name: &str) -> String {
+    let package = match name {
+        "mbsync" => "isync",
+        "notify-send" => "libnotify",
+        other => other,
+    };
+    if cfg!(target_os = "macos") {
+        format!("brew install {}", package)
+    } else {
+        format!(
+            "install the `{}` package with your distribution's package manager",
+            package
+        )
+    }
+}
+
+fn config_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let mu_config = config::config_path();
+    let problems = std::fs::read_to_string(&mu_config)
+        .map(|text| config_cmd::problems(&text).len())
+        .unwrap_or(0);
+    checks.push(match Config::load() {
+        Ok(_) if problems > 0 => Check::warn(
+            format!("{}: {} problem(s)", mu_config.display(), problems),
+            "see `mu config check`",
+        ),
+        Ok(_) if mu_config.exists() => Check::ok(format!("{} is valid", mu_config.display())),
+        Ok(_) => Check::ok("no mu config (using defaults)"),
+        Err(e) => Check::fail(
+            format!("{}: {:#}", mu_config.display(), e),
+            "fix the TOML error above (`mu config check` shows every problem)",
+        ),
+    });
+
+    let theme = theme::path();
+    if theme.exists() {
+        checks.push(match theme::load(&theme) {
+            Ok(_) => Check::ok(format!("{} is valid", theme.display())),
+            Err(e) => Check::warn(
+                format!("{:#}", e).trim_end(),
+                "fix the theme (the default colors are used meanwhile)",
+            ),
+        });
+    }
+
+    let mbsyncrc = config::mbsyncrc_path();
+    checks.push(match std::fs::read_to_string(&mbsyncrc) {
+        Ok(content) => mbsyncrc_check(&content),
+        Err(_) => Check::fail(
+            format!("{} not found", mbsyncrc.display()),
+            "create ~/.mbsyncrc with your IMAP accounts and channels",
+        ),
+    });
+    checks
+}
+
+/// mu sync needs at least one Channel (or Group) to sync
+fn mbsyncrc_check(content: &str) -> Check {
+    let channels = content
+        .lines()
+        .filter(|l| l.starts_with("Channel "))
+        .count();
+    let stores = content
+        .lines()
+        .filter(|l| l.starts_with("IMAPStore ") || l.starts_with("IMAPAccount "))
+        .count();
+    match (channels, stores) {
+        (0, _) => Check::fail(
+            "~/.mbsyncrc defines no channels",
+            "add a `Channel` section for each account",
+        ),
+        (_, 0) => Check::warn(
+            format!("~/.mbsyncrc: {} channel(s) but no IMAP store", channels),
+            "check the IMAPAccount/IMAPStore sections",
+        ),
+        (c, _) => Check::ok(format!("~/.mbsyncrc: {} channel(s)", c)),
+    }
+}
+
+fn notmuch_checks() -> Vec<Check> {
+    if process::which("notmuch").is_none() {
+        return vec![Check::warn(
+            "skipped (notmuch not installed)",
+            "install notmuch, or use `mu index`",
+        )];
+    }
+
+    let db = notmuch::config_get("database.path").unwrap_or_default();
+    if db.is_empty() || !Path::new(&db).exists() {
+        return vec![Check::fail(
+            "notmuch database.path is not set or missing",
+            "run `notmuch setup`",
+        )];
+    }
+
+    let mut checks = Vec::new();
+    checks.push(
+        match process::run(Command::new("notmuch").args(["count", "*"])) {
+            Ok(out) if out.status.success() => Check::ok(format!(
+                "{}: {} messages",
+                db,
+                String::from_utf8_lossy(&out.stdout).trim()
+            )),
+            _ => Check::fail(
+                format!("{}: database can't be read", db),
+                "run `notmuch new` (or `notmuch compact`)",
+            ),
+        },
+    );
+
+    checks.push(match hooks::not_installed() {
+        Ok(missing) if missing.is_empty() => Check::ok("mu hooks installed"),
+        Ok(missing) => Check::warn(
+            format!("hooks not managed by mu: {}", missing.join(", ")),
+            "mu hooks install",
+        ),
+        Err(e) => Check::warn(format!("hooks: {:#}", e), "mu hooks status"),
+    });
+    checks
+}
+
+fn glue_checks() -> Vec<Check> {
+    let home = config::home_dir();
+    let rc_candidates = [
+        home.join(".config/neomutt/neomuttrc"),
+        home.join(".neomuttrc"),
+        home.join(".config/mutt/muttrc"),
+        home.join(".muttrc"),
+    ];
+
+    let mut checks = Vec::new();
+    let rc = rc_candidates.iter().find(|p| p.exists());
+    checks.push(match rc.map(|p| (p, std::fs::read_to_string(p).unwrap_or_default())) {
+        Some((path, content)) if content.contains("mu.muttrc") || content.contains("mu fzf") => {
+            Check::ok(format!("{} uses mu", path.display()))
+        }
+        Some((path, _)) => Check::warn(
+            format!("{} doesn't source the mu glue", path.display()),
+            "mu muttrc generate -o ~/.config/neomutt/mu.muttrc, then add `source ~/.config/neomutt/mu.muttrc`",
+        ),
+        None => Check::warn("no neomuttrc found", "create ~/.config/neomutt/neomuttrc"),
+    });
+
+    let mailcap = home.join(".mailcap");
+    let has_block = std::fs::read_to_string(&mailcap).is_ok_and(|c| c.contains(mailcap::BEGIN));
+    checks.push(if has_block {
+        Check::ok("~/.mailcap has mu entries")
+    } else {
+        Check::warn(
+            "~/.mailcap has no mu entries",
+            "mu mailcap generate --write",
+        )
+    });
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool() {
+        assert_eq!(tool("sh", true, "shell").level, Level::Ok);
+        assert_eq!(tool("mu-no-such-tool", true, "x").level, Level::Fail);
+        assert_eq!(tool("mu-no-such-tool", false, "x").level, Level::Warn);
+    }
+
+    #[test]
+    fn test_mbsyncrc_check() {
+        let rc = "IMAPAccount work\nHost imap.example.com\n\nChannel work\nFar :work-remote:\n";
+        assert_eq!(mbsyncrc_check(rc).level, Level::Ok);
+        assert_eq!(mbsyncrc_check("IMAPAccount x\n").level, Level::Fail);
+        assert_eq!(mbsyncrc_check("Channel x\n").level, Level::Warn);
+    }
+}
@@ -0,0 +1,258 @@
+//! Retention policy: delete or archive-compress old mail per folder
+
+use anyhow::{Context, Result};
+use mail_parser::MessageParser;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, Config, Expire, ExpireAction};
+use crate::{color, import, maildir, mbox, process, runner, sweep};
+
+/// Arguments for `mu expire`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Report what would expire without touching anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Apply the retention rules, then reindex
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?;
+    let expired = apply(&cfg, args.dry_run, false)?;
+    if expired > 0 && !args.dry_run {
+        import::index()?;
+    }
+    Ok(())
+}
+
+/// Expire matching messages and print a per-folder report (unless quiet); returns the number expired
+///
+/// A `--simulate` run only reports, as with `dry_run`.
+pub fn apply(cfg: &Config, dry_run: bool, quiet: bool) -> Result<usize> {
+    if cfg.expire.rules.is_empty() {
+        return Ok(0);
+    }
+    let dry_run = dry_run || runner::simulating();
+    let root = import::maildir_root(cfg);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let planned = plan(&cfg.expire, &root, now)?;
+
+    let mut expired = 0;
+    for ((folder, action), files) in &planned {
+        let label = match action {
+            ExpireAction::Delete => "delete",
+            ExpireAction::ArchiveCompress => "archive",
+            ExpireAction::Keep => continue,
+        };
+        if !quiet {
+            println!("{:>6}  {:<8} {}", files.len(), label, folder);
+        }
+        if dry_run {
+            continue;
+        }
+        if *action == ExpireAction::ArchiveCompress {
+            compress(
+                &config::expand_tilde(&cfg.expire.archive_dir),
+                folder,
+                files,
+            )?;
+        }
+        for file in files {
+            std::fs::remove_file(file)
+                .with_context(|| format!("Failed to remove {}", file.display()))?;
+        }
+        expired += files.len();
+    }
+    if dry_run {
+        let total: usize = planned.values().map(Vec::len).sum();
+        eprintln!("{} Would expire {} messages", color::ok(), total);
+    } else if !quiet {
+        eprintln!("{} Expired {} messages", color::ok(), expired);
+    }
+    Ok(expired)
+}
+
+/// Files to expire, grouped by folder and action
+fn plan(
+    expire: &Expire,
+    root: &Path,
+    now: i64,
+) -> Result<BTreeMap<(String, ExpireAction), Vec<PathBuf>>> {
+    if let Some(i) = ageless(expire).next() {
+        anyhow::bail!(
+            "expire rule {} ({}) deletes or archives but has no age",
+            i + 1,
+            expire.rules[i].folder
+        );
+    }
+    let rules = expire
+        .rules
+        .iter()
+        .map(|r| {
+            let age = r
+                .age
+                .as_deref()
+                .map(sweep::parse_age)
+                .transpose()?
+                .unwrap_or(0);
+            Ok((glob(&r.folder)?, age, r.action))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut planned: BTreeMap<(String, ExpireAction), Vec<PathBuf>> = BTreeMap::new();
+    for file in maildir::files(root)? {
+        let Some(folder) = folder_of(root, &file) else {
+            continue;
+        };
+        let Some((_, min_age, action)) = rules.iter().find(|(re, _, _)| re.is_match(&folder))
+        else {
+            continue;
+        };
+        if *action == ExpireAction::Keep || now - date(&file).unwrap_or(now) < *min_age {
+            continue;
+        }
+        planned.entry((folder, *action)).or_default().push(file);
+    }
+    Ok(planned)
+}
+
+/// `Lists/rust` for `<root>/Lists/rust/cur/<file>`
+fn folder_of(root: &Path, file: &Path) -> Option<String> {
+    let dir = file.parent()?.parent()?;
+    Some(dir.strip_prefix(root).ok()?.to_string_lossy().into_owned())
+}
+
+/// Date header timestamp (the file's mtime when missing)
+fn date(file: &Path) -> Option<i64> {
+    let raw = std::fs::read(file).ok()?;
+    let header = MessageParser::default()
+        .parse_headers(&raw)
+        .and_then(|m| m.date().map(|d| d.to_timestamp()));
+    header.or_else(|| {
+        let modified = std::fs::metadata(file).ok()?.modified().ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+    })
+}
+
+/// Indexes of rules that would delete or archive without an age (everything in the folder)
+pub fn ageless(expire: &Expire) -> impl Iterator<Item = usize> + '_ {
+    expire
+        .rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| rule.action != ExpireAction::Keep && rule.age.is_none())
+        .map(|(i, _)| i)
+}
+
+/// Folder glob: `*` stays within one path segment, `**` crosses them
+fn glob(pattern: &str) -> Result<Regex> {
+    let escaped = regex::escape(pattern)
+        .replace(r"\*\*", ".*")
+        .replace(r"\*", "[^/]*");
+    Ok(Regex::new(&format!("^{}$", escaped))?)
+}
+
+/// Append the messages to `<archive_dir>/<folder>.mbox.gz` (gzip members concatenate)
+fn compress(archive_dir: &Path, folder: &str, files: &[PathBuf]) -> Result<()> {
+    std::fs::create_dir_all(archive_dir)
+        .with_context(|| format!("Failed to create {}", archive_dir.display()))?;
+    let target = archive_dir.join(format!("{}.mbox.gz", folder.replace('/', "_")));
+    let mut entries = Vec::new();
+    for file in files {
+        let raw =
+            std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        entries.extend(mbox::entry(&raw));
+    }
+
+    let output = process::run_with_input(Command::new("gzip").arg("-c"), &entries)
+        .context("Failed to run gzip")?;
+    if !output.status.success() {
+        anyhow::bail!("gzip failed writing {}", target.display());
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&target)
+        .and_then(|mut out| out.write_all(&output.stdout))
+        .with_context(|| format!("Failed to write {}", target.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("*/Trash").unwrap().is_match("work/Trash"));
+        assert!(!glob("*/Trash").unwrap().is_match("a/b/Trash"));
+        assert!(glob("Lists/**").unwrap().is_match("Lists/rust/announce"));
+        assert!(!glob("Lists/**").unwrap().is_match("Listsx"));
+    }
+
+    #[test]
+    fn test_plan_first_match() {
+        let root = std::env::temp_dir().join(format!("mu-expire-{}", std::process::id()));
+        let old = b"Date: Mon, 1 Jan 2001 00:00:00 +0000\r\nSubject: x\r\n\r\nold\r\n";
+        maildir::deliver(&root.join("work/Trash"), old, Some("S")).unwrap();
+        maildir::deliver(&root.join("Receipts/Trash"), old, Some("S")).unwrap();
+        let expire = Config::parse(
+            r#"
+[[expire.rules]]
+folder = "Receipts/**"
+action = "keep"
+
+[[expire.rules]]
+folder = "*/Trash"
+age = "30d"
+action = "delete"
+"#,
+        )
+        .unwrap()
+        .expire;
+        let planned = plan(&expire, &root, 1_000_000_000 + 40 * 86_400).unwrap();
+        let keys: Vec<_> = planned.keys().cloned().collect();
+        assert_eq!(keys, vec![("work/Trash".to_string(), ExpireAction::Delete)]);
+        // Too young at "now" = the Date itself
+        assert!(plan(&expire, &root, 978_307_200).unwrap().is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_destructive_rule_needs_age() {
+        let expire = Config::parse(
+            "[[expire.rules]]\nfolder = \"*/Trash\"\naction = \"keep\"\n\n\
+[[expire.rules]]\nfolder = \"Lists/**\"\naction = \"archive-compress\"\n",
+        )
+        .unwrap()
+        .expire;
+        assert_eq!(ageless(&expire).collect::<Vec<_>>(), [1]);
+        let err = plan(&expire, Path::new("/nonexistent"), 0).unwrap_err();
+        assert!(err.to_string().contains("rule 2 (Lists/**)"), "{}", err);
+    }
+
+    #[test]
+    fn test_simulated_apply_keeps_files() {
+        let root = std::env::temp_dir().join(format!("mu-expire-sim-{}", std::process::id()));
+        let old = b"Date: Mon, 1 Jan 2001 00:00:00 +0000\r\nSubject: x\r\n\r\nold\r\n";
+        maildir::deliver(&root.join("work/Trash"), old, Some("S")).unwrap();
+        let cfg = Config::parse(&format!(
+            "[index]\nmaildir = {:?}\n\n[[expire.rules]]\nfolder = \"*/Trash\"\nage = \"30d\"\naction = \"delete\"\n",
+            root.display().to_string()
+        ))
+        .unwrap();
+        let expired = runner::scoped(runner::Replay::new(Vec::new()), || {
+            apply(&cfg, false, true).unwrap()
+        });
+        assert_eq!(expired, 0);
+        assert_eq!(maildir::files(&root).unwrap().len(), 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
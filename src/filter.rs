@@ -0,0 +1,262 @@
+//! Local delivery filter (procmail replacement)
+//!
+//! Mail is never bounced for mu's sake: a message that doesn't parse goes to the default
+//! folder, and anything keeping it from being delivered (a broken config, a full disk)
+//! exits with EX_TEMPFAIL so the MTA keeps it and tries again.
+
+use anyhow::{Context, Result};
+use mail_parser::Message;
+use std::io::{self, Read};
+use std::process::Command;
+
+use crate::config::{self, Config, Filter, FilterRule};
+use crate::error::Error;
+use crate::{maildir, mime, process};
+
+/// Arguments for `mu filter`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Maildir root (overrides `[filter] maildir`)
+    #[arg(short, long)]
+    maildir: Option<String>,
+
+    /// Print where the message would go without delivering it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Where a message ends up
+#[derive(Debug, PartialEq)]
+struct Decision<'a> {
+    /// Folder to deliver into (None when discarded or forwarded only)
+    folder: Option<&'a str>,
+    forward: Option<&'a str>,
+}
+
+/// Read a message on stdin and deliver it according to the filter rules
+pub fn run(args: Args) -> Result<()> {
+    let mut raw = Vec::new();
+    io::stdin()
+        .read_to_end(&mut raw)
+        .context("Failed to read the message")
+        .and_then(|_| run_with(args, &Config::load()?, &raw))
+        .map_err(|e| e.context(Error::Deferred))
+}
+
+fn run_with(args: Args, cfg: &Config, raw: &[u8]) -> Result<()> {
+    let decision = match mime::parse(raw) {
+        Ok(message) => decide(&cfg.filter, &message, raw.len()),
+        Err(e) => {
+            tracing::warn!(error = %format!("{:#}", e), "unparsable message goes to the default folder");
+            Decision {
+                folder: Some(&cfg.filter.default_folder),
+                forward: None,
+            }
+        }
+    };
+
+    if args.dry_run {
+        println!("folder: {}", decision.folder.unwrap_or("(none)"));
+        if let Some(addr) = decision.forward {
+            println!("forward: {}", addr);
+        }
+        return Ok(());
+    }
+
+    // The copy is kept before it's forwarded: a failed forward then can't lose the message,
+    // and a failed delivery can't have sent it on already
+    if let Some(folder) = decision.folder {
+        let root = config::expand_tilde(args.maildir.as_deref().unwrap_or(&cfg.filter.maildir));
+        maildir::deliver(&root.join(folder), raw, None)?;
+    }
+    if let Some(addr) = decision.forward {
+        match forward(raw, addr) {
+            // Delivered already: deferring would deliver it twice
+            Err(e) if decision.folder.is_some() => {
+                tracing::warn!(error = %format!("{:#}", e), to = addr, "forward failed");
+            }
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Apply the first matching rule (or the default folder)
+fn decide<'a>(filter: &'a Filter, message: &Message, size: usize) -> Decision<'a> {
+    for rule in &filter.rules {
+        if !matches(rule, message, size) {
+            continue;
+        }
+        let folder = match (&rule.folder, rule.discard, &rule.forward) {
+            (_, true, _) => None,
+            (Some(folder), _, _) => Some(folder.as_str()),
+            (None, _, Some(_)) => None,
+            (None, _, None) => Some(filter.default_folder.as_str()),
+        };
+        return Decision {
+            folder,
+            forward: rule.forward.as_deref(),
+        };
+    }
+    Decision {
+        folder: Some(&filter.default_folder),
+        forward: None,
+    }
+}
+
+/// Check every condition the rule sets (a rule without conditions matches everything)
+fn matches(rule: &FilterRule, message: &Message, size: usize) -> bool {
+    if let (Some(name), Some(pattern)) = (&rule.header, &rule.matches)
+        && !header_values(message, name)
+            .iter()
+            .any(|v| pattern.is_match(v))
+    {
+        return false;
+    }
+    if let Some(query) = &rule.query
+        && !query_matches(query, message)
+    {
+        return false;
+    }
+    if rule.larger_than.is_some_and(|min| size <= min) {
+        return false;
+    }
+    rule.spam.is_none_or(|spam| spam == is_spam(message))
+}
+
+/// Decoded values of every header with the given name
+fn header_values(message: &Message, name: &str) -> Vec<String> {
+    message
+        .headers_raw()
+        .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| mime::decode_words(v))
+        .collect()
+}
+
+/// Evaluate notmuch-style terms: `from:x to:y subject:z list:l`, `not`, bare words match the subject
+fn query_matches(query: &str, message: &Message) -> bool {
+    let mut negate = false;
+    for token in query.split_whitespace() {
+        match token.to_lowercase().as_str() {
+            "and" => continue,
+            "not" => {
+                negate = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        let (field, value) = token.split_once(':').unwrap_or(("subject", token));
+        let field = field.to_lowercase();
+        let headers: &[&str] = match field.as_str() {
+            "from" => &["From", "Sender"],
+            "to" => &["To", "Cc", "Delivered-To"],
+            "list" => &["List-Id"],
+            "subject" => &["Subject"],
+            other => &[other],
+        };
+        let needle = value.trim_matches('"').to_lowercase();
+        let found = headers
+            .iter()
+            .flat_map(|h| header_values(message, h))
+            .any(|v| v.to_lowercase().contains(&needle));
+
+        if found == negate {
+            return false;
+        }
+        negate = false;
+    }
+    true
+}
+
+/// SpamAssassin (`X-Spam-Flag`, `X-Spam-Status`) and rspamd (`X-Spam`) verdicts
+fn is_spam(message: &Message) -> bool {
+    let yes = |name: &str| {
+        header_values(message, name)
+            .iter()
+            .any(|v| v.trim().to_lowercase().starts_with("yes"))
+    };
+    yes("X-Spam-Flag") || yes("X-Spam-Status") || yes("X-Spam")
+}
+
+/// Hand the message to sendmail for another recipient
+fn forward(raw: &[u8], address: &str) -> Result<()> {
+    let output = process::run_with_input(Command::new("sendmail").args(["-i", "--", address]), raw)
+        .context("Failed to run sendmail")?;
+    if !output.status.success() {
+        return Err(Error::command_failed("sendmail", &output.stderr))
+            .with_context(|| format!("Forwarding to {} failed", address));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"From: GitHub <noreply@github.com>\r\nTo: me@example.com\r\n\
+List-Id: <rust.lang.github.com>\r\nSubject: [rust] Build failed\r\nX-Spam-Status: No, score=0.1\r\n\r\nbody";
+
+    fn filter(toml: &str) -> Filter {
+        Config::parse(toml).unwrap().filter
+    }
+
+    #[test]
+    fn test_decide_first_match_wins() {
+        let f = filter(
+            r#"
+[[filter.rules]]
+spam = true
+folder = "Junk"
+
+[[filter.rules]]
+header = "list-id"
+matches = "RUST\\.lang"
+folder = "Lists/rust"
+forward = "phone@example.com"
+"#,
+        );
+        let msg = mime::parse(RAW).unwrap();
+        let d = decide(&f, &msg, RAW.len());
+        assert_eq!(d.folder, Some("Lists/rust"));
+        assert_eq!(d.forward, Some("phone@example.com"));
+    }
+
+    #[test]
+    fn test_decide_default_and_discard() {
+        let msg = mime::parse(RAW).unwrap();
+        let f = filter("");
+        assert_eq!(decide(&f, &msg, RAW.len()).folder, Some("INBOX"));
+
+        let f = filter("[[filter.rules]]\nlarger_than = 10\ndiscard = true\n");
+        assert_eq!(decide(&f, &msg, RAW.len()).folder, None);
+        assert_eq!(decide(&f, &msg, 5).folder, Some("INBOX"));
+    }
+
+    #[test]
+    fn test_unparsable_mail_goes_to_the_default_folder() {
+        let root = std::env::temp_dir().join(format!("mu-filter-{}", std::process::id()));
+        let args = Args {
+            maildir: Some(root.display().to_string()),
+            dry_run: false,
+        };
+        let cfg = Config::parse("[[filter.rules]]\ndiscard = true\n").unwrap();
+        run_with(args, &cfg, b"").unwrap();
+        assert_eq!(maildir::files(&root.join("INBOX")).unwrap().len(), 1);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // A bad rule is caught with the config, not when mail arrives
+        let bad = "[[filter.rules]]\nheader = \"List-Id\"\nmatches = \"(rust\"\n";
+        assert!(Config::parse(bad).is_err());
+        assert_eq!(Error::Deferred.exit_code(), 75);
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let msg = mime::parse(RAW).unwrap();
+        assert!(query_matches("from:github.com and build", &msg));
+        assert!(query_matches("list:rust not to:boss", &msg));
+        assert!(!query_matches("not from:github", &msg));
+        assert!(!is_spam(&msg));
+    }
+}
@@ -0,0 +1,188 @@
+//! Maildir flag changes without mutt: the files are renamed, then notmuch's tags follow
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::Error;
+use crate::{color, maildir, notmuch, process};
+
+/// Flags `mu flag` changes, with the notmuch tag each one stands for
+const FLAGS: [(&str, char, &str); 5] = [
+    ("seen", 'S', "unread"),
+    ("flagged", 'F', "flagged"),
+    ("replied", 'R', "replied"),
+    ("passed", 'P', "passed"),
+    ("draft", 'D', "draft"),
+];
+
+/// Arguments for `mu flag`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query
+    message: String,
+
+    /// Changes: +seen -seen +flagged -flagged +replied -replied (also passed, draft)
+    #[arg(required = true, allow_hyphen_values = true)]
+    changes: Vec<Change>,
+}
+
+/// One `+name`/`-name` change
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Change {
+    add: bool,
+    flag: char,
+    tag: &'static str,
+}
+
+impl std::str::FromStr for Change {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (add, name) = match s.split_at_checked(1) {
+            Some(("+", name)) => (true, name),
+            Some(("-", name)) => (false, name),
+            _ => return Err(format!("expected +flag or -flag, not {}", s)),
+        };
+        let (_, flag, tag) = FLAGS
+            .into_iter()
+            .find(|(known, ..)| *known == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown flag {} (seen, flagged, replied, passed, draft)",
+                    name
+                )
+            })?;
+        Ok(Change { add, flag, tag })
+    }
+}
+
+impl Change {
+    /// The notmuch tag change; `unread` is the opposite of seen
+    fn tag_change(&self) -> String {
+        let add = if self.tag == "unread" {
+            !self.add
+        } else {
+            self.add
+        };
+        format!("{}{}", if add { '+' } else { '-' }, self.tag)
+    }
+}
+
+/// Rename every file of the matching messages, then bring notmuch up to date
+pub fn run(args: Args) -> Result<()> {
+    let query = notmuch::query_for(&args.message);
+    let files = notmuch::search("files", &query)?;
+    if files.is_empty() {
+        return Err(Error::NoMatch { query }.into());
+    }
+    // The messages are tagged by id afterwards: once the flags change, a query such as
+    // `tag:unread` may no longer match them
+    let ids = notmuch::search("messages", &query)?;
+    for file in &files {
+        let path = Path::new(file);
+        maildir::set_flags(path, &changed(maildir::flags(path), &args.changes))?;
+    }
+
+    // notmuch has to see the new names before tagging (it renames files itself when
+    // `maildir.synchronize_flags` is on, and the old ones are gone)
+    let status = process::status(Command::new("notmuch").args(["new", "--quiet", "--no-hooks"]))
+        .context("Failed to run notmuch new")?;
+    if !status.success() {
+        anyhow::bail!("notmuch new failed");
+    }
+    let tags: Vec<String> = args.changes.iter().map(Change::tag_change).collect();
+    notmuch::tag_batch(&tag_lines(&tags, &ids))?;
+    eprintln!("{} {} ({} files)", color::ok(), tags.join(" "), files.len());
+    Ok(())
+}
+
+/// One `notmuch tag --batch` line per message
+fn tag_lines(tags: &[String], ids: &[String]) -> Vec<String> {
+    ids.iter()
+        .map(|id| format!("{} -- {}", tags.join(" "), notmuch::quote_id(id)))
+        .collect()
+}
+
+/// `flags` after `changes`
+fn changed(flags: &str, changes: &[Change]) -> String {
+    let mut flags: Vec<char> = flags.chars().collect();
+    for change in changes {
+        flags.retain(|&f| f != change.flag);
+        if change.add {
+            flags.push(change.flag);
+        }
+    }
+    flags.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_changes() {
+        let changes: Vec<Change> = ["+seen", "-flagged", "+replied"]
+            .iter()
+            .map(|c| c.parse().unwrap())
+            .collect();
+        assert_eq!(changed("FS", &changes), "SR");
+        let tags: Vec<String> = changes.iter().map(Change::tag_change).collect();
+        assert_eq!(tags, ["-unread", "-flagged", "+replied"]);
+        assert_eq!(
+            tag_lines(&tags, &["id:a@x".to_string(), "id:b@x".to_string()]),
+            [
+                "-unread -flagged +replied -- id:\"a@x\"",
+                "-unread -flagged +replied -- id:\"b@x\""
+            ]
+        );
+        assert!("seen".parse::<Change>().is_err());
+        assert!("+starred".parse::<Change>().is_err());
+    }
+
+    #[test]
+    fn test_run_renames_then_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = maildir::deliver(dir.path(), b"Subject: x\r\n\r\nhi\r\n", None).unwrap();
+        let search = |output: &str, stdout: &str| {
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["search", format!("--output={}", output), "--", "id:a@x"],
+                "stdout": stdout,
+            })
+            .to_string()
+        };
+        let replay = [
+            search("files", &format!("{}\n", file.display())),
+            search("messages", "id:a@x\n"),
+            r#"{"program":"notmuch","args":["new","--quiet","--no-hooks"]}"#.to_string(),
+            r#"{"program":"notmuch","args":["tag","--batch"]}"#.to_string(),
+        ]
+        .join("\n");
+        let args = Args {
+            message: "<a@x>".to_string(),
+            changes: vec!["+seen".parse().unwrap(), "+flagged".parse().unwrap()],
+        };
+        runner::scoped(Replay::parse(&replay).unwrap(), || run(args)).unwrap();
+        // Out of new/, into cur/ with both flags
+        assert!(!file.exists());
+        let renamed: Vec<_> = std::fs::read_dir(dir.path().join("cur"))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(maildir::flags(&renamed[0]), "FS");
+
+        let none = Replay::parse(&search("files", "")).unwrap();
+        let args = Args {
+            message: "id:a@x".to_string(),
+            changes: vec!["-seen".parse().unwrap()],
+        };
+        let err = runner::scoped(none, || run(args)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NoMatch { .. })
+        ));
+    }
+}
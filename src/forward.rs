@@ -0,0 +1,172 @@
+//! Forward-ready bodies: no trackers, no images, no quoted chains
+
+use anyhow::Result;
+use mail_parser::{Message, MimeHeaders};
+use regex::{Captures, Regex};
+
+use crate::{links, mime, render, tracker};
+
+/// Arguments for `mu forward-clean`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Replace images with `[image: alt]` references instead of dropping them
+    #[arg(long)]
+    reference_images: bool,
+
+    /// Keep quoted reply chains
+    #[arg(long)]
+    keep_quotes: bool,
+}
+
+/// Print a forward-ready body for the message
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+
+    let mut body = match mime::find_part(&message, "text/plain") {
+        Some(part) => unwrap_links(&mime::part_text(part))?,
+        None => match mime::find_part(&message, "text/html") {
+            Some(part) => {
+                render::plain_text(&images(&mime::part_text(part), args.reference_images)?)?
+            }
+            None => String::new(),
+        },
+    };
+    if !args.keep_quotes {
+        body = trim_quotes(&body);
+    }
+
+    println!("{}", header_block(&message));
+    println!("{}", body.trim_end());
+    let manifest = manifest(&message);
+    if !manifest.is_empty() {
+        println!("\nAttachments:\n{}", manifest.join("\n"));
+    }
+    Ok(())
+}
+
+/// `---------- Forwarded message ----------` block with the original headers
+fn header_block(message: &Message) -> String {
+    let addresses = |a: Option<&mail_parser::Address>| {
+        a.map(|a| {
+            a.iter()
+                .map(|addr| match (addr.name(), addr.address()) {
+                    (Some(n), Some(e)) => format!("{} <{}>", n, e),
+                    (n, e) => n.or(e).unwrap_or("").to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+    };
+
+    let mut lines = vec!["---------- Forwarded message ----------".to_string()];
+    lines.push(format!("From: {}", addresses(message.from())));
+    if let Some(date) = message.date() {
+        lines.push(format!("Date: {}", date.to_rfc822()));
+    }
+    lines.push(format!("Subject: {}", message.subject().unwrap_or("")));
+    lines.push(format!("To: {}", addresses(message.to())));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Replace tracking redirects in plain text with their destinations
+fn unwrap_links(text: &str) -> Result<String> {
+    let url = Regex::new(r#"https?://[^\s<>"'\)\]]+"#)?;
+    Ok(url
+        .replace_all(text, |c: &Captures| tracker::unwrap(&c[0]))
+        .to_string())
+}
+
+/// Drop `<img>` tags (tracking pixels always), or turn them into text references
+fn images(html: &str, reference: bool) -> Result<String> {
+    let img = Regex::new(r"(?is)<img\b[^>]*>")?;
+    let alt = Regex::new(r#"(?i)\balt\s*=\s*["']([^"']*)["']"#)?;
+
+    Ok(img
+        .replace_all(html, |c: &Captures| {
+            let tag = &c[0];
+            let text = alt
+                .captures(tag)
+                .map(|a| links::decode_entities(a[1].trim()));
+            match text {
+                Some(t) if reference && !t.is_empty() && !tracker::is_pixel(tag) => {
+                    format!("[image: {}]", t)
+                }
+                _ if reference && !tracker::is_pixel(tag) => "[image]".to_string(),
+                _ => String::new(),
+            }
+        })
+        .to_string())
+}
+
+/// Cut the body at the first quoted-chain marker and drop `>` lines
+fn trim_quotes(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        let t = line.trim();
+        if (t.starts_with("On ") && t.ends_with("wrote:"))
+            || t.starts_with("-----Original Message-----")
+            || t.starts_with("---------- Forwarded message")
+        {
+            break;
+        }
+        if !t.starts_with('>') {
+            kept.push(line);
+        }
+    }
+    kept.join("\n")
+}
+
+/// One line per attachment: name, type, size
+fn manifest(message: &Message) -> Vec<String> {
+    message
+        .attachments()
+        .enumerate()
+        .map(|(i, part)| {
+            format!(
+                "  {}. {} ({}, {})",
+                i + 1,
+                part.attachment_name().unwrap_or("unnamed"),
+                mime::content_type(part),
+                mime::format_size(part.len() as u64)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_images() {
+        let html =
+            r#"<p>Hi</p><img src="a.png" alt="Logo"><img src="t.gif" width="1" height="1" alt="">"#;
+        assert_eq!(images(html, false).unwrap(), "<p>Hi</p>");
+        assert_eq!(images(html, true).unwrap(), "<p>Hi</p>[image: Logo]");
+    }
+
+    #[test]
+    fn test_trim_quotes_and_links() {
+        let text = "See https://t.example/r?url=https%3A%2F%2Fexample.com\n> old\nOn Mon, Bob wrote:\n> older";
+        let body = trim_quotes(&unwrap_links(text).unwrap());
+        assert_eq!(body, "See https://example.com");
+    }
+
+    #[test]
+    fn test_manifest() {
+        let raw = b"Subject: x\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nhello\r\n--b\r\nContent-Type: application/pdf\r\n\
+Content-Disposition: attachment; filename=report.pdf\r\n\r\n%PDF\r\n--b--\r\n";
+        let msg = mime::parse(raw).unwrap();
+        assert_eq!(
+            manifest(&msg),
+            vec!["  1. report.pdf (application/pdf, 4 B)"]
+        );
+    }
+}
@@ -1,48 +1,252 @@
 //! Fuzzy mail search with fzf + notmuch
 
-use crate::render;
+use crate::config::{self, Config};
+use crate::hook::{self, Point};
+use crate::{
+    avatar, color, decrypt, error, headers, i18n, ics, imap, jmap, mime, notmuch, phish, process,
+    remote, render, renderer, runner, script, theme, vcard, worker,
+};
+#[cfg(feature = "index")]
+use crate::{crypt, index};
 use anyhow::{Context, Result};
-use std::io::Write;
-use std::process::{Command, Stdio};
+use mail_parser::{Message, MessagePart, MimeHeaders};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::Instant;
+
+/// File the neomutt macro sources after `mu fzf` (`$MU_CMD_FILE` overrides)
+///
+/// `/tmp/neomutt-fzf-cmd`, or the same name in the temp directory on Windows.
+pub fn cmd_file() -> PathBuf {
+    config::env_path("MU_CMD_FILE").unwrap_or_else(|| {
+        let dir = if cfg!(windows) {
+            std::env::temp_dir()
+        } else {
+            PathBuf::from("/tmp")
+        };
+        dir.join("neomutt-fzf-cmd")
+    })
+}
 
-const CMD_FILE: &str = "/tmp/neomutt-fzf-cmd";
+/// Where `mu fzf` puts the neomutt command for the picked mail (`--emit`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Emit {
+    /// [`cmd_file`], sourced by the macro afterwards
+    #[default]
+    File,
+    /// stdout, for `source "mu fzf --emit stdout |"`
+    Stdout,
+    /// A FIFO the macro sources; the command is handed over when it's opened, so nothing
+    /// stale is left behind
+    Fifo(PathBuf),
+}
+
+impl std::str::FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Emit::File),
+            "stdout" => Ok(Emit::Stdout),
+            _ => match s.strip_prefix("fifo:") {
+                Some(path) if !path.is_empty() => Ok(Emit::Fifo(config::expand_tilde(path))),
+                _ => Err(format!("expected file, stdout or fifo:PATH, not {}", s)),
+            },
+        }
+    }
+}
+
+impl Emit {
+    /// Hand `command` (empty when nothing was picked) to neomutt
+    fn send(&self, command: &str) -> Result<()> {
+        match self {
+            Emit::File => {
+                std::fs::write(cmd_file(), command).context("Failed to write neomutt command file")
+            }
+            Emit::Stdout => {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(command.as_bytes())?;
+                Ok(stdout.flush()?)
+            }
+            Emit::Fifo(path) => send_fifo(path, command),
+        }
+    }
+}
+
+/// Write `command` into the FIFO at `path` (made if missing) from a detached writer
+///
+/// The writer blocks until neomutt opens the FIFO, after `mu fzf` has exited. An empty
+/// command is written too, or neomutt's `source` would wait forever.
+#[cfg(unix)]
+fn send_fifo(path: &Path, command: &str) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(path) {
+        Ok(meta) if !meta.file_type().is_fifo() => {
+            anyhow::bail!("{} exists and is not a FIFO", path.display())
+        }
+        Ok(_) => {}
+        Err(_) => {
+            // Plumbing, not a mail tool: run even under --simulate
+            let status = Command::new("mkfifo")
+                .args(["-m", "600"])
+                .arg(path)
+                .status()
+                .map_err(|e| error::spawn_failed("mkfifo", e))?;
+            if !status.success() {
+                return Err(error::Error::command_failed("mkfifo", b"").into());
+            }
+        }
+    }
+    let mut writer = Command::new("sh")
+        .args(["-c", "cat > \"$1\"", "sh"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| error::spawn_failed("sh", e))?;
+    if let Some(mut stdin) = writer.stdin.take() {
+        stdin.write_all(command.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_fifo(_: &Path, _: &str) -> Result<()> {
+    anyhow::bail!("--emit fifo needs a Unix system")
+}
+
+/// Where listings and previews come from
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+pub enum Backend {
+    /// notmuch search/show
+    #[default]
+    Notmuch,
+    /// mu's own index (`mu index build`)
+    Builtin,
+    /// Decrypted PGP mail (`mu crypt index`)
+    Crypt,
+    /// Live search on the `[jmap]` server
+    Jmap,
+}
+
+/// Stand-ins when mu is built without the `index` feature
+#[cfg(not(feature = "index"))]
+mod index {
+    use std::path::{Path, PathBuf};
+
+    pub fn index_dir() -> PathBuf {
+        PathBuf::new()
+    }
+
+    pub fn listing(_: &Path, _: &str) -> anyhow::Result<Vec<String>> {
+        anyhow::bail!("mu was built without the `index` feature")
+    }
+
+    pub fn path_for(_: &Path, _: &str) -> anyhow::Result<String> {
+        anyhow::bail!("mu was built without the `index` feature")
+    }
+}
+
+#[cfg(not(feature = "index"))]
+mod crypt {
+    pub use super::index::index_dir;
+}
 
 /// Run fuzzy mail search and output neomutt command
-pub fn search(query: Option<&str>) -> Result<()> {
+///
+/// With `server_search`, IMAP matches that aren't synced locally are appended.
+pub fn search(
+    query: Option<&str>,
+    backend: Backend,
+    server_search: bool,
+    emit: &Emit,
+) -> Result<()> {
     let query = query.unwrap_or("*");
 
-    // Get mail list from notmuch
-    let mails = get_mail_list(query)?;
+    let mut mails = match backend {
+        Backend::Notmuch => get_mail_list(query)?,
+        Backend::Builtin => index::listing(&index::index_dir(), query)?,
+        Backend::Crypt => index::listing(&crypt::index_dir(), query)?,
+        Backend::Jmap => jmap::listing(&Config::load()?.jmap, query)?,
+    };
+    if server_search {
+        mails.extend(imap::search(&Config::load()?, query)?);
+    }
     if mails.is_empty() {
         eprintln!("No messages found");
-        write_empty_cmd()?;
-        return Ok(());
+        return emit.send("");
     }
 
     // Run fzf with preview - use mu preview directly, {1} = first field (thread ID)
-    let selected = run_fzf(&mails)?;
+    let selected = run_fzf(&mails, backend)?;
 
-    if let Some(line) = selected {
-        // Extract thread ID (first word, like "thread:0000000000000123")
-        let thread_id = line.split_whitespace().next().unwrap_or("");
-        if !thread_id.is_empty() {
-            write_neomutt_cmd(thread_id)?;
-        } else {
-            write_empty_cmd()?;
-        }
-    } else {
-        write_empty_cmd()?;
-    }
+    let command = match selected {
+        Some(line) => picked(query, &line, command_for(&line, backend)?)?,
+        None => String::new(),
+    };
+    emit.send(&command)
+}
 
-    Ok(())
+/// What `post-search-selection` hooks see; `command` goes to neomutt
+#[derive(Serialize, Deserialize)]
+struct Selection {
+    query: String,
+    line: String,
+    command: String,
 }
 
-/// Get formatted mail list from notmuch
+/// The neomutt command for a pick after the `post-search-selection` hooks (a veto sends none)
+fn picked(query: &str, line: &str, command: String) -> Result<String> {
+    let selection = Selection {
+        query: query.to_string(),
+        line: line.to_string(),
+        command,
+    };
+    Ok(hook::run(Point::PostSearchSelection, selection)?.map_or_else(String::new, |s| s.command))
+}
+
+/// neomutt command opening a picked listing line (empty if it has no ID)
+fn command_for(line: &str, backend: Backend) -> Result<String> {
+    // Extract thread ID (first word, like "thread:0000000000000123")
+    let thread_id = line.split_whitespace().next().unwrap_or("");
+    Ok(if thread_id.starts_with("imap:") {
+        let message_id = imap::download(&Config::load()?, thread_id)?;
+        query_cmd(&notmuch::quote_id(&message_id))
+    } else if thread_id.is_empty() {
+        String::new()
+    } else if backend == Backend::Builtin {
+        folder_cmd(&index::path_for(&index::index_dir(), thread_id)?, thread_id)
+    } else if backend == Backend::Jmap {
+        let (_, message_id) = jmap::message(&Config::load()?.jmap, thread_id)?;
+        query_cmd(&notmuch::quote_id(&message_id))
+    } else {
+        query_cmd(thread_id)
+    })
+}
+
+/// Get formatted mail list from notmuch (lines from the script's `listing`, if it has one)
 fn get_mail_list(query: &str) -> Result<Vec<String>> {
-    let output = Command::new("notmuch")
-        .args(["search", "--format=text", "--output=summary", query])
-        .output()
-        .context("Failed to run notmuch search")?;
+    if let Some(script) = script::load(&Config::load()?.script)?
+        && script.defines("listing")
+    {
+        return notmuch::summaries(query)?
+            .iter()
+            .map(|thread| {
+                let id = thread["thread"].as_str().unwrap_or_default();
+                Ok(format!("thread:{} {}", id, script.listing(thread)?))
+            })
+            .collect();
+    }
+    let output = process::run(&mut remote::command(
+        "notmuch",
+        &["search", "--format=text", "--output=summary", query],
+    ))
+    .context("Failed to run notmuch search")?;
 
     if !output.status.success() {
         anyhow::bail!(
@@ -52,37 +256,57 @@ fn get_mail_list(query: &str) -> Result<Vec<String>> {
     }
 
     let text = String::from_utf8_lossy(&output.stdout);
-    Ok(text.lines().map(String::from).collect())
+    // notmuch prints some encoded-words (`=?UTF-8?B?…?=`) as they are in the headers
+    Ok(text
+        .lines()
+        .map(|line| mime::decode_line(line).into_owned())
+        .collect())
 }
 
-/// Run fzf with mail preview
-fn run_fzf(items: &[String]) -> Result<Option<String>> {
-    let mut child = Command::new("fzf")
-        .args([
-            "--ansi",
-            "--preview",
-            "mu preview {1}", // {1} = first field = thread ID
-            "--preview-window=right:50%:wrap",
-            "--header",
-            "Enter: open | Esc: cancel",
-            "--prompt",
-            "mail> ",
-            "--no-mouse",
-        ])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit()) // Show fzf UI on terminal
-        .spawn()
-        .context("Failed to spawn fzf")?;
+/// Separates commands in fzf's preview shell (cmd.exe on Windows)
+const THEN: &str = if cfg!(windows) { " & " } else { "; " };
 
-    // Write items to fzf stdin
-    if let Some(mut stdin) = child.stdin.take() {
-        for item in items {
-            writeln!(stdin, "{}", item)?;
-        }
+/// Run fzf with mail preview
+fn run_fzf(items: &[String], backend: Backend) -> Result<Option<String>> {
+    // {1} = first field = thread ID (or id:<message-id> for the builtin index)
+    // Previews show in fzf's UI on the terminal, so they're colored when stderr is
+    let mu = if color::stderr() {
+        "mu --color always"
+    } else {
+        "mu --color never"
+    };
+    let preview = match backend {
+        // TL;DR first for large threads when a summarizer is configured
+        Backend::Notmuch => format!("{mu} summarize --preview {{1}}{THEN}{mu} preview {{1}}"),
+        Backend::Builtin => format!("{mu} preview --backend builtin {{1}}"),
+        Backend::Crypt => format!("{mu} preview --backend crypt {{1}}"),
+        Backend::Jmap => format!("{mu} preview --backend jmap {{1}}"),
+    };
+    let mut fzf = Command::new("fzf");
+    // Previews (`mu preview`) follow `--remote`
+    if let Some(host) = remote::host() {
+        fzf.env(remote::ENV, host);
     }
-
-    let output = child.wait_with_output()?;
+    // Stopped when fzf is done (dropped at the end of this function)
+    let worker = worker::start(backend, color::stderr());
+    if let Some(worker) = &worker {
+        fzf.env(worker::ENV, worker.socket());
+    }
+    fzf.args([
+        "--ansi",
+        "--preview",
+        &preview,
+        "--preview-window=right:50%:wrap",
+        "--header",
+        "Enter: open | Esc: cancel",
+        "--prompt",
+        "mail> ",
+        "--no-mouse",
+    ])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::inherit()); // Show fzf UI on terminal
+    let output = pick(&mut fzf, items)?;
 
     if output.status.success() {
         let selected = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -97,196 +321,428 @@ fn run_fzf(items: &[String]) -> Result<Option<String>> {
     }
 }
 
-/// Write neomutt command to navigate to thread
-fn write_neomutt_cmd(thread_id: &str) -> Result<()> {
-    let cmd = format!("push '<vfolder-from-query>{}<enter>'\n", thread_id);
-    std::fs::write(CMD_FILE, cmd).context("Failed to write neomutt command file")?;
-    Ok(())
+/// Run fzf over `items`, or replay its pick under `--simulate`
+fn pick(fzf: &mut Command, items: &[String]) -> Result<Output> {
+    let _span = process::span(fzf).entered();
+    if let Some(output) = runner::intercept(fzf) {
+        return output.map_err(|e| error::spawn_failed("fzf", e));
+    }
+    let started = Instant::now();
+    let mut child = fzf.spawn().map_err(|e| error::spawn_failed("fzf", e))?;
+
+    // Write items to fzf stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        for item in items {
+            writeln!(stdin, "{}", item)?;
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    process::exited(started, output.status);
+    runner::record(fzf, &output);
+    Ok(output)
 }
 
-/// Write empty command (cancelled)
-fn write_empty_cmd() -> Result<()> {
-    std::fs::write(CMD_FILE, "").context("Failed to write empty command file")?;
-    Ok(())
+/// neomutt command navigating to a thread (or any query)
+fn query_cmd(thread_id: &str) -> String {
+    format!("push '<vfolder-from-query>{}<enter>'\n", thread_id)
 }
 
-/// Preview a mail thread (for fzf preview)
-pub fn preview(thread_id: &str) -> Result<()> {
-    // Get the email in text format (notmuch handles MIME decoding)
-    let output = Command::new("notmuch")
-        .args(["show", "--format=text", "--entire-thread=false", thread_id])
-        .output()
-        .context("Failed to run notmuch show")?;
+/// neomutt command opening the message's Maildir folder and searching for it
+fn folder_cmd(path: &str, id: &str) -> String {
+    let folder = Path::new(path)
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(path));
+    let id = id.strip_prefix("id:").unwrap_or(id);
+    format!(
+        "push '<change-folder>{}<enter><search>~i \"{}\"<enter>'\n",
+        folder.display(),
+        regex::escape(id)
+    )
+}
 
-    if !output.status.success() {
-        anyhow::bail!("notmuch show failed");
+/// The query limited to one account's folders
+pub fn account_query(query: Option<&str>, account: &str) -> Result<String> {
+    let cfg = Config::load()?;
+    let Some(acct) = cfg.accounts.iter().find(|a| a.name == account) else {
+        anyhow::bail!("No account named {} in the config", account);
+    };
+    Ok(format!(
+        "({}) and path:\"{}/**\"",
+        query.unwrap_or("*"),
+        acct.dir()
+    ))
+}
+
+/// Preview a mail thread (for fzf preview), from the warm worker when `mu fzf` runs one
+pub fn preview(thread_id: &str, backend: Backend) -> Result<()> {
+    let preview = match worker::ask(thread_id) {
+        Some(preview) => preview,
+        None => render_preview(thread_id, backend)?,
+    };
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&preview)?;
+    Ok(stdout.flush()?)
+}
+
+/// What `mu preview` prints for a thread
+pub fn render_preview(thread_id: &str, backend: Backend) -> Result<Vec<u8>> {
+    let raw = raw_message(thread_id, backend)?;
+    let (raw, verdict) = match decrypt::open(&raw) {
+        Some((shown, verdict)) => (shown, Some(verdict)),
+        None => (raw, None),
+    };
+    let mut preview = Vec::new();
+    print_message(&mime::parse(&raw)?, verdict.as_ref(), &mut preview)?;
+    Ok(preview)
+}
+
+/// The raw message a preview shows (a notmuch thread's first)
+fn raw_message(thread_id: &str, backend: Backend) -> Result<Vec<u8>> {
+    if thread_id.starts_with("imap:") {
+        return imap::fetch(&Config::load()?, thread_id);
+    }
+    if backend == Backend::Jmap {
+        let (raw, _) = jmap::message(&Config::load()?.jmap, thread_id)?;
+        return Ok(raw);
+    }
+    if backend != Backend::Notmuch {
+        let dir = match backend {
+            Backend::Crypt => crypt::index_dir(),
+            _ => index::index_dir(),
+        };
+        let path = index::path_for(&dir, thread_id)?;
+        let raw = std::fs::read(&path).with_context(|| format!("Failed to read {}", path))?;
+        return match backend {
+            Backend::Crypt => decrypt::decrypted(&raw),
+            _ => Ok(raw),
+        };
     }
 
-    let text = String::from_utf8_lossy(&output.stdout);
+    let files = notmuch::thread_files(thread_id)?;
+    let path = files.first().ok_or_else(|| error::Error::NoMatch {
+        query: thread_id.to_string(),
+    })?;
+    remote::read(path)
+}
 
-    // Parse notmuch text output
-    let mut in_headers = false;
-    let mut in_body = false;
-    let mut body_type = String::new();
-    let mut body_content = String::new();
-    let mut headers_printed = false;
-    let mut body_printed = false;
-    let mut has_html_only = false;
-
-    for line in text.lines() {
-        // Notmuch text format markers
-        if line.starts_with("\u{c}header{") {
-            in_headers = true;
-            if !headers_printed {
-                println!("\x1b[1;36m=== Headers ===\x1b[0m");
-            }
+/// Preview a parsed message: key headers, gpg's verdict on PGP/MIME mail, then the text (or
+/// rendered HTML) body
+fn print_message(
+    message: &Message,
+    verdict: Option<&decrypt::Verdict>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    print_banner(message, out)?;
+    print_script_line(message, out)?;
+    print_auth_line(message, out)?;
+    writeln!(out, "{}", color::header("Headers"))?;
+    for name in ["Subject", "From", "To", "Date"] {
+        let Some(value) = message.header_raw(name) else {
             continue;
-        }
-        if line.starts_with("\u{c}header}") {
-            in_headers = false;
-            headers_printed = true;
-            continue;
-        }
-        if line.starts_with("\u{c}body{") {
-            in_body = true;
-            continue;
-        }
-        if line.starts_with("\u{c}body}") {
-            in_body = false;
-            continue;
-        }
-        if line.starts_with("\u{c}part{") {
-            // Extract content type
-            if let Some(ct) = line.split("content-type:").nth(1) {
-                body_type = ct.split_whitespace().next().unwrap_or("").to_string();
-            }
-            continue;
-        }
-        if line.starts_with("\u{c}part}") {
-            // End of part - render if we have useful content and haven't printed body yet
-            if !body_printed && !body_content.is_empty() {
-                if body_content.trim() == "Non-text part: text/html" {
-                    // HTML-only email - need to fetch raw and render
-                    has_html_only = true;
-                } else {
-                    print_body(&body_content, &body_type);
-                    body_printed = true;
-                }
-            }
-            body_content.clear();
-            body_type.clear();
-            continue;
-        }
-        if line.starts_with("\u{c}") {
-            // Other control lines - skip
-            continue;
-        }
-
-        if in_headers {
-            // Print key headers with colors
-            if line.starts_with("Subject:") {
-                println!("\x1b[1;33m{}\x1b[0m", line);
-            } else if line.starts_with("From:")
-                || line.starts_with("To:")
-                || line.starts_with("Date:")
-            {
-                println!("{}", line);
-            }
-        } else if in_body && !body_printed {
-            body_content.push_str(line);
-            body_content.push('\n');
+        };
+        let value = match message.date() {
+            Some(date) if name == "Date" => i18n::current().date(date),
+            _ => mime::decode_words(value),
+        };
+        let line = format!("{}: {}", name, value);
+        if name == "Subject" {
+            writeln!(out, "{}", color::paint(&theme::get().subject, line))?;
+        } else {
+            writeln!(out, "{}", line)?;
         }
     }
-
-    // Print any remaining body content
-    if !body_printed
-        && !body_content.is_empty()
-        && body_content.trim() != "Non-text part: text/html"
-    {
-        print_body(&body_content, &body_type);
-        body_printed = true;
+    draw_avatar(
+        &message
+            .header_raw("From")
+            .map(mime::decode_words)
+            .unwrap_or_default(),
+        out,
+    );
+    if let Some(verdict) = verdict {
+        writeln!(out, "\n{}", verdict.banner())?;
     }
 
-    // HTML-only email - fetch raw and render
-    if !body_printed && has_html_only {
-        preview_html_only(thread_id)?;
+    // Parts render in parallel (a huge HTML body doesn't hold up the attachments) and print in
+    // order; the width is per thread, so each worker is handed this one's
+    let cols = renderer::cols();
+    let rendered: Vec<(String, String)> = readable_parts(message)
+        .into_par_iter()
+        .map(|(title, part)| {
+            renderer::set_cols(Some(cols));
+            (title, render_part(part))
+        })
+        .collect();
+    for (title, text) in rendered {
+        print_body(&title, &text, out)?;
     }
-
     Ok(())
 }
 
-/// Preview HTML-only emails by fetching raw and rendering with w3m
-fn preview_html_only(thread_id: &str) -> Result<()> {
-    // Use notmuch to get the raw email, then extract and render HTML
-    // We'll use Python's email module which handles all MIME decoding properly
-    let output = Command::new("python3")
-        .args([
-            "-c",
-            &format!(
-                r#"
-import subprocess
-import email
-from email import policy
-
-# Get raw message from notmuch
-result = subprocess.run(['notmuch', 'show', '--format=raw', '{}'], capture_output=True)
-msg = email.message_from_bytes(result.stdout, policy=policy.default)
-
-# Find HTML part
-for part in msg.walk():
-    if part.get_content_type() == 'text/html':
-        html = part.get_content()
-        print(html)
-        break
-"#,
-                thread_id
-            ),
-        ])
-        .output()
-        .context("Failed to extract HTML")?;
-
-    if output.status.success() && !output.stdout.is_empty() {
-        let html = String::from_utf8_lossy(&output.stdout);
-        print_body(&html, "text/html");
+/// Calendar invites, the body (text, else HTML), text and forwarded-message attachments,
+/// then vCards, with titles
+fn readable_parts<'a>(message: &'a Message) -> Vec<(String, &'a MessagePart<'a>)> {
+    let invites = message.parts.iter().filter(|part| mime::is_calendar(part));
+    let contacts = message.parts.iter().filter(|part| mime::is_contact(part));
+    let body =
+        mime::find_part(message, "text/plain").or_else(|| mime::find_part(message, "text/html"));
+    let attachments = message.attachments().filter(|part| {
+        !body.is_some_and(|body| std::ptr::eq(body, *part))
+            && !mime::is_calendar(part)
+            && !mime::is_contact(part)
+            && matches!(
+                mime::content_type(part).as_str(),
+                "text/plain" | "text/html" | "message/rfc822"
+            )
+    });
+    invites
+        .map(|part| ("Invitation".to_string(), part))
+        .chain(body.map(|part| ("Preview".to_string(), part)))
+        .chain(attachments.map(|part| {
+            let name = part
+                .attachment_name()
+                .map_or_else(|| mime::content_type(part), String::from);
+            (format!("Attachment: {}", name), part)
+        }))
+        .chain(contacts.map(|part| ("Contact".to_string(), part)))
+        .collect()
+}
+
+/// A part as text: HTML rendered, invites and vCards as cards, forwarded messages with
+/// their key headers
+fn render_part(part: &MessagePart) -> String {
+    if let Some(nested) = part.message() {
+        return mime::as_text(nested).unwrap_or_else(|e| format!("{:#}", e));
+    }
+    let text = mime::part_text(part);
+    if mime::is_calendar(part) {
+        return color::for_stdout(ics::render(&text));
+    }
+    if mime::is_contact(part) {
+        return color::for_stdout(vcard::render(&text));
     }
+    let options = render::Options {
+        signatures: Config::load()
+            .map(|cfg| cfg.render.signatures)
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    if mime::content_type(part) != "text/html" {
+        return color::for_stdout(render::declutter(&text, &options));
+    }
+    match render::render_as(&text, render::ContentType::Html, &options) {
+        Ok(rendered) => color::for_stdout(rendered),
+        Err(_) => text,
+    }
+}
 
-    Ok(())
+/// Phishing warning above everything else, for risky messages only
+fn print_banner(message: &Message, out: &mut impl Write) -> io::Result<()> {
+    match phish::banner(message) {
+        Some(banner) => writeln!(
+            out,
+            "{}\n",
+            color::paint(&theme::get().alert, format!("⚠ {}", banner))
+        ),
+        None => Ok(()),
+    }
+}
+
+/// `✓ DKIM ✓ SPF ✗ DMARC` from the receiving server's Authentication-Results, when there is one
+fn print_auth_line(message: &Message, out: &mut impl Write) -> io::Result<()> {
+    let results = headers::auth_results(message);
+    if results.iter().all(|(_, result)| result.is_none()) {
+        return Ok(());
+    }
+    writeln!(out, "{}\n", auth_line(&results))
 }
 
-/// Print body content, rendering HTML if needed
-fn print_body(content: &str, content_type: &str) {
-    println!("\n\x1b[1;36m=== Preview ===\x1b[0m");
+/// Passing checks green, failing ones red, missing or neutral ones dim
+fn auth_line(results: &[(&str, Option<String>)]) -> String {
+    let theme = theme::get();
+    results
+        .iter()
+        .map(|(method, result)| {
+            let (style, mark) = match result.as_deref() {
+                Some("pass") => (&theme.pass, "✓"),
+                None | Some("none" | "neutral") => (&theme.muted, "–"),
+                Some(_) => (&theme.alert, "✗"),
+            };
+            color::paint(style, format!("{} {}", mark, method.to_uppercase()))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-    let rendered = if content_type.contains("text/html") {
-        // Render HTML to clean text
-        match render::render(content, true) {
-            Ok(text) => text,
-            Err(_) => content.to_string(),
+/// The script's `preview(message)` line, when it has one
+fn print_script_line(message: &Message, out: &mut impl Write) -> io::Result<()> {
+    let header = |name| message.header_raw(name).map(mime::decode_words);
+    let line = Config::load().and_then(|cfg| match script::load(&cfg.script)? {
+        Some(script) if script.defines("preview") => {
+            let fields = serde_json::json!({
+                "from": header("From"),
+                "to": header("To"),
+                "subject": header("Subject"),
+                "date": header("Date"),
+            });
+            script.preview(&fields).map(Some)
         }
-    } else {
-        content.to_string()
+        _ => Ok(None),
+    });
+    match line {
+        Ok(Some(line)) => writeln!(out, "{}\n", line),
+        Ok(None) => Ok(()),
+        Err(e) => writeln!(
+            out,
+            "{}\n",
+            color::paint(&theme::get().muted, format!("script: {:#}", e))
+        ),
+    }
+}
+
+/// Sender avatar under the headers, when `[avatars] preview` is on
+fn draw_avatar(from: &str, out: &mut impl Write) {
+    let Ok(cfg) = Config::load() else {
+        return;
     };
+    if !cfg.avatars.preview {
+        return;
+    }
+    let address = from
+        .rsplit('<')
+        .next()
+        .unwrap_or(from)
+        .trim_end_matches('>');
+    if let Some(path) = avatar::lookup(&cfg.avatars, address) {
+        let _ = avatar::show(&path, out);
+    }
+}
 
-    // Print first 30 lines
-    for (i, line) in rendered.lines().enumerate() {
+/// A titled section with a rendered part's first 30 lines
+fn print_body(title: &str, text: &str, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "\n{}", color::header(title))?;
+    for (i, line) in text.lines().enumerate() {
         if i >= 30 {
-            println!("\x1b[2m... (truncated)\x1b[0m");
+            writeln!(
+                out,
+                "{}",
+                color::paint(&theme::get().muted, "... (truncated)")
+            )?;
             break;
         }
-        println!("{}", line);
+        writeln!(out, "{}", line)?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_mail_list_and_pick_with_recordings() {
+        let replay = runner::Replay::parse(concat!(
+            r#"{"program":"notmuch","args":["search","--format=text","--output=summary","tag:inbox"],"stdout":"thread:01  today [1/1] Ann; Hi (inbox)\n"}"#,
+            "\n",
+            r#"{"program":"fzf","args":["--filter","Hi"],"stdout":"thread:01  today [1/1] Ann; Hi (inbox)\n"}"#,
+        ))
+        .unwrap();
+        runner::scoped(replay, || {
+            let items = get_mail_list("tag:inbox").unwrap();
+            assert_eq!(items.len(), 1);
+            let mut fzf = Command::new("fzf");
+            fzf.args(["--filter", "Hi"]);
+            let picked = pick(&mut fzf, &items).unwrap();
+            assert!(String::from_utf8_lossy(&picked.stdout).starts_with("thread:01"));
+        });
+    }
+
     #[test]
     fn test_write_neomutt_cmd() {
         let thread_id = "thread:0000000000000001";
-        write_neomutt_cmd(thread_id).unwrap();
-        let content = std::fs::read_to_string(CMD_FILE).unwrap();
+        Emit::File.send(&query_cmd(thread_id)).unwrap();
+        let content = std::fs::read_to_string(cmd_file()).unwrap();
         assert!(content.contains("vfolder-from-query"));
         assert!(content.contains(thread_id));
     }
+
+    #[test]
+    fn test_parse_emit() {
+        assert_eq!("stdout".parse(), Ok(Emit::Stdout));
+        assert_eq!(
+            "fifo:/tmp/mu-cmd".parse(),
+            Ok(Emit::Fifo(PathBuf::from("/tmp/mu-cmd")))
+        );
+        assert!("fifo:".parse::<Emit>().is_err());
+        assert!("pipe".parse::<Emit>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_emit_fifo() {
+        let path = std::env::temp_dir().join(format!("mu-test-fifo-{}", std::process::id()));
+        let emit = Emit::Fifo(path.clone());
+        emit.send("push '<enter>'\n").unwrap();
+        // Like neomutt's `source`: opening the FIFO lets the writer through
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "push '<enter>'\n");
+        emit.send("").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_auth_line() {
+        let results = [
+            ("dkim", Some("pass".to_string())),
+            ("spf", None),
+            ("dmarc", Some("fail".to_string())),
+        ];
+        assert_eq!(color::strip(&auth_line(&results)), "✓ DKIM – SPF ✗ DMARC");
+    }
+
+    #[test]
+    fn test_folder_cmd() {
+        let cmd = folder_cmd("/home/u/Mail/INBOX/cur/123:2,S", "id:a.b@x");
+        assert_eq!(
+            cmd,
+            "push '<change-folder>/home/u/Mail/INBOX<enter><search>~i \"a\\.b@x\"<enter>'\n"
+        );
+    }
+    #[test]
+    fn test_readable_parts_in_order() {
+        let raw = concat!(
+            "From: a@x\r\nSubject: Fwd\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n",
+            "--b\r\nContent-Type: text/plain\r\n\r\nSee below\r\n",
+            "--b\r\nContent-Type: text/plain; name=log.txt\r\n",
+            "Content-Disposition: attachment; filename=log.txt\r\n\r\nline 1\r\n",
+            "--b\r\nContent-Type: image/png\r\nContent-Disposition: attachment\r\n\r\nPNG\r\n",
+            "--b\r\nContent-Type: message/rfc822\r\nContent-Disposition: attachment\r\n\r\n",
+            "From: c@y\r\nSubject: Original\r\n\r\nOld text\r\n",
+            "--b\r\nContent-Type: application/octet-stream; name=invite.ics\r\n\r\n",
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Sync\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            "--b\r\nContent-Type: text/vcard\r\n\r\nBEGIN:VCARD\r\nFN:Ann\r\nEND:VCARD\r\n",
+            "--b--\r\n",
+        );
+        let message = mime::parse(raw.as_bytes()).unwrap();
+        let parts: Vec<(String, String)> = readable_parts(&message)
+            .into_iter()
+            .map(|(title, part)| (title, render_part(part)))
+            .collect();
+        let titles: Vec<&str> = parts.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Invitation",
+                "Preview",
+                "Attachment: log.txt",
+                "Attachment: message/rfc822",
+                "Contact"
+            ]
+        );
+        assert!(color::strip(&parts[0].1).starts_with("╭─ Sync ─"));
+        assert_eq!(parts[1].1.trim(), "See below");
+        assert!(parts[3].1.contains("Subject: Original\n\nOld text"));
+        assert!(color::strip(&parts[4].1).starts_with("╭─ Ann ─"));
+    }
 }
@@ -0,0 +1,172 @@
+//! Regex search in decoded bodies, for exact strings (error codes, order numbers) that
+//! notmuch's stemmed full-text search can't pin down
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+
+use crate::error::Error;
+use crate::{color, mime, notmuch};
+
+/// Arguments for `mu grep`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Regular expression matched against each body line
+    pattern: String,
+
+    /// notmuch query narrowing the messages searched
+    #[arg(short, long, default_value = "*")]
+    query: String,
+
+    /// Lines of context around each match
+    #[arg(short = 'C', long, value_name = "LINES", default_value_t = 0)]
+    context: usize,
+
+    /// Ignore case
+    #[arg(short, long)]
+    ignore_case: bool,
+}
+
+/// Print matching lines as `id:…:LINE:text` (context lines with `-`), grep style
+pub fn run(args: Args) -> Result<()> {
+    let regex = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .context("Invalid pattern")?;
+    let ids = notmuch::search("messages", &args.query)?;
+
+    // Bodies decode (and HTML renders) in parallel; output keeps notmuch's order
+    let found: Vec<String> = ids
+        .par_iter()
+        .filter_map(|id| match search_message(id, &regex, args.context) {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("{} {}: {:#}", color::warn(), id, e);
+                None
+            }
+        })
+        .collect();
+    if found.is_empty() {
+        return Err(Error::NoMatch {
+            query: format!("/{}/ in {}", args.pattern, args.query),
+        }
+        .into());
+    }
+    let separator = if args.context > 0 { "--\n" } else { "" };
+    print!("{}", found.join(separator));
+    Ok(())
+}
+
+/// The matching lines of one message's body, if any
+fn search_message(id: &str, regex: &Regex, context: usize) -> Result<Option<String>> {
+    let raw = notmuch::raw(&notmuch::quote_id(id))?;
+    let body = mime::body_text(&mime::parse(&raw)?)?;
+    Ok(excerpt(id, &body, regex, context))
+}
+
+/// Matching lines of `text` with `context` lines around them (gaps between marked `--`)
+fn excerpt(reference: &str, text: &str, regex: &Regex, context: usize) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let hits: Vec<bool> = lines.iter().map(|line| regex.is_match(line)).collect();
+    if !hits.contains(&true) {
+        return None;
+    }
+    let shown = |i: usize| {
+        let from = i.saturating_sub(context);
+        hits[from..(i + context + 1).min(lines.len())].contains(&true)
+    };
+
+    let mut out = String::new();
+    let mut last = None;
+    for (i, line) in lines.iter().enumerate().filter(|(i, _)| shown(*i)) {
+        if context > 0 && last.is_some_and(|last| last + 1 < i) {
+            out.push_str("--\n");
+        }
+        let (sep, line) = if hits[i] {
+            let marked = regex.replace_all(line, |caps: &regex::Captures| {
+                color::paint("1;31", &caps[0])
+            });
+            (':', marked.into_owned())
+        } else {
+            ('-', line.to_string())
+        };
+        out.push_str(&format!(
+            "{}{sep}{}{sep}{}\n",
+            color::paint("35", reference),
+            color::paint("32", i + 1),
+            line.trim_end_matches('\r')
+        ));
+        last = Some(i);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_excerpt() {
+        let text = "one\r\nError E1234 here\nthree\nfour\nfive\nsix\nE1234 again\n";
+        let regex = Regex::new(r"E\d{4}").unwrap();
+        assert_eq!(
+            excerpt("id:a@x", text, &regex, 0).unwrap(),
+            "id:a@x:2:Error E1234 here\nid:a@x:7:E1234 again\n"
+        );
+        assert_eq!(
+            excerpt("id:a@x", text, &regex, 1).unwrap(),
+            concat!(
+                "id:a@x-1-one\nid:a@x:2:Error E1234 here\nid:a@x-3-three\n--\n",
+                "id:a@x-6-six\nid:a@x:7:E1234 again\n"
+            )
+        );
+        assert!(excerpt("id:a@x", "nothing", &regex, 2).is_none());
+    }
+
+    #[test]
+    fn test_search_decoded_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a");
+        std::fs::write(
+            &file,
+            "Subject: x\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n\
+Your order =2312345 has shipped\r\n",
+        )
+        .unwrap();
+        let replay = [
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["search", "--output=messages", "--", "tag:orders"],
+                "stdout": "id:a@x\n",
+            }),
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["search", "--output=files", "--limit=1", "--", "id:\"a@x\""],
+                "stdout": format!("{}\n", file.display()),
+            }),
+        ]
+        .map(|line| line.to_string())
+        .join("\n");
+        let args = |pattern: &str| Args {
+            pattern: pattern.to_string(),
+            query: "tag:orders".to_string(),
+            context: 0,
+            ignore_case: true,
+        };
+        runner::scoped(Replay::parse(&replay).unwrap(), || {
+            let found = search_message("id:a@x", &Regex::new("#12345").unwrap(), 0).unwrap();
+            assert_eq!(
+                found.as_deref(),
+                Some("id:a@x:1:Your order #12345 has shipped\n")
+            );
+            run(args("ORDER #\\d+")).unwrap();
+            let err = run(args("refund")).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::NoMatch { query }) if query == "/refund/ in tag:orders"
+            ));
+            assert!(run(args("(")).is_err());
+        });
+    }
+}
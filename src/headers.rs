@@ -0,0 +1,296 @@
+//! Header inspection: decoded headers, Received chain, spoofing hints, SPF/DKIM/DMARC results
+
+use anyhow::Result;
+use mail_parser::{HeaderName, Message};
+use serde::Serialize;
+
+use crate::color;
+use crate::mime;
+
+/// Arguments for `mu headers`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    headers: Vec<(String, String)>,
+    received: Vec<Hop>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Hop {
+    from: String,
+    by: String,
+    with: String,
+    date: String,
+    /// Seconds since the previous hop (or the Date header for the first hop)
+    delay: Option<i64>,
+}
+
+/// Inspect the headers of a message
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+    let report = inspect(&message);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
+
+fn inspect(message: &Message) -> Report {
+    let headers = message
+        .headers_raw()
+        .map(|(name, value)| (name.to_string(), mime::decode_words(value)))
+        .collect();
+
+    Report {
+        headers,
+        received: received_chain(message),
+        warnings: warnings(message),
+    }
+}
+
+/// Rebuild the Received chain, oldest hop first, with per-hop delays
+fn received_chain(message: &Message) -> Vec<Hop> {
+    let mut prev = message.date().map(|d| d.to_timestamp());
+    let mut hops = Vec::new();
+
+    let received = message
+        .headers()
+        .iter()
+        .filter(|h| h.name == HeaderName::Received)
+        .filter_map(|h| h.value.as_received());
+
+    // Received headers are prepended by each hop, so the oldest is last
+    for r in received.collect::<Vec<_>>().into_iter().rev() {
+        let ts = r.date().map(|d| d.to_timestamp());
+        let delay = match (prev, ts) {
+            (Some(p), Some(t)) => Some(t - p),
+            _ => None,
+        };
+        if ts.is_some() {
+            prev = ts;
+        }
+        hops.push(Hop {
+            from: r.from().map(|h| h.to_string()).unwrap_or_default(),
+            by: r.by().map(|h| h.to_string()).unwrap_or_default(),
+            with: r.with().map(|p| p.to_string()).unwrap_or_default(),
+            date: r.date().map(|d| d.to_rfc822()).unwrap_or_default(),
+            delay,
+        });
+    }
+    hops
+}
+
+/// Flag From/Return-Path/Reply-To domain mismatches
+fn warnings(message: &Message) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let from = message
+        .from()
+        .and_then(|a| a.first())
+        .and_then(|a| a.address())
+        .and_then(mime::domain_of);
+    let Some(from) = from else {
+        return warnings;
+    };
+
+    let return_path = message.return_address().and_then(mime::domain_of);
+    if let Some(rp) = return_path
+        && !same_org(&from, &rp)
+    {
+        warnings.push(format!(
+            "From domain ({}) differs from Return-Path domain ({})",
+            from, rp
+        ));
+    }
+
+    let reply_to = message
+        .reply_to()
+        .and_then(|a| a.first())
+        .and_then(|a| a.address())
+        .and_then(mime::domain_of);
+    if let Some(rt) = reply_to
+        && !same_org(&from, &rt)
+    {
+        warnings.push(format!(
+            "From domain ({}) differs from Reply-To domain ({})",
+            from, rt
+        ));
+    }
+
+    warnings
+}
+
+/// Methods the receiving server's verdicts are shown for, in banner order
+pub const AUTH_METHODS: [&str; 3] = ["dkim", "spf", "dmarc"];
+
+/// The receiving server's verdict per method in [`AUTH_METHODS`] (`pass`, `fail`, ...), from
+/// the topmost Authentication-Results header; the ones below it came with the mail and can't
+/// be trusted. Of several DKIM signatures, a passing one wins.
+pub fn auth_results(message: &Message) -> Vec<(&'static str, Option<String>)> {
+    let header = message
+        .headers_raw()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Authentication-Results"))
+        .map_or("", |(_, value)| value);
+    // Comments (`(google.com: domain of ...)`) may hold anything, `;` included
+    let mut text = String::new();
+    let mut depth = 0usize;
+    for c in header.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => text.push(c),
+            _ => {}
+        }
+    }
+    // The first field names the server (`mx.example.com; ...`)
+    let verdicts: Vec<(String, String)> = text
+        .split(';')
+        .skip(1)
+        .filter_map(|info| {
+            let (method, rest) = info.trim().split_once('=')?;
+            let method = method.split('/').next()?.trim().to_ascii_lowercase();
+            let result = rest.split_whitespace().next()?.to_ascii_lowercase();
+            Some((method, result))
+        })
+        .collect();
+    AUTH_METHODS
+        .iter()
+        .map(|&method| {
+            let mut results = verdicts.iter().filter(|(m, _)| m == method);
+            let result = match results.clone().find(|(_, r)| r == "pass") {
+                Some((_, pass)) => Some(pass.clone()),
+                None => results.next().map(|(_, r)| r.clone()),
+            };
+            (method, result)
+        })
+        .collect()
+}
+
+/// Treat subdomains of each other as the same organization (bounces.example.com ~ example.com)
+fn same_org(a: &str, b: &str) -> bool {
+    a == b || a.ends_with(&format!(".{}", b)) || b.ends_with(&format!(".{}", a))
+}
+
+/// Format a delay in seconds as `+1h 2m`, `+5s`, ...
+fn format_delay(secs: i64) -> String {
+    let sign = if secs < 0 { "-" } else { "+" };
+    let s = secs.abs();
+    match s {
+        0..60 => format!("{}{}s", sign, s),
+        60..3600 => format!("{}{}m {}s", sign, s / 60, s % 60),
+        _ => format!("{}{}h {}m", sign, s / 3600, (s % 3600) / 60),
+    }
+}
+
+fn print_report(report: &Report) {
+    println!("{}", color::header("Headers"));
+    for (name, value) in &report.headers {
+        println!("{} {}", color::paint("33", format!("{}:", name)), value);
+    }
+
+    if !report.received.is_empty() {
+        println!("\n{}", color::header("Received chain (oldest first)"));
+        for (i, hop) in report.received.iter().enumerate() {
+            let delay = match hop.delay {
+                Some(d) if d < 0 => color::paint("31", format!("{} (clock skew)", format_delay(d))),
+                Some(d) if d >= 300 => color::paint("31", format_delay(d)),
+                Some(d) => format_delay(d),
+                None => "?".to_string(),
+            };
+            println!(
+                "{:>2}. {} → {} {} {}",
+                i + 1,
+                hop.from,
+                hop.by,
+                color::paint("2", format!("{} {}", hop.with, hop.date)),
+                delay
+            );
+        }
+    }
+
+    if !report.warnings.is_empty() {
+        println!("\n{}", color::paint("1;31", "=== Warnings ==="));
+        for w in &report.warnings {
+            println!("{} {}", color::paint("31", "⚠"), w);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"Received: from mx.example.net by mail.local with ESMTPS; Mon, 2 Feb 2026 10:00:30 +0000\r\n\
+Received: from laptop by mx.example.net with ESMTPSA; Mon, 2 Feb 2026 10:00:05 +0000\r\n\
+Return-Path: <bounce@evil.example>\r\n\
+From: Bank <support@bank.example>\r\n\
+Subject: =?UTF-8?B?VXJnZW50?=\r\n\
+Date: Mon, 2 Feb 2026 10:00:00 +0000\r\n\r\nbody";
+
+    #[test]
+    fn test_inspect_decodes_and_chains() {
+        let msg = mime::parse(RAW).unwrap();
+        let report = inspect(&msg);
+        assert!(
+            report
+                .headers
+                .contains(&("Subject".into(), "Urgent".into()))
+        );
+        assert_eq!(report.received.len(), 2);
+        assert_eq!(report.received[0].by, "mx.example.net");
+        assert_eq!(report.received[0].delay, Some(5));
+        assert_eq!(report.received[1].delay, Some(25));
+    }
+
+    #[test]
+    fn test_return_path_mismatch() {
+        let msg = mime::parse(RAW).unwrap();
+        let warnings = warnings(&msg);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("evil.example"));
+    }
+
+    #[test]
+    fn test_auth_results() {
+        let raw = b"Authentication-Results: mx.local;\r\n dkim=fail (bad sig; key ok) header.d=x.example;\r\n \
+dkim=pass header.d=x.example; spf=softfail (domain of a@x.example) smtp.mailfrom=x.example\r\n\
+Authentication-Results: forged.example; dmarc=pass\r\n\r\nbody";
+        let msg = mime::parse(raw).unwrap();
+        assert_eq!(
+            auth_results(&msg),
+            [
+                ("dkim", Some("pass".to_string())),
+                ("spf", Some("softfail".to_string())),
+                ("dmarc", None),
+            ]
+        );
+        let msg = mime::parse(b"Subject: x\r\n\r\nbody").unwrap();
+        assert!(
+            auth_results(&msg)
+                .iter()
+                .all(|(_, result)| result.is_none())
+        );
+    }
+
+    #[test]
+    fn test_same_org_and_delay() {
+        assert!(same_org("bounces.example.com", "example.com"));
+        assert!(!same_org("example.com", "example.org"));
+        assert_eq!(format_delay(5), "+5s");
+        assert_eq!(format_delay(125), "+2m 5s");
+        assert_eq!(format_delay(-3), "-3s");
+    }
+}
@@ -0,0 +1,172 @@
+//! notmuch hook management (pre-new / post-new)
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::color;
+use crate::notmuch;
+
+/// Marker line identifying hooks written by mu
+const MARKER: &str = "# Installed by mu (mu hooks install)";
+
+/// Hooks managed by mu and the command each one runs
+const HOOKS: &[(&str, &str)] = &[
+    // Fetch only: notmuch new is already running, so don't index again. `mu sync` itself
+    // indexes with --no-hooks, so this only runs for a plain `notmuch new`.
+    ("pre-new", "mu sync --quiet --no-index"),
+    ("post-new", "mu tagrules"),
+];
+
+/// Arguments for `mu hooks`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Write pre-new/post-new hooks into the notmuch hooks directory
+    Install {
+        /// Overwrite existing hooks not installed by mu (a .bak copy is kept)
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show which hooks are installed
+    Status,
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    Missing,
+    Installed,
+    /// A hook exists but wasn't written by mu
+    Foreign,
+}
+
+/// Manage notmuch hooks
+pub fn run(args: Args) -> Result<()> {
+    let dir = hooks_dir()?;
+    match args.action {
+        Action::Install { force } => install(&dir, force),
+        Action::Status => {
+            println!(
+                "{} {}",
+                color::paint("1;36", "Hooks directory:"),
+                dir.display()
+            );
+            for (name, command) in HOOKS {
+                let label = match state(&dir.join(name)) {
+                    State::Installed => color::paint("32", "✓ installed"),
+                    State::Foreign => color::paint("33", "! custom hook (not managed by mu)"),
+                    State::Missing => color::paint("2", "✗ missing"),
+                };
+                println!("  {:<9} {}  {}", name, label, color::paint("2", command));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn install(dir: &Path, force: bool) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    for (name, command) in HOOKS {
+        let path = dir.join(name);
+        if state(&path) == State::Foreign {
+            if !force {
+                anyhow::bail!(
+                    "{} exists and wasn't installed by mu (use --force to replace it)",
+                    path.display()
+                );
+            }
+            std::fs::copy(&path, path.with_extension("bak"))
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+        }
+
+        std::fs::write(&path, script(command))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        make_executable(&path)?;
+        eprintln!("{} {}", color::ok(), path.display());
+    }
+    Ok(())
+}
+
+fn script(command: &str) -> String {
+    format!("#!/bin/sh\n{}\nexec {}\n", MARKER, command)
+}
+
+fn state(path: &Path) -> State {
+    match std::fs::read_to_string(path) {
+        Ok(content) if content.contains(MARKER) => State::Installed,
+        Ok(_) => State::Foreign,
+        Err(_) if path.exists() => State::Foreign,
+        Err(_) => State::Missing,
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Hooks that are missing or not managed by mu
+pub fn not_installed() -> Result<Vec<&'static str>> {
+    let dir = hooks_dir()?;
+    Ok(HOOKS
+        .iter()
+        .filter(|(name, _)| state(&dir.join(name)) != State::Installed)
+        .map(|(name, _)| *name)
+        .collect())
+}
+
+/// Locate the notmuch hooks directory
+fn hooks_dir() -> Result<PathBuf> {
+    if let Some(dir) = notmuch::config_get("database.hook_dir") {
+        return Ok(PathBuf::from(dir));
+    }
+    let db =
+        notmuch::config_get("database.path").context("notmuch database.path is not configured")?;
+    Ok(PathBuf::from(db).join(".notmuch").join("hooks"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mu-hooks-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_install_and_state() {
+        let dir = temp_dir("install");
+        assert_eq!(state(&dir.join("pre-new")), State::Missing);
+        install(&dir, false).unwrap();
+        assert_eq!(state(&dir.join("pre-new")), State::Installed);
+        let post = std::fs::read_to_string(dir.join("post-new")).unwrap();
+        assert!(post.starts_with("#!/bin/sh") && post.contains("mu tagrules"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_install_refuses_foreign_hook() {
+        let dir = temp_dir("foreign");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pre-new"), "#!/bin/sh\nofflineimap\n").unwrap();
+        assert!(install(&dir, false).is_err());
+        install(&dir, true).unwrap();
+        assert!(dir.join("pre-new.bak").exists());
+        assert_eq!(state(&dir.join("pre-new")), State::Installed);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
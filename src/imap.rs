@@ -0,0 +1,398 @@
+//! Server-side IMAP search for mail that isn't synced locally (via curl)
+
+use anyhow::{Context, Result};
+use mail_parser::{DateTime, MessageParser};
+use std::iter::Peekable;
+use std::process::Command;
+use std::str::Chars;
+
+use crate::config::{Account, Config};
+use crate::{http, import, maildir, mime, notmuch, process, tracker};
+
+/// Newest matches kept per folder
+const LIMIT: usize = 100;
+
+/// Listing lines (`imap:<account>:<uid>:<folder>  date  from; subject`) for server
+/// matches whose Message-ID isn't in the local index
+pub fn search(cfg: &Config, query: &str) -> Result<Vec<String>> {
+    let criteria = criteria(query);
+    let mut lines = Vec::new();
+    for account in cfg.accounts.iter().filter(|a| a.imap_url.is_some()) {
+        let password = password(account)?;
+        let folders = match account.server_folders.as_slice() {
+            [] => &["INBOX".to_string()][..],
+            folders => folders,
+        };
+        for folder in folders {
+            lines.extend(search_folder(account, &password, folder, &criteria)?);
+        }
+    }
+    drop_local(lines)
+}
+
+/// `(listing line, Message-ID)` of the newest matches in one server folder
+fn search_folder(
+    account: &Account,
+    password: &str,
+    folder: &str,
+    criteria: &str,
+) -> Result<Vec<(String, String)>> {
+    let url = folder_url(account, folder);
+    let found = curl(
+        account,
+        password,
+        &url,
+        Some(&format!("UID SEARCH {}", criteria)),
+    )?;
+    let mut uids = parse_search(&String::from_utf8_lossy(&found));
+    uids.sort_unstable_by(|a, b| b.cmp(a));
+    uids.truncate(LIMIT);
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let set: Vec<String> = uids.iter().map(u32::to_string).collect();
+    // ENVELOPE comes back inline (curl doesn't print literals of custom commands)
+    let request = format!("UID FETCH {} (UID ENVELOPE)", set.join(","));
+    let fetched = curl(account, password, &url, Some(&request))?;
+    Ok(parse_envelopes(&String::from_utf8_lossy(&fetched))
+        .into_iter()
+        .map(|e| (listing_line(&account.name, folder, &e), e.message_id))
+        .collect())
+}
+
+/// Raw message for an `imap:` listing reference
+pub fn fetch(cfg: &Config, reference: &str) -> Result<Vec<u8>> {
+    let (account, uid, folder) = parse_reference(cfg, reference)?;
+    let url = format!("{};UID={}", folder_url(account, &folder), uid);
+    curl(account, &password(account)?, &url, None)
+}
+
+/// Copy a server message into the local Maildir and index it; returns its Message-ID
+pub fn download(cfg: &Config, reference: &str) -> Result<String> {
+    let raw = fetch(cfg, reference)?;
+    let (account, _, folder) = parse_reference(cfg, reference)?;
+    let dest = import::maildir_root(cfg).join(account.dir()).join(&folder);
+    maildir::deliver(&dest, &raw, Some("S"))?;
+    import::index()?;
+    Ok(message_id(&raw))
+}
+
+/// notmuch-style query → IMAP SEARCH criteria (`from:`, `to:`, `subject:`, words as TEXT)
+fn criteria(query: &str) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.trim_matches('"').replace(['\\', '"'], ""));
+    let keys: Vec<String> = query
+        .split_whitespace()
+        .filter(|w| !matches!(w.to_lowercase().as_str(), "and" | "*"))
+        .filter_map(|w| match w.split_once(':') {
+            Some(("from", v)) => Some(format!("FROM {}", quote(v))),
+            Some(("to", v)) => Some(format!("TO {}", quote(v))),
+            Some(("subject", v)) => Some(format!("SUBJECT {}", quote(v))),
+            // tag:, date: and the like have no IMAP equivalent
+            Some(_) => None,
+            None => Some(format!("TEXT {}", quote(w))),
+        })
+        .collect();
+    if keys.is_empty() {
+        "ALL".to_string()
+    } else {
+        format!("CHARSET UTF-8 {}", keys.join(" "))
+    }
+}
+
+/// UIDs from `* SEARCH 1 2 3` or `* ESEARCH (TAG "x") UID ALL 1:3,7`
+fn parse_search(response: &str) -> Vec<u32> {
+    let mut uids = Vec::new();
+    for line in response.lines() {
+        if let Some(rest) = line.strip_prefix("* SEARCH") {
+            uids.extend(
+                rest.split_whitespace()
+                    .filter_map(|n| n.parse::<u32>().ok()),
+            );
+        } else if line.starts_with("* ESEARCH")
+            && let Some(set) = line.split(" ALL ").nth(1)
+        {
+            for range in set.split_whitespace().next().unwrap_or("").split(',') {
+                let (start, end) = range.split_once(':').unwrap_or((range, range));
+                if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+                    uids.extend(start.min(end)..=start.max(end));
+                }
+            }
+        }
+    }
+    uids
+}
+
+/// One `* n FETCH (UID .. ENVELOPE (..))` response
+#[derive(Debug, Default, PartialEq)]
+struct Envelope {
+    uid: u32,
+    date: String,
+    from: String,
+    subject: String,
+    message_id: String,
+}
+
+/// A parsed IMAP value: atom, string (NIL is None) or parenthesized list
+#[derive(Debug, PartialEq)]
+enum Item {
+    Atom(String),
+    Str(Option<String>),
+    List(Vec<Item>),
+}
+
+impl Item {
+    fn text(&self) -> &str {
+        match self {
+            Item::Atom(s) | Item::Str(Some(s)) => s,
+            _ => "",
+        }
+    }
+
+    fn list(&self) -> &[Item] {
+        match self {
+            Item::List(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+fn parse_envelopes(response: &str) -> Vec<Envelope> {
+    response
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("* ")?
+                .split_once(" FETCH ")
+                .map(|(_, rest)| rest)
+        })
+        .filter_map(|rest| {
+            let mut chars = rest.chars().peekable();
+            let items = parse_item(&mut chars)?;
+            let items = items.list();
+            let value = |key: &str| {
+                items
+                    .iter()
+                    .position(|i| *i == Item::Atom(key.to_string()))
+                    .and_then(|p| items.get(p + 1))
+            };
+            let env = value("ENVELOPE")?.list();
+            let from = env
+                .get(2)
+                .and_then(|f| f.list().first())
+                .map(Item::list)
+                .unwrap_or(&[]);
+            let address = match (from.get(2), from.get(3)) {
+                (Some(mailbox), Some(host)) => format!("{}@{}", mailbox.text(), host.text()),
+                _ => String::new(),
+            };
+            let name = from
+                .first()
+                .map(|n| mime::decode_words(n.text()))
+                .unwrap_or_default();
+            Some(Envelope {
+                uid: value("UID")?.text().parse().ok()?,
+                date: DateTime::parse_rfc822(env.first()?.text())
+                    .map(|d| format!("{:04}-{:02}-{:02}", d.year, d.month, d.day))
+                    .unwrap_or_default(),
+                from: if name.is_empty() { address } else { name },
+                subject: mime::decode_words(env.get(1).map_or("", Item::text)),
+                message_id: env
+                    .get(9)
+                    .map_or("", Item::text)
+                    .trim_matches(['<', '>'])
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse one value, consuming it from `chars`
+fn parse_item(chars: &mut Peekable<Chars>) -> Option<Item> {
+    while chars.next_if_eq(&' ').is_some() {}
+    match chars.peek()? {
+        '(' => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                while chars.next_if_eq(&' ').is_some() {}
+                if chars.next_if_eq(&')').is_some() {
+                    return Some(Item::List(items));
+                }
+                items.push(parse_item(chars)?);
+            }
+        }
+        '"' => {
+            chars.next();
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => s.extend(chars.next()),
+                    '"' => return Some(Item::Str(Some(s))),
+                    c => s.push(c),
+                }
+            }
+            None
+        }
+        _ => {
+            let mut atom = String::new();
+            while let Some(c) = chars.next_if(|c| !matches!(c, ' ' | '(' | ')')) {
+                atom.push(c);
+            }
+            if atom.is_empty() {
+                return None;
+            }
+            Some(if atom == "NIL" {
+                Item::Str(None)
+            } else {
+                Item::Atom(atom)
+            })
+        }
+    }
+}
+
+fn listing_line(account: &str, folder: &str, envelope: &Envelope) -> String {
+    format!(
+        "imap:{}:{}:{}  {}  {}; {} [server]",
+        account,
+        envelope.uid,
+        folder.replace('%', "%25").replace(' ', "%20"),
+        envelope.date,
+        envelope.from,
+        envelope.subject
+    )
+}
+
+fn message_id(raw: impl AsRef<[u8]>) -> String {
+    MessageParser::default()
+        .parse_headers(raw.as_ref())
+        .and_then(|m| m.message_id().map(String::from))
+        .unwrap_or_default()
+}
+
+/// Keep only messages notmuch doesn't know
+fn drop_local(lines: Vec<(String, String)>) -> Result<Vec<String>> {
+    let ids: Vec<String> = lines
+        .iter()
+        .filter(|(_, id)| !id.is_empty())
+        .map(|(_, id)| notmuch::quote_id(id))
+        .collect();
+    let local: Vec<String> = if ids.is_empty() {
+        Vec::new()
+    } else {
+        notmuch::search("messages", &ids.join(" or "))?
+    };
+    Ok(lines
+        .into_iter()
+        .filter(|(_, id)| id.is_empty() || !local.contains(&format!("id:{}", id)))
+        .map(|(line, _)| line)
+        .collect())
+}
+
+/// `(account, uid, folder)` of an `imap:<account>:<uid>:<folder>` reference
+fn parse_reference<'a>(cfg: &'a Config, reference: &str) -> Result<(&'a Account, u32, String)> {
+    let rest = reference.strip_prefix("imap:").unwrap_or(reference);
+    let mut fields = rest.splitn(3, ':');
+    let (Some(name), Some(uid), Some(folder)) = (fields.next(), fields.next(), fields.next())
+    else {
+        anyhow::bail!("Malformed server reference {}", reference);
+    };
+    let account = cfg
+        .accounts
+        .iter()
+        .find(|a| a.name == name)
+        .with_context(|| format!("No account {}", name))?;
+    Ok((account, uid.parse()?, tracker::percent_decode(folder)))
+}
+
+fn folder_url(account: &Account, folder: &str) -> String {
+    let base = account
+        .imap_url
+        .as_deref()
+        .unwrap_or("")
+        .trim_end_matches('/');
+    format!(
+        "{}/{}",
+        base,
+        folder.replace('%', "%25").replace(' ', "%20")
+    )
+}
+
+fn password(account: &Account) -> Result<String> {
+    match &account.imap_password_command {
+        Some(cmd) => crate::config::command_output(cmd),
+        None => Ok(String::new()),
+    }
+}
+
+/// Run an IMAP request with curl; credentials go through its config on stdin
+fn curl(account: &Account, password: &str, url: &str, request: Option<&str>) -> Result<Vec<u8>> {
+    let mut config = format!("url = {}\n", http::quote(url));
+    if let Some(user) = &account.imap_user {
+        config.push_str(&format!(
+            "user = {}\n",
+            http::quote(&format!("{}:{}", user, password))
+        ));
+    }
+    if let Some(request) = request {
+        config.push_str(&format!("request = {}\n", http::quote(request)));
+    }
+    let output = process::run_with_input(
+        Command::new("curl").args(["-sS", "--max-time", "60", "-K", "-"]),
+        config.as_bytes(),
+    )
+    .context("Failed to run curl")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "IMAP request to {} failed: {}",
+            account.name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_criteria() {
+        assert_eq!(
+            criteria("from:alice subject:\"budget\" tag:inbox report"),
+            "CHARSET UTF-8 FROM \"alice\" SUBJECT \"budget\" TEXT \"report\""
+        );
+        assert_eq!(criteria("*"), "ALL");
+    }
+
+    #[test]
+    fn test_parse_search() {
+        assert_eq!(parse_search("* SEARCH 4 9 12\r\n"), vec![4, 9, 12]);
+        assert_eq!(
+            parse_search("* ESEARCH (TAG \"A2\") UID ALL 1:3,7\r\n"),
+            vec![1, 2, 3, 7]
+        );
+    }
+
+    #[test]
+    fn test_parse_envelopes() {
+        let response = "* 1 FETCH (UID 41 ENVELOPE (\"Mon, 2 Feb 2015 10:00:00 +0000\" \"Old \\\"plan\\\"\" \
+((\"Ann\" NIL \"ann\" \"x.org\")) NIL NIL NIL NIL NIL NIL \"<old1@x>\"))\r\n\
+* 2 FETCH (ENVELOPE (NIL \"=?utf-8?q?Caf=C3=A9?=\" ((NIL NIL \"b\" \"x\")) NIL NIL NIL NIL NIL NIL NIL) UID 42)\r\n";
+        let envelopes = parse_envelopes(response);
+        assert_eq!(
+            envelopes[0],
+            Envelope {
+                uid: 41,
+                date: "2015-02-02".into(),
+                from: "Ann".into(),
+                subject: "Old \"plan\"".into(),
+                message_id: "old1@x".into(),
+            }
+        );
+        assert_eq!((envelopes[1].uid, envelopes[1].from.as_str()), (42, "b@x"));
+        assert_eq!(envelopes[1].subject, "Café");
+        assert_eq!(
+            listing_line("work", "Archive 2015", &envelopes[0]),
+            "imap:work:41:Archive%202015  2015-02-02  Ann; Old \"plan\" [server]"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! `mu import`: mbox files into Maildir
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use mu_core::import::import_mbox;
+pub use mu_core::import::{index, maildir_root};
+
+use crate::color;
+use crate::config::{self, Config};
+
+/// Arguments for `mu import`
+#[derive(clap::Args)]
+pub struct Args {
+    /// mbox file to import
+    file: PathBuf,
+
+    /// Target folder below the Maildir root (e.g. Archive/2019)
+    #[arg(short, long)]
+    folder: String,
+
+    /// Maildir root (default: notmuch database.path, then `[index] maildir`)
+    #[arg(short, long)]
+    maildir: Option<String>,
+
+    /// Don't run `notmuch new` afterwards
+    #[arg(long)]
+    no_index: bool,
+}
+
+/// Split an mbox into a Maildir folder and index it
+pub fn run(args: Args) -> Result<()> {
+    let root = match &args.maildir {
+        Some(dir) => config::expand_tilde(dir),
+        None => maildir_root(&Config::load()?),
+    };
+    let dest = root.join(&args.folder);
+    let count = import_mbox(&args.file, &dest)?;
+    eprintln!(
+        "{} Imported {} messages into {}",
+        color::ok(),
+        count,
+        dest.display()
+    );
+
+    if !args.no_index {
+        index()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbox;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_run_imports_into_the_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("old.mbox");
+        let mut mbox = mbox::entry(b"Subject: one\nStatus: RO\n\nFrom me\n");
+        mbox.extend(mbox::entry(b"Subject: two\n\nyo\n"));
+        std::fs::write(&file, mbox).unwrap();
+        let args = |no_index| Args {
+            file: file.clone(),
+            folder: "Archive/2019".to_string(),
+            maildir: Some(dir.path().display().to_string()),
+            no_index,
+        };
+
+        run(args(true)).unwrap();
+        let cur = dir.path().join("Archive/2019/cur");
+        let mut messages: Vec<(String, String)> = std::fs::read_dir(&cur)
+            .unwrap()
+            .map(|e| {
+                let path = e.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read_to_string(&path).unwrap())
+            })
+            .collect();
+        messages.sort_by_key(|(name, _)| !name.ends_with(":2,S"));
+        // The body's `From ` line was quoted in the mbox and comes back as written
+        assert_eq!(messages[0].1, "Subject: one\nStatus: RO\n\nFrom me\n");
+        assert_eq!(messages[1].1, "Subject: two\n\nyo\n");
+        assert!(messages[1].0.ends_with(":2,"));
+
+        // Indexed afterwards unless --no-index
+        let new = Replay::parse(r#"{"program":"notmuch","args":["new","--quiet"]}"#).unwrap();
+        runner::scoped(new, || run(args(false))).unwrap();
+        assert_eq!(std::fs::read_dir(&cur).unwrap().count(), 4);
+        let failing =
+            Replay::parse(r#"{"program":"notmuch","args":["new","--quiet"],"status":1}"#).unwrap();
+        assert!(runner::scoped(failing, || run(args(false))).is_err());
+    }
+}
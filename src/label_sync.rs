@@ -0,0 +1,196 @@
+//! Gmail label folders ↔ notmuch tags
+//!
+//! Gmail exposes labels as IMAP folders: copying a message into `[Gmail]/Starred`
+//! stars it, removing it from `INBOX` archives it. Run after `mu sync`: tags
+//! changed since the last run become folder copies/removals (pushed by the next
+//! mbsync), then folder placement is mirrored back into tags.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config, LabelMap};
+use crate::{color, import, maildir, notmuch};
+
+/// Arguments for `mu label-sync`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Print the planned folder changes without touching anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Tagged message-ids per mapping at the end of the last run
+type State = BTreeMap<String, BTreeSet<String>>;
+
+/// Folder changes for one mapping
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+    /// Newly tagged locally: copy into the label folder
+    copy: Vec<String>,
+    /// Untagged locally: remove from the label folder
+    remove: Vec<String>,
+}
+
+/// Sync label folders and tags in both directions
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    if cfg.label_sync.is_empty() {
+        anyhow::bail!("No [[label_sync]] mappings in config.toml");
+    }
+    let root = import::maildir_root(cfg);
+    let mut state = load_state()?;
+
+    let mut moved = 0;
+    for map in &cfg.label_sync {
+        let tagged = ids(&format!("tag:{}", map.tag))?;
+        let in_folder = ids(&folder_query(map))?;
+        let plan = plan(state.get(&key(map)), &tagged, &in_folder);
+        if args.dry_run {
+            plan.copy
+                .iter()
+                .for_each(|id| println!("copy {} -> {}", id, map.folder));
+            plan.remove
+                .iter()
+                .for_each(|id| println!("remove {} from {}", id, map.folder));
+            continue;
+        }
+        moved += push(map, &plan, &root)?;
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    if moved > 0 {
+        import::index()?;
+    }
+
+    for map in &cfg.label_sync {
+        notmuch::tag_batch(&pull_lines(map))?;
+        state.insert(key(map), ids(&format!("tag:{}", map.tag))?);
+    }
+    save_state(&state)?;
+    eprintln!("{} {} message(s) moved, tags updated", color::ok(), moved);
+    Ok(())
+}
+
+/// Local tag changes since the last run, restricted to what the folder doesn't reflect yet
+///
+/// Without a previous state nothing is pushed: the first run only pulls.
+fn plan(
+    previous: Option<&BTreeSet<String>>,
+    tagged: &BTreeSet<String>,
+    in_folder: &BTreeSet<String>,
+) -> Plan {
+    let Some(previous) = previous else {
+        return Plan::default();
+    };
+    Plan {
+        copy: tagged
+            .difference(previous)
+            .filter(|id| !in_folder.contains(*id))
+            .cloned()
+            .collect(),
+        remove: previous
+            .difference(tagged)
+            .filter(|id| in_folder.contains(*id))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Apply a plan to the Maildir; returns the number of changed messages
+fn push(map: &LabelMap, plan: &Plan, root: &Path) -> Result<usize> {
+    let folder = root.join(&map.folder);
+    for id in &plan.copy {
+        let path = PathBuf::from(notmuch::first_file(&notmuch::quote_id(id))?);
+        let raw =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        maildir::deliver(&folder, &raw, Some(maildir::flags(&path)))?;
+    }
+    for id in &plan.remove {
+        let query = format!("{} and {}", notmuch::quote_id(id), folder_query(map));
+        for file in notmuch::search("files", &query)? {
+            std::fs::remove_file(&file).with_context(|| format!("Failed to remove {}", file))?;
+        }
+    }
+    Ok(plan.copy.len() + plan.remove.len())
+}
+
+/// Tag batch lines mirroring folder placement into the tag
+fn pull_lines(map: &LabelMap) -> Vec<String> {
+    let folder = folder_query(map);
+    vec![
+        format!("+{} -- {} and not tag:{}", map.tag, folder, map.tag),
+        format!("-{} -- tag:{} and not {}", map.tag, map.tag, folder),
+    ]
+}
+
+fn folder_query(map: &LabelMap) -> String {
+    format!("folder:\"{}\"", map.folder.replace('"', "\"\""))
+}
+
+fn key(map: &LabelMap) -> String {
+    format!("{} {}", map.tag, map.folder)
+}
+
+fn ids(query: &str) -> Result<BTreeSet<String>> {
+    Ok(notmuch::search("messages", query)?.into_iter().collect())
+}
+
+fn state_path() -> PathBuf {
+    config::data_dir().join("label-sync.json")
+}
+
+fn load_state() -> Result<State> {
+    match std::fs::read_to_string(state_path()) {
+        Ok(text) => serde_json::from_str(&text).context("Invalid label-sync state"),
+        Err(_) => Ok(State::new()),
+    }
+}
+
+fn save_state(state: &State) -> Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(state)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ids: &[&str]) -> BTreeSet<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let previous = set(&["id:a", "id:b"]);
+        // c newly tagged, b untagged, a unchanged; d only appeared in the folder
+        let p = plan(
+            Some(&previous),
+            &set(&["id:a", "id:c"]),
+            &set(&["id:a", "id:b", "id:d"]),
+        );
+        assert_eq!(p.copy, vec!["id:c"]);
+        assert_eq!(p.remove, vec!["id:b"]);
+        assert_eq!(plan(None, &set(&["id:c"]), &set(&[])), Plan::default());
+    }
+
+    #[test]
+    fn test_pull_lines_and_flags() {
+        let map = LabelMap {
+            folder: "gmail/[Gmail]/Starred".to_string(),
+            tag: "flagged".to_string(),
+        };
+        assert_eq!(
+            pull_lines(&map)[0],
+            "+flagged -- folder:\"gmail/[Gmail]/Starred\" and not tag:flagged"
+        );
+    }
+}
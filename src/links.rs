@@ -0,0 +1,197 @@
+//! Link extraction with tracking-redirect unwrapping
+
+use anyhow::{Context, Result};
+use mail_parser::Message;
+use regex::Regex;
+use serde::Serialize;
+use std::process::Command;
+
+use crate::{color, mime, platform, process, tracker};
+
+/// Arguments for `mu links`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Open link number N with the default browser
+    #[arg(long, value_name = "N")]
+    open: Option<usize>,
+
+    /// Follow remaining redirects over the network (curl) to find final URLs
+    #[arg(long)]
+    resolve: bool,
+
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Link {
+    pub url: String,
+    /// Anchor text, if the link came from HTML
+    pub text: String,
+    /// Original (tracking) URL when it was unwrapped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+}
+
+/// Extract links from a message
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+    let mut links = extract(&message)?;
+
+    if args.resolve {
+        for link in &mut links {
+            if let Ok(final_url) = resolve(&link.url)
+                && final_url != link.url
+            {
+                link.original.get_or_insert_with(|| link.url.clone());
+                link.url = final_url;
+            }
+        }
+    }
+
+    if let Some(n) = args.open {
+        let link = n
+            .checked_sub(1)
+            .and_then(|i| links.get(i))
+            .with_context(|| format!("No link #{} ({} links found)", n, links.len()))?;
+        return platform::open(&link.url);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&links)?);
+    } else {
+        for (i, link) in links.iter().enumerate() {
+            let text = if link.text.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", color::paint("2", &link.text))
+            };
+            println!("{:>3}  {}{}", i + 1, link.url, text);
+        }
+    }
+    Ok(())
+}
+
+/// Collect unwrapped, deduplicated links from all text and HTML parts
+pub fn extract(message: &Message) -> Result<Vec<Link>> {
+    let mut found = Vec::new();
+    // mail-parser lists text/plain parts under html_bodies when there's no HTML
+    for part in message.html_bodies() {
+        if mime::content_type(part) == "text/html" {
+            found.extend(from_html(&mime::part_text(part))?);
+        }
+    }
+    for part in message.text_bodies() {
+        if mime::content_type(part) != "text/html" {
+            found.extend(from_text(&mime::part_text(part))?);
+        }
+    }
+
+    let mut links: Vec<Link> = Vec::new();
+    for (url, text) in found {
+        let target = tracker::unwrap(&url);
+        if links.iter().any(|l| l.url == target) {
+            continue;
+        }
+        let original = (target != url).then_some(url);
+        links.push(Link {
+            url: target,
+            text,
+            original,
+        });
+    }
+    Ok(links)
+}
+
+/// `(href, anchor text)` pairs from HTML
+pub fn from_html(html: &str) -> Result<Vec<(String, String)>> {
+    let anchor = Regex::new(r#"(?is)<a\s[^>]*?href\s*=\s*["']([^"']+)["'][^>]*>(.*?)</a>"#)?;
+    let tag = Regex::new(r"(?s)<[^>]*>")?;
+    Ok(anchor
+        .captures_iter(html)
+        .filter_map(|c| {
+            let href = decode_entities(c.get(1)?.as_str().trim());
+            let text = tag.replace_all(c.get(2)?.as_str(), "");
+            let text = decode_entities(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+            href.starts_with("http").then_some((href, text))
+        })
+        .collect())
+}
+
+/// Bare URLs from plain text
+pub fn from_text(text: &str) -> Result<Vec<(String, String)>> {
+    let url = Regex::new(r#"https?://[^\s<>"'\)\]]+"#)?;
+    Ok(url
+        .find_iter(text)
+        .map(|m| {
+            (
+                m.as_str().trim_end_matches(['.', ',', ';']).to_string(),
+                String::new(),
+            )
+        })
+        .collect())
+}
+
+/// Follow redirects with curl and return the final URL
+fn resolve(url: &str) -> Result<String> {
+    let output = process::run(Command::new("curl").args([
+        "-sIL",
+        "--max-time",
+        "10",
+        "-o",
+        "/dev/null",
+        "-w",
+        "%{url_effective}",
+        url,
+    ]))
+    .context("Failed to run curl")?;
+    if !output.status.success() {
+        anyhow::bail!("curl failed for {}", url);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Decode the handful of HTML entities common in hrefs and anchor text
+pub fn decode_entities(s: &str) -> String {
+    s.replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_html_and_text() {
+        let html = r#"<p><a class="btn" href="https://x.example/?a=1&amp;b=2"><b>Click</b> here</a>
+            <a href="mailto:me@x.example">mail</a></p>"#;
+        let links = from_html(html).unwrap();
+        assert_eq!(
+            links,
+            vec![("https://x.example/?a=1&b=2".into(), "Click here".into())]
+        );
+        let text = from_text("See https://example.com/docs. Or (https://example.org)").unwrap();
+        assert_eq!(text[0].0, "https://example.com/docs");
+        assert_eq!(text[1].0, "https://example.org");
+    }
+
+    #[test]
+    fn test_extract_dedupes() {
+        let raw = b"Content-Type: text/html\r\n\r\n<a href=\"https://l.facebook.com/l.php?u=https%3A%2F%2Fa.example%2F\">A</a>\
+<a href=\"https://a.example/\">again</a>";
+        let msg = mime::parse(raw).unwrap();
+        let links = extract(&msg).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://a.example/");
+        assert!(links[0].original.as_deref().unwrap().contains("facebook"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Mailing lists in the store: counts, archives, unread picker, muting
+
+use anyhow::{Context, Result};
+use mail_parser::{DateTime, Message, MessageParser};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{color, config, fzf, i18n, mime, notmuch, platform, sweep};
+
+/// Arguments for `mu lists`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Only scan mail newer than this (`30d`, `6m`, `1y`)
+    #[arg(long, default_value = "1y", global = true)]
+    since: String,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Open the list's web archive (List-Archive)
+    Open {
+        /// List-Id or part of it
+        list: String,
+    },
+    /// Pick from the list's unread messages with fzf
+    Unread {
+        /// List-Id or part of it
+        list: String,
+    },
+    /// Archive the list's mail now and add a tag rule that keeps it out of the inbox
+    Mute {
+        /// List-Id or part of it
+        list: String,
+    },
+}
+
+/// A mailing list seen in the store
+#[derive(Debug, Default)]
+struct List {
+    id: String,
+    name: String,
+    post: Option<String>,
+    archive: Option<String>,
+    count: usize,
+    last: i64,
+}
+
+/// Show mailing lists, or act on one
+pub fn run(args: Args) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let since = now - sweep::parse_age(&args.since)?;
+    let lists = scan(since)?;
+
+    let Some(action) = args.action else {
+        let mut sorted: Vec<&List> = lists.values().collect();
+        sorted.sort_by_key(|l| std::cmp::Reverse(l.last));
+        for l in sorted {
+            println!("{:>6}  {}  {:<40}  {}", l.count, date(l.last), l.id, l.name);
+        }
+        return Ok(());
+    };
+
+    match action {
+        Action::Open { list } => {
+            let l = find(&lists, &list)?;
+            platform::open(
+                l.archive
+                    .as_deref()
+                    .with_context(|| format!("{} has no List-Archive", l.id))?,
+            )
+        }
+        Action::Unread { list } => fzf::search(
+            Some(&format!("{} and tag:unread", query(find(&lists, &list)?))),
+            fzf::Backend::Notmuch,
+            false,
+            &fzf::Emit::File,
+        ),
+        Action::Mute { list } => mute(find(&lists, &list)?),
+    }
+}
+
+/// Collect List-Id headers from every message since `since`
+fn scan(since: i64) -> Result<BTreeMap<String, List>> {
+    let mut lists: BTreeMap<String, List> = BTreeMap::new();
+    for file in notmuch::search("files", &format!("date:@{}..", since))? {
+        let Ok(raw) = std::fs::read(&file) else {
+            continue;
+        };
+        let Some(message) = MessageParser::default().parse_headers(&raw) else {
+            continue;
+        };
+        if let Some(seen) = list_of(&message) {
+            let entry = lists.entry(seen.id.clone()).or_insert(seen);
+            entry.count += 1;
+            entry.last = entry
+                .last
+                .max(message.date().map_or(0, |d| d.to_timestamp()));
+        }
+    }
+    Ok(lists)
+}
+
+/// List-Id, description, posting address and archive URL of one message
+fn list_of(message: &Message) -> Option<List> {
+    let header = |name: &str| message.header_raw(name).map(mime::decode_words);
+    let list_id = header("List-Id")?;
+    let (name, id) = match list_id.rsplit_once('<') {
+        Some((name, id)) => (
+            name.trim().trim_matches('"').to_string(),
+            id.trim_end_matches('>').trim(),
+        ),
+        None => (String::new(), list_id.trim()),
+    };
+    let bracketed = |value: Option<String>, scheme: &str| {
+        value?
+            .split(',')
+            .map(|v| {
+                v.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+            .find(|v| v.starts_with(scheme))
+    };
+    Some(List {
+        id: id.to_lowercase(),
+        name,
+        post: bracketed(header("List-Post"), "mailto:")
+            .map(|m| m.trim_start_matches("mailto:").to_string()),
+        archive: bracketed(header("List-Archive"), "http"),
+        ..List::default()
+    })
+}
+
+fn find<'a>(lists: &'a BTreeMap<String, List>, pattern: &str) -> Result<&'a List> {
+    let pattern = pattern.to_lowercase();
+    let matches: Vec<&List> = lists.values().filter(|l| l.id.contains(&pattern)).collect();
+    match matches.as_slice() {
+        [one] => Ok(one),
+        [] => anyhow::bail!("No list matches {:?}", pattern),
+        many => anyhow::bail!(
+            "{:?} is ambiguous: {}",
+            pattern,
+            many.iter()
+                .map(|l| l.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// notmuch query for a list's mail: its posting address, else `name@host` derived from the List-Id
+fn query(list: &List) -> String {
+    let address = list
+        .post
+        .clone()
+        .unwrap_or_else(|| list.id.replacen('.', "@", 1));
+    format!("to:\"{}\"", address)
+}
+
+/// Archive the list's mail and append a tag rule to the config so new mail skips the inbox
+fn mute(list: &List) -> Result<()> {
+    let query = query(list);
+    notmuch::tag_batch(&[format!("+muted -inbox -- {}", query)])?;
+
+    let path = config::config_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    write!(file, "{}", mute_rule(list, &query))?;
+    eprintln!(
+        "{} muted {} (rule added to {})",
+        color::ok(),
+        list.id,
+        path.display()
+    );
+    Ok(())
+}
+
+fn mute_rule(list: &List, query: &str) -> String {
+    format!(
+        "\n# Muted list {}\n[[tag_rules]]\nquery = {}\ntags = [\"+muted\", \"-inbox\"]\n",
+        list.id,
+        toml::Value::String(query.to_string())
+    )
+}
+
+fn date(ts: i64) -> String {
+    i18n::current().day(&DateTime::from_timestamp(ts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const RAW: &[u8] = b"List-Id: \"Rust Users\" <rust-users.lists.example.com>\r\n\
+List-Post: <mailto:rust-users@lists.example.com>\r\n\
+List-Archive: <mailto:archive@x>, <https://lists.example.com/rust-users>\r\n\r\n";
+
+    #[test]
+    fn test_list_of() {
+        let message = MessageParser::default().parse_headers(RAW).unwrap();
+        let list = list_of(&message).unwrap();
+        assert_eq!(list.id, "rust-users.lists.example.com");
+        assert_eq!(list.name, "Rust Users");
+        assert_eq!(list.post.as_deref(), Some("rust-users@lists.example.com"));
+        assert_eq!(
+            list.archive.as_deref(),
+            Some("https://lists.example.com/rust-users")
+        );
+    }
+
+    #[test]
+    fn test_query_and_mute_rule() {
+        let list = List {
+            id: "dev.lists.example.org".to_string(),
+            ..List::default()
+        };
+        assert_eq!(query(&list), "to:\"dev@lists.example.org\"");
+        let cfg = Config::parse(&mute_rule(&list, &query(&list))).unwrap();
+        assert_eq!(cfg.tag_rules[0].query, "to:\"dev@lists.example.org\"");
+        assert_eq!(cfg.tag_rules[0].tags, vec!["+muted", "-inbox"]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Diagnostics for `-v`/`--log-file`: external commands, render stages, notmuch queries
+
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::Level;
+
+use crate::{color, config};
+
+/// Where the log goes when `--log-file` isn't given (fzf previews inherit it)
+pub const ENV: &str = "MU_LOG_FILE";
+
+/// Start logging: `-v` commands and stages, `-vv` also per-chunk timings
+///
+/// A log file without `-v` gets the `-v` level. Nothing is set up otherwise.
+pub fn init(verbosity: u8, file: Option<PathBuf>) -> Result<()> {
+    let file = file.or_else(|| config::env_path(ENV));
+    let Some(level) = level(verbosity, file.is_some()) else {
+        return Ok(());
+    };
+    let logger = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false);
+    // A second init (there is none outside tests) keeps the first logger
+    let _ = match file {
+        Some(path) => {
+            let log = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            logger
+                .with_ansi(false)
+                .with_writer(Mutex::new(log))
+                .try_init()
+        }
+        None => logger
+            .with_ansi(color::stderr())
+            .with_writer(std::io::stderr)
+            .try_init(),
+    };
+    Ok(())
+}
+
+/// How much is logged at a verbosity, with or without a log file
+fn level(verbosity: u8, file: bool) -> Option<Level> {
+    match (verbosity, file) {
+        (0, false) => None,
+        (0 | 1, _) => Some(Level::DEBUG),
+        _ => Some(Level::TRACE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(level(0, false), None);
+        assert_eq!(level(0, true), Some(Level::DEBUG));
+        assert_eq!(level(1, false), Some(Level::DEBUG));
+        assert_eq!(level(2, false), Some(Level::TRACE));
+        assert_eq!(level(3, true), Some(Level::TRACE));
+    }
+
+    #[test]
+    fn test_unopenable_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("mu.log");
+        let err = init(1, Some(path.clone())).unwrap_err();
+        assert!(format!("{:#}", err).contains(&path.display().to_string()));
+        assert!(!path.exists());
+    }
+}
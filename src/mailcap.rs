@@ -0,0 +1,291 @@
+//! Mailcap generation, merged non-destructively into an existing file
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{self, Config};
+use crate::{color, platform, process};
+
+pub const BEGIN: &str = "# >>> mu mailcap >>>";
+const END: &str = "# <<< mu mailcap <<<";
+
+/// Office document types opened with the system handler
+const OFFICE_TYPES: &[&str] = &[
+    "application/msword",
+    "application/vnd.ms-excel",
+    "application/vnd.ms-powerpoint",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.oasis.opendocument.spreadsheet",
+];
+
+/// Arguments for `mu mailcap`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Print (or write) a mailcap merged with the existing one
+    Generate {
+        /// Mailcap file to merge with (default: ~/.mailcap)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Write the merged result back to the file instead of printing it
+        #[arg(short, long)]
+        write: bool,
+    },
+}
+
+/// Generate mailcap entries
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let Action::Generate { file, write } = args.action;
+    let path = file.unwrap_or_else(|| config::home_dir().join(".mailcap"));
+    let existing = read_existing(&path)?;
+    let (merged, skipped) = merge(&existing, &entries(cfg));
+
+    for mime in &skipped {
+        eprintln!(
+            "{}",
+            color::epaint("2", format!("keeping your existing entry for {}", mime))
+        );
+    }
+
+    if write {
+        std::fs::write(&path, merged)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        eprintln!("{} Updated {}", color::ok(), path.display());
+    } else {
+        print!("{}", merged);
+    }
+    Ok(())
+}
+
+fn read_existing(path: &Path) -> Result<String> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// mailcap entries mu provides
+fn entries(cfg: &Config) -> Vec<String> {
+    let mu = &cfg.muttrc.bin;
+    let open = platform::opener();
+    let mut lines = vec![
+        format!(
+            "text/html; {} render --content-type %t -i %s; copiousoutput",
+            mu
+        ),
+        format!(
+            "text/calendar; {} render --content-type %t -i %s; copiousoutput",
+            mu
+        ),
+        format!(
+            "text/vcard; {} render --content-type %t -i %s; copiousoutput",
+            mu
+        ),
+        format!(
+            "text/x-vcard; {} render --content-type %t -i %s; copiousoutput",
+            mu
+        ),
+    ];
+
+    if cfg.mailcap.images == "terminal" {
+        lines.push(format!(
+            "image/*; {} %s; needsterminal",
+            cfg.mailcap.image_command
+        ));
+    } else {
+        lines.push(format!("image/*; {} %s", open));
+    }
+
+    lines.push(format!("application/pdf; {} %s", open));
+    lines.push("application/pdf; pdftotext -layout %s -; copiousoutput".to_string());
+    lines.extend(OFFICE_TYPES.iter().map(|t| format!("{}; {} %s", t, open)));
+    lines
+}
+
+/// The command of the first entry (`~/.mailcap`, then `/etc/mailcap`) viewing `content_type`
+/// interactively: not a `copiousoutput` filter, and its `test=` passes
+pub fn viewer(content_type: &str) -> Option<String> {
+    let passes = |test: &str| process::status(&mut process::shell(test)).is_ok_and(|s| s.success());
+    [
+        config::home_dir().join(".mailcap"),
+        PathBuf::from("/etc/mailcap"),
+    ]
+    .iter()
+    .filter_map(|path| std::fs::read_to_string(path).ok())
+    .find_map(|text| find_viewer(&text, content_type, passes))
+}
+
+/// `content_type`'s viewer in mailcap `text`, `test` running `test=` commands
+fn find_viewer(text: &str, content_type: &str, test: impl Fn(&str) -> bool) -> Option<String> {
+    let major = content_type.split('/').next().unwrap_or(content_type);
+    text.replace("\\\n", "")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find_map(|line| {
+            let mut fields = line.split(';').map(str::trim);
+            let pattern = fields.next()?.to_lowercase();
+            let command = fields.next()?;
+            let flags: Vec<&str> = fields.collect();
+            let matches = pattern == content_type
+                || pattern == major
+                || pattern.strip_suffix("/*") == Some(major);
+            if !matches
+                || flags
+                    .iter()
+                    .any(|f| f.eq_ignore_ascii_case("copiousoutput"))
+            {
+                return None;
+            }
+            let tested = flags.iter().find_map(|f| f.strip_prefix("test="));
+            match tested {
+                Some(t) if !test(t.trim()) => None,
+                _ => Some(command.to_string()),
+            }
+        })
+}
+
+/// A mailcap command for the file at `path`: `%s` becomes the path (no `%s`: the file is
+/// on stdin), `%t` the content type
+pub fn view_command(template: &str, content_type: &str, path: &Path) -> Result<Command> {
+    let template = template.replace("%t", content_type);
+    if !template.contains("%s") {
+        let mut cmd = process::shell(&template);
+        cmd.stdin(std::fs::File::open(path)?);
+        return Ok(cmd);
+    }
+    // The path goes in as `$1`, so no quoting can go wrong
+    #[cfg(unix)]
+    let cmd = {
+        let mut cmd = process::shell(&template.replace("%s", "\"$1\""));
+        cmd.arg("sh").arg(path);
+        cmd
+    };
+    #[cfg(not(unix))]
+    let cmd = process::shell(&template.replace("%s", &format!("\"{}\"", path.display())));
+    Ok(cmd)
+}
+
+fn mime_of(entry: &str) -> String {
+    entry.split(';').next().unwrap_or("").trim().to_lowercase()
+}
+
+/// Replace mu's managed block, leaving user entries untouched
+///
+/// Types the user already defines outside the block are skipped so their
+/// entries keep precedence. Returns the merged file and the skipped types.
+fn merge(existing: &str, ours: &[String]) -> (String, Vec<String>) {
+    let mut user = Vec::new();
+    let mut in_block = false;
+    for line in existing.lines() {
+        match line.trim() {
+            BEGIN => in_block = true,
+            END => in_block = false,
+            _ if !in_block => user.push(line),
+            _ => {}
+        }
+    }
+
+    let user_types: Vec<String> = user
+        .iter()
+        .filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .map(|l| mime_of(l))
+        .collect();
+
+    let mut skipped = Vec::new();
+    let mut block = vec![BEGIN.to_string()];
+    for entry in ours {
+        let mime = mime_of(entry);
+        if user_types.contains(&mime) {
+            if !skipped.contains(&mime) {
+                skipped.push(mime);
+            }
+        } else {
+            block.push(entry.clone());
+        }
+    }
+    block.push(END.to_string());
+
+    while user.last().is_some_and(|l| l.trim().is_empty()) {
+        user.pop();
+    }
+    let mut out: Vec<String> = user.iter().map(|l| l.to_string()).collect();
+    if !out.is_empty() {
+        out.push(String::new());
+    }
+    out.extend(block);
+    (out.join("\n") + "\n", skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_terminal_images() {
+        let cfg =
+            Config::parse("[mailcap]\nimages = \"terminal\"\nimage_command = \"timg\"").unwrap();
+        let e = entries(&cfg);
+        assert!(e.contains(&"image/*; timg %s; needsterminal".to_string()));
+        assert!(e[0].starts_with("text/html; mu render"));
+    }
+
+    #[test]
+    fn test_merge_keeps_user_entries() {
+        let existing = "# mine\ntext/html; lynx -dump %s; copiousoutput\n";
+        let ours = vec![
+            "text/html; mu render -i %s; copiousoutput".to_string(),
+            "image/*; open %s".to_string(),
+        ];
+        let (merged, skipped) = merge(existing, &ours);
+        assert_eq!(skipped, vec!["text/html"]);
+        assert!(merged.contains("lynx -dump"));
+        assert!(!merged.contains("mu render"));
+        assert!(merged.contains(&format!("{}\nimage/*; open %s\n{}", BEGIN, END)));
+    }
+
+    #[test]
+    fn test_find_viewer() {
+        let mailcap = "# viewers\ntext/html; mu render %s; copiousoutput\n\
+            application/pdf; zathura %s; test=test -n \"$DISPLAY\"\n\
+            application/pdf; open \\\n %s\nimage/*; feh %s\n";
+        let with_display = |_: &str| true;
+        let without = |_: &str| false;
+        assert_eq!(
+            find_viewer(mailcap, "application/pdf", with_display).as_deref(),
+            Some("zathura %s")
+        );
+        assert_eq!(
+            find_viewer(mailcap, "application/pdf", without).as_deref(),
+            Some("open  %s")
+        );
+        assert_eq!(
+            find_viewer(mailcap, "image/png", without).as_deref(),
+            Some("feh %s")
+        );
+        assert_eq!(find_viewer(mailcap, "text/html", with_display), None);
+    }
+
+    #[test]
+    fn test_merge_replaces_old_block() {
+        let ours = vec!["image/*; open %s".to_string()];
+        let (first, _) = merge("", &ours);
+        let (second, _) = merge(&first, &ours);
+        assert_eq!(first, second);
+        assert_eq!(second.matches(BEGIN).count(), 1);
+    }
+}
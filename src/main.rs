@@ -2,110 +2,897 @@
 //!
 //! Handles stdin/stdout/files for mutt integration.
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::io::{self, Read, Write};
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 use std::path::PathBuf;
+use std::process::ExitCode;
 
+// Core modules live in mu-core; re-exported so commands keep using `crate::mime` etc.
+#[cfg(feature = "jmap")]
+use mu_core::jmap;
+use mu_core::{
+    bidi, color, config, decrypt, error, hook, http, i18n, ics, maildir, mbox, mime, notmuch, page,
+    process, remote, render, renderer, runner, sanitize, schema, spoof, stream, theme, tracker,
+    vcard, width,
+};
+
+/// Stand-ins when mu is built without the `jmap` feature
+#[cfg(not(feature = "jmap"))]
+mod jmap {
+    use crate::config::Jmap;
+    use anyhow::{Result, bail};
+
+    const MISSING: &str = "mu was built without the `jmap` feature";
+
+    pub struct Fetched {
+        pub new: usize,
+        pub deleted: usize,
+        pub updated: usize,
+    }
+
+    pub fn fetch(_: &Jmap) -> Result<Fetched> {
+        bail!(MISSING)
+    }
+
+    pub fn apply_tags() -> Result<()> {
+        bail!(MISSING)
+    }
+
+    pub fn listing(_: &Jmap, _: &str) -> Result<Vec<String>> {
+        bail!(MISSING)
+    }
+
+    pub fn message(_: &Jmap, _: &str) -> Result<(Vec<u8>, String)> {
+        bail!(MISSING)
+    }
+}
+
+/// Stand-in when mu is built without the `scripting` feature: a configured script is an error
+#[cfg(not(feature = "scripting"))]
+mod script {
+    use anyhow::{Result, bail};
+    use serde::Serialize;
+
+    const MISSING: &str = "mu was built without the `scripting` feature";
+
+    pub enum Script {}
+
+    pub fn load(cfg: &crate::config::Script) -> Result<Option<Script>> {
+        match cfg.path {
+            Some(_) => bail!(MISSING),
+            None => Ok(None),
+        }
+    }
+
+    impl Script {
+        pub fn defines(&self, _: &str) -> bool {
+            match *self {}
+        }
+
+        pub fn tag(&self, _: &impl Serialize) -> Result<Vec<String>> {
+            match *self {}
+        }
+
+        pub fn notify(&self, _: &impl Serialize) -> Result<bool> {
+            match *self {}
+        }
+
+        pub fn listing(&self, _: &impl Serialize) -> Result<String> {
+            match *self {}
+        }
+
+        pub fn preview(&self, _: &impl Serialize) -> Result<String> {
+            match *self {}
+        }
+    }
+}
+
+mod address;
+mod alias;
+mod avatar;
+mod cal;
+mod completions;
+mod config_cmd;
+mod contacts;
+#[cfg(feature = "index")]
+mod crypt;
+mod cryptcheck;
+mod db;
+mod diff;
+mod doctor;
+mod expire;
+mod filter;
+mod flag;
+mod forward;
 mod fzf;
-mod render;
+mod grep;
+mod headers;
+mod hooks;
+mod imap;
+mod import;
+#[cfg(feature = "index")]
+mod index;
+mod label_sync;
+mod links;
+mod lists;
+mod logging;
+mod mailcap;
+mod migrate;
+mod mute;
+mod muttrc;
+mod open;
+mod patch;
+mod phish;
+mod platform;
+mod print;
+mod push;
+mod quota;
+mod raw;
+mod receipts;
+mod reply;
+mod resend;
+mod rpc;
+#[cfg(feature = "scripting")]
+mod script;
+mod send;
+mod serve;
+mod sidebar;
+mod size;
+mod summarize;
+mod sweep;
 mod sync;
+mod tagrules;
+mod thread;
+mod throttle;
+mod todo;
+mod track;
+mod translate;
+#[cfg(feature = "tui")]
+mod tui;
+mod unsubscribe;
+mod watch;
+mod worker;
 
 #[derive(Parser)]
 #[command(name = "mu", version, about = "Swiss army knife for mutt/neomutt")]
 struct Cli {
+    /// How failures are reported on stderr (json: one object with kind and exit code)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    error_format: ErrorFormat,
+
+    /// When to color output (auto: terminals only, and not with NO_COLOR set)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    color: ColorChoice,
+
+    /// Log external commands, render stages and notmuch queries to stderr (-vv: more detail)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Append the log to a file instead (default $MU_LOG_FILE)
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Answer external commands (notmuch, mbsync, w3m, fzf…) from recordings instead of running them
+    #[arg(long, global = true, value_name = "FILE")]
+    simulate: Option<PathBuf>,
+
+    /// Run external commands and append their outputs to FILE, for --simulate
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "simulate")]
+    record: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+enum LinkChoice {
+    #[default]
+    Strip,
+    Keep,
+    Footnotes,
+    Shorten,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+enum FormatChoice {
+    #[default]
+    Ansi,
+    Markdown,
+    Plain,
+    Html,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+enum BidiChoice {
+    Isolate,
+    Reorder,
+    Off,
+}
+
+/// Arguments for `mu render`
+#[derive(clap::Args)]
+struct RenderArgs {
+    /// Input file (reads stdin if not provided)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+
+    /// Output file (writes stdout if not provided)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output: ansi (colored text), markdown (for glow), plain (no escape codes, for
+    /// scripts and indexing) or html (sanitized)
+    #[arg(long, value_enum, default_value_t, value_name = "FORMAT")]
+    format: FormatChoice,
+
+    /// Links: strip (drop targets and long URLs), keep, footnotes (`text[1]`, targets
+    /// listed at the end) or shorten (long URLs as `[host/…]`)
+    #[arg(long, value_enum, default_value_t, value_name = "MODE")]
+    links: LinkChoice,
+
+    /// End HTML with a line counting the tracking pixels and redirects removed
+    #[arg(long)]
+    tracker_report: bool,
+
+    /// Same as `--links strip` (kept for existing mailcap entries)
+    #[arg(long, hide = true)]
+    strip_urls: bool,
+
+    /// MIME type of the input (text/html, text/plain, text/calendar, text/vcard,
+    /// message/rfc822); sniffed if not given
+    #[arg(long, value_name = "MIME")]
+    content_type: Option<String>,
+
+    /// Drop signatures (`-- ` blocks and closing contact details) instead of dimming them
+    #[arg(long)]
+    strip_signatures: bool,
+
+    /// Leave quoted replies as they are (folded to a `▸ quoted message` line by default)
+    #[arg(long)]
+    show_quotes: bool,
+
+    /// Color the input as a patch (hunks, additions, deletions), diff or not; mail with a
+    /// diff gets this anyway
+    #[arg(long, conflicts_with = "content_type")]
+    diff: bool,
+
+    /// Right-to-left text in tables, headers and cards: isolate (for terminals that do
+    /// bidi), reorder (for those that don't) or off (default: `[render] bidi`, else isolate)
+    #[arg(long, value_enum, value_name = "MODE")]
+    bidi: Option<BidiChoice>,
+
+    /// Keep only the main text of HTML and its headline (for newsletters that are mostly
+    /// layout tables, buttons and footers)
+    #[arg(long)]
+    reader: bool,
+
+    /// Leave legal disclaimers ("this email is confidential…") and `[render] footers`
+    /// boilerplate in (dropped by default)
+    #[arg(long)]
+    keep_footers: bool,
+
+    /// End HTML with the addresses its `mailto:` links point to ("Contacts mentioned")
+    #[arg(long)]
+    contacts: bool,
+
+    /// Start a raw message with all its headers, decoded and wrapped (Received chain folded)
+    #[arg(long)]
+    headers: bool,
+
+    /// Write a standalone page (sanitized, inline images embedded) and open it in the
+    /// browser; with --output, only write it there
+    #[arg(long, conflicts_with = "format")]
+    browser: bool,
+
+    /// Wrap at this width (default: $COLUMNS or the terminal's, else 120)
+    #[arg(long, value_name = "N")]
+    cols: Option<usize>,
+}
+
+/// Arguments for `mu fzf`
+#[derive(clap::Args)]
+struct FzfArgs {
+    /// Search query (default: all mail; completes notmuch saved searches)
+    #[arg(short, long, add = ArgValueCandidates::new(completions::searches))]
+    query: Option<String>,
+
+    /// Only mail in this account's folders
+    #[arg(short, long, add = ArgValueCandidates::new(completions::accounts))]
+    account: Option<String>,
+
+    /// Search backend
+    #[arg(long, value_enum, default_value_t)]
+    backend: fzf::Backend,
+
+    /// Also search the accounts' IMAP servers for mail not synced locally
+    #[arg(long)]
+    server_search: bool,
+
+    /// Run notmuch on this SSH host (also `$MU_REMOTE`)
+    #[arg(long, value_name = "HOST")]
+    remote: Option<String>,
+
+    /// Where the neomutt command goes: file (the command file), stdout or fifo:PATH
+    #[arg(long, default_value = "file", value_name = "TARGET")]
+    emit: fzf::Emit,
+}
+
+/// Arguments for `mu preview`
+#[derive(clap::Args)]
+struct PreviewArgs {
+    /// Thread ID (e.g., thread:0000000000000123)
+    #[arg(required_unless_present = "serve")]
+    thread_id: Option<String>,
+
+    /// Answer previews on this Unix socket until stdin closes (the worker `mu fzf` starts)
+    #[arg(long, value_name = "SOCKET", hide = true)]
+    serve: Option<PathBuf>,
+
+    /// Preview backend
+    #[arg(long, value_enum, default_value_t)]
+    backend: fzf::Backend,
+
+    /// Run notmuch on this SSH host (also `$MU_REMOTE`)
+    #[arg(long, value_name = "HOST")]
+    remote: Option<String>,
+
+    /// Wrap at this width (default: fzf's preview pane, $COLUMNS or the terminal's)
+    #[arg(long, value_name = "N")]
+    cols: Option<usize>,
+}
+
+/// Arguments for `mu sync`
+#[derive(clap::Args)]
+struct SyncArgs {
+    /// Quiet mode (no output, just notify)
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Quick mode (inbox only)
+    #[arg(long)]
+    quick: bool,
+
+    /// Fetch only, skip notmuch indexing and notifications
+    #[arg(long)]
+    no_index: bool,
+
+    /// Run mbsync and notmuch on this SSH host, notify here (also `$MU_REMOTE`)
+    #[arg(long, value_name = "HOST")]
+    remote: Option<String>,
+
+    /// Where to fetch mail from
+    #[arg(long, value_enum, default_value_t)]
+    backend: sync::Backend,
+
+    /// Sync only these mbsync channels (repeatable)
+    #[arg(short, long = "channel", value_name = "CHANNEL", add = ArgValueCandidates::new(completions::channels))]
+    channels: Vec<String>,
+
+    /// mbsync channels to run at once
+    #[arg(short, long, default_value = "4")]
+    jobs: usize,
+
+    /// Seconds before a hung mbsync channel is killed
+    #[arg(long, default_value = "600")]
+    timeout: u64,
+
+    /// Keep running, syncing every SECS seconds and right after the config or tag script
+    /// changes (a config that doesn't load pauses syncing until it's fixed)
+    #[arg(
+        long,
+        value_name = "SECS",
+        conflicts_with = "no_index",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    watch: Option<u64>,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Render HTML email to markdown (pipe to glow for colors)
-    Render {
-        /// Input file (reads stdin if not provided)
-        #[arg(short, long)]
-        input: Option<PathBuf>,
-
-        /// Output file (writes stdout if not provided)
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-
-        /// Strip URLs from output
-        #[arg(long, default_value_t = true)]
-        strip_urls: bool,
-    },
+    Render(RenderArgs),
 
     /// Fuzzy search mail with fzf + notmuch
-    Fzf {
-        /// Search query (default: all mail)
-        #[arg(short, long)]
-        query: Option<String>,
-    },
+    Fzf(FzfArgs),
 
     /// Preview a mail thread (for fzf preview window)
-    Preview {
-        /// Thread ID (e.g., thread:0000000000000123)
-        thread_id: String,
-    },
+    Preview(PreviewArgs),
 
     /// Sync mail (mbsync + notmuch) with notifications
-    Sync {
-        /// Quiet mode (no output, just notify)
-        #[arg(short, long)]
-        quiet: bool,
+    Sync(SyncArgs),
+
+    /// Inspect decoded headers, Received chain and spoofing hints
+    Headers(headers::Args),
+
+    /// Show a thread as a tree (authors, dates, subject changes, unread)
+    Thread(thread::Args),
 
-        /// Quick mode (inbox only)
-        #[arg(long)]
-        quick: bool,
-    },
+    /// Manage notmuch pre-new/post-new hooks
+    Hooks(hooks::Args),
+
+    /// Apply tag rules from the config via notmuch
+    Tagrules(tagrules::Args),
+
+    /// Generate neomutt config glue (macros, filters, mailcap lines)
+    Muttrc(muttrc::Args),
+
+    /// Open the List-Unsubscribe link of a message
+    Unsubscribe(unsubscribe::Args),
+
+    /// Address completion from notmuch (for query_command)
+    Address(address::Args),
+
+    /// Generate mailcap entries merged with the existing ~/.mailcap
+    Mailcap(mailcap::Args),
+
+    /// Per-folder unread/total counts (neomutt sidebar, tmux, lf)
+    Sidebar(sidebar::Args),
+
+    /// Contacts: vCard import/export, khard and CardDAV sync
+    Contacts(contacts::Args),
+
+    /// Extract calendar invitations into .ics files (and khal)
+    Cal(cal::Args),
+
+    /// Extract links (tracking redirects unwrapped), optionally open one
+    Links(links::Args),
+
+    /// Print a raw message or a single decoded MIME part
+    Raw(raw::Args),
+
+    /// Regex search in decoded bodies (HTML rendered), with context lines
+    Grep(grep::Args),
+
+    /// Compare two messages' headers and bodies (unified or side-by-side diff)
+    Diff(diff::Args),
+
+    /// Open an attachment with its mailcap viewer or the system handler
+    Open(open::Args),
+
+    /// Quoted plain-text reply body (renders HTML-only mail)
+    ReplyQuote(reply::Args),
+
+    /// Forward-ready body without trackers, images or quoted chains
+    ForwardClean(forward::Args),
+
+    /// Deliver a message from stdin into Maildir by config rules (MDA)
+    Filter(filter::Args),
+
+    /// Built-in full-text index for setups without notmuch
+    #[cfg(feature = "index")]
+    Index(index::Args),
+
+    /// Check tools, configs, the notmuch database and neomutt glue
+    Doctor(doctor::Args),
+
+    /// Check the config file for mistakes, or write a starter one
+    Config(config_cmd::Args),
+
+    /// Local web UI for viewing rendered messages and downloading parts
+    Serve(serve::Args),
+
+    /// Import an mbox file into a Maildir folder
+    Import(import::Args),
+
+    /// Convert a Gmail Takeout or Thunderbird export into Maildir + notmuch tags
+    Migrate(migrate::Args),
+
+    /// Refuse to send plaintext replies to encrypted threads (sendmail wrapper)
+    Cryptcheck(cryptcheck::Args),
+
+    /// Add senders to the mutt alias file and search it
+    Alias(alias::Args),
+
+    /// Archive or delete old newsletters and promotions from the inbox
+    Sweep(sweep::Args),
+
+    /// Largest messages, folders and attachments, with interactive cleanup
+    Size(size::Args),
+
+    /// Format a message for printing (paginated text or PDF)
+    Print(print::Args),
+
+    /// Summarize a thread with the configured external command
+    Summarize(summarize::Args),
+
+    /// Mirror Gmail label folders as notmuch tags and tag changes back into folders
+    LabelSync(label_sync::Args),
+
+    /// Send via msmtp (sendmail-compatible), then store and tag the sent copy
+    Send(send::Args),
+
+    /// Resend (bounce) a message to new recipients with Resent-* headers
+    Resend(resend::Args),
+
+    /// JSON-RPC server for editor integrations (stdio or Unix socket)
+    Server(rpc::Args),
+
+    /// Terminal mail browser (thread list, preview, tag actions)
+    #[cfg(feature = "tui")]
+    Tui(tui::Args),
+
+    /// Take a patch series from a thread and apply it with git am
+    Patch(patch::Args),
+
+    /// Mailing lists in the store: counts, archive links, unread picker, muting
+    Lists(lists::Args),
+
+    /// Apply per-folder retention rules (delete, archive-compress, keep)
+    Expire(expire::Args),
+
+    /// Disk usage per account and folder, growth and quota projections
+    Quota(quota::Args),
+
+    /// Resolve a sender's avatar (local, Gravatar, Libravatar, BIMI)
+    Avatar(avatar::Args),
+
+    /// Score a message for phishing signals and print a risk report
+    Phish(phish::Args),
+
+    /// Export flagged mail as todo.txt/org action items, mark them done
+    Todo(todo::Args),
+
+    /// Translate a message body through the configured backend (cached)
+    Translate(translate::Args),
+
+    /// List carrier tracking numbers found in mail, with tracking links
+    Track(track::Args),
+
+    /// Collect invoice/receipt PDFs from mail into a folder
+    Receipts(receipts::Args),
+
+    /// Opt-in index of decrypted PGP mail for `mu fzf --backend crypt`
+    #[cfg(feature = "index")]
+    Crypt(crypt::Args),
+
+    /// notmuch database maintenance: compact, dump/restore tags, verify against disk
+    Db(db::Args),
+
+    /// Mute a thread: new replies are archived and marked read on every sync
+    Mute(mute::Args),
+
+    /// Set or clear maildir flags (seen, flagged, replied…) by renaming files; tags follow
+    Flag(flag::Args),
+
+    /// Print a shell completion script (bash, zsh, fish)
+    Completions(completions::Args),
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
+    // Completion requests from the shell scripts (`COMPLETE=bash mu -- mu fzf -q ...`)
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::ENV)
+        .complete();
+
     let cli = Cli::parse();
+    color::set(match cli.color {
+        ColorChoice::Auto => color::Mode::Auto,
+        ColorChoice::Always => color::Mode::Always,
+        ColorChoice::Never => color::Mode::Never,
+    });
+    let format = cli.error_format;
+    if let Err(e) = setup(cli.verbose, cli.log_file, cli.simulate, cli.record) {
+        return report(&e, format);
+    }
+    match run(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => report(&e, format),
+    }
+}
 
-    match cli.command {
-        Commands::Render {
-            input,
-            output,
-            strip_urls,
-        } => {
-            let content = read_input(input.as_deref())?;
-            let rendered = render::render(&content, strip_urls)?;
-            write_output(output.as_deref(), &rendered)?;
-        }
-        Commands::Fzf { query } => {
-            fzf::search(query.as_deref())?;
-        }
-        Commands::Preview { thread_id } => {
-            fzf::preview(&thread_id)?;
-        }
-        Commands::Sync { quiet, quick } => {
-            sync::sync(quiet, quick)?;
+/// Logging, and who runs external commands
+fn setup(
+    verbosity: u8,
+    log_file: Option<PathBuf>,
+    simulate: Option<PathBuf>,
+    record: Option<PathBuf>,
+) -> Result<()> {
+    logging::init(verbosity, log_file)?;
+    if let Some(path) = simulate {
+        runner::set(Box::new(runner::Replay::load(&path)?));
+    } else if let Some(path) = record {
+        runner::set(Box::new(runner::Recorder::create(&path)?));
+    }
+    Ok(())
+}
+
+/// Print a failure and pick the exit code of its kind
+fn report(err: &anyhow::Error, format: ErrorFormat) -> ExitCode {
+    // Output piped into a pager or `head` that quit early: nothing left to tell anyone
+    if error::is_broken_pipe(err) {
+        return ExitCode::SUCCESS;
+    }
+    let kind = error::find(err);
+    let code = kind.map_or(error::EXIT_OTHER, error::Error::exit_code);
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {:?}", err),
+        ErrorFormat::Json => {
+            let mut json = kind
+                .and_then(|k| serde_json::to_value(k).ok())
+                .unwrap_or_else(|| serde_json::json!({ "kind": "other" }));
+            json["message"] = format!("{:#}", err).into();
+            json["exit_code"] = code.into();
+            eprintln!("{}", json);
         }
     }
+    ExitCode::from(code)
+}
 
-    Ok(())
+fn run(command: Commands) -> Result<()> {
+    match command {
+        Commands::Render(args) => render(args),
+        Commands::Fzf(args) => fzf(args),
+        Commands::Preview(args) => preview(args),
+        Commands::Sync(args) => sync(args),
+        command => dispatch(command),
+    }
+}
+
+/// `mu fzf`: a search limited to the account, if one is given
+fn fzf(args: FzfArgs) -> Result<()> {
+    remote::set(args.remote);
+    let query = match &args.account {
+        Some(account) => Some(fzf::account_query(args.query.as_deref(), account)?),
+        None => args.query,
+    };
+    fzf::search(
+        query.as_deref(),
+        args.backend,
+        args.server_search,
+        &args.emit,
+    )
+}
+
+/// `mu preview`: one thread, or a worker answering previews on a socket
+fn preview(args: PreviewArgs) -> Result<()> {
+    remote::set(args.remote);
+    renderer::set_cols(args.cols);
+    match (args.serve, args.thread_id) {
+        (Some(socket), _) => worker::serve(&socket, args.backend),
+        (None, Some(thread_id)) => fzf::preview(&thread_id, args.backend),
+        (None, None) => Ok(()),
+    }
+}
+
+/// `mu sync`: once, or on a timer with `--watch`
+fn sync(args: SyncArgs) -> Result<()> {
+    remote::set(args.remote);
+    let limits = sync::Limits {
+        jobs: args.jobs,
+        timeout: std::time::Duration::from_secs(args.timeout),
+    };
+    let sync = || {
+        let channels = &args.channels;
+        sync::sync(
+            args.quiet,
+            args.quick,
+            args.no_index,
+            args.backend,
+            channels,
+            limits,
+        )
+    };
+    match args.watch {
+        Some(secs) => watch::run(std::time::Duration::from_secs(secs), args.quiet, sync),
+        None => sync(),
+    }
+}
+
+/// `mu render`: the options from the flags, then a page for the browser or a stream
+fn render(args: RenderArgs) -> Result<()> {
+    renderer::set_cols(args.cols);
+    if let Some(choice) = args.bidi {
+        bidi::set(match choice {
+            BidiChoice::Isolate => bidi::Mode::Isolate,
+            BidiChoice::Reorder => bidi::Mode::Reorder,
+            BidiChoice::Off => bidi::Mode::Off,
+        });
+    }
+    let content_type = if args.diff {
+        Some(render::ContentType::Patch)
+    } else {
+        args.content_type
+            .as_deref()
+            .map(render::ContentType::from_mime)
+    };
+    let options = render_options(&args);
+    if args.browser {
+        let mut text = String::new();
+        open_input(args.input.as_deref())?
+            .read_to_string(&mut text)
+            .context("Failed to read input")?;
+        let content_type = content_type.unwrap_or_else(|| render::ContentType::sniff(&text));
+        return open::page(&text, content_type, &options, args.output.as_deref());
+    }
+    let colors = if args.output.is_some() {
+        color::files()
+    } else {
+        color::stdout()
+    };
+    stream::render(
+        open_input(args.input.as_deref())?,
+        open_output(args.output.as_deref())?,
+        content_type,
+        &options,
+        colors,
+    )
+}
+
+/// [`render::Options`] for the `mu render` flags; signature handling falls back to the config
+fn render_options(args: &RenderArgs) -> render::Options {
+    let signatures = if args.strip_signatures {
+        render::Signatures::Strip
+    } else {
+        // A display filter shouldn't fail every message over a broken config
+        config::Config::load()
+            .map(|cfg| cfg.render.signatures)
+            .inspect_err(|e| tracing::warn!(error = %format!("{:#}", e), "config not loaded"))
+            .unwrap_or_default()
+    };
+    render::Options {
+        format: match args.format {
+            FormatChoice::Ansi => render::Format::Ansi,
+            FormatChoice::Markdown => render::Format::Markdown,
+            FormatChoice::Plain => render::Format::Plain,
+            FormatChoice::Html => render::Format::Html,
+        },
+        links: match args.links {
+            _ if args.strip_urls => render::Links::Strip,
+            LinkChoice::Strip => render::Links::Strip,
+            LinkChoice::Keep => render::Links::Keep,
+            LinkChoice::Footnotes => render::Links::Footnotes,
+            LinkChoice::Shorten => render::Links::Shorten,
+        },
+        tracker_report: args.tracker_report,
+        signatures,
+        show_quotes: args.show_quotes,
+        reader: args.reader,
+        keep_footers: args.keep_footers,
+        contacts: args.contacts,
+        headers: args.headers,
+    }
+}
+
+/// The subcommands that only hand their arguments on: reading and handling mail here,
+/// setup, delivery and maintenance in [`maintain`]
+fn dispatch(command: Commands) -> Result<()> {
+    match command {
+        Commands::Headers(args) => headers::run(args),
+        Commands::Thread(args) => thread::run(args),
+        Commands::Unsubscribe(args) => unsubscribe::run(args),
+        Commands::Address(args) => address::run(args),
+        Commands::Contacts(args) => contacts::run(args),
+        Commands::Cal(args) => cal::run(args),
+        Commands::Links(args) => links::run(args),
+        Commands::Raw(args) => raw::run(args),
+        Commands::Grep(args) => grep::run(args),
+        Commands::Diff(args) => diff::run(args),
+        Commands::Open(args) => open::run(args),
+        Commands::ReplyQuote(args) => reply::run(args),
+        Commands::ForwardClean(args) => forward::run(args),
+        Commands::Alias(args) => alias::run(args),
+        Commands::Sweep(args) => sweep::run(args),
+        Commands::Size(args) => size::run(args),
+        Commands::Print(args) => print::run(args),
+        Commands::Summarize(args) => summarize::run(args),
+        Commands::Patch(args) => patch::run(args),
+        Commands::Lists(args) => lists::run(args),
+        Commands::Expire(args) => expire::run(args),
+        Commands::Avatar(args) => avatar::run(args),
+        Commands::Phish(args) => phish::run(args),
+        Commands::Todo(args) => todo::run(args),
+        Commands::Translate(args) => translate::run(args),
+        Commands::Track(args) => track::run(args),
+        Commands::Receipts(args) => receipts::run(args),
+        Commands::Mute(args) => mute::run(args),
+        Commands::Flag(args) => flag::run(args),
+        command => maintain(command),
+    }
+}
+
+/// Setup, delivery, indexing and database subcommands
+fn maintain(command: Commands) -> Result<()> {
+    match command {
+        Commands::Hooks(args) => hooks::run(args),
+        Commands::Tagrules(args) => tagrules::run(args),
+        Commands::Muttrc(args) => muttrc::run(args),
+        Commands::Mailcap(args) => mailcap::run(args),
+        Commands::Sidebar(args) => sidebar::run(args),
+        Commands::Filter(args) => filter::run(args),
+        #[cfg(feature = "index")]
+        Commands::Index(args) => index::run(args),
+        Commands::Doctor(args) => doctor::run(args),
+        Commands::Config(args) => config_cmd::run(args),
+        Commands::Serve(args) => serve::run(args),
+        Commands::Import(args) => import::run(args),
+        Commands::Migrate(args) => migrate::run(args),
+        Commands::Cryptcheck(args) => cryptcheck::run(args),
+        Commands::LabelSync(args) => label_sync::run(args),
+        Commands::Send(args) => send::run(args),
+        Commands::Resend(args) => resend::run(args),
+        Commands::Server(args) => rpc::run(args),
+        #[cfg(feature = "tui")]
+        Commands::Tui(args) => tui::run(args),
+        Commands::Quota(args) => quota::run(args),
+        #[cfg(feature = "index")]
+        Commands::Crypt(args) => crypt::run(args),
+        Commands::Db(args) => db::run(args),
+        Commands::Completions(args) => completions::run(args),
+        _ => unreachable!("handled in run or dispatch"),
+    }
 }
 
 /// Read from file or stdin
-fn read_input(path: Option<&std::path::Path>) -> Result<String> {
+fn open_input(path: Option<&std::path::Path>) -> Result<Box<dyn Read + Send>> {
     match path {
-        Some(p) => Ok(std::fs::read_to_string(p)?),
-        None => {
-            let mut buf = String::new();
-            io::stdin().read_to_string(&mut buf)?;
-            Ok(buf)
+        Some(p) => {
+            Ok(Box::new(File::open(p).with_context(|| {
+                format!("Failed to open {}", p.display())
+            })?))
         }
+        None => Ok(Box::new(io::stdin())),
     }
 }
 
-/// Write to file or stdout
-fn write_output(path: Option<&std::path::Path>, content: &str) -> Result<()> {
+/// Write to file or stdout (buffered; flushed by the writer's user)
+fn open_output(path: Option<&std::path::Path>) -> Result<Box<dyn Write>> {
     match path {
-        Some(p) => Ok(std::fs::write(p, content)?),
-        None => {
-            io::stdout().write_all(content.as_bytes())?;
-            Ok(())
-        }
+        Some(p) => Ok(Box::new(BufWriter::new(
+            File::create(p).with_context(|| format!("Failed to create {}", p.display()))?,
+        ))),
+        None => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(flags: &[&str]) -> render::Links {
+        let argv = ["mu", "render", "--strip-signatures"].iter().chain(flags);
+        let Commands::Render(args) = Cli::try_parse_from(argv).unwrap().command else {
+            unreachable!("parsed as mu render");
+        };
+        render_options(&args).links
+    }
+
+    #[test]
+    fn test_watch_needs_an_interval() {
+        let watch = |secs| {
+            Cli::try_parse_from(["mu", "sync", "--watch", secs]).map(|cli| match cli.command {
+                Commands::Sync(args) => args.watch,
+                _ => unreachable!("parsed as mu sync"),
+            })
+        };
+        assert_eq!(watch("300").unwrap(), Some(300));
+        assert!(watch("0").is_err());
+    }
+
+    #[test]
+    fn test_strip_urls_is_links_strip() {
+        assert_eq!(links(&["--strip-urls"]), render::Links::Strip);
+        assert_eq!(
+            links(&["--strip-urls", "--links", "keep"]),
+            render::Links::Strip
+        );
+        assert_eq!(links(&["--links", "keep"]), render::Links::Keep);
     }
 }
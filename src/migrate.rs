@@ -0,0 +1,359 @@
+//! Migration from Gmail Takeout and Thunderbird exports into Maildir
+
+use anyhow::{Context, Result};
+use mail_parser::MessageParser;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{self, Config};
+use crate::{color, import, maildir, mbox, notmuch, process};
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+enum Source {
+    /// Google Takeout mbox export (X-Gmail-Labels become notmuch tags)
+    GmailTakeout,
+    /// Thunderbird profile mail directory (Local Folders or `ImapMail/<server>`)
+    Thunderbird,
+}
+
+/// Arguments for `mu migrate`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Export format
+    #[arg(long, value_enum)]
+    from: Source,
+
+    /// Takeout .mbox file (or directory of them), or Thunderbird mail directory
+    path: PathBuf,
+
+    /// Target folder below the Maildir root (default: gmail or thunderbird)
+    #[arg(short, long)]
+    folder: Option<String>,
+
+    /// Maildir root (default: notmuch database.path, then `[index] maildir`)
+    #[arg(short, long)]
+    maildir: Option<String>,
+
+    /// Don't run `notmuch new`, apply labels or verify counts
+    #[arg(long)]
+    no_index: bool,
+}
+
+/// One source mbox and the folder it maps to
+struct Mailbox {
+    file: PathBuf,
+    folder: String,
+}
+
+/// Convert an export into Maildir, tag it and verify the counts
+pub fn run(args: Args) -> Result<()> {
+    let root = match &args.maildir {
+        Some(dir) => config::expand_tilde(dir),
+        None => import::maildir_root(&Config::load()?),
+    };
+    let prefix = args.folder.clone().unwrap_or_else(|| match args.from {
+        Source::GmailTakeout => "gmail".to_string(),
+        Source::Thunderbird => "thunderbird".to_string(),
+    });
+
+    let mailboxes = match args.from {
+        Source::GmailTakeout => takeout_mailboxes(&args.path)?,
+        Source::Thunderbird => thunderbird_mailboxes(&args.path, "")?,
+    };
+
+    let (delivered, tag_lines) = convert(args.from, &mailboxes, &root.join(&prefix))?;
+    println!(
+        "Delivered {} messages from {} mailbox(es)",
+        delivered,
+        mailboxes.len()
+    );
+
+    if !args.no_index {
+        import::index()?;
+        if !tag_lines.is_empty() {
+            notmuch::tag_batch(&tag_lines)?;
+        }
+        verify(&prefix, delivered)?;
+    }
+    Ok(())
+}
+
+/// Deliver every mailbox below `target`; returns the count and notmuch tag lines
+fn convert(from: Source, mailboxes: &[Mailbox], target: &Path) -> Result<(usize, Vec<String>)> {
+    let mut delivered = 0;
+    let mut tag_lines = Vec::new();
+    for mailbox in mailboxes {
+        let data = std::fs::read(&mailbox.file)
+            .with_context(|| format!("Failed to read {}", mailbox.file.display()))?;
+        let dest = target.join(&mailbox.folder);
+        for raw in mbox::split(&data) {
+            let flags = match from {
+                Source::GmailTakeout => {
+                    let labels = gmail_labels(&raw);
+                    tag_lines.extend(tag_line(&raw, &labels));
+                    gmail_flags(&labels)
+                }
+                Source::Thunderbird => match mozilla_flags(&raw) {
+                    Some(flags) => flags,
+                    // Deleted in Thunderbird but not yet compacted away
+                    None => continue,
+                },
+            };
+            maildir::deliver(&dest, &raw, Some(&flags))?;
+            delivered += 1;
+        }
+        eprintln!(
+            "{} {} → {}",
+            color::ok(),
+            mailbox.file.display(),
+            dest.display()
+        );
+    }
+    Ok((delivered, tag_lines))
+}
+
+/// A Takeout .mbox file, or every .mbox in a Takeout directory
+fn takeout_mailboxes(path: &Path) -> Result<Vec<Mailbox>> {
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "mbox"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let stem = file
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            // "All mail Including Spam and Trash" is the whole account; labels carry the structure
+            let folder = if stem.starts_with("All mail") {
+                "archive".to_string()
+            } else {
+                stem
+            };
+            Mailbox { file, folder }
+        })
+        .collect())
+}
+
+/// Thunderbird keeps one mbox file per folder, with subfolders in `<name>.sbd/`
+fn thunderbird_mailboxes(dir: &Path, parent: &str) -> Result<Vec<Mailbox>> {
+    let mut mailboxes = Vec::new();
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let folder = if parent.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", parent, name)
+        };
+        if path.is_dir() {
+            if let Some(sub) = folder.strip_suffix(".sbd") {
+                mailboxes.extend(thunderbird_mailboxes(&path, sub)?);
+            }
+        } else if path.extension().is_none() && starts_with_from(&path) {
+            mailboxes.push(Mailbox { file: path, folder });
+        }
+    }
+    Ok(mailboxes)
+}
+
+fn starts_with_from(path: &Path) -> bool {
+    use std::io::Read;
+    let mut head = [0u8; 5];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut head))
+        .is_ok()
+        && &head == b"From "
+}
+
+/// Labels from `X-Gmail-Labels` (comma-separated, optionally quoted)
+fn gmail_labels(raw: &[u8]) -> Vec<String> {
+    let Some(headers) = MessageParser::default().parse_headers(raw) else {
+        return Vec::new();
+    };
+    let value = headers.header_raw("X-Gmail-Labels").unwrap_or("");
+
+    let mut labels = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => labels.push(std::mem::take(&mut current)),
+            '\r' | '\n' => {}
+            c => current.push(c),
+        }
+    }
+    labels.push(current);
+    labels
+        .into_iter()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Gmail label → notmuch tag (None for labels that only affect flags)
+fn label_tag(label: &str) -> Option<String> {
+    let tag = match label {
+        "Opened" | "Unread" => return None,
+        "Inbox" => "inbox",
+        "Sent" => "sent",
+        "Starred" => "flagged",
+        "Drafts" | "Draft" => "draft",
+        "Spam" => "spam",
+        "Trash" => "deleted",
+        "Archived" => "archive",
+        other => {
+            let other = other.strip_prefix("Category ").unwrap_or(other);
+            return Some(other.to_lowercase().replace(' ', "-"));
+        }
+    };
+    Some(tag.to_string())
+}
+
+fn gmail_flags(labels: &[String]) -> String {
+    let has = |l: &str| labels.iter().any(|x| x == l);
+    let mut flags = String::new();
+    if !has("Unread") {
+        flags.push('S');
+    }
+    if has("Starred") {
+        flags.push('F');
+    }
+    if has("Drafts") || has("Draft") {
+        flags.push('D');
+    }
+    flags
+}
+
+/// `notmuch tag --batch` line replacing notmuch's new-mail tags with the Gmail labels
+fn tag_line(raw: &[u8], labels: &[String]) -> Option<String> {
+    let headers = MessageParser::default().parse_headers(raw)?;
+    let id = headers.message_id()?;
+    let mut tags = vec!["-inbox".to_string(), "-unread".to_string()];
+    if labels.iter().any(|l| l == "Unread") {
+        tags.push("+unread".to_string());
+    }
+    tags.extend(
+        labels
+            .iter()
+            .filter_map(|l| label_tag(l))
+            .map(|t| format!("+{}", t)),
+    );
+    Some(format!("{} -- id:{}", tags.join(" "), id))
+}
+
+/// Maildir flags from `X-Mozilla-Status`; None if the message is marked expunged
+fn mozilla_flags(raw: &[u8]) -> Option<String> {
+    let headers = MessageParser::default().parse_headers(raw)?;
+    let Some(status) = headers.header_raw("X-Mozilla-Status") else {
+        return Some(mbox::flags(raw));
+    };
+    let bits = u32::from_str_radix(status.trim(), 16).unwrap_or(0);
+    if bits & 0x0008 != 0 {
+        return None;
+    }
+    let mut flags = String::new();
+    for (bit, flag) in [(0x0001, 'S'), (0x0002, 'R'), (0x0004, 'F')] {
+        if bits & bit != 0 {
+            flags.push(flag);
+        }
+    }
+    Some(flags)
+}
+
+/// Compare what notmuch indexed under the target folder with what was delivered
+fn verify(prefix: &str, delivered: usize) -> Result<()> {
+    let output =
+        process::run(Command::new("notmuch").args(["count", &format!("path:\"{}/**\"", prefix)]))
+            .context("Failed to run notmuch count")?;
+    let indexed: usize = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .unwrap_or(0);
+    if indexed == delivered {
+        eprintln!("{} notmuch indexed all {} messages", color::ok(), indexed);
+    } else {
+        eprintln!(
+            "{} notmuch indexed {} of {} messages (duplicates share one Message-ID)",
+            color::warn(),
+            indexed,
+            delivered
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GMAIL: &[u8] = b"Message-ID: <m1@mail.gmail.com>\nX-Gmail-Labels: Inbox,Starred,\"Work, Clients\",Category Updates,Unread\n\
+Subject: hi\n\nbody\n";
+
+    #[test]
+    fn test_gmail_labels_and_tags() {
+        let labels = gmail_labels(GMAIL);
+        assert_eq!(
+            labels,
+            vec![
+                "Inbox",
+                "Starred",
+                "Work, Clients",
+                "Category Updates",
+                "Unread"
+            ]
+        );
+        assert_eq!(gmail_flags(&labels), "F");
+        assert_eq!(
+            tag_line(GMAIL, &labels).unwrap(),
+            "-inbox -unread +unread +inbox +flagged +work,-clients +updates -- id:m1@mail.gmail.com"
+        );
+    }
+
+    #[test]
+    fn test_mozilla_flags() {
+        assert_eq!(
+            mozilla_flags(b"X-Mozilla-Status: 0005\n\nx").as_deref(),
+            Some("SF")
+        );
+        assert_eq!(mozilla_flags(b"X-Mozilla-Status: 0009\n\nx"), None);
+        assert_eq!(mozilla_flags(b"Status: RO\n\nx").as_deref(), Some("S"));
+    }
+
+    #[test]
+    fn test_thunderbird_mailboxes() {
+        let dir = std::env::temp_dir().join(format!("mu-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("Inbox.sbd")).unwrap();
+        let mbox = "From - Mon Feb  2 10:00:00 2026\nSubject: x\n\nhi\n";
+        std::fs::write(dir.join("Inbox"), mbox).unwrap();
+        std::fs::write(dir.join("Inbox.msf"), "// index").unwrap();
+        std::fs::write(dir.join("Inbox.sbd/Work"), mbox).unwrap();
+
+        let folders: Vec<String> = thunderbird_mailboxes(&dir, "")
+            .unwrap()
+            .into_iter()
+            .map(|m| m.folder)
+            .collect();
+        assert_eq!(folders, vec!["Inbox", "Inbox/Work"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
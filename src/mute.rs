@@ -0,0 +1,174 @@
+//! Muted threads: new replies are archived and marked read on every sync
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::{color, config, notmuch};
+
+/// Tag changes for mail in a muted thread
+const MUTE_TAGS: &str = "+muted -inbox -unread";
+
+/// Arguments for `mu mute`
+#[derive(clap::Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Args {
+    /// Thread to mute (`thread:ID`, a message-id, or any query naming one thread)
+    thread: Option<String>,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Show muted threads
+    List,
+    /// Stop muting a thread
+    Unmute { thread: String },
+}
+
+/// Mute, unmute or list threads
+pub fn run(args: Args) -> Result<()> {
+    let mut muted = load()?;
+    match (args.action, args.thread) {
+        (Some(Action::List), _) => {
+            for (thread, subject) in &muted {
+                println!("{}  {}", thread, subject);
+            }
+        }
+        (Some(Action::Unmute { thread }), _) => {
+            let thread = resolve(&thread)?;
+            if muted.remove(&thread).is_none() {
+                anyhow::bail!("{} is not muted", thread);
+            }
+            notmuch::tag_batch(&[format!("-muted -- {}", thread)])?;
+            save(&muted)?;
+            eprintln!("{} Unmuted {}", color::ok(), thread);
+        }
+        (None, Some(reference)) => {
+            let thread = resolve(&reference)?;
+            let subject = subject_of(&thread);
+            notmuch::tag_batch(&[format!("{} -- {}", MUTE_TAGS, thread)])?;
+            eprintln!("{} Muted {} ({})", color::ok(), thread, subject);
+            muted.insert(thread, subject);
+            save(&muted)?;
+        }
+        (None, None) => anyhow::bail!("Give a thread to mute, or `list`/`unmute`"),
+    }
+    Ok(())
+}
+
+/// Archive and mark read new mail in muted threads; returns the message count
+pub fn archive_muted() -> Result<usize> {
+    let muted = load()?;
+    let Some(query) = pending_query(muted.keys()) else {
+        return Ok(0);
+    };
+    let count = notmuch::search("messages", &query)?.len();
+    if count > 0 {
+        notmuch::tag_batch(&[format!("{} -- {}", MUTE_TAGS, query)])?;
+    }
+    Ok(count)
+}
+
+/// Unread or inbox mail in any of the threads
+fn pending_query<'a>(threads: impl Iterator<Item = &'a String>) -> Option<String> {
+    let threads: Vec<&str> = threads.map(String::as_str).collect();
+    if threads.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "({}) and (tag:inbox or tag:unread)",
+        threads.join(" or ")
+    ))
+}
+
+/// The single `thread:ID` a reference points at
+fn resolve(reference: &str) -> Result<String> {
+    if reference.starts_with("thread:") && !reference.contains(' ') {
+        return Ok(reference.to_string());
+    }
+    let threads = notmuch::search("threads", &notmuch::query_for(reference))?;
+    match threads.as_slice() {
+        [thread] => Ok(thread.clone()),
+        [] => anyhow::bail!("No thread matches {}", reference),
+        _ => anyhow::bail!("{} matches {} threads", reference, threads.len()),
+    }
+}
+
+fn subject_of(thread: &str) -> String {
+    notmuch::raw(thread)
+        .ok()
+        .and_then(|raw| {
+            mail_parser::MessageParser::default()
+                .parse_headers(&raw)?
+                .subject()
+                .map(String::from)
+        })
+        .unwrap_or_default()
+}
+
+fn state_path() -> PathBuf {
+    config::data_dir().join("muted.json")
+}
+
+/// Muted thread ids with their subjects
+fn load() -> Result<BTreeMap<String, String>> {
+    match std::fs::read_to_string(state_path()) {
+        Ok(text) => serde_json::from_str(&text).context("Invalid mute state"),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+fn save(muted: &BTreeMap<String, String>) -> Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(muted)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_pending_query() {
+        let none: Vec<String> = Vec::new();
+        assert_eq!(pending_query(none.iter()), None);
+        let threads = ["thread:0001".to_string(), "thread:0002".to_string()];
+        assert_eq!(
+            pending_query(threads.iter()).unwrap(),
+            "(thread:0001 or thread:0002) and (tag:inbox or tag:unread)"
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let threads = |query: &str, stdout: &str| {
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["search", "--output=threads", "--", query],
+                "stdout": stdout,
+            })
+            .to_string()
+        };
+        let replay = [
+            threads("id:a@x", "thread:0001\n"),
+            threads("from:bob to:me", "thread:0001\nthread:0002\n"),
+            threads("id:none@x", ""),
+        ]
+        .join("\n");
+        runner::scoped(Replay::parse(&replay).unwrap(), || {
+            // A thread id is taken as it is, without asking notmuch
+            assert_eq!(resolve("thread:0009").unwrap(), "thread:0009");
+            assert_eq!(resolve("<a@x>").unwrap(), "thread:0001");
+            let err = resolve("from:bob to:me").unwrap_err();
+            assert_eq!(err.to_string(), "from:bob to:me matches 2 threads");
+            assert!(resolve("none@x").is_err());
+        });
+    }
+}
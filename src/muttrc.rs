@@ -0,0 +1,200 @@
+//! neomutt config generation (macros, filters, mailcap glue)
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::config::{Config, Muttrc};
+use crate::fzf::{Emit, cmd_file};
+
+/// Arguments for `mu muttrc`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Print the muttrc lines wiring mu into neomutt
+    Generate {
+        /// Write to file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Generate neomutt glue
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    match args.action {
+        Action::Generate { output } => {
+            let text = generate(&cfg.muttrc)?;
+            match output {
+                Some(path) => std::fs::write(path, text)?,
+                None => print!("{}", text),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The search macro's keys, for the way `[muttrc] search_emit` hands the command over
+fn search_macro(m: &Muttrc) -> Result<String> {
+    let mu = &m.bin;
+    let emit: Emit = m.search_emit.parse().map_err(anyhow::Error::msg)?;
+    Ok(match emit {
+        Emit::File => format!(
+            "<shell-escape>{} fzf<enter><enter-command>source {}<enter>",
+            mu,
+            cmd_file().display()
+        ),
+        Emit::Stdout => format!("<enter-command>source '{} fzf --emit stdout |'<enter>", mu),
+        Emit::Fifo(path) => format!(
+            "<shell-escape>{} fzf --emit fifo:{}<enter><enter-command>source {}<enter>",
+            mu,
+            path.display(),
+            path.display()
+        ),
+    })
+}
+
+/// The whole muttrc: a header, then one commented block per feature
+fn generate(m: &Muttrc) -> Result<String> {
+    let mut blocks = vec![
+        vec![
+            "# neomutt glue for mu - generated by `mu muttrc generate`".to_string(),
+            "# Source it from your neomuttrc: source ~/.config/neomutt/mu.muttrc".to_string(),
+        ],
+        macros(m)?,
+        aliases(m),
+    ];
+    if m.display_filter {
+        blocks.push(display_filter(m));
+    }
+    blocks.push(mailcap(m));
+    let lines: Vec<String> = blocks.join(&String::new());
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Search, sync and unsubscribe macros
+fn macros(m: &Muttrc) -> Result<Vec<String>> {
+    let mu = &m.bin;
+    Ok(vec![
+        "# Fuzzy search (fzf + notmuch)".to_string(),
+        format!(
+            "macro index {} \"{}\" \"mu: fzf search\"",
+            m.search_key,
+            search_macro(m)?
+        ),
+        String::new(),
+        "# Sync (mbsync + notmuch)".to_string(),
+        format!(
+            "macro index {} \"<shell-escape>{} sync<enter>\" \"mu: sync all mail\"",
+            m.sync_key, mu
+        ),
+        format!(
+            "macro index {} \"<shell-escape>{} sync --quick<enter>\" \"mu: quick sync\"",
+            m.quick_sync_key, mu
+        ),
+        String::new(),
+        "# Unsubscribe via List-Unsubscribe".to_string(),
+        format!(
+            "macro index,pager {} \"<pipe-message>{} unsubscribe<enter>\" \"mu: unsubscribe\"",
+            m.unsubscribe_key, mu
+        ),
+    ])
+}
+
+/// The alias file and its macro, and address completion
+fn aliases(m: &Muttrc) -> Vec<String> {
+    let mu = &m.bin;
+    vec![
+        "# Aliases (sender → alias file, reloaded right away)".to_string(),
+        format!("set alias_file = \"{}\"", m.alias_file),
+        format!("source {}", m.alias_file),
+        format!(
+            "macro index,pager {} \"<pipe-message>{} alias add<enter><enter-command>source {}<enter>\" \"mu: add sender to aliases\"",
+            m.alias_key, mu, m.alias_file
+        ),
+        String::new(),
+        "# Address completion".to_string(),
+        format!("set query_command = \"{} address '%s'\"", mu),
+    ]
+}
+
+/// `mu render` as the display filter
+fn display_filter(m: &Muttrc) -> Vec<String> {
+    vec![
+        "# Clean up and colorize message bodies".to_string(),
+        // neomutt pipes the message through the filter, so colors are asked for explicitly
+        format!("set display_filter = \"{} --color always render\"", m.bin),
+        "set allow_ansi = yes".to_string(),
+    ]
+}
+
+/// HTML through `mu render` by way of mailcap
+fn mailcap(m: &Muttrc) -> Vec<String> {
+    vec![
+        "# HTML rendering via mailcap".to_string(),
+        "auto_view text/html".to_string(),
+        "alternative_order text/plain text/html".to_string(),
+        "# ~/.mailcap:".to_string(),
+        format!(
+            "#   text/html; {} render --content-type %t -i %s; copiousoutput",
+            m.bin
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_defaults() {
+        let text = generate(&Muttrc::default()).unwrap();
+        assert!(text.contains("macro index <C-f> \"<shell-escape>mu fzf<enter>"));
+        assert!(text.contains(&*cmd_file().to_string_lossy()));
+        assert!(text.contains("set display_filter = \"mu --color always render\""));
+        assert!(text.contains("set query_command = \"mu address '%s'\""));
+        assert!(text.contains("macro index,pager a \"<pipe-message>mu alias add<enter>"));
+    }
+
+    #[test]
+    fn test_generate_parameterized() {
+        let cfg = Config::parse(
+            r#"
+[muttrc]
+bin = "/opt/bin/mu"
+search_key = "\\Cs"
+display_filter = false
+"#,
+        )
+        .unwrap();
+        let text = generate(&cfg.muttrc).unwrap();
+        assert!(text.contains("macro index \\Cs \"<shell-escape>/opt/bin/mu fzf"));
+        assert!(!text.contains("display_filter"));
+    }
+
+    #[test]
+    fn test_generate_search_emit() {
+        let mut m = Muttrc {
+            search_emit: "fifo:/run/user/1000/mu-cmd".to_string(),
+            ..Muttrc::default()
+        };
+        assert!(generate(&m).unwrap().contains(
+            "<shell-escape>mu fzf --emit fifo:/run/user/1000/mu-cmd<enter><enter-command>source /run/user/1000/mu-cmd<enter>"
+        ));
+        m.search_emit = "stdout".to_string();
+        assert!(
+            generate(&m)
+                .unwrap()
+                .contains("\"<enter-command>source 'mu fzf --emit stdout |'<enter>\"")
+        );
+        m.search_emit = "socket".to_string();
+        assert!(generate(&m).is_err());
+    }
+}
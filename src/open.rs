@@ -0,0 +1,187 @@
+//! Open an attachment with its mailcap viewer or the system handler, without mutt
+//!
+//! The part is written to a fresh owner-only `mu-open-*` directory under `$XDG_RUNTIME_DIR`
+//! (else the temp directory) and removed once the viewer exits. Files handed to
+//! `xdg-open`/`open`, which return before the application reads them, stay until a later
+//! `mu open` sweeps copies older than an hour. `mu render --browser` leaves its pages there
+//! the same way.
+
+use anyhow::{Context, Result};
+use mail_parser::{MessagePart, MimeHeaders};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::render::{self, ContentType};
+use crate::{color, mailcap, mime, notmuch, page, platform, process, raw};
+
+/// Age at which a copy left for a detached viewer is removed
+const STALE: Duration = Duration::from_secs(3600);
+
+/// Name prefix of the directories copies are written to
+const PREFIX: &str = "mu-open-";
+
+/// Arguments for `mu open`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query
+    message: String,
+
+    /// Part to open, numbered as in `mu raw --list` (lists the parts when left out)
+    #[arg(short, long, value_name = "N")]
+    attachment: Option<usize>,
+}
+
+/// Open a part of a message, or list the parts
+pub fn run(args: Args) -> Result<()> {
+    let raw = notmuch::raw(&notmuch::query_for(&args.message))?;
+    let message = mime::parse(&raw)?;
+    let Some(n) = args.attachment else {
+        print!("{}", raw::list_parts(&message));
+        return Ok(());
+    };
+    let part = message
+        .parts
+        .get(n)
+        .with_context(|| format!("No part {} (message has {})", n, message.parts.len()))?;
+
+    let path = temp_copy(&file_name(part, n), &raw::decoded(part))?;
+    let content_type = mime::content_type(part);
+    let detached = view(&path, &content_type)?;
+    if !detached && let Some(dir) = path.parent() {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    Ok(())
+}
+
+/// Rendered mail as a standalone page (see [`page::page`]) written to `output`, else opened
+/// in the browser
+pub fn page(
+    text: &str,
+    content_type: ContentType,
+    options: &render::Options,
+    output: Option<&Path>,
+) -> Result<()> {
+    let html = page::page(text, content_type, options)?;
+    if let Some(path) = output {
+        return std::fs::write(path, html)
+            .with_context(|| format!("Failed to write {}", path.display()));
+    }
+    let path = temp_copy("message.html", html.as_bytes())?;
+    eprintln!("{} {} {}", color::ok(), platform::opener(), path.display());
+    platform::open(&path.to_string_lossy())
+}
+
+/// Where copies go: the user's runtime directory, else the temp directory
+fn base() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// `contents` written to `name` in a newly created directory readable by the user only,
+/// after sweeping stale copies
+fn temp_copy(name: &str, contents: &[u8]) -> Result<PathBuf> {
+    let base = base();
+    sweep(&base);
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(PREFIX);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o700));
+    }
+    let dir = builder
+        .tempdir_in(&base)
+        .with_context(|| format!("Failed to create a directory in {}", base.display()))?
+        .keep();
+    let path = dir.join(name);
+    std::fs::write(&path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Run the mailcap viewer (or the system handler); whether it may still need the file
+fn view(path: &Path, content_type: &str) -> Result<bool> {
+    let opener = platform::opener().split(' ').next().unwrap_or_default();
+    let Some(template) = mailcap::viewer(content_type) else {
+        eprintln!("{} {} {}", color::ok(), platform::opener(), path.display());
+        platform::open(&path.to_string_lossy())?;
+        return Ok(true);
+    };
+    let status = process::status(&mut mailcap::view_command(&template, content_type, path)?)
+        .with_context(|| format!("Failed to run {}", template))?;
+    if !status.success() {
+        anyhow::bail!("{} failed", template);
+    }
+    Ok(template.split_whitespace().next() == Some(opener))
+}
+
+/// The attachment's own name (without any directories), else `part-N.<subtype>`
+fn file_name(part: &MessagePart, n: usize) -> String {
+    let own = part
+        .attachment_name()
+        .and_then(|name| Path::new(name).file_name())
+        .map(|name| name.to_string_lossy().replace('\\', "_"))
+        .filter(|name| !name.starts_with('.'));
+    own.unwrap_or_else(|| {
+        let content_type = mime::content_type(part);
+        let subtype = content_type.rsplit('/').next().unwrap_or("bin");
+        format!("part-{}.{}", n, subtype)
+    })
+}
+
+/// Remove this user's copies older than [`STALE`] (others' can't be removed)
+fn sweep(base: &Path) {
+    let Ok(entries) = std::fs::read_dir(base) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(PREFIX) {
+            continue;
+        }
+        let age = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        if age.is_some_and(|age| age > STALE) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        let raw = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: application/pdf; name=\"../../etc/invoice.pdf\"\r\n\r\n%PDF\r\n\
+--b\r\nContent-Type: image/png\r\n\r\nPNG\r\n--b\r\n\
+Content-Type: text/plain; name=\".bashrc\"\r\n\r\nx\r\n--b--\r\n";
+        let message = mime::parse(raw).unwrap();
+        assert_eq!(file_name(&message.parts[1], 1), "invoice.pdf");
+        assert_eq!(file_name(&message.parts[2], 2), "part-2.png");
+        assert_eq!(file_name(&message.parts[3], 3), "part-3.plain");
+    }
+
+    #[test]
+    fn test_temp_copy() {
+        let first = temp_copy("a.txt", b"x").unwrap();
+        let second = temp_copy("a.txt", b"y").unwrap();
+        let dir = first.parent().unwrap();
+        assert_ne!(dir, second.parent().unwrap());
+        assert_eq!(std::fs::read(&second).unwrap(), b"y");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        for path in [first, second] {
+            std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        }
+    }
+}
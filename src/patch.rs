@@ -0,0 +1,237 @@
+//! Patch series from mailing-list threads into `git am` (b4-lite)
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::{color, mime, notmuch, process};
+
+/// Arguments for `mu patch`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Thread ID (e.g., thread:0000000000000123)
+    thread_id: String,
+
+    /// Pipe the selected patches into `git am` instead of printing an mbox
+    #[arg(long)]
+    apply: bool,
+
+    /// Repository to apply to
+    #[arg(long, default_value = ".")]
+    repo: PathBuf,
+
+    /// Patches to take (`1-3,5`); prompts when omitted and stdin is a terminal
+    #[arg(long)]
+    pick: Option<String>,
+
+    /// Series version (default: the newest in the thread)
+    #[arg(long)]
+    version: Option<u32>,
+}
+
+/// One `[PATCH vV N/M]` message
+#[derive(Debug, PartialEq)]
+struct Patch {
+    version: u32,
+    number: u32,
+    total: u32,
+    title: String,
+    raw: Vec<u8>,
+}
+
+/// List the series in a thread and hand the chosen patches to git am
+pub fn run(args: Args) -> Result<()> {
+    let mut patches = Vec::new();
+    for file in notmuch::search("files", &args.thread_id)? {
+        let raw = std::fs::read(&file).with_context(|| format!("Failed to read {}", file))?;
+        let subject = mime::parse(&raw)?.subject().unwrap_or("").to_string();
+        if let Some(patch) = parse_subject(&subject, raw) {
+            patches.push(patch);
+        }
+    }
+    let series = series(patches, args.version)?;
+
+    for p in &series {
+        eprintln!("  {:>2}/{} {}", p.number, p.total, p.title);
+    }
+    let pick = match args.pick {
+        Some(pick) => pick,
+        None if io::stdin().is_terminal() => prompt()?,
+        None => String::new(),
+    };
+    let chosen: Vec<&Patch> = series.iter().filter(|p| picked(&pick, p.number)).collect();
+    if chosen.is_empty() {
+        anyhow::bail!("No patches selected");
+    }
+
+    let mbox = mbox(&chosen);
+    if !args.apply {
+        io::stdout().write_all(&mbox)?;
+        return Ok(());
+    }
+    git_am(&args.repo, &mbox)?;
+    eprintln!("{} applied {} patch(es)", color::ok(), chosen.len());
+    Ok(())
+}
+
+/// `[PATCH v2 3/5] title` (also RFC/tree prefixes); replies and cover letters are not patches
+fn parse_subject(subject: &str, raw: Vec<u8>) -> Option<Patch> {
+    let re = Regex::new(r"^\[[^\]]*PATCH(?:\s+v(\d+))?(?:[^\]]*?\s(\d+)/(\d+))?[^\]]*\]\s*(.*)$")
+        .ok()?;
+    let c = re.captures(subject.trim())?;
+    let number = c.get(2).map_or(Ok(1), |m| m.as_str().parse()).ok()?;
+    if number == 0 {
+        return None;
+    }
+    Some(Patch {
+        version: c.get(1).map_or(Ok(1), |m| m.as_str().parse()).ok()?,
+        number,
+        total: c.get(3).map_or(Ok(1), |m| m.as_str().parse()).ok()?,
+        title: c[4].to_string(),
+        raw,
+    })
+}
+
+/// Patches of one version in order (duplicates, e.g. a resend, keep the first copy)
+fn series(mut patches: Vec<Patch>, version: Option<u32>) -> Result<Vec<Patch>> {
+    let newest = patches
+        .iter()
+        .map(|p| p.version)
+        .max()
+        .context("No patches in this thread")?;
+    let version = version.unwrap_or(newest);
+    patches.retain(|p| p.version == version);
+    if patches.is_empty() {
+        anyhow::bail!("No v{} patches in this thread", version);
+    }
+    patches.sort_by_key(|p| p.number);
+    patches.dedup_by_key(|p| p.number);
+    let total = patches[0].total;
+    if patches.len() < total as usize {
+        eprintln!(
+            "{} only {} of {} patches found",
+            color::warn(),
+            patches.len(),
+            total
+        );
+    }
+    Ok(patches)
+}
+
+fn prompt() -> Result<String> {
+    eprint!("Take which? [all] ");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// `1-3,5` selection (empty or `all` takes everything)
+fn picked(pick: &str, number: u32) -> bool {
+    let pick = pick.trim();
+    if pick.is_empty() || pick == "all" {
+        return true;
+    }
+    pick.split(',').any(|range| match range.trim().split_once('-') {
+        Some((a, b)) => matches!((a.parse::<u32>(), b.parse::<u32>()), (Ok(a), Ok(b)) if (a..=b).contains(&number)),
+        None => range.trim().parse() == Ok(number),
+    })
+}
+
+/// mboxrd for `git am --patch-format=mboxrd` (LF line endings, `>From ` escaping)
+fn mbox(patches: &[&Patch]) -> Vec<u8> {
+    let from = Regex::new(r"(?m)^(>*From )").ok();
+    let mut out = Vec::new();
+    for p in patches {
+        let text = String::from_utf8_lossy(&p.raw).replace("\r\n", "\n");
+        let text = match &from {
+            Some(re) => re.replace_all(&text, ">$1").to_string(),
+            None => text,
+        };
+        out.extend_from_slice(b"From mu Thu Jan  1 00:00:00 1970\n");
+        out.extend_from_slice(text.as_bytes());
+        if !text.ends_with('\n') {
+            out.push(b'\n');
+        }
+        out.push(b'\n');
+    }
+    out
+}
+
+fn git_am(repo: &std::path::Path, mbox: &[u8]) -> Result<()> {
+    let output = process::run_with_input(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["am", "-3", "--patch-format=mboxrd"]),
+        mbox,
+    )
+    .context("Failed to run git am")?;
+    // What git applied, or where it stopped
+    io::stderr().write_all(&output.stdout)?;
+    io::stderr().write_all(&output.stderr)?;
+    if !output.status.success() {
+        anyhow::bail!("git am failed (resolve, then `git am --continue` or `--abort`)");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(subject: &str) -> Option<Patch> {
+        parse_subject(subject, Vec::new())
+    }
+
+    #[test]
+    fn test_parse_subject() {
+        let p = patch("[PATCH v2 3/5] net: fix leak").unwrap();
+        assert_eq!((p.version, p.number, p.total), (2, 3, 5));
+        assert_eq!(p.title, "net: fix leak");
+        let p = patch("[RFC PATCH net-next 1/2] add thing").unwrap();
+        assert_eq!((p.version, p.number, p.total), (1, 1, 2));
+        assert_eq!(patch("[PATCH] single").unwrap().total, 1);
+        assert!(patch("[PATCH v2 0/5] cover").is_none());
+        assert!(patch("Re: [PATCH 1/2] add thing").is_none());
+    }
+
+    #[test]
+    fn test_series_newest_version() {
+        let subjects = [
+            "[PATCH 2/2] b",
+            "[PATCH v2 2/2] b2",
+            "[PATCH v2 1/2] a2",
+            "[PATCH 1/2] a",
+        ];
+        let all = || subjects.iter().filter_map(|s| patch(s)).collect::<Vec<_>>();
+        let titles: Vec<String> = series(all(), None)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.title)
+            .collect();
+        assert_eq!(titles, vec!["a2", "b2"]);
+        assert_eq!(series(all(), Some(1)).unwrap()[0].title, "a");
+        assert!(series(all(), Some(3)).is_err());
+    }
+
+    #[test]
+    fn test_picked_and_mbox() {
+        assert!(picked("", 4));
+        assert!(picked("1-3,5", 2) && picked("1-3,5", 5));
+        assert!(!picked("1-3,5", 4));
+
+        let p = parse_subject(
+            "[PATCH] x",
+            b"Subject: [PATCH] x\r\n\r\nFrom here\r\n".to_vec(),
+        )
+        .unwrap();
+        let out = String::from_utf8(mbox(&[&p])).unwrap();
+        assert_eq!(
+            out,
+            "From mu Thu Jan  1 00:00:00 1970\nSubject: [PATCH] x\n\n>From here\n\n"
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Phishing heuristics: deceptive links, lookalike domains, sender tricks, urgency
+
+use anyhow::Result;
+use mail_parser::{DateTime, Message};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::links::{self, Link};
+use crate::spoof::{self, BRANDS, base_domain, host, shown_host};
+use crate::{color, http, mime, render};
+
+/// Phrases that push the reader to act before thinking
+const URGENT: &str = r"(?i)\b(urgent|immediately|within 24 hours|account (has been |will be )?(suspended|locked|disabled|closed)|verify your (account|identity)|unusual (sign-in|activity)|confirm your (password|details)|final (notice|warning)|payment (failed|declined))\b";
+
+/// Domains younger than this count as freshly registered
+const FRESH_DAYS: i64 = 30;
+
+/// Arguments for `mu phish`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Print a single warning line for medium/high risk only (for previews)
+    #[arg(long)]
+    banner: bool,
+
+    /// Also look up domain registration dates (RDAP, needs network)
+    #[arg(long)]
+    online: bool,
+}
+
+/// One signal and its weight
+#[derive(Debug, PartialEq)]
+struct Finding {
+    score: u32,
+    text: String,
+}
+
+/// Score a message and print the risk report
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+    let findings = analyze(&message, args.online)?;
+    let score: u32 = findings.iter().map(|f| f.score).sum();
+    let (risk, sgr) = level(score);
+
+    if args.banner {
+        if score >= 3 {
+            let banner = format!("⚠ {} phishing risk: {}", risk, summary(&findings));
+            println!("{}\n", color::paint(&format!("1;{}", sgr), banner));
+        }
+        return Ok(());
+    }
+
+    println!("{}", color::header("Phishing report"));
+    for f in &findings {
+        println!(
+            "  {}  {}",
+            color::paint(
+                if f.score >= 4 { "31" } else { "33" },
+                format!("+{}", f.score)
+            ),
+            f.text
+        );
+    }
+    if findings.is_empty() {
+        println!("  no signals found");
+    }
+    println!(
+        "\n{}",
+        color::paint(
+            &format!("1;{}", sgr),
+            format!("Risk: {} (score {})", risk, score)
+        )
+    );
+    Ok(())
+}
+
+/// Preview banner text for a parsed message, if it looks risky (offline checks only)
+pub fn banner(message: &Message) -> Option<String> {
+    let findings = analyze(message, false).ok()?;
+    let score: u32 = findings.iter().map(|f| f.score).sum();
+    (score >= 3).then(|| format!("{} phishing risk: {}", level(score).0, summary(&findings)))
+}
+
+fn analyze(message: &Message, online: bool) -> Result<Vec<Finding>> {
+    let links = links::extract(message)?;
+    let mut findings = link_findings(&links);
+    findings.extend(sender_findings(message));
+    findings.extend(urgency(&body_text(message)?)?);
+    if online {
+        let mut domains: BTreeSet<String> = links
+            .iter()
+            .filter_map(|l| host(&l.url))
+            .map(|h| base_domain(&h))
+            .collect();
+        domains.extend(sender_domain(message, "From"));
+        findings.extend(domains.iter().filter_map(|d| fresh_domain(d)));
+    }
+    Ok(findings)
+}
+
+fn level(score: u32) -> (&'static str, &'static str) {
+    match score {
+        0..3 => ("low", "32"),
+        3..6 => ("medium", "33"),
+        _ => ("high", "31"),
+    }
+}
+
+/// The two strongest signals
+fn summary(findings: &[Finding]) -> String {
+    let mut sorted: Vec<&Finding> = findings.iter().collect();
+    sorted.sort_by_key(|f| std::cmp::Reverse(f.score));
+    sorted
+        .iter()
+        .take(2)
+        .map(|f| f.text.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn link_findings(links: &[Link]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for link in links {
+        let Some(href) = host(&link.url) else {
+            continue;
+        };
+        if let Some(shown) = shown_host(&link.text)
+            && base_domain(&shown) != base_domain(&href)
+        {
+            findings.push(Finding {
+                score: 4,
+                text: format!("link text shows {} but goes to {}", shown, href),
+            });
+        }
+        if link
+            .url
+            .split("://")
+            .nth(1)
+            .and_then(|r| r.split('/').next())
+            .is_some_and(|a| a.contains('@'))
+        {
+            findings.push(Finding {
+                score: 3,
+                text: format!("credentials trick in URL ({})", href),
+            });
+        }
+        findings.extend(domain_findings(&href));
+    }
+    findings.dedup();
+    findings
+}
+
+/// Punycode, raw IPs and lookalikes of well-known brands
+fn domain_findings(host: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if spoof::is_punycode(host) {
+        findings.push(Finding {
+            score: 3,
+            text: format!("punycode domain {}", host),
+        });
+    }
+    if host.split('.').all(|l| l.parse::<u8>().is_ok()) {
+        findings.push(Finding {
+            score: 3,
+            text: format!("link to a bare IP address {}", host),
+        });
+    }
+    let base = base_domain(host);
+    let label = base.split('.').next().unwrap_or(&base);
+    if let Some(brand) = spoof::imitated(host) {
+        findings.push(Finding {
+            score: 4,
+            text: format!("{} imitates {}", base, brand),
+        });
+    } else if let Some(brand) = BRANDS.iter().find(|b| label != **b && host.contains(**b)) {
+        findings.push(Finding {
+            score: 2,
+            text: format!("{} uses the name {} on another domain", host, brand),
+        });
+    }
+    findings
+}
+
+/// From/Reply-To divergence and addresses hidden in display names
+fn sender_findings(message: &Message) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let from = sender_domain(message, "From");
+    let reply_to = sender_domain(message, "Reply-To");
+    if let (Some(from), Some(reply)) = (&from, &reply_to)
+        && from != reply
+    {
+        findings.push(Finding {
+            score: 3,
+            text: format!("replies go to {} instead of {}", reply, from),
+        });
+    }
+
+    let name = message
+        .from()
+        .and_then(|a| a.first())
+        .and_then(|a| a.name())
+        .unwrap_or("");
+    if let (Some(shown), Some(from)) = (name.split_whitespace().find_map(mime::domain_of), &from)
+        && base_domain(&shown) != *from
+    {
+        findings.push(Finding {
+            score: 3,
+            text: format!("sender name shows {} but mail is from {}", shown, from),
+        });
+    }
+    if let Some(from) = &from {
+        findings.extend(domain_findings(from));
+    }
+    findings
+}
+
+fn sender_domain(message: &Message, header: &str) -> Option<String> {
+    let addresses = match header {
+        "Reply-To" => message.reply_to(),
+        _ => message.from(),
+    };
+    let address = addresses?.first()?.address()?;
+    mime::domain_of(address).map(|d| base_domain(&d))
+}
+
+fn urgency(text: &str) -> Result<Vec<Finding>> {
+    let re = Regex::new(URGENT)?;
+    let phrases: BTreeSet<String> = re
+        .find_iter(text)
+        .map(|m| m.as_str().to_lowercase())
+        .collect();
+    Ok(match phrases.len() {
+        0 => Vec::new(),
+        n => vec![Finding {
+            score: n.min(3) as u32,
+            text: format!(
+                "urgent language: {}",
+                phrases.into_iter().take(3).collect::<Vec<_>>().join(", ")
+            ),
+        }],
+    })
+}
+
+fn body_text(message: &Message) -> Result<String> {
+    match (
+        mime::find_part(message, "text/plain"),
+        mime::find_part(message, "text/html"),
+    ) {
+        (Some(part), _) => Ok(mime::part_text(part)),
+        (None, Some(part)) => render::plain_text(&mime::part_text(part)),
+        (None, None) => Ok(String::new()),
+    }
+}
+
+/// Registration date via RDAP, flagged when younger than FRESH_DAYS
+fn fresh_domain(domain: &str) -> Option<Finding> {
+    let response = http::Request::new("GET", &format!("https://rdap.org/domain/{}", domain))
+        .send()
+        .ok()?;
+    let json: serde_json::Value = serde_json::from_slice(&response.body).ok()?;
+    let registered = json["events"]
+        .as_array()?
+        .iter()
+        .find(|e| e["eventAction"] == "registration")?["eventDate"]
+        .as_str()?;
+    let ts = DateTime::parse_rfc3339(registered)?.to_timestamp();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    let days = (now - ts) / 86_400;
+    (days < FRESH_DAYS).then(|| Finding {
+        score: 3,
+        text: format!("{} was registered {} days ago", domain, days),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_mismatch_and_lookalikes() {
+        let html = "Content-Type: text/html\r\n\r\n<a href=\"https://secure-login.example.net/x\">https://www.paypal.com</a>\
+<a href=\"https://paypa1.com/\">here</a><a href=\"https://www.example.co.uk/a\">example.co.uk</a>";
+        let msg = mime::parse(html.as_bytes()).unwrap();
+        let texts: Vec<String> = link_findings(&links::extract(&msg).unwrap())
+            .into_iter()
+            .map(|f| f.text)
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "link text shows www.paypal.com but goes to secure-login.example.net",
+                "paypa1.com imitates paypal"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sender_findings() {
+        let raw = b"From: \"service@paypal.com\" <alerts@rnicrosoft.com>\r\nReply-To: x@evil.io\r\n\r\nhi";
+        let msg = mime::parse(raw).unwrap();
+        let scores: Vec<u32> = sender_findings(&msg).iter().map(|f| f.score).collect();
+        assert_eq!(scores, vec![3, 3, 4]);
+    }
+
+    #[test]
+    fn test_urgency_and_banner() {
+        let raw = b"From: a@example.com\r\n\r\nYour account will be suspended. Verify your account immediately.";
+        let msg = mime::parse(raw).unwrap();
+        let findings = urgency(&body_text(&msg).unwrap()).unwrap();
+        assert_eq!(findings[0].score, 3);
+        assert!(
+            banner(&msg)
+                .unwrap()
+                .starts_with("medium phishing risk: urgent language")
+        );
+        assert!(banner(&mime::parse(b"From: a@example.com\r\n\r\nlunch?").unwrap()).is_none());
+    }
+}
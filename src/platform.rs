@@ -0,0 +1,65 @@
+//! Platform-specific helpers
+
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+
+use crate::process;
+
+/// System command that opens files and URLs with the default handler
+///
+/// On Windows it's two words (`start` is a cmd.exe builtin and would reparse URLs).
+pub fn opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "rundll32 url.dll,FileProtocolHandler"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Open a file or URL with the default handler
+pub fn open(target: &str) -> Result<()> {
+    let mut words = opener().split(' ');
+    let program = words.next().unwrap_or_default();
+    let status = process::status(
+        Command::new(program)
+            .args(words)
+            .arg(target)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )
+    .with_context(|| format!("Failed to run {}", program))?;
+
+    if !status.success() {
+        anyhow::bail!("{} {} failed", opener(), target);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_open_runs_the_opener() {
+        // The opener's words, then the target as one argument
+        let mut words: Vec<&str> = opener().split(' ').collect();
+        let program = words.remove(0);
+        let target = "https://example.com/a b?c=d&e";
+        let recording = |status: i32| {
+            let line = serde_json::json!({
+                "program": program,
+                "args": words.iter().copied().chain([target]).collect::<Vec<_>>(),
+                "status": status,
+            });
+            Replay::parse(&line.to_string()).unwrap()
+        };
+        runner::scoped(recording(0), || open(target)).unwrap();
+        let err = runner::scoped(recording(1), || open(target)).unwrap_err();
+        assert_eq!(err.to_string(), format!("{} {} failed", opener(), target));
+        let unrecorded = Replay::new(Vec::new());
+        assert!(runner::scoped(unrecorded, || open(target)).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Printable output: paginated text for lpr, or PDF via typst/weasyprint
+
+use anyhow::{Context, Result};
+use mail_parser::{Message, MimeHeaders};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{color, mime, process, render, sanitize, width};
+
+/// Lines per page for text output (A4/Letter at 10-12pt)
+const PAGE_LINES: usize = 60;
+const PAGE_WIDTH: usize = 80;
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+enum Backend {
+    /// Paginated plain text with form feeds (pipe to lpr)
+    Text,
+    /// PDF via `typst compile`
+    Typst,
+    /// PDF via `weasyprint` (keeps the HTML layout)
+    Weasyprint,
+}
+
+/// Arguments for `mu print`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Output file (a .pdf output defaults to the typst backend)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output backend
+    #[arg(short, long, value_enum)]
+    backend: Option<Backend>,
+}
+
+/// Headers, body text and attachment list of a message
+struct Document {
+    headers: Vec<(&'static str, String)>,
+    body: String,
+    html: Option<String>,
+    attachments: Vec<String>,
+}
+
+/// Format a message for printing
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+    let doc = document(&message)?;
+
+    let is_pdf = args
+        .output
+        .as_ref()
+        .is_some_and(|p| p.extension().is_some_and(|e| e == "pdf"));
+    let backend = args.backend.unwrap_or(if is_pdf {
+        Backend::Typst
+    } else {
+        Backend::Text
+    });
+
+    match (backend, &args.output) {
+        (Backend::Text, Some(path)) => std::fs::write(path, paginate(&doc))
+            .with_context(|| format!("Failed to write {}", path.display())),
+        (Backend::Text, None) => {
+            print!("{}", paginate(&doc));
+            Ok(())
+        }
+        (_, None) => anyhow::bail!("PDF output needs -o <file.pdf>"),
+        (Backend::Typst, Some(path)) => pdf(&doc, path, "typ", typst_source, &["typst", "compile"]),
+        (Backend::Weasyprint, Some(path)) => pdf(&doc, path, "html", html_source, &["weasyprint"]),
+    }
+}
+
+fn document(message: &Message) -> Result<Document> {
+    let headers = ["From", "To", "Cc", "Date", "Subject"]
+        .into_iter()
+        .filter_map(|name| {
+            message
+                .header_raw(name)
+                .map(|v| (name, mime::decode_words(v)))
+        })
+        .collect();
+    let html = mime::find_part(message, "text/html").map(mime::part_text);
+    let body = match (mime::find_part(message, "text/plain"), &html) {
+        (Some(part), _) => mime::part_text(part),
+        (None, Some(html)) => render::plain_text(html)?,
+        (None, None) => String::new(),
+    };
+    let attachments = message
+        .attachments()
+        .map(|p| {
+            format!(
+                "{} ({}, {})",
+                p.attachment_name().unwrap_or("unnamed"),
+                mime::content_type(p),
+                mime::format_size(p.len() as u64)
+            )
+        })
+        .collect();
+    Ok(Document {
+        headers,
+        body,
+        html,
+        attachments,
+    })
+}
+
+/// Wrapped text split into pages with a footer and form feeds
+fn paginate(doc: &Document) -> String {
+    let mut lines: Vec<String> = doc
+        .headers
+        .iter()
+        .map(|(n, v)| format!("{}: {}", n, v))
+        .collect();
+    lines.push("-".repeat(PAGE_WIDTH));
+    lines.push(String::new());
+    for line in doc.body.lines() {
+        lines.extend(wrap(line.trim_end(), PAGE_WIDTH));
+    }
+    if !doc.attachments.is_empty() {
+        lines.push(String::new());
+        lines.push("Attachments:".to_string());
+        lines.extend(doc.attachments.iter().map(|a| format!("  - {}", a)));
+    }
+
+    let body_lines = PAGE_LINES - 2;
+    let pages: Vec<&[String]> = lines.chunks(body_lines).collect();
+    let total = pages.len();
+    let mut out = String::new();
+    for (i, page) in pages.iter().enumerate() {
+        for line in page.iter() {
+            out.push_str(line);
+            out.push('\n');
+        }
+        // Pad so the footer lands at the bottom of every page
+        out.push_str(&"\n".repeat(body_lines - page.len() + 1));
+        out.push_str(&format!(
+            "{:>width$}\n",
+            format!("Page {}/{}", i + 1, total),
+            width = PAGE_WIDTH
+        ));
+        if i + 1 < total {
+            out.push('\x0c');
+        }
+    }
+    out
+}
+
+/// Hard-wrap at word boundaries (long words are split)
+fn wrap(line: &str, columns: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    for word in line.split(' ') {
+        let mut word = word.to_string();
+        while width::width(&word) > columns {
+            // At least one cluster, however wide
+            let cut = match width::take(&word, columns).len() {
+                0 => word.chars().next().map_or(word.len(), char::len_utf8),
+                cut => cut,
+            };
+            let head = word[..cut].to_string();
+            word = word[cut..].to_string();
+            if !current.is_empty() {
+                out.push(std::mem::take(&mut current));
+            }
+            out.push(head);
+        }
+        if !current.is_empty() && width::width(&current) + 1 + width::width(&word) > columns {
+            out.push(std::mem::take(&mut current));
+        } else if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    out.push(current);
+    out
+}
+
+/// Write the source next to a temp body file and run the PDF tool on it
+fn pdf(
+    doc: &Document,
+    output: &Path,
+    ext: &str,
+    source: fn(&Document) -> String,
+    tool: &[&str],
+) -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("mu-print-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("body.txt"), &doc.body)?;
+    let input = dir.join(format!("message.{}", ext));
+    std::fs::write(&input, source(doc))?;
+
+    let status = process::status(
+        Command::new(tool[0])
+            .args(&tool[1..])
+            .arg(&input)
+            .arg(output),
+    )
+    .with_context(|| format!("Failed to run {} (is it installed?)", tool[0]));
+    let _ = std::fs::remove_dir_all(&dir);
+    if !status?.success() {
+        anyhow::bail!("{} failed", tool[0]);
+    }
+    eprintln!("{} {}", color::ok(), output.display());
+    Ok(())
+}
+
+/// typst string literal
+fn typst_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn typst_source(doc: &Document) -> String {
+    let mut src = String::from(
+        "#set page(paper: \"a4\", margin: 2cm, numbering: \"1 / 1\")\n#set text(size: 10pt)\n",
+    );
+    src.push_str("#table(columns: 2, stroke: none,\n");
+    for (name, value) in &doc.headers {
+        src.push_str(&format!("  [*{}*], [#{}],\n", name, typst_str(value)));
+    }
+    src.push_str(")\n#line(length: 100%)\n");
+    // The body is read verbatim, so nothing in it is interpreted as markup
+    src.push_str("#raw(read(\"body.txt\"), block: true)\n");
+    if !doc.attachments.is_empty() {
+        src.push_str("#line(length: 100%)\n*Attachments*\n");
+        for a in &doc.attachments {
+            src.push_str(&format!("- #{}\n", typst_str(a)));
+        }
+    }
+    src
+}
+
+fn html_source(doc: &Document) -> String {
+    let headers: String = doc
+        .headers
+        .iter()
+        .map(|(n, v)| format!("<tr><th>{}</th><td>{}</td></tr>", n, color::escape_html(v)))
+        .collect();
+    let body = match doc.html.as_deref().map(sanitize::html) {
+        Some(html) => html,
+        None => format!(
+            "<pre style=\"white-space:pre-wrap\">{}</pre>",
+            color::escape_html(&doc.body)
+        ),
+    };
+    let attachments: String = doc
+        .attachments
+        .iter()
+        .map(|a| format!("<li>{}</li>", color::escape_html(a)))
+        .collect();
+    format!(
+        "<!doctype html><html><head><meta charset=utf-8><style>@page{{size:A4;margin:2cm}}\
+th{{text-align:left;padding-right:1em}}</style></head><body><table>{}</table><hr>{}{}</body></html>",
+        headers,
+        body,
+        if attachments.is_empty() {
+            String::new()
+        } else {
+            format!("<hr><b>Attachments</b><ul>{}</ul>", attachments)
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(body: &str) -> Document {
+        Document {
+            headers: vec![("Subject", "Contract \"final\"".to_string())],
+            body: body.to_string(),
+            html: None,
+            attachments: vec!["contract.pdf (application/pdf, 2 KB)".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_paginate() {
+        let body = "line\n".repeat(100);
+        let text = paginate(&doc(&body));
+        let pages: Vec<&str> = text.split('\x0c').collect();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].lines().count(), PAGE_LINES);
+        assert!(pages[1].trim_end().ends_with("Page 2/2"));
+        assert!(text.contains("  - contract.pdf"));
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("aaa bbb ccc", 7), vec!["aaa bbb", "ccc"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(
+            wrap("日本語のメール", 5),
+            vec!["日本", "語の", "メー", "ル"]
+        );
+    }
+
+    #[test]
+    fn test_sources_escape() {
+        let typst = typst_source(&doc("#import \"x\""));
+        assert!(typst.contains("[#\"Contract \\\"final\\\"\"]"));
+        assert!(!typst.contains("#import"));
+        let html = html_source(&doc("<b>hi</b>"));
+        assert!(html.contains("&lt;b&gt;hi&lt;/b&gt;"));
+        assert!(html.contains("<li>contract.pdf"));
+    }
+}
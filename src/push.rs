@@ -0,0 +1,225 @@
+//! Phone push notifications (ntfy, Pushover, Gotify) for new mail
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+
+use crate::config::{self, PushPriority, PushRule, PushService, PushTarget};
+use crate::{http, notmuch};
+
+/// Priority of each thread worth pushing, by position (first matching rule wins)
+///
+/// Without rules every thread is pushed at the default priority.
+pub fn select(rules: &[PushRule], threads: &[&str]) -> Result<Vec<(usize, PushPriority)>> {
+    if rules.is_empty() {
+        return Ok((0..threads.len())
+            .map(|i| (i, PushPriority::Default))
+            .collect());
+    }
+    let scope = threads.join(" or ");
+    let mut hits = Vec::new();
+    for rule in rules {
+        let query = format!("({}) and ({})", scope, rule.query);
+        let matched: HashSet<String> = notmuch::search("threads", &query)?.into_iter().collect();
+        hits.push((matched, rule.priority));
+    }
+    Ok(threads
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| first_match(&hits, t).map(|p| (i, p)))
+        .collect())
+}
+
+fn first_match(hits: &[(HashSet<String>, PushPriority)], thread: &str) -> Option<PushPriority> {
+    hits.iter()
+        .find(|(matched, _)| matched.contains(thread))
+        .map(|(_, priority)| *priority)
+}
+
+/// Push one notification to a service
+pub fn send(target: &PushTarget, title: &str, body: &str, priority: PushPriority) -> Result<()> {
+    let token = target
+        .token_command
+        .as_deref()
+        .map(config::command_output)
+        .transpose()?;
+    let (url, headers, payload) = request(target, token.as_deref(), title, body, priority)?;
+
+    let mut request = http::Request::new("POST", &url)
+        .header("Content-Type: application/json")
+        .body(&payload.to_string());
+    for h in &headers {
+        request = request.header(h);
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("Failed to push to {}", url))?;
+    if !response.is_success() {
+        anyhow::bail!(
+            "{} answered {}: {}",
+            url,
+            response.status,
+            response.text().trim()
+        );
+    }
+    Ok(())
+}
+
+/// Endpoint, extra headers and JSON body of a push
+type Push = (String, Vec<String>, Value);
+
+/// The push for a service; `priority` maps onto each one's own scale
+fn request(
+    target: &PushTarget,
+    token: Option<&str>,
+    title: &str,
+    body: &str,
+    priority: PushPriority,
+) -> Result<Push> {
+    let level = |low, default, high| match priority {
+        PushPriority::Low => low,
+        PushPriority::Default => default,
+        PushPriority::High => high,
+    };
+    match target.service {
+        PushService::Ntfy => ntfy(target, token, title, body, level(2, 3, 4)),
+        PushService::Pushover => pushover(target, token, title, body, level(-1, 0, 1)),
+        PushService::Gotify => gotify(target, token, title, body, level(2, 5, 8)),
+    }
+}
+
+/// ntfy: the topic in the body, the token (if any) as a bearer header
+fn ntfy(
+    target: &PushTarget,
+    token: Option<&str>,
+    title: &str,
+    body: &str,
+    level: i32,
+) -> Result<Push> {
+    let topic = target
+        .topic
+        .as_deref()
+        .context("ntfy target needs a topic")?;
+    let url = target.url.as_deref().unwrap_or("https://ntfy.sh");
+    let headers = token
+        .map(|t| vec![format!("Authorization: Bearer {}", t)])
+        .unwrap_or_default();
+    let payload = json!({
+        "topic": topic,
+        "title": title,
+        "message": body,
+        "priority": level,
+        "tags": ["email"],
+    });
+    Ok((url.trim_end_matches('/').to_string(), headers, payload))
+}
+
+/// Pushover: user key and app token both in the body
+fn pushover(
+    target: &PushTarget,
+    token: Option<&str>,
+    title: &str,
+    body: &str,
+    level: i32,
+) -> Result<Push> {
+    let user = target
+        .user
+        .as_deref()
+        .context("Pushover target needs a user key")?;
+    let token = token.context("Pushover target needs a token_command")?;
+    let url = target
+        .url
+        .as_deref()
+        .unwrap_or("https://api.pushover.net/1/messages.json");
+    let payload = json!({
+        "token": token,
+        "user": user,
+        "title": title,
+        "message": body,
+        "priority": level,
+    });
+    Ok((url.to_string(), Vec::new(), payload))
+}
+
+/// Gotify: the app token in `X-Gotify-Key`
+fn gotify(
+    target: &PushTarget,
+    token: Option<&str>,
+    title: &str,
+    body: &str,
+    level: i32,
+) -> Result<Push> {
+    let url = target.url.as_deref().context("Gotify target needs a url")?;
+    let token = token.context("Gotify target needs a token_command")?;
+    let payload = json!({
+        "title": title,
+        "message": body,
+        "priority": level,
+    });
+    Ok((
+        format!("{}/message", url.trim_end_matches('/')),
+        vec![format!("X-Gotify-Key: {}", token)],
+        payload,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(service: PushService) -> PushTarget {
+        PushTarget {
+            service,
+            url: None,
+            topic: None,
+            user: None,
+            token_command: None,
+        }
+    }
+
+    #[test]
+    fn test_request_per_service() {
+        let mut ntfy = target(PushService::Ntfy);
+        assert!(request(&ntfy, None, "t", "b", PushPriority::Default).is_err());
+        ntfy.topic = Some("mail-abc".into());
+        let (url, headers, payload) = request(&ntfy, None, "t", "b", PushPriority::High).unwrap();
+        assert_eq!(url, "https://ntfy.sh");
+        assert!(headers.is_empty());
+        assert_eq!(payload["topic"], "mail-abc");
+        assert_eq!(payload["priority"], 4);
+
+        let mut pushover = target(PushService::Pushover);
+        pushover.user = Some("ukey".into());
+        let (url, _, payload) =
+            request(&pushover, Some("app"), "t", "b", PushPriority::Low).unwrap();
+        assert_eq!(url, "https://api.pushover.net/1/messages.json");
+        assert_eq!(payload["token"], "app");
+        assert_eq!(payload["user"], "ukey");
+        assert_eq!(payload["priority"], -1);
+
+        let mut gotify = target(PushService::Gotify);
+        gotify.url = Some("https://push.example.com/".into());
+        assert!(request(&gotify, None, "t", "b", PushPriority::Default).is_err());
+        let (url, headers, payload) =
+            request(&gotify, Some("tok"), "t", "b", PushPriority::Default).unwrap();
+        assert_eq!(url, "https://push.example.com/message");
+        assert_eq!(headers, vec!["X-Gotify-Key: tok"]);
+        assert_eq!(payload["priority"], 5);
+    }
+
+    #[test]
+    fn test_first_match() {
+        let set = |ids: &[&str]| ids.iter().map(|s| s.to_string()).collect::<HashSet<_>>();
+        let hits = vec![
+            (set(&["thread:1"]), PushPriority::High),
+            (set(&["thread:1", "thread:2"]), PushPriority::Low),
+        ];
+        assert_eq!(first_match(&hits, "thread:1"), Some(PushPriority::High));
+        assert_eq!(first_match(&hits, "thread:2"), Some(PushPriority::Low));
+        assert_eq!(first_match(&hits, "thread:3"), None);
+        assert_eq!(
+            select(&[], &["thread:1", "thread:2"]).unwrap(),
+            vec![(0, PushPriority::Default), (1, PushPriority::Default)]
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Disk usage per account and folder, growth since the last run, quota projections
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, Account, Config};
+use crate::{color, import, maildir, mime, size};
+
+/// Arguments for `mu quota`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Number of fastest-growing folders to list
+    #[arg(short, long, default_value = "10")]
+    top: usize,
+
+    /// Don't record this run as the baseline for the next one
+    #[arg(long)]
+    no_save: bool,
+
+    /// Maildir root (default: notmuch database.path, then `[index] maildir`)
+    #[arg(short, long)]
+    maildir: Option<String>,
+}
+
+/// Folder sizes at a point in time
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    time: i64,
+    folders: BTreeMap<String, u64>,
+}
+
+/// Report usage, growth and quota projections
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?;
+    let root = match &args.maildir {
+        Some(dir) => config::expand_tilde(dir),
+        None => import::maildir_root(&cfg),
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let current = scan(&root, now)?;
+    let previous = load()?;
+
+    print_accounts(&cfg, &current, previous.as_ref());
+    if let Some(previous) = &previous {
+        print_growth(previous, &current, args.top);
+    }
+
+    if !args.no_save {
+        save(&current)?;
+    }
+    Ok(())
+}
+
+/// The size of every folder below `root` at `time`
+fn scan(root: &Path, time: i64) -> Result<Snapshot> {
+    let mut snapshot = Snapshot {
+        time,
+        ..Snapshot::default()
+    };
+    for path in maildir::files(root)? {
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        *snapshot
+            .folders
+            .entry(size::folder_of(root, &path))
+            .or_default() += size;
+    }
+    Ok(snapshot)
+}
+
+/// One line per account, with its growth and quota if there's a `previous` run
+fn print_accounts(cfg: &Config, current: &Snapshot, previous: Option<&Snapshot>) {
+    println!("{}", color::header("Accounts"));
+    for (name, used) in accounts(current) {
+        let before = previous.map(|p| account_total(p, &name));
+        let account = cfg.accounts.iter().find(|a| a.dir() == name);
+        println!(
+            "{}",
+            account_line(
+                &name,
+                used,
+                before,
+                account,
+                previous.map(|p| current.time - p.time)
+            )
+        );
+    }
+}
+
+/// The `top` folders that grew most since `previous`
+fn print_growth(previous: &Snapshot, current: &Snapshot, top: usize) {
+    println!("\n{}", color::header("Largest growth"));
+    for (folder, delta) in growth(previous, current).into_iter().take(top) {
+        println!(
+            "{:>10}  {}",
+            format!("+{}", mime::format_size(delta)),
+            folder
+        );
+    }
+}
+
+/// Total per account (first path segment), largest first
+fn accounts(snapshot: &Snapshot) -> Vec<(String, u64)> {
+    let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+    for (folder, size) in &snapshot.folders {
+        *totals
+            .entry(folder.split('/').next().unwrap_or(folder).to_string())
+            .or_default() += size;
+    }
+    let mut totals: Vec<(String, u64)> = totals.into_iter().collect();
+    totals.sort_by_key(|t| std::cmp::Reverse(t.1));
+    totals
+}
+
+fn account_total(snapshot: &Snapshot, account: &str) -> u64 {
+    snapshot
+        .folders
+        .iter()
+        .filter(|(f, _)| f.split('/').next() == Some(account))
+        .map(|(_, s)| s)
+        .sum()
+}
+
+/// `12.3 MB  work  (+1.1 MB)  82% of 15 GB, full in ~40 days`
+fn account_line(
+    name: &str,
+    used: u64,
+    before: Option<u64>,
+    account: Option<&Account>,
+    elapsed: Option<i64>,
+) -> String {
+    let mut line = format!("{:>9}  {}", mime::format_size(used), name);
+    let delta = before.map(|b| used as i64 - b as i64);
+    if let Some(delta) = delta.filter(|d| *d != 0) {
+        let sign = if delta > 0 { "+" } else { "-" };
+        line.push_str(&format!(
+            "  ({}{})",
+            sign,
+            mime::format_size(delta.unsigned_abs())
+        ));
+    }
+    let Some(limit) = account
+        .and_then(|a| a.quota.as_deref())
+        .and_then(parse_size)
+    else {
+        return line;
+    };
+    let percent = used as f64 * 100.0 / limit as f64;
+    let sgr = if percent >= 90.0 {
+        "31"
+    } else if percent >= 75.0 {
+        "33"
+    } else {
+        "32"
+    };
+    line.push_str(&format!(
+        "  {} of {}",
+        color::paint(sgr, format!("{:.0}%", percent)),
+        mime::format_size(limit)
+    ));
+    if let (Some(delta), Some(elapsed)) = (delta, elapsed)
+        && let Some(days) = days_until_full(used, limit, delta, elapsed)
+    {
+        line.push_str(&format!(", full in ~{} days", days));
+    }
+    line
+}
+
+/// Linear projection from the growth rate since the last run
+fn days_until_full(used: u64, limit: u64, delta: i64, elapsed: i64) -> Option<u64> {
+    if delta <= 0 || elapsed <= 0 || used >= limit {
+        return None;
+    }
+    let per_day = delta as f64 * 86_400.0 / elapsed as f64;
+    Some(((limit - used) as f64 / per_day).ceil() as u64)
+}
+
+/// Folders that grew, largest growth first
+fn growth(previous: &Snapshot, current: &Snapshot) -> Vec<(String, u64)> {
+    let mut grown: Vec<(String, u64)> = current
+        .folders
+        .iter()
+        .filter_map(|(f, size)| {
+            let before = previous.folders.get(f).copied().unwrap_or(0);
+            (*size > before).then(|| (f.clone(), size - before))
+        })
+        .collect();
+    grown.sort_by_key(|g| std::cmp::Reverse(g.1));
+    grown
+}
+
+/// `15GB`, `500 MB`, `1.5G`, `2048K` → bytes (binary units, like IMAP QUOTA's KiB)
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_uppercase();
+    let text = text.trim_end_matches('B').trim_end_matches('I');
+    let (num, unit) = text.split_at(
+        text.find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(text.len()),
+    );
+    let factor: u64 = match unit.trim() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    Some((num.trim().parse::<f64>().ok()? * factor as f64) as u64)
+}
+
+fn snapshot_path() -> PathBuf {
+    config::data_dir().join("quota.json")
+}
+
+fn load() -> Result<Option<Snapshot>> {
+    match std::fs::read_to_string(snapshot_path()) {
+        Ok(text) => Ok(Some(
+            serde_json::from_str(&text).context("Invalid quota snapshot")?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+fn save(snapshot: &Snapshot) -> Result<()> {
+    let path = snapshot_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(snapshot)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(time: i64, folders: &[(&str, u64)]) -> Snapshot {
+        Snapshot {
+            time,
+            folders: folders.iter().map(|(f, s)| (f.to_string(), *s)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("15GB"), Some(15 << 30));
+        assert_eq!(parse_size("500 MiB"), Some(500 << 20));
+        assert_eq!(parse_size("1.5G"), Some(3 << 29));
+        assert_eq!(parse_size("12"), Some(12));
+        assert_eq!(parse_size("lots"), None);
+    }
+
+    #[test]
+    fn test_growth_and_accounts() {
+        let before = snapshot(0, &[("work/INBOX", 100), ("home/INBOX", 50)]);
+        let now = snapshot(
+            86_400,
+            &[("work/INBOX", 300), ("work/Lists", 20), ("home/INBOX", 40)],
+        );
+        assert_eq!(
+            growth(&before, &now),
+            vec![
+                ("work/INBOX".to_string(), 200),
+                ("work/Lists".to_string(), 20)
+            ]
+        );
+        assert_eq!(
+            accounts(&now),
+            vec![("work".to_string(), 320), ("home".to_string(), 40)]
+        );
+        assert_eq!(account_total(&before, "work"), 100);
+    }
+
+    #[test]
+    fn test_scan() {
+        let root = tempfile::tempdir().unwrap();
+        maildir::deliver(&root.path().join("work/INBOX"), b"12345", Some("S")).unwrap();
+        maildir::deliver(&root.path().join("work/INBOX"), b"123", None).unwrap();
+        maildir::deliver(&root.path().join("home/INBOX"), b"1", None).unwrap();
+        let scanned = scan(root.path(), 7).unwrap();
+        assert_eq!(scanned.time, 7);
+        assert_eq!(
+            accounts(&scanned),
+            [("work".to_string(), 8), ("home".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_days_until_full() {
+        // 1 KB a day, 10 KB left
+        assert_eq!(days_until_full(1024, 11 * 1024, 1024, 86_400), Some(10));
+        assert_eq!(days_until_full(1024, 11 * 1024, -5, 86_400), None);
+        assert_eq!(days_until_full(2048, 1024, 1, 86_400), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Raw message and MIME part extraction
+
+use anyhow::{Context, Result};
+use mail_parser::{Message, MessagePart, MimeHeaders, PartType};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::{mime, notmuch};
+
+/// Arguments for `mu raw`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query
+    message: String,
+
+    /// Output only part N (see --list), decoded
+    #[arg(short, long, conflicts_with = "content_type")]
+    part: Option<usize>,
+
+    /// Output the first part with this content type (e.g. text/html), decoded
+    #[arg(short = 't', long = "type")]
+    content_type: Option<String>,
+
+    /// List the message's MIME parts
+    #[arg(short, long)]
+    list: bool,
+
+    /// Output file (writes stdout if not provided)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Print the raw message, a decoded part, or the part list
+pub fn run(args: Args) -> Result<()> {
+    let raw = notmuch::raw(&notmuch::query_for(&args.message))?;
+
+    let bytes = if args.list {
+        let message = mime::parse(&raw)?;
+        list_parts(&message).into_bytes()
+    } else if let Some(n) = args.part {
+        let message = mime::parse(&raw)?;
+        let part = message
+            .parts
+            .get(n)
+            .with_context(|| format!("No part {} (message has {})", n, message.parts.len()))?;
+        decoded(part)
+    } else if let Some(ct) = &args.content_type {
+        let message = mime::parse(&raw)?;
+        let part = mime::find_part(&message, ct).with_context(|| format!("No {} part", ct))?;
+        decoded(part)
+    } else {
+        raw
+    };
+
+    match args.output {
+        Some(path) => std::fs::write(&path, bytes)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => Ok(io::stdout().write_all(&bytes)?),
+    }
+}
+
+/// Decoded bytes of a part (charset-decoded text, transfer-decoded binary)
+pub fn decoded(part: &MessagePart) -> Vec<u8> {
+    match &part.body {
+        PartType::Text(t) | PartType::Html(t) => t.as_bytes().to_vec(),
+        PartType::Message(m) => m.raw_message().to_vec(),
+        _ => part.contents().to_vec(),
+    }
+}
+
+/// One line per part: index, content type, size, filename
+pub fn list_parts(message: &Message) -> String {
+    message
+        .parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let name = part
+                .attachment_name()
+                .map(|n| format!("  {}", n))
+                .unwrap_or_default();
+            let size = match part.body {
+                PartType::Multipart(_) => String::new(),
+                _ => format!("  {} bytes", part.len()),
+            };
+            format!("{:>2}  {}{}{}\n", i, mime::content_type(part), size, name)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/html; charset=iso-8859-1\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n<p>caf=E9</p>\r\n\
+--b\r\nContent-Type: application/pdf; name=\"a.pdf\"\r\nContent-Transfer-Encoding: base64\r\n\r\nJVBERi0=\r\n--b--\r\n";
+
+    #[test]
+    fn test_list_parts() {
+        let msg = mime::parse(RAW).unwrap();
+        let list = list_parts(&msg);
+        assert!(list.contains(" 0  multipart/mixed\n"));
+        assert!(list.contains(" 2  application/pdf  5 bytes  a.pdf"));
+    }
+
+    #[test]
+    fn test_decoded_parts() {
+        let msg = mime::parse(RAW).unwrap();
+        let html = mime::find_part(&msg, "TEXT/HTML").unwrap();
+        assert_eq!(String::from_utf8(decoded(html)).unwrap(), "<p>café</p>");
+        assert_eq!(decoded(&msg.parts[2]), b"%PDF-");
+    }
+}
@@ -0,0 +1,303 @@
+//! Invoice and receipt PDFs collected from mail into a folder
+
+use anyhow::{Context, Result};
+use mail_parser::{Message, MessagePart, MimeHeaders};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config, ReceiptRule};
+use crate::{color, mime, notmuch, render};
+
+/// Subject words that mark a receipt on their own
+const KEYWORDS: &str = r"(?i)\b(invoice|receipt|faktura|rachunek|rechnung|facture|factura|fattura|payment confirmation|order confirmation|billing statement)";
+
+/// Sender local parts used for billing mail
+const BILLING_SENDERS: [&str; 5] = ["billing", "invoice", "invoices", "receipts", "payments"];
+
+/// Arguments for `mu receipts`
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Save new receipt PDFs as `YYYY-MM-vendor-amount.pdf`
+    Collect {
+        /// Destination folder (default: `[receipts] dest`)
+        #[arg(long)]
+        dest: Option<String>,
+
+        /// Messages to consider (default: `[receipts] query`)
+        #[arg(short, long)]
+        query: Option<String>,
+
+        /// List what would be saved without writing or recording anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// What a receipt PDF is saved as
+#[derive(Debug, PartialEq)]
+struct Meta {
+    month: String,
+    vendor: String,
+    amount: Option<String>,
+}
+
+/// Collect receipt attachments
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?.receipts;
+    let Action::Collect {
+        dest,
+        query,
+        dry_run,
+    } = args.action;
+    let dest = config::expand_tilde(dest.as_deref().unwrap_or(&cfg.dest));
+    let mut collected = load()?;
+    let mut saved = 0;
+
+    for id in notmuch::search("messages", query.as_deref().unwrap_or(&cfg.query))? {
+        if collected.contains(&id) {
+            continue;
+        }
+        let raw = notmuch::raw(&notmuch::quote_id(&id))?;
+        let message = mime::parse(&raw)?;
+        let Some(meta) = receipt(&cfg.rules, &message)? else {
+            continue;
+        };
+        for part in pdfs(&message) {
+            let path = unique_path(&dest, &file_name(&meta));
+            println!("{}", path.display());
+            if !dry_run {
+                std::fs::create_dir_all(&dest)?;
+                std::fs::write(&path, part.contents())
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            saved += 1;
+        }
+        collected.insert(id);
+    }
+
+    if !dry_run {
+        save(&collected)?;
+    }
+    eprintln!("{} {} receipt(s) in {}", color::ok(), saved, dest.display());
+    Ok(())
+}
+
+/// File name metadata if the message looks like a receipt with a PDF attached
+fn receipt(rules: &[ReceiptRule], message: &Message) -> Result<Option<Meta>> {
+    if pdfs(message).next().is_none() {
+        return Ok(None);
+    }
+    let address = message
+        .from()
+        .and_then(|a| a.first())
+        .and_then(|a| a.address())
+        .unwrap_or("")
+        .to_lowercase();
+    let rule = rules
+        .iter()
+        .find(|r| address.contains(&r.from.to_lowercase()));
+    if rule.is_some_and(|r| r.skip) {
+        return Ok(None);
+    }
+
+    let subject = message.subject().unwrap_or("");
+    let body = body_text(message)?;
+    let keywords = Regex::new(KEYWORDS)?;
+    let local = address.split('@').next().unwrap_or("");
+    let amount = amount(&format!("{}\n{}", subject, body))?;
+    let looks_like = keywords.is_match(subject)
+        || BILLING_SENDERS.contains(&local)
+        || (keywords.is_match(&body) && amount.is_some());
+    if rule.is_none() && !looks_like {
+        return Ok(None);
+    }
+
+    let vendor = rule
+        .and_then(|r| r.vendor.clone())
+        .unwrap_or_else(|| vendor_of(&address));
+    let month = message.date().map_or_else(
+        || "0000-00".to_string(),
+        |d| format!("{:04}-{:02}", d.year, d.month),
+    );
+    Ok(Some(Meta {
+        month,
+        vendor,
+        amount,
+    }))
+}
+
+fn body_text(message: &Message) -> Result<String> {
+    if let Some(part) = mime::find_part(message, "text/plain") {
+        return Ok(mime::part_text(part));
+    }
+    match mime::find_part(message, "text/html") {
+        Some(part) => render::plain_text(&mime::part_text(part)),
+        None => Ok(String::new()),
+    }
+}
+
+fn pdfs<'a>(message: &'a Message) -> impl Iterator<Item = &'a MessagePart<'a>> {
+    message.attachments().filter(|p| {
+        mime::content_type(p) == "application/pdf"
+            || p.attachment_name()
+                .is_some_and(|n| n.to_lowercase().ends_with(".pdf"))
+    })
+}
+
+/// Registrable name of the sender's domain (`billing.hetzner.com` → `hetzner`, `amazon.co.uk` → `amazon`)
+fn vendor_of(address: &str) -> String {
+    let domain = address.rsplit('@').next().unwrap_or(address);
+    let labels: Vec<&str> = domain.split('.').collect();
+    let name = match labels.as_slice() {
+        [.., name, second, tld] if tld.len() == 2 && second.len() <= 3 => name,
+        [.., name, _] => name,
+        _ => domain,
+    };
+    if name.is_empty() {
+        "unknown".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Total amount with its currency (`49.99EUR`), preferring one labelled as a total
+fn amount(text: &str) -> Result<Option<String>> {
+    let money = r"((?:[$€£]|usd|eur|gbp|pln|chf)\s?\d[\d ,.]*\d|\d[\d ,.]*[.,]\d{2}\s?(?:[$€£]|usd|eur|gbp|pln|chf|zł))";
+    let total = Regex::new(&format!(
+        r"(?i)(?:total|amount due|amount|summe|gesamtbetrag|kwota|razem|do zapłaty|montant)[^\n\d$€£]{{0,20}}{}",
+        money
+    ))?;
+    let any = Regex::new(&format!("(?i){}", money))?;
+    let found = total
+        .captures(text)
+        .or_else(|| any.captures(text))
+        .map(|c| c[1].to_string());
+    Ok(found.and_then(|m| normalize_amount(&m)))
+}
+
+/// `€ 1.234,50` → `1234.50EUR`
+fn normalize_amount(money: &str) -> Option<String> {
+    let lower = money.to_lowercase();
+    let currency = [
+        ("$", "USD"),
+        ("usd", "USD"),
+        ("€", "EUR"),
+        ("eur", "EUR"),
+        ("£", "GBP"),
+        ("gbp", "GBP"),
+        ("pln", "PLN"),
+        ("zł", "PLN"),
+        ("chf", "CHF"),
+    ]
+    .iter()
+    .find(|(token, _)| lower.contains(token))
+    .map_or("", |(_, code)| code);
+    let digits: String = money
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ','))
+        .collect();
+    // The last separator followed by exactly two digits is the decimal point
+    let (whole, cents) = match digits.rfind(['.', ',']) {
+        Some(i) if digits.len() - i == 3 => (&digits[..i], &digits[i + 1..]),
+        _ => (digits.as_str(), "00"),
+    };
+    let whole: String = whole.chars().filter(char::is_ascii_digit).collect();
+    (!whole.is_empty()).then(|| format!("{}.{}{}", whole, cents, currency))
+}
+
+fn file_name(meta: &Meta) -> String {
+    let vendor: String = meta
+        .vendor
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    match &meta.amount {
+        Some(amount) => format!("{}-{}-{}", meta.month, vendor.trim_matches('-'), amount),
+        None => format!("{}-{}", meta.month, vendor.trim_matches('-')),
+    }
+}
+
+/// `<name>.pdf`, or `<name>-2.pdf` and so on if taken
+fn unique_path(dest: &Path, name: &str) -> PathBuf {
+    let mut path = dest.join(format!("{}.pdf", name));
+    let mut n = 2;
+    while path.exists() {
+        path = dest.join(format!("{}-{}.pdf", name, n));
+        n += 1;
+    }
+    path
+}
+
+fn state_path() -> PathBuf {
+    config::data_dir().join("receipts.json")
+}
+
+/// Message-ids already collected
+fn load() -> Result<BTreeSet<String>> {
+    match std::fs::read_to_string(state_path()) {
+        Ok(text) => serde_json::from_str(&text).context("Invalid receipts state"),
+        Err(_) => Ok(BTreeSet::new()),
+    }
+}
+
+fn save(collected: &BTreeSet<String>) -> Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(collected)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"From: Hetzner <billing@hetzner.com>\r\nDate: Tue, 3 Feb 2026 10:00:00 +0000\r\n\
+Subject: Your invoice R0012\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nNet 41,30 EUR\r\nTotal amount: 49,15 EUR\r\n--b\r\n\
+Content-Type: application/pdf\r\nContent-Disposition: attachment; filename=R0012.pdf\r\n\r\n%PDF\r\n--b--\r\n";
+
+    #[test]
+    fn test_receipt_meta() {
+        let message = mime::parse(RAW).unwrap();
+        let meta = receipt(&[], &message).unwrap().unwrap();
+        assert_eq!(file_name(&meta), "2026-02-hetzner-49.15EUR");
+
+        let rules = [ReceiptRule {
+            from: "@hetzner.com".into(),
+            vendor: None,
+            skip: true,
+        }];
+        assert_eq!(receipt(&rules, &message).unwrap(), None);
+    }
+
+    #[test]
+    fn test_amount() {
+        assert_eq!(
+            amount("Total: $1,234.50").unwrap(),
+            Some("1234.50USD".into())
+        );
+        assert_eq!(
+            amount("Do zapłaty: 99,00 zł").unwrap(),
+            Some("99.00PLN".into())
+        );
+        assert_eq!(amount("Price € 12").unwrap(), Some("12.00EUR".into()));
+        assert_eq!(amount("Order 12345 shipped").unwrap(), None);
+    }
+
+    #[test]
+    fn test_vendor_of() {
+        assert_eq!(vendor_of("billing@hetzner.com"), "hetzner");
+        assert_eq!(vendor_of("auto-confirm@amazon.co.uk"), "amazon");
+        assert_eq!(vendor_of("noreply@mail.github.com"), "github");
+    }
+}
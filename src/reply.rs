@@ -0,0 +1,147 @@
+//! Quoted reply bodies (HTML rendered to text, attribution, signatures stripped)
+
+use anyhow::Result;
+use mail_parser::Message;
+
+use crate::{mime, render, width};
+
+/// Arguments for `mu reply-quote`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Wrap unquoted lines at this width before quoting
+    #[arg(short, long, default_value = "72")]
+    width: usize,
+
+    /// Keep the sender's signature
+    #[arg(long)]
+    keep_signature: bool,
+}
+
+/// Print a quoted reply body for the message
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+
+    let mut text = body_text(&message)?;
+    if !args.keep_signature {
+        text = strip_signature(&text);
+    }
+
+    println!("{}", attribution(&message));
+    println!("{}", quote(&text, args.width));
+    Ok(())
+}
+
+/// Plain text body, rendering the HTML part when there is no text/plain one
+pub fn body_text(message: &Message) -> Result<String> {
+    if let Some(part) = mime::find_part(message, "text/plain") {
+        return Ok(mime::part_text(part));
+    }
+    match mime::find_part(message, "text/html") {
+        Some(part) => render::plain_text(&mime::part_text(part)),
+        None => Ok(String::new()),
+    }
+}
+
+/// `On <date>, <name> wrote:`
+fn attribution(message: &Message) -> String {
+    let sender = message.from().and_then(|a| a.first());
+    let name = sender
+        .and_then(|a| a.name().or(a.address()))
+        .unwrap_or("someone");
+    match message.date() {
+        Some(date) => format!("On {}, {} wrote:", date.to_rfc822(), name),
+        None => format!("{} wrote:", name),
+    }
+}
+
+/// Cut everything from the signature separator (`-- `) on
+pub fn strip_signature(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        if matches!(line.trim_end_matches('\r'), "-- " | "--") {
+            break;
+        }
+        kept.push(line);
+    }
+    kept.join("\n")
+}
+
+/// Prefix lines with `> `, wrapping unquoted text and collapsing blank runs
+fn quote(text: &str, width: usize) -> String {
+    let mut lines = Vec::new();
+    let mut blank = true;
+
+    for line in text.lines().map(|l| l.trim_end()) {
+        if line.is_empty() {
+            if !blank {
+                lines.push(">".to_string());
+            }
+            blank = true;
+            continue;
+        }
+        blank = false;
+
+        // Already-quoted lines nest without an extra space (`>> `)
+        if line.starts_with('>') {
+            lines.push(format!(">{}", line));
+        } else {
+            lines.extend(wrap(line, width).into_iter().map(|l| format!("> {}", l)));
+        }
+    }
+
+    if lines.last().is_some_and(|l| l == ">") {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Greedy word wrap
+fn wrap(line: &str, columns: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        if !current.is_empty() && width::width(&current) + 1 + width::width(word) > columns {
+            out.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    out.push(current);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_only_reply() {
+        let raw =
+            b"From: Alice Smith <alice@example.com>\r\nDate: Mon, 2 Feb 2026 10:00:00 +0000\r\n\
+Content-Type: text/html\r\n\r\n<html><body><p>Hi there</p><p>Thanks!</p>\
+<p>-- </p><p>Alice</p></body></html>";
+        let msg = mime::parse(raw).unwrap();
+        assert!(attribution(&msg).starts_with("On Mon, 2 Feb 2026"));
+        assert!(attribution(&msg).ends_with("Alice Smith wrote:"));
+        let body = body_text(&msg).unwrap();
+        assert!(body.contains("Hi there") && !body.contains("<p>"));
+    }
+
+    #[test]
+    fn test_quote_and_signature() {
+        let text = "Hello\n\n\n> earlier\n-- \nAlice\n";
+        assert_eq!(quote(&strip_signature(text), 72), "> Hello\n>\n>> earlier");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("aaa bbb ccc", 7), vec!["aaa bbb", "ccc"]);
+        assert_eq!(wrap("", 7), vec![""]);
+    }
+}
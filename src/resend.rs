@@ -0,0 +1,188 @@
+//! Resend (bounce) a message with a proper Resent-* block
+
+use anyhow::{Context, Result};
+use mail_parser::DateTime;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::{color, mime, notmuch, process};
+
+/// Trace headers from the original delivery that make the next hop see a loop
+const DROPPED: &[&str] = &["Delivered-To", "Return-Path"];
+
+/// Arguments for `mu resend`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query
+    message: String,
+
+    /// Recipient(s)
+    #[arg(long, required = true)]
+    to: Vec<String>,
+
+    /// Resent-From address (defaults to notmuch's user.name and user.primary_email)
+    #[arg(long)]
+    from: Option<String>,
+
+    /// Command the message is piped into (recipients are appended)
+    #[arg(long, value_name = "CMD", default_value = "sendmail")]
+    sendmail: String,
+
+    /// Print the resent message instead of sending it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Resend a message unchanged to new recipients
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(Some(&args.message))?;
+    let from = match args.from {
+        Some(from) => from,
+        None => {
+            default_from().context("No --from given and notmuch user.primary_email is unset")?
+        }
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let resent = resent(&raw, &from, &args.to, now);
+
+    if args.dry_run {
+        std::io::stdout().write_all(&resent)?;
+        return Ok(());
+    }
+    send(&args.sendmail, &args.to, &resent)?;
+    eprintln!("{} resent to {}", color::ok(), args.to.join(", "));
+    Ok(())
+}
+
+/// `Name <address>` from the notmuch user config
+fn default_from() -> Option<String> {
+    let email = notmuch::config_get("user.primary_email")?;
+    Some(match notmuch::config_get("user.name") {
+        Some(name) => format!("{} <{}>", name, email),
+        None => email,
+    })
+}
+
+/// Prepend the Resent-* block; header lines other than trace headers and the body stay byte-identical
+fn resent(raw: &[u8], from: &str, to: &[String], now: u64) -> Vec<u8> {
+    let date = DateTime::from_timestamp(now as i64).to_rfc822();
+    let domain = mime::domain_of(from.rsplit('<').next().unwrap_or(from))
+        .unwrap_or_else(|| "localhost".into());
+    let mut out = format!(
+        "Resent-From: {}\r\nResent-To: {}\r\nResent-Date: {}\r\nResent-Message-ID: <{}.{}.resent@{}>\r\n",
+        from,
+        to.join(", "),
+        date,
+        now,
+        std::process::id(),
+        domain
+    )
+    .into_bytes();
+
+    let mut dropping = false;
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(rest.len(), |i| i + 1);
+        let (line, tail) = rest.split_at(end);
+        if line == b"\r\n" || line == b"\n" {
+            break;
+        }
+        if !line.starts_with(b" ") && !line.starts_with(b"\t") {
+            dropping = DROPPED.iter().any(|h| is_header(line, h));
+        }
+        if !dropping {
+            out.extend_from_slice(line);
+        }
+        rest = tail;
+    }
+    out.extend_from_slice(rest);
+    out
+}
+
+fn is_header(line: &[u8], name: &str) -> bool {
+    line.len() > name.len()
+        && line[..name.len()].eq_ignore_ascii_case(name.as_bytes())
+        && line[name.len()] == b':'
+}
+
+fn send(cmd: &str, to: &[String], raw: &[u8]) -> Result<()> {
+    let output = process::run_with_input(
+        &mut process::shell_with_args(&format!("{} -i --", cmd), to),
+        raw,
+    )
+    .with_context(|| format!("Failed to run {}", cmd))?;
+    if !output.status.success() {
+        return Err(Error::command_failed(cmd, &output.stderr).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_resent() {
+        let raw = b"Return-Path: <b@x>\r\nDelivered-To: me@example.com\r\nFrom: Bob <b@x>\r\n\
+Subject: long\r\n folded\r\n\r\nbody\r\nDelivered-To: stays in body\r\n";
+        let out = resent(raw, "Me <me@example.com>", &["c@y".to_string()], 0);
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with(
+            "Resent-From: Me <me@example.com>\r\nResent-To: c@y\r\nResent-Date: Thu, 1 Jan 1970 00:00:00 +0000\r\n"
+        ));
+        assert!(text.contains(
+            "@example.com>\r\nFrom: Bob <b@x>\r\nSubject: long\r\n folded\r\n\r\nbody\r\n"
+        ));
+        assert!(text.ends_with("Delivered-To: stays in body\r\n"));
+        assert_eq!(text.matches("Delivered-To").count(), 1);
+    }
+
+    #[test]
+    fn test_default_from() {
+        let config = |key: &str, value: &str| {
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["config", "get", key],
+                "stdout": format!("{}\n", value),
+            })
+            .to_string()
+        };
+        let named = [
+            config("user.primary_email", "me@example.com"),
+            config("user.name", "Me Too"),
+        ]
+        .join("\n");
+        let from = runner::scoped(Replay::parse(&named).unwrap(), default_from);
+        assert_eq!(from.as_deref(), Some("Me Too <me@example.com>"));
+        let unnamed = Replay::parse(&config("user.primary_email", "me@example.com")).unwrap();
+        let from = runner::scoped(unnamed, default_from);
+        assert_eq!(from.as_deref(), Some("me@example.com"));
+        assert_eq!(runner::scoped(Replay::new(Vec::new()), default_from), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_send_appends_recipients() {
+        let sendmail = |status: i32| {
+            let line = serde_json::json!({
+                "program": "sh",
+                "args": ["-c", "exec msmtp -a work -i -- \"$@\"", "sh", "c@y", "d@z"],
+                "status": status,
+                "stderr": "no route",
+            });
+            Replay::parse(&line.to_string()).unwrap()
+        };
+        let to = ["c@y".to_string(), "d@z".to_string()];
+        runner::scoped(sendmail(0), || send("msmtp -a work", &to, b"raw")).unwrap();
+        let err = runner::scoped(sendmail(1), || send("msmtp -a work", &to, b"raw")).unwrap_err();
+        assert!(format!("{:#}", err).contains("no route"));
+    }
+}
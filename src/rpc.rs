@@ -0,0 +1,276 @@
+//! JSON-RPC 2.0 server for editor integrations (newline-delimited, stdio or Unix socket)
+//!
+//! Methods: `search {query, limit?}`, `show {id}`, `preview {id}`, `tag {query, tags}`,
+//! `count {query}`. One request per line, one response per line.
+
+use anyhow::{Context, Result};
+use mail_parser::MimeHeaders;
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::{mime, notmuch, process, render};
+
+/// Arguments for `mu server`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Listen on a Unix socket instead of stdin/stdout
+    #[arg(long)]
+    socket: Option<PathBuf>,
+}
+
+/// A failed call: JSON-RPC error code and message
+struct RpcError(i64, String);
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        RpcError(-32000, format!("{:#}", e))
+    }
+}
+
+/// Serve requests until stdin closes (or forever on a socket)
+pub fn run(args: Args) -> Result<()> {
+    match args.socket {
+        Some(path) => listen(&path),
+        None => serve(io::stdin().lock(), io::stdout().lock()),
+    }
+}
+
+#[cfg(unix)]
+fn listen(path: &std::path::Path) -> Result<()> {
+    use crate::color;
+    use std::io::BufReader;
+    use std::os::unix::net::UnixListener;
+
+    // A stale socket from a previous run would make bind fail
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    eprintln!("{} Listening on {}", color::ok(), path.display());
+
+    for stream in listener.incoming().flatten() {
+        std::thread::spawn(move || {
+            let reader = match stream.try_clone() {
+                Ok(s) => BufReader::new(s),
+                Err(_) => return,
+            };
+            if let Err(e) = serve(reader, stream) {
+                eprintln!("{} {:#}", color::fail(), e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_: &std::path::Path) -> Result<()> {
+    anyhow::bail!("--socket needs Unix domain sockets; use stdio")
+}
+
+fn serve(input: impl BufRead, mut output: impl Write) -> Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(&line) {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Response line for one request line (None for notifications)
+fn handle(line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(v) => v,
+        Err(e) => return Some(error(Value::Null, RpcError(-32700, e.to_string()))),
+    };
+    let id = request.get("id").cloned();
+    let method = request.get("method").and_then(Value::as_str).unwrap_or("");
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+    let result = call(method, &params);
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error(id, e),
+    })
+}
+
+fn error(id: Value, RpcError(code, message): RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn call(method: &str, params: &Value) -> Result<Value, RpcError> {
+    let str_param = |name: &str| {
+        params
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError(-32602, format!("missing string param `{}`", name)))
+    };
+    match method {
+        "search" => {
+            let limit = params.get("limit").and_then(Value::as_u64).unwrap_or(100);
+            Ok(search(str_param("query")?, limit)?)
+        }
+        "count" => Ok(count(str_param("query")?)?),
+        "show" => Ok(show(str_param("id")?)?),
+        "preview" => Ok(preview(str_param("id")?)?),
+        "tag" => {
+            let tags = tag_changes(params)?;
+            Ok(tag(str_param("query")?, &tags)?)
+        }
+        _ => Err(RpcError(-32601, format!("unknown method `{}`", method))),
+    }
+}
+
+fn search(query: &str, limit: u64) -> Result<Value> {
+    let output = process::run(Command::new("notmuch").args([
+        "search",
+        "--format=json",
+        &format!("--limit={}", limit),
+        query,
+    ]))
+    .context("Failed to run notmuch search")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "notmuch search failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    serde_json::from_slice(&output.stdout).context("Invalid notmuch JSON")
+}
+
+fn count(query: &str) -> Result<Value> {
+    let output = process::run(Command::new("notmuch").args(["count", query]))
+        .context("Failed to run notmuch count")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "notmuch count failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let n: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .unwrap_or(0);
+    Ok(json!(n))
+}
+
+/// Decoded headers, text body (HTML rendered) and attachment list
+fn show(id: &str) -> Result<Value> {
+    let raw = notmuch::raw(&notmuch::query_for(id))?;
+    let message = mime::parse(&raw)?;
+    let mut headers = serde_json::Map::new();
+    for name in ["From", "To", "Cc", "Date", "Subject", "Message-ID"] {
+        if let Some(value) = message.header_raw(name) {
+            headers.insert(name.to_lowercase(), json!(mime::decode_words(value)));
+        }
+    }
+    let body = mime::body_text(&message)?;
+    let attachments: Vec<Value> = message
+        .attachments()
+        .map(|p| {
+            json!({
+                "name": p.attachment_name(),
+                "content_type": mime::content_type(p),
+                "size": p.len(),
+            })
+        })
+        .collect();
+    Ok(json!({"headers": headers, "body": body, "attachments": attachments}))
+}
+
+/// Colored markdown rendering of the HTML part, like `mu render`
+fn preview(id: &str) -> Result<Value> {
+    let raw = notmuch::raw(&notmuch::query_for(id))?;
+    let message = mime::parse(&raw)?;
+    let text = match mime::find_part(&message, "text/html") {
+        Some(part) => render::render_as(
+            &mime::part_text(part),
+            render::ContentType::Html,
+            &Default::default(),
+        )?,
+        None => mime::find_part(&message, "text/plain")
+            .map(mime::part_text)
+            .unwrap_or_default(),
+    };
+    Ok(json!(text))
+}
+
+/// The `tags` param: `+tag`/`-tag` strings, without spaces or control characters (which
+/// would end the change in a `notmuch tag --batch` line)
+fn tag_changes(params: &Value) -> Result<Vec<&str>, RpcError> {
+    let invalid = || RpcError(-32602, "tags must be `+tag`/`-tag` strings".to_string());
+    let tags = params
+        .get("tags")
+        .and_then(Value::as_array)
+        .filter(|tags| !tags.is_empty())
+        .ok_or_else(invalid)?;
+    tags.iter()
+        .map(|tag| {
+            let tag = tag.as_str().ok_or_else(invalid)?;
+            let name = tag.strip_prefix(['+', '-']).ok_or_else(invalid)?;
+            if name.is_empty() || name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(invalid());
+            }
+            Ok(tag)
+        })
+        .collect()
+}
+
+fn tag(query: &str, tags: &[&str]) -> Result<Value> {
+    notmuch::tag_batch(&[format!("{} -- ({})", tags.join(" "), query)])?;
+    Ok(json!(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner;
+
+    #[test]
+    fn test_handle_errors() {
+        let r = handle("{nope").unwrap();
+        assert_eq!(r["error"]["code"], -32700);
+
+        let r = handle(r#"{"jsonrpc":"2.0","id":1,"method":"frobnicate"}"#).unwrap();
+        assert_eq!(r["id"], 1);
+        assert_eq!(r["error"]["code"], -32601);
+
+        let r = handle(r#"{"jsonrpc":"2.0","id":"a","method":"show","params":{}}"#).unwrap();
+        assert_eq!(r["error"]["code"], -32602);
+
+        for tags in [r#"["inbox"]"#, r#"["+a b"]"#, r#"["+a\n-b"]"#, "[]", "[1]"] {
+            let r = handle(&format!(
+                r#"{{"jsonrpc":"2.0","id":2,"method":"tag","params":{{"query":"*","tags":{}}}}}"#,
+                tags
+            ));
+            assert_eq!(r.unwrap()["error"]["code"], -32602, "{}", tags);
+        }
+
+        let replay =
+            runner::Replay::parse(r#"{"program":"notmuch","args":["count","*"],"status":1}"#)
+                .unwrap();
+        let r = runner::scoped(replay, || {
+            handle(r#"{"jsonrpc":"2.0","id":3,"method":"count","params":{"query":"*"}}"#)
+        });
+        assert_eq!(r.unwrap()["error"]["code"], -32000);
+    }
+
+    #[test]
+    fn test_notification_has_no_response() {
+        assert!(handle(r#"{"jsonrpc":"2.0","method":"frobnicate"}"#).is_none());
+    }
+
+    #[test]
+    fn test_serve_lines() {
+        let input = "{\"id\":1,\"method\":\"x\"}\n\n{\"id\":2,\"method\":\"y\"}\n";
+        let mut out = Vec::new();
+        serve(input.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+    }
+}
@@ -0,0 +1,191 @@
+//! Rhai scripts for tagging, notification filters and listing/preview formatting
+//!
+//! `[script] path` names a script defining any of these, each called with a map:
+//!
+//! - `tag(t)`: tag changes (`["+list", "-inbox"]`, or nothing) for each thread matching
+//!   `[script] query`, applied by `mu tagrules`
+//! - `notify(msg)`: `false` keeps a new message out of `mu sync` notifications
+//! - `listing(t)`: the text of a `mu fzf` line, after the thread id
+//! - `preview(msg)`: a line above the headers in `mu preview`
+//!
+//! Threads are notmuch's JSON summaries (`id`, `authors`, `subject`, `tags`, `timestamp`,
+//! `date_relative`, `matched`, `total`); messages have `from`, `subject` and, for
+//! notifications, `id` (of the thread), or for previews `to` and `date`. (`thread` is a
+//! reserved word in Rhai, hence `id`.) Scripts can call
+//! `search(query)` (thread ids), `count(query)` (messages), `apply_tags(query, changes)` and
+//! `matches(text, regex)`; `print` goes to stderr.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use rhai::{AST, Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use serde::Serialize;
+
+use crate::config;
+use crate::notmuch;
+
+/// A compiled script and the engine that runs it
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+/// The script at `[script] path`, if one is set
+pub fn load(cfg: &config::Script) -> Result<Option<Script>> {
+    let Some(path) = &cfg.path else {
+        return Ok(None);
+    };
+    let path = config::expand_tilde(path);
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Script::compile(&source)
+        .with_context(|| format!("In {}", path.display()))
+        .map(Some)
+}
+
+impl Script {
+    pub fn compile(source: &str) -> Result<Self> {
+        let engine = engine();
+        let ast = engine
+            .compile(source)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(Self { engine, ast })
+    }
+
+    /// Whether the script defines `name` taking one argument
+    pub fn defines(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == 1)
+    }
+
+    /// Tag changes for a thread (`+tag`/`-tag`)
+    pub fn tag(&self, thread: &impl Serialize) -> Result<Vec<String>> {
+        let changes = self.call("tag", thread)?;
+        if changes.is_unit() {
+            return Ok(Vec::new());
+        }
+        let changes = changes
+            .try_cast::<Array>()
+            .context("tag() should return an array of \"+tag\"/\"-tag\" strings")?;
+        tag_changes(changes).map_err(|e| anyhow::anyhow!("tag(): {}", e))
+    }
+
+    /// Whether a new message should be notified about
+    pub fn notify(&self, message: &impl Serialize) -> Result<bool> {
+        self.call("notify", message)?
+            .as_bool()
+            .map_err(|kind| anyhow::anyhow!("notify() should return true or false, not {}", kind))
+    }
+
+    /// A `mu fzf` line for a thread
+    pub fn listing(&self, thread: &impl Serialize) -> Result<String> {
+        self.call("listing", thread).map(|line| line.to_string())
+    }
+
+    /// A line above a preview's headers
+    pub fn preview(&self, message: &impl Serialize) -> Result<String> {
+        self.call("preview", message).map(|line| line.to_string())
+    }
+
+    fn call(&self, name: &str, arg: &impl Serialize) -> Result<Dynamic> {
+        let mut arg = rhai::serde::to_dynamic(arg).map_err(|e| anyhow::anyhow!("{}", e))?;
+        // `t.thread` doesn't parse (`thread` is reserved), so the id is also under `id`
+        if let Some(mut map) = arg.write_lock::<Map>()
+            && let Some(id) = map.get("thread").cloned()
+        {
+            map.insert("id".into(), id);
+        }
+        self.engine
+            .call_fn(&mut Scope::new(), &self.ast, name, (arg,))
+            .map_err(|e| anyhow::anyhow!("{}(): {}", name, e))
+    }
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.register_fn("search", |query: &str| -> HostResult<Array> {
+        let threads = notmuch::search("threads", query).map_err(host_error)?;
+        Ok(threads.into_iter().map(Dynamic::from).collect())
+    });
+    engine.register_fn("count", |query: &str| -> HostResult<i64> {
+        let messages = notmuch::search("messages", query).map_err(host_error)?;
+        Ok(messages.len() as i64)
+    });
+    engine.register_fn(
+        "apply_tags",
+        |query: &str, changes: Array| -> HostResult<()> {
+            let line = format!("{} -- ({})", tag_changes(changes)?.join(" "), query);
+            notmuch::tag_batch(&[line]).map_err(host_error)
+        },
+    );
+    engine.register_fn("matches", |text: &str, pattern: &str| -> HostResult<bool> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        Ok(regex.is_match(text))
+    });
+    // stdout belongs to listings, previews and notmuch batches
+    engine.on_print(|text| eprintln!("{}", text));
+    engine
+}
+
+type HostResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn host_error(e: anyhow::Error) -> Box<EvalAltResult> {
+    format!("{:#}", e).into()
+}
+
+/// `"+tag"`/`"-tag"` strings from a script array
+fn tag_changes(changes: Array) -> Result<Vec<String>, String> {
+    changes
+        .into_iter()
+        .map(|change| {
+            let change = change.to_string();
+            match change.strip_prefix(['+', '-']) {
+                Some(tag) if !tag.is_empty() && !tag.contains(char::is_whitespace) => Ok(change),
+                _ => Err(format!("`{}` is not a +tag or -tag change", change)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCRIPT: &str = r#"
+        fn tag(t) {
+            if matches(t.authors, "(?i)github") { ["+github", "-inbox"] }
+        }
+        fn notify(msg) { !msg.subject.contains("[spam]") }
+        fn listing(t) { `${t.id}: ${t.date_relative} ${t.subject} (${t.total})` }
+    "#;
+
+    #[test]
+    fn test_script_functions() {
+        let script = Script::compile(SCRIPT).unwrap();
+        assert!(script.defines("listing"));
+        assert!(!script.defines("preview"));
+        let thread = json!({
+            "thread": "0001", "authors": "GitHub", "subject": "PR merged",
+            "date_relative": "today", "total": 3, "tags": ["inbox"]
+        });
+        assert_eq!(script.tag(&thread).unwrap(), ["+github", "-inbox"]);
+        let other = json!({"authors": "Alice"});
+        assert!(script.tag(&other).unwrap().is_empty());
+        assert_eq!(
+            script.listing(&thread).unwrap(),
+            "0001: today PR merged (3)"
+        );
+        assert!(!script.notify(&json!({"subject": "[spam] pills"})).unwrap());
+    }
+
+    #[test]
+    fn test_bad_tag_changes() {
+        let script = Script::compile(r#"fn tag(t) { ["github"] }"#).unwrap();
+        let err = script.tag(&json!({})).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tag(): `github` is not a +tag or -tag change"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! sendmail-compatible msmtp wrapper that files, indexes and tags sent mail
+
+use anyhow::{Context, Result};
+use mail_parser::Message;
+use std::io::{self, Read};
+use std::process::Command;
+
+use crate::config::{Account, Config};
+use crate::error::Error;
+use crate::{color, import, maildir, mime, notmuch, process};
+
+/// Arguments for `mu send`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Command that actually sends the message
+    #[arg(long, value_name = "CMD", default_value = "msmtp")]
+    msmtp: String,
+
+    /// Send without storing a copy in the Sent folder
+    #[arg(long)]
+    no_store: bool,
+
+    /// Arguments passed through to msmtp (`-oi -- rcpt...`, `-a account`)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    sendmail_args: Vec<String>,
+}
+
+/// Send the message on stdin, then store it in the account's Sent maildir
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let mut raw = Vec::new();
+    io::stdin().read_to_end(&mut raw)?;
+    send(&args.msmtp, &args.sendmail_args, &raw)?;
+    if args.no_store {
+        return Ok(());
+    }
+
+    // The mail is out: failing to file it must not look like a failed send to neomutt
+    if let Err(e) = store(cfg, &args.sendmail_args, &raw) {
+        eprintln!("{} sent, but not stored: {:#}", color::warn(), e);
+    }
+    Ok(())
+}
+
+fn send(cmd: &str, args: &[String], raw: &[u8]) -> Result<()> {
+    let output = process::run_with_input(Command::new(cmd).args(args), raw)
+        .with_context(|| format!("Failed to run {}", cmd))?;
+    if !output.status.success() {
+        return Err(Error::command_failed(cmd, &output.stderr).into());
+    }
+    Ok(())
+}
+
+/// Deliver into `<account>/<sent>`, index, and tag `+sent +<account>`
+fn store(cfg: &Config, sendmail_args: &[String], raw: &[u8]) -> Result<()> {
+    let message = mime::parse(raw)?;
+    let account = account_for(&cfg.accounts, &message, sendmail_args)
+        .context("No [[accounts]] entry to store sent mail in")?;
+    let folder = import::maildir_root(cfg)
+        .join(account.dir())
+        .join(account.sent_folder());
+    maildir::deliver(&folder, raw, Some("S"))?;
+    import::index()?;
+
+    match message.message_id() {
+        Some(id) => notmuch::tag_batch(&[tag_line(&account.name, id)]),
+        None => {
+            eprintln!("{} no Message-ID, stored untagged", color::warn());
+            Ok(())
+        }
+    }
+}
+
+/// Account named by msmtp's `-a`/`--account`, else the one owning the From address, else the first
+fn account_for<'a>(
+    accounts: &'a [Account],
+    message: &Message,
+    args: &[String],
+) -> Option<&'a Account> {
+    let named = args
+        .iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.as_str() {
+            "-a" | "--account" => args.get(i + 1).map(String::as_str),
+            _ => arg.strip_prefix("--account="),
+        });
+    if let Some(name) = named {
+        return accounts.iter().find(|a| a.name == name);
+    }
+
+    let from = message
+        .from()
+        .and_then(|f| f.first())
+        .and_then(|a| a.address())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    accounts
+        .iter()
+        .find(|a| {
+            a.addresses
+                .iter()
+                .any(|addr| addr.eq_ignore_ascii_case(&from))
+        })
+        .or(accounts.first())
+}
+
+fn tag_line(account: &str, message_id: &str) -> String {
+    format!(
+        "+sent +{} -inbox -unread -- {}",
+        account,
+        notmuch::quote_id(message_id)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] =
+        b"From: Me <Me@Work.example>\r\nTo: a@x\r\nMessage-ID: <m1@work>\r\n\r\nhi\r\n";
+
+    fn accounts() -> Vec<Account> {
+        Config::parse(
+            r#"
+[[accounts]]
+name = "personal"
+
+[[accounts]]
+name = "work"
+addresses = ["me@work.example"]
+sent = "Sent Items"
+"#,
+        )
+        .unwrap()
+        .accounts
+    }
+
+    #[test]
+    fn test_account_for() {
+        let accounts = accounts();
+        let msg = mime::parse(RAW).unwrap();
+        let by_from = account_for(&accounts, &msg, &[]).unwrap();
+        assert_eq!(by_from.name, "work");
+        assert_eq!(by_from.sent_folder(), "Sent Items");
+
+        let args = vec!["-a".to_string(), "personal".to_string(), "--".to_string()];
+        assert_eq!(
+            account_for(&accounts, &msg, &args).unwrap().name,
+            "personal"
+        );
+        assert!(account_for(&accounts, &msg, &["--account=none".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_tag_line() {
+        assert_eq!(
+            tag_line("work", "m1@work"),
+            "+sent +work -inbox -unread -- id:\"m1@work\""
+        );
+    }
+}
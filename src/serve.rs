@@ -0,0 +1,469 @@
+//! Local HTTP preview server (sanitized HTML rendering, attachment downloads)
+//!
+//! Only `127.0.0.1:<port>` and `localhost:<port>` are accepted as Host (so a page on another
+//! site can't reach the server through DNS rebinding), and every request must carry the random
+//! token printed at startup.
+
+use anyhow::{Context, Result};
+use mail_parser::{Message, MimeHeaders, PartType};
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::color::escape_html as escape;
+use crate::error::{self, Error};
+use crate::{color, mime, notmuch, process, remote, sanitize, tracker};
+
+/// Arguments for `mu serve`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Port to listen on (localhost only)
+    #[arg(short, long, default_value = "8377")]
+    port: u16,
+}
+
+/// Locks rendered mail down: inline styles only, no scripts, no remote loads
+const CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; img-src data:; frame-src 'self'";
+
+const PAGE_STYLE: &str = "body{font:15px sans-serif;max-width:60em;margin:1em auto}\
+table.h td{padding:0 .5em}iframe{width:100%;height:70vh;border:1px solid #ccc}";
+
+/// The port the server listens on and the token every request must carry
+struct Session {
+    port: u16,
+    token: String,
+}
+
+impl Session {
+    fn new(port: u16) -> Self {
+        Self {
+            port,
+            token: token(),
+        }
+    }
+
+    /// The request names this server as its Host and carries the token
+    fn allows(&self, host: Option<&str>, token: Option<&str>) -> bool {
+        let host_ok = host.is_some_and(|host| {
+            ["127.0.0.1", "localhost"]
+                .iter()
+                .any(|name| host.eq_ignore_ascii_case(&format!("{}:{}", name, self.port)))
+        });
+        host_ok && token.is_some_and(|token| same(token.as_bytes(), self.token.as_bytes()))
+    }
+}
+
+/// 128 random bits as hex, from std's randomly keyed hasher
+fn token() -> String {
+    let state = RandomState::new();
+    (0..2u8)
+        .map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u8(i);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Compare without stopping at the first difference
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Serve the web UI until interrupted
+pub fn run(args: Args) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", args.port))
+        .with_context(|| format!("Failed to listen on port {}", args.port))?;
+    let session = Session::new(args.port);
+    eprintln!(
+        "{} Serving on http://127.0.0.1:{}/?token={}",
+        color::ok(),
+        args.port,
+        session.token
+    );
+
+    for stream in listener.incoming().flatten() {
+        if let Err(e) = handle(stream, &session) {
+            eprintln!("{} {:#}", color::fail(), e);
+        }
+    }
+    Ok(())
+}
+
+/// A GET request: its path, its query string and the Host header
+#[derive(Debug, Default, PartialEq)]
+struct Request {
+    path: String,
+    query: String,
+    host: Option<String>,
+}
+
+impl Request {
+    /// The request line and headers (only GET without a body is supported)
+    fn read(reader: &mut impl BufRead) -> Result<Self> {
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Drain headers, keeping Host
+        let mut host = None;
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("host")
+            {
+                host = Some(value.trim().to_string());
+            }
+            line.clear();
+        }
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Ok(Self {
+            path: path.to_string(),
+            query: query.to_string(),
+            host,
+        })
+    }
+
+    /// A query parameter, percent-decoded
+    fn param(&self, name: &str) -> Option<String> {
+        self.query.split('&').find_map(|pair| {
+            let (k, v) = pair.split_once('=')?;
+            (k == name).then(|| tracker::percent_decode(v))
+        })
+    }
+}
+
+fn handle(mut stream: TcpStream, session: &Session) -> Result<()> {
+    let request = Request::read(&mut BufReader::new(&stream))?;
+    if !session.allows(request.host.as_deref(), request.param("token").as_deref()) {
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            b"forbidden",
+            &[],
+        );
+    }
+    route(&mut stream, &request, &session.token)
+}
+
+/// Answer an allowed request with the page for its path
+fn route(stream: &mut TcpStream, request: &Request, token: &str) -> Result<()> {
+    let id = || request.param("id").unwrap_or_default();
+    let result = match request.path.as_str() {
+        "/" => index_page(
+            &request
+                .param("q")
+                .unwrap_or_else(|| "tag:inbox".to_string()),
+            token,
+        ),
+        "/message" => message_page(&id(), token),
+        "/body" => body_frame(&id()),
+        "/part" => {
+            let n = request.param("n").and_then(|n| n.parse().ok()).unwrap_or(0);
+            return download(stream, &id(), n);
+        }
+        _ => {
+            return respond(stream, "404 Not Found", "text/plain", b"not found", &[]);
+        }
+    };
+
+    match result {
+        Ok(html) => respond(
+            stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            html.as_bytes(),
+            &[],
+        ),
+        Err(e) => {
+            let body = page(
+                "Error",
+                &format!("<p>{}</p>", escape(&format!("{:#}", e))),
+                token,
+            );
+            respond(
+                stream,
+                "500 Internal Server Error",
+                "text/html; charset=utf-8",
+                body.as_bytes(),
+                &[],
+            )
+        }
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    extra: &[String],
+) -> Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nContent-Security-Policy: {}\r\nConnection: close\r\n",
+        status,
+        content_type,
+        body.len(),
+        CSP
+    );
+    for h in extra {
+        head.push_str(h);
+        head.push_str("\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+fn page(title: &str, content: &str, token: &str) -> String {
+    format!(
+        "<!doctype html><html><head><meta charset=utf-8><title>{} - mu</title><style>{}</style></head>\
+<body><form action=/><input type=hidden name=token value={}>\
+<input name=q size=50 placeholder=\"notmuch query or message-id\"> \
+<button>Search</button></form>{}</body></html>",
+        escape(title),
+        PAGE_STYLE,
+        token,
+        content
+    )
+}
+
+/// Search results (a bare message-id jumps straight to the message)
+fn index_page(query: &str, token: &str) -> Result<String> {
+    if query.contains('@') && !query.contains(':') {
+        return message_page(query, token);
+    }
+    let output = process::run(&mut remote::command(
+        "notmuch",
+        &["search", "--format=json", "--limit=50", "--", query],
+    ))
+    .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !output.status.success() {
+        return Err(Error::command_failed("notmuch search", &output.stderr).into());
+    }
+    let threads: Value = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
+
+    let rows: String = threads
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|t| {
+            let field = |name: &str| escape(t[name].as_str().unwrap_or(""));
+            format!(
+                "<li><a href=\"/message?id=thread:{}&token={}\">{}</a> — {} <small>{}</small></li>",
+                field("thread"),
+                token,
+                field("subject"),
+                field("authors"),
+                field("date_relative")
+            )
+        })
+        .collect();
+    Ok(page(
+        query,
+        &format!("<h2>{}</h2><ul>{}</ul>", escape(query), rows),
+        token,
+    ))
+}
+
+/// Headers, sandboxed body frame and attachment list
+fn message_page(id: &str, token: &str) -> Result<String> {
+    let raw = notmuch::raw(&notmuch::query_for(id))?;
+    let message = mime::parse(&raw)?;
+    let id_param = escape(&encode(id));
+
+    let mut content = String::from("<table class=h>");
+    for name in ["From", "To", "Cc", "Date", "Subject"] {
+        if let Some(value) = message.header_raw(name) {
+            content.push_str(&format!(
+                "<tr><td><b>{}</b></td><td>{}</td></tr>",
+                name,
+                escape(&mime::decode_words(value))
+            ));
+        }
+    }
+    content.push_str("</table>");
+    content.push_str(&format!(
+        "<iframe sandbox src=\"/body?id={}&token={}\"></iframe>",
+        id_param, token
+    ));
+
+    let attachments: String = attachment_list(&message)
+        .iter()
+        .map(|(n, name, ct, size)| {
+            format!(
+                "<li><a href=\"/part?id={}&n={}&token={}\">{}</a> <small>{}, {} bytes</small></li>",
+                id_param,
+                n,
+                token,
+                escape(name),
+                escape(ct),
+                size
+            )
+        })
+        .collect();
+    if !attachments.is_empty() {
+        content.push_str(&format!("<h3>Attachments</h3><ul>{}</ul>", attachments));
+    }
+    Ok(page(
+        message.subject().unwrap_or("(no subject)"),
+        &content,
+        token,
+    ))
+}
+
+/// `(part index, name, content type, size)` for every attachment
+fn attachment_list(message: &Message) -> Vec<(usize, String, String, usize)> {
+    message
+        .parts
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.attachment_name().is_some() || matches!(p.body, PartType::Binary(_)))
+        .map(|(i, p)| {
+            (
+                i,
+                p.attachment_name().unwrap_or("unnamed").to_string(),
+                mime::content_type(p),
+                p.len(),
+            )
+        })
+        .collect()
+}
+
+/// The message body as a standalone (sanitized) document for the iframe
+fn body_frame(id: &str) -> Result<String> {
+    let raw = notmuch::raw(&notmuch::query_for(id))?;
+    let message = mime::parse(&raw)?;
+    if let Some(part) = mime::find_part(&message, "text/html") {
+        return Ok(sanitize::html(&mime::part_text(part)));
+    }
+    let text = mime::find_part(&message, "text/plain")
+        .map(mime::part_text)
+        .unwrap_or_default();
+    Ok(format!(
+        "<pre style=\"white-space:pre-wrap\">{}</pre>",
+        escape(&text)
+    ))
+}
+
+/// Send one decoded MIME part as a download
+fn download(stream: &mut TcpStream, id: &str, n: usize) -> Result<()> {
+    let raw = notmuch::raw(&notmuch::query_for(id))?;
+    let message = mime::parse(&raw)?;
+    let Some(part) = message.parts.get(n) else {
+        return respond(stream, "404 Not Found", "text/plain", b"no such part", &[]);
+    };
+    let name = part
+        .attachment_name()
+        .unwrap_or("part")
+        .replace(['"', '\r', '\n'], "_");
+    let disposition = format!("Content-Disposition: attachment; filename=\"{}\"", name);
+    respond(
+        stream,
+        "200 OK",
+        &mime::content_type(part),
+        part.contents(),
+        &[disposition],
+    )
+}
+
+/// Percent-encode a query parameter value
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'@' | b':' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner;
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let id = "a+b c/d@example.com";
+        assert_eq!(encode(id), "a%2Bb%20c%2Fd@example.com");
+        assert_eq!(tracker::percent_decode(&encode(id)), id);
+    }
+
+    #[test]
+    fn test_request_read() {
+        let raw = "GET /message?id=a%40x&token=t HTTP/1.1\r\nHOST: 127.0.0.1:8025\r\n\
+Accept: */*\r\n\r\n";
+        let request = Request::read(&mut raw.as_bytes()).unwrap();
+        assert_eq!(request.path, "/message");
+        assert_eq!(request.host.as_deref(), Some("127.0.0.1:8025"));
+        assert_eq!(request.param("id").as_deref(), Some("a@x"));
+        assert_eq!(request.param("n"), None);
+        let bare = Request::read(&mut "GET / HTTP/1.0\r\n\r\n".as_bytes()).unwrap();
+        assert_eq!((bare.path.as_str(), bare.host), ("/", None));
+    }
+
+    #[test]
+    fn test_session_allows() {
+        let session = Session::new(8377);
+        let token = session.token.clone();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, Session::new(8377).token);
+        assert!(session.allows(Some("127.0.0.1:8377"), Some(&token)));
+        assert!(session.allows(Some("localhost:8377"), Some(&token)));
+        // DNS rebinding: the browser sends the attacker's host name
+        assert!(!session.allows(Some("evil.example:8377"), Some(&token)));
+        assert!(!session.allows(Some("localhost:9000"), Some(&token)));
+        assert!(!session.allows(None, Some(&token)));
+        assert!(!session.allows(Some("127.0.0.1:8377"), None));
+        assert!(!session.allows(Some("127.0.0.1:8377"), Some("0000")));
+    }
+
+    #[test]
+    fn test_index_page_ends_options() {
+        let replay = runner::Replay::parse(
+            r#"{"program":"notmuch","args":["search","--format=json","--limit=50","--","--help"],"status":0,"stdout":"[]"}"#,
+        )
+        .unwrap();
+        let html = runner::scoped(replay, || index_page("--help", "t0k")).unwrap();
+        assert!(html.contains("name=token value=t0k"));
+    }
+
+    #[test]
+    fn test_message_page_escapes_attachments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1");
+        std::fs::write(
+            &path,
+            "Subject: x\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nhi\r\n--b\r\nContent-Type: application/<u1>\r\n\
+Content-Disposition: attachment; filename=\"<u2>.pdf\"\r\n\r\n%PDF\r\n--b--\r\n",
+        )
+        .unwrap();
+        let recording = serde_json::json!({
+            "program": "notmuch",
+            "args": ["search", "--output=files", "--limit=1", "--", "id:a@x"],
+            "stdout": format!("{}\n", path.display()),
+        });
+        let replay = runner::Replay::parse(&recording.to_string()).unwrap();
+        let html = runner::scoped(replay, || message_page("a@x", "t0k")).unwrap();
+        assert!(html.contains("&lt;u2&gt;.pdf"));
+        assert!(!html.contains("<u1>") && !html.contains("<u2>"));
+    }
+
+    #[test]
+    fn test_attachment_list() {
+        let raw = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nhello\r\n--b\r\nContent-Type: application/pdf\r\n\
+Content-Disposition: attachment; filename=r.pdf\r\n\r\n%PDF\r\n--b--\r\n";
+        let msg = mime::parse(raw).unwrap();
+        assert_eq!(
+            attachment_list(&msg),
+            vec![(2, "r.pdf".to_string(), "application/pdf".to_string(), 4)]
+        );
+    }
+}
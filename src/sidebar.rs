@@ -0,0 +1,193 @@
+//! Per-folder unread/total counts for sidebars and status bars
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::{process, width};
+
+/// Arguments for `mu sidebar`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Plain)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+    /// `folder unread/total` lines (lf/ranger previews, scripts)
+    Plain,
+    /// neomutt `named-mailboxes` lines with unread counts in the label
+    Neomutt,
+    /// Single-line tmux status segment
+    Tmux,
+    /// JSON array
+    Json,
+}
+
+#[derive(Serialize)]
+struct Folder {
+    account: String,
+    folder: String,
+    /// Mailbox path relative to the notmuch root
+    path: String,
+    unread: u64,
+    total: u64,
+}
+
+/// Print folder counts for all configured accounts
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    if cfg.accounts.is_empty() {
+        anyhow::bail!("No [[accounts]] configured in ~/.config/mu/config.toml");
+    }
+
+    // Two queries per folder: total, unread
+    let mut queries = Vec::new();
+    let mut folders = Vec::new();
+    for acct in &cfg.accounts {
+        for folder in &acct.folders {
+            let q = acct.folder_query(folder);
+            queries.push(q.clone());
+            queries.push(format!("{} and tag:unread", q));
+            folders.push(Folder {
+                account: acct.name.clone(),
+                folder: folder.clone(),
+                path: format!("{}/{}", acct.dir(), folder),
+                unread: 0,
+                total: 0,
+            });
+        }
+    }
+
+    let counts = count_batch(&queries)?;
+    for (folder, pair) in folders.iter_mut().zip(counts.chunks(2)) {
+        folder.total = pair.first().copied().unwrap_or(0);
+        folder.unread = pair.get(1).copied().unwrap_or(0);
+    }
+
+    println!("{}", format_output(&folders, args.format)?);
+    Ok(())
+}
+
+/// Run `notmuch count --batch` (one query per line, one count per line)
+fn count_batch(queries: &[String]) -> Result<Vec<u64>> {
+    let input: String = queries.iter().map(|q| format!("{}\n", q)).collect();
+    let output = process::run_with_input(
+        Command::new("notmuch").args(["count", "--batch"]),
+        input.as_bytes(),
+    )
+    .context("Failed to run notmuch count")?;
+    if !output.status.success() {
+        anyhow::bail!("notmuch count failed");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim().parse().unwrap_or(0))
+        .collect())
+}
+
+fn format_output(folders: &[Folder], format: Format) -> Result<String> {
+    let out = match format {
+        Format::Plain => {
+            let columns = folders
+                .iter()
+                .map(|f| width::width(&f.path))
+                .max()
+                .unwrap_or(0);
+            folders
+                .iter()
+                .map(|f| format!("{}  {}/{}", width::pad(&f.path, columns), f.unread, f.total))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        Format::Neomutt => folders
+            .iter()
+            .map(|f| {
+                let label = match f.unread {
+                    0 => format!("{}/{}", f.account, f.folder),
+                    n => format!("{}/{} ({})", f.account, f.folder, n),
+                };
+                format!("named-mailboxes \"{}\" \"+{}\"", label, f.path)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Tmux => tmux_segment(folders),
+        Format::Json => serde_json::to_string_pretty(folders)?,
+    };
+    Ok(out)
+}
+
+/// `✉ 5 (work 3 · home 2)`, or empty when nothing is unread
+fn tmux_segment(folders: &[Folder]) -> String {
+    let mut per_account: Vec<(String, u64)> = Vec::new();
+    for f in folders.iter().filter(|f| f.unread > 0) {
+        match per_account.iter_mut().find(|(a, _)| *a == f.account) {
+            Some((_, n)) => *n += f.unread,
+            None => per_account.push((f.account.clone(), f.unread)),
+        }
+    }
+
+    let total: u64 = per_account.iter().map(|(_, n)| n).sum();
+    if total == 0 {
+        return String::new();
+    }
+    let detail = per_account
+        .iter()
+        .map(|(a, n)| format!("{} {}", a, n))
+        .collect::<Vec<_>>()
+        .join(" · ");
+    format!("✉ {} ({})", total, detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folders() -> Vec<Folder> {
+        vec![
+            Folder {
+                account: "work".into(),
+                folder: "INBOX".into(),
+                path: "Work/INBOX".into(),
+                unread: 3,
+                total: 120,
+            },
+            Folder {
+                account: "home".into(),
+                folder: "INBOX".into(),
+                path: "home/INBOX".into(),
+                unread: 0,
+                total: 5,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_format_plain_and_neomutt() {
+        let plain = format_output(&folders(), Format::Plain).unwrap();
+        assert_eq!(plain.lines().next().unwrap(), "Work/INBOX  3/120");
+        let nm = format_output(&folders(), Format::Neomutt).unwrap();
+        assert!(nm.contains("named-mailboxes \"work/INBOX (3)\" \"+Work/INBOX\""));
+        assert!(nm.contains("named-mailboxes \"home/INBOX\" \"+home/INBOX\""));
+    }
+
+    #[test]
+    fn test_tmux_segment() {
+        assert_eq!(tmux_segment(&folders()), "✉ 3 (work 3)");
+        assert_eq!(tmux_segment(&[]), "");
+    }
+
+    #[test]
+    fn test_run_without_accounts() {
+        let args = Args {
+            format: Format::Plain,
+        };
+        assert!(run_with(args, &Config::default()).is_err());
+    }
+}
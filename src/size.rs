@@ -0,0 +1,380 @@
+//! Largest messages and attachments, with interactive cleanup
+
+use anyhow::{Context, Result};
+use mail_parser::{MessageParser, MimeHeaders, PartType};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::error::{self, Error};
+use crate::{color, import, maildir, mime, notmuch, process, remote};
+
+/// Arguments for `mu size`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Number of messages and attachments to list
+    #[arg(short, long, default_value = "50")]
+    top: usize,
+
+    /// Walk the largest messages and delete them or strip their attachments
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// Maildir root (default: notmuch database.path, then `[index] maildir`)
+    #[arg(short, long)]
+    maildir: Option<String>,
+}
+
+/// A message file and its size
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    /// Folder relative to the root (`account/INBOX`)
+    folder: String,
+}
+
+/// Report the largest messages, folders and attachments
+pub fn run(args: Args) -> Result<()> {
+    let root = match &args.maildir {
+        Some(dir) => config::expand_tilde(dir),
+        None => import::maildir_root(&Config::load()?),
+    };
+    let mut entries: Vec<Entry> = maildir::files(&root)?
+        .into_iter()
+        .filter_map(|path| {
+            let size = std::fs::metadata(&path).ok()?.len();
+            let folder = folder_of(&root, &path);
+            Some(Entry { path, size, folder })
+        })
+        .collect();
+    entries.sort_by_key(|e| Reverse(e.size));
+
+    print_totals(
+        "Accounts",
+        &totals(&entries, |f| f.split('/').next().unwrap_or(f)),
+    );
+    print_totals("Folders", &totals(&entries, |f| f));
+
+    let top = &entries[..args.top.min(entries.len())];
+    println!("{}", color::header("Largest messages"));
+    for (i, e) in top.iter().enumerate() {
+        println!(
+            "{:>3}. {:>9}  {:<24} {}",
+            i + 1,
+            mime::format_size(e.size),
+            e.folder,
+            summary(&e.path)
+        );
+    }
+
+    print_attachments(top, args.top);
+
+    if args.interactive {
+        interactive(top)?;
+    }
+    Ok(())
+}
+
+/// Largest attachments within the given messages
+fn print_attachments(top: &[Entry], limit: usize) {
+    let mut attachments: Vec<(u64, String, String)> = top
+        .iter()
+        .flat_map(|e| {
+            attachments(&e.path)
+                .into_iter()
+                .map(|(name, size)| (size, name, e.folder.clone()))
+        })
+        .collect();
+    attachments.sort_by_key(|a| Reverse(a.0));
+    println!("\n{}", color::header("Largest attachments"));
+    for (size, name, folder) in attachments.iter().take(limit) {
+        println!("{:>9}  {:<24} {}", mime::format_size(*size), folder, name);
+    }
+}
+
+/// Folder of a message file relative to the root, without cur/new
+pub fn folder_of(root: &Path, path: &Path) -> String {
+    let dir = path.parent().and_then(Path::parent).unwrap_or(root);
+    let rel = dir
+        .strip_prefix(root)
+        .unwrap_or(dir)
+        .to_string_lossy()
+        .to_string();
+    if rel.is_empty() { ".".to_string() } else { rel }
+}
+
+/// Total size and count per key, largest first
+fn totals<'a>(
+    entries: &'a [Entry],
+    key: impl Fn(&'a str) -> &'a str,
+) -> Vec<(&'a str, u64, usize)> {
+    let mut map: BTreeMap<&str, (u64, usize)> = BTreeMap::new();
+    for e in entries {
+        let t = map.entry(key(&e.folder)).or_default();
+        t.0 += e.size;
+        t.1 += 1;
+    }
+    let mut totals: Vec<(&str, u64, usize)> =
+        map.into_iter().map(|(k, (s, c))| (k, s, c)).collect();
+    totals.sort_by_key(|t| Reverse(t.1));
+    totals
+}
+
+fn print_totals(title: &str, totals: &[(&str, u64, usize)]) {
+    println!("{}", color::header(title));
+    for (name, size, count) in totals.iter().take(20) {
+        println!(
+            "{:>9}  {:>6} msgs  {}",
+            mime::format_size(*size),
+            count,
+            name
+        );
+    }
+    println!();
+}
+
+/// `From — Subject` for listings
+fn summary(path: &Path) -> String {
+    let Ok(raw) = std::fs::read(path) else {
+        return String::new();
+    };
+    let Some(headers) = MessageParser::default().parse_headers(&raw) else {
+        return String::new();
+    };
+    let from = headers
+        .from()
+        .and_then(|a| a.first())
+        .and_then(|a| a.name().or(a.address()));
+    format!(
+        "{} — {}",
+        from.unwrap_or("?"),
+        headers.subject().unwrap_or("(no subject)")
+    )
+}
+
+/// `(name, decoded size)` of each attachment in a message file
+fn attachments(path: &Path) -> Vec<(String, u64)> {
+    let Ok(raw) = std::fs::read(path) else {
+        return Vec::new();
+    };
+    let Ok(message) = mime::parse(&raw) else {
+        return Vec::new();
+    };
+    message
+        .attachments()
+        .map(|p| {
+            (
+                p.attachment_name().unwrap_or("unnamed").to_string(),
+                p.len() as u64,
+            )
+        })
+        .collect()
+}
+
+/// Prompt for each message: delete, strip attachments, skip or quit
+fn interactive(entries: &[Entry]) -> Result<()> {
+    let stdin = io::stdin();
+    for e in entries {
+        print!(
+            "{} {} — [d]elete, [s]trip attachments, [n]ext, [q]uit? ",
+            mime::format_size(e.size),
+            summary(&e.path)
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            break;
+        }
+        match answer.trim() {
+            "d" => {
+                std::fs::remove_file(&e.path)
+                    .with_context(|| format!("Failed to delete {}", e.path.display()))?;
+                eprintln!(
+                    "{} deleted (run `notmuch new` to update the index)",
+                    color::ok()
+                );
+            }
+            "s" => {
+                let raw = std::fs::read(&e.path)?;
+                let stripped = strip_attachments(&raw)?;
+                replace(&e.path, &stripped)?;
+                if let Err(e) = reindex(&raw) {
+                    tracing::warn!(error = %format!("{:#}", e), "reindex failed");
+                }
+                eprintln!(
+                    "{} {} → {}",
+                    color::ok(),
+                    mime::format_size(e.size),
+                    mime::format_size(stripped.len() as u64)
+                );
+            }
+            "q" => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite a message with each attachment replaced by a short text/plain note
+fn strip_attachments(raw: &[u8]) -> Result<Vec<u8>> {
+    let message = mime::parse(raw)?;
+    let eol = if raw.windows(2).any(|w| w == b"\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    // Attachments (by disposition or file name, whatever their type) among the leaf parts
+    // after the root, replaced back to front so offsets stay valid
+    let mut spans: Vec<(usize, usize, String)> = message
+        .parts
+        .iter()
+        .skip(1)
+        .filter(|p| !matches!(p.body, PartType::Multipart(_)))
+        .filter(|p| {
+            p.content_disposition().is_some_and(|d| d.is_attachment())
+                || p.attachment_name().is_some()
+        })
+        .map(|p| {
+            let name = p.attachment_name().unwrap_or("unnamed");
+            let note = format!(
+                "Content-Type: text/plain; charset=utf-8{eol}{eol}[attachment removed by mu: {} ({}, {})]",
+                name,
+                mime::content_type(p),
+                mime::format_size(p.len() as u64)
+            );
+            (p.offset_header as usize, p.offset_end as usize, note)
+        })
+        .collect();
+    spans.sort_by_key(|s| Reverse(s.0));
+
+    let mut out = raw.to_vec();
+    for (start, end, note) in spans {
+        out.splice(start..end.min(out.len()), note.into_bytes());
+    }
+    Ok(out)
+}
+
+/// Rewrite a message file in place: written to the maildir's `tmp/`, then renamed over it
+fn replace(path: &Path, contents: &[u8]) -> Result<()> {
+    let (Some(name), Some(folder)) = (path.file_name(), path.parent().and_then(Path::parent))
+    else {
+        anyhow::bail!("Not a maildir file: {}", path.display());
+    };
+    let tmp_dir = folder.join("tmp");
+    std::fs::create_dir_all(&tmp_dir)
+        .with_context(|| format!("Failed to create {}", tmp_dir.display()))?;
+    let tmp = tmp_dir.join(name);
+    std::fs::write(&tmp, contents).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Tell notmuch the message in `raw` changed
+///
+/// It's found by Message-ID (a `path:` query matches folders, not files); without one
+/// there's nothing to reindex.
+fn reindex(raw: &[u8]) -> Result<()> {
+    let Some(query) = reindex_query(raw) else {
+        return Ok(());
+    };
+    let output = process::run(&mut remote::command("notmuch", &["reindex", "--", &query]))
+        .map_err(|e| error::spawn_failed("notmuch", e))?;
+    if !output.status.success() {
+        return Err(Error::command_failed("notmuch reindex", &output.stderr).into());
+    }
+    Ok(())
+}
+
+/// The quoted `id:` query for the message in `raw`
+fn reindex_query(raw: &[u8]) -> Option<String> {
+    let message = MessageParser::default().parse_headers(raw)?;
+    Some(notmuch::quote_id(message.message_id()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    const RAW: &[u8] = b"Subject: x\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/plain\r\n\r\nhello\r\n--b\r\nContent-Type: application/pdf\r\n\
+Content-Disposition: attachment; filename=big.pdf\r\nContent-Transfer-Encoding: base64\r\n\r\n\
+JVBERi0xLjQKJeLjz9MK\r\n--b--\r\n";
+
+    #[test]
+    fn test_strip_attachments() {
+        let stripped = strip_attachments(RAW).unwrap();
+        let msg = mime::parse(&stripped).unwrap();
+        assert_eq!(msg.attachments().count(), 0);
+        assert_eq!(mime::part_text(&msg.parts[1]).trim(), "hello");
+        let note = mime::part_text(&msg.parts[2]);
+        assert!(note.contains("attachment removed by mu: big.pdf (application/pdf, 15 B)"));
+        assert!(String::from_utf8_lossy(&stripped).ends_with("B)]\r\n--b--\r\n"));
+
+        // Text and HTML files go too; the inline body stays
+        let raw = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\
+Content-Type: text/html\r\n\r\n<p>hello</p>\r\n--b\r\nContent-Type: text/html\r\n\
+Content-Disposition: attachment; filename=report.html\r\n\r\n<p>report</p>\r\n--b\r\n\
+Content-Type: text/csv; name=data.csv\r\n\r\na,b\r\n--b--\r\n";
+        let stripped = String::from_utf8(strip_attachments(raw).unwrap()).unwrap();
+        assert!(stripped.contains("<p>hello</p>"));
+        assert!(stripped.contains("removed by mu: report.html (text/html"));
+        assert!(stripped.contains("removed by mu: data.csv (text/csv"));
+    }
+
+    #[test]
+    fn test_reindex_query() {
+        let raw = b"Message-ID: <a\"b c@x>\r\nSubject: x\r\n\r\nhi\r\n";
+        assert_eq!(reindex_query(raw).as_deref(), Some("id:\"a\"\"b c@x\""));
+        assert_eq!(reindex_query(b"Subject: x\r\n\r\nhi\r\n"), None);
+        let recording = r#"{"program":"notmuch","args":["reindex","--","id:\"a\"\"b c@x\""]}"#;
+        let replay = Replay::parse(recording).unwrap();
+        runner::scoped(replay, || reindex(raw)).unwrap();
+        let failing = Replay::parse(&recording.replace("}", r#","status":1}"#)).unwrap();
+        assert!(runner::scoped(failing, || reindex(raw)).is_err());
+    }
+
+    #[test]
+    fn test_replace_goes_through_tmp() {
+        let dir = std::env::temp_dir().join(format!("mu-size-{}", std::process::id()));
+        let path = maildir::deliver(&dir, RAW, Some("S")).unwrap();
+        replace(&path, b"Subject: x\r\n\r\nsmall\r\n").unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"Subject: x\r\n\r\nsmall\r\n"
+        );
+        assert_eq!(std::fs::read_dir(dir.join("tmp")).unwrap().count(), 0);
+        assert_eq!(std::fs::read_dir(dir.join("cur")).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_folder_of_and_totals() {
+        let root = Path::new("/m");
+        assert_eq!(
+            folder_of(root, Path::new("/m/work/INBOX/cur/1:2,S")),
+            "work/INBOX"
+        );
+        let entries = vec![
+            Entry {
+                path: "a".into(),
+                size: 10,
+                folder: "work/INBOX".into(),
+            },
+            Entry {
+                path: "b".into(),
+                size: 30,
+                folder: "home/INBOX".into(),
+            },
+            Entry {
+                path: "c".into(),
+                size: 25,
+                folder: "work/Sent".into(),
+            },
+        ];
+        let accounts = totals(&entries, |f| f.split('/').next().unwrap_or(f));
+        assert_eq!(accounts, vec![("work", 35, 2), ("home", 30, 1)]);
+    }
+}
@@ -0,0 +1,200 @@
+//! Thread summaries through a user-configured command (local LLM, fabric, a script)
+
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use crate::config::{self, Config, Summarize};
+use crate::{color, mime, notmuch, process, remote};
+
+/// Arguments for `mu summarize`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Thread ID or notmuch query (reads a raw message from stdin if not provided)
+    thread: Option<String>,
+
+    /// fzf preview mode: print a TL;DR block for large threads only, never fail
+    #[arg(long)]
+    preview: bool,
+
+    /// Ignore the cached summary
+    #[arg(long)]
+    refresh: bool,
+}
+
+/// Print a summary of the thread
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?;
+    if args.preview {
+        // A broken summarizer must not break the preview window
+        if let Ok(Some(summary)) = preview(&args, &cfg.summarize) {
+            println!("{}\n{}\n", color::header("TL;DR"), summary.trim_end());
+        }
+        return Ok(());
+    }
+    run_with(&args, &cfg.summarize)
+}
+
+fn run_with(args: &Args, cfg: &Summarize) -> Result<()> {
+    let text = match &args.thread {
+        Some(thread) => thread_text(thread)?,
+        None => stdin_text()?,
+    };
+    println!("{}", summary(cfg, &text, args.refresh)?.trim_end());
+    Ok(())
+}
+
+/// Summary for the preview window, or None for small threads
+fn preview(args: &Args, cfg: &Summarize) -> Result<Option<String>> {
+    let Some(thread) = &args.thread else {
+        return Ok(None);
+    };
+    if cfg.command.is_none() || message_count(thread)? < cfg.preview_min_messages {
+        return Ok(None);
+    }
+    summary(cfg, &thread_text(thread)?, args.refresh).map(Some)
+}
+
+/// Cached summary of the text, running the configured command on a miss
+fn summary(cfg: &Summarize, text: &str, refresh: bool) -> Result<String> {
+    let command = cfg
+        .command
+        .as_deref()
+        .context("No summarizer configured (set [summarize] command in config.toml)")?;
+    let path = cache_path(command, text);
+    if !refresh && let Ok(cached) = std::fs::read_to_string(&path) {
+        return Ok(cached);
+    }
+
+    let summary = pipe(command, text)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, &summary)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(summary)
+}
+
+/// Cache file keyed by command and thread text (new replies invalidate it)
+fn cache_path(command: &str, text: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    command.hash(&mut hasher);
+    text.hash(&mut hasher);
+    config::data_dir()
+        .join("summaries")
+        .join(format!("{:016x}.txt", hasher.finish()))
+}
+
+/// Run the summarizer with the text on stdin
+fn pipe(command: &str, text: &str) -> Result<String> {
+    let output = process::run_with_input(&mut process::shell(command), text.as_bytes())
+        .with_context(|| format!("Failed to run {}", command))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed", command);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn message_count(thread: &str) -> Result<usize> {
+    let output = process::run(&mut remote::command("notmuch", &["count", thread]))
+        .context("Failed to run notmuch count")?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .unwrap_or(0))
+}
+
+/// Whole thread as text, oldest message first (HTML-only parts rendered)
+fn thread_text(thread: &str) -> Result<String> {
+    let mut text = Vec::new();
+    for path in notmuch::thread_files(thread)? {
+        let raw = remote::read(&path)?;
+        text.push(mime::as_text(&mime::parse(&raw)?)?);
+    }
+    Ok(text.join("\n").trim().to_string())
+}
+
+/// Headers and body of a raw message on stdin
+fn stdin_text() -> Result<String> {
+    let mut raw = Vec::new();
+    io::stdin().read_to_end(&mut raw)?;
+    mime::as_text(&mime::parse(&raw)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_summary_cached() {
+        let cfg = Summarize {
+            command: Some("tr a-z A-Z".to_string()),
+            preview_min_messages: 8,
+        };
+        let text = format!("cache test {}", std::process::id());
+        assert_eq!(summary(&cfg, &text, true).unwrap(), text.to_uppercase());
+        let path = cache_path("tr a-z A-Z", &text);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text.to_uppercase());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preview_only_for_long_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a");
+        let body = format!("preview test {}", std::process::id());
+        std::fs::write(&file, format!("Subject: Plans\r\n\r\n{}\r\n", body)).unwrap();
+        let replay = |count: usize| {
+            let lines = [
+                serde_json::json!({
+                    "program": "notmuch",
+                    "args": ["count", "thread:0a"],
+                    "stdout": format!("{}\n", count),
+                }),
+                serde_json::json!({
+                    "program": "notmuch",
+                    "args": ["search", "--output=files", "--duplicate=1", "--sort=oldest-first",
+                             "--", "thread:0a"],
+                    "stdout": format!("{}\n", file.display()),
+                }),
+                serde_json::json!({
+                    "program": "sh",
+                    "args": ["-c", "summarize"],
+                    "stdout": "They made plans.\n",
+                }),
+            ];
+            let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+            Replay::parse(&lines.join("\n")).unwrap()
+        };
+        let args = Args {
+            thread: Some("thread:0a".to_string()),
+            preview: true,
+            refresh: true,
+        };
+        let cfg = Summarize {
+            command: Some("summarize".to_string()),
+            preview_min_messages: 8,
+        };
+        let short = runner::scoped(replay(3), || preview(&args, &cfg)).unwrap();
+        assert_eq!(short, None);
+        let long = runner::scoped(replay(12), || preview(&args, &cfg)).unwrap();
+        assert_eq!(long.as_deref(), Some("They made plans.\n"));
+        let text = runner::scoped(replay(12), || thread_text("thread:0a")).unwrap();
+        assert!(text.contains(&body));
+        let _ = std::fs::remove_file(cache_path("summarize", &text));
+
+        // Without a summarizer nothing is asked of notmuch
+        let unset = Summarize {
+            command: None,
+            ..cfg
+        };
+        assert_eq!(
+            runner::scoped(Replay::new(Vec::new()), || preview(&args, &unset)).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Bulk mail sweep: archive or delete old newsletters and promotions
+
+use anyhow::{Context, Result};
+use mail_parser::{Message, MessageParser};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, Sweep, SweepAction};
+use crate::{color, mime, notmuch};
+
+/// Return-Path/From domains of common email service providers
+const ESP_DOMAINS: &[&str] = &[
+    "mcsv.net",
+    "mcdlv.net",
+    "rsgsv.net",
+    "mandrillapp.com",
+    "sendgrid.net",
+    "mailgun.org",
+    "amazonses.com",
+    "sparkpostmail.com",
+    "constantcontact.com",
+    "hubspotemail.net",
+    "klaviyomail.com",
+    "exacttarget.com",
+    "sailthru.com",
+    "cmail19.com",
+    "cmail20.com",
+];
+
+/// Headers only bulk senders set
+const ESP_HEADERS: &[&str] = &[
+    "X-Mailchimp-Id",
+    "X-SG-EID",
+    "X-Mailgun-Sid",
+    "X-SES-Outgoing",
+    "X-Campaign",
+];
+
+/// Arguments for `mu sweep`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Show what would be swept without tagging anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Sweep old bulk mail out of the inbox
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let min_age = std::iter::once(&cfg.sweep.age)
+        .chain(cfg.sweep.senders.iter().filter_map(|r| r.age.as_ref()))
+        .map(|a| parse_age(a))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .min()
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    let mut report: BTreeMap<(String, &str), usize> = BTreeMap::new();
+    for id in inbox_ids(now - min_age)? {
+        let Ok(raw) = notmuch::raw(&id) else {
+            continue;
+        };
+        let Some(message) = MessageParser::default().parse_headers(&raw) else {
+            continue;
+        };
+        let sender = sender(&message);
+        let age = now - message.date().map(|d| d.to_timestamp()).unwrap_or(now);
+        let Some(action) = decide(&cfg.sweep, &message, &sender, age)? else {
+            continue;
+        };
+        let (tags, label) = match action {
+            SweepAction::Archive => ("-inbox", "archived"),
+            SweepAction::Delete => ("+deleted -inbox -unread", "deleted"),
+            SweepAction::Keep => continue,
+        };
+        lines.push(format!("{} -- {}", tags, id));
+        *report.entry((sender, label)).or_default() += 1;
+    }
+
+    for ((sender, label), count) in &report {
+        println!("{:>4}  {:<8}  {}", count, label, sender);
+    }
+    if !args.dry_run && !lines.is_empty() {
+        notmuch::tag_batch(&lines)?;
+    }
+    let verb = if args.dry_run { "Would sweep" } else { "Swept" };
+    eprintln!("{} {} {} messages", color::ok(), verb, lines.len());
+    Ok(())
+}
+
+/// Action for one inbox message, or None if it isn't bulk mail or isn't old enough
+fn decide(sweep: &Sweep, message: &Message, sender: &str, age: i64) -> Result<Option<SweepAction>> {
+    if !is_bulk(message) {
+        return Ok(None);
+    }
+    let rule = sweep
+        .senders
+        .iter()
+        .find(|r| sender.contains(&r.from.to_lowercase()));
+    let min_age = parse_age(rule.and_then(|r| r.age.as_deref()).unwrap_or(&sweep.age))?;
+    if age < min_age {
+        return Ok(None);
+    }
+    Ok(Some(rule.map(|r| r.action).unwrap_or(sweep.action)))
+}
+
+/// List-Unsubscribe, bulk precedence, or an email service provider's fingerprints
+fn is_bulk(message: &Message) -> bool {
+    let header = |name: &str| message.header_raw(name).map(|v| v.trim().to_lowercase());
+    if header("List-Unsubscribe").is_some() || ESP_HEADERS.iter().any(|h| header(h).is_some()) {
+        return true;
+    }
+    if header("Precedence").is_some_and(|p| matches!(p.as_str(), "bulk" | "list" | "junk")) {
+        return true;
+    }
+    let return_path = message.return_address().and_then(mime::domain_of);
+    return_path.is_some_and(|domain| {
+        ESP_DOMAINS
+            .iter()
+            .any(|esp| domain == *esp || domain.ends_with(&format!(".{}", esp)))
+    })
+}
+
+fn sender(message: &Message) -> String {
+    message
+        .from()
+        .and_then(|a| a.first())
+        .and_then(|a| a.address())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+/// Inbox message ids (`id:...`) dated before the cutoff
+fn inbox_ids(before: i64) -> Result<Vec<String>> {
+    notmuch::search("messages", &format!("tag:inbox and date:..@{}", before))
+}
+
+/// `30d`, `2w`, `6m`, `1y` → seconds
+pub fn parse_age(age: &str) -> Result<i64> {
+    let age = age.trim();
+    let (num, unit) = age.split_at(age.len().saturating_sub(1));
+    let n: i64 = num
+        .parse()
+        .with_context(|| format!("Invalid age {:?} (use e.g. 30d, 2w, 6m)", age))?;
+    let day = 86_400;
+    Ok(n * match unit {
+        "d" => day,
+        "w" => 7 * day,
+        "m" => 30 * day,
+        "y" => 365 * day,
+        _ => anyhow::bail!("Invalid age unit in {:?} (use d, w, m or y)", age),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(raw: &[u8]) -> Message<'_> {
+        MessageParser::default().parse_headers(raw).unwrap()
+    }
+
+    #[test]
+    fn test_is_bulk() {
+        assert!(is_bulk(&headers(b"List-Unsubscribe: <mailto:x@y>\r\n\r\n")));
+        assert!(is_bulk(&headers(b"Precedence: Bulk\r\n\r\n")));
+        assert!(is_bulk(&headers(
+            b"Return-Path: <bounce@mail123.sendgrid.net>\r\n\r\n"
+        )));
+        assert!(!is_bulk(&headers(b"From: friend@example.com\r\n\r\n")));
+    }
+
+    #[test]
+    fn test_decide_sender_rules() {
+        let cfg = Config::parse(
+            r#"
+[sweep]
+age = "30d"
+
+[[sweep.senders]]
+from = "@shop.example"
+action = "delete"
+age = "7d"
+
+[[sweep.senders]]
+from = "bank.example"
+action = "keep"
+"#,
+        )
+        .unwrap();
+        let msg = headers(b"List-Unsubscribe: <mailto:x@y>\r\n\r\n");
+        let day = 86_400;
+        let decide = |sender, age| decide(&cfg.sweep, &msg, sender, age).unwrap();
+        assert_eq!(
+            decide("deals@shop.example", 10 * day),
+            Some(SweepAction::Delete)
+        );
+        assert_eq!(decide("news@other.example", 10 * day), None);
+        assert_eq!(
+            decide("news@other.example", 40 * day),
+            Some(SweepAction::Archive)
+        );
+        assert_eq!(
+            decide("alerts@bank.example", 40 * day),
+            Some(SweepAction::Keep)
+        );
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("2w").unwrap(), 14 * 86_400);
+        assert!(parse_age("soon").is_err());
+        assert!(parse_age("3x").is_err());
+    }
+}
@@ -1,120 +1,348 @@
 //! Mail sync with notifications
 
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Write};
 use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::{self, Config};
+use crate::error::{self, Error};
+use crate::hook::{self, Point};
+use crate::i18n::{self, Msg};
+use crate::{
+    avatar, color, expire, jmap, mime, mute, notmuch, process, push, remote, runner, script,
+    tagrules, theme, throttle, track, width::truncate,
+};
+
+/// Where `mu sync` fetches mail from
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+pub enum Backend {
+    /// mbsync channels from ~/.mbsyncrc
+    #[default]
+    Mbsync,
+    /// A JMAP server (`[jmap]`), downloaded into Maildir
+    Jmap,
+}
 
-/// Sync mail and notify of new messages
-pub fn sync(quiet: bool, quick: bool) -> Result<()> {
-    use std::io::{self, Write};
+/// How many mbsync channels run at once, and how long each may take
+#[derive(Clone, Copy)]
+pub struct Limits {
+    pub jobs: usize,
+    pub timeout: Duration,
+}
 
-    // Get list of channels from mbsync
-    let channels = get_mbsync_channels(quick)?;
-    let total_steps = channels.len() + 1; // +1 for indexing
-    let mut sync_stats: Vec<(String, SyncStats)> = Vec::new();
+/// Sync mail and notify of new messages
+///
+/// With `no_index`, only fetch (used from the notmuch pre-new hook, where
+/// notmuch itself is about to index).
+pub fn sync(
+    quiet: bool,
+    quick: bool,
+    no_index: bool,
+    backend: Backend,
+    channels: &[String],
+    limits: Limits,
+) -> Result<()> {
+    let (sync_stats, steps) = match backend {
+        Backend::Mbsync => sync_mbsync(quiet, quick, channels, limits)?,
+        Backend::Jmap => sync_jmap(quiet)?,
+    };
+    let housekeeping = Housekeeping::new(quiet);
+    housekeeping.expire();
 
-    // Sync each channel with progress bar
-    for (i, channel) in channels.iter().enumerate() {
+    if no_index {
         if !quiet {
-            print_progress(i, total_steps, &format!("Syncing {}", channel));
+            clear_progress();
+            print_summary(&sync_stats, false);
         }
+        return Ok(());
+    }
+    let new_messages = index(quiet, steps)?;
+    housekeeping.tag_rules();
 
-        let mbsync = Command::new("mbsync")
-            .args(["-V", channel]) // -V for verbose output with counts
-            .output()
-            .context("Failed to run mbsync")?;
+    // JMAP labels and keywords become tags once notmuch knows the messages
+    if backend == Backend::Jmap {
+        jmap::apply_tags()?;
+    }
+    housekeeping.mute();
 
-        if !mbsync.status.success() {
-            let stderr = String::from_utf8_lossy(&mbsync.stderr);
-            if !quiet {
-                eprintln!("\r\x1b[K\x1b[31m✗\x1b[0m mbsync {} failed", channel);
-            }
-            anyhow::bail!("mbsync {} failed: {}", channel, stderr);
-        }
+    // Show sync results
+    if !quiet {
+        print_summary(&sync_stats, !new_messages.is_empty());
+    }
 
-        // Parse mbsync output for stats
-        let output = String::from_utf8_lossy(&mbsync.stdout);
-        let stderr = String::from_utf8_lossy(&mbsync.stderr);
-        let stats = parse_mbsync_output(&output, &stderr);
-        if stats.has_activity() {
-            sync_stats.push((channel.clone(), stats));
-        }
+    // Notify (within the rate limit; a quiet sync may announce mail held back before)
+    announce(&new_messages)?;
+    if !new_messages.is_empty() {
+        housekeeping.track();
     }
 
-    // Index with notmuch
+    Ok(())
+}
+
+/// `notmuch new` as the last step; the messages it added
+///
+/// Without its hooks: mu's pre-new hook is this sync, and its post-new step runs after.
+fn index(quiet: bool, steps: usize) -> Result<Vec<NewMessage>> {
     if !quiet {
-        print_progress(channels.len(), total_steps, "Indexing");
+        print_progress(steps, steps + 1, "Indexing");
     }
 
-    let notmuch = Command::new("notmuch")
-        .args(["new"])
-        .output()
-        .context("Failed to run notmuch new")?;
+    let notmuch = process::run(&mut remote::command("notmuch", &["new", "--no-hooks"]))
+        .map_err(|e| error::spawn_failed("notmuch", e))?;
 
     if !notmuch.status.success() {
-        let stderr = String::from_utf8_lossy(&notmuch.stderr);
         if !quiet {
-            eprintln!("\r\x1b[K\x1b[31m✗\x1b[0m notmuch failed");
+            clear_progress();
+            eprintln!("{} notmuch failed", color::fail());
         }
-        anyhow::bail!("notmuch new failed: {}", stderr);
+        return Err(Error::command_failed("notmuch new", &notmuch.stderr).into());
     }
 
     // Clear progress line
     if !quiet {
-        eprint!("\r\x1b[K");
+        clear_progress();
         io::stderr().flush()?;
     }
+    Ok(parse_new_messages(&String::from_utf8_lossy(
+        &notmuch.stdout,
+    )))
+}
 
-    // Parse notmuch output for new messages
-    let output = String::from_utf8_lossy(&notmuch.stdout);
-    let new_messages = parse_new_messages(&output);
+/// The steps around indexing: `[expire]` before, tag rules (the post-new hook's job),
+/// archiving replies to muted threads and shipment notifications after
+///
+/// None of them may fail the sync. Expiring works on local files, so a `--remote` sync leaves
+/// it to the box's own `mu sync`; a `--simulate` run neither expires nor remembers shipments.
+struct Housekeeping {
+    cfg: Option<Config>,
+    quiet: bool,
+    remote: bool,
+    simulated: bool,
+}
 
-    // Show sync results
-    if !quiet {
-        if sync_stats.is_empty() && new_messages.is_empty() {
-            eprintln!("\x1b[32m✓\x1b[0m No changes");
-        } else {
-            // Show per-account stats
-            for (channel, stats) in &sync_stats {
-                let parts: Vec<String> = [
-                    (stats.new_msgs, "new"),
-                    (stats.deleted, "del"),
-                    (stats.flags, "flags"),
-                ]
-                .iter()
-                .filter(|(n, _)| *n > 0)
-                .map(|(n, label)| format!("{} {}", n, label))
-                .collect();
-
-                if !parts.is_empty() {
-                    eprintln!(
-                        "\x1b[32m✓\x1b[0m \x1b[33m{}\x1b[0m: {}",
-                        channel,
-                        parts.join(", ")
-                    );
-                }
+impl Housekeeping {
+    fn new(quiet: bool) -> Self {
+        Self {
+            cfg: Config::load().ok(),
+            quiet,
+            remote: remote::host().is_some(),
+            simulated: runner::simulating(),
+        }
+    }
+
+    /// Run `[expire]` rules before indexing so notmuch never sees the expired files
+    fn expire(&self) {
+        let Some(cfg) = &self.cfg else {
+            return;
+        };
+        if !cfg.expire.on_sync || self.remote || self.simulated {
+            return;
+        }
+        if !self.quiet {
+            clear_progress();
+        }
+        if let Err(e) = expire::apply(cfg, false, self.quiet) {
+            eprintln!("{} expire: {:#}", color::warn(), e);
+        }
+    }
+
+    /// Tag rules from the config and the script, as `mu tagrules` in the post-new hook
+    fn tag_rules(&self) {
+        let Some(cfg) = &self.cfg else {
+            return;
+        };
+        if let Err(e) = tagrules::apply(cfg) {
+            eprintln!("{} tagrules: {:#}", color::warn(), e);
+        }
+    }
+
+    /// Replies to muted threads never reach the inbox (or the notifications)
+    fn mute(&self) {
+        match mute::archive_muted() {
+            Ok(0) => {}
+            Ok(n) if !self.quiet => eprintln!(
+                "{}",
+                color::epaint(
+                    &theme::get().muted,
+                    format!("{} message(s) in muted threads archived", n)
+                )
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("{} mute: {:#}", color::warn(), e),
+        }
+    }
+
+    /// Notify about tracking numbers in the new mail when `[track] notify_on_sync` is set
+    fn track(&self) {
+        let Some(cfg) = &self.cfg else {
+            return;
+        };
+        if !cfg.track.notify_on_sync || self.simulated {
+            return;
+        }
+        match track::new_on_sync(&cfg.track) {
+            Ok(shipments) if !shipments.is_empty() => {
+                let body = shipments
+                    .iter()
+                    .map(|s| format!("• {} {}: {}", s.carrier, s.number, truncate(&s.subject, 30)))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let _ = send_notification("New shipment", &body, None);
             }
+            Ok(_) => {}
+            Err(e) => eprintln!("{} track: {:#}", color::warn(), e),
+        }
+    }
+}
 
-            // Summary
-            let total_new: usize = sync_stats.iter().map(|(_, s)| s.new_msgs).sum();
-            if total_new > 0 {
-                eprintln!(
-                    "\x1b[32m✉\x1b[0m {} new message{}",
-                    total_new,
-                    if total_new == 1 { "" } else { "s" }
-                );
+/// Run the mbsync channels concurrently with a progress bar; returns the stats and the step count
+fn sync_mbsync(
+    quiet: bool,
+    quick: bool,
+    only: &[String],
+    limits: Limits,
+) -> Result<(Vec<(String, SyncStats)>, usize)> {
+    let channels = get_mbsync_channels(quick, only)?;
+    let total_steps = channels.len() + 1;
+    if !quiet {
+        print_progress(
+            0,
+            total_steps,
+            &format!("Syncing {} channels", channels.len()),
+        );
+    }
+
+    let progress = |done: usize, channel: &str| {
+        if !quiet {
+            print_progress(done, total_steps, &format!("Synced {}", channel));
+        }
+    };
+    let results = match process::block_on(run_channels(&channels, limits, progress))? {
+        Ok(results) => results,
+        Err(e) => {
+            if !quiet {
+                clear_progress();
+                eprintln!("{} {}", color::fail(), e);
             }
+            return Err(e);
         }
+    };
+
+    let sync_stats = channels
+        .into_iter()
+        .zip(results)
+        .filter(|(_, stats)| stats.has_activity())
+        .collect();
+    Ok((sync_stats, total_steps - 1))
+}
+
+/// mbsync every channel, `limits.jobs` at a time; the first failure cancels (kills) the rest
+async fn run_channels(
+    channels: &[String],
+    limits: Limits,
+    mut progress: impl FnMut(usize, &str),
+) -> Result<Vec<SyncStats>> {
+    let slots = Arc::new(Semaphore::new(limits.jobs.max(1)));
+    let mut tasks = JoinSet::new();
+    let rc = mbsyncrc_override();
+    for (i, channel) in channels.iter().enumerate() {
+        let slots = Arc::clone(&slots);
+        let mut args = vec!["-V", channel.as_str()]; // -V for verbose output with counts
+        if let Some(rc) = &rc {
+            args.extend(["-c", rc.as_str()]);
+        }
+        let mbsync = remote::command("mbsync", &args);
+        tasks.spawn(async move {
+            let _slot = slots.acquire_owned().await;
+            (i, process::output(mbsync, None, limits.timeout).await)
+        });
     }
 
-    // Send notification if there are new messages
-    if !new_messages.is_empty() {
-        notify(&new_messages)?;
+    let mut results = vec![SyncStats::default(); channels.len()];
+    let mut done = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (i, output) = joined?;
+        let channel = &channels[i];
+        let output = output.with_context(|| format!("mbsync {} failed", channel))?;
+        if !output.status.success() {
+            let tool = format!("mbsync {}", channel);
+            return Err(Error::command_failed(&tool, &output.stderr).into());
+        }
+        done += 1;
+        progress(done, channel);
+        results[i] = parse_mbsync_output(
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+        );
     }
+    Ok(results)
+}
 
-    Ok(())
+/// Download from the `[jmap]` server as a single step
+fn sync_jmap(quiet: bool) -> Result<(Vec<(String, SyncStats)>, usize)> {
+    if !quiet {
+        print_progress(0, 2, "Syncing jmap");
+    }
+    let fetched = jmap::fetch(&Config::load()?.jmap)?;
+    let stats = SyncStats {
+        new_msgs: fetched.new,
+        deleted: fetched.deleted,
+        flags: fetched.updated,
+    };
+    let sync_stats = if stats.has_activity() {
+        vec![("jmap".to_string(), stats)]
+    } else {
+        Vec::new()
+    };
+    Ok((sync_stats, 1))
+}
+
+/// Print per-channel stats and the new message total
+fn print_summary(sync_stats: &[(String, SyncStats)], has_new: bool) {
+    if sync_stats.is_empty() && !has_new {
+        eprintln!("{} No changes", color::ok());
+        return;
+    }
+
+    // Show per-account stats
+    for (channel, stats) in sync_stats {
+        let parts: Vec<String> = [
+            (stats.new_msgs, Msg::Fetched as fn(usize) -> Msg<'static>),
+            (stats.deleted, Msg::Deleted),
+            (stats.flags, Msg::FlagChanges),
+        ]
+        .into_iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, msg)| i18n::tr(msg(n)))
+        .collect();
+
+        if !parts.is_empty() {
+            eprintln!(
+                "{} {}: {}",
+                color::ok(),
+                color::epaint(&theme::get().channel, channel),
+                parts.join(", ")
+            );
+        }
+    }
+
+    // Summary
+    let total_new: usize = sync_stats.iter().map(|(_, s)| s.new_msgs).sum();
+    if total_new > 0 {
+        eprintln!(
+            "{} {}",
+            color::epaint(&theme::get().new_mail, "✉"),
+            i18n::tr(Msg::NewMessages(total_new))
+        );
+    }
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone)]
 struct SyncStats {
     new_msgs: usize,
     deleted: usize,
@@ -166,48 +394,44 @@ fn parse_mbsync_output(stdout: &str, stderr: &str) -> SyncStats {
 }
 
 /// Print progress bar
-fn print_progress(current: usize, total: usize, label: &str) {
-    use std::io::{self, Write};
-
+///
+/// Only drawn on a terminal, so redirected stderr doesn't fill with redraws.
+pub fn print_progress(current: usize, total: usize, label: &str) {
+    if !io::stderr().is_terminal() {
+        return;
+    }
     let bar_width = 20;
     let filled = (current * bar_width) / total;
     let empty = bar_width - filled;
 
+    let theme = theme::get();
     let bar: String = format!(
-        "\x1b[36m{}\x1b[0m\x1b[2m{}\x1b[0m",
-        "█".repeat(filled),
-        "░".repeat(empty)
+        "{}{}",
+        color::epaint(&theme.progress, "█".repeat(filled)),
+        color::epaint(&theme.progress_empty, "░".repeat(empty))
     );
 
     eprint!("\r\x1b[K{} {}", bar, label);
     let _ = io::stderr().flush();
 }
 
-/// Get list of mbsync channels from config
-fn get_mbsync_channels(quick: bool) -> Result<Vec<String>> {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let config_path = format!("{}/.mbsyncrc", home);
-    let content = std::fs::read_to_string(&config_path).context("Failed to read ~/.mbsyncrc")?;
-
-    let mut channels = Vec::new();
-    for line in content.lines() {
-        if line.starts_with("Channel ")
-            && let Some(name) = line.strip_prefix("Channel ")
-        {
-            let name = name.trim().to_string();
-            if quick {
-                // Quick mode: only -inbox channels
-                if name.ends_with("-inbox") {
-                    channels.push(name);
-                }
-            } else {
-                // Full mode: skip -inbox channels (they're subsets)
-                if !name.ends_with("-inbox") {
-                    channels.push(name);
-                }
-            }
-        }
+/// Erase the progress bar line
+pub fn clear_progress() {
+    if io::stderr().is_terminal() {
+        eprint!("\r\x1b[K");
     }
+}
+
+/// mbsync channels to run: `only` if given, else the -inbox channels (quick) or the rest
+fn get_mbsync_channels(quick: bool, only: &[String]) -> Result<Vec<String>> {
+    if !only.is_empty() {
+        return Ok(only.to_vec());
+    }
+    let mut channels: Vec<String> = channel_names(&read_mbsyncrc()?)
+        .into_iter()
+        // -inbox channels are subsets of the full ones
+        .filter(|name| name.ends_with("-inbox") == quick)
+        .collect();
 
     if channels.is_empty() {
         // Fallback to -a
@@ -217,6 +441,37 @@ fn get_mbsync_channels(quick: bool) -> Result<Vec<String>> {
     Ok(channels)
 }
 
+/// ~/.mbsyncrc or `$MU_MBSYNCRC` (on the `--remote` host when set)
+pub fn read_mbsyncrc() -> Result<String> {
+    if remote::host().is_some() {
+        let path = mbsyncrc_override().unwrap_or_else(|| ".mbsyncrc".to_string());
+        return Ok(String::from_utf8_lossy(&remote::read(&path)?).to_string());
+    }
+    let path = config::mbsyncrc_path();
+    std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// The `$MU_MBSYNCRC` path mbsync is pointed at with `-c` (as given, for a remote host)
+fn mbsyncrc_override() -> Option<String> {
+    let value = std::env::var("MU_MBSYNCRC")
+        .ok()
+        .filter(|v| !v.is_empty())?;
+    if remote::host().is_some() {
+        Some(value)
+    } else {
+        Some(config::mbsyncrc_path().display().to_string())
+    }
+}
+
+/// Every `Channel NAME` in an mbsync config
+pub fn channel_names(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("Channel "))
+        .map(|name| name.trim().to_string())
+        .collect()
+}
+
 /// Parse notmuch new output for new message info
 fn parse_new_messages(output: &str) -> Vec<NewMessage> {
     let mut messages = Vec::new();
@@ -244,16 +499,17 @@ fn parse_new_messages(output: &str) -> Vec<NewMessage> {
 
 /// Get messages added in the last sync (within last 2 minutes)
 fn get_recent_messages() -> Result<Vec<NewMessage>> {
-    let output = Command::new("notmuch")
-        .args([
+    let output = process::run(&mut remote::command(
+        "notmuch",
+        &[
             "search",
             "--format=text",
             "--output=summary",
             "date:2min..",
             "tag:inbox",
-        ])
-        .output()
-        .context("Failed to query recent messages")?;
+        ],
+    ))
+    .context("Failed to query recent messages")?;
 
     if !output.status.success() {
         return Ok(Vec::new());
@@ -302,96 +558,319 @@ fn parse_notmuch_line(line: &str) -> Option<NewMessage> {
         } else {
             subject
         };
-        (sender.to_string(), subject.to_string())
+        (mime::decode_words(sender), mime::decode_words(subject))
     } else {
         return None;
     };
 
-    Some(NewMessage { sender, subject })
+    Some(NewMessage {
+        thread: parts[0].to_string(),
+        sender,
+        subject,
+    })
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct NewMessage {
+    thread: String,
+    #[serde(rename = "from")]
     sender: String,
     subject: String,
 }
 
-/// Send notification (platform-specific)
-fn notify(messages: &[NewMessage]) -> Result<()> {
-    let (title, body) = if messages.len() == 1 {
-        let msg = &messages[0];
-        (format!("New mail from {}", msg.sender), msg.subject.clone())
-    } else {
-        (
-            format!("{} new messages", messages.len()),
-            messages
-                .iter()
-                .take(5)
-                .map(|m| {
-                    format!(
-                        "• {}: {}",
-                        truncate(&m.sender, 20),
-                        truncate(&m.subject, 30)
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join("\n"),
-        )
+/// Desktop and push notifications for the new mail, unless over `[push] limit`
+fn announce(messages: &[NewMessage]) -> Result<()> {
+    let cfg = Config::load()?;
+    if !cfg.push.desktop && cfg.push.targets.is_empty() {
+        return Ok(());
+    }
+    let messages = wanted(&cfg, messages);
+    let Some(held) = throttle::admit(&cfg.push, messages.len()) else {
+        return Ok(());
     };
+    if cfg.push.desktop {
+        notify(&messages, held)?;
+    }
+    push_on_sync(&cfg.push, &messages, held);
+    Ok(())
+}
+
+/// The messages the script's `notify` lets through (all of them if it fails)
+fn wanted(cfg: &Config, messages: &[NewMessage]) -> Vec<NewMessage> {
+    let filtered = script::load(&cfg.script).and_then(|script| match script {
+        Some(script) if script.defines("notify") => messages
+            .iter()
+            .map(|m| Ok(script.notify(m)?.then(|| m.clone())))
+            .filter_map(Result::transpose)
+            .collect(),
+        _ => Ok(messages.to_vec()),
+    });
+    filtered.unwrap_or_else(|e| {
+        eprintln!("{} script: {:#}", color::warn(), e);
+        messages.to_vec()
+    })
+}
 
+/// Notify about new messages (and `held` ones held back earlier), with the sender's avatar
+/// for a single one
+fn notify(messages: &[NewMessage], held: usize) -> Result<()> {
+    let messages_ref: Vec<&NewMessage> = messages.iter().collect();
+    let summary = summary(&messages_ref, held);
+    let Some((title, body)) = pre_notify("desktop", &messages_ref, held, summary)? else {
+        return Ok(());
+    };
+    let icon = match (messages, held) {
+        ([msg], 0) => sender_icon(&msg.thread),
+        _ => None,
+    };
+    send_notification(&title, &body, icon.as_deref())
+}
+
+/// Push the messages selected by `[[push.rules]]` to every `[[push.targets]]` service
+///
+/// Held-back mail is only counted in; on its own it's announced on the desktop.
+fn push_on_sync(push: &config::Push, messages: &[NewMessage], held: usize) {
+    if push.targets.is_empty() || messages.is_empty() {
+        return;
+    }
+    let threads: Vec<&str> = messages.iter().map(|m| m.thread.as_str()).collect();
+    let selected = match push::select(&push.rules, &threads) {
+        Ok(selected) => selected,
+        Err(e) => {
+            eprintln!("{} push rules: {:#}", color::warn(), e);
+            return;
+        }
+    };
+    let Some(priority) = selected.iter().map(|(_, p)| *p).max() else {
+        return;
+    };
+    let picked: Vec<&NewMessage> = selected.iter().map(|(i, _)| &messages[*i]).collect();
+    let (title, body) = match pre_notify("push", &picked, held, summary(&picked, held)) {
+        Ok(Some(notification)) => notification,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("{} push: {:#}", color::warn(), e);
+            return;
+        }
+    };
+    for target in &push.targets {
+        if let Err(e) = push::send(target, &title, &body, priority) {
+            eprintln!("{} push: {:#}", color::warn(), e);
+        }
+    }
+}
+
+/// What `pre-notify` hooks see; `channel` is `desktop` or `push`
+#[derive(Serialize, Deserialize)]
+struct Notification {
+    channel: String,
+    title: String,
+    body: String,
+    messages: Vec<NewMessage>,
+    held: usize,
+}
+
+/// Title and body after the `pre-notify` hooks, None if one vetoed
+fn pre_notify(
+    channel: &str,
+    messages: &[&NewMessage],
+    held: usize,
+    (title, body): (String, String),
+) -> Result<Option<(String, String)>> {
+    if !hook::enabled(Point::PreNotify) {
+        return Ok(Some((title, body)));
+    }
+    let notification = Notification {
+        channel: channel.to_string(),
+        title,
+        body,
+        messages: messages.iter().map(|m| (*m).clone()).collect(),
+        held,
+    };
+    Ok(hook::run(Point::PreNotify, notification)?.map(|n| (n.title, n.body)))
+}
+
+/// Messages listed in a notification body
+const LISTED: usize = 5;
+
+/// Notification title and body: the message itself, or a list of the first few and a count
+/// of the rest, `held` included
+fn summary(messages: &[&NewMessage], held: usize) -> (String, String) {
+    if let ([msg], 0) = (messages, held) {
+        return (i18n::tr(Msg::NewMailFrom(&msg.sender)), msg.subject.clone());
+    }
+    let mut lines: Vec<String> = messages
+        .iter()
+        .take(LISTED)
+        .map(|m| {
+            format!(
+                "• {}: {}",
+                truncate(&m.sender, 20),
+                truncate(&m.subject, 30)
+            )
+        })
+        .collect();
+    let more = messages.len().saturating_sub(LISTED) + held;
+    if more > 0 && !messages.is_empty() {
+        lines.push(i18n::tr(Msg::AndMore(more)));
+    }
+    (
+        i18n::tr(Msg::NewMessages(messages.len() + held)),
+        lines.join("\n"),
+    )
+}
+
+/// Show a desktop notification (platform-specific)
+fn send_notification(title: &str, body: &str, icon: Option<&std::path::Path>) -> Result<()> {
     #[cfg(target_os = "macos")]
     {
-        Command::new("terminal-notifier")
-            .args([
-                "-title",
-                "Mail",
-                "-subtitle",
-                &title,
-                "-message",
-                &body,
-                "-sound",
-                "default",
-                "-group",
-                "mu-mail",
-                "-activate",
-                "com.apple.Terminal",
-            ])
-            .output()
-            .context("Failed to send notification")?;
+        let mut cmd = Command::new("terminal-notifier");
+        if let Some(icon) = &icon {
+            cmd.arg("-contentImage").arg(icon);
+        }
+        cmd.args([
+            "-title",
+            "Mail",
+            "-subtitle",
+            title,
+            "-message",
+            body,
+            "-sound",
+            "default",
+            "-group",
+            "mu-mail",
+            "-activate",
+            "com.apple.Terminal",
+        ]);
+        process::run(&mut cmd).context("Failed to send notification")?;
     }
 
     #[cfg(target_os = "linux")]
     {
-        Command::new("notify-send")
-            .args(["--app-name=Mail", &title, &body])
-            .output()
-            .context("Failed to send notification")?;
+        let mut cmd = Command::new("notify-send");
+        if let Some(icon) = &icon {
+            cmd.arg(format!("--icon={}", icon.display()));
+        }
+        cmd.args(["--app-name=Mail", title, body]);
+        process::run(&mut cmd).context("Failed to send notification")?;
+    }
+
+    #[cfg(windows)]
+    {
+        // The text goes through the environment, out of reach of PowerShell's quoting
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-NonInteractive", "-Command", WINDOWS_TOAST])
+            .env("MU_TOAST_TITLE", title)
+            .env("MU_TOAST_BODY", body);
+        if let Some(icon) = &icon {
+            cmd.env("MU_TOAST_ICON", icon);
+        }
+        process::run(&mut cmd).context("Failed to send notification")?;
     }
 
     Ok(())
 }
 
-/// Truncate string to max length
-fn truncate(s: &str, max: usize) -> String {
-    if s.chars().count() <= max {
-        s.to_string()
-    } else {
-        format!("{}…", s.chars().take(max - 1).collect::<String>())
+/// A toast with `$env:MU_TOAST_TITLE` and `_BODY` (and `_ICON`, if set), shown as
+/// PowerShell's: unpackaged apps have no id of their own to show toasts under
+#[cfg(windows)]
+const WINDOWS_TOAST: &str = r#"
+$ErrorActionPreference = 'Stop'
+$m = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]
+$kind = if ($env:MU_TOAST_ICON) { 'ToastImageAndText02' } else { 'ToastText02' }
+$xml = $m::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::$kind)
+$text = $xml.GetElementsByTagName('text')
+$text.Item(0).AppendChild($xml.CreateTextNode($env:MU_TOAST_TITLE)) > $null
+$text.Item(1).AppendChild($xml.CreateTextNode($env:MU_TOAST_BODY)) > $null
+if ($env:MU_TOAST_ICON) {
+  $xml.GetElementsByTagName('image').Item(0).SetAttribute('src', $env:MU_TOAST_ICON)
+}
+$app = '{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe'
+$m::CreateToastNotifier($app).Show([Windows.UI.Notifications.ToastNotification]::new($xml))
+"#;
+
+/// Avatar of the thread's sender, when `[avatars] notifications` is on
+fn sender_icon(thread: &str) -> Option<std::path::PathBuf> {
+    let cfg = Config::load().ok()?;
+    if !cfg.avatars.notifications {
+        return None;
     }
+    let raw = notmuch::raw(thread).ok()?;
+    let message = mail_parser::MessageParser::default().parse_headers(&raw)?;
+    let address = message.from()?.first()?.address()?.to_string();
+    avatar::lookup(&cfg.avatars, &address)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner;
+
+    #[test]
+    fn test_run_channels_with_recorded_mbsync() {
+        let replay = runner::Replay::parse(concat!(
+            r#"{"program":"mbsync","args":["-V","work"],"stdout":"Channels: 1    Boxes: 2    Far: +0 *1 #0 -0    Near: +3 *0 #0 -2\n"}"#,
+            "\n",
+            r#"{"program":"mbsync","args":["-V","home"],"status":1,"stderr":"IMAP error: bad password"}"#,
+        ))
+        .unwrap();
+        let limits = Limits {
+            jobs: 2,
+            timeout: Duration::from_secs(5),
+        };
+        runner::scoped(replay, || {
+            let work = ["work".to_string()];
+            let stats = process::block_on(run_channels(&work, limits, |_, _| {}))
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                (stats[0].new_msgs, stats[0].flags, stats[0].deleted),
+                (3, 1, 2)
+            );
+            let home = ["home".to_string()];
+            let err = process::block_on(run_channels(&home, limits, |_, _| {}))
+                .unwrap()
+                .unwrap_err();
+            assert!(format!("{:#}", err).contains("bad password"));
+        });
+    }
+
+    #[test]
+    fn test_index_skips_notmuch_hooks() {
+        // With the hooks, `notmuch new` would run this sync again from pre-new
+        let replay = runner::Replay::parse(concat!(
+            r#"{"program":"notmuch","args":["new","--no-hooks"],"stdout":"Added 1 new message to the database.\n"}"#,
+            "\n",
+            r#"{"program":"notmuch","args":["search","--format=text","--output=summary","date:2min..","tag:inbox"],"stdout":"thread:000000000000000a  2026-02-16 [1/1] Google; Security alert (inbox)\n"}"#,
+        ))
+        .unwrap();
+        let added = runner::scoped(replay, || index(true, 1)).unwrap();
+        assert_eq!(added.len(), 1);
+        let failing = runner::Replay::parse(
+            r#"{"program":"notmuch","args":["new","--no-hooks"],"status":1,"stderr":"database locked"}"#,
+        )
+        .unwrap();
+        assert!(runner::scoped(failing, || index(true, 1)).is_err());
+    }
 
     #[test]
     fn test_parse_notmuch_line() {
         let line = "thread:000000000000000a  2026-02-16 [1/1] Google; Security alert (inbox)";
         let msg = parse_notmuch_line(line).unwrap();
+        assert_eq!(msg.thread, "thread:000000000000000a");
         assert_eq!(msg.sender, "Google");
         assert_eq!(msg.subject, "Security alert");
     }
 
+    #[test]
+    fn test_parse_notmuch_line_decodes_words() {
+        let line =
+            "thread:0b  today [1/1] =?UTF-8?Q?Ren=C3=A9e?=; =?ISO-8859-1?Q?R=E9union?= (inbox)";
+        let msg = parse_notmuch_line(line).unwrap();
+        assert_eq!(msg.sender, "Ren\u{e9}e");
+        assert_eq!(msg.subject, "R\u{e9}union");
+    }
+
     #[test]
     fn test_truncate() {
         assert_eq!(truncate("hello", 10), "hello");
@@ -0,0 +1,99 @@
+//! Apply config-defined tag rules (and the script's `tag`) via `notmuch tag --batch`
+
+use anyhow::Result;
+
+use crate::config::{Config, TagRule};
+use crate::{notmuch, script};
+
+/// Arguments for `mu tagrules`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Print the notmuch batch commands instead of running them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Apply tag rules from the config
+pub fn run(args: Args) -> Result<()> {
+    run_with(args, &Config::load()?)
+}
+
+/// Apply the tag rules in `cfg` (after indexing in `mu sync`)
+pub fn apply(cfg: &Config) -> Result<()> {
+    run_with(Args { dry_run: false }, cfg)
+}
+
+fn run_with(args: Args, cfg: &Config) -> Result<()> {
+    let mut lines = batch_lines(&cfg.tag_rules);
+    lines.extend(script_lines(cfg)?);
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    if args.dry_run {
+        for line in &lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    notmuch::tag_batch(&lines)
+}
+
+/// Build `notmuch tag --batch` input lines (`+a -b -- query`)
+fn batch_lines(rules: &[TagRule]) -> Vec<String> {
+    rules
+        .iter()
+        .filter(|r| !r.tags.is_empty() && !r.query.trim().is_empty())
+        .map(|r| format!("{} -- ({})", r.tags.join(" "), r.query))
+        .collect()
+}
+
+/// Batch lines from the script's `tag(thread)`, for the threads matching `[script] query`
+fn script_lines(cfg: &Config) -> Result<Vec<String>> {
+    let Some(script) = script::load(&cfg.script)? else {
+        return Ok(Vec::new());
+    };
+    if !script.defines("tag") {
+        return Ok(Vec::new());
+    }
+    let mut lines = Vec::new();
+    for thread in notmuch::summaries(&cfg.script.query)? {
+        let changes = script.tag(&thread)?;
+        if let Some(id) = thread["thread"].as_str()
+            && !changes.is_empty()
+        {
+            lines.push(format!("{} -- thread:{}", changes.join(" "), id));
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_lines() {
+        let rules = vec![
+            TagRule {
+                query: "from:github.com".into(),
+                tags: vec!["+github".into(), "-inbox".into()],
+            },
+            TagRule {
+                query: "to:me".into(),
+                tags: vec![],
+            },
+        ];
+        assert_eq!(
+            batch_lines(&rules),
+            vec!["+github -inbox -- (from:github.com)"]
+        );
+    }
+
+    #[test]
+    fn test_run_with_no_rules() {
+        let args = Args { dry_run: false };
+        assert!(run_with(args, &Config::default()).is_ok());
+    }
+}
@@ -0,0 +1,289 @@
+//! Thread tree visualization
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::{color, notmuch, width};
+
+/// Arguments for `mu thread`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Thread ID (e.g., thread:0000000000000123)
+    thread_id: String,
+
+    /// Show a one-line body snippet under each message
+    #[arg(short, long)]
+    snippets: bool,
+}
+
+/// One message in the thread tree
+#[derive(Debug)]
+struct Node {
+    author: String,
+    date: String,
+    subject: String,
+    unread: bool,
+    snippet: Option<String>,
+    replies: Vec<Node>,
+}
+
+/// Print the thread as a tree
+pub fn run(args: Args) -> Result<()> {
+    for line in render_tree(&forest(&args)?) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// The thread's messages, read with `notmuch show` (on the `--remote` host, if any)
+fn forest(args: &Args) -> Result<Vec<Node>> {
+    let query = notmuch::query_for(&args.thread_id);
+    let roots = parse_forest(&notmuch::threads(&query, args.snippets)?);
+    if roots.is_empty() {
+        return Err(Error::NoMatch { query }.into());
+    }
+    Ok(roots)
+}
+
+/// Parse notmuch's `[[message, [replies]], ...]` thread structure
+///
+/// The top-level output is a list of threads; each thread is a forest.
+fn parse_forest(json: &Value) -> Vec<Node> {
+    let Some(items) = json.as_array() else {
+        return Vec::new();
+    };
+
+    let mut nodes = Vec::new();
+    for item in items {
+        match item.as_array().map(|a| a.as_slice()) {
+            // [message, replies] pair
+            Some([msg, replies]) if msg.is_object() => {
+                let mut node = parse_message(msg);
+                node.replies = parse_forest(replies);
+                nodes.push(node);
+            }
+            // Nested thread/forest
+            Some(_) => nodes.extend(parse_forest(item)),
+            None => {}
+        }
+    }
+    nodes
+}
+
+fn parse_message(msg: &Value) -> Node {
+    let header = |name: &str| msg["headers"][name].as_str().unwrap_or("").to_string();
+    let unread = msg["tags"]
+        .as_array()
+        .is_some_and(|tags| tags.iter().any(|t| t == "unread"));
+
+    Node {
+        author: author_name(&header("From")),
+        date: msg["date_relative"].as_str().unwrap_or("").to_string(),
+        subject: header("Subject"),
+        unread,
+        snippet: first_text(&msg["body"]).map(|t| snippet(&t)),
+        replies: Vec::new(),
+    }
+}
+
+/// Display name from a `Name <addr>` header, falling back to the address
+fn author_name(from: &str) -> String {
+    match from.split_once('<') {
+        Some((name, _)) if !name.trim().is_empty() => name.trim().trim_matches('"').to_string(),
+        Some((_, addr)) => addr.trim_end_matches('>').to_string(),
+        None => from.trim().to_string(),
+    }
+}
+
+/// Find the first text/plain content in a notmuch JSON body
+fn first_text(body: &Value) -> Option<String> {
+    for part in body.as_array()? {
+        if part["content-type"] == "text/plain"
+            && let Some(text) = part["content"].as_str()
+        {
+            return Some(text.to_string());
+        }
+        if let Some(text) = first_text(&part["content"]) {
+            return Some(text);
+        }
+    }
+    None
+}
+
+/// First meaningful body line (skipping quotes and attributions)
+fn snippet(text: &str) -> String {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('>') && !l.ends_with("wrote:"))
+        .unwrap_or("");
+    width::truncate(line, 71)
+}
+
+/// Strip reply/forward prefixes for subject comparison
+fn base_subject(subject: &str) -> String {
+    let mut s = subject.trim();
+    loop {
+        let lower = s.to_lowercase();
+        let Some(prefix) = ["re:", "fwd:", "fw:", "aw:"]
+            .iter()
+            .find(|p| lower.starts_with(*p))
+        else {
+            return s.to_string();
+        };
+        s = s[prefix.len()..].trim_start();
+    }
+}
+
+fn render_tree(roots: &[Node]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for root in roots {
+        render_node(root, "", "", None, &mut lines);
+    }
+    lines
+}
+
+fn render_node(
+    node: &Node,
+    prefix: &str,
+    child_prefix: &str,
+    parent_subject: Option<&str>,
+    lines: &mut Vec<String>,
+) {
+    lines.push(format!("{}{}", prefix, summary(node, parent_subject)));
+    if let Some(snippet) = node.snippet.as_deref().filter(|s| !s.is_empty()) {
+        let bar = if node.replies.is_empty() {
+            "  "
+        } else {
+            "│ "
+        };
+        lines.push(format!(
+            "{}{}{}",
+            child_prefix,
+            bar,
+            color::paint("2", snippet)
+        ));
+    }
+
+    for (i, reply) in node.replies.iter().enumerate() {
+        let last = i == node.replies.len() - 1;
+        let (branch, cont) = if last {
+            ("└─", "  ")
+        } else {
+            ("├─", "│ ")
+        };
+        render_node(
+            reply,
+            &format!("{}{}", child_prefix, branch),
+            &format!("{}{}", child_prefix, cont),
+            Some(&node.subject),
+            lines,
+        );
+    }
+}
+
+/// A message's line: read state, author and date, and its subject if it changed from
+/// `parent_subject`
+fn summary(node: &Node, parent_subject: Option<&str>) -> String {
+    let marker = if node.unread {
+        color::paint("1;32", "●")
+    } else {
+        color::paint("2", "○")
+    };
+    let author = if node.unread {
+        color::paint("1", &node.author)
+    } else {
+        node.author.clone()
+    };
+
+    // Only show the subject when it starts the thread or changes
+    let subject = match parent_subject {
+        Some(parent) if base_subject(parent) == base_subject(&node.subject) => String::new(),
+        _ => format!(" {}", color::paint("33", &node.subject)),
+    };
+
+    format!(
+        "{} {} {}{}",
+        marker,
+        author,
+        color::paint("2", &node.date),
+        subject
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    fn sample() -> Value {
+        serde_json::json!([[[
+            {"headers": {"From": "Alice <a@x.org>", "Subject": "Plan"}, "date_relative": "2 days ago",
+             "tags": ["inbox"], "body": [{"content-type": "text/plain", "content": "Let's plan.\n"}]},
+            [
+                [{"headers": {"From": "b@x.org", "Subject": "Re: Plan"}, "date_relative": "yesterday",
+                  "tags": ["unread"]}, []],
+                [{"headers": {"From": "\"Carol\" <c@x.org>", "Subject": "New topic"}, "date_relative": "today",
+                  "tags": []}, []]
+            ]
+        ]]])
+    }
+
+    #[test]
+    fn test_parse_forest() {
+        let roots = parse_forest(&sample());
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].author, "Alice");
+        assert_eq!(roots[0].snippet.as_deref(), Some("Let's plan."));
+        assert_eq!(roots[0].replies.len(), 2);
+        assert!(roots[0].replies[0].unread);
+        assert_eq!(roots[0].replies[1].author, "Carol");
+    }
+
+    #[test]
+    fn test_render_tree_shows_subject_changes_only() {
+        let lines = render_tree(&parse_forest(&sample()));
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with("├─") && !lines[2].contains("Plan"));
+        assert!(lines[3].starts_with("└─") && lines[3].contains("New topic"));
+    }
+
+    #[test]
+    fn test_forest_from_notmuch_show() {
+        let show = |stdout: &str, status: i32| {
+            let recording = serde_json::json!({
+                "program": "notmuch",
+                "args": ["show", "--format=json", "--entire-thread=true", "--body=false", "--",
+                         "thread:0a"],
+                "status": status,
+                "stdout": stdout,
+                "stderr": if status == 0 { "" } else { "database locked" },
+            });
+            Replay::parse(&recording.to_string()).unwrap()
+        };
+        let args = Args {
+            thread_id: "thread:0a".to_string(),
+            snippets: false,
+        };
+        let roots = runner::scoped(show(&sample().to_string(), 0), || forest(&args)).unwrap();
+        assert_eq!(roots[0].replies.len(), 2);
+
+        let err = runner::scoped(show("[]", 0), || forest(&args)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NoMatch { query }) if query == "thread:0a"
+        ));
+        let err = runner::scoped(show("", 1), || forest(&args)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ExternalCommandFailed { stderr, .. }) if stderr.contains("locked")
+        ));
+    }
+
+    #[test]
+    fn test_base_subject() {
+        assert_eq!(base_subject("Re: Fwd: RE: Hello"), "Hello");
+        assert_eq!(base_subject("Hello"), "Hello");
+    }
+}
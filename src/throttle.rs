@@ -0,0 +1,127 @@
+//! Notification rate limiting across syncs
+//!
+//! Past `[push] limit` notifications in `limit_minutes`, new mail is held back and counted;
+//! the next notification let through says how much more arrived ("…and 17 more messages").
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::color;
+use crate::config::{self, Push};
+
+/// Recent notifications and the mail held back since, kept between syncs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    /// When each notification in the window went out (Unix seconds)
+    sent: Vec<i64>,
+    /// New messages nobody was told about yet
+    held: usize,
+}
+
+impl State {
+    /// Whether a notification about `count` messages fits at `now`, and if so how many
+    /// held-back messages it should mention; `count` 0 asks to announce only those
+    fn admit(&mut self, now: i64, count: usize, limit: usize, window: i64) -> Option<usize> {
+        self.sent.retain(|&at| at > now - window);
+        if count == 0 && self.held == 0 {
+            return None;
+        }
+        if limit > 0 && self.sent.len() >= limit {
+            self.held += count;
+            return None;
+        }
+        self.sent.push(now);
+        Some(std::mem::take(&mut self.held))
+    }
+}
+
+/// How many held-back messages a notification about `count` new ones should add, or None
+/// to hold these back too (and, with `count` 0, whether there's held-back mail to announce)
+pub fn admit(push: &Push, count: usize) -> Option<usize> {
+    if push.limit == 0 {
+        return (count > 0).then_some(0);
+    }
+    let mut state = load().unwrap_or_else(|e| {
+        eprintln!("{} notification limit: {:#}", color::warn(), e);
+        State::default()
+    });
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let window = push.limit_minutes.saturating_mul(60) as i64;
+    let admitted = state.admit(now, count, push.limit, window);
+    if let Err(e) = save(&state) {
+        eprintln!("{} notification limit: {:#}", color::warn(), e);
+    }
+    admitted
+}
+
+fn state_path() -> PathBuf {
+    config::data_dir().join("notifications.json")
+}
+
+fn load() -> Result<State> {
+    match std::fs::read_to_string(state_path()) {
+        Ok(text) => serde_json::from_str(&text).context("Invalid notification state"),
+        Err(_) => Ok(State::default()),
+    }
+}
+
+fn save(state: &State) -> Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(state)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow_is_held_then_counted() {
+        let mut state = State::default();
+        let minute = 60;
+        assert_eq!(state.admit(0, 3, 2, 10 * minute), Some(0));
+        assert_eq!(state.admit(minute, 40, 2, 10 * minute), Some(0));
+        // The storm goes on: held back
+        assert_eq!(state.admit(2 * minute, 9, 2, 10 * minute), None);
+        assert_eq!(state.admit(3 * minute, 8, 2, 10 * minute), None);
+        assert_eq!(state.admit(4 * minute, 0, 2, 10 * minute), None);
+        // The first notification left the window: room for one, carrying the rest
+        assert_eq!(state.admit(11 * minute, 0, 2, 10 * minute), Some(17));
+        assert_eq!(state.admit(12 * minute, 0, 2, 10 * minute), None);
+        assert_eq!(state.held, 0);
+    }
+
+    #[test]
+    fn test_no_limit_lets_everything_through() {
+        let push = Push {
+            limit: 0,
+            ..Push::default()
+        };
+        assert_eq!(admit(&push, 3), Some(0));
+        assert_eq!(admit(&push, 0), None);
+
+        // Nothing held back: a count of 0 has nothing to announce
+        let mut state = State::default();
+        assert_eq!(state.admit(0, 0, 2, 600), None);
+        assert!(state.sent.is_empty());
+    }
+
+    #[test]
+    fn test_state_survives_a_save() {
+        let state = State {
+            sent: vec![60, 120],
+            held: 17,
+        };
+        let text = serde_json::to_string(&state).unwrap();
+        let mut loaded: State = serde_json::from_str(&text).unwrap();
+        assert_eq!(loaded.sent, [60, 120]);
+        assert_eq!(loaded.admit(700, 0, 2, 600), Some(17));
+    }
+}
@@ -0,0 +1,272 @@
+//! Flagged mail as action items (todo.txt or org-mode), done by retagging
+
+use anyhow::{Context, Result};
+use mail_parser::{DateTime, Message};
+use regex::Regex;
+
+use crate::config::{Config, Todo};
+use crate::{color, mime, notmuch};
+
+const WEEKDAYS: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Arguments for `mu todo`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Messages to export (overrides `[todo] query`)
+    #[arg(short, long)]
+    query: Option<String>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "todotxt")]
+    format: Format,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+enum Format {
+    Todotxt,
+    Org,
+}
+
+#[derive(clap::Subcommand)]
+enum Action {
+    /// Mark items done by retagging (`[todo] done_tags`)
+    Done {
+        /// Message-ids
+        ids: Vec<String>,
+
+        /// todo.txt file: every completed (`x `) line with a `mid:` is marked done
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+    },
+}
+
+/// One exported action item
+#[derive(Debug, PartialEq)]
+struct Item {
+    id: String,
+    from: String,
+    subject: String,
+    /// Mail date and due date hint as `YYYY-MM-DD`
+    date: String,
+    due: Option<String>,
+}
+
+/// Export action items, or mark some done
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?;
+    match args.action {
+        Some(Action::Done { ids, file }) => done(&cfg.todo, ids, file.as_deref()),
+        None => {
+            let query = args.query.as_deref().unwrap_or(&cfg.todo.query);
+            for id in notmuch::search("messages", query)? {
+                let raw = notmuch::raw(&notmuch::quote_id(&id))?;
+                let item = item(&mime::parse(&raw)?)?;
+                match args.format {
+                    Format::Todotxt => println!("{}", todotxt(&item)),
+                    Format::Org => println!("{}", org(&item)),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn done(cfg: &Todo, mut ids: Vec<String>, file: Option<&std::path::Path>) -> Result<()> {
+    if let Some(path) = file {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        ids.extend(completed_ids(&text));
+    }
+    if ids.is_empty() {
+        anyhow::bail!("Nothing to mark done");
+    }
+    let tags = cfg.done_tags.join(" ");
+    let lines: Vec<String> = ids
+        .iter()
+        .map(|id| {
+            format!(
+                "{} -- {}",
+                tags,
+                notmuch::quote_id(id.trim_start_matches('<').trim_end_matches('>'))
+            )
+        })
+        .collect();
+    notmuch::tag_batch(&lines)?;
+    eprintln!("{} {} item(s) done", color::ok(), lines.len());
+    Ok(())
+}
+
+/// `mid:` values of completed todo.txt lines
+fn completed_ids(text: &str) -> Vec<String> {
+    text.lines()
+        .filter(|l| l.starts_with("x "))
+        .filter_map(|l| l.split_whitespace().find_map(|w| w.strip_prefix("mid:")))
+        .map(String::from)
+        .collect()
+}
+
+fn item(message: &Message) -> Result<Item> {
+    let sent = message.date().map_or(0, |d| d.to_timestamp());
+    let body = mime::find_part(message, "text/plain")
+        .map(mime::part_text)
+        .unwrap_or_default();
+    let subject = message.subject().unwrap_or("(no subject)");
+    let from = message.from().and_then(|a| a.first());
+    Ok(Item {
+        id: message.message_id().unwrap_or("").to_string(),
+        from: from
+            .and_then(|a| a.name().or(a.address()))
+            .unwrap_or("")
+            .to_string(),
+        subject: subject.split_whitespace().collect::<Vec<_>>().join(" "),
+        date: ymd(sent),
+        due: due_hint(&format!("{}\n{}", subject, body), sent)?.map(ymd),
+    })
+}
+
+/// First deadline phrase in the text: ISO date, `by Friday`, `tomorrow`, `due March 3`
+fn due_hint(text: &str, sent: i64) -> Result<Option<i64>> {
+    let iso = Regex::new(r"\b(20\d\d)-(\d\d)-(\d\d)\b")?;
+    let keyword = Regex::new(
+        r"(?i)\b(?:by|before|due|until|deadline:?|no later than)\s+(?:(?:this|next)\s+)?(tomorrow|today|eod|monday|tuesday|wednesday|thursday|friday|saturday|sunday|(?:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*\.?\s+(\d{1,2})|(\d{1,2})\s+(?:of\s+)?(jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*)",
+    )?;
+    if let Some(c) = iso.captures(text) {
+        return Ok(
+            DateTime::parse_rfc3339(&format!("{}-{}-{}T00:00:00Z", &c[1], &c[2], &c[3]))
+                .map(|d| d.to_timestamp()),
+        );
+    }
+    let Some(c) = keyword.captures(text) else {
+        return Ok(None);
+    };
+    let word = c[1].to_lowercase();
+    let day = 86_400;
+    let due = match word.as_str() {
+        "today" | "eod" => Some(sent),
+        "tomorrow" => Some(sent + day),
+        w if WEEKDAYS.contains(&w) => {
+            let target = WEEKDAYS.iter().position(|d| *d == w).unwrap_or(0) as i64;
+            let today = DateTime::from_timestamp(sent).day_of_week() as i64;
+            let ahead = (target - today).rem_euclid(7);
+            Some(sent + day * if ahead == 0 { 7 } else { ahead })
+        }
+        _ => {
+            let month_name = c.get(4).map_or(&word[..3], |m| m.as_str());
+            let day_of_month = c
+                .get(2)
+                .or(c.get(3))
+                .and_then(|m| m.as_str().parse::<u8>().ok());
+            let month = MONTHS
+                .iter()
+                .position(|m| month_name.to_lowercase().starts_with(m));
+            match (month, day_of_month) {
+                (Some(m), Some(d)) => month_day(sent, m as u8 + 1, d),
+                _ => None,
+            }
+        }
+    };
+    Ok(due)
+}
+
+/// Next occurrence of month/day on or after the mail date
+fn month_day(sent: i64, month: u8, day: u8) -> Option<i64> {
+    let year = DateTime::from_timestamp(sent).year;
+    [year, year + 1]
+        .iter()
+        .filter_map(|y| {
+            DateTime::parse_rfc3339(&format!("{:04}-{:02}-{:02}T00:00:00Z", y, month, day))
+        })
+        .map(|d| d.to_timestamp())
+        .find(|ts| *ts + 86_400 > sent)
+}
+
+fn ymd(ts: i64) -> String {
+    DateTime::from_timestamp(ts)
+        .to_rfc3339()
+        .chars()
+        .take(10)
+        .collect()
+}
+
+/// `2026-02-01 Subject from:Bob due:2026-02-06 mid:<id> +mail`
+fn todotxt(item: &Item) -> String {
+    let from: String = item.from.split_whitespace().collect::<Vec<_>>().join("_");
+    let mut line = format!("{} {} from:{}", item.date, item.subject, from);
+    if let Some(due) = &item.due {
+        line.push_str(&format!(" due:{}", due));
+    }
+    line.push_str(&format!(" mid:{} +mail", item.id));
+    line
+}
+
+/// Org heading with a DEADLINE and an ol-notmuch link
+fn org(item: &Item) -> String {
+    let mut entry = format!("* TODO {}\n", item.subject);
+    if let Some(due) = &item.due {
+        entry.push_str(&format!("  DEADLINE: <{}>\n", due));
+    }
+    entry.push_str(&format!(
+        "  :PROPERTIES:\n  :FROM: {}\n  :SENT: [{}]\n  :END:\n  [[notmuch:id:{}][Open mail]]",
+        item.from, item.date, item.id
+    ));
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wed, 4 Feb 2026 10:00:00 UTC
+    const SENT: i64 = 1_770_199_200;
+
+    fn due(text: &str) -> Option<String> {
+        due_hint(text, SENT).unwrap().map(ymd)
+    }
+
+    #[test]
+    fn test_due_hint() {
+        assert_eq!(ymd(SENT), "2026-02-04");
+        assert_eq!(due("Please review by Friday"), Some("2026-02-06".into()));
+        assert_eq!(due("need it by Wednesday"), Some("2026-02-11".into()));
+        assert_eq!(due("send it by tomorrow"), Some("2026-02-05".into()));
+        assert_eq!(due("Deadline: March 3rd"), Some("2026-03-03".into()));
+        assert_eq!(due("due 2 Jan"), Some("2027-01-02".into()));
+        assert_eq!(
+            due("invoice 2026-02-20 attached"),
+            Some("2026-02-20".into())
+        );
+        assert_eq!(due("thanks!"), None);
+    }
+
+    #[test]
+    fn test_formats() {
+        let raw = b"From: Bob Smith <bob@x>\r\nDate: Wed, 4 Feb 2026 10:00:00 +0000\r\nMessage-ID: <m1@x>\r\n\
+Subject: Budget review\r\n\r\nCan you send numbers by Friday?\r\n";
+        let item = item(&mime::parse(raw).unwrap()).unwrap();
+        assert_eq!(
+            todotxt(&item),
+            "2026-02-04 Budget review from:Bob_Smith due:2026-02-06 mid:m1@x +mail"
+        );
+        assert!(org(&item).starts_with("* TODO Budget review\n  DEADLINE: <2026-02-06>\n"));
+    }
+
+    #[test]
+    fn test_completed_ids() {
+        let text = "x 2026-02-05 2026-02-04 Budget mid:m1@x +mail\n2026-02-04 Other mid:m2@x\n";
+        assert_eq!(completed_ids(text), vec!["m1@x"]);
+    }
+}
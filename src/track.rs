@@ -0,0 +1,294 @@
+//! Carrier tracking numbers found in shipment mail
+
+use anyhow::{Context, Result};
+use mail_parser::Message;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::config::{self, Config, Track};
+use crate::{color, mime, notmuch, process, render};
+
+/// A carrier's number format, tracking URL and, for bare digit runs, a word the mail must mention
+struct Carrier {
+    name: &'static str,
+    pattern: &'static str,
+    context: Option<&'static str>,
+    url: &'static str,
+}
+
+/// Most specific formats first: a number belongs to the first carrier that matches it
+const CARRIERS: &[Carrier] = &[
+    Carrier {
+        name: "UPS",
+        pattern: r"1Z[0-9A-Z]{16}",
+        context: None,
+        url: "https://www.ups.com/track?tracknum={}",
+    },
+    Carrier {
+        name: "Amazon",
+        pattern: r"TBA\d{12}",
+        context: None,
+        url: "https://track.amazon.com/tracking/{}",
+    },
+    Carrier {
+        name: "USPS",
+        pattern: r"9[2-5]\d{20}",
+        context: None,
+        url: "https://tools.usps.com/go/TrackConfirmAction?tLabels={}",
+    },
+    Carrier {
+        name: "Royal Mail",
+        pattern: r"[A-Z]{2}\d{9}GB",
+        context: None,
+        url: "https://www.royalmail.com/track-your-item#/tracking-results/{}",
+    },
+    Carrier {
+        name: "Post",
+        pattern: r"[A-Z]{2}\d{9}[A-Z]{2}",
+        context: Some("track"),
+        url: "https://parcelsapp.com/en/tracking/{}",
+    },
+    Carrier {
+        name: "InPost",
+        pattern: r"\d{24}",
+        context: Some("inpost"),
+        url: "https://inpost.pl/sledzenie-przesylek?number={}",
+    },
+    Carrier {
+        name: "FedEx",
+        pattern: r"\d{12}|\d{15}",
+        context: Some("fedex"),
+        url: "https://www.fedex.com/fedextrack/?trknbr={}",
+    },
+    Carrier {
+        name: "DPD",
+        pattern: r"\d{14}",
+        context: Some("dpd"),
+        url: "https://tracking.dpd.de/status/en_US/parcel/{}",
+    },
+    Carrier {
+        name: "DHL",
+        pattern: r"\d{10}|JJD\d{18}",
+        context: Some("dhl"),
+        url: "https://www.dhl.com/global-en/home/tracking.html?tracking-id={}",
+    },
+];
+
+/// Arguments for `mu track`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Messages to scan (overrides `[track] query`)
+    #[arg(short, long)]
+    query: Option<String>,
+
+    /// Only numbers not reported before (and remember them)
+    #[arg(long)]
+    new: bool,
+
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// A tracking number and the mail it came in
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Shipment {
+    pub carrier: &'static str,
+    pub number: String,
+    pub url: String,
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+/// List shipments found in mail
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?.track;
+    let query = args.query.as_deref().unwrap_or(&cfg.query);
+    let mut shipments = scan(query)?;
+    if args.new {
+        shipments = unseen(shipments)?;
+    }
+    if let Some(command) = &cfg.status_command {
+        for s in &mut shipments {
+            s.status = status(command, s);
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&shipments)?);
+        return Ok(());
+    }
+    if shipments.is_empty() {
+        eprintln!("{} No shipments", color::ok());
+    }
+    for s in &shipments {
+        println!(
+            "{} {}  {}",
+            color::paint("1", format!("{:<10}", s.carrier)),
+            s.number,
+            s.subject
+        );
+        if let Some(status) = &s.status {
+            println!("           {}", color::paint("33", status));
+        }
+        println!("           {}", color::paint("2", &s.url));
+    }
+    Ok(())
+}
+
+/// Numbers from recent mail not seen before, for `mu sync` notifications
+pub fn new_on_sync(cfg: &Track) -> Result<Vec<Shipment>> {
+    unseen(scan(&format!("({}) and date:2d..", cfg.query))?)
+}
+
+/// Shipments in the matching messages, newest mail first, each number once
+fn scan(query: &str) -> Result<Vec<Shipment>> {
+    let mut shipments: Vec<Shipment> = Vec::new();
+    for id in notmuch::search("messages", query)? {
+        let raw = notmuch::raw(&notmuch::quote_id(&id))?;
+        let message = mime::parse(&raw)?;
+        let subject = message.subject().unwrap_or("(no subject)").to_string();
+        for (carrier, number) in find(&body_text(&message)?)? {
+            if shipments.iter().all(|s| s.number != number) {
+                shipments.push(Shipment {
+                    url: carrier.url.replace("{}", &number),
+                    carrier: carrier.name,
+                    number,
+                    subject: subject.clone(),
+                    status: None,
+                });
+            }
+        }
+    }
+    Ok(shipments)
+}
+
+fn body_text(message: &Message) -> Result<String> {
+    let mut text = message.subject().unwrap_or("").to_string();
+    text.push('\n');
+    if let Some(part) = mime::find_part(message, "text/plain") {
+        text.push_str(&mime::part_text(part));
+    } else if let Some(part) = mime::find_part(message, "text/html") {
+        text.push_str(&render::plain_text(&mime::part_text(part))?);
+    }
+    Ok(text)
+}
+
+/// Tracking numbers in the text with their carriers
+fn find(text: &str) -> Result<Vec<(&'static Carrier, String)>> {
+    let lower = text.to_lowercase();
+    let mut found: Vec<(&Carrier, String)> = Vec::new();
+    for carrier in CARRIERS {
+        if carrier.context.is_some_and(|word| !lower.contains(word)) {
+            continue;
+        }
+        let re = Regex::new(&format!(r"\b(?:{})\b", carrier.pattern))?;
+        for m in re.find_iter(text) {
+            if found.iter().all(|(_, n)| n != m.as_str()) {
+                found.push((carrier, m.as_str().to_string()));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// First line printed by the status command (None if it fails)
+fn status(command: &str, shipment: &Shipment) -> Option<String> {
+    let output = process::run(
+        process::shell(command)
+            .env("MU_TRACK_CARRIER", shipment.carrier)
+            .env("MU_TRACK_NUMBER", &shipment.number),
+    )
+    .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?.trim();
+    (output.status.success() && !line.is_empty()).then(|| line.to_string())
+}
+
+fn seen_path() -> PathBuf {
+    config::data_dir().join("track.json")
+}
+
+/// Drop shipments already reported and record the rest
+fn unseen(shipments: Vec<Shipment>) -> Result<Vec<Shipment>> {
+    let path = seen_path();
+    let mut seen: BTreeSet<String> = match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).context("Invalid tracking state")?,
+        Err(_) => BTreeSet::new(),
+    };
+    let fresh: Vec<Shipment> = shipments
+        .into_iter()
+        .filter(|s| seen.insert(s.number.clone()))
+        .collect();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(&seen)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(fresh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(text: &str) -> Vec<(&'static str, String)> {
+        find(text)
+            .unwrap()
+            .into_iter()
+            .map(|(c, n)| (c.name, n))
+            .collect()
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(
+            numbers("Your package shipped: 1Z999AA10123456784, also TBA123456789012"),
+            vec![
+                ("UPS", "1Z999AA10123456784".to_string()),
+                ("Amazon", "TBA123456789012".to_string())
+            ]
+        );
+        assert_eq!(
+            numbers("Track it: RR123456785PL"),
+            vec![("Post", "RR123456785PL".to_string())]
+        );
+        assert_eq!(
+            numbers("Royal Mail item AB123456789GB"),
+            vec![("Royal Mail", "AB123456789GB".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_find_needs_carrier_context() {
+        assert!(numbers("Order 123456789012 confirmed").is_empty());
+        assert_eq!(
+            numbers("FedEx tracking number 123456789012"),
+            vec![("FedEx", "123456789012".to_string())]
+        );
+        // Not the tail of a longer digit run
+        assert!(numbers("fedex 1234567890123456789").is_empty());
+    }
+
+    #[test]
+    fn test_status_command() {
+        let shipment = Shipment {
+            carrier: "UPS",
+            number: "1Z1".into(),
+            url: String::new(),
+            subject: String::new(),
+            status: None,
+        };
+        assert_eq!(
+            status(
+                "echo \"$MU_TRACK_CARRIER $MU_TRACK_NUMBER: delivered\"",
+                &shipment
+            ),
+            Some("UPS 1Z1: delivered".to_string())
+        );
+        assert_eq!(status("exit 1", &shipment), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! Body translation through a configured backend, cached per message
+
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use crate::config::{self, Config, Translate};
+use crate::{color, http, mime, process, render};
+
+/// Common short words per language, for a cheap guess at the source language
+const STOPWORDS: [(&str, &[&str]); 9] = [
+    (
+        "en",
+        &[
+            "the", "and", "is", "you", "that", "for", "with", "this", "have", "are",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "ist", "nicht", "ich", "sie", "mit", "das", "wir",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "vous", "pour", "une", "pas", "nous",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "y", "es", "que", "para", "una", "por", "con", "usted",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "che", "di", "per", "una", "sono", "non", "della", "questo", "gli",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "que", "para", "uma", "não", "com", "você", "é", "por",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "niet", "van", "wij", "dat", "voor",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "i", "nie", "się", "jest", "że", "na", "do", "to", "czy", "jak",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "är", "som", "för", "inte", "med", "jag", "vi",
+        ],
+    ),
+];
+
+/// Arguments for `mu translate`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads a raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Target language (default: `[translate] to`)
+    #[arg(long)]
+    to: Option<String>,
+
+    /// Source language, skipping detection
+    #[arg(long)]
+    from: Option<String>,
+
+    /// Ignore the cached translation
+    #[arg(long)]
+    refresh: bool,
+}
+
+/// Print the translated body of a message
+pub fn run(args: Args) -> Result<()> {
+    let cfg = Config::load()?.translate;
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+    let body = body_text(&message)?;
+    let to = args.to.as_deref().unwrap_or(&cfg.to);
+    let from = args
+        .from
+        .clone()
+        .or_else(|| detect(&body).map(String::from));
+
+    if from.as_deref() == Some(to) {
+        eprintln!("{} Already in {}", color::warn(), to);
+        println!("{}", body.trim_end());
+        return Ok(());
+    }
+
+    let key = message.message_id().unwrap_or(&body);
+    let path = cache_path(key, to);
+    if !args.refresh
+        && let Ok(cached) = std::fs::read_to_string(&path)
+    {
+        println!("{}", cached.trim_end());
+        return Ok(());
+    }
+
+    let translated = translate(&cfg, &body, from.as_deref(), to)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, &translated)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("{}", translated.trim_end());
+    Ok(())
+}
+
+/// Plain text body, HTML-only mail rendered first
+fn body_text(message: &mail_parser::Message) -> Result<String> {
+    if let Some(part) = mime::find_part(message, "text/plain") {
+        return Ok(mime::part_text(part));
+    }
+    match mime::find_part(message, "text/html") {
+        Some(part) => render::plain_text(&mime::part_text(part)),
+        None => anyhow::bail!("No text body to translate"),
+    }
+}
+
+/// Best-guess ISO 639-1 code: script first, then stopword frequency
+fn detect(text: &str) -> Option<&'static str> {
+    let script =
+        |range: std::ops::RangeInclusive<char>| text.chars().filter(|c| range.contains(c)).count();
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count().max(1);
+    for (lang, range) in [
+        ("ru", '\u{400}'..='\u{4ff}'),
+        ("el", '\u{370}'..='\u{3ff}'),
+        ("ar", '\u{600}'..='\u{6ff}'),
+        ("he", '\u{590}'..='\u{5ff}'),
+        ("ja", '\u{3040}'..='\u{30ff}'),
+        ("zh", '\u{4e00}'..='\u{9fff}'),
+        ("ko", '\u{ac00}'..='\u{d7af}'),
+    ] {
+        if script(range) * 4 > letters {
+            return Some(lang);
+        }
+    }
+
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    STOPWORDS
+        .iter()
+        .map(|(lang, stop)| {
+            (
+                *lang,
+                words.iter().filter(|w| stop.contains(&w.as_str())).count(),
+            )
+        })
+        .filter(|(_, hits)| *hits > 0)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(lang, _)| lang)
+}
+
+/// Run the command backend, else the HTTP API
+fn translate(cfg: &Translate, text: &str, from: Option<&str>, to: &str) -> Result<String> {
+    if let Some(command) = &cfg.command {
+        return pipe(command, text, from.unwrap_or("auto"), to);
+    }
+    let url = cfg
+        .url
+        .as_deref()
+        .context("No translator configured (set [translate] command or url in config.toml)")?;
+    let mut request = serde_json::json!({
+        "q": text,
+        "source": from.unwrap_or("auto"),
+        "target": to,
+        "format": "text",
+    });
+    if let Some(key) = &cfg.api_key {
+        request["api_key"] = key.clone().into();
+    }
+    let response = http::Request::new("POST", url)
+        .header("Content-Type: application/json")
+        .body(&request.to_string())
+        .send()?;
+    if !response.is_success() {
+        anyhow::bail!("{} returned {}: {}", url, response.status, response.text());
+    }
+    let reply: serde_json::Value = serde_json::from_slice(&response.body)?;
+    reply["translatedText"]
+        .as_str()
+        .map(String::from)
+        .context("No translatedText in the response")
+}
+
+/// Run the translator with the text on stdin
+fn pipe(command: &str, text: &str, from: &str, to: &str) -> Result<String> {
+    let output = process::run_with_input(
+        process::shell(command)
+            .env("MU_TRANSLATE_FROM", from)
+            .env("MU_TRANSLATE_TO", to),
+        text.as_bytes(),
+    )
+    .with_context(|| format!("Failed to run {}", command))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed", command);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Cache file keyed by message-id (or body) and target language
+fn cache_path(key: &str, to: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    config::data_dir()
+        .join("translations")
+        .join(format!("{:016x}.{}.txt", hasher.finish(), to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect("Die Rechnung ist nicht bezahlt und wir warten"),
+            Some("de")
+        );
+        assert_eq!(
+            detect("Nous avons reçu votre commande, merci pour la confiance"),
+            Some("fr")
+        );
+        assert_eq!(detect("Thanks, this is the plan for the week"), Some("en"));
+        assert_eq!(detect("Здравствуйте, ваш заказ отправлен"), Some("ru"));
+        assert_eq!(detect("12345"), None);
+    }
+
+    #[test]
+    fn test_command_backend() {
+        let cfg = Translate {
+            command: Some(
+                "printf '%s>%s:' \"$MU_TRANSLATE_FROM\" \"$MU_TRANSLATE_TO\"; cat".into(),
+            ),
+            ..Translate::default()
+        };
+        assert_eq!(
+            translate(&cfg, "hallo", Some("de"), "en").unwrap(),
+            "de>en:hallo"
+        );
+        assert!(translate(&Translate::default(), "hallo", None, "en").is_err());
+    }
+}
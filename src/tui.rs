@@ -0,0 +1,412 @@
+//! Terminal mail browser: thread list from notmuch, rendered preview, tag actions
+
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::{config, mime, notmuch, process, render};
+
+/// Arguments for `mu tui`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Search query (default: the last one used, or `tag:inbox`)
+    #[arg(short, long)]
+    query: Option<String>,
+}
+
+/// One row of `notmuch search --format=json`
+struct Thread {
+    id: String,
+    date: String,
+    authors: String,
+    subject: String,
+    tags: Vec<String>,
+}
+
+/// Query and selected thread, restored on the next start
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    query: String,
+    thread: Option<String>,
+}
+
+struct App {
+    query: String,
+    threads: Vec<Thread>,
+    list: ListState,
+    preview: String,
+    scroll: u16,
+    /// Text typed after `/` (None when not editing the query)
+    input: Option<String>,
+    status: String,
+}
+
+const HELP: &str =
+    "j/k move  J/K scroll  / search  a archive  d delete  u unread  f flag  Enter neomutt  q quit";
+
+/// Browse mail until `q`
+pub fn run(args: Args) -> Result<()> {
+    let saved = load_saved();
+    let query = args.query.unwrap_or_else(|| match saved.query.as_str() {
+        "" => "tag:inbox".to_string(),
+        q => q.to_string(),
+    });
+    let mut app = App {
+        query,
+        threads: Vec::new(),
+        list: ListState::default(),
+        preview: String::new(),
+        scroll: 0,
+        input: None,
+        status: HELP.to_string(),
+    };
+    app.reload(saved.thread.as_deref())?;
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    save(&app)?;
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    loop {
+        terminal.draw(|f| draw(f, app))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if app.input.is_some() {
+            app.edit_query(key.code)?;
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('j') | KeyCode::Down => app.select(1),
+            KeyCode::Char('k') | KeyCode::Up => app.select(-1),
+            KeyCode::Char('J') | KeyCode::PageDown => app.scroll = app.scroll.saturating_add(10),
+            KeyCode::Char('K') | KeyCode::PageUp => app.scroll = app.scroll.saturating_sub(10),
+            KeyCode::Char('/') => app.input = Some(String::new()),
+            KeyCode::Char('r') => app.reload(None)?,
+            KeyCode::Char('a') => app.tag(&["-inbox"])?,
+            KeyCode::Char('d') => app.tag(&["+deleted", "-inbox"])?,
+            KeyCode::Char('u') => app.toggle("unread")?,
+            KeyCode::Char('f') => app.toggle("flagged")?,
+            KeyCode::Enter => {
+                ratatui::restore();
+                let opened = app.open();
+                *terminal = ratatui::init();
+                opened?;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(f: &mut Frame, app: &mut App) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(f.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(main);
+
+    let items: Vec<ListItem> = app.threads.iter().map(row).collect();
+    let list = List::new(items)
+        .block(Block::bordered().title(format!(" {} ({}) ", app.query, app.threads.len())))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, left, &mut app.list);
+
+    let preview = Paragraph::new(app.preview.as_str())
+        .block(Block::bordered())
+        .wrap(Wrap { trim: false })
+        .scroll((app.scroll, 0));
+    f.render_widget(preview, right);
+
+    let line = match &app.input {
+        Some(text) => format!("/{}", text),
+        None => app.status.clone(),
+    };
+    f.render_widget(Line::from(line), status);
+}
+
+fn row(thread: &Thread) -> ListItem<'_> {
+    let style = if thread.tags.iter().any(|t| t == "unread") {
+        Style::new().add_modifier(Modifier::BOLD)
+    } else {
+        Style::new()
+    };
+    let flag = if thread.tags.iter().any(|t| t == "flagged") {
+        "*"
+    } else {
+        " "
+    };
+    ListItem::new(format!(
+        "{}{:>12} {:20.20} {}",
+        flag, thread.date, thread.authors, thread.subject
+    ))
+    .style(style)
+}
+
+impl App {
+    /// Re-run the query, keeping `keep` (or the current thread) selected if it's still there
+    fn reload(&mut self, keep: Option<&str>) -> Result<()> {
+        let current = keep
+            .map(String::from)
+            .or_else(|| self.selected().map(|t| t.id.clone()));
+        self.threads = search(&self.query)?;
+        let index = current
+            .and_then(|id| self.threads.iter().position(|t| t.id == id))
+            .unwrap_or(0);
+        self.list
+            .select((!self.threads.is_empty()).then_some(index));
+        self.load_preview();
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Thread> {
+        self.list.selected().and_then(|i| self.threads.get(i))
+    }
+
+    fn select(&mut self, delta: isize) {
+        let Some(i) = self.list.selected() else {
+            return;
+        };
+        let last = self.threads.len().saturating_sub(1);
+        self.list
+            .select(Some(i.saturating_add_signed(delta).min(last)));
+        self.load_preview();
+    }
+
+    fn load_preview(&mut self) {
+        self.scroll = 0;
+        self.preview = match self.selected() {
+            Some(thread) => preview(&thread.id).unwrap_or_else(|e| format!("{:#}", e)),
+            None => "No messages".to_string(),
+        };
+    }
+
+    fn tag(&mut self, tags: &[&str]) -> Result<()> {
+        let Some(thread) = self.selected() else {
+            return Ok(());
+        };
+        notmuch::tag_batch(&[format!("{} -- {}", tags.join(" "), thread.id)])?;
+        self.status = format!("{} {}", tags.join(" "), thread.id);
+        self.reload(None)
+    }
+
+    fn toggle(&mut self, tag: &str) -> Result<()> {
+        let has = self
+            .selected()
+            .is_some_and(|t| t.tags.iter().any(|x| x == tag));
+        self.tag(&[&format!("{}{}", if has { '-' } else { '+' }, tag)])
+    }
+
+    fn edit_query(&mut self, key: KeyCode) -> Result<()> {
+        let Some(input) = self.input.as_mut() else {
+            return Ok(());
+        };
+        match key {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.input = None,
+            KeyCode::Enter => {
+                if let Some(query) = self.input.take().filter(|q| !q.trim().is_empty()) {
+                    self.query = query;
+                    self.reload(None)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Open the thread in neomutt as a virtual folder
+    fn open(&self) -> Result<()> {
+        let Some(thread) = self.selected() else {
+            return Ok(());
+        };
+        process::status(Command::new("neomutt").args([
+            "-e",
+            &format!("push '<vfolder-from-query>{}<enter>'", thread.id),
+        ]))
+        .context("Failed to run neomutt")?;
+        Ok(())
+    }
+}
+
+fn search(query: &str) -> Result<Vec<Thread>> {
+    let output = process::run(Command::new("notmuch").args([
+        "search",
+        "--format=json",
+        "--limit=1000",
+        query,
+    ]))
+    .context("Failed to run notmuch search")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "notmuch search failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let json: Value = serde_json::from_slice(&output.stdout).context("Invalid notmuch JSON")?;
+    Ok(parse_threads(&json))
+}
+
+fn parse_threads(json: &Value) -> Vec<Thread> {
+    let field = |t: &Value, name: &str| t[name].as_str().unwrap_or("").to_string();
+    json.as_array()
+        .map(|threads| {
+            threads
+                .iter()
+                .map(|t| Thread {
+                    id: format!("thread:{}", field(t, "thread")),
+                    date: field(t, "date_relative"),
+                    authors: field(t, "authors"),
+                    subject: field(t, "subject"),
+                    tags: t["tags"]
+                        .as_array()
+                        .map(|a| {
+                            a.iter()
+                                .filter_map(Value::as_str)
+                                .map(String::from)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Headers and plain-text body of the thread's first message
+fn preview(thread: &str) -> Result<String> {
+    let raw = notmuch::raw(thread)?;
+    let message = mime::parse(&raw)?;
+    let mut text: String = ["From", "To", "Date", "Subject"]
+        .into_iter()
+        .filter_map(|h| {
+            message
+                .header_raw(h)
+                .map(|v| format!("{}: {}\n", h, mime::decode_words(v)))
+        })
+        .collect();
+    text.push('\n');
+    match (
+        mime::find_part(&message, "text/plain"),
+        mime::find_part(&message, "text/html"),
+    ) {
+        (Some(part), _) => text.push_str(&mime::part_text(part)),
+        (None, Some(part)) => text.push_str(&render::plain_text(&mime::part_text(part))?),
+        (None, None) => {}
+    }
+    Ok(text)
+}
+
+fn state_path() -> PathBuf {
+    config::data_dir().join("tui.json")
+}
+
+fn load_saved() -> Saved {
+    std::fs::read_to_string(state_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save(app: &App) -> Result<()> {
+    let saved = Saved {
+        query: app.query.clone(),
+        thread: app.selected().map(|t| t.id.clone()),
+    };
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_string(&saved)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Replay};
+
+    #[test]
+    fn test_parse_threads() {
+        let json: Value = serde_json::from_str(
+            r#"[{"thread":"0000000000000001","date_relative":"Today 10:00","authors":"Bob",
+               "subject":"Hi","tags":["inbox","unread"]}]"#,
+        )
+        .unwrap();
+        let threads = parse_threads(&json);
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].id, "thread:0000000000000001");
+        assert_eq!(threads[0].tags, vec!["inbox", "unread"]);
+        assert!(parse_threads(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn test_app_selection_and_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("b");
+        std::fs::write(&file, "From: Bob <b@x>\r\nSubject: Hi\r\n\r\nSee you\r\n").unwrap();
+        let threads = r#"[{"thread":"1","authors":"Ann","subject":"Plans","tags":["inbox"]},
+                          {"thread":"2","authors":"Bob","subject":"Hi","tags":["flagged"]}]"#;
+        let replay = [
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["search", "--format=json", "--limit=1000", "tag:inbox"],
+                "stdout": threads,
+            }),
+            serde_json::json!({
+                "program": "notmuch",
+                "args": ["search", "--output=files", "--limit=1", "--", "thread:2"],
+                "stdout": format!("{}\n", file.display()),
+            }),
+            serde_json::json!({"program": "notmuch", "args": ["tag", "--batch"]}),
+        ]
+        .map(|line| line.to_string())
+        .join("\n");
+        let mut app = App {
+            query: "tag:inbox".to_string(),
+            threads: Vec::new(),
+            list: ListState::default(),
+            preview: String::new(),
+            scroll: 3,
+            input: None,
+            status: HELP.to_string(),
+        };
+        runner::scoped(Replay::parse(&replay).unwrap(), || {
+            // The saved thread is selected again, with its preview
+            app.reload(Some("thread:2")).unwrap();
+            assert_eq!(app.list.selected(), Some(1));
+            assert!(
+                app.preview
+                    .starts_with("From: Bob <b@x>\nSubject: Hi\n\nSee you")
+            );
+            assert_eq!(app.scroll, 0);
+            // Moving stops at either end
+            app.select(5);
+            assert_eq!(app.list.selected(), Some(1));
+            app.select(-5);
+            assert_eq!(app.selected().unwrap().id, "thread:1");
+            // A tag the thread has is removed, one it lacks added
+            app.toggle("flagged").unwrap();
+            assert_eq!(app.status, "+flagged thread:1");
+            app.select(1);
+            app.toggle("flagged").unwrap();
+            assert_eq!(app.status, "-flagged thread:2");
+        });
+    }
+}
@@ -0,0 +1,76 @@
+//! Unsubscribe via the List-Unsubscribe header
+
+use anyhow::Result;
+
+use crate::{color, mime, platform};
+
+/// Arguments for `mu unsubscribe`
+#[derive(clap::Args)]
+pub struct Args {
+    /// Message-id or notmuch query (reads raw message from stdin if not provided)
+    message: Option<String>,
+
+    /// Print the unsubscribe target instead of opening it
+    #[arg(long)]
+    print: bool,
+}
+
+/// Open the unsubscribe link (or print the mailto address) for a message
+pub fn run(args: Args) -> Result<()> {
+    let raw = mime::load(args.message.as_deref())?;
+    let message = mime::parse(&raw)?;
+    let header = message.header_raw("List-Unsubscribe").unwrap_or_default();
+
+    let Some(target) = pick_target(&targets(header)) else {
+        anyhow::bail!("No List-Unsubscribe header in message");
+    };
+
+    if args.print || target.starts_with("mailto:") {
+        println!("{}", target);
+    } else {
+        platform::open(&target)?;
+        eprintln!("{} Opened {}", color::ok(), target);
+    }
+    Ok(())
+}
+
+/// Extract `<...>` entries from a List-Unsubscribe header
+fn targets(header: &str) -> Vec<String> {
+    header
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>').map(|(t, _)| mime::unfold(t)))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Prefer https links over mailto
+fn pick_target(targets: &[String]) -> Option<String> {
+    targets
+        .iter()
+        .find(|t| t.starts_with("https://"))
+        .or_else(|| targets.iter().find(|t| t.starts_with("http://")))
+        .or_else(|| targets.first())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_targets_prefers_https() {
+        let t = targets(
+            "<mailto:leave@list.example?subject=unsubscribe>,\r\n <https://list.example/u/123>",
+        );
+        assert_eq!(t.len(), 2);
+        assert_eq!(pick_target(&t).unwrap(), "https://list.example/u/123");
+    }
+
+    #[test]
+    fn test_targets_mailto_only() {
+        let t = targets("<mailto:leave@list.example>");
+        assert_eq!(pick_target(&t).unwrap(), "mailto:leave@list.example");
+        assert_eq!(pick_target(&[]), None);
+    }
+}
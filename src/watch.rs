@@ -0,0 +1,163 @@
+//! `mu sync --watch`: sync on a timer in one long-running process, picking up edits to the
+//! config (tag rules included) and the tag script without a restart
+//!
+//! Every step of a sync loads the config afresh, so a reload is just noticing the change:
+//! the files are compared every second, what changed is reported, and a sync follows at once.
+//! A config that doesn't parse is reported and waited out; syncs resume once it's fixed.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::color;
+use crate::config::{self, Config};
+
+/// How often the config and script are compared while waiting
+const POLL: Duration = Duration::from_secs(1);
+
+/// The config file and tag script as last seen
+struct Watched {
+    config: PathBuf,
+    text: Option<String>,
+    script: Option<(PathBuf, Option<String>)>,
+}
+
+impl Watched {
+    fn new(config: PathBuf) -> Self {
+        let text = read(&config);
+        let script = script_path(text.as_deref()).map(|path| {
+            let source = read(&path);
+            (path, source)
+        });
+        Self {
+            config,
+            text,
+            script,
+        }
+    }
+
+    /// What changed since the last look: config sections by name, and `script`
+    fn changes(&mut self) -> Vec<String> {
+        let now = Watched::new(self.config.clone());
+        let mut changes = Vec::new();
+        if now.text != self.text {
+            changes.extend(sections(self.text.as_deref(), now.text.as_deref()));
+        }
+        if now.script != self.script {
+            changes.push("script".to_string());
+        }
+        *self = now;
+        changes
+    }
+}
+
+/// Sync now and every `every` after, until killed; `sync` is one full `mu sync`
+///
+/// A failed sync is reported and retried at the next turn rather than ending the watch.
+pub fn run(every: Duration, quiet: bool, mut sync: impl FnMut() -> Result<()>) -> Result<()> {
+    let mut watched = Watched::new(config::config_path());
+    loop {
+        match Config::load() {
+            Ok(_) => {
+                if let Err(e) = sync() {
+                    report(quiet, &format!("sync failed: {:#}", e));
+                }
+            }
+            Err(e) => report(
+                quiet,
+                &format!("sync skipped until the config loads: {:#}", e),
+            ),
+        }
+        let next = Instant::now() + every;
+        while Instant::now() < next {
+            thread::sleep(POLL.min(next.saturating_duration_since(Instant::now())));
+            let changes = watched.changes();
+            if !changes.is_empty() {
+                tracing::info!(changed = %changes.join(", "), "config reloaded");
+                if !quiet {
+                    eprintln!("{} Config reloaded: {}", color::ok(), changes.join(", "));
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// A warning on stderr (unless `quiet`) and in the log
+fn report(quiet: bool, message: &str) {
+    tracing::warn!("{}", message);
+    if !quiet {
+        eprintln!("{} {}", color::warn(), message);
+    }
+}
+
+fn read(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+/// `[script] path` of a config's text, `~/` expanded
+fn script_path(text: Option<&str>) -> Option<PathBuf> {
+    let table: toml::Table = toml::from_str(text?).ok()?;
+    let path = table.get("script")?.get("path")?.as_str()?;
+    Some(config::expand_tilde(path))
+}
+
+/// The top-level keys whose values differ between two config texts (`config` when either
+/// doesn't parse)
+fn sections(old: Option<&str>, new: Option<&str>) -> Vec<String> {
+    let parse = |text: Option<&str>| -> Option<toml::Table> {
+        text.map_or(Some(toml::Table::new()), |text| toml::from_str(text).ok())
+    };
+    let (Some(old), Some(new)) = (parse(old), parse(new)) else {
+        return vec!["config".to_string()];
+    };
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections() {
+        let old = "[[tag_rules]]\nquery = \"from:a\"\ntags = [\"+a\"]\n[render]\nreader = true\n";
+        let new = "[[tag_rules]]\nquery = \"from:b\"\ntags = [\"+a\"]\n[render]\nreader = true\n\
+                   [expire]\n";
+        assert_eq!(sections(Some(old), Some(new)), ["expire", "tag_rules"]);
+        assert_eq!(sections(None, Some("[push]\n")), ["push"]);
+        assert!(sections(Some(old), Some(old)).is_empty());
+        assert_eq!(sections(Some(old), Some("[render")), ["config"]);
+    }
+
+    #[test]
+    fn test_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        let script = dir.path().join("tag.rhai");
+        std::fs::write(
+            &config,
+            format!("[script]\npath = {:?}\n", script.display().to_string()),
+        )
+        .unwrap();
+        std::fs::write(&script, "fn tag(t) { [] }").unwrap();
+        let mut watched = Watched::new(config.clone());
+        assert!(watched.changes().is_empty());
+
+        std::fs::write(&script, "fn tag(t) { [\"+x\"] }").unwrap();
+        assert_eq!(watched.changes(), ["script"]);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&config)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, b"[[tag_rules]]\n"))
+            .unwrap();
+        assert_eq!(watched.changes(), ["tag_rules"]);
+        assert!(watched.changes().is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! Warm preview worker: `mu fzf` starts one `mu preview --serve`, and the `mu preview` fzf
+//! runs on each keystroke asks it over a Unix socket instead of starting from scratch
+//!
+//! The worker keeps its config and renderer chain loaded and remembers the previews it drew,
+//! so coming back to a thread is instant. It exits when `mu fzf` does (its stdin closes).
+//! Without a worker, or when it doesn't answer, `mu preview` renders by itself.
+
+use std::path::{Path, PathBuf};
+use std::process::Child;
+
+use crate::fzf::Backend;
+
+/// The socket `mu preview` asks, set in fzf's environment
+pub const ENV: &str = "MU_PREVIEW_SOCKET";
+
+/// A running worker, stopped when dropped
+pub struct Worker {
+    child: Child,
+    socket: PathBuf,
+}
+
+impl Worker {
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{ask, serve, start};
+
+#[cfg(unix)]
+mod unix {
+    use anyhow::{Context, Result};
+    use clap::ValueEnum;
+    use std::collections::HashMap;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{Backend, ENV, Worker};
+    use crate::{fzf, remote, renderer};
+
+    /// Previews remembered before the cache starts over
+    const CACHED: usize = 500;
+
+    /// Longer than a renderer may take, so a wedged worker only costs one slow preview
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    type Cache = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Start a worker for `backend` previews (colored or not), None if it can't run
+    pub fn start(backend: Backend, color: bool) -> Option<Worker> {
+        let socket = std::env::temp_dir().join(format!("mu-preview-{}.sock", std::process::id()));
+        let exe = std::env::current_exe().ok()?;
+        let backend = backend.to_possible_value()?;
+        let mut cmd = Command::new(exe);
+        cmd.args(["--color", if color { "always" } else { "never" }])
+            .args(["preview", "--backend", backend.get_name(), "--serve"])
+            .arg(&socket)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(host) = remote::host() {
+            cmd.env(remote::ENV, host);
+        }
+        match cmd.spawn() {
+            Ok(child) => Some(Worker { child, socket }),
+            Err(e) => {
+                tracing::debug!(error = %e, "no preview worker");
+                None
+            }
+        }
+    }
+
+    /// Answer previews on `socket` until stdin closes
+    pub fn serve(socket: &Path, backend: Backend) -> Result<()> {
+        // A stale socket from a previous run would make bind fail
+        let _ = std::fs::remove_file(socket);
+        let listener = UnixListener::bind(socket)
+            .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+        let path = socket.to_path_buf();
+        std::thread::spawn(move || {
+            let _ = io::copy(&mut io::stdin(), &mut io::sink());
+            let _ = std::fs::remove_file(path);
+            std::process::exit(0);
+        });
+
+        let cache = Cache::default();
+        for stream in listener.incoming().flatten() {
+            let cache = Arc::clone(&cache);
+            std::thread::spawn(move || {
+                if let Err(e) = answer(stream, backend, &cache) {
+                    tracing::debug!(error = %e, "preview left to mu preview");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// One request: a `thread id<TAB>width` line, answered with its preview (nothing if it
+    /// failed, for `mu preview` to render and report the error itself)
+    fn answer(mut stream: UnixStream, backend: Backend, cache: &Cache) -> Result<()> {
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+        let request = request.trim();
+        let (thread_id, cols) = request.split_once('\t').unwrap_or((request, ""));
+        renderer::set_cols(cols.parse().ok());
+        let cached = cache.lock().ok().and_then(|c| c.get(request).cloned());
+        let preview = match cached {
+            Some(preview) => preview,
+            None => {
+                let preview = fzf::render_preview(thread_id, backend)?;
+                if let Ok(mut cache) = cache.lock() {
+                    if cache.len() >= CACHED {
+                        cache.clear();
+                    }
+                    cache.insert(request.to_string(), preview.clone());
+                }
+                preview
+            }
+        };
+        Ok(stream.write_all(&preview)?)
+    }
+
+    /// The preview from the worker in `$MU_PREVIEW_SOCKET`, if one answers, drawn as wide
+    /// as this pane
+    pub fn ask(thread_id: &str) -> Option<Vec<u8>> {
+        let mut stream = UnixStream::connect(std::env::var_os(ENV)?).ok()?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+        writeln!(stream, "{}\t{}", thread_id, renderer::cols()).ok()?;
+        let mut preview = Vec::new();
+        stream.read_to_end(&mut preview).ok()?;
+        (!preview.is_empty()).then_some(preview)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::runner::{self, Replay};
+
+        #[test]
+        fn test_answer_from_cache() {
+            let cache = Cache::default();
+            cache
+                .lock()
+                .unwrap()
+                .insert("thread:01\t80".to_string(), b"=== Headers ===\n".to_vec());
+            let (mut client, worker) = UnixStream::pair().unwrap();
+            client.write_all(b"thread:01\t80\n").unwrap();
+            answer(worker, Backend::Notmuch, &cache).unwrap();
+            let mut preview = String::new();
+            client.read_to_string(&mut preview).unwrap();
+            assert_eq!(preview, "=== Headers ===\n");
+        }
+
+        /// `request` answered by `answer`: its result and what the client read
+        fn exchange(request: &str, cache: &Cache) -> (Result<()>, String) {
+            let (mut client, worker) = UnixStream::pair().unwrap();
+            writeln!(client, "{}", request).unwrap();
+            let result = answer(worker, Backend::Notmuch, cache);
+            let mut preview = String::new();
+            client.read_to_string(&mut preview).unwrap();
+            (result, preview)
+        }
+
+        #[test]
+        fn test_answer_renders_then_caches() {
+            let dir = tempfile::tempdir().unwrap();
+            let file = dir.path().join("a");
+            std::fs::write(
+                &file,
+                "From: Ann <a@x>\r\nSubject: Plans\r\n\r\nSee you at 5\r\n",
+            )
+            .unwrap();
+            let files = |query: &str, stdout: String| {
+                serde_json::json!({
+                    "program": "notmuch",
+                    "args": ["search", "--output=files", "--duplicate=1", "--sort=oldest-first",
+                             "--", query],
+                    "stdout": stdout,
+                })
+                .to_string()
+            };
+            let replay = [
+                files("thread:01", format!("{}\n", file.display())),
+                files("thread:02", String::new()),
+            ]
+            .join("\n");
+            let cache = Cache::default();
+            runner::scoped(Replay::parse(&replay).unwrap(), || {
+                let (result, preview) = exchange("thread:01\t80", &cache);
+                result.unwrap();
+                assert!(preview.contains("See you at 5"));
+                assert_eq!(cache.lock().unwrap()["thread:01\t80"], preview.as_bytes());
+
+                // A preview that fails is left to `mu preview`: no answer, nothing cached
+                let (result, preview) = exchange("thread:02\t80", &cache);
+                assert!(result.is_err());
+                assert!(preview.is_empty());
+                assert_eq!(cache.lock().unwrap().len(), 1);
+            });
+        }
+    }
+}
+
+/// No Unix sockets: previews render in `mu preview`
+#[cfg(not(unix))]
+pub fn start(_: Backend, _: bool) -> Option<Worker> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn serve(_: &Path, _: Backend) -> anyhow::Result<()> {
+    anyhow::bail!("the preview worker needs Unix domain sockets")
+}
+
+#[cfg(not(unix))]
+pub fn ask(_: &str) -> Option<Vec<u8>> {
+    None
+}